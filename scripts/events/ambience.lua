@@ -0,0 +1,29 @@
+-- Rings the shrine bell now and then, warns people about the loose floorboard
+-- in the Drowsy Goose and sometimes shows them the shrine's ghost. Names and places here are from the
+-- starting village, change them to suit your own world.
+
+local BELL_EVERY = 600
+
+on("tick", function(event, world)
+  if event.tick % BELL_EVERY == 0 then
+    world.say("Old Shrine", "The shrine bell tolls, low and slow")
+  end
+end)
+
+on("player_entered_room", function(event, world)
+  if event.place == "The Drowsy Goose" and math.random() < 0.1 then
+    world.send(event.player, "A floorboard gives under your foot and you twist your ankle")
+    world.hurt(event.player, 2)
+  end
+end)
+
+on("player_entered_room", function(event, world)
+  if event.place ~= "Old Shrine" or math.random() >= 0.05 then
+    return
+  end
+  world.say("Old Shrine", "The candles gutter and a pale figure kneels at the altar")
+  wait(3)
+  world.say("Old Shrine", "'Sleep lightly, dreamer,' it whispers, 'the god is stirring'")
+  wait(3)
+  world.say("Old Shrine", "The figure fades and the candles flare back to life")
+end)
@@ -0,0 +1,367 @@
+//! Warnings for whoever runs the server. The engine takes readings as it goes,
+//! how long ticks take, how much generation fails, failed saves and how many
+//! players connect, and checks them against the configured limits every so
+//! often. Rules that break a limit alert admins in game and on the webhook
+//! until someone acknowledges them, and admins can mute a noisy rule for a while.
+
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display},
+    time::{Duration, Instant},
+};
+
+use serde_json::json;
+
+use crate::{config::AlertConfig, digest::WebhookKind};
+
+/// Too few generation results to judge the failure rate by
+const MIN_GENERATIONS: u32 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum AlertRule {
+    SlowTicks,
+    GenerationFailures,
+    SaveFailures,
+    PlayerFlood,
+}
+
+impl AlertRule {
+    pub const ALL: [AlertRule; 4] = [
+        AlertRule::SlowTicks,
+        AlertRule::GenerationFailures,
+        AlertRule::SaveFailures,
+        AlertRule::PlayerFlood,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            AlertRule::SlowTicks => "slow-ticks",
+            AlertRule::GenerationFailures => "generation-failures",
+            AlertRule::SaveFailures => "save-failures",
+            AlertRule::PlayerFlood => "player-flood",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|r| r.name().eq_ignore_ascii_case(name))
+    }
+}
+
+impl Display for AlertRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// What's been seen since the last check
+#[derive(Debug, Default, Clone, PartialEq)]
+struct Readings {
+    ticks: u32,
+    tick_time: Duration,
+    generated: u32,
+    failed: u32,
+    connections: u32,
+    save_failures: u64,
+}
+
+/// An alert that's gone off and hasn't cleared
+#[derive(Debug, Clone, PartialEq)]
+pub struct Firing {
+    pub message: String,
+    pub since: Instant,
+    pub acknowledged: bool,
+    last_notified: Instant,
+}
+
+/// Something admins should hear about after a check
+#[derive(Debug, Clone, PartialEq)]
+pub enum Notice {
+    Fired(AlertRule, String),
+    /// Still firing and nobody's acknowledged it
+    Reminder(AlertRule, String),
+    Resolved(AlertRule),
+}
+
+impl Notice {
+    pub fn text(&self, realm: &str) -> String {
+        match self {
+            Notice::Fired(rule, message) => format!("[{realm}] Alert {rule}: {message}"),
+            Notice::Reminder(rule, message) => {
+                format!("[{realm}] Alert {rule} is still firing: {message}")
+            }
+            Notice::Resolved(rule) => format!("[{realm}] Alert {rule} has cleared"),
+        }
+    }
+
+    /// The body to post for each kind of webhook
+    pub fn payload(&self, realm: &str, kind: WebhookKind) -> serde_json::Value {
+        match kind {
+            WebhookKind::Discord => json!({ "content": self.text(realm) }),
+            WebhookKind::Slack => json!({ "text": self.text(realm) }),
+            WebhookKind::Json => {
+                let (rule, state, message) = match self {
+                    Notice::Fired(rule, message) => (rule, "fired", Some(message)),
+                    Notice::Reminder(rule, message) => (rule, "firing", Some(message)),
+                    Notice::Resolved(rule) => (rule, "resolved", None),
+                };
+                json!({ "realm": realm, "alert": rule.name(), "state": state, "message": message })
+            }
+        }
+    }
+}
+
+/// The engine's readings and the alerts they've set off
+#[derive(Debug)]
+pub struct Alerts {
+    readings: Readings,
+    checked: Instant,
+    /// Save failures counted at the last check, the count is kept server wide
+    save_failures_seen: u64,
+    pub firing: BTreeMap<AlertRule, Firing>,
+    /// Rules that won't notify anyone until the time given
+    pub muted: BTreeMap<AlertRule, Instant>,
+}
+
+impl Alerts {
+    pub fn new(now: Instant, save_failures: u64) -> Self {
+        Self {
+            readings: Readings::default(),
+            checked: now,
+            save_failures_seen: save_failures,
+            firing: BTreeMap::new(),
+            muted: BTreeMap::new(),
+        }
+    }
+
+    pub fn record_tick(&mut self, took: Duration) {
+        self.readings.ticks += 1;
+        self.readings.tick_time += took;
+    }
+
+    pub fn record_generation(&mut self, failed: bool) {
+        self.readings.generated += 1;
+        self.readings.failed += failed as u32;
+    }
+
+    pub fn record_connections(&mut self, count: usize) {
+        self.readings.connections += count as u32;
+    }
+
+    /// Checks the readings against the limits once enough time has passed,
+    /// returning what admins should hear about
+    pub fn check(&mut self, now: Instant, save_failures: u64, config: &AlertConfig) -> Vec<Notice> {
+        if now.duration_since(self.checked).as_secs_f64() < config.check_seconds {
+            return Vec::new();
+        }
+        self.checked = now;
+        let mut readings = std::mem::take(&mut self.readings);
+        readings.save_failures = save_failures.saturating_sub(self.save_failures_seen);
+        self.save_failures_seen = save_failures;
+
+        let breaches = breaches(&readings, config);
+        self.muted.retain(|_, until| *until > now);
+        let remind = Duration::from_secs_f64(config.remind_minutes * 60.0);
+        let mut notices = Vec::new();
+
+        let cleared: Vec<_> = self
+            .firing
+            .keys()
+            .filter(|rule| !breaches.iter().any(|(r, _)| r == *rule))
+            .copied()
+            .collect();
+        for rule in cleared {
+            self.firing.remove(&rule);
+            notices.push(Notice::Resolved(rule));
+        }
+
+        for (rule, message) in breaches {
+            match self.firing.get_mut(&rule) {
+                Some(firing) => {
+                    firing.message = message.clone();
+                    if !firing.acknowledged && now.duration_since(firing.last_notified) >= remind {
+                        firing.last_notified = now;
+                        notices.push(Notice::Reminder(rule, message));
+                    }
+                }
+                None => {
+                    self.firing.insert(
+                        rule,
+                        Firing {
+                            message: message.clone(),
+                            since: now,
+                            acknowledged: false,
+                            last_notified: now,
+                        },
+                    );
+                    notices.push(Notice::Fired(rule, message));
+                }
+            }
+        }
+
+        notices.retain(|n| {
+            let (Notice::Fired(rule, _) | Notice::Reminder(rule, _) | Notice::Resolved(rule)) = n;
+            !self.muted.contains_key(rule)
+        });
+        notices
+    }
+
+    /// Stops reminders for a firing alert, or every one, returning how many were acknowledged
+    pub fn acknowledge(&mut self, rule: Option<AlertRule>) -> usize {
+        self.firing
+            .iter_mut()
+            .filter(|(r, f)| rule.is_none_or(|rule| **r == rule) && !f.acknowledged)
+            .map(|(_, f)| f.acknowledged = true)
+            .count()
+    }
+
+    /// A summary for admins
+    pub fn report(&self, now: Instant) -> String {
+        let mut res = String::from("Alerts\n\n");
+        if self.firing.is_empty() {
+            res.push_str("Nothing's firing\n");
+        }
+        for (rule, firing) in &self.firing {
+            let state = if firing.acknowledged {
+                "acknowledged"
+            } else {
+                "unacknowledged"
+            };
+            res.push_str(&format!(
+                "{rule} for {}m, {state}: {}\n",
+                now.duration_since(firing.since).as_secs() / 60,
+                firing.message
+            ));
+        }
+        for (rule, until) in &self.muted {
+            res.push_str(&format!(
+                "{rule} is muted for another {}m\n",
+                until.saturating_duration_since(now).as_secs().div_ceil(60)
+            ));
+        }
+        res
+    }
+}
+
+/// The rules the readings break and what's wrong
+fn breaches(readings: &Readings, config: &AlertConfig) -> Vec<(AlertRule, String)> {
+    let mut res = Vec::new();
+
+    if readings.ticks > 0 {
+        let average = readings.tick_time.as_secs_f64() * 1000.0 / readings.ticks as f64;
+        if average > config.slow_tick_ms {
+            res.push((
+                AlertRule::SlowTicks,
+                format!(
+                    "ticks are taking {average:.1}ms on average, over the {}ms limit",
+                    config.slow_tick_ms
+                ),
+            ));
+        }
+    }
+
+    if readings.generated >= MIN_GENERATIONS {
+        let rate = readings.failed as f64 / readings.generated as f64;
+        if rate > config.generation_failure_rate {
+            res.push((
+                AlertRule::GenerationFailures,
+                format!(
+                    "{} of the last {} generation requests failed",
+                    readings.failed, readings.generated
+                ),
+            ));
+        }
+    }
+
+    if readings.save_failures > config.save_failures {
+        res.push((
+            AlertRule::SaveFailures,
+            format!("{} world saves failed", readings.save_failures),
+        ));
+    }
+
+    if readings.connections > config.connections {
+        res.push((
+            AlertRule::PlayerFlood,
+            format!(
+                "{} connections since the last check, over the limit of {}",
+                readings.connections, config.connections
+            ),
+        ));
+    }
+
+    res
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fires_reminds_and_clears() {
+        let config = AlertConfig {
+            check_seconds: 60.0,
+            remind_minutes: 5.0,
+            ..Default::default()
+        };
+        let start = Instant::now();
+        let minutes = |m: u64| start + Duration::from_secs(m * 60);
+        let mut alerts = Alerts::new(start, 0);
+
+        alerts.record_connections(config.connections as usize + 1);
+        assert!(alerts.check(start, 2, &config).is_empty());
+        assert_eq!(
+            alerts.check(minutes(1), 2, &config),
+            [
+                Notice::Fired(AlertRule::SaveFailures, "2 world saves failed".to_string()),
+                Notice::Fired(
+                    AlertRule::PlayerFlood,
+                    format!(
+                        "{} connections since the last check, over the limit of {}",
+                        config.connections + 1,
+                        config.connections
+                    )
+                ),
+            ]
+        );
+
+        // Saves are still failing but the flood has passed
+        assert_eq!(
+            alerts.check(minutes(2), 3, &config),
+            [Notice::Resolved(AlertRule::PlayerFlood)]
+        );
+        assert!(matches!(
+            alerts.check(minutes(7), 4, &config)[..],
+            [Notice::Reminder(AlertRule::SaveFailures, _)]
+        ));
+
+        assert_eq!(alerts.acknowledge(None), 1);
+        assert!(alerts.check(minutes(13), 5, &config).is_empty());
+
+        alerts.muted.insert(AlertRule::SaveFailures, minutes(20));
+        assert!(alerts.check(minutes(14), 5, &config).is_empty());
+        assert!(alerts.firing.is_empty());
+    }
+
+    #[test]
+    fn judges_failure_rates() {
+        let config = AlertConfig::default();
+        let mut readings = Readings {
+            generated: 3,
+            failed: 3,
+            ..Default::default()
+        };
+        assert!(breaches(&readings, &config).is_empty());
+
+        readings.generated = 4;
+        assert_eq!(
+            breaches(&readings, &config)[0].0,
+            AlertRule::GenerationFailures
+        );
+
+        readings.ticks = 10;
+        readings.tick_time = Duration::from_secs(10);
+        assert_eq!(breaches(&readings, &config)[0].0, AlertRule::SlowTicks);
+    }
+}
@@ -0,0 +1,148 @@
+//! What players type after a command, split into arguments. Words split on
+//! whitespace unless they're in double quotes, so 'give "iron sword" bob' has
+//! two. Commands taking free text, like 'say', read the rest as it was typed
+//! instead. Targets pick out everything with 'all', everything of one name with
+//! 'all.torch' or one of several sharing a name with '2.goblin'.
+
+/// The arguments to a command, taken in order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Args<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Args<'a> {
+    pub fn new(text: &'a str) -> Self {
+        Self { rest: text.trim() }
+    }
+
+    /// The arguments in a whole line, everything after the command's name
+    pub fn after_command(line: &'a str) -> Self {
+        let line = line.trim_start();
+        let rest = line
+            .split_once(char::is_whitespace)
+            .map_or("", |(_, rest)| rest);
+        Self::new(rest)
+    }
+
+    /// Everything not taken yet, exactly as it was typed, quotes and all
+    pub fn rest(&mut self) -> &'a str {
+        std::mem::take(&mut self.rest)
+    }
+
+    /// Every argument left joined with single spaces, for names that run to
+    /// several words
+    pub fn joined(&mut self) -> String {
+        self.collect::<Vec<_>>().join(" ")
+    }
+}
+
+impl<'a> Iterator for Args<'a> {
+    type Item = &'a str;
+
+    /// The next word, or everything inside the next pair of quotes. A quote
+    /// that's never closed runs to the end.
+    fn next(&mut self) -> Option<&'a str> {
+        if self.rest.is_empty() {
+            return None;
+        }
+        let (arg, rest) = match self.rest.strip_prefix('"') {
+            Some(quoted) => quoted.split_once('"').unwrap_or((quoted, "")),
+            None => self
+                .rest
+                .split_once(char::is_whitespace)
+                .unwrap_or((self.rest, "")),
+        };
+        self.rest = rest.trim_start();
+        Some(arg)
+    }
+}
+
+/// Which of several things a player means
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Selector {
+    /// 'all'
+    All,
+    /// Everything called this, 'all.torch'
+    AllOf(String),
+    /// The nth thing called this counting from 1, '2.goblin', or the first for
+    /// just 'goblin'
+    Nth(usize, String),
+}
+
+impl Selector {
+    /// Reads a target, nothing if there's no name to go on
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        if text.eq_ignore_ascii_case("all") {
+            return Some(Selector::All);
+        }
+
+        let (selector, name) = match text.split_once('.') {
+            Some((all, name)) if all.eq_ignore_ascii_case("all") => {
+                (Selector::AllOf(name.trim().to_string()), name)
+            }
+            Some((n, name)) if n.parse::<usize>().is_ok_and(|n| n > 0) => {
+                let n = n.parse().unwrap();
+                (Selector::Nth(n, name.trim().to_string()), name)
+            }
+            _ => (Selector::Nth(1, text.to_string()), text),
+        };
+        (!name.trim().is_empty()).then_some(selector)
+    }
+
+    /// The name it picks things by, if it doesn't pick everything
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            Selector::All => None,
+            Selector::AllOf(name) | Selector::Nth(_, name) => Some(name),
+        }
+    }
+
+    /// Whether it can pick more than one thing
+    pub fn is_many(&self) -> bool {
+        matches!(self, Selector::All | Selector::AllOf(_))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn splits_words_and_quotes() {
+        let mut args = Args::after_command("give  \"iron sword\" bob 2");
+        assert_eq!(args.next(), Some("iron sword"));
+        assert_eq!(args.rest(), "bob 2");
+        assert_eq!(args.next(), None);
+
+        let args = Args::new("say \"unfinished  thought");
+        assert_eq!(args.collect::<Vec<_>>(), vec!["say", "unfinished  thought"]);
+        assert_eq!(Args::after_command("look").next(), None);
+        assert_eq!(Args::new("a \"\" b").joined(), "a  b");
+    }
+
+    #[test]
+    fn reads_targets() {
+        assert_eq!(Selector::parse("ALL"), Some(Selector::All));
+        assert_eq!(
+            Selector::parse("all.bone dust"),
+            Some(Selector::AllOf("bone dust".into()))
+        );
+        assert_eq!(
+            Selector::parse("2.goblin"),
+            Some(Selector::Nth(2, "goblin".into()))
+        );
+        assert_eq!(
+            Selector::parse("cave rat"),
+            Some(Selector::Nth(1, "cave rat".into()))
+        );
+        assert_eq!(
+            Selector::parse("0.goblin"),
+            Some(Selector::Nth(1, "0.goblin".into()))
+        );
+        assert_eq!(Selector::parse("all."), None);
+        assert_eq!(Selector::parse("3."), None);
+        assert_eq!(Selector::parse(" "), None);
+        assert!(Selector::parse("all.torch").is_some_and(|s| s.is_many()));
+    }
+}
@@ -0,0 +1,219 @@
+//! Simulated players for load testing. Bots connect through the same broker as
+//! real players and send a mix of what players send, exploring, fighting and
+//! chatting, so a busy tick loop or contended lock shows up as slow replies
+//! before real players are waiting on them.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{
+    connections::{EngineConnection, PlayerConnectionBroker},
+    state::PlayerId,
+};
+
+/// The most bots an admin can start at once
+pub const MAX_BOTS: usize = 500;
+/// How long bots wait between commands on average
+const THINK_TIME: Duration = Duration::from_secs(2);
+/// How often the bot thread checks for replies
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+const CHATTER: &[&str] = &[
+    "well met",
+    "anyone seen the way to the dungeon?",
+    "selling honeycomb, cheap",
+    "this place gives me the creeps",
+    "has anyone found a good sword?",
+];
+
+/// Replies the bots have had, shared with the thread running them
+#[derive(Debug, Default)]
+struct BotStats {
+    sent: AtomicU64,
+    received: AtomicU64,
+    answered: AtomicU64,
+    total_latency_micros: AtomicU64,
+    worst_latency_micros: AtomicU64,
+}
+
+#[derive(Debug)]
+struct BotRun {
+    count: usize,
+    started: Instant,
+    stop: Arc<AtomicBool>,
+    stats: Arc<BotStats>,
+    thread: JoinHandle<()>,
+}
+
+/// The engine's handle on its simulated players
+#[derive(Debug)]
+pub struct Bots {
+    broker: PlayerConnectionBroker,
+    running: Option<BotRun>,
+}
+
+impl Bots {
+    pub fn new(broker: PlayerConnectionBroker) -> Self {
+        Self {
+            broker,
+            running: None,
+        }
+    }
+
+    /// Connects `count` bots, replacing any already running
+    pub fn start(&mut self, count: usize) {
+        self.stop();
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let stats = Arc::new(BotStats::default());
+        let connections: Vec<_> = (0..count)
+            .map(|n| self.broker.setup_connection(PlayerId::bot(n)))
+            .collect();
+
+        let (broker, thread_stop, thread_stats) =
+            (self.broker.clone(), stop.clone(), stats.clone());
+        let thread =
+            std::thread::spawn(move || run_bots(broker, connections, &thread_stop, &thread_stats));
+
+        self.running = Some(BotRun {
+            count,
+            started: Instant::now(),
+            stop,
+            stats,
+            thread,
+        });
+    }
+
+    /// Disconnects every bot, returning how many there were. Waits for the bots'
+    /// thread to finish so its disconnects can't catch bots started after it.
+    pub fn stop(&mut self) -> usize {
+        let Some(run) = self.running.take() else {
+            return 0;
+        };
+        run.stop.store(true, Ordering::Relaxed);
+        if run.thread.join().is_err() {
+            tracing::error!("The bots' thread panicked");
+        }
+        run.count
+    }
+
+    /// A short summary for admins
+    pub fn report(&self) -> String {
+        let Some(run) = &self.running else {
+            return "No bots are running, start some with 'bots start <count>'".to_string();
+        };
+        let stats = &run.stats;
+        let sent = stats.sent.load(Ordering::Relaxed);
+        let answered = stats.answered.load(Ordering::Relaxed);
+        let average = match answered {
+            0 => 0,
+            _ => stats.total_latency_micros.load(Ordering::Relaxed) / answered / 1000,
+        };
+        let worst = stats.worst_latency_micros.load(Ordering::Relaxed) / 1000;
+
+        format!(
+            "Bots\n\n{} bots running for {}s\n{sent} commands sent, {answered} answered, {} messages received\nReplies took {average}ms on average, {worst}ms at worst",
+            run.count,
+            run.started.elapsed().as_secs(),
+            stats.received.load(Ordering::Relaxed),
+        )
+    }
+}
+
+impl Drop for Bots {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// One simulated player
+struct Bot {
+    connection: EngineConnection,
+    /// Lines waiting to be sent before the bot starts acting on its own
+    script: Vec<String>,
+    next_action: Instant,
+    /// When the bot sent the command it's waiting to hear back about
+    waiting_since: Option<Instant>,
+}
+
+fn run_bots(
+    broker: PlayerConnectionBroker,
+    connections: Vec<EngineConnection>,
+    stop: &AtomicBool,
+    stats: &BotStats,
+) {
+    let mut rng = rand::thread_rng();
+    let now = Instant::now();
+    let mut bots: Vec<_> = connections
+        .into_iter()
+        .enumerate()
+        .map(|(n, connection)| Bot {
+            connection,
+            // Name and finish creating the character, bots that already have one
+            // just get told these aren't commands
+            script: vec!["done".to_string(), format!("Simbot {n}")],
+            next_action: now + THINK_TIME.mul_f64(rng.gen()),
+            waiting_since: None,
+        })
+        .collect();
+
+    while !stop.load(Ordering::Relaxed) {
+        let now = Instant::now();
+        for bot in &mut bots {
+            while bot.connection.try_recv().is_some() {
+                stats.received.fetch_add(1, Ordering::Relaxed);
+                if let Some(sent) = bot.waiting_since.take() {
+                    let latency = now.duration_since(sent).as_micros() as u64;
+                    stats.answered.fetch_add(1, Ordering::Relaxed);
+                    stats
+                        .total_latency_micros
+                        .fetch_add(latency, Ordering::Relaxed);
+                    stats
+                        .worst_latency_micros
+                        .fetch_max(latency, Ordering::Relaxed);
+                }
+            }
+
+            if now < bot.next_action {
+                continue;
+            }
+            let command = bot.script.pop().unwrap_or_else(|| pick_command(&mut rng));
+            if bot.connection.send(command).is_err() {
+                continue;
+            }
+            stats.sent.fetch_add(1, Ordering::Relaxed);
+            bot.waiting_since.get_or_insert(now);
+            bot.next_action = now + THINK_TIME.mul_f64(rng.gen_range(0.5..1.5));
+        }
+
+        std::thread::sleep(POLL_INTERVAL);
+    }
+
+    for bot in bots {
+        broker.end_connection(bot.connection.player());
+    }
+}
+
+/// Something a player might type, mostly wandering with some fighting and talking
+fn pick_command(rng: &mut impl Rng) -> String {
+    match rng.gen_range(0..20) {
+        0..=7 => ["north", "south", "east", "west", "up", "down"]
+            .choose(rng)
+            .unwrap()
+            .to_string(),
+        8..=10 => "look".to_string(),
+        11..=13 => "attack".to_string(),
+        14..=16 => format!("say {}", CHATTER.choose(rng).unwrap()),
+        17 => "inventory".to_string(),
+        18 => "who".to_string(),
+        _ => "recap".to_string(),
+    }
+}
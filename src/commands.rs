@@ -1,14 +1,93 @@
-use std::sync::OnceLock;
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+    sync::{atomic::Ordering, OnceLock},
+    time::{Duration, Instant},
+};
 
-use crate::{engine::Engine, mud::world::Direction, state::PlayerId};
+use rand::{seq::SliceRandom, Rng};
 
-pub type CmdFn = Box<dyn Fn(&mut Engine, PlayerId, &mut dyn Iterator<Item = &str>) + Send + Sync>;
+use crate::{
+    alerts::AlertRule,
+    args::{Args, Selector},
+    bots, config,
+    engine::{self, Engine, Target, SHUTTING_DOWN, SPEED_RANGE},
+    fuzzy,
+    generation::{self, GenerationReq},
+    markup::{self, styled, Style},
+    mccp,
+    mud::{
+        building, cartography,
+        character::{
+            Attribute, Character, Coating, Encumbrance, MAX_ALIASES, MAX_ALIAS_LENGTH,
+            MAX_ALIAS_NAME,
+        },
+        combat::{self, AttackOutcome, PoisonOutcome, MAX_POISON_STACKS},
+        companion::{self, Companion, Stance},
+        corpse::Corpse,
+        crafting::{self, Craft, Recipe},
+        entity::{self, EntityId},
+        experience::{self, LevelUp},
+        haggle::Haggle,
+        history::{self, EventKind},
+        home::{Decoration, Home, MAX_LOOK_LENGTH, MAX_SCRIPT_LINES},
+        items::{Inventory, ItemInstance, Quality, COMMODITY_VALUE, ESSENCE, MAX_ENCHANTMENTS},
+        ledger::{self, Discrepancy},
+        lore, party,
+        relationship::{self, Conversation, Dialogue, Standing, Topic},
+        shop::{self, Shop},
+        social::{self, Social, SOCIALS},
+        stall::{self, Stall},
+        work_order::{self, WorkOrder},
+        workshop::{Blueprint, Workshop},
+        world::{
+            Direction, Location, Place, MARKET_TAG, SAFE_TAG, SHOP_TAG, VENDOR_TAG, VOID_TAG,
+            WORKSHOP_TAG,
+        },
+    },
+    prompt::{self, Answer, ChoiceFn, Prompt},
+    reports::{self, Report, ReportKind, MAX_REPORT_LENGTH, REPORT_DIR},
+    rules::PvpMode,
+    script_store,
+    scripting::{self, Event},
+    state::{self, PlayerId},
+    PlayerAccount, Role,
+};
+
+/// The item used as money
+pub const GOLD: &str = "Gold Coin";
+/// Read to return to wherever the reader's bound
+pub const RECALL_SCROLL: &str = "Recall Scroll";
+
+/// The most items a shopkeeper is offered to pick their wares from
+const SHOP_CANDIDATES: usize = 20;
+/// The most products and materials a workshop is offered to draw blueprints from
+const WORKSHOP_CANDIDATES: usize = 20;
+/// The narrowest players can have messages wrapped to
+const MIN_WIDTH: u16 = 20;
+
+/// How many entries the chronicle command shows
+const CHRONICLE_LENGTH: usize = 15;
+
+pub type CmdFn = Box<dyn Fn(&mut Engine, PlayerId, &mut Args) + Send + Sync>;
+pub type CheckFn =
+    Box<dyn Fn(&mut Engine, PlayerId, &mut Args) -> Result<(), String> + Send + Sync>;
 
 pub struct Command {
     pub name: String,
     pub aliases: Vec<String>,
     pub help: String,
     pub cmd_fn: CmdFn,
+    /// What the player is doing and for how many seconds, if the command takes a while
+    pub act: Option<(&'static str, f64)>,
+    /// Whether a command that takes a while can go ahead, checked before the player starts
+    pub check: Option<CheckFn>,
+    /// Runs as soon as it's typed, even while the player's busy
+    pub immediate: bool,
+    /// Ghosts can use it, most commands need a body
+    pub ghostly: bool,
+    /// Only admins can use it or see it listed, see [`is_admin`]
+    pub admin: bool,
 }
 
 impl Command {
@@ -18,9 +97,44 @@ impl Command {
             aliases: aliases.iter().map(|s| s.to_string()).collect(),
             help: help.to_string(),
             cmd_fn,
+            act: None,
+            check: None,
+            immediate: false,
+            ghostly: false,
+            admin: false,
         }
     }
 
+    /// Lets the command skip the queue of anything the player has lined up
+    pub fn immediate(mut self) -> Self {
+        self.immediate = true;
+        self
+    }
+
+    /// Lets ghosts use the command
+    pub fn ghostly(mut self) -> Self {
+        self.ghostly = true;
+        self
+    }
+
+    /// Keeps the command for admins
+    pub fn admin(mut self) -> Self {
+        self.admin = true;
+        self
+    }
+
+    /// Makes the command take a while, running once the player's done
+    pub fn takes(mut self, verb: &'static str, seconds: f64) -> Self {
+        self.act = Some((verb, seconds));
+        self
+    }
+
+    /// Checks the command can go ahead before the player spends any time on it
+    pub fn checked(mut self, check: CheckFn) -> Self {
+        self.check = Some(check);
+        self
+    }
+
     pub fn match_name(&self, name: &str) -> bool {
         self.name == name || self.aliases.iter().any(|a| a == name)
     }
@@ -31,11 +145,115 @@ pub fn get_command_list() -> &'static Vec<Command> {
 
     COMMANDS.get_or_init(|| {
         let mut base = vec![
-            help_command().into(),
-            quit_command().into(),
-            look_command().into(),
+            help_command(),
+            quit_command(),
+            camp_command(),
+            bind_command(),
+            recall_command(),
+            revive_command(),
+            loot_command(),
+            stop_command(),
+            clear_command(),
+            look_command(),
+            say_command(),
+            emote_command(),
+            tell_command(),
+            reply_command(),
+            inventory_command(),
+            score_command(),
+            attack_command(),
+            recap_command(),
+            balance_command(),
+            digest_command(),
+            alerts_command(),
+            audit_command(),
+            generations_command(),
+            script_command(),
+            teleport_command(),
+            spawn_command(),
+            setstat_command(),
+            kick_command(),
+            ban_command(),
+            unban_command(),
+            role_command(),
+            save_command(),
+            shutdown_command(),
+            dig_command(),
+            describe_command(),
+            rename_command(),
+            tag_command(),
+            link_command(),
+            unlink_command(),
+            deed_command(),
+            decorations_command(),
+            clock_command(),
+            history_command(),
+            pvp_command(),
+            rules_command(),
+            color_command(),
+            width_command(),
+            compress_command(),
+            bandwidth_command(),
+            alias_command(),
+            unalias_command(),
+            aliases_command(),
+            bots_command(),
+            name_command(),
+            decorate_command(),
+            report_command(ReportKind::Bug),
+            report_command(ReportKind::Typo),
+            chronicle_command(),
+            who_command(),
+            map_command(),
+            chart_command(),
+            time_command(),
+            weather_command(),
+            lore_command(),
+            gather_command(),
+            craft_command(Craft::Cooking),
+            craft_command(Craft::Alchemy),
+            recipes_command(),
+            blueprints_command(),
+            craft_equipment_command(),
+            consume_command(),
+            coat_command(),
+            wield_command(),
+            enchant_command(),
+            disenchant_command(),
+            engrave_command(),
+            take_command(),
+            drop_command(),
+            junk_command(),
+            sell_command(),
+            compare_command(),
+            haggle_command(),
+            accept_command(),
+            list_command(),
+            give_command(),
+            stall_command(),
+            buy_command(),
+            order_command(),
+            mail_command(),
+            tithe_command(),
+            talk_command(),
+            befriend_command(),
+            companion_command(),
+            hire_command(),
+            rescue_command(),
         ];
         base.extend(move_commands());
+        base.extend(social_commands());
+
+        for command in scripting::load_commands(Path::new(scripting::SCRIPT_DIR)) {
+            if base.iter().any(|c| c.match_name(&command.name)) {
+                tracing::warn!(
+                    "Script command {} clashes with a built in one",
+                    command.name
+                );
+                continue;
+            }
+            base.push(command);
+        }
         base
     })
 }
@@ -44,28 +262,79 @@ pub fn look_command() -> Command {
     Command::new(
         "look",
         &["l"],
-        "Describes your surroundings to you",
-        Box::new(|engine, player, _| {
-            let player_character = engine.world.player_characters.entry(player).or_default();
-
-            if let Some(place) = engine.world.places.get(&player_character.location) {
-                let look_msg = place.look(&engine.world, "You're standing in");
+        "Describes your surroundings to you, or what's in one direction or something here, like 'look north' or 'look cave rat'",
+        Box::new(|engine, player, args| {
+            let name = args.joined();
+            let ghost = engine.world.player_character(player).ghost.is_some();
+            if !name.is_empty() {
+                let msg = match engine.find_target(player, &name) {
+                    _ if ghost => "It's too faint to make out through the haze".to_string(),
+                    Some(target) => describe_target(engine, target),
+                    None if Direction::values()
+                        .iter()
+                        .any(|d| d.name().eq_ignore_ascii_case(&name)) =>
+                    {
+                        format!("There's no way {name} from here")
+                    }
+                    None => format!("You don't see any {name} here"),
+                };
+                engine.connection_broker.send_player_message(player, msg);
+                return;
+            }
 
-                engine
-                    .connection_broker
-                    .send_player_message(player, look_msg);
-            } else {
+            if engine.world.check_player_location(player) {
                 engine.connection_broker.send_player_message(
                     player,
-                    "Invalid location, resetting to start".to_string(),
+                    "The world shifts and you find yourself somewhere new".to_string(),
                 );
-                player_character.location = engine
-                    .world
-                    .overworld_locales
-                    .first()
-                    .map(|l| *l)
-                    .unwrap_or_default();
             }
+
+            let location = engine.world.player_character(player).location;
+            let place = &engine.world.places[&location];
+            let look_msg = match ghost {
+                true => place.ghost_look(&engine.world, "You drift through"),
+                false => place.look(&engine.world, "You're standing in"),
+            };
+
+            engine
+                .connection_broker
+                .send_player_message(player, look_msg);
+            visit_place(engine, player);
+        }),
+    )
+    .ghostly()
+}
+
+pub fn inventory_command() -> Command {
+    Command::new(
+        "inventory",
+        &["i", "inv"],
+        "Lists the items you're carrying and their total weight",
+        Box::new(|engine, player, _| {
+            engine.world.player_character(player);
+            let world = &engine.world;
+            let weight_of = |name: &str| world.weight_of(name);
+            let character = &world.player_characters[&player];
+            let mut res = character.inventory.describe(weight_of);
+            if let Some(item) = &character.wielding {
+                res.push_str(&format!("\nWielding: {}", item.name()));
+            }
+            res.push_str(&format!(
+                "\nYou can carry {} without slowing down",
+                character.carry_capacity()
+            ));
+            match character.encumbrance(weight_of) {
+                Encumbrance::Unburdened => {}
+                Encumbrance::Burdened => res.push_str(&styled(
+                    Style::Warn,
+                    "\nYou're burdened and need to catch your breath between moves",
+                )),
+                Encumbrance::Overloaded => res.push_str(&styled(
+                    Style::Warn,
+                    "\nYou're carrying too much to move at all",
+                )),
+            }
+            engine.connection_broker.send_player_message(player, res);
         }),
     )
 }
@@ -75,43 +344,96 @@ pub fn move_commands() -> Vec<Command> {
 
     for direction in Direction::values() {
         let cmd = Command::new(
-            &direction.name(),
+            direction.name(),
             &[&direction.name()[0..1]],
             &format!(
                 "Moves your character {} and describes where you end up",
                 direction.name()
             ),
             Box::new(move |engine, player, _| {
-                let player_character = engine.world.player_characters.entry(player).or_default();
+                if engine.world.check_player_location(player) {
+                    let location = engine.world.player_character(player).location;
+                    engine.connection_broker.send_player_message(
+                        player,
+                        engine.world.places[&location]
+                            .look(&engine.world, "The world shifts and you find yourself in"),
+                    );
+                    return;
+                }
 
-                if let Some(place) = engine.world.places.get(&player_character.location) {
-                    match place.connections().get(&direction) {
-                        Some(l) => {
-                            player_character.location = *l;
-                            let new_place = &engine.world.places[l];
-                            engine.connection_broker.send_player_message(
-                                player,
-                                new_place.look(&engine.world, "You move to"),
-                            );
-                        }
-                        None => {
-                            engine.connection_broker.send_player_message(
-                                player,
-                                format!("You cannot go {direction:?} from here"),
-                            );
-                        }
-                    }
-                } else {
+                let character = engine.world.player_character(player);
+                let (location, ghost) = (character.location, character.ghost.is_some());
+                if character.camping_until.take().is_some() {
+                    engine
+                        .connection_broker
+                        .send_player_message(player, "You break camp".to_string());
+                }
+                let character = engine.world.player_character(player);
+                if character.recalling_until.take().is_some() {
                     engine.connection_broker.send_player_message(
                         player,
-                        "Invalid location, resetting to start".to_string(),
+                        "You stop reading the recall scroll".to_string(),
                     );
-                    player_character.location = engine
-                        .world
-                        .overworld_locales
-                        .first()
-                        .map(|l| *l)
-                        .unwrap_or_default();
+                }
+
+                match engine.world.places[&location].connections().get(&direction) {
+                    // Ghosts slip by unnoticed
+                    Some(l) if ghost => {
+                        let l = *l;
+                        engine.world.player_character(player).location = l;
+                        engine.connection_broker.send_player_message(
+                            player,
+                            engine.world.places[&l].ghost_look(&engine.world, "You drift to"),
+                        );
+                    }
+                    Some(l) => {
+                        let l = *l;
+                        if let Err(e) = check_burden(engine, player) {
+                            engine
+                                .connection_broker
+                                .send_player_message(player, styled(Style::Warn, e));
+                            return;
+                        }
+                        let name = engine.world.player_character(player).name.clone();
+                        engine.connection_broker.broadcast_to_location(
+                            &engine.world,
+                            location,
+                            format!("{name} heads {}", direction.name()),
+                            Some(player),
+                        );
+                        engine
+                            .world
+                            .record_event(location, EventKind::Left(name.clone(), direction));
+                        engine
+                            .world
+                            .record_event(l, EventKind::Arrived(name.clone()));
+                        let character = engine.world.player_character(player);
+                        character.location = l;
+                        character.offer = None;
+                        character.conversation = None;
+                        engine.hooks.emit(Event::PlayerEnteredRoom(player, l));
+                        engine.connection_broker.broadcast_to_location(
+                            &engine.world,
+                            l,
+                            format!("{name} arrives {}", direction.reverse().arrival()),
+                            Some(player),
+                        );
+                        engine.connection_broker.send_player_message(
+                            player,
+                            engine.world.places[&l].look(&engine.world, "You move to"),
+                        );
+                        visit_place(engine, player);
+                        check_poison_laws(engine, player);
+                    }
+                    None => {
+                        engine.connection_broker.send_player_message(
+                            player,
+                            styled(
+                                Style::Warn,
+                                format!("You cannot go {direction:?} from here"),
+                            ),
+                        );
+                    }
                 }
             }),
         );
@@ -122,22 +444,5920 @@ pub fn move_commands() -> Vec<Command> {
     move_commands
 }
 
-pub fn quit_command() -> Command {
+/// Whether a character can move with what they're carrying, making burdened
+/// ones catch their breath before they can move again
+fn check_burden(engine: &mut Engine, player: PlayerId) -> Result<(), String> {
+    let config = config::get();
+    let current_tick = engine.world.current_tick;
+    let world = &engine.world;
+    let character = &world.player_characters[&player];
+    match character.encumbrance(|name| world.weight_of(name)) {
+        Encumbrance::Unburdened => Ok(()),
+        Encumbrance::Overloaded => {
+            Err("You're carrying too much to move, drop or sell something first".to_string())
+        }
+        Encumbrance::Burdened if character.next_move_tick > current_tick => {
+            Err("You're still catching your breath under all that weight".to_string())
+        }
+        Encumbrance::Burdened => {
+            engine.world.player_character(player).next_move_tick =
+                current_tick + config.seconds_to_ticks(config.burdened_move_seconds);
+            Ok(())
+        }
+    }
+}
+
+/// Records the player's visit to where they're standing, congratulating
+/// them if it makes them the first to explore the whole area
+pub fn visit_place(engine: &mut Engine, player: PlayerId) {
+    let character = &engine.world.player_characters[&player];
+    let first_visit = character.ghost.is_none() && !character.visited.contains(&character.location);
+    let discovered = engine.world.visit(player);
+
+    if first_visit {
+        let msg = award_experience(
+            engine,
+            player,
+            experience::EXPLORE_XP,
+            "exploring somewhere new",
+        );
+        engine
+            .connection_broker
+            .send_player_message(player, msg.trim_start().to_string());
+    }
+    if let Some(region) = discovered {
+        engine.connection_broker.send_player_message(
+            player,
+            styled(
+                Style::Good,
+                format!(
+                    "You're the first to explore every corner of {}! Your name will be remembered, \
+                    and you may give it a new one with 'name <new name>'",
+                    engine.world.places[&region].name
+                ),
+            ),
+        );
+        let msg = award_experience(engine, player, experience::DISCOVERY_XP, "your discovery");
+        engine
+            .connection_broker
+            .send_player_message(player, msg.trim_start().to_string());
+    }
+}
+
+/// Gives a character experience, scaled by the rules, returning a note of it
+/// and any levels reached to add to what they're told
+pub fn award_experience(engine: &mut Engine, player: PlayerId, amount: u32, why: &str) -> String {
+    let amount = (amount as f64 * engine.rules.xp_rate).round() as u32;
+    if amount == 0 {
+        return String::new();
+    }
+
+    let mut res = format!("\nYou gain {amount} experience for {why}");
+    for LevelUp { level, attribute } in engine
+        .world
+        .player_character(player)
+        .gain_experience(amount)
+    {
+        res.push_str(&format!(
+            "\n{}",
+            styled(
+                Style::Good,
+                format!("You reach level {level}! Your {attribute} rises and you feel hardier")
+            )
+        ));
+    }
+    res
+}
+
+/// Writes what a player gained or lost since `before` in the ledger. Only for
+/// things that make or destroy gold and items, moving them around isn't recorded.
+pub fn record_changes(
+    engine: &mut Engine,
+    player: PlayerId,
+    before: BTreeMap<String, i64>,
+    why: &str,
+) {
+    let character = engine.world.player_character(player);
+    let (name, after) = (character.name.clone(), character.holdings());
+    for (item, change) in ledger::changes(&before, &after) {
+        engine.world.record_transaction(&name, &item, change, why);
+    }
+}
+
+pub fn score_command() -> Command {
     Command::new(
-        "quit",
-        &["exit"],
-        "Log your character out of the game world and exit the session",
+        "score",
+        &["stats", "level"],
+        "Shows your level, how much experience you need for the next and your attributes",
         Box::new(|engine, player, _| {
-            let player_reg = engine.player_registry.blocking_read();
-            let name = player_reg
-                .get(&player)
-                .map(|p| p.username.as_str())
-                .unwrap_or("");
+            let character = &engine.world.player_characters[&player];
+            let level = character.level();
+            let progress = match level {
+                experience::MAX_LEVEL => "the highest level there is".to_string(),
+                _ => format!(
+                    "{} more to reach level {}",
+                    experience::threshold(level + 1) - character.experience,
+                    level + 1
+                ),
+            };
+
+            let base = character.attributes.named();
+            let attributes: Vec<_> = character
+                .effective_attributes()
+                .named()
+                .into_iter()
+                .zip(base)
+                .map(|((name, effective), (_, base))| {
+                    let change = match effective.value() - base.value() {
+                        0 => String::new(),
+                        n => format!(" ({n:+})"),
+                    };
+                    format!("  {name:<14}{}{change}", effective.value())
+                })
+                .collect();
+
+            let msg = format!(
+                "{}, level {level}\n\nExperience {}, {progress}\nHealth {}/{}\n\n{}",
+                styled(Style::Name, &character.name),
+                character.experience,
+                character.health,
+                character.max_health(),
+                attributes.join("\n")
+            );
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+    .ghostly()
+}
+
+pub fn name_command() -> Command {
+    Command::new(
+        "name",
+        &[],
+        "Renames an area you were the first to fully explore, like 'name The Sunless Deep'. Each area can only be renamed once",
+        Box::new(|engine, player, args| {
+            let new_name = args.joined();
+            let character = engine.world.player_character(player);
+            let discoverer = character.name.clone();
+            let location = character.location;
+            let region = engine.world.places[&location].parent.unwrap_or(location);
+
+            let claimable = engine.world.places[&region]
+                .discovery
+                .as_ref()
+                .is_some_and(|d| d.discoverer == discoverer && !d.renamed);
+            let taken = engine
+                .world
+                .places
+                .values()
+                .any(|p| p.name.eq_ignore_ascii_case(&new_name));
+
+            let res = if !claimable {
+                Err("You can only name an area you were the first to explore, and only once".to_string())
+            } else if taken {
+                Err(format!("There's already somewhere called {new_name}"))
+            } else {
+                check_name(&new_name)
+            };
+
+            let msg = match res {
+                Ok(()) => {
+                    engine.world.place_changed(region);
+                    let place = engine.world.places.get_mut(&region).unwrap();
+                    let old_name = std::mem::replace(&mut place.name, new_name.clone());
+                    if let Some(discovery) = &mut place.discovery {
+                        discovery.renamed = true;
+                    }
+                    engine.world.record_chronicle(format!(
+                        "{discoverer} renamed {old_name} to {new_name}"
+                    ));
+                    format!("{old_name} will be known as {new_name} from now on")
+                }
+                Err(e) => e,
+            };
+
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+}
+
+/// Checks a player chosen name is a sensible length, uses ordinary
+/// characters and avoids the server's blocked words
+pub fn check_name(name: &str) -> Result<(), String> {
+    if !(3..=40).contains(&name.chars().count()) {
+        return Err("Names need to be between 3 and 40 characters long".to_string());
+    }
+
+    if !name
+        .chars()
+        .all(|c| c.is_alphanumeric() || matches!(c, ' ' | '\'' | '-'))
+    {
+        return Err(
+            "Names can only use letters, numbers, spaces, apostrophes and hyphens".to_string(),
+        );
+    }
+
+    let lowercase = name.to_lowercase();
+    if config::get()
+        .blocked_words
+        .iter()
+        .any(|w| lowercase.contains(&w.to_lowercase()))
+    {
+        return Err("That name isn't allowed here".to_string());
+    }
+
+    Ok(())
+}
+
+/// What a player sees looking closely at something
+fn describe_target(engine: &Engine, target: Target) -> String {
+    let world = &engine.world;
+    match target {
+        Target::Exit(direction, location) => {
+            let place = &world.places[&location];
+            // Just the first sentence, the rest is for when they get there
+            format!(
+                "Looking {} you see {}\n\n{}",
+                styled(Style::Exit, direction.name()),
+                styled(Style::Title, &place.name),
+                place.first_sentence()
+            )
+        }
+        Target::Character(player) => {
+            let character = &world.player_characters[&player];
+            let mut res = format!(
+                "{} looks {}",
+                styled(Style::Name, &character.name),
+                character.condition()
+            );
+            if let Some(item) = &character.wielding {
+                res.push_str(&format!(", holding {}", item.name()));
+            }
+            res
+        }
+        Target::Creature(id) => {
+            let Some((_, creature)) = world.find_creature(id) else {
+                return "It's gone".to_string();
+            };
+            let mut res = format!(
+                "{} looks {}",
+                styled(Style::Name, &creature.name),
+                creature.condition()
+            );
+            if let Some(lore) = world.lore.get(&creature.name) {
+                res.push_str(&format!("\n\n{lore}"));
+            }
+            res
+        }
+        Target::Equipment(item) => item.describe(world.items.get(&item.template)),
+        Target::Item(name) => {
+            let about = world
+                .items
+                .get(&name)
+                .map(|i| i.description.as_str())
+                .or_else(|| world.lore.get(&name).map(String::as_str));
+            match about {
+                Some(about) => format!("{name}\n\n{about}"),
+                None => format!("{name}\n\n'lore {name}' might tell you more about it"),
+            }
+        }
+    }
+}
+
+pub fn say_command() -> Command {
+    Command::new(
+        "say",
+        &[],
+        "Says something to everyone nearby, like 'say well met'",
+        Box::new(|engine, player, args| {
+            let text = args.rest().to_string();
+            if text.is_empty() {
+                engine
+                    .connection_broker
+                    .send_player_message(player, "Say what? Like 'say well met'".to_string());
+                return;
+            }
 
+            let character = engine.world.player_character(player);
+            let (name, location) = (character.name.clone(), character.location);
+            let said = markup::escape(&text);
+            engine.connection_broker.broadcast_to_location(
+                &engine.world,
+                location,
+                format!("{name} says, \"{said}\""),
+                Some(player),
+            );
+            engine
+                .world
+                .record_event(location, EventKind::Said(name, text));
             engine
                 .connection_broker
-                .send_player_message(player, format!("Logging out, goodbye {name}!"));
-            engine.connection_broker.disconnect_player(player);
+                .send_player_message(player, format!("You say, \"{said}\""));
+        }),
+    )
+}
+
+pub fn emote_command() -> Command {
+    Command::new(
+        "emote",
+        &["me"],
+        "Shows everyone nearby your character doing something, like 'emote grins wickedly'",
+        Box::new(|engine, player, args| {
+            let text = args.rest();
+            if text.is_empty() {
+                engine.connection_broker.send_player_message(
+                    player,
+                    "Emote what? Like 'emote grins wickedly'".to_string(),
+                );
+                return;
+            }
+
+            let character = engine.world.player_character(player);
+            let (name, location) = (character.name.clone(), character.location);
+            let msg = social::emote(&name, &markup::escape(text));
+            engine
+                .connection_broker
+                .broadcast_to_room(&engine.world, location, msg, &[]);
+        }),
+    )
+}
+
+pub fn social_commands() -> Vec<Command> {
+    SOCIALS
+        .iter()
+        .map(|social| {
+            Command::new(
+                social.name,
+                &[],
+                &format!(
+                    "Lets everyone nearby see you {0}, or {0} at someone here like '{0} Ada'",
+                    social.name
+                ),
+                Box::new(move |engine, player, args| {
+                    let target = args.joined();
+                    perform_social(engine, player, social, &target);
+                }),
+            )
+        })
+        .collect()
+}
+
+fn perform_social(engine: &mut Engine, player: PlayerId, social: &Social, target: &str) {
+    let character = engine.world.player_character(player);
+    let (name, location) = (character.name.clone(), character.location);
+
+    let target = if target.is_empty() {
+        None
+    } else {
+        let here: Vec<_> = engine
+            .world
+            .player_characters
+            .iter()
+            .filter(|(p, c)| **p != player && c.location == location)
+            .map(|(p, c)| (*p, c.name.clone()))
+            .collect();
+        match here.iter().find(|(_, n)| n.eq_ignore_ascii_case(target)) {
+            Some(found) => Some(found.clone()),
+            None => {
+                let suggestion = fuzzy::did_you_mean(target, here.iter().map(|(_, n)| n));
+                engine.connection_broker.send_player_message(
+                    player,
+                    format!("There's nobody called {target} here{suggestion}"),
+                );
+                return;
+            }
+        }
+    };
+
+    let rendered = social.render(&name, target.as_ref().map(|(_, n)| n.as_str()));
+    let mut exclude = vec![player];
+    if let (Some((target, _)), Some(msg)) = (&target, rendered.target) {
+        exclude.push(*target);
+        engine.connection_broker.send_player_message(*target, msg);
+    }
+    engine
+        .connection_broker
+        .broadcast_to_room(&engine.world, location, rendered.room, &exclude);
+    engine
+        .connection_broker
+        .send_player_message(player, rendered.actor);
+}
+
+pub fn tell_command() -> Command {
+    Command::new(
+        "tell",
+        &["whisper"],
+        "Says something privately to another dreamer wherever they are, like 'tell Ada meet me at the well'",
+        Box::new(|engine, player, args| {
+            let text = args.rest();
+            let Some((target, name, msg)) = player_named(engine, text) else {
+                let res = match text.split_whitespace().next() {
+                    Some(name) => format!("There's no dreamer called {name}"),
+                    None => "Tell who what? Like 'tell Ada meet me at the well'".to_string(),
+                };
+                engine.connection_broker.send_player_message(player, res);
+                return;
+            };
+            send_tell(engine, player, target, &name, msg);
+        }),
+    )
+}
+
+pub fn reply_command() -> Command {
+    Command::new(
+        "reply",
+        &[],
+        "Answers whoever last told you something privately, like 'reply on my way'",
+        Box::new(|engine, player, args| {
+            let text = args.rest();
+            let Some(target) = engine.connection_broker.last_tell(player) else {
+                engine
+                    .connection_broker
+                    .send_player_message(player, "Nobody has told you anything yet".to_string());
+                return;
+            };
+            let name = engine
+                .world
+                .player_characters
+                .get(&target)
+                .map(|c| c.name.clone())
+                .unwrap_or_default();
+            send_tell(engine, player, target, &name, text);
+        }),
+    )
+}
+
+/// Finds the player whose character or account name starts the text, returning
+/// them, the name they were found by and whatever follows it
+fn player_named<'a>(engine: &Engine, text: &'a str) -> Option<(PlayerId, String, &'a str)> {
+    let accounts = engine.player_registry.blocking_read();
+    let characters = engine
+        .world
+        .player_characters
+        .iter()
+        .map(|(p, c)| (*p, c.name.as_str()));
+    let usernames = accounts.iter().map(|(p, a)| (*p, a.username.as_str()));
+
+    // The longest match wins, so 'Ada Lovelace' isn't mistaken for 'Ada'
+    characters
+        .chain(usernames)
+        .filter(|(_, name)| !name.is_empty())
+        .filter_map(|(p, name)| {
+            let head = text.get(..name.len())?;
+            let rest = &text[name.len()..];
+            (head.eq_ignore_ascii_case(name) && (rest.is_empty() || rest.starts_with(' ')))
+                .then(|| (p, name.to_string(), rest.trim()))
+        })
+        .max_by_key(|(_, name, _)| name.len())
+}
+
+fn send_tell(engine: &mut Engine, player: PlayerId, target: PlayerId, name: &str, text: &str) {
+    let res = if text.is_empty() {
+        format!("Tell {name} what?")
+    } else if target == player {
+        "You mutter to yourself".to_string()
+    } else if !engine.connection_broker.is_connected(target) {
+        format!("{name} isn't dreaming right now")
+    } else {
+        let text = markup::escape(text);
+        let from = engine.world.player_character(player).name.clone();
+        engine.connection_broker.tell(
+            player,
+            target,
+            format!("{} tells you, \"{text}\"", styled(Style::Name, from)),
+        );
+        format!("You tell {}, \"{text}\"", styled(Style::Name, name))
+    };
+    engine.connection_broker.send_player_message(player, res);
+}
+
+pub fn chronicle_command() -> Command {
+    Command::new(
+        "chronicle",
+        &[],
+        "Shows the most recent notable events in the world's history",
+        Box::new(|engine, player, _| {
+            let mut res = String::from("The Chronicle\n\n");
+            let recent = engine
+                .world
+                .chronicle
+                .iter()
+                .rev()
+                .take(CHRONICLE_LENGTH)
+                .rev();
+            for entry in recent {
+                res.push_str(&format!("Cycle {}: {}\n", entry.cycle, entry.text));
+            }
+            if engine.world.chronicle.is_empty() {
+                res.push_str("Nothing of note has happened yet");
+            }
+
+            engine.connection_broker.send_player_message(player, res);
+        }),
+    )
+}
+
+pub fn map_command() -> Command {
+    Command::new(
+        "map",
+        &[],
+        "Lists the areas you know of, from having been there or from maps you've bought, and how much of each you know",
+        Box::new(|engine, player, _| {
+            let world = &engine.world;
+            let character = &world.player_characters[&player];
+            let knows = |l: &Location| character.visited.contains(l) || character.charted.contains(l);
+
+            let mut areas = Vec::new();
+            for region in &world.overworld_locales {
+                let places: Vec<_> = std::iter::once(*region)
+                    .chain(
+                        world
+                            .places
+                            .values()
+                            .filter(|p| p.parent == Some(*region))
+                            .map(|p| p.location),
+                    )
+                    .collect();
+                let known = places.iter().filter(|l| knows(l)).count();
+                if known == 0 {
+                    continue;
+                }
+                let visited = places
+                    .iter()
+                    .filter(|l| character.visited.contains(l))
+                    .count();
+                areas.push(format!(
+                    "  {} - {known} of {} places known, {visited} visited",
+                    styled(Style::Title, world.locale_name(*region).unwrap_or("Unknown")),
+                    places.len()
+                ));
+            }
+
+            let msg = match areas.is_empty() {
+                true => "You don't know of anywhere yet, explore or buy a map from a cartographer"
+                    .to_string(),
+                false => format!("Areas you know of\n\n{}", areas.join("\n")),
+            };
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+    .ghostly()
+}
+
+pub fn chart_command() -> Command {
+    Command::new(
+        "chart",
+        &["cartographer"],
+        "Trades maps with a cartographer here. 'chart sell' tells them of the places you've been for a few coins each, 'chart buy' buys a copy of the village's map, or 'chart' alone to hear what they'd pay and ask",
+        Box::new(|engine, player, args| {
+            let res = match args.next() {
+                Some("sell") => sell_map(engine, player),
+                Some("buy") => buy_map(engine, player),
+                _ => map_offers(engine, player),
+            }
+            .unwrap_or_else(|e| e);
+            engine.connection_broker.send_player_message(player, res);
+        }),
+    )
+}
+
+/// The cartographer where the player is and the village whose map they keep
+fn cartographer_here(engine: &Engine, player: PlayerId) -> Result<(String, Location), String> {
+    let location = engine.world.player_characters[&player].location;
+    let cartographer = engine.world.places[&location]
+        .cartographer()
+        .ok_or("There's no cartographer here")?;
+    Ok((cartographer, engine.world.region_of(location)))
+}
+
+/// Places on the village's map the player doesn't know of yet
+fn unknown_places(engine: &Engine, player: PlayerId, village: Location) -> Vec<Location> {
+    let world = &engine.world;
+    let character = &world.player_characters[&player];
+    let Some(map) = world.maps.get(&village) else {
+        return Vec::new();
+    };
+    cartography::unknown(map, |l| {
+        character.visited.contains(l)
+            || character.charted.contains(l)
+            || !world.places.contains_key(l)
+    })
+    .collect()
+}
+
+fn map_offers(engine: &Engine, player: PlayerId) -> Result<String, String> {
+    let (cartographer, village) = cartographer_here(engine, player)?;
+    let map = engine.world.maps.get(&village);
+    let new = engine.world.player_characters[&player]
+        .visited
+        .iter()
+        .filter(|l| map.is_none_or(|m| !m.contains(l)))
+        .count() as u32;
+    let unknown = unknown_places(engine, player, village).len() as u32;
+
+    Ok(format!(
+        "{cartographer} has {} places on their map. They'd pay {} {GOLD}s to hear of the {new} you've been to that they haven't mapped, and sell you the {unknown} you don't know of for {} {GOLD}s",
+        map.map_or(0, |m| m.len()),
+        new * cartography::PAY_PER_PLACE,
+        unknown * cartography::PRICE_PER_PLACE
+    ))
+}
+
+fn sell_map(engine: &mut Engine, player: PlayerId) -> Result<String, String> {
+    let (cartographer, village) = cartographer_here(engine, player)?;
+    let visited = engine.world.player_characters[&player].visited.clone();
+    let map = engine.world.maps.entry(village).or_default();
+    let added = cartography::contribute(map, &visited) as u32;
+    if added == 0 {
+        return Err(format!(
+            "{cartographer} already has everywhere you've been on their map"
+        ));
+    }
+
+    let pay = added * cartography::PAY_PER_PLACE;
+    let character = engine.world.player_character(player);
+    character.inventory.add(GOLD, pay);
+    let name = character.name.clone();
+    engine
+        .world
+        .record_transaction(&name, GOLD, pay.into(), "selling a map");
+    Ok(format!(
+        "{cartographer} listens closely as you describe {added} places they hadn't mapped, and pays you {pay} {GOLD}s"
+    ))
+}
+
+fn buy_map(engine: &mut Engine, player: PlayerId) -> Result<String, String> {
+    let (cartographer, village) = cartographer_here(engine, player)?;
+    let unknown = unknown_places(engine, player, village);
+    if unknown.is_empty() {
+        return Err(format!(
+            "{cartographer}'s map doesn't show anywhere you don't already know of"
+        ));
+    }
+
+    let count = unknown.len() as u32;
+    let price = count * cartography::PRICE_PER_PLACE;
+    let character = engine.world.player_character(player);
+    if !character.inventory.remove(GOLD, price) {
+        return Err(format!(
+            "A copy of {cartographer}'s map costs {price} {GOLD}s, more than you have"
+        ));
+    }
+    character.charted.extend(unknown);
+    let name = character.name.clone();
+    engine
+        .world
+        .record_transaction(&name, GOLD, -i64::from(price), "buying a map");
+    Ok(format!(
+        "You buy a copy of {cartographer}'s map for {price} {GOLD}s, it shows {count} places you didn't know of. 'map' to look it over"
+    ))
+}
+
+pub fn time_command() -> Command {
+    Command::new(
+        "time",
+        &[],
+        "Tells you the time of day in the world",
+        Box::new(|engine, player, _| {
+            let msg = engine.world.time().describe();
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+    .ghostly()
+}
+
+pub fn weather_command() -> Command {
+    Command::new(
+        "weather",
+        &[],
+        "Tells you what the weather's doing over the area you're in",
+        Box::new(|engine, player, _| {
+            let world = &engine.world;
+            let location = world.player_characters[&player].location;
+            let region = world.region_of(location);
+            let name = world
+                .places
+                .get(&region)
+                .map(|p| p.name.as_str())
+                .unwrap_or("somewhere strange");
+            let mut msg = match world.weather_at(location) {
+                Some(front) => format!("Over {name} there's {}. {}", front.name, front.description),
+                None => format!("The weather over {name} is settled and unremarkable"),
+            };
+            if !world.places[&location].outdoors() {
+                msg.push_str(
+                    "\nYou can't see the sky from here, that's just the last you saw of it",
+                );
+            }
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+    .ghostly()
+}
+
+pub fn who_command() -> Command {
+    Command::new(
+        "who",
+        &["online"],
+        "Lists the dreamers who are online, roughly where they are and how long they've been idle",
+        Box::new(|engine, player, _| {
+            let mut online: Vec<_> = engine
+                .connection_broker
+                .online()
+                .into_iter()
+                .filter_map(|(p, idle)| {
+                    let character = engine.world.player_characters.get(&p)?;
+                    if character.creating.is_some() {
+                        return None;
+                    }
+                    let locale = engine
+                        .world
+                        .locale_name(character.location)
+                        .unwrap_or("somewhere strange");
+                    Some((character.name.clone(), locale.to_string(), idle))
+                })
+                .collect();
+            online.sort();
+
+            let mut res = format!("Dreamers online ({})\n", online.len());
+            for (name, locale, idle) in online {
+                res.push_str(&format!("\n  {} - {locale}", styled(Style::Name, name)));
+                let minutes = idle.as_secs() / 60;
+                match minutes {
+                    0 => {}
+                    1..60 => res.push_str(&format!(", idle {minutes}m")),
+                    _ => res.push_str(&format!(", idle {}h {}m", minutes / 60, minutes % 60)),
+                }
+            }
+
+            engine.connection_broker.send_player_message(player, res);
+        }),
+    )
+    .ghostly()
+}
+
+pub fn lore_command() -> Command {
+    Command::new(
+        "lore",
+        &[],
+        "Looks up what's known about an item or creature you've come across, like 'lore Torch'. Run alone to list everything you could look up",
+        Box::new(|engine, player, args| {
+            let name = args.joined();
+            let character = engine.world.player_character(player);
+
+            if name.is_empty() {
+                let mut known: Vec<_> = character
+                    .encountered
+                    .keys()
+                    .map(String::as_str)
+                    .chain(character.inventory.names())
+                    .chain(character.wielding.iter().map(|i| i.template.as_str()))
+                    .collect();
+                known.sort_unstable();
+                known.dedup();
+
+                let res = if known.is_empty() {
+                    "You haven't come across anything worth looking up yet".to_string()
+                } else {
+                    format!("You could look up\n\n{}", known.join("\n"))
+                };
+                engine.connection_broker.send_player_message(player, res);
+                return;
+            }
+
+            let Some((name, kind)) = character.known_lore(&name) else {
+                engine.connection_broker.send_player_message(
+                    player,
+                    format!("You don't know anything about {name}"),
+                );
+                return;
+            };
+
+            if let Some(text) = engine.world.lore.get(&name) {
+                engine
+                    .connection_broker
+                    .send_player_message(player, lore::entry(&name, kind, text));
+                return;
+            }
+
+            let waiting = engine.pending_lore.entry(name.clone()).or_default();
+            if waiting.is_empty() {
+                engine
+                    .gen_handle
+                    .request_generate(GenerationReq::Lore(name.clone(), kind));
+            }
+            if !waiting.contains(&player) {
+                waiting.push(player);
+            }
+            engine.connection_broker.send_player_message(
+                player,
+                format!("You try to recall what you know about {name}..."),
+            );
+        }),
+    )
+}
+
+pub fn gather_command() -> Command {
+    Command::new(
+        "gather",
+        &["forage"],
+        "Searches your surroundings for cooking and alchemy ingredients. What you find depends on where you are",
+        Box::new(|engine, player, _| {
+            let current_tick = engine.world.current_tick;
+            let location = engine.world.player_character(player).location;
+            let place = &engine.world.places[&location];
+            let region = place.parent.and_then(|p| engine.world.places.get(&p));
+            let tags = place
+                .tags
+                .iter()
+                .chain(region.into_iter().flat_map(|r| r.tags.iter()))
+                .map(String::as_str);
+            let found = crafting::gatherable(tags)
+                .choose(&mut rand::thread_rng())
+                .map(|i| i.name);
+
+            let held = engine.world.player_character(player).holdings();
+            let character = engine.world.player_character(player);
+            let msg = if character.next_gather_tick > current_tick {
+                "You've picked this area clean for now, give it a little while".to_string()
+            } else if let Some(found) = found {
+                character.next_gather_tick =
+                    current_tick + config::get().seconds_to_ticks(config::get().gather_seconds);
+                character.inventory.add(found, 1);
+                format!("You search around and find some {found}")
+            } else {
+                "There's nothing useful to gather here".to_string()
+            };
+            record_changes(engine, player, held, "gathering");
+
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+}
+
+/// The cook and brew commands, which combine ingredients into consumables
+pub fn craft_command(craft: Craft) -> Command {
+    let (name, verb, help) = match craft {
+        Craft::Cooking => (
+            "cook",
+            "cooking",
+            "Cooks a dish from 2 or 3 ingredients you're carrying, like 'cook barley, honeycomb'. Food gives long lasting small boosts",
+        ),
+        Craft::Alchemy => (
+            "brew",
+            "brewing",
+            "Brews a potion from 2 or 3 ingredients you're carrying, like 'brew bat wing, glowmoss'. Potions give brief strong boosts",
+        ),
+    };
+
+    Command::new(
+        name,
+        &[],
+        help,
+        Box::new(move |engine, player, args| {
+            let (recipe, needed) = match ingredients_for(engine, player, craft, &args.joined()) {
+                Ok(found) => found,
+                Err(e) => {
+                    engine.connection_broker.send_player_message(player, e);
+                    return;
+                }
+            };
+
+            let held = engine.world.player_character(player).holdings();
+            let inventory = &mut engine.world.player_character(player).inventory;
+            for (ingredient, count) in needed {
+                inventory.remove(&ingredient, count);
+            }
+            record_changes(engine, player, held, verb);
+
+            if let Some(known) = recipe.clone().with_known_name() {
+                finish_crafting(engine, player, &known);
+            } else if let Some(invented) = engine.world.recipes.get(&recipe.key()).cloned() {
+                finish_crafting(engine, player, &invented);
+            } else {
+                let key = recipe.key();
+                let waiting = engine.pending_recipes.entry(key).or_default();
+                if waiting.is_empty() {
+                    engine
+                        .gen_handle
+                        .request_generate(GenerationReq::NameRecipe(recipe));
+                }
+                waiting.push(player);
+                engine.connection_broker.send_player_message(
+                    player,
+                    format!(
+                        "You combine the ingredients into something you've never seen before, it needs a moment to {}",
+                        if craft == Craft::Cooking { "cook" } else { "settle" }
+                    ),
+                );
+            }
+        }),
+    )
+    .takes(verb, config::get().craft_seconds)
+    .checked(Box::new(move |engine, player, args| {
+        ingredients_for(engine, player, craft, &args.joined()).map(drop)
+    }))
+}
+
+/// The recipe the ingredients listed make and how many of each it takes, if
+/// the player has them all
+fn ingredients_for(
+    engine: &mut Engine,
+    player: PlayerId,
+    craft: Craft,
+    input: &str,
+) -> Result<(Recipe, HashMap<String, u32>), String> {
+    let ingredients: Vec<_> = input
+        .split([',', '+'])
+        .flat_map(|i| i.split(" and "))
+        .map(str::trim)
+        .filter(|i| !i.is_empty())
+        .collect();
+    let recipe = Recipe::combine(craft, &ingredients)?;
+
+    let mut needed: HashMap<String, u32> = HashMap::new();
+    for ingredient in &recipe.ingredients {
+        *needed.entry(ingredient.clone()).or_default() += 1;
+    }
+    let inventory = &engine.world.player_character(player).inventory;
+    if let Some((missing, _)) = needed
+        .iter()
+        .find(|(i, count)| inventory.get(i).is_none_or(|s| s.count < **count))
+    {
+        return Err(format!("You don't have enough {missing}"));
+    }
+
+    Ok((recipe, needed))
+}
+
+/// Gives the player what they made, teaching them the recipe if it's new to them
+pub fn finish_crafting(engine: &mut Engine, player: PlayerId, recipe: &Recipe) {
+    let character = engine.world.player_character(player);
+    character.inventory.add(&recipe.name, 1);
+    let name = character.name.clone();
+    engine
+        .world
+        .record_transaction(&name, &recipe.name, 1, "crafting");
+
+    let character = engine.world.player_character(player);
+    let mut msg = format!("You made a {}", recipe.name);
+    if character.known_recipes.insert(recipe.name.clone()) {
+        msg.push_str(&format!(
+            "\nYou've discovered a new recipe! {}\n{}",
+            recipe.description,
+            recipe.effect()
+        ));
+    }
+
+    engine.connection_broker.send_player_message(player, msg);
+}
+
+pub fn recipes_command() -> Command {
+    Command::new(
+        "recipes",
+        &[],
+        "Lists the dishes and potions you know how to make, and hints at some you don't",
+        Box::new(|engine, player, _| {
+            let known = engine.world.player_character(player).known_recipes.clone();
+            let mut res = String::from("Recipes you know\n\n");
+
+            for recipe in crafting::known_recipes().chain(engine.world.recipes.values().cloned()) {
+                if known.contains(&recipe.name) {
+                    res.push_str(&format!(
+                        "{} ({}): {}\n  {}\n",
+                        recipe.name,
+                        recipe.craft.name(),
+                        recipe.ingredients.join(", "),
+                        recipe.effect()
+                    ));
+                }
+            }
+            if known.is_empty() {
+                res.push_str("None yet, try experimenting with 'cook' and 'brew'\n");
+            }
+
+            // Only the fixed recipes get hinted at, invented ones are for players to share
+            let undiscovered: Vec<_> = crafting::known_recipes()
+                .filter(|r| !known.contains(&r.name))
+                .collect();
+            if !undiscovered.is_empty() {
+                res.push_str("\nRumored recipes\n\n");
+                for recipe in undiscovered {
+                    res.push_str(&format!(
+                        "A {} made with {} and something else\n",
+                        recipe.craft.product(),
+                        recipe.ingredients[0]
+                    ));
+                }
+            }
+
+            engine.connection_broker.send_player_message(player, res);
+        }),
+    )
+}
+
+pub fn blueprints_command() -> Command {
+    Command::new(
+        "blueprints",
+        &[],
+        "Lists what the workshop here can make and the materials each takes, 'craft <item>' to make one",
+        Box::new(|engine, player, _| {
+            let res = list_blueprints(engine, player).unwrap_or_else(|e| e);
+            engine.connection_broker.send_player_message(player, res);
+        }),
+    )
+}
+
+pub fn craft_equipment_command() -> Command {
+    Command::new(
+        "craft",
+        &["forge"],
+        "Makes equipment at a workshop from the materials its blueprint takes, like 'craft iron sword'. 'blueprints' lists what can be made",
+        Box::new(|engine, player, args| {
+            let name = args.joined();
+            let res = craft_equipment(engine, player, &name).unwrap_or_else(|e| e);
+            engine.connection_broker.send_player_message(player, res);
+        }),
+    )
+    .takes("crafting", config::get().craft_seconds)
+    .checked(Box::new(|engine, player, args| {
+        blueprint_for(engine, player, &args.joined()).map(drop)
+    }))
+}
+
+/// The workshop the player's in and its name
+fn workshop_here(engine: &Engine, player: PlayerId) -> Result<(Location, String), String> {
+    let location = engine.world.player_characters[&player].location;
+    let place = &engine.world.places[&location];
+    if !place.tags.contains(WORKSHOP_TAG) {
+        return Err("There's no workshop here, look for one in a village".to_string());
+    }
+    Ok((location, place.name.clone()))
+}
+
+pub fn list_blueprints(engine: &mut Engine, player: PlayerId) -> Result<String, String> {
+    let (location, name) = workshop_here(engine, player)?;
+    let place = &engine.world.places[&location];
+    if let Some(workshop) = &place.workshop {
+        return Ok(workshop.describe(&name));
+    }
+    if engine.world.items.is_empty() {
+        return Err(format!(
+            "Nobody at {name} has seen anything worth making yet"
+        ));
+    }
+
+    let waiting = engine.pending_workshops.entry(location).or_default();
+    if waiting.is_empty() {
+        let value_of = |name: String| Some((name.clone(), engine.world.base_value(&name)?));
+        let mut rng = rand::thread_rng();
+        let mut products: Vec<_> = engine
+            .world
+            .items
+            .keys()
+            .cloned()
+            .filter_map(value_of)
+            .collect();
+        products.shuffle(&mut rng);
+        products.truncate(WORKSHOP_CANDIDATES);
+        let mut materials: Vec<_> = obtainable_items(engine)
+            .into_iter()
+            .filter_map(value_of)
+            .collect();
+        materials.shuffle(&mut rng);
+        materials.truncate(WORKSHOP_CANDIDATES);
+        engine
+            .gen_handle
+            .request_generate(GenerationReq::Blueprints(
+                location,
+                name.clone(),
+                place.description.clone(),
+                products,
+                materials,
+            ));
+    }
+    let waiting = engine.pending_workshops.entry(location).or_default();
+    if !waiting.contains(&player) {
+        waiting.push(player);
+    }
+    Ok(format!(
+        "Someone at {name} starts sketching out blueprints..."
+    ))
+}
+
+/// Pins up the blueprints a workshop drew up, settled so nothing can be made
+/// for less than it's worth
+pub fn open_workshop(engine: &mut Engine, location: Location, blueprints: Vec<Blueprint>) {
+    let world = &engine.world;
+    let blueprints = blueprints
+        .into_iter()
+        .filter(|b| world.items.contains_key(&b.name))
+        .filter_map(|b| b.settled(|name| world.base_value(name)))
+        .collect();
+
+    let Some(place) = engine.world.places.get_mut(&location) else {
+        return;
+    };
+    place.workshop.get_or_insert(Workshop::new(blueprints));
+    engine.world.place_changed(location);
+}
+
+/// The blueprint here for something, if the player has the materials it takes.
+/// A workshop without blueprints yet starts drawing them up.
+fn blueprint_for(engine: &mut Engine, player: PlayerId, name: &str) -> Result<Blueprint, String> {
+    let (location, workshop) = workshop_here(engine, player)?;
+    if name.trim().is_empty() {
+        return Err("Craft what? 'blueprints' lists what can be made here".to_string());
+    }
+    let Some(found) = &engine.world.places[&location].workshop else {
+        return Err(list_blueprints(engine, player).unwrap_or_else(|e| e));
+    };
+    let blueprint = found.blueprint(name).cloned().ok_or_else(|| {
+        format!(
+            "There's no blueprint for {name} at {workshop}{}",
+            fuzzy::did_you_mean(name, found.blueprints.iter().map(|b| &b.name))
+        )
+    })?;
+
+    let counts = engine.world.player_character(player).inventory.counts();
+    if let Some((missing, _)) = blueprint
+        .materials
+        .iter()
+        .find(|(m, count)| counts.get(m).copied().unwrap_or_default() < i64::from(*count))
+    {
+        return Err(format!(
+            "You don't have enough {missing}, the {} takes {}",
+            blueprint.name,
+            blueprint.materials_list()
+        ));
+    }
+
+    Ok(blueprint)
+}
+
+/// Uses up the materials a blueprint here takes to make its equipment
+fn craft_equipment(engine: &mut Engine, player: PlayerId, name: &str) -> Result<String, String> {
+    let blueprint = blueprint_for(engine, player, name)?;
+    let held = engine.world.player_character(player).holdings();
+    let inventory = &mut engine.world.player_character(player).inventory;
+    for (material, count) in &blueprint.materials {
+        if inventory.remove(material, *count) {
+            continue;
+        }
+        // Equipment, the worst made pieces go first
+        let mut pieces: Vec<_> = inventory
+            .equipment()
+            .iter()
+            .filter(|i| &i.template == material)
+            .map(|i| (i.quality, i.id))
+            .collect();
+        pieces.sort_by_key(|(quality, _)| *quality);
+        for (_, id) in pieces.into_iter().take(*count as usize) {
+            inventory.take_by_id(id);
+        }
+    }
+    inventory.add_instance(ItemInstance::new(&blueprint.name, Quality::Common));
+    record_changes(engine, player, held, "crafting");
+
+    Ok(format!(
+        "You craft a {} from {}",
+        blueprint.name,
+        blueprint.materials_list()
+    ))
+}
+
+pub fn consume_command() -> Command {
+    Command::new(
+        "consume",
+        &["eat", "drink"],
+        "Eats or drinks a dish or potion you're carrying, like 'eat honey cake'",
+        Box::new(|engine, player, args| {
+            let name = args.joined();
+            let current_tick = engine.world.current_tick;
+            let recipe = engine.world.find_recipe(&name);
+            let character = engine.world.player_character(player);
+            let who = character.name.clone();
+
+            let Some(recipe) = recipe.filter(|r| character.inventory.remove(&r.name, 1)) else {
+                engine.connection_broker.send_player_message(
+                    player,
+                    format!(
+                        "You don't have anything called {name} to consume{}",
+                        fuzzy::did_you_mean(&name, character.inventory.names())
+                    ),
+                );
+                return;
+            };
+
+            engine.world.heal_player(player, recipe.heal);
+            let character = engine.world.player_character(player);
+            let until_tick = current_tick + config::get().seconds_to_ticks(recipe.duration_seconds);
+            let msg = match recipe.status_effect(until_tick) {
+                Some(poisoned) if recipe.is_poison() => {
+                    character.stack_effect(poisoned, MAX_POISON_STACKS);
+                    format!("You drink the {} and immediately regret it", recipe.name)
+                }
+                Some(effect) => {
+                    character.add_effect(effect);
+                    format!("You consume the {}, it {}", recipe.name, recipe.effect())
+                }
+                None => format!("You consume the {}, it {}", recipe.name, recipe.effect()),
+            };
+            engine
+                .world
+                .record_transaction(&who, &recipe.name, -1, "consuming it");
+
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+}
+
+pub fn coat_command() -> Command {
+    Command::new(
+        "coat",
+        &[],
+        "Coats your weapon with a poison you're carrying, like 'coat widow's kiss'. It lasts a few hits. Villages don't take kindly to poisoned blades",
+        Box::new(|engine, player, args| {
+            let name = args.joined();
+            let poison = engine.world.find_recipe(&name).filter(|r| r.is_poison());
+            let held = engine.world.player_character(player).holdings();
+            let character = engine.world.player_character(player);
+
+            let msg = match poison {
+                Some(poison) if character.inventory.remove(&poison.name, 1) => {
+                    let charges = config::get().coating_charges;
+                    character.coating = Some(Coating {
+                        poison: poison.name.clone(),
+                        charges,
+                    });
+                    format!(
+                        "You carefully coat your weapon with {}, it should last {charges} hits",
+                        poison.name
+                    )
+                }
+                _ => format!("You don't have a poison called {name}"),
+            };
+            record_changes(engine, player, held, "coating a weapon");
+
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+    .takes("coating your weapon", config::get().craft_seconds)
+}
+
+/// Villages search anyone who comes in for poisoned weapons, taking the poison
+/// and a fine from those who are caught. Being sneaky helps.
+fn check_poison_laws(engine: &mut Engine, player: PlayerId) {
+    let location = engine.world.player_character(player).location;
+    let Some(prosperity) = engine.world.prosperity(location) else {
+        return;
+    };
+    let character = engine.world.player_character(player);
+    if character.coating.is_none() {
+        return;
+    }
+
+    // Better paid guards are harder to sneak past
+    let sneak =
+        rand::thread_rng().gen_range(1..=20) + character.effective_attributes().agility.modifier();
+    if sneak >= 12 + prosperity.guard_bonus() {
+        return;
+    }
+
+    character.coating = None;
+    let fine = config::get().poison_fine.min(
+        character
+            .inventory
+            .get(GOLD)
+            .map(|g| g.count)
+            .unwrap_or_default(),
+    );
+    character.inventory.remove(GOLD, fine);
+    for score in character.relationships.values_mut() {
+        *score -= relationship::CRIME_PENALTY;
+    }
+    let name = character.name.clone();
+    if let Some(faction) = engine.world.faction_mut(location) {
+        faction.deposit(fine);
+    }
+    engine
+        .world
+        .record_transaction(&name, GOLD, -i64::from(fine), "a fine");
+
+    engine.connection_broker.send_player_message(
+        player,
+        format!(
+            "A guard spots the poison on your weapon! They wipe it clean and fine you {fine} gold. Word of it will get around"
+        ),
+    );
+    engine.connection_broker.broadcast_to_location(
+        &engine.world,
+        location,
+        format!("A guard catches {name} with a poisoned weapon and hauls them aside"),
+        Some(player),
+    );
+}
+
+pub fn wield_command() -> Command {
+    Command::new(
+        "wield",
+        &["equip"],
+        "Takes a piece of equipment you're carrying in hand, like 'wield iron sword'. Run alone to put away what you're holding",
+        Box::new(|engine, player, args| {
+            let name = args.joined();
+            let character = engine.world.player_character(player);
+
+            let msg = if name.is_empty() {
+                match character.wielding.take() {
+                    Some(held) => {
+                        let msg = format!("You put away the {}", held.name());
+                        character.inventory.add_instance(held);
+                        msg
+                    }
+                    None => "You aren't holding anything".to_string(),
+                }
+            } else {
+                match character.inventory.take_instance(&name) {
+                    Some(item) => {
+                        let msg = format!("You wield the {}", item.name());
+                        if let Some(held) = character.wielding.replace(item) {
+                            character.inventory.add_instance(held);
+                        }
+                        msg
+                    }
+                    None => format!(
+                        "You don't have any equipment called {name}{}",
+                        fuzzy::did_you_mean(&name, equipment_names(character))
+                    ),
+                }
+            };
+
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+}
+
+/// Finds a piece of equipment in the character's hand or pack
+/// The names of everything a character could wield, for suggesting what they meant
+fn equipment_names(character: &Character) -> Vec<String> {
+    character
+        .wielding
+        .iter()
+        .chain(character.inventory.equipment())
+        .map(|i| i.name())
+        .collect()
+}
+
+fn held_equipment<'a>(character: &'a mut Character, name: &str) -> Option<&'a mut ItemInstance> {
+    match &mut character.wielding {
+        Some(held) if held.matches(name) => Some(held),
+        _ => character.inventory.find_instance_mut(name),
+    }
+}
+
+pub fn enchant_command() -> Command {
+    Command::new(
+        "enchant",
+        &[],
+        "Spends arcane essence to enchant a piece of equipment you're carrying, like 'enchant iron sword with strength'. Each enchantment costs more and is riskier than the last",
+        Box::new(|engine, player, args| {
+            let input = args.joined();
+            let Some((name, attribute)) = input.rsplit_once(" with ") else {
+                engine.connection_broker.send_player_message(
+                    player,
+                    "Enchant what with what? Like 'enchant iron sword with strength'".to_string(),
+                );
+                return;
+            };
+            let attribute = attribute.trim().to_lowercase();
+
+            let character = engine.world.player_character(player);
+            let essence = character.inventory.get(ESSENCE).map(|e| e.count).unwrap_or_default();
+            let known_attribute = character.attributes.named().iter().any(|(n, _)| *n == attribute);
+            let who = character.name.clone();
+            let Some(item) = held_equipment(character, name.trim()) else {
+                let suggestion = fuzzy::did_you_mean(name, equipment_names(character));
+                engine.connection_broker.send_player_message(
+                    player,
+                    format!("You don't have any equipment called {name}{suggestion}"),
+                );
+                return;
+            };
+
+            let cost = item.enchant_cost();
+            let refusal = if !known_attribute {
+                Some(format!("{attribute} isn't something you can enchant for"))
+            } else if item.enchantments.len() >= MAX_ENCHANTMENTS {
+                Some(format!("The {} can't hold any more enchantments", item.name()))
+            } else if essence < cost {
+                Some(format!("You need {cost} {ESSENCE} to enchant the {}", item.name()))
+            } else {
+                None
+            };
+            if let Some(refusal) = refusal {
+                engine.connection_broker.send_player_message(player, refusal);
+                return;
+            }
+
+            let old_name = item.name();
+            let enchanted = if rand::thread_rng().gen_bool(item.enchant_failure_chance()) {
+                None
+            } else {
+                item.enchant(&attribute);
+                Some(item.clone())
+            };
+            character.inventory.remove(ESSENCE, cost);
+
+            let msg = match enchanted {
+                Some(item) => styled(
+                    Style::Good,
+                    format!(
+                        "The {old_name} glows as the enchantment takes hold!\n\n{}",
+                        item.describe(engine.world.items.get(&item.template))
+                    ),
+                ),
+                None => styled(
+                    Style::Warn,
+                    format!("The essence fizzles away and the {old_name} is unchanged"),
+                ),
+            };
+            engine
+                .world
+                .record_transaction(&who, ESSENCE, -i64::from(cost), "enchanting");
+
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+    .takes("enchanting", config::get().enchant_seconds)
+}
+
+pub fn disenchant_command() -> Command {
+    Command::new(
+        "disenchant",
+        &[],
+        "Breaks down an enchanted item you're carrying into arcane essence, destroying it",
+        Box::new(|engine, player, args| {
+            let name = args.joined();
+            let held = engine.world.player_character(player).holdings();
+            let character = engine.world.player_character(player);
+            let enchanted = character
+                .inventory
+                .find_instance(&name)
+                .is_some_and(|i| !i.enchantments.is_empty());
+
+            let item = if enchanted {
+                character.inventory.take_instance(&name)
+            } else {
+                None
+            };
+            let msg = match item {
+                Some(item) => {
+                    let essence = item.essence_value();
+                    character.inventory.add(ESSENCE, essence);
+                    format!(
+                        "The {} crumbles to dust, leaving {essence} {ESSENCE}",
+                        item.name()
+                    )
+                }
+                None => format!(
+                    "You don't have anything magical called {name}{}",
+                    fuzzy::did_you_mean(&name, equipment_names(character))
+                ),
+            };
+            record_changes(engine, player, held, "disenchanting");
+
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+}
+
+pub fn engrave_command() -> Command {
+    Command::new(
+        "engrave",
+        &[],
+        "Gives a piece of equipment you're carrying a name of its own, like 'engrave iron sword as Nightfall'. Engrave it as nothing to scratch the name off",
+        Box::new(|engine, player, args| {
+            let input = args.rest();
+            let Some((name, new_name)) = input.rsplit_once(" as ") else {
+                engine.connection_broker.send_player_message(
+                    player,
+                    "Engrave what as what? Like 'engrave iron sword as Nightfall'".to_string(),
+                );
+                return;
+            };
+            let new_name = new_name.trim();
+
+            if let Err(e) = check_name(new_name) {
+                engine.connection_broker.send_player_message(player, e);
+                return;
+            }
+
+            let character = engine.world.player_character(player);
+            let msg = match held_equipment(character, name.trim()) {
+                Some(item) if new_name.eq_ignore_ascii_case("nothing") => {
+                    item.engraved = None;
+                    format!("You scratch the name off, it's just a {} again", item.name())
+                }
+                Some(item) => {
+                    let old_name = item.name();
+                    item.engraved = Some(new_name.to_string());
+                    format!("You carefully engrave \"{new_name}\" into the {old_name}")
+                }
+                None => format!(
+                    "You don't have any equipment called {name}{}",
+                    fuzzy::did_you_mean(name, equipment_names(character))
+                ),
+            };
+
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+    .takes("engraving", config::get().enchant_seconds)
+}
+
+/// Splits a trailing count off an item name, like 'torch 3'
+fn name_and_count(args: &mut Args) -> (String, Option<u32>) {
+    let mut words: Vec<_> = args.collect();
+    let count = words.last().and_then(|w| w.parse().ok());
+    if count.is_some() {
+        words.pop();
+    }
+    (words.join(" "), count)
+}
+
+/// The names of everything in the inventory picked by a selector for several
+/// things. 'all' leaves out anything whose template `skip` turns down, naming
+/// it outright with 'all.<name>' still picks it.
+fn selected_names(
+    inventory: &Inventory,
+    selector: &Selector,
+    skip: impl Fn(&str) -> bool,
+) -> Vec<String> {
+    inventory
+        .equipment()
+        .iter()
+        .map(|i| (i.name(), i.template.clone()))
+        .chain(inventory.stacks().map(|s| (s.name.clone(), s.name.clone())))
+        .filter(|(name, template)| match selector.name() {
+            Some(wanted) => {
+                name.eq_ignore_ascii_case(wanted) || template.eq_ignore_ascii_case(wanted)
+            }
+            None => !skip(template),
+        })
+        .map(|(name, _)| name)
+        .collect()
+}
+
+pub fn take_command() -> Command {
+    Command::new(
+        "take",
+        &["get", "pickup"],
+        "Picks up something lying around, like 'take iron sword', 'take torch 2' or 'take 2.iron sword'. \
+        Use 'take all' to pick up everything that isn't flagged as junk, or 'take all.torch' for every torch",
+        Box::new(|engine, player, args| {
+            let (name, count) = name_and_count(args);
+            let world = &mut engine.world;
+            let location = world.player_character(player).location;
+            let character = world.player_characters.get_mut(&player).unwrap();
+            let place = world.places.get_mut(&location).unwrap();
+
+            let selector = Selector::parse(&name).filter(Selector::is_many);
+            let taken: Vec<_> = match &selector {
+                Some(selector) => {
+                    let names = selected_names(&place.ground, selector, |template| {
+                        character.junk.contains(template)
+                    });
+                    names
+                        .iter()
+                        .filter_map(|n| place.ground.give(&mut character.inventory, n, None))
+                        .collect()
+                }
+                None => place
+                    .ground
+                    .give(&mut character.inventory, &name, count)
+                    .into_iter()
+                    .collect(),
+            };
+
+            if taken.is_empty() {
+                let msg = match selector.as_ref().map(Selector::name) {
+                    Some(None) => "There's nothing here worth taking".to_string(),
+                    Some(Some(name)) => format!(
+                        "There isn't any {name} here{}",
+                        fuzzy::did_you_mean(name, place.ground.names())
+                    ),
+                    None => format!(
+                        "There isn't enough {name} here{}",
+                        fuzzy::did_you_mean(&name, place.ground.names())
+                    ),
+                };
+                engine.connection_broker.send_player_message(player, msg);
+                return;
+            }
+
+            let name = character.name.clone();
+            for item in &taken {
+                engine
+                    .hooks
+                    .emit(Event::ItemPickedUp(player, location, item.clone()));
+            }
+            let taken = taken.join(", ");
+            engine
+                .connection_broker
+                .send_player_message(player, format!("You pick up {taken}"));
+            engine.connection_broker.broadcast_to_location(
+                &engine.world,
+                location,
+                format!("{name} picks up {taken}"),
+                Some(player),
+            );
+        }),
+    )
+}
+
+pub fn drop_command() -> Command {
+    Command::new(
+        "drop",
+        &[],
+        "Leaves something you're carrying on the ground, like 'drop iron sword' or 'drop torch 2'. Drops the whole stack if no count is given. \
+        'drop all.torch' drops every torch and 'drop all' everything but your gold",
+        Box::new(|engine, player, args| {
+            let (name, count) = name_and_count(args);
+            let world = &mut engine.world;
+            let location = world.player_character(player).location;
+            let character = world.player_characters.get_mut(&player).unwrap();
+            let place = world.places.get_mut(&location).unwrap();
+
+            let selector = Selector::parse(&name).filter(Selector::is_many);
+            let dropped: Vec<_> = match &selector {
+                Some(selector) => {
+                    let names = selected_names(&character.inventory, selector, |t| t == GOLD);
+                    names
+                        .iter()
+                        .filter_map(|n| character.inventory.give(&mut place.ground, n, None))
+                        .collect()
+                }
+                None => character
+                    .inventory
+                    .give(&mut place.ground, &name, count)
+                    .into_iter()
+                    .collect(),
+            };
+            if dropped.is_empty() {
+                let msg = match selector.as_ref().map(Selector::name) {
+                    Some(None) => "You aren't carrying anything to drop".to_string(),
+                    Some(Some(name)) => format!("You don't have any {name}"),
+                    None => format!("You don't have enough {name}"),
+                };
+                engine.connection_broker.send_player_message(player, msg);
+                return;
+            }
+            let dropped = dropped.join(", ");
+
+            let name = character.name.clone();
+            engine
+                .connection_broker
+                .send_player_message(player, format!("You drop {dropped}"));
+            engine.connection_broker.broadcast_to_location(
+                &engine.world,
+                location,
+                format!("{name} drops {dropped}"),
+                Some(player),
+            );
+        }),
+    )
+}
+
+pub fn junk_command() -> Command {
+    Command::new(
+        "junk",
+        &[],
+        "Flags or unflags something you're carrying as junk, like 'junk bone dust'. Junk is sold off all at once with 'sell junk'. Run alone to list your junk",
+        Box::new(|engine, player, args| {
+            let name = args.joined();
+            let character = engine.world.player_character(player);
+
+            if name.is_empty() {
+                let res = if character.junk.is_empty() {
+                    "You haven't flagged anything as junk".to_string()
+                } else {
+                    format!(
+                        "You've flagged as junk\n\n{}",
+                        character.junk.iter().cloned().collect::<Vec<_>>().join("\n")
+                    )
+                };
+                engine.connection_broker.send_player_message(player, res);
+                return;
+            }
+
+            let flagged = character
+                .junk
+                .iter()
+                .find(|j| j.eq_ignore_ascii_case(&name))
+                .cloned();
+            let carried = character
+                .inventory
+                .names()
+                .find(|n| n.eq_ignore_ascii_case(&name))
+                .map(str::to_string);
+
+            let msg = match (flagged, carried) {
+                (Some(flagged), _) => {
+                    character.junk.remove(&flagged);
+                    format!("{flagged} is no longer junk")
+                }
+                (None, Some(name)) if name == GOLD => "Gold is never junk".to_string(),
+                (None, Some(name)) => {
+                    let msg = format!("{name} is now junk");
+                    character.junk.insert(name);
+                    msg
+                }
+                (None, None) => format!("You aren't carrying anything called {name}"),
+            };
+
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+}
+
+pub fn sell_command() -> Command {
+    Command::new(
+        "sell",
+        &[],
+        "Sells something you're carrying to a vendor, like 'sell bone dust' or 'sell bone dust 3'. Use 'sell junk' to sell everything you've flagged as junk, apart from anything enchanted or engraved",
+        Box::new(|engine, player, args| {
+            let (name, count) = name_and_count(args);
+            let location = engine.world.player_character(player).location;
+            if !engine.world.places[&location].tags.contains(VENDOR_TAG) {
+                engine.connection_broker.send_player_message(
+                    player,
+                    "There's nobody here to sell to".to_string(),
+                );
+                return;
+            }
+
+            let held = engine.world.player_character(player).holdings();
+            let character = engine.world.player_character(player);
+            let junk = character.junk.clone();
+            let (sold, value) = if name.eq_ignore_ascii_case("junk") {
+                character.inventory.sell(|n| junk.contains(n))
+            } else if name.eq_ignore_ascii_case(GOLD) {
+                (Vec::new(), 0)
+            } else {
+                character
+                    .inventory
+                    .sell_named(&name, count)
+                    .unwrap_or_default()
+            };
+
+            let msg = if sold.is_empty() {
+                format!(
+                    "You don't have any {name} to sell{}",
+                    fuzzy::did_you_mean(&name, character.inventory.names())
+                )
+            } else {
+                character.inventory.add(GOLD, value);
+                format!(
+                    "You sell {} for {value} {GOLD}{}",
+                    sold.join(", "),
+                    if value == 1 { "" } else { "s" }
+                )
+            };
+            record_changes(engine, player, held, "selling to a vendor");
+
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+}
+
+pub fn haggle_command() -> Command {
+    Command::new(
+        "haggle",
+        &["barter"],
+        "Tries to talk a vendor into paying more for a piece of equipment, like 'haggle iron sword, it slew the Pale Knight'. Sharp wits and a famous name help",
+        Box::new(|engine, player, args| {
+            let input = args.rest();
+            let (name, pitch) = input.split_once(',').unwrap_or((input, ""));
+            let (name, pitch) = (name.trim(), pitch.trim());
+
+            let location = engine.world.player_character(player).location;
+            let place = &engine.world.places[&location];
+            if !place.tags.contains(VENDOR_TAG) {
+                engine.connection_broker.send_player_message(
+                    player,
+                    "There's nobody here to haggle with".to_string(),
+                );
+                return;
+            }
+            let shopkeeper = place.keeper().unwrap_or_default();
+
+            let current_tick = engine.world.current_tick;
+            let reputation = engine
+                .world
+                .discoveries_by(&engine.world.player_characters[&player].name);
+            let character = engine.world.player_character(player);
+            let standing = character.standing(&shopkeeper);
+            if standing == Standing::Hostile {
+                engine.connection_broker.send_player_message(
+                    player,
+                    format!("{shopkeeper} won't do business with the likes of you"),
+                );
+                return;
+            }
+            if character.next_haggle_tick > current_tick {
+                engine.connection_broker.send_player_message(
+                    player,
+                    "The vendor's tired of haggling with you, give them a moment".to_string(),
+                );
+                return;
+            }
+            let Some(item) = character.inventory.find_instance(name) else {
+                let suggestion = fuzzy::did_you_mean(name, equipment_names(character));
+                engine.connection_broker.send_player_message(
+                    player,
+                    format!("You don't have any equipment called {name}{suggestion}"),
+                );
+                return;
+            };
+
+            let haggle = Haggle::new(
+                shopkeeper,
+                item.name(),
+                item.id,
+                pitch.to_string(),
+                item.value(),
+                standing,
+                character.effective_attributes().intelligence,
+                reputation,
+                &mut rand::thread_rng(),
+            );
+            character.offer = None;
+            character.next_haggle_tick =
+                current_tick + config::get().seconds_to_ticks(config::get().haggle_seconds);
+
+            engine.connection_broker.send_player_message(
+                player,
+                format!("You start haggling over the {}...", haggle.item),
+            );
+            engine::redact_player_names(engine);
+            engine
+                .gen_handle
+                .request_generate(GenerationReq::Haggle(player, haggle));
+        }),
+    )
+}
+
+pub fn accept_command() -> Command {
+    Command::new(
+        "accept",
+        &[],
+        "Accepts the price a vendor offered you while haggling",
+        Box::new(|engine, player, _| {
+            let location = engine.world.player_character(player).location;
+            let at_vendor = engine.world.places[&location].tags.contains(VENDOR_TAG);
+            let held = engine.world.player_character(player).holdings();
+            let character = engine.world.player_character(player);
+
+            let msg = match character.offer.take() {
+                Some(offer) if at_vendor => match character.inventory.take_by_id(offer.item) {
+                    Some(item) => {
+                        character.inventory.add(GOLD, offer.price);
+                        format!("You sell the {} for {} {GOLD}", item.name(), offer.price)
+                    }
+                    None => format!("You don't have the {} any more", offer.name),
+                },
+                Some(_) => "The vendor you were haggling with isn't here".to_string(),
+                None => "Nobody's made you an offer".to_string(),
+            };
+            record_changes(engine, player, held, "selling to a vendor");
+
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+}
+
+pub fn list_command() -> Command {
+    Command::new(
+        "list",
+        &["wares", "shop"],
+        "Shows what the shop here sells and for how much, 'buy <item>' to buy something",
+        Box::new(|engine, player, _| {
+            let res = list_wares(engine, player).unwrap_or_else(|e| e);
+            engine.connection_broker.send_player_message(player, res);
+        }),
+    )
+}
+
+/// The shop here and its shopkeeper, restocked if it's time
+fn shop_here(engine: &mut Engine, player: PlayerId) -> Result<(Location, String), String> {
+    let config = config::get();
+    let current_tick = engine.world.current_tick;
+    let location = engine.world.player_character(player).location;
+    let place = engine.world.places.get_mut(&location).unwrap();
+    if !place.tags.contains(SHOP_TAG) {
+        return Err("There's no shop here".to_string());
+    }
+    let keeper = place.keeper().unwrap_or_default();
+    if let Some(shop) = &mut place.shop {
+        shop.restock(
+            current_tick,
+            config.seconds_to_ticks(config.shop_restock_hours * 3600.0),
+        );
+    }
+    Ok((location, keeper))
+}
+
+pub fn list_wares(engine: &mut Engine, player: PlayerId) -> Result<String, String> {
+    let (location, keeper) = shop_here(engine, player)?;
+    let place = &engine.world.places[&location];
+    if let Some(shop) = &place.shop {
+        return Ok(shop.describe(&keeper));
+    }
+
+    let waiting = engine.pending_shops.entry(location).or_default();
+    if waiting.is_empty() {
+        let mut candidates: Vec<_> = obtainable_items(engine)
+            .into_iter()
+            .filter_map(|name| Some((name.clone(), engine.world.base_value(&name)?)))
+            .collect();
+        candidates.shuffle(&mut rand::thread_rng());
+        candidates.truncate(SHOP_CANDIDATES);
+        engine.gen_handle.request_generate(GenerationReq::ShopStock(
+            location,
+            keeper.clone(),
+            place.name.clone(),
+            place.description.clone(),
+            candidates,
+        ));
+    }
+    let waiting = engine.pending_shops.entry(location).or_default();
+    if !waiting.contains(&player) {
+        waiting.push(player);
+    }
+    Ok(format!("{keeper} starts setting out their wares..."))
+}
+
+/// Fills a shop with the wares its shopkeeper picked, at prices it can't be
+/// undercut at by selling them straight back
+pub fn open_shop(engine: &mut Engine, location: Location, wares: Vec<(String, u32)>) {
+    let config = config::get();
+    let wares = wares
+        .into_iter()
+        .filter_map(|(name, asked)| {
+            let value = engine.world.base_value(&name)?;
+            Some((name, shop::fair_price(value, asked)))
+        })
+        .collect();
+    let restocks_at =
+        engine.world.current_tick + config.seconds_to_ticks(config.shop_restock_hours * 3600.0);
+
+    let Some(place) = engine.world.places.get_mut(&location) else {
+        return;
+    };
+    place.shop.get_or_insert(Shop::new(wares, restocks_at));
+    engine.world.place_changed(location);
+}
+
+/// Buys one of something from the shop here, if it sells anything by that name
+fn buy_ware(engine: &mut Engine, player: PlayerId, name: &str) -> Option<String> {
+    let (location, keeper) = shop_here(engine, player).ok()?;
+    let ware = engine.world.places[&location]
+        .shop
+        .as_ref()?
+        .ware(name)?
+        .clone();
+    if ware.count == 0 {
+        return Some(format!(
+            "{keeper} has sold out of {}, come back later",
+            ware.name
+        ));
+    }
+
+    let is_equipment = engine.world.items.contains_key(&ware.name);
+    let mut tx = engine.world.transaction();
+    tx.place(location)
+        .ok()?
+        .shop
+        .as_mut()?
+        .sell_one(&ware.name)?;
+    let character = tx.character(player).ok()?;
+    if !character.inventory.remove(GOLD, ware.price) {
+        return Some(format!(
+            "The {} costs {} {GOLD}s, more than you have",
+            ware.name, ware.price
+        ));
+    }
+    if is_equipment {
+        character
+            .inventory
+            .add_instance(ItemInstance::new(&ware.name, Quality::Common));
+    } else {
+        character.inventory.add(&ware.name, 1);
+    }
+    let who = character.name.clone();
+    let why = "buying from a shop";
+    tx.record(&who, GOLD, -i64::from(ware.price), why);
+    tx.record(&who, &ware.name, 1, why);
+    tx.commit();
+
+    Some(format!(
+        "You buy the {} from {keeper} for {} {GOLD}s",
+        ware.name, ware.price
+    ))
+}
+
+/// Items players could get hold of for an NPC who asks for them
+fn obtainable_items(engine: &Engine) -> Vec<String> {
+    engine
+        .world
+        .items
+        .keys()
+        .cloned()
+        .chain(crafting::INGREDIENTS.iter().map(|i| i.name.to_string()))
+        .collect()
+}
+
+/// Tells a player the next chapter of an NPC's storyline they're close enough to hear,
+/// moving them on to the one after unless it asks them to bring something first
+pub fn tell_story(engine: &mut Engine, player: PlayerId, npc: &str) {
+    let Some(story) = engine.world.storylines.get(npc).cloned() else {
+        return;
+    };
+    let character = engine.world.player_character(player);
+    let stage = character.stories.get(npc).copied().unwrap_or_default();
+
+    let Some(chapter) = story.stages.get(stage) else {
+        engine.connection_broker.send_player_message(
+            player,
+            format!("{npc} has already told you all of {}", story.title),
+        );
+        return;
+    };
+    if character.standing(npc) < story.required_standing(stage) {
+        engine.connection_broker.send_player_message(
+            player,
+            format!(
+                "{npc} isn't ready to tell you any more of {} yet",
+                story.title
+            ),
+        );
+        return;
+    }
+
+    let mut msg = format!(
+        "{} ({}/{})\n{npc} says\n\"{}\"",
+        styled(Style::Title, &story.title),
+        stage + 1,
+        story.stages.len(),
+        chapter.text
+    );
+    match &chapter.wants {
+        Some(item) => msg.push_str(&format!("\nBring them a {item} to hear more")),
+        None if stage + 1 < story.stages.len() => {
+            character.stories.insert(npc.to_string(), stage + 1);
+        }
+        None => {
+            character.stories.insert(npc.to_string(), stage + 1);
+            character.change_relationship(npc, relationship::ERRAND_SCORE);
+            let mut keepsake = ItemInstance::new(&story.reward.name, Quality::Masterwork);
+            keepsake.engraved = Some(story.reward.name.clone());
+            msg.push_str(&styled(
+                Style::Good,
+                format!("\n{npc} gives you the {}", keepsake.name()),
+            ));
+            character.inventory.add_instance(keepsake);
+            let who = character.name.clone();
+            engine
+                .world
+                .record_transaction(&who, &story.reward.name, 1, "a keepsake");
+            engine.world.register_item(story.reward);
+        }
+    }
+
+    engine.connection_broker.send_player_message(player, msg);
+}
+
+/// The named NPC here a player means, matching the start of their name
+fn npc_here(engine: &Engine, player: PlayerId, name: &str) -> Option<String> {
+    if name.is_empty() {
+        return None;
+    }
+    let location = engine.world.player_characters[&player].location;
+    engine.world.places[&location]
+        .keeper()
+        .filter(|k| k.to_lowercase().starts_with(&name.to_lowercase()))
+}
+
+pub fn give_command() -> Command {
+    Command::new(
+        "give",
+        &["gift"],
+        "Gives something you're carrying to someone here, like 'give honeycomb to marta', or hands it to another player, like 'give ada torch 2'. Gifts and errands won people over",
+        Box::new(|engine, player, args| {
+            let input = args.joined();
+            if let Some((target, _, item)) =
+                player_named(engine, &input).filter(|(_, _, item)| !item.is_empty())
+            {
+                let res = give_to_player(engine, player, target, item).unwrap_or_else(|e| e);
+                engine.connection_broker.send_player_message(player, res);
+                return;
+            }
+            let Some((item, to)) = input.rsplit_once(" to ") else {
+                engine.connection_broker.send_player_message(
+                    player,
+                    "Give what to who? Like 'give honeycomb to marta'".to_string(),
+                );
+                return;
+            };
+            let Some(npc) = npc_here(engine, player, to.trim()) else {
+                if let Some((target, _, "")) = player_named(engine, to.trim()) {
+                    let res = give_to_player(engine, player, target, item.trim())
+                        .unwrap_or_else(|e| e);
+                    engine.connection_broker.send_player_message(player, res);
+                    return;
+                }
+                let location = engine.world.player_characters[&player].location;
+                let keeper = engine.world.places[&location].keeper();
+                engine.connection_broker.send_player_message(
+                    player,
+                    format!(
+                        "There's nobody called {} here{}",
+                        to.trim(),
+                        fuzzy::did_you_mean(to.trim(), keeper)
+                    ),
+                );
+                return;
+            };
+
+            let current_tick = engine.world.current_tick;
+            let giver = &engine.world.player_characters[&player];
+            let stage = giver.stories.get(&npc).copied().unwrap_or_default();
+            let story_wants = engine
+                .world
+                .storylines
+                .get(&npc)
+                .filter(|story| giver.standing(&npc) >= story.required_standing(stage))
+                .and_then(|story| story.stages.get(stage))
+                .and_then(|chapter| chapter.wants.clone());
+
+            let held = engine.world.player_character(player).holdings();
+            let character = engine.world.player_character(player);
+            let item = item.trim();
+            let stack = character
+                .inventory
+                .stacks()
+                .find(|s| s.name.eq_ignore_ascii_case(item))
+                .map(|s| s.name.clone());
+            let gift = match character.inventory.take_instance(item) {
+                Some(instance) => Some((
+                    instance.template.clone(),
+                    instance.name(),
+                    instance.value(),
+                )),
+                None => stack
+                    .filter(|name| character.inventory.remove(name, 1))
+                    .map(|name| (name.clone(), name, COMMODITY_VALUE)),
+            };
+            let Some((template, name, value)) = gift else {
+                engine
+                    .connection_broker
+                    .send_player_message(player, format!("You aren't carrying any {item}"));
+                return;
+            };
+
+            let before = character.standing(&npc);
+            let errand = character.errands.get(&npc).cloned();
+            let fulfils_story = story_wants.is_some_and(|w| w.eq_ignore_ascii_case(&template));
+            let mut msg = if fulfils_story {
+                character.stories.insert(npc.clone(), stage + 1);
+                character.change_relationship(&npc, relationship::gift_score(value));
+                format!("You give the {name} to {npc}, who holds it close for a long moment")
+            } else if errand.is_some_and(|e| e.eq_ignore_ascii_case(&template)) {
+                character.errands.remove(&npc);
+                character.change_relationship(&npc, relationship::ERRAND_SCORE);
+                character.inventory.add(GOLD, relationship::ERRAND_REWARD);
+                format!(
+                    "You give the {name} to {npc}, just what they asked for! They press {} {GOLD}s into your hand",
+                    relationship::ERRAND_REWARD
+                )
+            } else if character.next_gift_ticks.get(&npc).is_some_and(|t| *t > current_tick) {
+                format!("You give the {name} to {npc}, who thanks you, though you've been generous with them a lot lately")
+            } else {
+                character.change_relationship(&npc, relationship::gift_score(value));
+                character.next_gift_ticks.insert(
+                    npc.clone(),
+                    current_tick + config::get().seconds_to_ticks(config::get().gift_seconds),
+                );
+                format!("You give the {name} to {npc}, who thanks you for it")
+            };
+            let after = character.standing(&npc);
+            if after > before {
+                msg.push_str(&styled(
+                    Style::Good,
+                    format!("\n{npc} now thinks of you as a {}", after.name()),
+                ));
+            }
+            record_changes(engine, player, held, &format!("a gift for {npc}"));
+
+            engine.connection_broker.send_player_message(player, msg);
+            if fulfils_story {
+                tell_story(engine, player, &npc);
+            }
+        }),
+    )
+}
+
+/// Hands another player here one of something, or as many as the item ends with,
+/// like 'torch 2'
+fn give_to_player(
+    engine: &mut Engine,
+    player: PlayerId,
+    target: PlayerId,
+    item: &str,
+) -> Result<String, String> {
+    let (name, count) = name_and_count(&mut Args::new(item));
+    let location = engine.world.player_characters[&player].location;
+    let giver = engine.world.player_characters[&player].name.clone();
+    let other = &engine.world.player_characters[&target];
+    if target == player {
+        return Err("You pass it from one hand to the other".to_string());
+    }
+    if other.location != location || !engine.connection_broker.is_connected(target) {
+        return Err(format!("{} isn't here", other.name));
+    }
+    if other.ghost.is_some() {
+        return Err(format!("Your hand passes straight through {}", other.name));
+    }
+    let receiver = other.name.clone();
+
+    let mut tx = engine.world.transaction();
+    let mut handed = Inventory::default();
+    let given = tx
+        .character(player)?
+        .inventory
+        .give(&mut handed, &name, Some(count.unwrap_or(1)))
+        .ok_or_else(|| format!("You don't have enough {name}"))?;
+    tx.character(target)?.inventory.append(handed);
+    tx.commit();
+
+    engine
+        .connection_broker
+        .send_player_message(target, format!("{giver} gives you {given}"));
+    engine.connection_broker.broadcast_to_room(
+        &engine.world,
+        location,
+        format!("{giver} gives {given} to {receiver}"),
+        &[player, target],
+    );
+    Ok(format!("You give {given} to {receiver}"))
+}
+
+pub fn talk_command() -> Command {
+    Command::new(
+        "talk",
+        &["chat"],
+        "Talks to the shopkeeper here about something, like 'talk rumours', or says something to them, like 'talk Hilde, what's past the gate?'. The better they know you the more they'll talk about",
+        Box::new(|engine, player, args| {
+            let input = args.rest();
+            let location = engine.world.player_characters[&player].location;
+            let place = &engine.world.places[&location];
+            let Some(npc) = place.keeper() else {
+                engine.connection_broker.send_player_message(
+                    player,
+                    "There's nobody here to talk to".to_string(),
+                );
+                return;
+            };
+            let topic_name = addressed_to(&npc, input).to_string();
+            let place_name = place.name.clone();
+            let description = place.description.clone();
+            let rumour = engine
+                .world
+                .unexplored()
+                .map(|p| (p.name.clone(), p.location))
+                .collect::<Vec<_>>()
+                .choose(&mut rand::thread_rng())
+                .cloned();
+            let witnessed = engine
+                .world
+                .recent_events(location, history::RECALLED_EVENTS);
+            let heard = rumour.as_ref().map_or(Vec::new(), |(_, rumoured)| {
+                engine
+                    .world
+                    .recent_events(*rumoured, history::RECALLED_EVENTS)
+            });
+            let obtainable = obtainable_items(engine);
+            let wanted = obtainable.choose(&mut rand::thread_rng()).cloned();
+
+            let character = engine.world.player_character(player);
+            let standing = character.standing(&npc);
+            let topics = standing.topics();
+            // Anything that isn't a topic is something said to them
+            let (topic, said) = match Topic::find(&topic_name) {
+                Some(topic) => (Some(topic), None),
+                None if topic_name.is_empty() => (Some(Topic::Greeting), None),
+                None => (Some(Topic::Greeting), Some(topic_name)),
+            };
+
+            let topic = match topic {
+                _ if topics.is_empty() => {
+                    engine.connection_broker.send_player_message(
+                        player,
+                        format!("{npc} turns their back on you"),
+                    );
+                    return;
+                }
+                Some(topic) if topics.contains(&topic) => topic,
+                _ => {
+                    let choices = topics
+                        .iter()
+                        .map(|t| {
+                            let name = t.name();
+                            let choice: ChoiceFn = Box::new(move |engine, player| {
+                                let talk = get_command_list().iter().find(|c| c.name == "talk");
+                                if let Some(talk) = talk {
+                                    (talk.cmd_fn)(engine, player, &mut Args::new(name));
+                                }
+                            });
+                            (name.to_string(), choice)
+                        })
+                        .collect();
+                    let title = format!(
+                        "{npc} doesn't know you well enough to talk about that. What would you like to talk about?"
+                    );
+                    prompt::ask(engine, player, Prompt::menu(&title, choices));
+                    return;
+                }
+            };
+
+            let subject = match topic {
+                Topic::Story => {
+                    if engine.world.storylines.contains_key(&npc) {
+                        tell_story(engine, player, &npc);
+                    } else {
+                        let waiting = engine.pending_stories.entry(npc.clone()).or_default();
+                        if waiting.is_empty() {
+                            engine.gen_handle.request_generate(GenerationReq::Storyline(
+                                npc.clone(),
+                                place_name,
+                                obtainable,
+                            ));
+                        }
+                        waiting.push(player);
+                        engine.connection_broker.send_player_message(
+                            player,
+                            format!("{npc} pauses, wondering where to begin..."),
+                        );
+                    }
+                    return;
+                }
+                Topic::Greeting => None,
+                Topic::Rumours => rumour.map(|(name, _)| name),
+                Topic::Errand => match character.errands.get(&npc) {
+                    Some(item) => Some(item.clone()),
+                    None => wanted.inspect(|item| {
+                        character.errands.insert(npc.clone(), item.clone());
+                    }),
+                },
+            };
+
+            let conversation = character
+                .conversation
+                .take()
+                .filter(|c| c.npc == npc)
+                .unwrap_or_else(|| Conversation::with(&npc));
+            let recent = conversation.lines();
+            let conversation = character.conversation.insert(conversation);
+            if let Some(said) = &said {
+                conversation.add(&character.name, said);
+            }
+
+            let dialogue = Dialogue {
+                score: character.relationships.get(&npc).copied().unwrap_or_default(),
+                npc,
+                place: place_name,
+                standing,
+                topic,
+                subject,
+                witnessed,
+                heard: if topic == Topic::Rumours { heard } else { Vec::new() },
+                description,
+                said,
+                recent,
+            };
+            engine::redact_player_names(engine);
+            engine
+                .gen_handle
+                .request_generate(GenerationReq::Dialogue(player, dialogue));
+        }),
+    )
+}
+
+/// What's left of what a player typed once any name they addressed the NPC by is
+/// taken off, so 'talk Hilde, rumours' and 'talk rumours' mean the same
+fn addressed_to<'a>(npc: &str, input: &'a str) -> &'a str {
+    let first_name = npc.split(" of ").next().unwrap_or(npc);
+    let input = input.trim();
+    let input = input
+        .get(..3)
+        .filter(|t| t.eq_ignore_ascii_case("to "))
+        .map_or(input, |_| &input[3..]);
+    [npc, first_name]
+        .into_iter()
+        .find_map(|name| {
+            let head = input.get(..name.len())?;
+            let rest = &input[name.len()..];
+            (head.eq_ignore_ascii_case(name) && !rest.starts_with(char::is_alphanumeric))
+                .then_some(rest)
+        })
+        .unwrap_or(input)
+        .trim_start_matches([',', ':'])
+        .trim()
+}
+
+pub fn stall_command() -> Command {
+    Command::new(
+        "stall",
+        &["market"],
+        "Shows the market stalls here. Use 'stall rent' to rent one, 'stall stock <item> [count] for <price>' to put something up for sale, leaving off the price to restock at what you asked before, 'stall collect' to take your earnings or 'stall close' to pack it all up",
+        Box::new(|engine, player, args| {
+            let subcommand = args.next().unwrap_or_default().to_lowercase();
+            let rest = args.joined();
+
+            let res = match subcommand.as_str() {
+                "" => list_stalls(engine, player),
+                "rent" => rent_stall(engine, player),
+                "stock" => stock_stall(engine, player, &rest),
+                "collect" => collect_stall(engine, player, false),
+                "close" => collect_stall(engine, player, true),
+                _ => Err("Try 'stall rent', 'stall stock', 'stall collect' or 'stall close'".to_string()),
+            };
+
+            engine
+                .connection_broker
+                .send_player_message(player, res.unwrap_or_else(|e| e));
+        }),
+    )
+}
+
+fn list_stalls(engine: &mut Engine, player: PlayerId) -> Result<String, String> {
+    let location = engine.world.player_character(player).location;
+    let place = &engine.world.places[&location];
+    if !place.tags.contains(MARKET_TAG) {
+        return match engine.world.stall_location(player) {
+            Some(l) => Ok(format!("Your stall is in {}", engine.world.places[&l].name)),
+            None => Err("There's no market here".to_string()),
+        };
+    }
+
+    if place.stalls.is_empty() {
+        return Ok(format!(
+            "Nobody's renting a stall here, 'stall rent' to take one for {} {GOLD}s",
+            config::get().stall_rent
+        ));
+    }
+    Ok(place
+        .stalls
+        .iter()
+        .map(|s| s.describe(engine.world.current_tick))
+        .collect::<Vec<_>>()
+        .join("\n\n"))
+}
+
+fn rent_stall(engine: &mut Engine, player: PlayerId) -> Result<String, String> {
+    let config = config::get();
+    let current_tick = engine.world.current_tick;
+    let location = engine.world.player_character(player).location;
+    let owned = engine.world.stall_location(player);
+    if owned.is_some_and(|l| l != location) {
+        return Err("You already have a stall in another market".to_string());
+    }
+
+    let place = &engine.world.places[&location];
+    if !place.tags.contains(MARKET_TAG) {
+        return Err("There's no market here".to_string());
+    }
+    let open = place.stalls.iter().filter(|s| s.open(current_tick)).count();
+    if owned.is_none() && open >= stall::MAX_STALLS {
+        return Err("Every stall here is taken".to_string());
+    }
+    if owned.is_none() && place.stalls.len() >= stall::MAX_STALLS {
+        clear_lapsed_stalls(engine, location);
+    }
+
+    let character = engine.world.player_characters.get_mut(&player).unwrap();
+    if !character.inventory.remove(GOLD, config.stall_rent) {
+        return Err(format!(
+            "Renting a stall costs {} {GOLD}s",
+            config.stall_rent
+        ));
+    }
+    let name = character.name.clone();
+    engine
+        .world
+        .record_transaction(&name, GOLD, -i64::from(config.stall_rent), "stall rent");
+
+    let rent = config.seconds_to_ticks(config.stall_rent_hours * 3600.0);
+    let place = engine.world.places.get_mut(&location).unwrap();
+    match place.stalls.iter_mut().find(|s| s.owner == player) {
+        Some(stall) => {
+            stall.rented_until = stall.rented_until.max(current_tick) + rent;
+            Ok(format!(
+                "You pay another {} {GOLD}s to keep your stall",
+                config.stall_rent
+            ))
+        }
+        None => {
+            place
+                .stalls
+                .push(Stall::new(player, name, current_tick + rent));
+            Ok(format!(
+                "You rent a stall for {} {GOLD}s, 'stall stock' to put things up for sale",
+                config.stall_rent
+            ))
+        }
+    }
+}
+
+/// Packs up stalls here whose rent has run out to make room, mailing what was
+/// left in them back to their owners
+fn clear_lapsed_stalls(engine: &mut Engine, location: Location) {
+    let current_tick = engine.world.current_tick;
+    let place = engine.world.places.get_mut(&location).unwrap();
+    let (lapsed, open) = std::mem::take(&mut place.stalls)
+        .into_iter()
+        .partition::<Vec<_>, _>(|s| !s.open(current_tick));
+    place.stalls = open;
+    let market = place.name.clone();
+
+    for mut stall in lapsed {
+        let Some(owner) = engine.world.player_characters.get_mut(&stall.owner) else {
+            continue;
+        };
+        owner.mailbox.add(GOLD, std::mem::take(&mut stall.proceeds));
+        owner.mailbox.append(std::mem::take(&mut stall.stock));
+        owner.letters.push(format!(
+            "The rent ran out on your stall in {market}, so it was packed up and everything in it sent to you"
+        ));
+        engine.world.player_changed(stall.owner);
+    }
+    engine.world.place_changed(location);
+}
+
+fn stock_stall(engine: &mut Engine, player: PlayerId, args: &str) -> Result<String, String> {
+    let (item, price) = match args.rsplit_once(" for ") {
+        Some((item, price)) => match price.trim().parse::<u32>() {
+            Ok(0) => return Err("Nobody sets up a stall to give things away".to_string()),
+            Ok(price) => (item, Some(price)),
+            Err(_) => {
+                return Err("Stock your stall like 'stall stock honeycomb 3 for 2'".to_string())
+            }
+        },
+        None => (args, None),
+    };
+    let (name, count) = name_and_count(&mut Args::new(item));
+    if name.eq_ignore_ascii_case(GOLD) {
+        return Err("You can't sell gold".to_string());
+    }
+
+    let location = engine.world.player_character(player).location;
+    let mut tx = engine.world.transaction();
+    tx.stall(location, player)
+        .map_err(|_| "You don't have a stall here".to_string())?;
+    let character = tx.character(player)?;
+    let listed = match character.inventory.find_instance(&name) {
+        Some(instance) => instance.name(),
+        None => character
+            .inventory
+            .stacks()
+            .find(|s| s.name.eq_ignore_ascii_case(&name))
+            .map(|s| s.name.clone())
+            .ok_or_else(|| format!("You aren't carrying any {name}"))?,
+    };
+    let mut stock = Inventory::default();
+    let Some(stocked) = character.inventory.give(&mut stock, &listed, count) else {
+        return Err(format!("You don't have that many {listed}"));
+    };
+    let stall = tx.stall(location, player)?;
+    let before = stall.prices.get(&listed).copied();
+    let Some(price) = price.or(before) else {
+        return Err(format!(
+            "Say what to ask for it, like 'stall stock {name} for 2'"
+        ));
+    };
+    stall.stock.append(stock);
+    stall.prices.insert(listed, price);
+    tx.commit();
+
+    Ok(match before {
+        Some(before) if before != price => format!(
+            "You put {stocked} up for sale, everything like it now going for {price} {GOLD}s each instead of {before}"
+        ),
+        _ => format!("You put {stocked} up for sale at {price} {GOLD}s each"),
+    })
+}
+
+fn collect_stall(engine: &mut Engine, player: PlayerId, close: bool) -> Result<String, String> {
+    let location = engine.world.player_character(player).location;
+    let mut tx = engine.world.transaction();
+    let stall = tx
+        .stall(location, player)
+        .map_err(|_| "You don't have a stall here".to_string())?;
+    let proceeds = std::mem::take(&mut stall.proceeds);
+    stall.ledger.clear();
+    let stock = if close {
+        std::mem::take(&mut stall.stock)
+    } else {
+        Inventory::default()
+    };
+    if close {
+        tx.place(location)?.stalls.retain(|s| s.owner != player);
+    }
+
+    let character = tx.character(player)?;
+    character.inventory.add(GOLD, proceeds);
+    character.inventory.append(stock);
+    tx.commit();
+
+    Ok(if close {
+        format!("You pack up your stall, taking back everything unsold and {proceeds} {GOLD}s")
+    } else {
+        format!("You collect {proceeds} {GOLD}s from your stall")
+    })
+}
+
+pub fn buy_command() -> Command {
+    Command::new(
+        "buy",
+        &[],
+        "Buys something from the shop or a market stall here, like 'buy iron sword' or 'buy honeycomb from ada'",
+        Box::new(|engine, player, args| {
+            let input = args.joined();
+            let (name, seller) = match input.rsplit_once(" from ") {
+                Some((name, seller)) => (name.trim(), Some(seller.trim())),
+                None => (input.trim(), None),
+            };
+
+            if let Some(msg) = seller.is_none().then(|| buy_ware(engine, player, name)).flatten() {
+                engine.connection_broker.send_player_message(player, msg);
+                return;
+            }
+
+            let res = buy_from_stall(engine, player, name, seller).unwrap_or_else(|e| e);
+            engine.connection_broker.send_player_message(player, res);
+        }),
+    )
+}
+
+fn buy_from_stall(
+    engine: &mut Engine,
+    player: PlayerId,
+    name: &str,
+    seller: Option<&str>,
+) -> Result<String, String> {
+    let current_tick = engine.world.current_tick;
+    let location = engine.world.player_character(player).location;
+    let owner = engine.world.places[&location]
+        .stalls
+        .iter()
+        .find(|s| {
+            s.open(current_tick)
+                && s.owner != player
+                && seller.is_none_or(|n| s.owner_name.eq_ignore_ascii_case(n))
+                && s.listing(name).is_some()
+        })
+        .map(|s| s.owner)
+        .ok_or_else(|| format!("Nobody here is selling any {name}"))?;
+
+    let mut tx = engine.world.transaction();
+    let stall = tx.stall(location, owner)?;
+    let mut bought = Inventory::default();
+    let (item, price) = stall
+        .sell_to(&mut bought, name)
+        .ok_or_else(|| format!("Nobody here is selling any {name}"))?;
+    let tax = stall.pay_tax(price, config::get().market_tax);
+    let seller_name = stall.owner_name.clone();
+    let buyer = tx.character(player)?;
+    if !buyer.inventory.remove(GOLD, price) {
+        return Err(format!(
+            "The {item} costs {price} {GOLD}s, more than you have"
+        ));
+    }
+    buyer.inventory.append(bought);
+    let buyer_name = buyer.name.clone();
+    tx.record(&seller_name, GOLD, -i64::from(tax), "market tax");
+    tx.commit();
+
+    if let Some(faction) = engine.world.faction_mut(location) {
+        faction.deposit(tax);
+    }
+    engine.connection_broker.send_player_message(
+        owner,
+        format!("{buyer_name} buys the {item} from your stall for {price} {GOLD}s"),
+    );
+    Ok(format!("You buy the {item} for {price} {GOLD}s"))
+}
+
+pub fn order_command() -> Command {
+    Command::new(
+        "order",
+        &["orders", "board"],
+        "Shows the work orders on the market board. Use 'order post <item> [count] for <price>' to ask for something at a price each, paid up front, 'order fill <number> [count]' to supply one or 'order cancel <number>' to take yours down",
+        Box::new(|engine, player, args| {
+            let subcommand = args.next().unwrap_or_default().to_lowercase();
+            let rest: Vec<_> = args.collect();
+
+            let location = engine.world.player_character(player).location;
+            let res = if !engine.world.places[&location].tags.contains(MARKET_TAG) {
+                Err("The work order board is in the market".to_string())
+            } else {
+                match subcommand.as_str() {
+                    "" | "list" => list_orders(engine),
+                    "post" => post_order(engine, player, &rest.join(" ")),
+                    "fill" => fill_order(engine, player, &rest),
+                    "cancel" => cancel_order(engine, player, &rest),
+                    _ => Err("Try 'order post', 'order fill' or 'order cancel'".to_string()),
+                }
+            };
+
+            engine
+                .connection_broker
+                .send_player_message(player, res.unwrap_or_else(|e| e));
+        }),
+    )
+}
+
+fn list_orders(engine: &mut Engine) -> Result<String, String> {
+    if engine.world.work_orders.is_empty() {
+        return Ok("The work order board is empty, 'order post' to ask for something".to_string());
+    }
+    Ok(engine
+        .world
+        .work_orders
+        .iter()
+        .map(WorkOrder::describe)
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+fn post_order(engine: &mut Engine, player: PlayerId, args: &str) -> Result<String, String> {
+    let Some((item, price)) = args
+        .rsplit_once(" for ")
+        .and_then(|(i, p)| Some((i, p.trim().parse::<u32>().ok()?)))
+    else {
+        return Err("Post an order like 'order post honeycomb 20 for 2'".to_string());
+    };
+    let (name, count) = name_and_count(&mut Args::new(item));
+    let count = count.unwrap_or(1);
+    if name.is_empty() || count == 0 || price == 0 {
+        return Err("Orders need an item, a count and a price".to_string());
+    }
+    if name.eq_ignore_ascii_case(GOLD) {
+        return Err("Nobody's going to sell you gold for gold".to_string());
+    }
+    // Use the proper name for anything artisans know how to make
+    let item = obtainable_items(engine)
+        .into_iter()
+        .find(|i| i.eq_ignore_ascii_case(&name))
+        .unwrap_or(name);
+
+    let mut tx = engine.world.transaction();
+    if tx
+        .work_orders()
+        .iter()
+        .filter(|o| o.poster == player)
+        .count()
+        >= work_order::MAX_ORDERS
+    {
+        return Err(format!(
+            "You can only have {} orders on the board at once",
+            work_order::MAX_ORDERS
+        ));
+    }
+    let character = tx.character(player)?;
+    let Some(escrow) = count.checked_mul(price) else {
+        return Err("There isn't that much gold in all the world".to_string());
+    };
+    if !character.inventory.remove(GOLD, escrow) {
+        return Err(format!(
+            "You'd need {escrow} {GOLD}s to pay for all of that up front"
+        ));
+    }
+    let poster_name = character.name.clone();
+
+    let work_orders = tx.work_orders();
+    let order = WorkOrder {
+        id: work_orders.iter().map(|o| o.id).max().unwrap_or_default() + 1,
+        poster: player,
+        poster_name,
+        item,
+        wanted: count,
+        price,
+    };
+    let msg = format!(
+        "You pin up order #{} and hand over {escrow} {GOLD}s to hold in escrow",
+        order.id
+    );
+    work_orders.push(order);
+    tx.commit();
+    Ok(msg)
+}
+
+/// Finds one of the orders on the board by the number given as the first argument
+fn find_order(engine: &Engine, args: &[&str]) -> Result<usize, String> {
+    let id = args
+        .first()
+        .and_then(|a| a.trim_start_matches('#').parse::<u32>().ok())
+        .ok_or_else(|| "Which order? Give its number, like 'order fill 3'".to_string())?;
+    engine
+        .world
+        .work_orders
+        .iter()
+        .position(|o| o.id == id)
+        .ok_or_else(|| format!("There's no order #{id} on the board"))
+}
+
+fn fill_order(engine: &mut Engine, player: PlayerId, args: &[&str]) -> Result<String, String> {
+    let idx = find_order(engine, args)?;
+    let order = engine.world.work_orders[idx].clone();
+    if order.poster == player {
+        return Err("You can't fill your own order".to_string());
+    }
+    let count = args
+        .get(1)
+        .and_then(|c| c.parse::<u32>().ok())
+        .unwrap_or(order.wanted)
+        .min(order.wanted);
+
+    let mut tx = engine.world.transaction();
+    let character = tx.character(player)?;
+    let mut parcel = Inventory::default();
+    let mut moved = 0;
+    while moved < count
+        && character
+            .inventory
+            .give(&mut parcel, &order.item, Some(1))
+            .is_some()
+    {
+        moved += 1;
+    }
+    if moved == 0 {
+        return Err(format!("You don't have any {} to give", order.item));
+    }
+
+    let (filled, payout) = tx.work_orders()[idx].fill(moved);
+    tx.work_orders().retain(|o| o.wanted > 0);
+    let character = tx.character(player)?;
+    character.inventory.add(GOLD, payout);
+    let (poster, id, item) = (order.poster, order.id, order.item);
+    let letter = format!(
+        "{} sends {item} x{filled} for work order #{id}",
+        character.name
+    );
+    tx.character(poster)
+        .map_err(|_| format!("Whoever posted order #{id} is nowhere to be found"))?
+        .send_mail(letter.clone(), parcel);
+    tx.commit();
+
+    engine.connection_broker.send_player_message(
+        poster,
+        format!("{letter}, 'mail' in any village to collect it"),
+    );
+    Ok(format!(
+        "You send {item} x{filled} off for order #{id} and are paid {payout} {GOLD}s"
+    ))
+}
+
+fn cancel_order(engine: &mut Engine, player: PlayerId, args: &[&str]) -> Result<String, String> {
+    let idx = find_order(engine, args)?;
+    if engine.world.work_orders[idx].poster != player {
+        return Err("That isn't your order to take down".to_string());
+    }
+
+    let mut tx = engine.world.transaction();
+    let order = tx.work_orders().remove(idx);
+    let refund = order.escrow();
+    tx.character(player)?.inventory.add(GOLD, refund);
+    tx.commit();
+    Ok(format!(
+        "You take down order #{} and get back the {refund} {GOLD}s held for it",
+        order.id
+    ))
+}
+
+pub fn tithe_command() -> Command {
+    Command::new(
+        "tithe",
+        &["treasury", "donate"],
+        "Shows how the village council's treasury is doing. Add an amount to donate to it, like 'tithe 10'",
+        Box::new(|engine, player, args| {
+            let amount = args.next().and_then(|a| a.parse::<u32>().ok());
+            let world = &mut engine.world;
+            let location = world.player_character(player).location;
+
+            let msg = match (world.village_of(location), amount) {
+                (None, _) => "There's no village council here".to_string(),
+                (Some(village), None) => {
+                    let name = &world.places[&village].name;
+                    let faction = world.factions.get(&village).cloned().unwrap_or_default();
+                    format!(
+                        "The council of {name} holds {} {GOLD}s, the village is {}",
+                        faction.treasury,
+                        faction.prosperity().name()
+                    )
+                }
+                (Some(_), Some(amount)) => {
+                    let character = world.player_characters.get_mut(&player).unwrap();
+                    if character.inventory.remove(GOLD, amount) {
+                        let name = character.name.clone();
+                        world.record_transaction(&name, GOLD, -i64::from(amount), "a tithe");
+                        world.faction_mut(location).unwrap().deposit(amount);
+                        format!("You give {amount} {GOLD}s to the village council")
+                    } else {
+                        format!("You don't have {amount} {GOLD}s to give")
+                    }
+                }
+            };
+
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+}
+
+pub fn mail_command() -> Command {
+    Command::new(
+        "mail",
+        &["letters"],
+        "Collects your letters and anything sent to you, in any village. \
+        'mail <player>' writes them a letter, also from a village",
+        Box::new(|engine, player, args| {
+            let to = args.joined();
+            if !to.is_empty() {
+                if let Err(e) = write_letter(engine, player, &to) {
+                    engine.connection_broker.send_player_message(player, e);
+                }
+                return;
+            }
+
+            let world = &mut engine.world;
+            let location = world.player_character(player).location;
+            let safe = world.places[&location].tags.contains(SAFE_TAG);
+            let character = world.player_characters.get_mut(&player).unwrap();
+
+            let msg = if character.letters.is_empty() && character.mailbox.is_empty() {
+                "There's no mail for you".to_string()
+            } else if !safe {
+                format!(
+                    "You have {} letters waiting in the villages",
+                    character.letters.len()
+                )
+            } else {
+                let mailbox = std::mem::take(&mut character.mailbox);
+                character.inventory.append(mailbox);
+                let mut msg = std::mem::take(&mut character.letters).join("\n");
+                msg.push_str("\nYou collect everything that was sent with them");
+                msg
+            };
+
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+}
+
+/// Opens the editor for a letter to another player, delivered when it's saved
+fn write_letter(engine: &mut Engine, player: PlayerId, to: &str) -> Result<(), String> {
+    let location = engine.world.player_character(player).location;
+    if !engine.world.places[&location].tags.contains(SAFE_TAG) {
+        return Err("Letters can only be sent from a village".to_string());
+    }
+    let (target, name, _) = player_named(engine, to)
+        .filter(|(_, _, rest)| rest.is_empty())
+        .ok_or_else(|| format!("There's nobody called {to} to write to"))?;
+    if target == player {
+        return Err("You think better of writing to yourself".to_string());
+    }
+    if !engine.world.player_characters.contains_key(&target) {
+        return Err(format!("{name} hasn't dreamt of this realm yet"));
+    }
+
+    prompt::edit(
+        engine,
+        player,
+        &format!("Write your letter to {name}"),
+        String::new(),
+        Box::new(move |engine, player, text| {
+            if text.is_empty() {
+                return Answer::Retry("There's nothing written to send".to_string());
+            }
+            let from = engine.world.player_character(player).name.clone();
+            let msg = match engine.world.player_characters.get_mut(&target) {
+                Some(character) => {
+                    character.send_mail(
+                        format!("A letter from {from}:\n{}", markup::escape(text)),
+                        Inventory::default(),
+                    );
+                    engine.world.player_changed(target);
+                    engine.connection_broker.send_player_message(
+                        target,
+                        format!("A letter from {from} is waiting for you in the villages"),
+                    );
+                    format!("Your letter's on its way to {name}")
+                }
+                None => format!("{name} can't be found to send it to"),
+            };
+            engine.connection_broker.send_player_message(player, msg);
+            Answer::Done
+        }),
+    );
+    Ok(())
+}
+
+pub fn compare_command() -> Command {
+    Command::new(
+        "compare",
+        &[],
+        "Compares a piece of equipment you're carrying with the one in your hand, like 'compare iron sword'",
+        Box::new(|engine, player, args| {
+            let name = args.joined();
+            let character = engine.world.player_character(player);
+
+            let msg = match character.inventory.find_instance(&name) {
+                Some(item) => item.compare(character.wielding.as_ref()),
+                None => format!(
+                    "You don't have any equipment called {name}{}",
+                    fuzzy::did_you_mean(&name, equipment_names(character))
+                ),
+            };
+
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+}
+
+pub fn attack_command() -> Command {
+    Command::new(
+        "attack",
+        &["kill", "k"],
+        "Attacks a creature or another adventurer in the same place as you, like 'attack cave rat' or 'attack <name>'. \
+        The fight ends when someone is knocked out, beaten creatures flee",
+        Box::new(|engine, player, args| {
+            let target_name = args.joined();
+            if target_name.is_empty() {
+                engine
+                    .connection_broker
+                    .send_player_message(player, "Attack who?".to_string());
+                return;
+            }
+
+            let accounts = engine.player_registry.blocking_read();
+            let name_of = |id: &PlayerId| {
+                accounts
+                    .get(id)
+                    .map(|p| p.username.clone())
+                    .unwrap_or_default()
+            };
+
+            let mut attacker = engine.world.player_character(player).clone();
+            let location = attacker.location;
+
+            let here: Vec<_> = engine
+                .world
+                .player_characters
+                .iter()
+                .filter(|(id, c)| {
+                    // Players who log out mid fight linger until their lockout ends
+                    let present = engine.connection_broker.is_connected(**id)
+                        || c.in_combat(engine.world.current_tick);
+                    **id != player && c.location == location && c.ghost.is_none() && present
+                })
+                .map(|(id, _)| (*id, name_of(id)))
+                .collect();
+            let target = here
+                .iter()
+                .find(|(_, name)| name.eq_ignore_ascii_case(&target_name))
+                .map(|(id, _)| *id);
+
+            let Some(target) = target else {
+                drop(accounts);
+                let creatures = engine.world.npcs.get(&location).into_iter().flatten();
+                let names: Vec<_> = creatures.map(|c| (c.id, c.name.clone())).collect();
+                let creature =
+                    entity::resolve(&target_name, names.iter().map(|(id, n)| (*id, n.as_str())));
+                let msg = match creature {
+                    Some(creature) => styled(Style::Combat, attack_creature(engine, player, creature)),
+                    None => format!(
+                        "There's nobody called {target_name} here{}",
+                        fuzzy::did_you_mean(
+                            &target_name,
+                            here.iter().map(|(_, n)| n).chain(names.iter().map(|(_, n)| n))
+                        )
+                    ),
+                };
+                engine.connection_broker.send_player_message(player, msg);
+                return;
+            };
+
+            let refusal = match engine.rules.pvp {
+                PvpMode::Off => Some("Fighting other adventurers isn't allowed here"),
+                PvpMode::Consent if !attacker.pvp_consent => {
+                    Some("You need to turn on 'pvp' before you can attack other adventurers")
+                }
+                PvpMode::Consent if !engine.world.player_characters[&target].pvp_consent => {
+                    Some("They haven't agreed to fight other adventurers")
+                }
+                _ => None,
+            };
+            if let Some(refusal) = refusal {
+                engine
+                    .connection_broker
+                    .send_player_message(player, refusal.to_string());
+                return;
+            }
+
+            let attacker_name = name_of(&player);
+            let target_name = name_of(&target);
+            let target_level = engine.world.player_characters[&target].level();
+            drop(accounts);
+            let lockout = engine.world.current_tick
+                + config::get().seconds_to_ticks(config::get().combat_lockout_seconds);
+            for p in [player, target] {
+                let character = engine.world.player_character(p);
+                character.in_combat_until = lockout;
+                character.camping_until = None;
+                character.recalling_until = None;
+            }
+
+            let defender = engine.world.player_characters.get_mut(&target).unwrap();
+            let outcome = combat::attack(&attacker, defender, &mut rand::thread_rng());
+            let interrupted = interrupt(defender, outcome);
+
+            let fight = engine.world.fights.entry(location).or_default();
+            fight.players.insert(player);
+            fight.players.insert(target);
+
+            let (mut attacker_msg, mut target_msg) = match outcome {
+                AttackOutcome::Miss => {
+                    fight.record_miss(&attacker_name, &target_name);
+                    (
+                        format!("You swing at {target_name} and miss"),
+                        format!("{attacker_name} swings at you and misses"),
+                    )
+                }
+                AttackOutcome::Hit(damage) => {
+                    fight.record_damage(&attacker_name, &target_name, damage);
+                    (
+                        format!("You hit {target_name} for {damage} damage"),
+                        format!("{attacker_name} hits you for {damage} damage"),
+                    )
+                }
+                AttackOutcome::Downed(damage) => {
+                    fight.record_damage(&attacker_name, &target_name, damage);
+                    fight.record_kill(&attacker_name);
+                    fight.downed_players += 1;
+                    engine.hooks.emit(Event::PlayerDowned(target, location));
+                    engine.world.record_event(
+                        location,
+                        EventKind::KnockedOut(target_name.clone(), attacker_name.clone()),
+                    );
+                    (
+                        format!("You hit {target_name} for {damage} damage, knocking them out!"),
+                        format!("{attacker_name} hits you for {damage} damage, you're knocked out!"),
+                    )
+                }
+            };
+            target_msg.push_str(&interrupted);
+            if let AttackOutcome::Downed(_) = outcome {
+                target_msg.push_str(&knock_out(engine, target, location));
+                engine.world.end_fight(location);
+                target_msg.push_str(&settle_companions(engine, player, target));
+                attacker_msg.push_str(&award_experience(
+                    engine,
+                    player,
+                    experience::KNOCKOUT_XP_PER_LEVEL * target_level,
+                    &format!("knocking out {target_name}"),
+                ));
+            }
+
+            let poison = attacker
+                .coating
+                .as_ref()
+                .and_then(|c| engine.world.find_recipe(&c.poison));
+            if let (AttackOutcome::Hit(_), Some(poison)) = (outcome, poison) {
+                let until_tick = engine.world.current_tick
+                    + config::get().seconds_to_ticks(config::get().poison_seconds);
+                let defender = engine.world.player_characters.get_mut(&target).unwrap();
+                let rng = &mut rand::thread_rng();
+
+                match combat::apply_coating(&mut attacker, defender, &poison, until_tick, rng) {
+                    Some(PoisonOutcome::Poisoned(stacks)) => {
+                        attacker_msg.push_str(&format!(
+                            "\nYour poison takes hold, {target_name} is poisoned x{stacks}"
+                        ));
+                        target_msg.push_str(&format!(
+                            "\nPoison burns in the wound, you're poisoned x{stacks}"
+                        ));
+                    }
+                    Some(PoisonOutcome::Resisted) => {
+                        attacker_msg.push_str(&format!("\n{target_name} shrugs off your poison"));
+                        target_msg.push_str("\nYou shrug off the poison on their blade");
+                    }
+                    None => {}
+                }
+
+                if attacker.coating.is_none() {
+                    attacker_msg.push_str("\nThe last of the poison on your weapon is used up");
+                }
+                engine.world.player_character(player).coating = attacker.coating;
+            }
+
+            if matches!(outcome, AttackOutcome::Hit(_) | AttackOutcome::Downed(_)) {
+                if let Some(weapon) = &mut engine.world.player_character(player).wielding {
+                    if weapon.wear(1) {
+                        attacker_msg.push_str(&format!("\nYour {} breaks!", weapon.name()));
+                    }
+                }
+            }
+
+            // Companions join in while the fight's still going
+            let mut fighting = !matches!(outcome, AttackOutcome::Downed(_));
+            for (owner, enemy, owner_attacking) in [(player, target, true), (target, player, false)] {
+                if !fighting {
+                    break;
+                }
+                if let Some((owner_msg, enemy_msg, downed)) =
+                    companion_strike(engine, owner, enemy, owner_attacking)
+                {
+                    let (owner_out, enemy_out) = if owner == player {
+                        (&mut attacker_msg, &mut target_msg)
+                    } else {
+                        (&mut target_msg, &mut attacker_msg)
+                    };
+                    owner_out.push_str(&owner_msg);
+                    enemy_out.push_str(&enemy_msg);
+                    fighting = !downed;
+                }
+            }
+
+            engine
+                .connection_broker
+                .send_player_message(player, styled(Style::Combat, attacker_msg));
+            engine
+                .connection_broker
+                .send_player_message(target, styled(Style::Combat, target_msg));
+        }),
+    )
+}
+
+/// A round of a player fighting a creature here, which strikes back if it's still
+/// standing. Others here see how it goes.
+fn attack_creature(engine: &mut Engine, player: PlayerId, creature: EntityId) -> String {
+    let rng = &mut rand::thread_rng();
+    let lockout = engine.world.current_tick
+        + config::get().seconds_to_ticks(config::get().combat_lockout_seconds);
+    let character = engine.world.player_character(player);
+    character.in_combat_until = lockout;
+    character.camping_until = None;
+    character.recalling_until = None;
+    let attacker = character.clone();
+    let location = attacker.location;
+
+    engine.world.place_changed(location);
+    let creatures = engine.world.npcs.entry(location).or_default();
+    let Some(idx) = creatures.iter().position(|c| c.id == creature) else {
+        return "It's already gone".to_string();
+    };
+    let creature = &mut creatures[idx];
+    let creature_name = creature.name.clone();
+    let outcome = combat::attack(&attacker, creature, rng);
+    let creature_level = creature.level();
+    let fight = engine.world.fights.entry(location).or_default();
+    fight.players.insert(player);
+    fight.creatures.insert(creature_name.clone());
+
+    let mut msg = match outcome {
+        AttackOutcome::Miss => {
+            fight.record_miss(&attacker.name, &creature_name);
+            format!("You swing at the {creature_name} and miss")
+        }
+        AttackOutcome::Hit(damage) => {
+            fight.record_damage(&attacker.name, &creature_name, damage);
+            format!("You hit the {creature_name} for {damage} damage")
+        }
+        AttackOutcome::Downed(damage) => {
+            fight.record_damage(&attacker.name, &creature_name, damage);
+            fight.record_kill(&attacker.name);
+            format!("You hit the {creature_name} for {damage} damage")
+        }
+    };
+    if matches!(outcome, AttackOutcome::Hit(_) | AttackOutcome::Downed(_)) {
+        if let Some(weapon) = &mut engine.world.player_character(player).wielding {
+            if weapon.wear(1) {
+                msg.push_str(&format!(", your {} breaks!", weapon.name()));
+            }
+        }
+    }
+
+    if let AttackOutcome::Downed(_) = outcome {
+        let fled = drive_off(engine, location, idx, rng);
+        engine.world.end_fight(location);
+        engine.connection_broker.broadcast_to_location(
+            &engine.world,
+            location,
+            styled(
+                Style::Combat,
+                format!("{} beats the {creature_name}, {fled}", attacker.name),
+            ),
+            Some(player),
+        );
+        msg.push_str(&format!(", it {fled}"));
+        msg.push_str(&award_experience(
+            engine,
+            player,
+            experience::KNOCKOUT_XP_PER_LEVEL * creature_level,
+            &format!("beating the {creature_name}"),
+        ));
+        return msg;
+    }
+
+    let creature = engine.world.npcs[&location][idx].clone();
+    let defender = engine.world.player_characters.get_mut(&player).unwrap();
+    let outcome = combat::attack(&creature, defender, rng);
+    let interrupted = interrupt(defender, outcome);
+    let fight = engine.world.fights.entry(location).or_default();
+    let seen = match outcome {
+        AttackOutcome::Miss => {
+            fight.record_miss(&creature_name, &attacker.name);
+            msg.push_str(&format!("\nThe {creature_name} lunges at you and misses"));
+            format!("{} trades blows with the {creature_name}", attacker.name)
+        }
+        AttackOutcome::Hit(damage) => {
+            fight.record_damage(&creature_name, &attacker.name, damage);
+            msg.push_str(&format!(
+                "\nThe {creature_name} hits you for {damage} damage"
+            ));
+            format!("{} trades blows with the {creature_name}", attacker.name)
+        }
+        AttackOutcome::Downed(damage) => {
+            fight.record_damage(&creature_name, &attacker.name, damage);
+            fight.record_kill(&creature_name);
+            fight.downed_players += 1;
+            engine.hooks.emit(Event::PlayerDowned(player, location));
+            engine.world.record_event(
+                location,
+                EventKind::KnockedOut(attacker.name.clone(), creature_name.clone()),
+            );
+            msg.push_str(&format!(
+                "\nThe {creature_name} hits you for {damage} damage, you're knocked out!"
+            ));
+            format!("The {creature_name} knocks out {}!", attacker.name)
+        }
+    };
+    msg.push_str(&interrupted);
+    engine.connection_broker.broadcast_to_location(
+        &engine.world,
+        location,
+        styled(Style::Combat, seen),
+        Some(player),
+    );
+    if let AttackOutcome::Downed(_) = outcome {
+        msg.push_str(&knock_out(engine, player, location));
+        engine.world.end_fight(location);
+    }
+    msg
+}
+
+/// Sends a beaten creature, healed, through an exit that doesn't lead somewhere
+/// safe. One with nowhere to run is gone for good. Returns how it went.
+fn drive_off(engine: &mut Engine, location: Location, idx: usize, rng: &mut impl Rng) -> String {
+    let mut creature = engine.world.npcs.get_mut(&location).unwrap().remove(idx);
+    creature.health = creature.max_health();
+    let exits: Vec<_> = engine.world.places[&location]
+        .connections()
+        .values()
+        .copied()
+        .filter(|l| {
+            engine
+                .world
+                .places
+                .get(l)
+                .is_some_and(|p| !p.tags.contains(SAFE_TAG))
+        })
+        .collect();
+
+    match exits.choose(rng) {
+        Some(to) => {
+            engine.world.npcs.entry(*to).or_default().push(creature);
+            engine.world.place_changed(*to);
+            "driving it off".to_string()
+        }
+        None => "and with nowhere to run it's gone for good".to_string(),
+    }
+}
+
+/// Lets a fighter's companion strike their opponent if its stance allows. Returns what
+/// the owner and their opponent see and whether the opponent was knocked out.
+fn companion_strike(
+    engine: &mut Engine,
+    owner: PlayerId,
+    enemy: PlayerId,
+    owner_attacking: bool,
+) -> Option<(String, String, bool)> {
+    let (companion, kind) = engine.world.player_characters[&owner]
+        .companion
+        .as_ref()
+        .filter(|c| c.stance.fights(owner_attacking))
+        .map(|c| (c.creature.clone(), c.kind.clone()))?;
+    let name = companion.name.clone();
+    let enemy_name = engine.world.player_characters[&enemy].name.clone();
+    let location = engine.world.player_characters[&owner].location;
+
+    let defender = engine.world.player_characters.get_mut(&enemy).unwrap();
+    let outcome = combat::attack(&companion, defender, &mut rand::thread_rng());
+    let interrupted = interrupt(defender, outcome);
+    let fight = engine.world.fights.entry(location).or_default();
+    fight.creatures.insert(kind);
+
+    let mut res = match outcome {
+        AttackOutcome::Miss => {
+            fight.record_miss(&name, &enemy_name);
+            (
+                format!("\n{name} lunges at {enemy_name} and misses"),
+                format!("\n{name} lunges at you and misses"),
+                false,
+            )
+        }
+        AttackOutcome::Hit(damage) => {
+            fight.record_damage(&name, &enemy_name, damage);
+            (
+                format!("\n{name} bites into {enemy_name} for {damage} damage"),
+                format!("\n{name} bites into you for {damage} damage"),
+                false,
+            )
+        }
+        AttackOutcome::Downed(damage) => {
+            fight.record_damage(&name, &enemy_name, damage);
+            fight.record_kill(&name);
+            fight.downed_players += 1;
+            engine.hooks.emit(Event::PlayerDowned(enemy, location));
+            engine.world.record_event(
+                location,
+                EventKind::KnockedOut(enemy_name.clone(), name.clone()),
+            );
+            let enemy_level = engine.world.player_characters[&enemy].level();
+            let fate = knock_out(engine, enemy, location);
+            engine.world.end_fight(location);
+            let leaving = settle_companions(engine, owner, enemy);
+            let gained = award_experience(
+                engine,
+                owner,
+                experience::KNOCKOUT_XP_PER_LEVEL * enemy_level,
+                &format!("{name} knocking out {enemy_name}"),
+            );
+            (
+                format!(
+                    "\n{name} hits {enemy_name} for {damage} damage, knocking them out!{gained}"
+                ),
+                format!(
+                    "\n{name} hits you for {damage} damage, you're knocked out!{fate}{leaving}"
+                ),
+                true,
+            )
+        }
+    };
+    res.1.push_str(&interrupted);
+
+    Some(res)
+}
+
+/// What becomes of a knocked out player. If the rules have ghosts they leave
+/// their body there until they revive, otherwise they leave a corpse holding
+/// what the death penalty takes and come to where they're bound or at a safe
+/// overworld place.
+fn knock_out(engine: &mut Engine, player: PlayerId, location: Location) -> String {
+    // Adventurer parties don't die, they wait to be rescued
+    let current_tick = engine.world.current_tick;
+    let party = engine
+        .world
+        .parties
+        .iter_mut()
+        .find(|p| p.members.contains(&player));
+    if let Some(party) = party {
+        party.stranded_since = Some(current_tick);
+        let name = party.name.clone();
+        let character = engine.world.player_character(player);
+        character.health = character.max_health();
+        engine.connection_broker.broadcast_to_location(
+            &engine.world,
+            location,
+            format!("{name} are left battered and helpless"),
+            None,
+        );
+        return String::new();
+    }
+
+    let ghosts = engine.rules.ghosts;
+    let death_penalty = engine.rules.death_penalty;
+    let wakes_at = engine
+        .world
+        .player_character(player)
+        .bound_to
+        .filter(|l| engine.world.places.contains_key(l))
+        .or_else(|| engine.world.spawn_location())
+        .filter(|l| *l != location);
+    let crumbles_at =
+        engine.world.current_tick + config::get().seconds_to_ticks(config::get().corpse_seconds);
+    let character = engine.world.player_character(player);
+    character.recalling_until = None;
+    if ghosts {
+        character.ghost = Some(location);
+        return "\nYour spirit slips free of your body. Find your way back to it or to a shrine and 'revive'"
+            .to_string();
+    }
+
+    character.health = character.max_health();
+    let name = character.name.clone();
+    let lost = character.inventory.take_share(death_penalty);
+    let mut msg = match wakes_at {
+        Some(wakes_at) => {
+            character.location = wakes_at;
+            format!(
+                "\nYou come to some time later in {}, battered but alive",
+                engine.world.places[&wakes_at].name
+            )
+        }
+        None => "\nYou come to some time later, battered but alive".to_string(),
+    };
+
+    let loot = engine.world.worth(&lost);
+    if let Some(fight) = engine.world.fights.get_mut(&location) {
+        fight.loot_value = fight.loot_value.saturating_add(loot);
+    }
+
+    let seen = if lost.is_empty() {
+        format!("{name} crumples to the ground")
+    } else {
+        msg.push_str(&format!(
+            ". What you lost lies with your corpse in {}, 'loot' it before it crumbles",
+            engine.world.places[&location].name
+        ));
+        engine.world.place_changed(location);
+        if let Some(place) = engine.world.places.get_mut(&location) {
+            place
+                .corpses
+                .push(Corpse::new(player, name.clone(), lost, crumbles_at));
+        }
+        format!("{name} crumples to the ground, leaving a corpse behind")
+    };
+    engine
+        .connection_broker
+        .broadcast_to_location(&engine.world, location, seen, Some(player));
+    if let Some(wakes_at) = wakes_at {
+        engine.connection_broker.broadcast_to_location(
+            &engine.world,
+            wakes_at,
+            format!("{name} stumbles in, pale and shaken"),
+            Some(player),
+        );
+    }
+    msg
+}
+
+/// Getting hurt stops whatever the defender was busy doing
+fn interrupt(defender: &mut Character, outcome: AttackOutcome) -> String {
+    match outcome {
+        AttackOutcome::Miss => None,
+        AttackOutcome::Hit(_) | AttackOutcome::Downed(_) => defender.interrupt(),
+    }
+    .unwrap_or_default()
+}
+
+/// Adjusts companion loyalty once a fight's been won, the loser's companion
+/// might leave them. Returns a message for the loser if it does.
+fn settle_companions(engine: &mut Engine, winner: PlayerId, loser: PlayerId) -> String {
+    if let Some(companion) = &mut engine.world.player_character(winner).companion {
+        companion.won_fight();
+    }
+
+    let loser = engine.world.player_character(loser);
+    let leaving = loser.companion.as_mut().is_some_and(|c| c.lost_fight());
+    match loser.companion.take_if(|_| leaving) {
+        Some(companion) => format!(
+            "\n{} has lost faith in you and wanders off",
+            companion.name()
+        ),
+        None => String::new(),
+    }
+}
+
+pub fn hire_command() -> Command {
+    Command::new(
+        "hire",
+        &[],
+        "Pays a party of adventurers in the same place as you to follow you for a while, like 'hire Lantern Company'",
+        Box::new(|engine, player, args| {
+            let name = args.joined();
+            let res = hire_party(engine, player, &name).unwrap_or_else(|e| e);
+            engine.connection_broker.send_player_message(player, res);
+        }),
+    )
+}
+
+fn hire_party(engine: &mut Engine, player: PlayerId, name: &str) -> Result<String, String> {
+    let location = engine.world.player_character(player).location;
+    let idx = party_here(engine, location, name)?;
+    let current_tick = engine.world.current_tick;
+    let party = &engine.world.parties[idx];
+    if party.stranded_since.is_some() {
+        return Err(format!("{} are in no state to go anywhere", party.name));
+    }
+    match party.employer(current_tick) {
+        Some(p) if p == player => return Err(format!("{} already work for you", party.name)),
+        Some(_) => return Err(format!("{} are already working for someone", party.name)),
+        None => {}
+    }
+
+    if !engine
+        .world
+        .player_character(player)
+        .inventory
+        .remove(GOLD, party::HIRE_COST)
+    {
+        let name = &engine.world.parties[idx].name;
+        return Err(format!(
+            "{name} want {} {GOLD}s to follow you",
+            party::HIRE_COST
+        ));
+    }
+    let until = current_tick + config::get().seconds_to_ticks(party::HIRE_SECONDS);
+    let party = &mut engine.world.parties[idx];
+    party.hired_by = Some((player, until));
+    let (name, leader) = (party.name.clone(), party.leader());
+    engine
+        .world
+        .player_character(leader)
+        .inventory
+        .add(GOLD, party::HIRE_COST);
+
+    Ok(format!(
+        "You pay {name} {} {GOLD}s, they'll follow you for the next {:.0} minutes",
+        party::HIRE_COST,
+        party::HIRE_SECONDS / 60.0
+    ))
+}
+
+pub fn rescue_command() -> Command {
+    Command::new(
+        "rescue",
+        &[],
+        "Helps a knocked out party of adventurers in the same place as you back on their feet, like 'rescue Lantern Company'",
+        Box::new(|engine, player, args| {
+            let name = args.joined();
+            let res = rescue_party(engine, player, &name).unwrap_or_else(|e| e);
+            engine.connection_broker.send_player_message(player, res);
+        }),
+    )
+}
+
+fn rescue_party(engine: &mut Engine, player: PlayerId, name: &str) -> Result<String, String> {
+    let location = engine.world.player_character(player).location;
+    let idx = party_here(engine, location, name)?;
+    let party = &mut engine.world.parties[idx];
+    if party.stranded_since.take().is_none() {
+        return Err(format!("{} don't need rescuing", party.name));
+    }
+    let (name, leader) = (party.name.clone(), party.leader());
+
+    let rescuer = engine.world.player_character(player).name.clone();
+    engine.connection_broker.broadcast_to_location(
+        &engine.world,
+        location,
+        format!("{rescuer} helps {name} back on their feet"),
+        Some(player),
+    );
+    let paid = engine
+        .world
+        .player_character(leader)
+        .inventory
+        .remove(GOLD, party::RESCUE_REWARD);
+    if !paid {
+        return Ok(format!(
+            "You help {name} back on their feet, they've nothing to give but thanks"
+        ));
+    }
+    engine
+        .world
+        .player_character(player)
+        .inventory
+        .add(GOLD, party::RESCUE_REWARD);
+    Ok(format!(
+        "You help {name} back on their feet, they press {} {GOLD}s into your hand",
+        party::RESCUE_REWARD
+    ))
+}
+
+/// Which party in a place the player means, suggesting one if they're close
+fn party_here(engine: &Engine, location: Location, name: &str) -> Result<usize, String> {
+    let here: Vec<_> = engine
+        .world
+        .parties_at(location)
+        .map(|p| p.name.clone())
+        .collect();
+    if here.is_empty() {
+        return Err("There are no adventurers here".to_string());
+    }
+    engine.world.party_named(location, name).ok_or_else(|| {
+        format!(
+            "There's no party called {name} here{}",
+            fuzzy::did_you_mean(name, here.iter())
+        )
+    })
+}
+
+pub fn befriend_command() -> Command {
+    Command::new(
+        "befriend",
+        &["tame"],
+        "Tries to win over a creature in the same place as you so it follows you as a companion, like 'befriend cave rat'",
+        Box::new(|engine, player, args| {
+            let name = args.joined();
+            let character = engine.world.player_character(player).clone();
+            if let Some(companion) = &character.companion {
+                engine.connection_broker.send_player_message(
+                    player,
+                    format!("{} wouldn't like sharing you", companion.name()),
+                );
+                return;
+            }
+
+            let id = engine.world.creature_named(character.location, &name);
+            let creatures = engine.world.npcs.entry(character.location).or_default();
+            let Some(idx) = id.and_then(|id| creatures.iter().position(|c| c.id == id)) else {
+                let suggestion = fuzzy::did_you_mean(&name, creatures.iter().map(|c| &c.name));
+                engine.connection_broker.send_player_message(
+                    player,
+                    format!("There's no {name} here{suggestion}"),
+                );
+                return;
+            };
+
+            let msg = if companion::befriend(&character, &creatures[idx], &mut rand::thread_rng()) {
+                let creature = creatures.remove(idx);
+                let msg = format!(
+                    "The {} warily accepts you, it'll follow you now. Give it a name with 'companion name <name>'",
+                    creature.name
+                );
+                engine.world.player_character(player).companion =
+                    Some(Box::new(Companion::new(creature)));
+                styled(Style::Good, msg)
+            } else {
+                format!("The {} won't have anything to do with you", creatures[idx].name)
+            };
+
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+}
+
+pub fn companion_command() -> Command {
+    Command::new(
+        "companion",
+        &["pet"],
+        "Shows how your companion is doing. Use 'companion stance <aggressive|defensive|passive>' to choose when it fights, 'companion name <name>' to name it or 'companion dismiss' to let it go",
+        Box::new(|engine, player, args| {
+            let subcommand = args.next().unwrap_or_default().to_lowercase();
+            let rest = args.joined();
+            let character = engine.world.player_character(player);
+
+            let Some(companion) = &mut character.companion else {
+                engine.connection_broker.send_player_message(
+                    player,
+                    "You don't have a companion, try to 'befriend' a creature".to_string(),
+                );
+                return;
+            };
+
+            let msg = match subcommand.as_str() {
+                "" => companion.describe(),
+                "stance" => match Stance::values()
+                    .into_iter()
+                    .find(|s| s.name().eq_ignore_ascii_case(&rest))
+                {
+                    Some(stance) => {
+                        companion.stance = stance;
+                        format!("{} is now {}", companion.name(), stance.name())
+                    }
+                    None => "Stances are aggressive, defensive or passive".to_string(),
+                },
+                "name" => match check_name(&rest) {
+                    Ok(()) => {
+                        let msg = format!("{} will answer to {rest} now", companion.name());
+                        companion.creature.name = rest;
+                        msg
+                    }
+                    Err(e) => e,
+                },
+                "dismiss" => {
+                    let msg = format!("You let {} go, it slinks off", companion.name());
+                    character.companion = None;
+                    msg
+                }
+                _ => format!("Companions can't '{subcommand}', see 'help companion'"),
+            };
+
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+}
+
+pub fn pvp_command() -> Command {
+    Command::new(
+        "pvp",
+        &[],
+        "Toggles whether you agree to fight other adventurers, when the server rules ask for consent",
+        Box::new(|engine, player, _| {
+            let current_tick = engine.world.current_tick;
+            let character = engine.world.player_character(player);
+
+            let msg = if engine.rules.pvp != PvpMode::Consent {
+                "This server doesn't use pvp consent, see 'rules'".to_string()
+            } else if character.in_combat(current_tick) {
+                "You can't change your mind in the middle of a fight!".to_string()
+            } else {
+                character.pvp_consent = !character.pvp_consent;
+                if character.pvp_consent {
+                    "You'll now fight other adventurers who challenge you".to_string()
+                } else {
+                    "You'll no longer fight other adventurers".to_string()
+                }
+            };
+
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+}
+
+pub fn rules_command() -> Command {
+    Command::new(
+        "rules",
+        &[],
+        "Shows the game rules this server is running with",
+        Box::new(|engine, player, _| {
+            engine
+                .connection_broker
+                .send_player_message(player, engine.rules.describe(engine.realm.rule_profile()));
+        }),
+    )
+    .ghostly()
+}
+
+pub fn color_command() -> Command {
+    Command::new(
+        "color",
+        &["colour"],
+        "Turns coloured text on or off, like 'color off'. Run alone to switch it",
+        Box::new(|engine, player, args| {
+            let current = engine
+                .player_registry
+                .blocking_read()
+                .get(&player)
+                .is_none_or(|p| p.color);
+            let color = match args.next() {
+                Some("on") => true,
+                Some("off") => false,
+                _ => !current,
+            };
+
+            let msg = match engine
+                .player_registry
+                .blocking_update(player, |p| p.color = color)
+            {
+                Ok(()) if color => styled(Style::Good, "Colour is on"),
+                Ok(()) => "Colour is off".to_string(),
+                Err(e) => {
+                    tracing::error!("Failed saving colour setting: {e}");
+                    "Something went wrong saving your setting, try again later".to_string()
+                }
+            };
+
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+    .ghostly()
+}
+
+pub fn compress_command() -> Command {
+    Command::new(
+        "compress",
+        &[],
+        "Turns compressed output on or off for telnet clients that support it, like 'compress off'. Run alone to switch it",
+        Box::new(|engine, player, args| {
+            let current = engine
+                .player_registry
+                .blocking_read()
+                .get(&player)
+                .is_none_or(|p| p.compress);
+            let compress = match args.next() {
+                Some("on") => true,
+                Some("off") => false,
+                _ => !current,
+            };
+
+            let msg = match engine
+                .player_registry
+                .blocking_update(player, |p| p.compress = compress)
+            {
+                Ok(()) if compress => styled(Style::Good, "Compression is on"),
+                Ok(()) => "Compression is off".to_string(),
+                Err(e) => {
+                    tracing::error!("Failed saving compression setting: {e}");
+                    "Something went wrong saving your setting, try again later".to_string()
+                }
+            };
+
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+    .ghostly()
+}
+
+pub fn bandwidth_command() -> Command {
+    Command::new(
+        "bandwidth",
+        &[],
+        "Admin only, shows how much compressing telnet output is saving",
+        Box::new(|engine, player, _| {
+            engine
+                .connection_broker
+                .send_player_message(player, mccp::stats().report());
+        }),
+    )
+    .ghostly()
+    .admin()
+}
+
+pub fn bots_command() -> Command {
+    Command::new(
+        "bots",
+        &[],
+        "Admin only, runs simulated players for load testing. Use 'bots start <count>', 'bots stop', or 'bots' alone to see how they're getting on",
+        Box::new(|engine, player, args| {
+            let res = match (args.next(), args.next().map(str::parse::<usize>)) {
+                (Some("start"), Some(Ok(count @ 1..=bots::MAX_BOTS))) => {
+                    engine.bots.start(count);
+                    format!("Started {count} bots")
+                }
+                (Some("start"), _) => {
+                    format!("Start between 1 and {} bots, like 'bots start 50'", bots::MAX_BOTS)
+                }
+                (Some("stop"), _) => match engine.bots.stop() {
+                    0 => "No bots are running".to_string(),
+                    n => format!("Stopped {n} bots"),
+                },
+                _ => engine.bots.report(),
+            };
+
+            engine.connection_broker.send_player_message(player, res);
+        }),
+    )
+    .admin()
+}
+
+pub fn width_command() -> Command {
+    Command::new(
+        "width",
+        &["wrap"],
+        "Sets how wide messages wrap, like 'width 100'. 'width auto' goes back to what your client reports, or 80 columns",
+        Box::new(|engine, player, args| {
+            let width = match args.next() {
+                Some("auto") => Ok(None),
+                Some(w) => w
+                    .parse::<u16>()
+                    .ok()
+                    .filter(|w| MIN_WIDTH <= *w)
+                    .map(Some)
+                    .ok_or_else(|| format!("Pick a width of at least {MIN_WIDTH}, or 'auto'")),
+                None => Err("Pick a width like 'width 100', or 'width auto'".to_string()),
+            };
+
+            let msg = match width.and_then(|width| {
+                engine
+                    .player_registry
+                    .blocking_update(player, |p| p.width = width)
+                    .map(|()| width)
+                    .map_err(|e| {
+                        tracing::error!("Failed saving width setting: {e}");
+                        "Something went wrong saving your setting, try again later".to_string()
+                    })
+            }) {
+                Ok(Some(width)) => format!("Messages now wrap at {width} columns"),
+                Ok(None) => "Messages now wrap to fit your client".to_string(),
+                Err(e) => e,
+            };
+
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+    .ghostly()
+}
+
+/// The commands for managing aliases, which can't be aliased themselves so
+/// nobody locks themselves out of fixing one
+const ALIAS_COMMANDS: [&str; 3] = ["alias", "unalias", "aliases"];
+
+pub fn alias_command() -> Command {
+    Command::new(
+        "alias",
+        &[],
+        "Sets up a shorthand for a command, like 'alias gb give bob' so 'gb 2 bread' gives bob 2 bread. \
+        'alias gb' alone shows what it stands for",
+        Box::new(|engine, player, args| {
+            let name = args.next().unwrap_or_default().to_string();
+            let expansion = args.rest().to_string();
+            let character = engine.world.player_character(player);
+
+            let msg = set_alias(character, name, expansion).unwrap_or_else(|e| e);
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+    .ghostly()
+    .immediate()
+}
+
+fn set_alias(character: &mut Character, name: String, expansion: String) -> Result<String, String> {
+    if name.is_empty() {
+        return Err("Like 'alias gb give bob', or 'aliases' to list yours".to_string());
+    }
+    if expansion.is_empty() {
+        return match character.aliases.get(&name) {
+            Some(expansion) => Ok(format!("'{name}' stands for '{expansion}'")),
+            None => Err(format!("You don't have an alias called '{name}'")),
+        };
+    }
+
+    if ALIAS_COMMANDS.contains(&name.as_str()) {
+        return Err(format!("'{name}' can't be an alias"));
+    }
+    if name.chars().count() > MAX_ALIAS_NAME || name.chars().any(char::is_control) {
+        return Err(format!(
+            "Alias names can be up to {MAX_ALIAS_NAME} letters, without anything odd in them"
+        ));
+    }
+    if expansion.chars().count() > MAX_ALIAS_LENGTH {
+        return Err(format!(
+            "That's too long, aliases can stand for up to {MAX_ALIAS_LENGTH} characters"
+        ));
+    }
+    if !character.aliases.contains_key(&name) && character.aliases.len() >= MAX_ALIASES {
+        return Err(format!(
+            "You already have {MAX_ALIASES} aliases, 'unalias' one first"
+        ));
+    }
+
+    let msg = format!("'{name}' now stands for '{expansion}'");
+    character.aliases.insert(name, expansion);
+    Ok(msg)
+}
+
+pub fn unalias_command() -> Command {
+    Command::new(
+        "unalias",
+        &[],
+        "Forgets one of your aliases, like 'unalias gb'",
+        Box::new(|engine, player, args| {
+            let name = args.next().unwrap_or_default();
+            let character = engine.world.player_character(player);
+            let msg = match character.aliases.remove(name) {
+                Some(expansion) => format!("'{name}' no longer stands for '{expansion}'"),
+                None if name.is_empty() => "Which alias? Like 'unalias gb'".to_string(),
+                None => format!("You don't have an alias called '{name}'"),
+            };
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+    .ghostly()
+    .immediate()
+}
+
+pub fn aliases_command() -> Command {
+    Command::new(
+        "aliases",
+        &[],
+        "Lists the shorthands you've set up with 'alias'",
+        Box::new(|engine, player, _| {
+            let character = engine.world.player_character(player);
+            let msg = if character.aliases.is_empty() {
+                "You haven't set up any aliases, try 'alias gb give bob'".to_string()
+            } else {
+                let width = character.aliases.keys().map(|n| n.chars().count()).max();
+                let width = width.unwrap_or_default();
+                let lines: Vec<_> = character
+                    .aliases
+                    .iter()
+                    .map(|(name, expansion)| format!("{name:<width$}  {expansion}"))
+                    .collect();
+                format!("Your aliases\n\n{}", lines.join("\n"))
+            };
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+    .ghostly()
+    .immediate()
+}
+
+pub fn recap_command() -> Command {
+    Command::new(
+        "recap",
+        &[],
+        "Shows a breakdown of the last fight you took part in, who dealt and took what",
+        Box::new(|engine, player, _| {
+            let res = match engine.world.last_fights.get(&player) {
+                Some(fight) => fight.recap(),
+                None => "You haven't finished a fight yet".to_string(),
+            };
+
+            engine.connection_broker.send_player_message(player, res);
+        }),
+    )
+}
+
+pub fn balance_command() -> Command {
+    Command::new(
+        "balance",
+        &[],
+        "Admin only, reports fight outcomes for generated creatures and places and flags any that look overtuned",
+        Box::new(|engine, player, _| {
+            let res = engine
+                .world
+                .telemetry
+                .report(config::get().balance_outlier_factor);
+
+            engine.connection_broker.send_player_message(player, res);
+        }),
+    )
+    .admin()
+}
+
+pub fn digest_command() -> Command {
+    Command::new(
+        "digest",
+        &[],
+        "Admin only, shows the daily digest so far. 'digest send' posts it to the webhook now and starts the next one",
+        Box::new(|engine, player, args| {
+            let res = if args.next() == Some("send") {
+                match &config::get().digest {
+                    Some(webhook) => {
+                        let digest = engine.digest.take(&engine.realm.name, &engine.world);
+                        engine.digest.post(&webhook.url, webhook.kind, &digest);
+                        format!("Posting the digest\n\n{}", digest.text())
+                    }
+                    None => "There's no digest webhook set up in config.yaml".to_string(),
+                }
+            } else {
+                engine
+                    .digest
+                    .digest(&engine.realm.name, &engine.world)
+                    .text()
+            };
+
+            engine.connection_broker.send_player_message(player, res);
+        }),
+    )
+    .admin()
+}
+
+pub fn alerts_command() -> Command {
+    Command::new(
+        "alerts",
+        &["alert"],
+        "Admin only, shows what's gone wrong lately. Use 'alerts ack [rule]' to stop the reminders, 'alerts mute <rule> <minutes>' to quiet a noisy rule or 'alerts unmute <rule>'",
+        Box::new(|engine, player, args| {
+            let sub = args.next().unwrap_or_default();
+            let msg = manage_alerts(engine, sub, args).unwrap_or_else(|e| e);
+
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+    .ghostly()
+    .admin()
+}
+
+fn manage_alerts(engine: &mut Engine, sub: &str, args: &mut Args) -> Result<String, String> {
+    let rule = |name: Option<&str>| {
+        let name = name.ok_or("Which rule?")?;
+        AlertRule::from_name(name).ok_or_else(|| {
+            let names = AlertRule::ALL.map(|r| r.name());
+            format!(
+                "There's no alert rule called {name}, try {}",
+                names.join(", ")
+            )
+        })
+    };
+    let alerts = &mut engine.alerts;
+
+    match sub {
+        "" | "status" => Ok(alerts.report(Instant::now())),
+        "ack" | "acknowledge" => {
+            let rule = args.next().map(|r| rule(Some(r))).transpose()?;
+            match alerts.acknowledge(rule) {
+                0 => Err("There's nothing firing to acknowledge".to_string()),
+                n => Ok(format!("Acknowledged {n} alerts")),
+            }
+        }
+        "mute" => {
+            let rule = rule(args.next())?;
+            let minutes: f64 = args
+                .next()
+                .and_then(|m| m.parse().ok())
+                .filter(|m| *m > 0.0)
+                .ok_or("Mute it for how many minutes?")?;
+            alerts
+                .muted
+                .insert(rule, Instant::now() + Duration::from_secs_f64(minutes * 60.0));
+            Ok(format!("{rule} is muted for {minutes} minutes"))
+        }
+        "unmute" => {
+            let rule = rule(args.next())?;
+            match alerts.muted.remove(&rule) {
+                Some(_) => Ok(format!("{rule} is no longer muted")),
+                None => Err(format!("{rule} isn't muted")),
+            }
+        }
+        _ => Err("Use 'alerts', 'alerts ack [rule]', 'alerts mute <rule> <minutes>' or 'alerts unmute <rule>'".to_string()),
+    }
+}
+
+pub fn audit_command() -> Command {
+    Command::new(
+        "audit",
+        &[],
+        "Admin only, counts every item and coin players hold and checks it against the ledger of what's been made and destroyed since the last audit",
+        Box::new(|engine, player, _| {
+            let (started, since) = (
+                engine.world.ledger.started(),
+                engine.world.ledger.audited_tick,
+            );
+            let pending = engine.world.ledger.pending();
+            let discrepancies = audit(engine);
+            let mut msg = String::from("Audit\n\n");
+            if !started {
+                msg.push_str("Took the first count, the next audit will check against it\n");
+            } else if discrepancies.is_empty() {
+                msg.push_str(&format!(
+                    "Everything adds up, {pending} transactions since tick {since}\n"
+                ));
+            }
+            for discrepancy in discrepancies {
+                msg.push_str(&styled(Style::Warn, discrepancy.to_string()));
+                msg.push('\n');
+            }
+
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+    .ghostly()
+    .admin()
+}
+
+/// How much of a captured prompt or reply is shown, the rest is in the files
+const GENERATION_PREVIEW: usize = 600;
+
+pub fn generations_command() -> Command {
+    Command::new(
+        "generations",
+        &[],
+        "Admin only, shows the latest prompts sent to the AI backend and what it said back, like 'generations 5'. \
+        Only works with generation-capture turned on in the config",
+        Box::new(|engine, player, args| {
+            let count = args.next().and_then(|n| n.parse().ok()).unwrap_or(3usize);
+            let msg = show_generations(count.clamp(1, 20)).unwrap_or_else(|e| e);
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+    .ghostly()
+    .admin()
+}
+
+fn show_generations(count: usize) -> Result<String, String> {
+    if !config::get().generation_capture {
+        return Err(
+            "Generations aren't being captured, turn on generation-capture in the config"
+                .to_string(),
+        );
+    }
+    let generations =
+        generation::recent_generations(&state::make_save_path(generation::CAPTURE_DIR), count)
+            .map_err(|e| {
+                tracing::error!("Failed reading captured generations: {e}");
+                "Something went wrong reading the captured generations".to_string()
+            })?;
+    if generations.is_empty() {
+        return Ok("Nothing's been generated since capturing started".to_string());
+    }
+
+    let preview = |text: &str| match text.char_indices().nth(GENERATION_PREVIEW) {
+        Some((end, _)) => format!("{}...", &text[..end]),
+        None => text.to_string(),
+    };
+    let now = reports::now() as u64;
+    let mut msg = String::from("Latest generations, oldest first\n");
+    for generation in generations {
+        let minutes = now.saturating_sub(generation.at) / 60_000;
+        let kind = if generation.json { "json" } else { "text" };
+        let response = match generation.failed {
+            true => styled(Style::Warn, format!("Failed: {}", generation.response)),
+            false => preview(&generation.response),
+        };
+        msg.push_str(&format!(
+            "\n{minutes} minutes ago, {kind}, seed {}\nPrompt: {}\nResponse: {response}\n",
+            generation.seed,
+            preview(&generation.prompt),
+        ));
+    }
+    Ok(msg)
+}
+
+pub fn script_command() -> Command {
+    Command::new(
+        "script",
+        &[],
+        "Admin only, 'script dryrun <file> [args]' runs a command or event script from the scripts folder as you and shows what it would do, without doing any of it. 'script versions' lists the stored versions of every script, 'script diff <file> [from] [to]' shows what changed between two and 'script rollback <file> [version]' puts an earlier one live",
+        Box::new(|engine, player, args| {
+            let sub = args.next().unwrap_or_default();
+            let msg = manage_scripts(engine, player, sub, args).unwrap_or_else(|e| e);
+
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+    .ghostly()
+    .admin()
+}
+
+fn manage_scripts(
+    engine: &Engine,
+    player: PlayerId,
+    sub: &str,
+    args: &mut Args,
+) -> Result<String, String> {
+    if sub == "dryrun" {
+        let file = args
+            .next()
+            .ok_or("Which script? Like 'script dryrun emote.lua waves'")?;
+        let rest = args.joined();
+        return dry_run_script(engine, player, file, &rest);
+    }
+
+    let mut store = script_store::store().lock().unwrap();
+    if sub == "versions" {
+        return Ok(store.describe());
+    }
+    let file = args.next().unwrap_or_default();
+    let attachment = store
+        .find(file)
+        .ok_or_else(|| format!("No versions of '{file}' have been stored"))?;
+    let live = store.live(&attachment).unwrap_or_default();
+    let mut versions = args.map(|a| {
+        a.trim_start_matches('v')
+            .parse::<u32>()
+            .map_err(|_| format!("'{a}' isn't a version number"))
+    });
+
+    match sub {
+        "diff" => {
+            let (from, to) = match (versions.next().transpose()?, versions.next().transpose()?) {
+                (Some(from), Some(to)) => (from, to),
+                (Some(from), None) => (from, live),
+                _ => (live.saturating_sub(1).max(1), live),
+            };
+            store.diff(&attachment, from, to).map_err(|e| e.to_string())
+        }
+        "rollback" => {
+            let to = versions.next().transpose()?;
+            let version = store.rollback(&attachment, to).map_err(|e| e.to_string())?;
+            tracing::info!("An admin put {attachment} version {version} live");
+            Ok(format!(
+                "{attachment} is running version {version} now, it was on {live}"
+            ))
+        }
+        _ => Err(format!("Unknown script command '{sub}'")),
+    }
+}
+
+/// Finds a script by file name among the command and event scripts, never
+/// anywhere outside them, and dry runs it
+fn dry_run_script(
+    engine: &Engine,
+    player: PlayerId,
+    file: &str,
+    args: &str,
+) -> Result<String, String> {
+    if file.contains("..") || file.contains('/') || file.contains('\\') {
+        return Err("Just the script's file name, like 'emote.lua'".to_string());
+    }
+    let file = if file.ends_with(".lua") {
+        file.to_string()
+    } else {
+        format!("{file}.lua")
+    };
+    let path = [scripting::SCRIPT_DIR, scripting::HOOK_DIR]
+        .iter()
+        .map(|dir| Path::new(dir).join(&file))
+        .find(|path| path.is_file())
+        .ok_or_else(|| format!("There's no script called {file}"))?;
+
+    scripting::dry_run(&path, &engine.world, player, args)
+        .map_err(|e| format!("The script didn't load: {e:#}"))
+}
+
+/// Counts what players hold against the ledger, starting it over from the count
+pub fn audit(engine: &mut Engine) -> Vec<Discrepancy> {
+    let counted = engine.world.holdings();
+    let current_tick = engine.world.current_tick;
+    engine.world.ledger.audit(counted, current_tick)
+}
+
+pub fn clock_command() -> Command {
+    Command::new(
+        "clock",
+        &["tick"],
+        "Admin only, controls the world's clock. Use 'clock pause [reason]', 'clock resume', 'clock speed <multiplier>' or 'clock step [ticks]'",
+        Box::new(|engine, player, args| {
+            let sub = args.next().unwrap_or_default();
+            let msg = set_clock(engine, sub, args).unwrap_or_else(|e| e);
+
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+    .ghostly()
+    .admin()
+}
+
+pub fn history_command() -> Command {
+    Command::new(
+        "history",
+        &[],
+        "Admin only, shows what's happened lately in a place, like 'history here' or 'history Old Shrine'",
+        Box::new(|engine, player, args| {
+            let name = args.joined();
+            let msg = place_history(engine, player, &name).unwrap_or_else(|e| e);
+
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+    .admin()
+}
+
+fn place_history(engine: &Engine, player: PlayerId, name: &str) -> Result<String, String> {
+    let place = if name.is_empty() || name.eq_ignore_ascii_case("here") {
+        &engine.world.places[&engine.world.player_characters[&player].location]
+    } else {
+        place_named(engine, name)?
+    };
+
+    let mut res = format!("History of {}\n", place.name);
+    let events = engine.world.history.get(&place.location);
+    for event in events.into_iter().flatten() {
+        let what = markup::escape(&event.kind.to_string());
+        res.push_str(&format!("\nTick {}: {what}", event.tick));
+    }
+    if events.is_none_or(|e| e.is_empty()) {
+        res.push_str("\nNothing has happened here lately");
+    }
+    Ok(res)
+}
+
+/// A place by its name, ignoring case
+fn place_named<'a>(engine: &'a Engine, name: &str) -> Result<&'a Place, String> {
+    engine
+        .world
+        .places
+        .values()
+        .find(|p| p.name.eq_ignore_ascii_case(name))
+        .ok_or_else(|| {
+            let names = engine.world.places.values().map(|p| p.name.as_str());
+            format!(
+                "There's nowhere called {name}{}",
+                fuzzy::did_you_mean(name, names)
+            )
+        })
+}
+
+fn set_clock(engine: &mut Engine, sub: &str, args: &mut Args) -> Result<String, String> {
+    let clock = &mut engine.clock;
+    match sub {
+        "" | "status" => Ok(format!(
+            "The world is {} at {}x speed, on tick {}",
+            match &clock.paused {
+                Some(reason) => format!("paused ({reason})"),
+                None => "running".to_string(),
+            },
+            clock.speed,
+            engine.world.current_tick
+        )),
+        "pause" => {
+            if clock.paused.is_some() {
+                return Err("The world is already paused".to_string());
+            }
+            let reason = args.joined();
+            let reason = if reason.is_empty() {
+                "maintenance".to_string()
+            } else {
+                reason
+            };
+            engine.connection_broker.broadcast(format!(
+                "The world falls still, paused for {reason}. Hold tight!"
+            ));
+            clock.paused = Some(reason);
+            clock.steps = 0;
+            Ok("You pause the world".to_string())
+        }
+        "resume" => {
+            if clock.paused.take().is_none() {
+                return Err("The world isn't paused".to_string());
+            }
+            engine
+                .connection_broker
+                .broadcast("The world stirs and carries on".to_string());
+            Ok("You resume the world".to_string())
+        }
+        "speed" => {
+            let speed = args
+                .next()
+                .and_then(|a| a.trim_end_matches('x').parse::<f64>().ok())
+                .filter(|s| SPEED_RANGE.contains(s))
+                .ok_or_else(|| {
+                    let range = SPEED_RANGE;
+                    format!(
+                        "Give a speed between {}x and {}x",
+                        range.start(),
+                        range.end()
+                    )
+                })?;
+            clock.speed = speed;
+            Ok(format!("The world now runs at {speed}x speed"))
+        }
+        "step" => {
+            if clock.paused.is_none() {
+                return Err("Pause the world before stepping it".to_string());
+            }
+            let steps = match args.next() {
+                Some(a) => a
+                    .parse::<u32>()
+                    .map_err(|_| format!("'{a}' isn't a number of ticks"))?,
+                None => 1,
+            };
+            clock.steps = clock.steps.saturating_add(steps);
+            Ok(format!("The world moves on {steps} ticks"))
+        }
+        _ => Err(format!("Unknown clock command '{sub}'")),
+    }
+}
+
+/// The highest an admin can set an attribute
+const MAX_ATTRIBUTE: i32 = 30;
+
+pub fn teleport_command() -> Command {
+    Command::new(
+        "teleport",
+        &["goto"],
+        "Admin only, takes you straight to a place, a place's id or a player, like 'teleport Old Shrine' or 'teleport ada'. \
+        'teleport bob to Old Shrine' or 'teleport bob here' moves someone else",
+        Box::new(|engine, player, args| {
+            let text = args.joined();
+            let msg = teleport_command_target(engine, player, &text).and_then(|(who, to, note)| {
+                if let Some(note) = note {
+                    engine.connection_broker.send_player_message(player, note);
+                }
+                teleport(engine, player, who, to)
+            });
+            let msg = msg.unwrap_or_else(|e| e);
+            if !msg.is_empty() {
+                engine.connection_broker.send_player_message(player, msg);
+            }
+        }),
+    )
+    .ghostly()
+    .admin()
+}
+
+/// Who's being teleported and where to, with a note if the place was a guess
+fn teleport_command_target(
+    engine: &Engine,
+    player: PlayerId,
+    text: &str,
+) -> Result<(PlayerId, Location, Option<String>), String> {
+    if text.is_empty() {
+        return Err("Teleport where? Like 'teleport Village Square'".to_string());
+    }
+    let exact_place = engine
+        .world
+        .places
+        .values()
+        .any(|p| p.name.eq_ignore_ascii_case(text));
+    if !exact_place {
+        if let Some((target, _, rest)) = player_named(engine, text) {
+            if let Some(to) = rest.strip_prefix("to ") {
+                let (to, note) = destination(engine, to.trim())?;
+                return Ok((target, to, note));
+            }
+            if rest.eq_ignore_ascii_case("here") {
+                let here = engine.world.player_characters[&player].location;
+                return Ok((target, here, None));
+            }
+        }
+    }
+    let (to, note) = destination(engine, text)?;
+    Ok((player, to, note))
+}
+
+/// Where a place's name, a place's id or a player's name leads. A name that
+/// isn't quite right goes to the closest place, noting the guess.
+fn destination(engine: &Engine, text: &str) -> Result<(Location, Option<String>), String> {
+    let places = &engine.world.places;
+    if let Some(place) = places.values().find(|p| p.name.eq_ignore_ascii_case(text)) {
+        return Ok((place.location, None));
+    }
+    if let Some((target, _, "")) = player_named(engine, text) {
+        let character = engine
+            .world
+            .player_characters
+            .get(&target)
+            .ok_or("They have no character in this world")?;
+        return Ok((character.location, None));
+    }
+    if let Some(location) = Location::parse(text).filter(|l| places.contains_key(l)) {
+        return Ok((location, None));
+    }
+
+    let names = places.values().map(|p| p.name.as_str());
+    let closest = fuzzy::closest(text, names)
+        .ok_or_else(|| format!("There's nowhere and nobody called {text}"))?;
+    let place = places.values().find(|p| p.name == closest).unwrap();
+    Ok((
+        place.location,
+        Some(format!("Taking '{text}' to mean {}", place.name)),
+    ))
+}
+
+/// Moves a player straight to a place, returning what to tell whoever sent
+/// them if it wasn't themselves
+fn teleport(
+    engine: &mut Engine,
+    by: PlayerId,
+    player: PlayerId,
+    to: Location,
+) -> Result<String, String> {
+    let character = engine
+        .world
+        .player_characters
+        .get_mut(&player)
+        .ok_or("They have no character in this world")?;
+    let (from, name, ghost) = (
+        character.location,
+        character.name.clone(),
+        character.ghost.is_some(),
+    );
+    if from == to {
+        return Err(match by == player {
+            true => "You're already there".to_string(),
+            false => format!("{name} is already there"),
+        });
+    }
+    character.location = to;
+    character.offer = None;
+    character.conversation = None;
+    engine.world.player_changed(player);
+
+    let sent = match by == player {
+        true => String::new(),
+        false => format!("You send {name} to {}", engine.world.places[&to].name),
+    };
+    if ghost {
+        let look = engine.world.places[&to].ghost_look(&engine.world, "You drift to");
+        engine.connection_broker.send_player_message(player, look);
+        return Ok(sent);
+    }
+    engine.connection_broker.broadcast_to_location(
+        &engine.world,
+        from,
+        format!("{name} vanishes"),
+        Some(player),
+    );
+    engine.connection_broker.broadcast_to_location(
+        &engine.world,
+        to,
+        format!("{name} appears out of thin air"),
+        Some(player),
+    );
+    let start = match by == player {
+        true => "You blink and find yourself in",
+        false => "The world lurches and you find yourself in",
+    };
+    let look = engine.world.places[&to].look(&engine.world, start);
+    engine.connection_broker.send_player_message(player, look);
+    engine.hooks.emit(Event::PlayerEnteredRoom(player, to));
+    visit_place(engine, player);
+    Ok(sent)
+}
+
+pub fn spawn_command() -> Command {
+    Command::new(
+        "spawn",
+        &[],
+        "Admin only, makes items out of nothing and puts them in your pack, like 'spawn iron sword' or 'spawn gold coin 50'",
+        Box::new(|engine, player, args| {
+            let (name, count) = name_and_count(args);
+            let msg = spawn_items(engine, player, &name, count.unwrap_or(1))
+                .unwrap_or_else(|e| e);
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+    .admin()
+}
+
+fn spawn_items(
+    engine: &mut Engine,
+    player: PlayerId,
+    name: &str,
+    count: u32,
+) -> Result<String, String> {
+    let known = obtainable_items(engine);
+    let item = known
+        .iter()
+        .map(String::as_str)
+        .chain([GOLD])
+        .find(|i| i.eq_ignore_ascii_case(name.trim()))
+        .ok_or_else(|| {
+            format!(
+                "There's no item called {name}{}",
+                fuzzy::did_you_mean(name, &known)
+            )
+        })?
+        .to_string();
+    if count == 0 {
+        return Err("Spawn at least one".to_string());
+    }
+
+    let equipment = engine.world.items.contains_key(&item);
+    let character = engine.world.player_character(player);
+    if equipment {
+        for _ in 0..count {
+            character
+                .inventory
+                .add_instance(ItemInstance::new(&item, Quality::Common));
+        }
+    } else {
+        character.inventory.add(&item, count);
+    }
+    let name = character.name.clone();
+    engine
+        .world
+        .record_transaction(&name, &item, count as i64, "spawning it");
+    Ok(format!("{item} x{count} appears in your pack"))
+}
+
+pub fn setstat_command() -> Command {
+    Command::new(
+        "setstat",
+        &[],
+        "Admin only, sets a character's attribute, health or experience, like 'setstat ada strength 14' or 'setstat ada health 20'",
+        Box::new(|engine, player, args| {
+            let text = args.joined();
+            let msg = set_stat(engine, &text).unwrap_or_else(|e| e);
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+    .ghostly()
+    .admin()
+}
+
+fn set_stat(engine: &mut Engine, text: &str) -> Result<String, String> {
+    const USAGE: &str = "Like 'setstat ada strength 14'";
+    let (target, name, rest) = player_named(engine, text).ok_or(USAGE)?;
+    let mut words = rest.split_whitespace();
+    let (Some(stat), Some(value), None) = (words.next(), words.next(), words.next()) else {
+        return Err(USAGE.to_string());
+    };
+    let value: i64 = value
+        .parse()
+        .map_err(|_| format!("'{value}' isn't a number"))?;
+    let Some(character) = engine.world.player_characters.get_mut(&target) else {
+        return Err(format!("{name} has no character in this world"));
+    };
+
+    match stat.to_lowercase().as_str() {
+        "health" => {
+            let max = character.max_health() as i64;
+            if !(1..=max).contains(&value) {
+                return Err(format!("{name}'s health can be from 1 to {max}"));
+            }
+            character.health = value as u32;
+        }
+        "experience" | "xp" => {
+            character.experience =
+                u32::try_from(value).map_err(|_| "Experience can't be negative".to_string())?;
+            character.health = character.health.min(character.max_health());
+        }
+        attribute => {
+            let (_, slot) = character
+                .attributes
+                .named_mut()
+                .into_iter()
+                .find(|(n, _)| *n == attribute)
+                .ok_or_else(|| {
+                    format!("There's no stat called {attribute}, try an attribute, health or experience")
+                })?;
+            if !(1..=MAX_ATTRIBUTE as i64).contains(&value) {
+                return Err(format!("Attributes can be from 1 to {MAX_ATTRIBUTE}"));
+            }
+            *slot = Attribute::new(value as i32);
+            character.health = character.health.min(character.max_health());
+        }
+    }
+    engine.world.player_changed(target);
+    Ok(format!("Set {name}'s {stat} to {value}"))
+}
+
+pub fn kick_command() -> Command {
+    Command::new(
+        "kick",
+        &[],
+        "Admin only, disconnects a player, like 'kick bob'",
+        Box::new(|engine, player, args| {
+            let text = args.joined();
+            let msg = match player_named(engine, &text) {
+                Some((target, _, _)) if target == player => "You can't kick yourself".to_string(),
+                Some((target, name, "")) if engine.connection_broker.is_connected(target) => {
+                    kick(engine, target, "You've been disconnected by an admin");
+                    format!("Kicked {name}")
+                }
+                Some((_, name, "")) => format!("{name} isn't connected"),
+                _ => "Kick who? Like 'kick bob'".to_string(),
+            };
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+    .ghostly()
+    .admin()
+}
+
+/// Tells a player why and drops their connection
+fn kick(engine: &mut Engine, target: PlayerId, why: &str) {
+    engine
+        .connection_broker
+        .send_player_message(target, styled(Style::Warn, why));
+    if let Some(character) = engine.world.player_characters.get(&target) {
+        engine.connection_broker.broadcast_to_location(
+            &engine.world,
+            character.location,
+            format!("{} fades from the dream", character.name),
+            Some(target),
+        );
+    }
+    engine.connection_broker.disconnect_player(target);
+}
+
+pub fn ban_command() -> Command {
+    Command::new(
+        "ban",
+        &[],
+        "Admin only, stops a player logging in and disconnects them, like 'ban bob'. Use 'unban' to let them back",
+        Box::new(|engine, player, args| {
+            let text = args.joined();
+            let msg = match player_named(engine, &text) {
+                Some((target, _, _)) if target == player => "You can't ban yourself".to_string(),
+                Some((target, name, "")) => {
+                    set_account(engine, target, |a| a.banned = true);
+                    if engine.connection_broker.is_connected(target) {
+                        kick(engine, target, "You've been banned by an admin");
+                    }
+                    tracing::info!("{name} was banned");
+                    format!("Banned {name}")
+                }
+                _ => "Ban who? Like 'ban bob'".to_string(),
+            };
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+    .ghostly()
+    .admin()
+}
+
+pub fn unban_command() -> Command {
+    Command::new(
+        "unban",
+        &[],
+        "Admin only, lets a banned player log in again, like 'unban bob'",
+        Box::new(|engine, player, args| {
+            let text = args.joined();
+            let msg = match player_named(engine, &text) {
+                Some((target, name, "")) => {
+                    set_account(engine, target, |a| a.banned = false);
+                    format!("{name} can log in again")
+                }
+                _ => "Unban who? Like 'unban bob'".to_string(),
+            };
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+    .ghostly()
+    .admin()
+}
+
+pub fn role_command() -> Command {
+    Command::new(
+        "role",
+        &[],
+        "Admin only, makes a player an admin or takes it away, like 'role bob admin' or 'role bob player'",
+        Box::new(|engine, player, args| {
+            let text = args.joined();
+            let role = match player_named(engine, &text) {
+                Some((target, _, _)) if target == player => {
+                    Err("You can't change your own role".to_string())
+                }
+                Some((target, name, "admin")) => Ok((target, name, Role::Admin)),
+                Some((target, name, "player")) => Ok((target, name, Role::Player)),
+                _ => Err("Like 'role bob admin' or 'role bob player'".to_string()),
+            };
+            let listed = |target| {
+                let accounts = engine.player_registry.blocking_read();
+                accounts
+                    .get(&target)
+                    .is_some_and(|a| listed_admin(a, &config::get().admins))
+            };
+            let msg = match role {
+                Ok((target, name, Role::Player)) if listed(target) => format!(
+                    "{name} is on the config's list of admins, take them off it there instead"
+                ),
+                Ok((target, name, role)) => {
+                    set_account(engine, target, |a| a.role = role);
+                    let msg = match role {
+                        Role::Admin => "You've been made an admin",
+                        Role::Player => "You're no longer an admin",
+                    };
+                    engine
+                        .connection_broker
+                        .send_player_message(target, msg.to_string());
+                    format!("{name} is now {role:?}")
+                }
+                Err(e) => e,
+            };
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+    .ghostly()
+    .admin()
+}
+
+/// Changes a player's account, logging it if the registry can't be saved
+fn set_account(engine: &Engine, player: PlayerId, update: impl FnOnce(&mut PlayerAccount)) {
+    if let Err(e) = engine.player_registry.blocking_update(player, update) {
+        tracing::error!("Failed to save the player registry: {e}");
+    }
+}
+
+pub fn save_command() -> Command {
+    Command::new(
+        "save",
+        &[],
+        "Admin only, saves the world now rather than waiting for the next autosave",
+        Box::new(|engine, player, _| {
+            state::persist(engine.world.take_changes());
+            engine
+                .connection_broker
+                .send_player_message(player, "Saving the world".to_string());
+        }),
+    )
+    .ghostly()
+    .admin()
+}
+
+pub fn shutdown_command() -> Command {
+    Command::new(
+        "shutdown",
+        &[],
+        "Admin only, saves every realm and stops the server. Type 'shutdown now' to be sure",
+        Box::new(|engine, player, args| {
+            let msg = if args.next() == Some("now") {
+                tracing::info!("An admin is shutting the server down");
+                SHUTTING_DOWN.store(true, Ordering::SeqCst);
+                "Shutting down, every realm will save first"
+            } else {
+                "This stops the whole server, type 'shutdown now' if you're sure"
+            };
+            engine
+                .connection_broker
+                .send_player_message(player, msg.to_string());
+        }),
+    )
+    .ghostly()
+    .admin()
+}
+
+pub fn dig_command() -> Command {
+    Command::new(
+        "dig",
+        &[],
+        "Admin only, makes a new place the given way from here and joins them both ways, like 'dig north Old Cellar'",
+        Box::new(|engine, player, args| {
+            let direction = args.next().unwrap_or_default();
+            let name = args.joined();
+            let msg = dig(engine, player, direction, name).unwrap_or_else(|e| e);
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+    .ghostly()
+    .admin()
+}
+
+fn dig(
+    engine: &mut Engine,
+    player: PlayerId,
+    direction: &str,
+    name: String,
+) -> Result<String, String> {
+    let direction = building::direction_named(direction)?;
+    check_place_name(engine, &name)?;
+    let location = engine.world.player_character(player).location;
+    building::dig(&mut engine.world, location, direction, name.clone())?;
+    Ok(format!(
+        "You dig out {name}, {} from here. 'describe' it once you're there",
+        direction.name()
+    ))
+}
+
+pub fn describe_command() -> Command {
+    Command::new(
+        "describe",
+        &[],
+        "Admin only, rewrites the description of where you are, like 'describe A damp cellar lined with barrels', \
+        or just 'describe' to open the editor on it",
+        Box::new(|engine, player, args| {
+            let description = args.rest().to_string();
+            let location = engine.world.player_character(player).location;
+            if description.is_empty() {
+                let description = engine.world.places[&location].description.clone();
+                prompt::edit(
+                    engine,
+                    player,
+                    "Rewrite the description of where you are",
+                    description,
+                    Box::new(move |engine, player, description| {
+                        if description.is_empty() {
+                            return Answer::Retry("Every place needs describing".to_string());
+                        }
+                        let msg = match engine.world.places.get_mut(&location) {
+                            Some(place) => {
+                                place.description = description.to_string();
+                                engine.world.place_changed(location);
+                                "The description's been rewritten"
+                            }
+                            None => "That place doesn't exist any more",
+                        };
+                        engine
+                            .connection_broker
+                            .send_player_message(player, msg.to_string());
+                        Answer::Done
+                    }),
+                );
+                return;
+            }
+
+            engine.world.places.get_mut(&location).unwrap().description = description;
+            engine.world.place_changed(location);
+            engine
+                .connection_broker
+                .send_player_message(player, "The description's been rewritten".to_string());
+        }),
+    )
+    .ghostly()
+    .admin()
+}
+
+pub fn rename_command() -> Command {
+    Command::new(
+        "rename",
+        &[],
+        "Admin only, renames where you are, like 'rename The Sunken Cellar'",
+        Box::new(|engine, player, args| {
+            let name = args.joined();
+            let location = engine.world.player_character(player).location;
+            let msg = match check_place_name(engine, &name) {
+                Ok(()) => {
+                    let place = engine.world.places.get_mut(&location).unwrap();
+                    let old_name = std::mem::replace(&mut place.name, name.clone());
+                    engine.world.place_changed(location);
+                    format!("{old_name} is now called {name}")
+                }
+                Err(e) => e,
+            };
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+    .ghostly()
+    .admin()
+}
+
+/// Checks a name for a place is allowed and isn't already another place's
+fn check_place_name(engine: &Engine, name: &str) -> Result<(), String> {
+    check_name(name)?;
+    if engine
+        .world
+        .places
+        .values()
+        .any(|p| p.name.eq_ignore_ascii_case(name))
+    {
+        return Err(format!("There's already somewhere called {name}"));
+    }
+    Ok(())
+}
+
+pub fn tag_command() -> Command {
+    Command::new(
+        "tag",
+        &[],
+        "Admin only, lists the tags on where you are, or adds a tag or takes it off again, like 'tag safe'",
+        Box::new(|engine, player, args| {
+            let tag = args.next().unwrap_or_default().to_lowercase();
+            let location = engine.world.player_character(player).location;
+            let place = engine.world.places.get_mut(&location).unwrap();
+            let msg = if tag.is_empty() {
+                let mut tags: Vec<_> = place.tags.iter().map(String::as_str).collect();
+                tags.sort();
+                match tags.is_empty() {
+                    true => format!("{} has no tags", place.name),
+                    false => format!("{} is tagged {}", place.name, tags.join(", ")),
+                }
+            } else if tag == VOID_TAG {
+                "There's only one void, it can't be tagged onto other places".to_string()
+            } else if place.tags.remove(&tag) {
+                engine.world.place_changed(location);
+                format!("Took the {tag} tag off")
+            } else {
+                place.tags.insert(tag.clone());
+                engine.world.place_changed(location);
+                format!("Tagged it {tag}")
+            };
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+    .ghostly()
+    .admin()
+}
+
+pub fn link_command() -> Command {
+    Command::new(
+        "link",
+        &[],
+        "Admin only, joins where you are to another place both ways, like 'link east Old Shrine'",
+        Box::new(|engine, player, args| {
+            let direction = args.next().unwrap_or_default();
+            let name = args.joined();
+            let msg = link(engine, player, direction, &name).unwrap_or_else(|e| e);
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+    .ghostly()
+    .admin()
+}
+
+fn link(
+    engine: &mut Engine,
+    player: PlayerId,
+    direction: &str,
+    name: &str,
+) -> Result<String, String> {
+    let direction = building::direction_named(direction)?;
+    let location = engine.world.player_character(player).location;
+    let to = place_named(engine, name)?;
+    let (to, name) = (to.location, to.name.clone());
+    building::link(&mut engine.world, location, direction, to)?;
+    Ok(format!("The way {} now leads to {name}", direction.name()))
+}
+
+pub fn unlink_command() -> Command {
+    Command::new(
+        "unlink",
+        &[],
+        "Admin only, closes off the way out in a direction and the way back, like 'unlink east'",
+        Box::new(|engine, player, args| {
+            let location = engine.world.player_character(player).location;
+            let msg = building::direction_named(args.next().unwrap_or_default())
+                .and_then(|d| building::unlink(&mut engine.world, location, d).map(|to| (d, to)))
+                .map(|(d, to)| {
+                    let name = engine.world.places.get(&to).map_or("", |p| p.name.as_str());
+                    format!("The way {} to {name} is closed off", d.name())
+                })
+                .unwrap_or_else(|e| e);
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+    .ghostly()
+    .admin()
+}
+
+/// `bug` or `typo`, which file a report for worldbuilders to look at
+pub fn report_command(kind: ReportKind) -> Command {
+    let help = match kind {
+        ReportKind::Bug => "Reports something that's broken where you are, like 'bug the door east leads nowhere'",
+        ReportKind::Typo => "Reports a mistake in the words describing where you are, like 'typo fountian should be fountain'",
+    };
+    Command::new(
+        kind.name(),
+        &[],
+        help,
+        Box::new(move |engine, player, args| {
+            let message = args.rest().to_string();
+            let msg = file_report(engine, player, kind, message).unwrap_or_else(|e| e);
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+    .ghostly()
+}
+
+fn file_report(
+    engine: &mut Engine,
+    player: PlayerId,
+    kind: ReportKind,
+    message: String,
+) -> Result<String, String> {
+    if message.is_empty() {
+        return Err(format!(
+            "What's wrong? Like '{} <what you noticed>'",
+            kind.name()
+        ));
+    }
+    if message.chars().count() > MAX_REPORT_LENGTH {
+        return Err(format!(
+            "That's too long, keep it under {MAX_REPORT_LENGTH} characters"
+        ));
+    }
+    let current_tick = engine.world.current_tick;
+    let character = engine.world.player_character(player);
+    if character.next_report_tick > current_tick {
+        return Err("You've only just filed a report, give it a moment".to_string());
+    }
+    let (character, location) = (character.name.clone(), character.location);
+    let place = &engine.world.places[&location];
+    let report = Report {
+        kind,
+        filed_at: reports::now(),
+        realm: engine.realm.name.clone(),
+        tick: engine.world.current_tick,
+        player,
+        character,
+        location,
+        place: place.name.clone(),
+        description: place.description.clone(),
+        message,
+    };
+
+    match report.file(&state::make_save_path(REPORT_DIR)) {
+        Ok(path) => {
+            engine.world.player_character(player).next_report_tick =
+                current_tick + config::get().seconds_to_ticks(config::get().report_seconds);
+            tracing::info!(
+                "{} filed a {} report, {}",
+                report.character,
+                kind.name(),
+                path.display()
+            );
+            Ok("Thanks, that's been noted for the worldbuilders".to_string())
+        }
+        Err(e) => {
+            tracing::error!("Couldn't file a {} report: {e:#}", kind.name());
+            Err("Sorry, that couldn't be noted down right now".to_string())
+        }
+    }
+}
+
+pub fn deed_command() -> Command {
+    Command::new(
+        "deed",
+        &[],
+        "Admin only, gives where you are to a player as their home, like 'deed bob', or takes it back with 'deed nobody'",
+        Box::new(|engine, player, args| {
+            let text = args.joined();
+            let location = engine.world.player_character(player).location;
+            let owner = match player_named(engine, &text) {
+                _ if engine.world.places[&location].tags.contains(VOID_TAG) => {
+                    Err("Nobody can live in the void".to_string())
+                }
+                _ if text.eq_ignore_ascii_case("nobody") => Ok(None),
+                Some((target, name, "")) => Ok(Some((target, name))),
+                _ => Err("Like 'deed bob' or 'deed nobody'".to_string()),
+            };
+            let msg = match owner {
+                Ok(owner) => {
+                    let place = engine.world.places.get_mut(&location).unwrap();
+                    let place_name = place.name.clone();
+                    place.home = owner.clone().map(|(target, name)| Home::new(target, name));
+                    engine.world.place_changed(location);
+                    match owner {
+                        Some((target, name)) => {
+                            engine.connection_broker.send_player_message(
+                                target,
+                                format!("{place_name} is your home now, 'decorate' it while you're there"),
+                            );
+                            format!("{place_name} is now {name}'s home")
+                        }
+                        None => format!("{place_name} isn't anyone's home any more"),
+                    }
+                }
+                Err(e) => e,
+            };
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+    .ghostly()
+    .admin()
+}
+
+pub fn decorate_command() -> Command {
+    Command::new(
+        "decorate",
+        &[],
+        "Decorates your home while you're in it. 'decorate look <text>' adds to how it looks, \
+        or 'decorate look' alone opens the editor to write more, 'decorate script' writes a script for greetings or ambient messages, and 'decorate clear' \
+        takes it all down. An admin looks changes over before anyone sees them",
+        Box::new(|engine, player, args| {
+            let sub = args.next().unwrap_or_default().to_lowercase();
+            let rest = args.rest().to_string();
+            let msg = decorate(engine, player, &sub, rest).unwrap_or_else(|e| e);
+            if !msg.is_empty() {
+                engine.connection_broker.send_player_message(player, msg);
+            }
+        }),
+    )
+}
+
+fn decorate(
+    engine: &mut Engine,
+    player: PlayerId,
+    sub: &str,
+    rest: String,
+) -> Result<String, String> {
+    let home = home_of(engine, player)?;
+    match sub {
+        "" => Ok(format!(
+            "Showing now:\n{}\nWaiting for an admin:\n{}",
+            home.decoration.describe(),
+            home.pending
+                .as_ref()
+                .map_or("Nothing\n".to_string(), |d| d.describe())
+        )),
+        "look" if rest.is_empty() => {
+            let look = home
+                .pending
+                .as_ref()
+                .unwrap_or(&home.decoration)
+                .look
+                .clone();
+            prompt::edit(
+                engine,
+                player,
+                "Write what's added to how your home looks, leave it empty to take it down",
+                look.unwrap_or_default(),
+                Box::new(|engine, player, look| {
+                    if look.chars().count() > MAX_LOOK_LENGTH {
+                        return Answer::Retry(format!(
+                            "That's too long, keep it under {MAX_LOOK_LENGTH} characters"
+                        ));
+                    }
+                    let look = (!look.is_empty()).then(|| look.to_string());
+                    let msg = submit_decoration(engine, player, |d| d.look = look);
+                    engine
+                        .connection_broker
+                        .send_player_message(player, msg.unwrap_or_else(|e| e));
+                    Answer::Done
+                }),
+            );
+            Ok(String::new())
+        }
+        "look" if rest.chars().count() > MAX_LOOK_LENGTH => Err(format!(
+            "That's too long, keep it under {MAX_LOOK_LENGTH} characters"
+        )),
+        "look" => submit_decoration(engine, player, |d| d.look = Some(rest)),
+        "script" => {
+            let script = home
+                .pending
+                .as_ref()
+                .unwrap_or(&home.decoration)
+                .script
+                .clone();
+            prompt::edit(
+                engine,
+                player,
+                "Write your script, leave it empty to take it down",
+                script.unwrap_or_default(),
+                Box::new(|engine, player, source| {
+                    if source.lines().count() > MAX_SCRIPT_LINES {
+                        return Answer::Retry(format!(
+                            "Scripts can only be {MAX_SCRIPT_LINES} lines long"
+                        ));
+                    }
+                    if scripting::check_decoration(source).is_err() {
+                        return Answer::Retry(
+                            "That script has a syntax error, look it over and try again"
+                                .to_string(),
+                        );
+                    }
+                    let script = (!source.is_empty()).then(|| source.to_string());
+                    let msg = submit_decoration(engine, player, |d| d.script = script);
+                    engine
+                        .connection_broker
+                        .send_player_message(player, msg.unwrap_or_else(|e| e));
+                    Answer::Done
+                }),
+            );
+            Ok(String::new())
+        }
+        "clear" => {
+            home.decoration = Default::default();
+            home.pending = None;
+            let location = engine.world.player_character(player).location;
+            engine.world.place_changed(location);
+            Ok("You take down every decoration".to_string())
+        }
+        _ => Err(
+            "Like 'decorate', 'decorate look <text>', 'decorate script' or 'decorate clear'"
+                .to_string(),
+        ),
+    }
+}
+
+/// The home the player's standing in, if it's theirs
+fn home_of(engine: &mut Engine, player: PlayerId) -> Result<&mut Home, String> {
+    let location = engine.world.player_character(player).location;
+    engine
+        .world
+        .places
+        .get_mut(&location)
+        .and_then(|p| p.home.as_mut())
+        .filter(|h| h.owner == player)
+        .ok_or_else(|| "You can only decorate your own home, while you're in it".to_string())
+}
+
+/// Changes the decoration waiting on the player's home and lets admins know
+/// it's there to look over
+fn submit_decoration(
+    engine: &mut Engine,
+    player: PlayerId,
+    change: impl FnOnce(&mut Decoration),
+) -> Result<String, String> {
+    change(home_of(engine, player)?.draft());
+    let location = engine.world.player_character(player).location;
+    engine.world.place_changed(location);
+    let place = &engine.world.places[&location];
+    let owner = place.home.as_ref().map_or("", |h| h.owner_name.as_str());
+    let msg = format!(
+        "{owner} has decorations for {} waiting, see 'decorations show {}'",
+        place.name, place.name
+    );
+    let admins = engine::admins_online(engine);
+    engine.connection_broker.broadcast_to(admins, msg);
+    Ok("Your decorations are waiting for an admin to look them over".to_string())
+}
+
+pub fn decorations_command() -> Command {
+    Command::new(
+        "decorations",
+        &[],
+        "Admin only, lists homes with decorations waiting to be looked over, or shows, approves or rejects one, like 'decorations show Mill Loft' or 'decorations approve Mill Loft'",
+        Box::new(|engine, player, args| {
+            let sub = args.next().unwrap_or_default().to_lowercase();
+            let name = args.joined();
+            let msg = review_decorations(engine, &sub, &name).unwrap_or_else(|e| e);
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+    .ghostly()
+    .admin()
+}
+
+fn review_decorations(engine: &mut Engine, sub: &str, name: &str) -> Result<String, String> {
+    if sub.is_empty() {
+        let mut waiting: Vec<_> = engine
+            .world
+            .places
+            .values()
+            .filter_map(|p| {
+                let home = p.home.as_ref().filter(|h| h.pending.is_some())?;
+                Some(format!("{} ({})", p.name, home.owner_name))
+            })
+            .collect();
+        waiting.sort();
+        return match waiting.is_empty() {
+            true => Ok("No decorations are waiting to be looked over".to_string()),
+            false => Ok(format!("Decorations waiting\n\n{}", waiting.join("\n"))),
+        };
+    }
+
+    let location = place_named(engine, name)?.location;
+    let place = engine.world.places.get_mut(&location).unwrap();
+    let place_name = place.name.clone();
+    let Some(home) = place.home.as_mut().filter(|h| h.pending.is_some()) else {
+        return Err(format!("{place_name} has no decorations waiting"));
+    };
+    let (owner, pending) = (home.owner, home.pending.take().unwrap());
+    let (msg, told) = match sub {
+        "show" => {
+            home.pending = Some(pending.clone());
+            return Ok(format!(
+                "{place_name}, {}'s home\n\nShowing now:\n{}\nWaiting:\n{}",
+                home.owner_name,
+                home.decoration.describe(),
+                pending.describe()
+            ));
+        }
+        "approve" => {
+            home.decoration = pending;
+            (
+                format!("{place_name}'s decorations are up"),
+                format!("Your decorations in {place_name} were approved"),
+            )
+        }
+        "reject" => (
+            format!("{place_name}'s decorations were turned down"),
+            format!("Your decorations in {place_name} were turned down"),
+        ),
+        _ => {
+            home.pending = Some(pending);
+            return Err("Like 'decorations show <home>', 'decorations approve <home>' or 'decorations reject <home>'".to_string());
+        }
+    };
+    engine.world.place_changed(location);
+    engine.connection_broker.send_player_message(owner, told);
+    Ok(msg)
+}
+
+/// Whether the player's account has the admin role, or their username's on the
+/// configured list of admins
+pub fn is_admin(engine: &Engine, player: PlayerId) -> bool {
+    let accounts = engine.player_registry.blocking_read();
+    accounts
+        .get(&player)
+        .is_some_and(|p| p.role == Role::Admin || listed_admin(p, &config::get().admins))
+}
+
+/// Whether the account's username is on a list of admins, whatever its role
+pub fn listed_admin(account: &PlayerAccount, admins: &[String]) -> bool {
+    admins
+        .iter()
+        .any(|a| a.eq_ignore_ascii_case(&account.username))
+}
+
+pub fn quit_command() -> Command {
+    Command::new(
+        "quit",
+        &["exit"],
+        "Log your character out of the game world and exit the session",
+        Box::new(|engine, player, _| {
+            let current_tick = engine.world.current_tick;
+            let character = engine.world.player_character(player);
+            let location = character.location;
+
+            if character.in_combat(current_tick) {
+                let seconds = (character.in_combat_until - current_tick) as f64
+                    / config::get().ticks_per_second;
+                engine.connection_broker.send_player_message(
+                    player,
+                    format!("You can't leave in the middle of a fight! Try again in {seconds:.0} seconds"),
+                );
+                return;
+            }
+
+            if engine.rules.safe_logout_only
+                && !engine.world.places[&location].tags.contains(SAFE_TAG)
+            {
+                engine.connection_broker.send_player_message(
+                    player,
+                    "It isn't safe to leave here, find a safe room or 'camp' to log out"
+                        .to_string(),
+                );
+                return;
+            }
+
+            let player_reg = engine.player_registry.blocking_read();
+            let name = player_reg
+                .get(&player)
+                .map(|p| p.username.as_str())
+                .unwrap_or("");
+
+            engine
+                .connection_broker
+                .send_player_message(player, format!("Logging out, goodbye {name}!"));
+            engine.connection_broker.broadcast_to_location(
+                &engine.world,
+                location,
+                format!("{name} lies down and drifts off to sleep"),
+                Some(player),
+            );
+            engine.connection_broker.disconnect_player(player);
+        }),
+    )
+    .ghostly()
+}
+
+pub fn bind_command() -> Command {
+    Command::new(
+        "bind",
+        &[],
+        "Binds you to the shrine or inn you're in, you'll come to here after being knocked out and recall scrolls bring you back. Use 'bind scroll' to buy a recall scroll here, or 'bind' anywhere else to see where you're bound",
+        Box::new(|engine, player, args| {
+            let res = match args.next() {
+                Some("scroll") => buy_recall_scroll(engine, player),
+                _ => bind(engine, player),
+            }
+            .unwrap_or_else(|e| e);
+            engine.connection_broker.send_player_message(player, res);
+        }),
+    )
+}
+
+fn bind(engine: &mut Engine, player: PlayerId) -> Result<String, String> {
+    let character = engine.world.player_character(player);
+    let (location, bound_to) = (character.location, character.bound_to);
+    if !engine.world.is_bind_point(location) {
+        let bound = bound_to
+            .and_then(|l| engine.world.places.get(&l))
+            .map(|p| format!("You're bound to {}", p.name))
+            .unwrap_or_else(|| "You aren't bound anywhere".to_string());
+        return Err(format!(
+            "{bound}. You can only bind yourself at a shrine or inn"
+        ));
+    }
+    if bound_to == Some(location) {
+        return Err("You're already bound here".to_string());
+    }
+
+    engine.world.player_character(player).bound_to = Some(location);
+    Ok(format!(
+        "You bind yourself to {}, you'll come to here after being knocked out and recall scrolls will bring you back",
+        styled(Style::Title, &engine.world.places[&location].name)
+    ))
+}
+
+fn buy_recall_scroll(engine: &mut Engine, player: PlayerId) -> Result<String, String> {
+    let cost = config::get().recall_scroll_cost;
+    let character = engine.world.player_character(player);
+    let location = character.location;
+    if !engine.world.is_bind_point(location) {
+        return Err("Recall scrolls are sold at shrines and inns".to_string());
+    }
+    let character = engine.world.player_character(player);
+    if !character.inventory.remove(GOLD, cost) {
+        return Err(format!(
+            "A {RECALL_SCROLL} costs {cost} {GOLD}s, more than you have"
+        ));
+    }
+    character.inventory.add(RECALL_SCROLL, 1);
+    let name = character.name.clone();
+    engine
+        .world
+        .record_transaction(&name, GOLD, -i64::from(cost), "a recall scroll");
+    engine
+        .world
+        .record_transaction(&name, RECALL_SCROLL, 1, "a recall scroll");
+    // What's paid goes to the village, like market taxes
+    if let Some(faction) = engine.world.faction_mut(location) {
+        faction.deposit(cost);
+    }
+    Ok(format!(
+        "You buy a {RECALL_SCROLL} for {cost} {GOLD}s, 'recall' to read it"
+    ))
+}
+
+pub fn recall_command() -> Command {
+    Command::new(
+        "recall",
+        &[],
+        "Reads a recall scroll, bringing you back to where you're bound. Takes a while and is interrupted by moving or fighting",
+        Box::new(|engine, player, _| {
+            let res = start_recall(engine, player).unwrap_or_else(|e| e);
+            engine.connection_broker.send_player_message(player, res);
+        }),
+    )
+}
+
+fn start_recall(engine: &mut Engine, player: PlayerId) -> Result<String, String> {
+    let current_tick = engine.world.current_tick;
+    let seconds = config::get().recall_seconds;
+    let character = engine.world.player_character(player);
+    let bound_to = character.bound_to;
+    if character.inventory.get(RECALL_SCROLL).is_none() {
+        return Err(format!(
+            "You don't have a {RECALL_SCROLL}, shrines and inns sell them with 'bind scroll'"
+        ));
+    }
+    if character.in_combat(current_tick) {
+        return Err("You can't read a scroll in the middle of a fight!".to_string());
+    }
+    if character.recalling_until.is_some() {
+        return Err("You're already reading a recall scroll".to_string());
+    }
+    let Some(place) = bound_to.and_then(|l| engine.world.places.get(&l)) else {
+        return Err(
+            "You aren't bound anywhere for the scroll to take you, 'bind' at a shrine or inn"
+                .to_string(),
+        );
+    };
+    let name = place.name.clone();
+
+    engine.world.player_character(player).recalling_until =
+        Some(current_tick + config::get().seconds_to_ticks(seconds));
+    Ok(format!(
+        "You start reading the recall scroll, you'll be back in {name} in {seconds:.0} seconds"
+    ))
+}
+
+pub fn revive_command() -> Command {
+    Command::new(
+        "revive",
+        &["resurrect"],
+        "Brings a ghost back to life, where their body lies, at a shrine or where they're bound. Reviving away from your body leaves some of your gold behind with it",
+        Box::new(|engine, player, _| {
+            let res = revive(engine, player).unwrap_or_else(|e| e);
+            engine.connection_broker.send_player_message(player, res);
+        }),
+    )
+    .ghostly()
+}
+
+fn revive(engine: &mut Engine, player: PlayerId) -> Result<String, String> {
+    let character = engine.world.player_character(player);
+    let (location, name) = (character.location, character.name.clone());
+    let Some(body) = character.ghost else {
+        return Err("You're not a ghost".to_string());
+    };
+    let bound_here = character.bound_to == Some(location);
+    if location != body && !bound_here && !engine.world.is_shrine(location) {
+        return Err(format!(
+            "You can only revive where your body lies, at a shrine or where you're bound, your body's back in {}",
+            engine
+                .world
+                .locale_name(body)
+                .unwrap_or("somewhere strange")
+        ));
+    }
+
+    let death_penalty = engine.rules.death_penalty;
+    let character = engine.world.player_character(player);
+    character.ghost = None;
+    character.health = character.max_health();
+    let (msg, seen) = if location == body {
+        (
+            "You slip back into your body and open your eyes, battered but alive".to_string(),
+            format!("{name} stirs and gets to their feet"),
+        )
+    } else {
+        let gold = character
+            .inventory
+            .get(GOLD)
+            .map(|g| g.count)
+            .unwrap_or_default();
+        let lost = (gold as f64 * death_penalty).round() as u32;
+        character.inventory.remove(GOLD, lost);
+        engine.world.place_changed(body);
+        if let Some(place) = engine.world.places.get_mut(&body) {
+            place.ground.add(GOLD, lost);
+        }
+        let mut msg = "A warm light gathers and you wake in a new body".to_string();
+        if lost > 0 {
+            msg.push_str(&format!(
+                ", the {lost} {GOLD} you had stayed with the old one"
+            ));
+        }
+        (
+            msg,
+            format!("The candles flare and {name} appears before the altar"),
+        )
+    };
+    engine
+        .connection_broker
+        .broadcast_to_location(&engine.world, location, seen, Some(player));
+    Ok(msg)
+}
+
+pub fn loot_command() -> Command {
+    Command::new(
+        "loot",
+        &["retrieve"],
+        "Gathers up everything from your corpse, if you left one here when you were knocked out",
+        Box::new(|engine, player, _| {
+            let res = loot(engine, player).unwrap_or_else(|e| e);
+            engine.connection_broker.send_player_message(player, res);
+        }),
+    )
+}
+
+fn loot(engine: &mut Engine, player: PlayerId) -> Result<String, String> {
+    let world = &mut engine.world;
+    let location = world.player_character(player).location;
+    let place = world.places.get_mut(&location).unwrap();
+    let Some(idx) = place.corpses.iter().position(|c| c.owner == player) else {
+        return Err(match place.corpses.first() {
+            Some(corpse) => format!("That's {}'s corpse, leave it be", corpse.owner_name),
+            None => "Your corpse isn't here".to_string(),
+        });
+    };
+
+    let corpse = place.corpses.remove(idx);
+    let names: Vec<_> = corpse
+        .contents
+        .equipment()
+        .iter()
+        .map(|i| i.name())
+        .chain(corpse.contents.stacks().map(|s| match s.count {
+            1 => s.name.clone(),
+            count => format!("{} x{count}", s.name),
+        }))
+        .collect();
+    world.place_changed(location);
+    let character = world.player_character(player);
+    character.inventory.append(corpse.contents);
+    let name = character.name.clone();
+
+    engine.connection_broker.broadcast_to_location(
+        &engine.world,
+        location,
+        format!("{name} gathers their things from their corpse"),
+        Some(player),
+    );
+    Ok(format!(
+        "You gather your things from your corpse: {}",
+        names.join(", ")
+    ))
+}
+
+pub fn stop_command() -> Command {
+    Command::new(
+        "stop",
+        &["cancel"],
+        "Stops whatever you're busy doing, like crafting. Anything you lined up after still happens, 'clear' to forget it",
+        Box::new(|engine, player, _| {
+            let msg = match engine.world.player_character(player).action.take() {
+                Some(action) => format!("You stop {}", action.verb),
+                None => "You aren't busy doing anything".to_string(),
+            };
+
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+    .immediate()
+}
+
+pub fn clear_command() -> Command {
+    Command::new(
+        "clear",
+        &[],
+        "Forgets every command you lined up while you were busy, and stops waiting on anyone you asked for a story",
+        Box::new(|engine, player, _| {
+            let cleared = engine.world.player_character(player).input.clear();
+            let mut waited = false;
+            for waiting in engine.pending_stories.values_mut() {
+                waited |= waiting.contains(&player);
+                waiting.retain(|p| *p != player);
+            }
+
+            let mut msg = match cleared {
+                0 => "You don't have anything lined up".to_string(),
+                1 => "You forget the command you had lined up".to_string(),
+                n => format!("You forget the {n} commands you had lined up"),
+            };
+            if waited {
+                msg.push_str(", and stop waiting for a story");
+            }
+
+            engine.connection_broker.send_player_message(player, msg);
+        }),
+    )
+    .immediate()
+    .ghostly()
+}
+
+pub fn camp_command() -> Command {
+    Command::new(
+        "camp",
+        &[],
+        "Makes camp to log out somewhere that isn't a safe room. Takes a while and is interrupted by moving or fighting",
+        Box::new(|engine, player, _| {
+            let current_tick = engine.world.current_tick;
+            let camp_ticks = config::get().seconds_to_ticks(config::get().camp_seconds);
+            let character = engine.world.player_character(player);
+
+            let msg = if character.in_combat(current_tick) {
+                "You can't make camp in the middle of a fight!".to_string()
+            } else if character.camping_until.is_some() {
+                "You're already making camp".to_string()
+            } else {
+                character.camping_until = Some(current_tick + camp_ticks);
+                format!(
+                    "You start making camp, you'll be settled in {:.0} seconds",
+                    config::get().camp_seconds
+                )
+            };
+
+            engine.connection_broker.send_player_message(player, msg);
         }),
     )
 }
@@ -178,7 +6398,8 @@ pub fn help_command() -> Command {
                     let mut res = String::new();
                     res.push_str("Listing all commands\nRun 'help <command name>' to get help for a specific command\n\n");
                     let mut count = 0;
-                    for cmd in get_command_list() {
+                    let admin = is_admin(engine, player);
+                    for cmd in get_command_list().iter().filter(|c| admin || !c.admin) {
                         res.push_str(&format!("{:20}", cmd.name));
                         count += 1;
                         if count % 4 == 0 {
@@ -192,4 +6413,5 @@ pub fn help_command() -> Command {
             engine.connection_broker.send_player_message(player, res);
         }),
     )
+    .ghostly()
 }
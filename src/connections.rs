@@ -1,46 +1,137 @@
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use crossbeam::channel::Receiver;
 use crossbeam::channel::Sender;
 use tokio::sync::mpsc::UnboundedReceiver as TokioReceiver;
 use tokio::sync::mpsc::UnboundedSender as TokioSender;
 
+use crate::mud::world::{Location, World};
 use crate::state::PlayerId;
-use crate::AppErrors;
+use crate::{config, AppErrors};
 
 pub type MudMessage = String;
+/// What the engine sends a player. It's shared rather than copied when the same
+/// message goes out to many players at once.
+pub type Outbound = Arc<str>;
+/// A document the engine's left for the player's connection to open in the
+/// editor, see [`crate::editor`]
+pub type EditorSlot = Arc<Mutex<Option<String>>>;
 
 /// The connection object the engine holds to talk to the player
 #[derive(Debug, Clone)]
 pub struct PlayerConnection(
     pub PlayerId,
     pub Receiver<MudMessage>,
-    pub TokioSender<MudMessage>,
+    pub TokioSender<Outbound>,
+    pub TokenBucket,
+    pub EditorSlot,
 );
 
+/// Limits how fast a player's commands are taken, allowing short bursts
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    tokens: f64,
+    burst: f64,
+    per_second: f64,
+    last: Instant,
+    /// Whether the player's been told to slow down since they last ran out
+    warned: bool,
+}
+
+impl TokenBucket {
+    pub fn new(burst: u32, per_second: f64, now: Instant) -> Self {
+        Self {
+            tokens: burst as f64,
+            burst: burst as f64,
+            per_second,
+            last: now,
+            warned: false,
+        }
+    }
+
+    /// Takes a token if there's one, refilling for the time since last asked
+    pub fn take(&mut self, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.per_second).min(self.burst);
+        self.last = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            self.warned = false;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Whether to warn the player, only once each time they run out
+    fn warn(&mut self) -> bool {
+        !std::mem::replace(&mut self.warned, true)
+    }
+}
+
 impl PlayerConnection {
     pub fn poll(&mut self) -> anyhow::Result<MudMessage> {
         Ok(self.1.try_recv()?)
     }
+
+    /// The player's next message, dropping any sent faster than their rate limit allows
+    fn next_message(&mut self, now: Instant) -> Option<MudMessage> {
+        while let Ok(msg) = self.poll() {
+            if self.3.take(now) {
+                return Some(msg);
+            }
+
+            if self.3.warn() {
+                let warning = "Whoa, slow down! Some of what you sent was ignored";
+                if let Err(e) = self.2.send(warning.into()) {
+                    tracing::error!("Error sending to player {e}");
+                }
+            }
+        }
+
+        None
+    }
 }
 
 /// The connection object the player task holds to talk to the engine
 #[derive(Debug)]
-pub struct EngineConnection(PlayerId, TokioReceiver<MudMessage>, Sender<MudMessage>);
+pub struct EngineConnection(
+    PlayerId,
+    TokioReceiver<Outbound>,
+    Sender<MudMessage>,
+    EditorSlot,
+);
 
 impl EngineConnection {
+    pub fn player(&self) -> PlayerId {
+        self.0
+    }
+
     pub fn send(&mut self, msg: MudMessage) -> anyhow::Result<()> {
         self.2.send(msg)?;
         Ok(())
     }
 
-    pub async fn recv(&mut self) -> anyhow::Result<MudMessage> {
+    /// The next message from the engine if one's arrived, without waiting
+    pub fn try_recv(&mut self) -> Option<Outbound> {
+        self.1.try_recv().ok()
+    }
+
+    pub async fn recv(&mut self) -> anyhow::Result<Outbound> {
         let msg = self.1.recv().await;
         match msg {
             Some(m) => Ok(m),
             None => Err(AppErrors::PlayerDisconnected(self.0).into()),
         }
     }
+
+    /// The document to open the editor on, if the engine's asked for it
+    pub fn take_editor(&self) -> Option<String> {
+        self.3.lock().ok()?.take()
+    }
 }
 
 pub enum PlayerConnectMsg {
@@ -60,12 +151,23 @@ impl PlayerConnectionBroker {
     pub fn setup_connection(&self, player_id: PlayerId) -> EngineConnection {
         let (s_engine, r_engine) = crossbeam::channel::unbounded();
         let (s_player, r_player) = tokio::sync::mpsc::unbounded_channel();
+        let config = config::get();
+        let bucket = TokenBucket::new(
+            config.command_burst,
+            config.commands_per_second,
+            Instant::now(),
+        );
+        let editor = EditorSlot::default();
         self.0
             .send(PlayerConnectMsg::Connect(PlayerConnection(
-                player_id, r_engine, s_player,
+                player_id,
+                r_engine,
+                s_player,
+                bucket,
+                editor.clone(),
             )))
             .expect("Join message send to engine shouldn't error");
-        EngineConnection(player_id, r_player, s_engine)
+        EngineConnection(player_id, r_player, s_engine, editor)
     }
 
     pub fn end_connection(&self, player_id: PlayerId) {
@@ -79,6 +181,13 @@ impl PlayerConnectionBroker {
 pub struct EngineConnectionBroker {
     incoming_connections: Receiver<PlayerConnectMsg>,
     player_connections: HashMap<PlayerId, PlayerConnection>,
+    /// Messages waiting to go out at the end of the tick, so everything a player
+    /// is told in one tick arrives together
+    outbox: HashMap<PlayerId, Vec<Outbound>>,
+    /// When each player last sent something, or connected if they haven't yet
+    last_input: HashMap<PlayerId, Instant>,
+    /// Who last sent each player a private message, for replying to
+    last_tell: HashMap<PlayerId, PlayerId>,
 }
 
 impl EngineConnectionBroker {
@@ -86,41 +195,289 @@ impl EngineConnectionBroker {
         Self {
             incoming_connections,
             player_connections: HashMap::new(),
+            outbox: HashMap::new(),
+            last_input: HashMap::new(),
+            last_tell: HashMap::new(),
         }
     }
 
-    pub fn handle_connection_changes(&mut self) {
+    /// Adds and removes player connections, returning the players that just connected
+    pub fn handle_connection_changes(&mut self) -> Vec<PlayerId> {
+        let mut connected = Vec::new();
+
         while let Ok(msg) = self.incoming_connections.try_recv() {
             match msg {
-                PlayerConnectMsg::Connect(player_connection) => self
-                    .player_connections
-                    .insert(player_connection.0, player_connection),
+                PlayerConnectMsg::Connect(player_connection) => {
+                    connected.push(player_connection.0);
+                    self.last_input.insert(player_connection.0, Instant::now());
+                    self.player_connections
+                        .insert(player_connection.0, player_connection);
+                }
                 PlayerConnectMsg::Disconnect(player_id) => {
-                    self.player_connections.remove(&player_id)
+                    connected.retain(|p| *p != player_id);
+                    self.player_connections.remove(&player_id);
+                    self.outbox.remove(&player_id);
+                    self.last_input.remove(&player_id);
+                    self.last_tell.remove(&player_id);
                 }
             };
         }
+
+        connected
     }
 
-    pub fn poll_player_messages(&mut self) -> Option<(PlayerId, MudMessage)> {
-        for player_connection in self.player_connections.values_mut() {
-            if let Ok(msg) = player_connection.poll() {
-                return Some((player_connection.0, msg));
+    /// Takes up to `per_player` messages from each player, one from everyone
+    /// in turn so nobody waits behind a busy player. The rest wait for next tick
+    pub fn poll_player_messages(&mut self, per_player: usize) -> Vec<(PlayerId, MudMessage)> {
+        let now = Instant::now();
+        let mut batch = Vec::new();
+        for _ in 0..per_player {
+            let before = batch.len();
+            for player_connection in self.player_connections.values_mut() {
+                if let Some(msg) = player_connection.next_message(now) {
+                    self.last_input.insert(player_connection.0, now);
+                    batch.push((player_connection.0, msg));
+                }
+            }
+            if batch.len() == before {
+                break;
             }
         }
 
-        None
+        batch
     }
 
+    /// Queues a message for the player, it's sent when the tick's output is flushed
     pub fn send_player_message(&mut self, player: PlayerId, msg: MudMessage) {
-        if let Some(player_connection) = self.player_connections.get(&player) {
-            if let Err(e) = player_connection.2.send(msg) {
-                tracing::error!("Error sending to player {e}");
+        if self.player_connections.contains_key(&player) {
+            self.outbox.entry(player).or_default().push(msg.into());
+        }
+    }
+
+    /// Has the player's connection open the editor on a document, their lines
+    /// go to it until they save or give up
+    pub fn open_editor(&mut self, player: PlayerId, document: String) {
+        if let Some(connection) = self.player_connections.get(&player) {
+            if let Ok(mut slot) = connection.4.lock() {
+                *slot = Some(document);
             }
         }
     }
 
+    /// Queues one message for many players, sharing it between them
+    pub fn broadcast_to(&mut self, players: impl IntoIterator<Item = PlayerId>, msg: MudMessage) {
+        let msg: Outbound = msg.into();
+        for player in players {
+            if self.player_connections.contains_key(&player) {
+                self.outbox.entry(player).or_default().push(msg.clone());
+            }
+        }
+    }
+
+    /// Passes a private message from one player to another, remembering who
+    /// sent it so the recipient can reply
+    pub fn tell(&mut self, from: PlayerId, to: PlayerId, msg: MudMessage) {
+        if self.player_connections.contains_key(&to) {
+            self.last_tell.insert(to, from);
+            self.send_player_message(to, msg);
+        }
+    }
+
+    /// Who last sent the player a private message
+    pub fn last_tell(&self, player: PlayerId) -> Option<PlayerId> {
+        self.last_tell.get(&player).copied()
+    }
+
+    /// Sends a message to every connected player
+    pub fn broadcast(&mut self, msg: MudMessage) {
+        let players: Vec<_> = self.player_connections.keys().copied().collect();
+        self.broadcast_to(players, msg);
+    }
+
+    /// Sends a message to every connected player whose character is at the location,
+    /// optionally leaving one out, usually whoever caused the message
+    pub fn broadcast_to_location(
+        &mut self,
+        world: &World,
+        location: Location,
+        msg: MudMessage,
+        exclude: Option<PlayerId>,
+    ) {
+        self.broadcast_to_room(world, location, msg, exclude.as_slice());
+    }
+
+    /// Sends a message to every connected player whose character is at the
+    /// location, except those left out, like both sides of a social
+    pub fn broadcast_to_room(
+        &mut self,
+        world: &World,
+        location: Location,
+        msg: MudMessage,
+        exclude: &[PlayerId],
+    ) {
+        let present = world
+            .player_characters
+            .iter()
+            .filter(|(id, c)| c.location == location && !exclude.contains(id))
+            .map(|(id, _)| *id);
+
+        self.broadcast_to(present, msg);
+    }
+
+    /// Sends everything queued this tick, joining each player's messages into one
+    pub fn flush(&mut self) {
+        let players: Vec<_> = self.outbox.keys().copied().collect();
+        for player in players {
+            self.flush_player(player);
+        }
+    }
+
+    fn flush_player(&mut self, player: PlayerId) {
+        let Some(mut msgs) = self.outbox.remove(&player) else {
+            return;
+        };
+        let Some(player_connection) = self.player_connections.get(&player) else {
+            return;
+        };
+
+        let msg = match msgs.len() {
+            1 => msgs.pop().unwrap(),
+            _ => msgs.join("\n").into(),
+        };
+        if let Err(e) = player_connection.2.send(msg) {
+            tracing::error!("Error sending to player {e}");
+        }
+    }
+
+    /// How many players are currently connected
+    pub fn connected(&self) -> usize {
+        self.player_connections.len()
+    }
+
+    /// Every connected player and how long since they last sent anything
+    pub fn online(&self) -> Vec<(PlayerId, Duration)> {
+        let now = Instant::now();
+        self.player_connections
+            .keys()
+            .map(|p| {
+                let idle = self
+                    .last_input
+                    .get(p)
+                    .map_or(Duration::ZERO, |t| now.saturating_duration_since(*t));
+                (*p, idle)
+            })
+            .collect()
+    }
+
+    pub fn is_connected(&self, player: PlayerId) -> bool {
+        self.player_connections.contains_key(&player)
+    }
+
+    /// Drops the player's connection, after sending them whatever they're still owed
     pub fn disconnect_player(&mut self, player: PlayerId) {
+        self.flush_player(player);
         self.player_connections.remove(&player);
+        self.last_input.remove(&player);
+        self.last_tell.remove(&player);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    #[test]
+    fn drains_in_turns() {
+        let (player_broker, mut engine_broker) = PlayerConnectionBroker::new();
+        let (busy, quiet) = (PlayerId::new_random(), PlayerId::new_random());
+        let mut busy_connection = player_broker.setup_connection(busy);
+        let mut quiet_connection = player_broker.setup_connection(quiet);
+        engine_broker.handle_connection_changes();
+
+        for i in 0..5 {
+            busy_connection.send(format!("busy {i}")).unwrap();
+        }
+        quiet_connection.send("quiet".to_string()).unwrap();
+
+        let batch = engine_broker.poll_player_messages(2);
+        assert_eq!(batch.len(), 3);
+        assert!(batch[..2].iter().any(|(p, _)| *p == quiet));
+        assert_eq!(batch[2], (busy, "busy 1".to_string()));
+
+        let batch = engine_broker.poll_player_messages(2);
+        let msgs: Vec<_> = batch.into_iter().map(|(_, m)| m).collect();
+        assert_eq!(msgs, ["busy 2", "busy 3"]);
+
+        let online: HashSet<_> = engine_broker.online().into_iter().map(|(p, _)| p).collect();
+        assert_eq!(online, HashSet::from([busy, quiet]));
+    }
+
+    #[test]
+    fn coalesces_output() {
+        let (player_broker, mut engine_broker) = PlayerConnectionBroker::new();
+        let (ada, bryn) = (PlayerId::new_random(), PlayerId::new_random());
+        let mut ada_connection = player_broker.setup_connection(ada);
+        let mut bryn_connection = player_broker.setup_connection(bryn);
+        engine_broker.handle_connection_changes();
+
+        engine_broker.send_player_message(ada, "You wave".to_string());
+        engine_broker.broadcast("The bells ring".to_string());
+        assert!(bryn_connection.1.try_recv().is_err());
+
+        engine_broker.flush();
+        assert_eq!(
+            &*ada_connection.1.try_recv().unwrap(),
+            "You wave\nThe bells ring"
+        );
+        let bells = bryn_connection.1.try_recv().unwrap();
+        assert_eq!(&*bells, "The bells ring");
+        assert!(ada_connection.1.try_recv().is_err());
+
+        engine_broker.send_player_message(bryn, "Goodbye".to_string());
+        engine_broker.disconnect_player(bryn);
+        assert_eq!(&*bryn_connection.1.try_recv().unwrap(), "Goodbye");
+    }
+
+    #[test]
+    fn remembers_who_told() {
+        let (player_broker, mut engine_broker) = PlayerConnectionBroker::new();
+        let (ada, bryn) = (PlayerId::new_random(), PlayerId::new_random());
+        let mut bryn_connection = player_broker.setup_connection(bryn);
+        engine_broker.handle_connection_changes();
+
+        engine_broker.tell(
+            ada,
+            bryn,
+            "Ada tells you, \"meet me at the well\"".to_string(),
+        );
+        engine_broker.flush();
+        assert!(bryn_connection.1.try_recv().is_ok());
+        assert_eq!(engine_broker.last_tell(bryn), Some(ada));
+
+        // Nobody's there to hear it, so there's nobody to reply to
+        engine_broker.tell(bryn, ada, "Bryn tells you, \"on my way\"".to_string());
+        assert_eq!(engine_broker.last_tell(ada), None);
+
+        engine_broker.disconnect_player(bryn);
+        assert_eq!(engine_broker.last_tell(bryn), None);
+    }
+
+    #[test]
+    fn limits_bursts() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(3, 2.0, start);
+        assert!((0..3).all(|_| bucket.take(start)));
+        assert!(!bucket.take(start));
+        assert!(bucket.warn());
+        assert!(!bucket.warn());
+
+        let later = start + Duration::from_millis(500);
+        assert!(bucket.take(later));
+        assert!(!bucket.take(later));
+
+        let much_later = later + Duration::from_secs(60);
+        assert_eq!((0..10).filter(|_| bucket.take(much_later)).count(), 3);
     }
 }
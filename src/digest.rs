@@ -0,0 +1,252 @@
+//! A daily digest of how a realm is getting on, posted to a webhook so whoever
+//! runs the server can keep an eye on it without logging in. It's pieced
+//! together from the balance telemetry, each place's history and the errors
+//! the engine ran into since the last digest went out.
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::runtime::Handle;
+
+use crate::{
+    commands::GOLD,
+    mud::{history::EventKind, items::Inventory, world::World},
+};
+
+/// The most knockouts and errors listed by name, the rest are only counted
+const MAX_LISTED: usize = 10;
+/// Discord turns away messages longer than this
+const DISCORD_LIMIT: usize = 2000;
+
+/// Which shape of message the webhook expects
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WebhookKind {
+    Discord,
+    Slack,
+    /// The digest's fields as they are, for anything else
+    #[default]
+    Json,
+}
+
+/// What's happened since the last digest, kept by the engine as it goes
+#[derive(Debug)]
+pub struct DigestLog {
+    /// The tick the last digest went out, or the engine started
+    pub since_tick: u64,
+    pub places_generated: usize,
+    pub errors: usize,
+    /// The first few errors, the rest are only counted
+    recent_errors: Vec<String>,
+    /// Fights in the telemetry when the log started
+    fights_before: u32,
+    client: reqwest::Client,
+    /// Where webhooks are posted from, the engine's own thread isn't async
+    runtime: Option<Handle>,
+}
+
+impl DigestLog {
+    pub fn new(world: &World, runtime: Option<Handle>) -> Self {
+        Self {
+            since_tick: world.current_tick,
+            places_generated: 0,
+            errors: 0,
+            recent_errors: Vec::new(),
+            fights_before: total_fights(world),
+            client: reqwest::Client::new(),
+            runtime,
+        }
+    }
+
+    pub fn error(&mut self, why: String) {
+        self.errors += 1;
+        if self.recent_errors.len() < MAX_LISTED {
+            self.recent_errors.push(why);
+        }
+    }
+
+    /// Writes up everything since the last digest, without starting over
+    pub fn digest(&self, realm: &str, world: &World) -> Digest {
+        let mut knockouts: Vec<_> = world
+            .history
+            .iter()
+            .flat_map(|(location, events)| events.iter().map(move |e| (location, e)))
+            .filter(|(_, e)| e.tick >= self.since_tick)
+            .filter_map(|(location, e)| match &e.kind {
+                EventKind::KnockedOut(..) => {
+                    let place = world.places.get(location).map_or("somewhere", |p| &p.name);
+                    Some((e.tick, format!("{} in {place}", e.kind)))
+                }
+                _ => None,
+            })
+            .collect();
+        knockouts.sort();
+
+        let gold = |inventory: &Inventory| inventory.get(GOLD).map_or(0, |s| s.count);
+
+        Digest {
+            realm: realm.to_string(),
+            day: world.time().day,
+            places_generated: self.places_generated,
+            total_places: world.places.len(),
+            fights: total_fights(world).saturating_sub(self.fights_before),
+            knockouts: knockouts.len(),
+            notable_knockouts: knockouts
+                .into_iter()
+                .rev()
+                .take(MAX_LISTED)
+                .map(|(_, k)| k)
+                .collect(),
+            characters: world.player_characters.len(),
+            gold_carried: world
+                .player_characters
+                .values()
+                .map(|c| gold(&c.inventory))
+                .sum(),
+            council_treasuries: world.factions.values().map(|f| f.treasury).sum(),
+            open_work_orders: world.work_orders.len(),
+            errors: self.errors,
+            recent_errors: self.recent_errors.clone(),
+        }
+    }
+
+    /// Writes up the digest and starts the log again from now
+    pub fn take(&mut self, realm: &str, world: &World) -> Digest {
+        let digest = self.digest(realm, world);
+        *self = Self::new(world, self.runtime.take());
+        digest
+    }
+
+    /// Sends a digest off in the background, failures are only logged
+    pub fn post(&self, url: &str, kind: WebhookKind, digest: &Digest) {
+        self.send(url, digest.payload(kind), "the daily digest");
+    }
+
+    /// Posts anything to the webhook in the background, like alerts
+    pub fn send(&self, url: &str, payload: serde_json::Value, what: &'static str) {
+        let Some(runtime) = &self.runtime else {
+            tracing::warn!("Can't post {what}, there's no async runtime to send it from");
+            return;
+        };
+        let request = self.client.post(url).json(&payload);
+        runtime.spawn(async move {
+            match request.send().await.and_then(|r| r.error_for_status()) {
+                Ok(_) => tracing::info!("Posted {what}"),
+                Err(e) => tracing::error!("Failed posting {what}: {e}"),
+            }
+        });
+    }
+}
+
+fn total_fights(world: &World) -> u32 {
+    world.telemetry.places.values().map(|s| s.fights).sum()
+}
+
+/// A realm's day, ready to send
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Digest {
+    pub realm: String,
+    /// The day of the dream it was sent on
+    pub day: u64,
+    pub places_generated: usize,
+    pub total_places: usize,
+    pub fights: u32,
+    pub knockouts: usize,
+    /// The latest knockouts, who did it and where
+    pub notable_knockouts: Vec<String>,
+    pub characters: usize,
+    pub gold_carried: u32,
+    pub council_treasuries: u32,
+    pub open_work_orders: usize,
+    pub errors: usize,
+    pub recent_errors: Vec<String>,
+}
+
+impl Digest {
+    pub fn text(&self) -> String {
+        let mut res = format!(
+            "{} digest, day {} of the dream\n\n{} new places generated, {} in all\n{} fights, {} knockouts\n",
+            self.realm,
+            self.day,
+            self.places_generated,
+            self.total_places,
+            self.fights,
+            self.knockouts
+        );
+        for knockout in &self.notable_knockouts {
+            res.push_str(&format!("  - {knockout}\n"));
+        }
+        res.push_str(&format!(
+            "{} characters carry {} {GOLD}s, councils hold {}, {} work orders open\n",
+            self.characters, self.gold_carried, self.council_treasuries, self.open_work_orders
+        ));
+        match self.errors {
+            0 => res.push_str("No errors\n"),
+            n => res.push_str(&format!("{n} errors\n")),
+        }
+        for error in &self.recent_errors {
+            res.push_str(&format!("  - {error}\n"));
+        }
+        res
+    }
+
+    /// The body to post for each kind of webhook
+    pub fn payload(&self, kind: WebhookKind) -> serde_json::Value {
+        match kind {
+            WebhookKind::Discord => {
+                let text = self.text();
+                let end = text
+                    .char_indices()
+                    .map(|(i, c)| i + c.len_utf8())
+                    .take_while(|end| *end <= DISCORD_LIMIT)
+                    .last()
+                    .unwrap_or(0);
+                json!({ "content": &text[..end] })
+            }
+            WebhookKind::Slack => json!({ "text": self.text() }),
+            WebhookKind::Json => serde_json::to_value(self).unwrap_or_default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn shapes_each_webhook() {
+        let digest = Digest {
+            realm: "Somnuscape".into(),
+            day: 3,
+            places_generated: 12,
+            total_places: 140,
+            fights: 8,
+            knockouts: 1,
+            notable_knockouts: vec!["Wolf knocked out Ada in Old Shrine".into()],
+            characters: 5,
+            gold_carried: 320,
+            council_treasuries: 80,
+            open_work_orders: 2,
+            errors: 0,
+            recent_errors: Vec::new(),
+        };
+
+        let text = digest.text();
+        assert!(text.starts_with("Somnuscape digest, day 3 of the dream"));
+        assert!(text.contains("  - Wolf knocked out Ada in Old Shrine\n"));
+        assert!(text.ends_with("No errors\n"));
+
+        assert_eq!(digest.payload(WebhookKind::Slack)["text"], text);
+        assert_eq!(digest.payload(WebhookKind::Json)["gold-carried"], 320);
+
+        let long = Digest {
+            notable_knockouts: vec!["é".repeat(1500)],
+            ..digest
+        };
+        let content = long.payload(WebhookKind::Discord)["content"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        assert!(content.len() <= DISCORD_LIMIT);
+    }
+}
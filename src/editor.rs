@@ -0,0 +1,165 @@
+//! A line editor for writing more than a line at a time, like letters and
+//! descriptions. It runs in the player's connection rather than the engine, so
+//! the engine only hears about the finished document. A command asks for it
+//! with [`crate::prompt::edit`], the player's connection opens it on the next
+//! message it passes on, and whatever's saved comes back as the answer to that
+//! prompt. Giving up answers `cancel`.
+
+/// The most lines one document can have
+pub const MAX_LINES: usize = 100;
+/// What the connection sends the engine when the player gives up
+pub const ABORT: &str = "cancel";
+pub const HELP: &str = "Type to add lines. '.l' lists them, '.d 3' deletes line 3, \
+    '.s old/new' swaps words, '.c' clears everything, '.' saves and '.q' gives up";
+
+/// What an editor made of a line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Edit {
+    /// Still editing, tell the player this if it isn't empty
+    Reply(String),
+    /// Finished, the document's ready
+    Save(String),
+    Abort,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Editor {
+    lines: Vec<String>,
+}
+
+impl Editor {
+    /// Starts editing a document, which can be empty
+    pub fn new(document: &str) -> Self {
+        Self {
+            lines: document.lines().map(String::from).collect(),
+        }
+    }
+
+    /// Handles a line from the player, either text to add or a command
+    pub fn handle(&mut self, line: &str) -> Edit {
+        let line = line.trim_end();
+        let Some(command) = line.strip_prefix('.') else {
+            return self.append(line);
+        };
+        // Doubling the dot writes a line that starts with one
+        if command.starts_with('.') {
+            return self.append(command);
+        }
+
+        let (command, rest) = command.split_once(' ').unwrap_or((command, ""));
+        match command {
+            "" | "w" => Edit::Save(self.lines.join("\n")),
+            "q" => Edit::Abort,
+            "l" => Edit::Reply(self.list()),
+            "h" => Edit::Reply(HELP.to_string()),
+            "c" => {
+                self.lines.clear();
+                Edit::Reply("Cleared, it's empty now".to_string())
+            }
+            "d" => Edit::Reply(self.delete(rest.trim())),
+            "s" => Edit::Reply(self.substitute(rest)),
+            _ => Edit::Reply(format!(
+                "'.{command}' isn't something the editor does. {HELP}"
+            )),
+        }
+    }
+
+    fn append(&mut self, line: &str) -> Edit {
+        if self.lines.len() >= MAX_LINES {
+            return Edit::Reply(format!(
+                "That's as long as it can be, {MAX_LINES} lines. '.' to save it"
+            ));
+        }
+        self.lines.push(line.to_string());
+        Edit::Reply(String::new())
+    }
+
+    fn list(&self) -> String {
+        if self.lines.is_empty() {
+            return "Nothing's written yet".to_string();
+        }
+        self.lines
+            .iter()
+            .enumerate()
+            .map(|(i, line)| format!("{:>3}: {line}", i + 1))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn delete(&mut self, n: &str) -> String {
+        match n.parse::<usize>() {
+            Ok(n) if (1..=self.lines.len()).contains(&n) => {
+                let line = self.lines.remove(n - 1);
+                format!("Deleted line {n}: {line}")
+            }
+            _ => format!("Which line? Like '.d 2', there are {}", self.lines.len()),
+        }
+    }
+
+    fn substitute(&mut self, rest: &str) -> String {
+        let Some((old, new)) = rest.split_once('/').filter(|(old, _)| !old.is_empty()) else {
+            return "Like '.s old words/new words'".to_string();
+        };
+        let mut swapped = 0;
+        for line in &mut self.lines {
+            swapped += line.matches(old).count();
+            *line = line.replace(old, new);
+        }
+        match swapped {
+            0 => format!("There's no '{old}' to swap"),
+            1 => "Swapped it once".to_string(),
+            n => format!("Swapped it {n} times"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn edits_line_by_line() {
+        let mut editor = Editor::new("Dear Bob,\n");
+        assert_eq!(
+            editor.handle("The wheat is in."),
+            Edit::Reply(String::new())
+        );
+        assert_eq!(
+            editor.handle("The barley is not."),
+            Edit::Reply(String::new())
+        );
+        assert_eq!(editor.handle("..signed"), Edit::Reply(String::new()));
+        assert_eq!(
+            editor.handle(".l"),
+            Edit::Reply(
+                "  1: Dear Bob,\n  2: The wheat is in.\n  3: The barley is not.\n  4: .signed"
+                    .to_string()
+            )
+        );
+
+        assert_eq!(
+            editor.handle(".d 4"),
+            Edit::Reply("Deleted line 4: .signed".to_string())
+        );
+        assert!(matches!(editor.handle(".d 9"), Edit::Reply(r) if r.starts_with("Which line")));
+        assert_eq!(
+            editor.handle(".s is/was"),
+            Edit::Reply("Swapped it 2 times".to_string())
+        );
+        assert!(matches!(editor.handle(".x"), Edit::Reply(r) if r.contains("isn't something")));
+        assert_eq!(
+            editor.handle("."),
+            Edit::Save("Dear Bob,\nThe wheat was in.\nThe barley was not.".to_string())
+        );
+
+        assert_eq!(
+            editor.handle(".c"),
+            Edit::Reply("Cleared, it's empty now".to_string())
+        );
+        for _ in 0..MAX_LINES {
+            editor.handle("la");
+        }
+        assert!(matches!(editor.handle("la"), Edit::Reply(r) if r.contains("as long as")));
+        assert_eq!(editor.handle(".q"), Edit::Abort);
+    }
+}
@@ -1,38 +1,174 @@
-use std::{collections::HashMap, time::Duration};
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+use rand::{seq::SliceRandom, Rng};
 
 use crate::{
-    commands::{self, Command},
-    config,
+    alerts::Alerts,
+    args::Args,
+    bots::Bots,
+    commands::{self, Command, GOLD},
+    config::{self, RealmConfig},
     connections::{EngineConnectionBroker, PlayerConnectionBroker},
-    generation::{GenerationReq, GenerationRes, GeneratorHandle},
-    mud::world::{Direction, Location, Place, World},
+    digest::DigestLog,
+    fuzzy,
+    generation::{
+        GenerationReq, GenerationRes, GeneratorHandle, PlaceType, DUNGEON_PLACE_TYPE,
+        VILLAGE_PLACE_TYPE,
+    },
+    markup::{styled, Style},
+    mud::{
+        action::Action,
+        areas,
+        creation::{self, CreationStep},
+        entity::EntityId,
+        haggle::Offer,
+        input::InputState,
+        items::{Inventory, ItemInstance, Quality},
+        lore,
+        party::{self, Party},
+        weather::WorldTime,
+        world::{Direction, Location, Place, World},
+    },
+    prompt::{self, Answer, Prompt},
+    rules::GameRules,
+    scripting::{self, Event, Hooks, HOOK_DIR},
+    state::{self, PlayerId},
+    systems::{Phase, Schedule, System},
     AccountStorage,
 };
 
 /// All the engine state kept between ticks, including world info
 pub struct Engine {
+    pub realm: RealmConfig,
+    pub rules: GameRules,
     pub connection_broker: EngineConnectionBroker,
     pub player_registry: AccountStorage,
     pub gen_handle: GeneratorHandle,
     pub world: World,
+    /// Players waiting on an encyclopedia entry to be written, by its name
+    pub pending_lore: HashMap<String, Vec<PlayerId>>,
+    /// Players waiting on a new recipe to be named, by its key
+    pub pending_recipes: HashMap<String, Vec<PlayerId>>,
+    /// Players waiting to hear an NPC's storyline, by NPC
+    pub pending_stories: HashMap<String, Vec<PlayerId>>,
+    /// Players waiting to see what a shop sells, by where it is
+    pub pending_shops: HashMap<Location, Vec<PlayerId>>,
+    /// Players waiting to see what a workshop can make, by where it is
+    pub pending_workshops: HashMap<Location, Vec<PlayerId>>,
+    /// Places requested from the generator that haven't arrived yet
+    pub pending_places: usize,
+    /// Regions waiting on their next weather front
+    pub pending_weather: HashSet<Location>,
+    /// How fast the world runs, and whether an admin has paused it
+    pub clock: Clock,
+    /// Questions players have open, latest last
+    pub prompts: HashMap<PlayerId, Vec<Prompt>>,
+    /// Lua scripts reacting to what happens in the world
+    pub hooks: Hooks,
+    /// Simulated players admins have started for load testing
+    pub bots: Bots,
+    /// What's happened since the last daily digest
+    pub digest: DigestLog,
+    /// Readings taken to warn admins when something's wrong
+    pub alerts: Alerts,
+}
+
+/// Set by an admin to stop the server, every realm saves and stops on its next tick
+pub static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+/// Realms that haven't stopped yet, the last one to stop ends the process
+static RUNNING_REALMS: AtomicUsize = AtomicUsize::new(0);
+
+/// The slowest and fastest admins can set the world running
+pub const SPEED_RANGE: std::ops::RangeInclusive<f64> = 0.1..=20.0;
+
+/// Admin controls over the engine tick. While paused, connections and admin
+/// commands are still handled but the world itself only moves when stepped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clock {
+    /// Multiplier on the configured ticks per second
+    pub speed: f64,
+    /// Why the world was paused, if it is
+    pub paused: Option<String>,
+    /// Ticks left to run while paused
+    pub steps: u32,
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self {
+            speed: 1.0,
+            paused: None,
+            steps: 0,
+        }
+    }
+}
+
+impl Clock {
+    /// How long each tick takes at the current speed
+    pub fn period(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / (config::get().ticks_per_second * self.speed))
+    }
+
+    /// Whether the world moves on this tick, using up a step if it's paused
+    pub fn advance(&mut self) -> bool {
+        if self.paused.is_none() {
+            return true;
+        }
+        if self.steps > 0 {
+            self.steps -= 1;
+            return true;
+        }
+        false
+    }
 }
 
 impl Engine {
     pub fn start_engine(
         player_registry: AccountStorage,
         gen_handle: GeneratorHandle,
+        realm: RealmConfig,
     ) -> PlayerConnectionBroker {
         let (player_connection_broker, connection_broker) = PlayerConnectionBroker::new();
+        let bot_broker = player_connection_broker.clone();
+        let runtime = tokio::runtime::Handle::try_current().ok();
+        // Profiles are checked when the config's loaded, this is just in case
+        let rules = GameRules::profile(realm.rule_profile()).unwrap_or_else(|| {
+            tracing::error!("Unknown rule profile '{}'", realm.rule_profile());
+            GameRules::default()
+        });
+        RUNNING_REALMS.fetch_add(1, Ordering::SeqCst);
 
         std::thread::spawn(move || {
+            tracing::info!("Starting realm {}", realm.name);
+            let world = World::load_or_default(&realm.save_dir);
             let mut mud = Engine {
+                realm,
+                rules,
                 player_registry,
                 connection_broker,
                 gen_handle,
-                world: World::load_or_default(),
+                pending_lore: HashMap::new(),
+                pending_recipes: HashMap::new(),
+                pending_stories: HashMap::new(),
+                pending_shops: HashMap::new(),
+                pending_workshops: HashMap::new(),
+                pending_places: 0,
+                pending_weather: HashSet::new(),
+                clock: Clock::default(),
+                prompts: HashMap::new(),
+                hooks: Hooks::load(Path::new(HOOK_DIR)),
+                bots: Bots::new(bot_broker),
+                digest: DigestLog::new(&world, runtime),
+                alerts: Alerts::new(Instant::now(), state::save_failures()),
+                world,
             };
 
-            startup_generation(&mut mud);
+            populate_world(&mut mud);
 
             run_engine(mud);
         });
@@ -41,58 +177,1028 @@ impl Engine {
     }
 }
 
+/// Something a player can point at, see [`Engine::find_target`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Target {
+    Exit(Direction, Location),
+    Character(PlayerId),
+    Creature(EntityId),
+    /// A piece of equipment, on the ground, carried or in hand
+    Equipment(ItemInstance),
+    /// A stack of items, on the ground or carried, by name
+    Item(String),
+}
+
+impl Engine {
+    /// Works out what a player means by a name. Exits come first, by direction or
+    /// its first letter, then who and what's in the place with them, then what
+    /// they're carrying. Names match as in [`entity::resolve`].
+    pub fn find_target(&self, player: PlayerId, name: &str) -> Option<Target> {
+        let name = name.trim();
+        let character = self.world.player_characters.get(&player)?;
+        let location = character.location;
+        let place = self.world.places.get(&location)?;
+
+        let exit = Direction::values().into_iter().find(|d| {
+            d.name().eq_ignore_ascii_case(name) || d.name()[..1].eq_ignore_ascii_case(name)
+        });
+        if let Some(direction) = exit {
+            return place
+                .connections()
+                .get(&direction)
+                .map(|l| Target::Exit(direction, *l));
+        }
+
+        let others: Vec<_> = self
+            .world
+            .player_characters
+            .iter()
+            .filter(|(p, c)| **p != player && c.location == location)
+            .collect();
+        if let Some((p, _)) = others
+            .iter()
+            .find(|(_, c)| c.name.eq_ignore_ascii_case(name))
+        {
+            return Some(Target::Character(**p));
+        }
+        if let Some(id) = self.world.creature_named(location, name) {
+            return Some(Target::Creature(id));
+        }
+
+        let equipment = character
+            .wielding
+            .as_ref()
+            .filter(|w| w.matches(name))
+            .or_else(|| place.ground.find_instance(name))
+            .or_else(|| character.inventory.find_instance(name));
+        if let Some(item) = equipment {
+            return Some(Target::Equipment(item.clone()));
+        }
+        place
+            .ground
+            .find_stack(name)
+            .or_else(|| character.inventory.find_stack(name))
+            .map(|s| Target::Item(s.name.clone()))
+    }
+}
+
+/// Every system the engine runs each tick. New systems register here with
+/// their phase and whatever they need to run after
+fn systems() -> Vec<System<Engine>> {
+    vec![
+        // Add new players and remove disconnected ones
+        System::new("connections", Phase::Input, handle_connections),
+        // Get and handle player messages
+        System::new("commands", Phase::Input, handle_player_commands).after(&["connections"]),
+        // Finish what players were busy doing
+        System::new("actions", Phase::Simulation, progress_actions),
+        // Run what players lined up while they were busy
+        System::new("queued-input", Phase::Simulation, run_queued_input).after(&["actions"]),
+        // Log out players who have finished making camp
+        System::new("camping", Phase::Simulation, finish_camping),
+        // Send players who've finished reading recall scrolls home
+        System::new("recall", Phase::Simulation, finish_recalls),
+        // Let food and potions wear off
+        System::new("effects", Phase::Simulation, expire_effects),
+        // End fights everyone's walked away from
+        System::new("fights", Phase::Simulation, |engine| {
+            engine.world.end_abandoned_fights()
+        }),
+        // Crumble old corpses, spilling what they held
+        System::new("corpses", Phase::Simulation, crumble_corpses),
+        // Warn about and run the end of the dream cycle
+        System::new("dream-cycle", Phase::Simulation, check_dream_cycle),
+        // Add generation results to the world
+        System::new("generation", Phase::Simulation, incorperate_generation),
+        // Generate more of the world if players are running out of it
+        System::new("expand-world", Phase::Simulation, expand_world).after(&["generation"]),
+        // Let passing traders buy from market stalls
+        System::new("stalls", Phase::Simulation, trade_at_stalls),
+        // Let artisans take on work orders
+        System::new("work-orders", Phase::Simulation, work_orders),
+        // Have village councils collect tribute, pay guards and throw festivals
+        System::new("councils", Phase::Simulation, run_councils),
+        // Send adventurer parties exploring, fighting and following whoever hired them
+        System::new("parties", Phase::Simulation, run_parties).after(&["commands"]),
+        // Turn day into night and blow new weather over regions players are in
+        System::new("weather", Phase::Simulation, run_weather),
+        // Let event scripts react to what happened this tick
+        System::new("hooks", Phase::Output, scripting::run_hooks),
+        // Post the daily digest if one's due
+        System::new("digest", Phase::Output, send_digest),
+        // Warn admins if anything's going wrong
+        System::new("alerts", Phase::Output, check_alerts),
+        // Check no gold or items have appeared or vanished
+        System::new("audit", Phase::Output, audit_economy),
+        // Increment the world time and save if needed
+        System::new("save", Phase::Persistence, |engine| {
+            engine
+                .world
+                .tick_and_check_save(config::get().save_every_x_ticks)
+        }),
+    ]
+}
+
 fn run_engine(mut engine: Engine) -> ! {
-    let tick_period = Duration::from_secs_f64(1.0 / config::get().ticks_per_second);
-    let tick_duration = crossbeam::channel::tick(tick_period);
+    let schedule = Schedule::build(systems()).unwrap_or_else(|e| panic!("{e}"));
+    let order: Vec<_> = schedule.names().collect();
+    tracing::debug!("Running systems {}", order.join(", "));
+    let mut speed = engine.clock.speed;
+    let mut tick_duration = crossbeam::channel::tick(engine.clock.period());
 
     // Core game loop
     loop {
         // Wait for a tick from the channel
         tick_duration.recv().expect("Tick channel should not close");
 
-        // Add new players and remove disconnected ones
-        engine.connection_broker.handle_connection_changes();
+        // Restart the ticker if an admin changed the speed
+        if engine.clock.speed != speed {
+            speed = engine.clock.speed;
+            tick_duration = crossbeam::channel::tick(engine.clock.period());
+        }
 
-        // Get and handle player messages
-        handle_player_commands(&mut engine);
+        // Only input is handled while the world is paused, so admins can still act
+        let advancing = engine.clock.advance();
+        let started = Instant::now();
+        schedule.run(&mut engine, |phase| advancing || phase == Phase::Input);
+        engine.alerts.record_tick(started.elapsed());
+        // Whatever the tick had to say goes out together, paused or not
+        engine.connection_broker.flush();
 
-        // Add generation results to the world
-        incorperate_generation(&mut engine);
+        if SHUTTING_DOWN.load(Ordering::SeqCst) {
+            shut_down(engine);
+        }
+    }
+}
 
-        // Increment the world time and save if needed
+/// Saves the realm and says goodbye, ending the process once every realm has
+fn shut_down(mut engine: Engine) -> ! {
+    tracing::info!("Shutting down realm {}", engine.realm.name);
+    engine
+        .connection_broker
+        .broadcast("The dream fades as the server shuts down, goodbye!".to_string());
+    engine.connection_broker.flush();
+    state::persist(engine.world.take_changes());
+
+    if RUNNING_REALMS.fetch_sub(1, Ordering::SeqCst) == 1 {
+        state::wait_for_persistence();
+        tracing::info!("Every realm is saved, stopping");
+        std::process::exit(0);
+    }
+    loop {
+        std::thread::park();
+    }
+}
+
+fn handle_connections(engine: &mut Engine) {
+    let connected = engine.connection_broker.handle_connection_changes();
+    engine.alerts.record_connections(connected.len());
+    if !connected.is_empty() {
+        redact_player_names(engine);
+    }
+    place_connected_players(engine, connected);
+}
+
+/// Keeps everyone's names out of captured generations. Done as players join
+/// and again before asking for anything that might mention them, since
+/// characters can be named or renamed in between.
+pub fn redact_player_names(engine: &Engine) {
+    if !config::get().generation_capture {
+        return;
+    }
+    let accounts = engine.player_registry.blocking_read();
+    let names = engine
+        .world
+        .player_characters
+        .iter()
+        .filter(|(player, _)| !engine.world.in_party(**player))
+        .map(|(_, c)| c.name.clone())
+        .chain(accounts.values().map(|a| a.username.clone()))
+        .collect();
+    engine.gen_handle.redact_names(names);
+}
+
+/// Wakes reconnecting players, or starts newcomers off creating their character
+fn place_connected_players(engine: &mut Engine, connected: Vec<PlayerId>) {
+    for player in connected {
+        // Anything asked last time has gone stale
+        engine.prompts.remove(&player);
+
+        if !engine.world.player_characters.contains_key(&player) {
+            let void = engine.world.void_location();
+            let character = engine.world.player_character(player);
+            character.location = void;
+            character.attributes = creation::starting_attributes();
+            character.creating = Some(CreationStep::Name);
+        }
+
+        match engine.world.player_characters[&player].creating {
+            Some(step) => {
+                let prompt = creation_prompt(engine, player, step);
+                prompt::ask(engine, player, prompt);
+            }
+            None => wake_player(engine, player),
+        }
+
+        if let Some(reason) = &engine.clock.paused {
+            let notice = format!("The world is paused for {reason}, it'll be back shortly");
+            engine.connection_broker.send_player_message(player, notice);
+        }
+    }
+}
+
+fn username(engine: &Engine, player: PlayerId) -> String {
+    engine
+        .player_registry
+        .blocking_read()
+        .get(&player)
+        .map(|p| p.username.clone())
+        .unwrap_or_default()
+}
+
+fn creation_prompt(engine: &Engine, player: PlayerId, step: CreationStep) -> Prompt {
+    let attributes = &engine.world.player_characters[&player].attributes;
+    Prompt::new(
+        step.prompt(&username(engine, player), attributes),
+        Box::new(move |engine, player, msg| continue_creation(engine, player, step, msg)),
+    )
+}
+
+/// Takes a player's answer to the step of character creation they're on,
+/// waking them in the world once they're done
+fn continue_creation(
+    engine: &mut Engine,
+    player: PlayerId,
+    step: CreationStep,
+    msg: &str,
+) -> Answer {
+    let username = username(engine, player);
+
+    let res = match step {
+        CreationStep::Name => {
+            let name = if msg.is_empty() { &username } else { msg };
+            let taken = engine
+                .world
+                .player_characters
+                .iter()
+                .any(|(p, c)| *p != player && c.name.eq_ignore_ascii_case(name))
+                || engine
+                    .player_registry
+                    .blocking_read()
+                    .iter()
+                    .any(|(p, a)| *p != player && a.username.eq_ignore_ascii_case(name));
+
+            if taken {
+                Err(format!("Someone already goes by {name}"))
+            } else {
+                commands::check_name(name).map(|()| {
+                    engine.world.player_character(player).name = name.to_string();
+                    Some(CreationStep::Attributes(creation::STARTING_POINTS))
+                })
+            }
+        }
+        CreationStep::Attributes(_) if msg.eq_ignore_ascii_case("done") => Ok(None),
+        CreationStep::Attributes(points) => {
+            let mut args = msg.split_whitespace();
+            let attribute = args.next().unwrap_or_default();
+            let amount = args.next().and_then(|a| a.parse().ok());
+            match amount {
+                Some(amount) => creation::spend(
+                    &mut engine.world.player_character(player).attributes,
+                    points,
+                    attribute,
+                    amount,
+                )
+                .map(|left| Some(CreationStep::Attributes(left))),
+                None => Err("Spend points like 'strength 3'".to_string()),
+            }
+        }
+    };
+
+    match res {
+        Ok(Some(next)) => {
+            engine.world.player_character(player).creating = Some(next);
+            let prompt = creation_prompt(engine, player, next);
+            prompt::ask(engine, player, prompt);
+        }
+        Ok(None) => {
+            engine.world.player_character(player).creating = None;
+            engine.connection_broker.send_player_message(
+                player,
+                "The dream takes hold and you begin to stir...".to_string(),
+            );
+            wake_player(engine, player);
+        }
+        Err(e) => return Answer::Retry(e),
+    }
+
+    Answer::Done
+}
+
+/// Puts a player with a finished character into the world, where they last were
+/// or somewhere safe if that place no longer exists
+fn wake_player(engine: &mut Engine, player: PlayerId) {
+    let username = username(engine, player);
+    engine.world.check_player_location(player);
+    let character = engine.world.player_character(player);
+    if character.name.is_empty() {
+        character.name = username;
+    }
+    character.camping_until = None;
+    character.recalling_until = None;
+    character.action = None;
+    character.input.clear();
+
+    let location = engine.world.player_character(player).location;
+    let look_msg = engine.world.places[&location].look(&engine.world, "You wake in");
+    engine
+        .connection_broker
+        .send_player_message(player, look_msg);
+    let character = &engine.world.player_characters[&player];
+    let mut wakes = format!("{} wakes up", character.name);
+    if let Some(companion) = &character.companion {
+        wakes.push_str(&format!(", {} at their side", companion.name()));
+        engine.connection_broker.send_player_message(
+            player,
+            format!("{} is curled up beside you", companion.name()),
+        );
+    }
+    engine
+        .connection_broker
+        .broadcast_to_location(&engine.world, location, wakes, Some(player));
+    commands::visit_place(engine, player);
+
+    let stall = engine
+        .world
+        .stall_location(player)
+        .map(|l| &engine.world.places[&l])
+        .and_then(|p| Some((p.name.clone(), p.stalls.iter().find(|s| s.owner == player)?)))
+        .filter(|(_, s)| !s.ledger.is_empty());
+    if let Some((place, stall)) = stall {
+        let msg = format!(
+            "While you were away your stall in {place} sold\n{}\n{} {GOLD}s are waiting, 'stall collect' to take them",
+            stall.ledger.join("\n"),
+            stall.proceeds
+        );
+        engine.connection_broker.send_player_message(player, msg);
+    }
+
+    let letters = engine.world.player_characters[&player].letters.len();
+    if letters > 0 {
+        engine.connection_broker.send_player_message(
+            player,
+            format!("You have {letters} letters waiting, 'mail' in any village to collect them"),
+        );
+    }
+}
+
+fn trade_at_stalls(engine: &mut Engine) {
+    let config = config::get();
+    let current_tick = engine.world.current_tick;
+    if !current_tick.is_multiple_of(config.seconds_to_ticks(config.stall_trader_seconds).max(1)) {
+        return;
+    }
+
+    let mut rng = rand::thread_rng();
+    let mut sales = Vec::new();
+    for place in engine.world.places.values_mut() {
+        for stall in place.stalls.iter_mut().filter(|s| s.open(current_tick)) {
+            if let Some((item, price)) = stall.npc_sale(&mut rng) {
+                let tax = stall.pay_tax(price, config.market_tax);
+                let owner = (stall.owner, stall.owner_name.clone());
+                sales.push((place.location, owner, item, price, tax));
+            }
+        }
+    }
+
+    for (location, (owner, owner_name), item, price, tax) in sales {
+        engine.world.place_changed(location);
+        if let Some(faction) = engine.world.faction_mut(location) {
+            faction.deposit(tax);
+        }
+        let why = "selling to a passing trader";
+        engine.world.record_transaction(&owner_name, &item, -1, why);
+        engine
+            .world
+            .record_transaction(&owner_name, GOLD, i64::from(price), why);
         engine
             .world
-            .tick_and_check_save(config::get().save_every_x_ticks);
+            .record_transaction(&owner_name, GOLD, -i64::from(tax), "market tax");
+        engine.connection_broker.send_player_message(
+            owner,
+            format!("A passing trader buys the {item} from your stall for {price} {GOLD}"),
+        );
+    }
+}
+
+fn work_orders(engine: &mut Engine) {
+    let config = config::get();
+    if !engine
+        .world
+        .current_tick
+        .is_multiple_of(config.seconds_to_ticks(config.artisan_seconds).max(1))
+    {
+        return;
+    }
+
+    let mut rng = rand::thread_rng();
+    // Artisans can't make things nobody knows how to find
+    let orders: Vec<_> = engine
+        .world
+        .work_orders
+        .iter()
+        .filter_map(|o| {
+            let equipment = engine.world.items.contains_key(&o.item);
+            Some((o.id, engine.world.base_value(&o.item)?, equipment))
+        })
+        .collect();
+
+    for (id, value, equipment) in orders {
+        let mut tx = engine.world.transaction();
+        let Some(order) = tx.work_orders().iter_mut().find(|o| o.id == id) else {
+            continue;
+        };
+        let (made, payout) = order.fill(order.artisan_work(value, &mut rng));
+        if made == 0 {
+            continue;
+        }
+        let (poster, item) = (order.poster, order.item.clone());
+        tx.work_orders().retain(|o| o.wanted > 0);
+
+        let mut parcel = Inventory::default();
+        if equipment {
+            for _ in 0..made {
+                parcel.add_instance(ItemInstance::new(&item, Quality::Common));
+            }
+        } else {
+            parcel.add(&item, made);
+        }
+        let letter = format!(
+            "An artisan sends {item} x{made} for work order #{id}, paid {payout} {GOLD}s from escrow"
+        );
+        // There's nobody to deliver to, so the order's left as it was
+        let Ok(character) = tx.character(poster) else {
+            continue;
+        };
+        character.send_mail(letter.clone(), parcel);
+        let name = character.name.clone();
+        let why = "an artisan's work";
+        tx.record(&name, &item, made.into(), why);
+        tx.record(&name, GOLD, -i64::from(payout), why);
+        tx.commit();
+
+        engine.connection_broker.send_player_message(
+            poster,
+            format!("{letter}, 'mail' in any village to collect it"),
+        );
+    }
+}
+
+fn run_councils(engine: &mut Engine) {
+    let config = config::get();
+    if !engine
+        .world
+        .current_tick
+        .is_multiple_of(config.seconds_to_ticks(config.council_seconds).max(1))
+    {
+        return;
+    }
+
+    let mut rng = rand::thread_rng();
+    for village in engine.world.villages() {
+        let tribute = engine.world.tributaries(village) as u32 * config.dungeon_tribute;
+        let faction = engine.world.factions.entry(village).or_default();
+        faction.deposit(tribute);
+        faction.treasury = faction.treasury.saturating_sub(config.guard_wages);
+
+        let chance = faction.prosperity().festival_chance();
+        if rng.gen_bool(chance) && engine.world.hold_festival(village) {
+            let name = engine.world.places[&village].name.clone();
+            engine
+                .connection_broker
+                .broadcast(format!("The council of {name} is throwing a festival!"));
+        }
+    }
+}
+
+fn run_weather(engine: &mut Engine) {
+    let current_tick = engine.world.current_tick;
+    let time = engine.world.time();
+    let config = config::get();
+    let ticks_per_day = config.seconds_to_ticks(config.day_minutes * 60.0);
+    let before = WorldTime::at_tick(current_tick.saturating_sub(1), ticks_per_day);
+    let online: Vec<_> = engine
+        .connection_broker
+        .online()
+        .into_iter()
+        .filter_map(|(p, _)| Some((p, engine.world.player_characters.get(&p)?.location)))
+        .collect();
+
+    if current_tick > 0 && before.phase() != time.phase() {
+        for (player, location) in &online {
+            if engine
+                .world
+                .places
+                .get(location)
+                .is_some_and(|p| p.outdoors())
+            {
+                engine
+                    .connection_broker
+                    .send_player_message(*player, time.phase().arrival().to_string());
+            }
+        }
+    }
+
+    let regions: HashSet<_> = online
+        .iter()
+        .map(|(_, l)| engine.world.region_of(*l))
+        .collect();
+    for region in regions {
+        if engine.world.weather_at(region).is_some() || engine.pending_weather.contains(&region) {
+            continue;
+        }
+        let Some(place) = engine.world.places.get(&region) else {
+            continue;
+        };
+        let last = engine.world.weather.get(&region).map(|w| w.name.clone());
+        engine.pending_weather.insert(region);
+        engine.gen_handle.request_generate(GenerationReq::Weather(
+            region,
+            place.name.clone(),
+            place.description.clone(),
+            time,
+            last,
+        ));
+    }
+}
+
+fn run_parties(engine: &mut Engine) {
+    let config = config::get();
+    if !engine
+        .world
+        .current_tick
+        .is_multiple_of(config.seconds_to_ticks(config.party_seconds).max(1))
+    {
+        return;
+    }
+
+    let mut rng = rand::thread_rng();
+    while engine.world.parties.len() < config.adventurer_parties {
+        form_party(engine, &mut rng);
+    }
+
+    for idx in 0..engine.world.parties.len() {
+        let party = engine.world.parties[idx].clone();
+        for member in &party.members {
+            engine.world.check_player_location(*member);
+        }
+        let location = engine.world.player_characters[&party.leader()].location;
+
+        if let Some(since) = party.stranded_since {
+            let waited = engine.world.current_tick.saturating_sub(since);
+            if waited >= config.seconds_to_ticks(party::STRANDED_SECONDS) {
+                engine.world.parties[idx].stranded_since = None;
+                engine.connection_broker.broadcast_to_location(
+                    &engine.world,
+                    location,
+                    format!("{} pick themselves up, nobody came to help", party.name),
+                    None,
+                );
+            }
+            continue;
+        }
+
+        if let Some((employer, _)) = party.hired_by {
+            if party.employer(engine.world.current_tick).is_none() {
+                engine.world.parties[idx].hired_by = None;
+                engine.connection_broker.send_player_message(
+                    employer,
+                    format!(
+                        "{}'s contract with you is up, they go their own way",
+                        party.name
+                    ),
+                );
+            } else if engine.connection_broker.is_connected(employer) {
+                let target = engine.world.player_character(employer).location;
+                if target != location {
+                    follow(engine, &party, location, target);
+                    continue;
+                }
+            }
+        }
+
+        let creatures = engine
+            .world
+            .npcs
+            .get(&location)
+            .is_some_and(|c| !c.is_empty());
+        if creatures && rng.gen_bool(0.6) {
+            skirmish(engine, idx, location);
+        } else if rng.gen_bool(0.2) {
+            let line = party::CHATTER.choose(&mut rng).unwrap();
+            let member = *party.members.choose(&mut rng).unwrap();
+            run_command(engine, member, &format!("say {line}"));
+        } else if party.hired_by.is_none() {
+            let visited = &engine.world.player_characters[&party.leader()].visited;
+            let exits: Vec<_> = engine.world.places[&location]
+                .connections()
+                .iter()
+                .map(|(d, l)| (*d, visited.contains(l)))
+                .collect();
+            if let Some(direction) = party::pick_exit(&exits, &mut rng) {
+                for member in &party.members {
+                    run_command(engine, *member, direction.name());
+                }
+            }
+        }
+    }
+}
+
+/// Starts a new party off from a spawn point
+fn form_party(engine: &mut Engine, rng: &mut impl Rng) {
+    let (party, names) = Party::new(engine.world.parties.len(), rng);
+    let location = engine.world.player_character(party.leader()).location;
+    for (member, name) in party.members.iter().zip(names) {
+        let character = engine.world.player_character(*member);
+        character.name = name;
+        character.location = location;
+    }
+    tracing::info!("{} set out adventuring", party.name);
+    engine.world.parties.push(party);
+}
+
+/// Moves a hired party towards whoever hired them, a step at a time if they're
+/// next door, otherwise they hurry to catch up
+fn follow(engine: &mut Engine, party: &Party, location: Location, target: Location) {
+    let step = engine.world.places[&location]
+        .connections()
+        .iter()
+        .find(|(_, l)| **l == target)
+        .map(|(d, _)| *d);
+
+    match step {
+        Some(direction) => {
+            for member in &party.members {
+                run_command(engine, *member, direction.name());
+            }
+        }
+        None => {
+            for member in &party.members {
+                engine.world.player_character(*member).location = target;
+                engine.world.player_changed(*member);
+            }
+            engine.connection_broker.broadcast_to_location(
+                &engine.world,
+                target,
+                format!("{} catch up, out of breath", party.name),
+                None,
+            );
+        }
+    }
+}
+
+/// A round of the party fighting the first creature where they are. Beaten
+/// creatures are driven off somewhere nearby, and a party member being knocked
+/// out leaves the whole party stranded until someone comes to help.
+fn skirmish(engine: &mut Engine, idx: usize, location: Location) {
+    let party = engine.world.parties[idx].clone();
+    let Some(creature) = engine.world.npcs.get(&location).and_then(|c| c.first()) else {
+        return;
+    };
+    let (creature, name) = (creature.id, creature.name.clone());
+
+    for member in party.members {
+        let here = engine.world.npcs.get(&location);
+        let standing = here.is_some_and(|c| c.iter().any(|c| c.id == creature));
+        if !standing || engine.world.parties[idx].stranded_since.is_some() {
+            break;
+        }
+        run_command(engine, member, &format!("attack {name}"));
+    }
+}
+
+fn finish_camping(engine: &mut Engine) {
+    let current_tick = engine.world.current_tick;
+    let camped: Vec<_> = engine
+        .world
+        .player_characters
+        .iter_mut()
+        .filter(|(_, c)| c.camping_until.is_some_and(|t| t <= current_tick))
+        .map(|(p, c)| {
+            c.camping_until = None;
+            *p
+        })
+        .collect();
+
+    for player in camped {
+        engine.connection_broker.send_player_message(
+            player,
+            "You settle into your camp and drift off to sleep. Goodbye!".to_string(),
+        );
+        let character = &engine.world.player_characters[&player];
+        engine.connection_broker.broadcast_to_location(
+            &engine.world,
+            character.location,
+            format!(
+                "{} settles into their camp and drifts off to sleep",
+                character.name
+            ),
+            Some(player),
+        );
+        engine.connection_broker.disconnect_player(player);
+    }
+}
+
+fn finish_recalls(engine: &mut Engine) {
+    let current_tick = engine.world.current_tick;
+    let recalled: Vec<_> = engine
+        .world
+        .player_characters
+        .iter_mut()
+        .filter(|(_, c)| c.recalling_until.is_some_and(|t| t <= current_tick))
+        .map(|(p, c)| {
+            c.recalling_until = None;
+            *p
+        })
+        .collect();
+
+    for player in recalled {
+        let character = engine.world.player_character(player);
+        let (from, name) = (character.location, character.name.clone());
+        let Some(to) = character
+            .bound_to
+            .filter(|l| engine.world.places.contains_key(l))
+        else {
+            continue;
+        };
+        let character = engine.world.player_character(player);
+        if !character.inventory.remove(commands::RECALL_SCROLL, 1) {
+            engine.connection_broker.send_player_message(
+                player,
+                "You reach the end of the spell, but the scroll's no longer in your hands"
+                    .to_string(),
+            );
+            continue;
+        }
+        character.location = to;
+        character.offer = None;
+        engine
+            .world
+            .record_transaction(&name, commands::RECALL_SCROLL, -1, "recalling");
+
+        engine.connection_broker.broadcast_to_location(
+            &engine.world,
+            from,
+            format!("{name} reads a scroll and vanishes in a swirl of light"),
+            Some(player),
+        );
+        engine.connection_broker.broadcast_to_location(
+            &engine.world,
+            to,
+            format!("{name} appears in a swirl of light"),
+            Some(player),
+        );
+        engine.world.player_changed(player);
+        let look_msg = engine.world.places[&to].look(
+            &engine.world,
+            "The scroll crumbles to dust and you find yourself in",
+        );
+        engine
+            .connection_broker
+            .send_player_message(player, look_msg);
+        engine.hooks.emit(Event::PlayerEnteredRoom(player, to));
+    }
+}
+
+fn expire_effects(engine: &mut Engine) {
+    let current_tick = engine.world.current_tick;
+    let expired: Vec<_> = engine
+        .world
+        .player_characters
+        .iter_mut()
+        .flat_map(|(p, c)| c.expire_effects(current_tick).into_iter().map(|e| (*p, e)))
+        .collect();
+
+    for (player, effect) in expired {
+        engine.world.player_changed(player);
+        engine
+            .connection_broker
+            .send_player_message(player, format!("The effects of {effect} wear off"));
+    }
+}
+
+fn crumble_corpses(engine: &mut Engine) {
+    let current_tick = engine.world.current_tick;
+    let mut crumbled = Vec::new();
+    for place in engine.world.places.values_mut() {
+        if !place.corpses.iter().any(|c| c.crumbled(current_tick)) {
+            continue;
+        }
+
+        let (old, kept) = std::mem::take(&mut place.corpses)
+            .into_iter()
+            .partition(|c| c.crumbled(current_tick));
+        place.corpses = kept;
+        for corpse in old {
+            place.ground.append(corpse.contents);
+            crumbled.push((
+                place.location,
+                place.name.clone(),
+                corpse.owner,
+                corpse.owner_name,
+            ));
+        }
+    }
+
+    for (location, place_name, owner, owner_name) in crumbled {
+        engine.world.place_changed(location);
+        engine.connection_broker.broadcast_to_location(
+            &engine.world,
+            location,
+            format!("The corpse of {owner_name} crumbles away, spilling what it held"),
+            Some(owner),
+        );
+        engine.connection_broker.send_player_message(
+            owner,
+            format!("Your corpse in {place_name} has crumbled, anyone can take what it held now"),
+        );
     }
 }
 
 fn handle_player_commands(engine: &mut Engine) {
     let command_list = commands::get_command_list();
 
-    while let Some((player, msg)) = engine.connection_broker.poll_player_messages() {
-        let mut args_iter = msg.split_whitespace();
-        if let Some(cmd) = args_iter.next() {
-            let c = command_list.iter().find(|c| c.match_name(cmd));
-            match c {
-                Some(cmd) => (cmd.cmd_fn)(engine, player, &mut args_iter),
-                None => engine
-                    .connection_broker
-                    .send_player_message(player, get_close_commands(cmd, command_list)),
-            };
+    let per_player = config::get().commands_per_tick;
+    for (player, msg) in engine.connection_broker.poll_player_messages(per_player) {
+        if let Some(reason) = &engine.clock.paused {
+            if !commands::is_admin(engine, player) {
+                let notice = format!("The world is paused for {reason}, please wait a moment");
+                engine.connection_broker.send_player_message(player, notice);
+                continue;
+            }
+        }
+
+        let state = input_state(engine, player);
+        if state == InputState::Menu {
+            prompt::answer(engine, player, &msg);
+            continue;
+        }
+
+        // Aliases stand in for what the player would have typed, so they
+        // expand before anything looks at the command
+        let msg = engine
+            .world
+            .player_characters
+            .get(&player)
+            .and_then(|c| c.expand_alias(&msg))
+            .unwrap_or(msg);
+        let immediate = msg
+            .split_whitespace()
+            .next()
+            .and_then(|cmd| command_list.iter().find(|c| c.match_name(cmd)))
+            .is_some_and(|c| c.immediate);
+        let character = engine.world.player_character(player);
+        if immediate || (state == InputState::Ready && character.input.is_empty()) {
+            run_command(engine, player, &msg);
+            continue;
+        }
+
+        let msg = if character.input.push(msg) {
+            state.holding_note()
+        } else {
+            "You've got too much lined up already, 'clear' to forget it".to_string()
+        };
+        engine.connection_broker.send_player_message(player, msg);
+    }
+}
+
+/// Whether what the player types next can run now
+fn input_state(engine: &Engine, player: PlayerId) -> InputState {
+    let Some(character) = engine.world.player_characters.get(&player) else {
+        return InputState::Ready;
+    };
+
+    if prompt::is_prompting(engine, player) {
+        InputState::Menu
+    } else if let Some(action) = &character.action {
+        InputState::Busy(action.verb.clone())
+    } else if let Some((npc, _)) = engine
+        .pending_stories
+        .iter()
+        .find(|(_, waiting)| waiting.contains(&player))
+    {
+        InputState::Prompting(npc.clone())
+    } else {
+        InputState::Ready
+    }
+}
+
+/// Runs a command a player typed, or starts an action if the command takes a while
+fn run_command(engine: &mut Engine, player: PlayerId, line: &str) {
+    let command_list = commands::get_command_list();
+    let Some(cmd) = line.split_whitespace().next() else {
+        return;
+    };
+    let Some(command) = command_list.iter().find(|c| c.match_name(cmd)) else {
+        engine
+            .connection_broker
+            .send_player_message(player, get_close_commands(cmd, command_list));
+        return;
+    };
+    if command.admin && !commands::is_admin(engine, player) {
+        let msg = format!("Only admins can use '{}'", command.name);
+        engine.connection_broker.send_player_message(player, msg);
+        return;
+    }
+
+    let ghost = engine
+        .world
+        .player_characters
+        .get(&player)
+        .is_some_and(|c| c.ghost.is_some());
+    if ghost && !command.ghostly {
+        engine.connection_broker.send_player_message(
+            player,
+            "Your hands pass through everything, ghosts can only look around, move and 'revive'"
+                .to_string(),
+        );
+        return;
+    }
+
+    if let Some((verb, seconds)) = command.act {
+        let check = command.check.as_ref();
+        if let Some(Err(e)) = check.map(|c| c(engine, player, &mut Args::after_command(line))) {
+            engine.connection_broker.send_player_message(player, e);
+            return;
         }
+        let current_tick = engine.world.current_tick;
+        let ticks = config::get().seconds_to_ticks(seconds);
+        let action = Action::new(line.to_string(), verb, current_tick, ticks);
+        engine.world.player_character(player).action = Some(action);
+        let msg = format!("You start {verb}, it'll take {seconds:.0} seconds");
+        engine.connection_broker.send_player_message(player, msg);
+        return;
     }
+
+    perform(engine, player, command, line);
+}
+
+fn perform(engine: &mut Engine, player: PlayerId, command: &Command, line: &str) {
+    // Commands can change where the player is and where they end up
+    engine.world.player_changed(player);
+    (command.cmd_fn)(engine, player, &mut Args::after_command(line));
+    engine.world.player_changed(player);
 }
 
-pub fn get_close_commands<'a>(input: &str, commands: &'a Vec<Command>) -> String {
-    let mut closest: Vec<(&str, usize)> = commands
+/// Runs what players lined up while they were busy, in order, for as long as they're free
+fn run_queued_input(engine: &mut Engine) {
+    let queued: Vec<_> = engine
+        .world
+        .player_characters
         .iter()
-        .map(|c| (c.name.as_str(), strsim::levenshtein(input, &c.name)))
+        .filter(|(_, c)| !c.input.is_empty())
+        .map(|(p, _)| *p)
         .collect();
 
-    closest.sort_by_key(|(_, d)| *d);
+    for player in queued {
+        while input_state(engine, player) == InputState::Ready {
+            let Some(line) = engine.world.player_character(player).input.pop() else {
+                break;
+            };
+            run_command(engine, player, &line);
+        }
+    }
+}
+
+/// Reports how players' actions are going and runs the ones that are done
+fn progress_actions(engine: &mut Engine) {
+    let current_tick = engine.world.current_tick;
+    let mut finished = Vec::new();
+    for (player, character) in engine.world.player_characters.iter_mut() {
+        let Some(action) = &character.action else {
+            continue;
+        };
+        if action.is_done(current_tick) {
+            finished.push((*player, character.action.take().unwrap()));
+        } else if let Some(note) = action.progress(current_tick) {
+            engine.connection_broker.send_player_message(*player, note);
+        }
+    }
+
+    let command_list = commands::get_command_list();
+    for (player, action) in finished {
+        let cmd = action.line.split_whitespace().next().unwrap_or_default();
+        if let Some(command) = command_list.iter().find(|c| c.match_name(cmd)) {
+            perform(engine, player, command, &action.line);
+        }
+    }
+}
+
+pub fn get_close_commands(input: &str, commands: &[Command]) -> String {
+    let closest = fuzzy::ranked(input, commands.iter().map(|c| c.name.as_str()));
 
     let mut res = format!("Command '{input}' not found, did you mean one of these? ");
-    for (cmd, _) in closest.iter().take(3) {
+    for cmd in closest.iter().take(3) {
         res.push('\'');
         res.push_str(cmd);
         res.push('\'');
@@ -102,52 +1208,575 @@ pub fn get_close_commands<'a>(input: &str, commands: &'a Vec<Command>) -> String
     res
 }
 
+/// Posts a digest of the realm's day to the configured webhook
+fn send_digest(engine: &mut Engine) {
+    let config = config::get();
+    let Some(webhook) = &config.digest else {
+        return;
+    };
+    let due = engine.digest.since_tick + config.seconds_to_ticks(webhook.hours * 3600.0).max(1);
+    if engine.world.current_tick < due {
+        return;
+    }
+
+    let digest = engine.digest.take(&engine.realm.name, &engine.world);
+    engine.digest.post(&webhook.url, webhook.kind, &digest);
+}
+
+/// Admins connected to the realm
+pub fn admins_online(engine: &Engine) -> Vec<PlayerId> {
+    engine
+        .connection_broker
+        .online()
+        .into_iter()
+        .map(|(p, _)| p)
+        .filter(|p| commands::is_admin(engine, *p))
+        .collect()
+}
+
+/// Checks the alert rules, telling admins online and the webhook about any changes
+fn check_alerts(engine: &mut Engine) {
+    let config = config::get();
+    let notices = engine
+        .alerts
+        .check(Instant::now(), state::save_failures(), &config.alerts);
+    if notices.is_empty() {
+        return;
+    }
+
+    let admins = admins_online(engine);
+    for notice in notices {
+        let text = notice.text(&engine.realm.name);
+        tracing::warn!("{text}");
+        engine
+            .connection_broker
+            .broadcast_to(admins.iter().copied(), styled(Style::Warn, &text));
+        if let Some(webhook) = &config.digest {
+            let payload = notice.payload(&engine.realm.name, webhook.kind);
+            engine.digest.send(&webhook.url, payload, "an alert");
+        }
+    }
+}
+
+fn audit_economy(engine: &mut Engine) {
+    let config = config::get();
+    if config.audit_seconds <= 0.0
+        || !engine
+            .world
+            .current_tick
+            .is_multiple_of(config.seconds_to_ticks(config.audit_seconds).max(1))
+    {
+        return;
+    }
+
+    let discrepancies = commands::audit(engine);
+    if discrepancies.is_empty() {
+        return;
+    }
+    let admins = admins_online(engine);
+    for discrepancy in discrepancies {
+        let text = format!("[{}] Audit found {discrepancy}", engine.realm.name);
+        tracing::warn!("{text}");
+        engine.digest.error(format!(
+            "Audit found {} {} where the ledger expected {}",
+            discrepancy.counted, discrepancy.item, discrepancy.expected
+        ));
+        engine
+            .connection_broker
+            .broadcast_to(admins.iter().copied(), styled(Style::Warn, &text));
+    }
+}
+
 fn incorperate_generation(engine: &mut Engine) {
     while let Some(r) = engine.gen_handle.get_responses() {
+        if !matches!(r, GenerationRes::Streamed(..)) {
+            engine
+                .alerts
+                .record_generation(matches!(r, GenerationRes::Failed(..)));
+        }
         match r {
-            GenerationRes::Place(place, rooms) => add_new_locale(engine, place, rooms),
+            GenerationRes::Place(place, rooms) => {
+                engine.pending_places = engine.pending_places.saturating_sub(1);
+                engine.digest.places_generated += rooms.len() + 1;
+                add_new_locale(engine, place, rooms)
+            }
+            GenerationRes::Failed(req, why) => {
+                tracing::error!("Gave up generating {req:?}: {why}");
+                engine.digest.error(format!("Gave up generating: {why}"));
+                // The world's checked for missing places regularly, so they'll be asked for again
+                if let GenerationReq::Places(_, count) = req {
+                    engine.pending_places = engine.pending_places.saturating_sub(count);
+                }
+            }
+            GenerationRes::Lore(name, text) => {
+                for player in engine.pending_lore.remove(&name).unwrap_or_default() {
+                    if let Some((_, kind)) = engine.world.player_character(player).known_lore(&name)
+                    {
+                        engine
+                            .connection_broker
+                            .send_player_message(player, lore::entry(&name, kind, &text));
+                    }
+                }
+                engine.world.lore.insert(name, text);
+            }
+            GenerationRes::Creatures(creatures) => {
+                for (location, creature) in creatures {
+                    if engine.world.places.contains_key(&location) {
+                        engine
+                            .world
+                            .npcs
+                            .entry(location)
+                            .or_default()
+                            .push(creature);
+                    }
+                }
+            }
+            GenerationRes::Items(region, items) => {
+                if engine.world.places.contains_key(&region) {
+                    engine
+                        .world
+                        .scatter_loot(region, items, &mut rand::thread_rng());
+                } else {
+                    items
+                        .into_iter()
+                        .for_each(|i| engine.world.register_item(i));
+                }
+            }
+            GenerationRes::Haggle(player, haggle, reply, offer) => {
+                let price = haggle.settle(offer);
+                engine.world.player_character(player).offer = Some(Offer {
+                    item: haggle.item_id,
+                    name: haggle.item.clone(),
+                    price,
+                });
+                engine.connection_broker.send_player_message(
+                    player,
+                    format!(
+                        "You haggle with {}\n\"{reply}\"\nThey'll pay {price} {GOLD} for the {}, 'accept' to take the offer",
+                        haggle.shopkeeper, haggle.item
+                    ),
+                );
+            }
+            GenerationRes::Streamed(player, text) => {
+                engine.connection_broker.send_player_message(player, text);
+            }
+            GenerationRes::Dialogue(player, dialogue, line, streamed) => {
+                let conversation = engine
+                    .world
+                    .player_characters
+                    .get_mut(&player)
+                    .and_then(|c| c.conversation.as_mut())
+                    .filter(|c| c.npc == dialogue.npc);
+                if let Some(conversation) = conversation {
+                    conversation.add(&dialogue.npc, &line);
+                }
+                if !streamed {
+                    engine
+                        .connection_broker
+                        .send_player_message(player, format!("{} says\n\"{line}\"", dialogue.npc));
+                }
+            }
+            GenerationRes::Weather(region, mut front) => {
+                engine.pending_weather.remove(&region);
+                let minutes =
+                    config::get().weather_minutes * rand::thread_rng().gen_range(0.5..1.5);
+                front.until_tick =
+                    engine.world.current_tick + config::get().seconds_to_ticks(minutes * 60.0);
+                let msg = format!("The weather turns. {}", front.description);
+                engine.world.weather.insert(region, front);
+
+                for (player, _) in engine.connection_broker.online() {
+                    let Some(character) = engine.world.player_characters.get(&player) else {
+                        continue;
+                    };
+                    let location = character.location;
+                    let outdoors = engine
+                        .world
+                        .places
+                        .get(&location)
+                        .is_some_and(|p| p.outdoors());
+                    if outdoors && engine.world.region_of(location) == region {
+                        engine
+                            .connection_broker
+                            .send_player_message(player, msg.clone());
+                    }
+                }
+            }
+            GenerationRes::Storyline(story) => {
+                let npc = story.npc.clone();
+                engine.world.storylines.entry(npc.clone()).or_insert(story);
+                let waiting = engine.pending_stories.remove(&npc);
+                for player in waiting.unwrap_or_default() {
+                    commands::tell_story(engine, player, &npc);
+                }
+            }
+            GenerationRes::ShopStock(location, wares) => {
+                commands::open_shop(engine, location, wares);
+                let waiting = engine.pending_shops.remove(&location);
+                for player in waiting.unwrap_or_default() {
+                    // They may have wandered off while the shopkeeper was busy
+                    if engine.world.player_character(player).location != location {
+                        continue;
+                    }
+                    let res = commands::list_wares(engine, player).unwrap_or_else(|e| e);
+                    engine.connection_broker.send_player_message(player, res);
+                }
+            }
+            GenerationRes::Blueprints(location, blueprints) => {
+                commands::open_workshop(engine, location, blueprints);
+                let waiting = engine.pending_workshops.remove(&location);
+                for player in waiting.unwrap_or_default() {
+                    if engine.world.player_character(player).location != location {
+                        continue;
+                    }
+                    let res = commands::list_blueprints(engine, player).unwrap_or_else(|e| e);
+                    engine.connection_broker.send_player_message(player, res);
+                }
+            }
+            GenerationRes::Recipe(mut recipe) => {
+                if engine.world.find_recipe(&recipe.name).is_some() {
+                    let last = recipe.ingredients.last().cloned().unwrap_or_default();
+                    recipe.name = format!("{} of {last}", recipe.name);
+                }
+
+                let waiting = engine.pending_recipes.remove(&recipe.key());
+                engine.world.recipes.insert(recipe.key(), recipe.clone());
+                for player in waiting.unwrap_or_default() {
+                    commands::finish_crafting(engine, player, &recipe);
+                }
+            }
         }
     }
 }
 
-/// Add a new overworld map entry to the world and connect it to existing entries
-fn add_new_locale(engine: &mut Engine, mut place: Place, rooms: HashMap<Location, Place>) {
-    for ow_location in &engine.world.overworld_locales {
-        let ow_place = engine.world.places.get_mut(ow_location).unwrap();
-        // Limit to 5 connections to avoid adding things in the up direction
-        if ow_place.connections().len() < 5 {
-            let dir = ow_place
-                .add_connection(Direction::North, place.location)
-                .expect("Should be able to add overworld connection");
-            place
-                .add_connection(dir.reverse(), *ow_location)
-                .expect("Should be able to add overworld connection");
-            break;
-        }
+/// Add a new overworld map entry and its rooms to the world
+fn add_new_locale(engine: &mut Engine, place: Place, rooms: HashMap<Location, Place>) {
+    for (location, mut room) in rooms.into_iter() {
+        room.parent = Some(place.location);
+        engine.world.place_changed(location);
+        engine.world.places.insert(location, room);
     }
 
-    engine.world.overworld_locales.push(place.location);
-    engine.world.places.insert(place.location, place);
+    let is_dungeon = place.tags.contains(DUNGEON_PLACE_TYPE.name());
+    let dungeon = (place.name.clone(), place.description.clone());
+    let region = place.location;
+    engine.world.add_overworld_locale(place);
 
-    for (location, room) in rooms.into_iter() {
-        engine.world.places.insert(location, room);
+    if is_dungeon {
+        let rooms = engine.world.area_depths(region);
+        let loot = rooms.len() / 2 + 1;
+        engine
+            .gen_handle
+            .request_generate(GenerationReq::Creatures(dungeon, rooms));
+
+        engine
+            .gen_handle
+            .request_generate(GenerationReq::Items(region, DUNGEON_PLACE_TYPE, loot));
     }
 }
 
-fn startup_generation(engine: &mut Engine) {
-    if engine.world.places.len() == 0 {
-        let count = 3;
-        tracing::info!("Requesting {count} new villages");
-        engine.gen_handle.request_generate(GenerationReq::Places(
-            crate::generation::VILLAGE_PLACE_TYPE,
-            count,
+/// Fills out a fresh or loaded world with its fixed areas and kicks off generation if it's empty
+fn populate_world(engine: &mut Engine) {
+    startup_generation(engine);
+    if config::get().starting_village {
+        engine.world.ensure_starting_village();
+    }
+    areas::load_areas(&mut engine.world, config::get().areas_dir.as_ref());
+}
+
+/// How long before a world reset players are warned, in seconds
+const DREAM_CYCLE_WARNINGS: [f64; 4] = [3600.0, 600.0, 60.0, 10.0];
+
+fn check_dream_cycle(engine: &mut Engine) {
+    let config = config::get();
+    if config.dream_cycle_hours <= 0.0 {
+        return;
+    }
+
+    let cycle_end = engine.world.cycle_started_tick
+        + config.seconds_to_ticks(config.dream_cycle_hours * 3600.0);
+    let remaining = cycle_end.saturating_sub(engine.world.current_tick);
+
+    if let Some(seconds) = DREAM_CYCLE_WARNINGS
+        .iter()
+        .find(|s| config.seconds_to_ticks(**s) == remaining)
+    {
+        let time = if *seconds >= 60.0 {
+            format!("{:.0} minutes", seconds / 60.0)
+        } else {
+            format!("{seconds:.0} seconds")
+        };
+        engine.connection_broker.broadcast(format!(
+            "The dream grows thin... this world will fade in {time}"
         ));
+    }
+
+    if remaining == 0 {
+        tracing::info!("Ending dream cycle {}", engine.world.cycle);
+        let old_world = engine.world.next_cycle(config.reset_wealth_carryover);
+        std::thread::spawn(move || {
+            if let Err(e) = old_world.archive() {
+                tracing::error!("Failed archiving world: {e}");
+            }
+        });
+
+        populate_world(engine);
+
+        let players: Vec<_> = engine.world.player_characters.keys().copied().collect();
+        for player in players {
+            if engine.connection_broker.is_connected(player) {
+                engine.world.check_player_location(player);
+                let location = engine.world.player_character(player).location;
+                let look_msg = engine.world.places[&location]
+                    .look(&engine.world, "The old world fades away and you wake in");
+                engine
+                    .connection_broker
+                    .send_player_message(player, look_msg);
+            }
+        }
+    }
+}
 
-        let count = 5;
-        tracing::info!("Requesting {count} new dungeons");
-        engine.gen_handle.request_generate(GenerationReq::Places(
-            crate::generation::DUNGEON_PLACE_TYPE,
-            count,
+fn startup_generation(engine: &mut Engine) {
+    if engine.world.places.is_empty() {
+        request_places(engine, VILLAGE_PLACE_TYPE, 3);
+        request_places(engine, DUNGEON_PLACE_TYPE, 5);
+    }
+}
+
+/// Keeps a few unexplored places ahead of the players, requesting more once they've
+/// explored enough that fewer than the configured number remain
+fn expand_world(engine: &mut Engine) {
+    let config = config::get();
+    let check_ticks = config
+        .seconds_to_ticks(config.expansion_check_seconds)
+        .max(1);
+    if !engine.world.current_tick.is_multiple_of(check_ticks) || engine.pending_places > 0 {
+        return;
+    }
+
+    let locales = engine.world.overworld_locales.len();
+    let wanted = config
+        .min_unexplored_locales
+        .max(engine.connection_broker.connected());
+    let missing = wanted
+        .saturating_sub(engine.world.unexplored_locales())
+        .min(config.max_overworld_locales.saturating_sub(locales));
+    if missing == 0 {
+        return;
+    }
+
+    // Keep roughly two dungeons for every village
+    let villages = engine
+        .world
+        .overworld_locales
+        .iter()
+        .filter(|l| {
+            engine.world.places[l]
+                .tags
+                .contains(VILLAGE_PLACE_TYPE.name())
+        })
+        .count();
+    let place_type = if villages * 3 < locales {
+        VILLAGE_PLACE_TYPE
+    } else {
+        DUNGEON_PLACE_TYPE
+    };
+
+    request_places(engine, place_type, missing);
+}
+
+fn request_places(engine: &mut Engine, place_type: PlaceType, count: usize) {
+    tracing::info!("Requesting {count} new {}s", place_type.name());
+    engine.pending_places += count;
+    engine
+        .gen_handle
+        .request_generate(GenerationReq::Places(place_type, count));
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{connections::EngineConnection, state::Password, PlayerAccount, Role};
+
+    use super::*;
+
+    fn account(username: &str, role: Role) -> PlayerAccount {
+        PlayerAccount {
+            username: username.to_string(),
+            password: Password::Legacy(0),
+            color: false,
+            width: None,
+            compress: false,
+            role,
+            banned: false,
+        }
+    }
+
+    /// An engine with a village and the given accounts, which are saved to a
+    /// temp file named for the test
+    fn test_engine(
+        name: &str,
+        accounts: HashMap<PlayerId, PlayerAccount>,
+    ) -> (Engine, PlayerConnectionBroker) {
+        let path = std::env::temp_dir().join(format!(
+            "somnuscape-{name}-{}/accounts.yaml",
+            std::process::id()
         ));
+        let (player_broker, connection_broker) = PlayerConnectionBroker::new();
+        let (_, gen_handle) = crate::generation::Generator::new();
+        let mut world = World::default();
+        world.ensure_starting_village();
+        let engine = Engine {
+            realm: RealmConfig {
+                name: "Test".into(),
+                save_dir: String::new(),
+                server_address: None,
+                rule_profile: None,
+            },
+            rules: GameRules::default(),
+            player_registry: AccountStorage::at(path, accounts),
+            connection_broker,
+            gen_handle,
+            pending_lore: HashMap::new(),
+            pending_recipes: HashMap::new(),
+            pending_stories: HashMap::new(),
+            pending_shops: HashMap::new(),
+            pending_workshops: HashMap::new(),
+            pending_places: 0,
+            pending_weather: HashSet::new(),
+            clock: Clock::default(),
+            prompts: HashMap::new(),
+            hooks: Hooks::load(Path::new("no-such-hooks")),
+            bots: Bots::new(player_broker.clone()),
+            digest: DigestLog::new(&world, None),
+            alerts: Alerts::new(Instant::now(), 0),
+            world,
+        };
+        (engine, player_broker)
+    }
+
+    fn connect(
+        engine: &mut Engine,
+        broker: &PlayerConnectionBroker,
+        player: PlayerId,
+        name: &str,
+    ) -> EngineConnection {
+        let connection = broker.setup_connection(player);
+        engine.connection_broker.handle_connection_changes();
+        engine.world.player_character(player).name = name.to_string();
+        connection
+    }
+
+    /// Everything the engine's sent a player so far
+    fn received(engine: &mut Engine, connection: &mut EngineConnection) -> String {
+        engine.connection_broker.flush();
+        std::iter::from_fn(|| connection.try_recv())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn only_admins_use_admin_commands() {
+        let player = PlayerId::new_random();
+        let accounts = HashMap::from([(player, account("bob", Role::Player))]);
+        let (mut engine, broker) = test_engine("admin-gate", accounts);
+        let mut connection = connect(&mut engine, &broker, player, "Bob");
+
+        let admin_commands: Vec<_> = commands::get_command_list()
+            .iter()
+            .filter(|c| c.admin)
+            .map(|c| c.name.clone())
+            .collect();
+        assert!(!admin_commands.is_empty());
+        for name in admin_commands {
+            run_command(&mut engine, player, &name);
+            let reply = received(&mut engine, &mut connection);
+            assert_eq!(reply, format!("Only admins can use '{name}'"));
+        }
+        assert!(engine.connection_broker.is_connected(player));
+    }
+
+    #[test]
+    fn bans_disconnect_players() {
+        let (admin, bob) = (PlayerId::new_random(), PlayerId::new_random());
+        let accounts = HashMap::from([
+            (admin, account("ada", Role::Admin)),
+            (bob, account("bob", Role::Player)),
+        ]);
+        let (mut engine, broker) = test_engine("ban", accounts);
+        let mut admin_connection = connect(&mut engine, &broker, admin, "Ada");
+        let mut bob_connection = connect(&mut engine, &broker, bob, "Bob");
+
+        run_command(&mut engine, admin, "ban bob");
+        assert_eq!(
+            received(&mut engine, &mut admin_connection),
+            "Bob fades from the dream\nBanned bob"
+        );
+        assert!(engine.player_registry.blocking_read()[&bob].banned);
+        assert!(!engine.connection_broker.is_connected(bob));
+        assert!(received(&mut engine, &mut bob_connection).contains("You've been banned"));
+
+        run_command(&mut engine, admin, "ban ada");
+        assert_eq!(
+            received(&mut engine, &mut admin_connection),
+            "You can't ban yourself"
+        );
+    }
+
+    #[test]
+    fn no_camping_mid_fight() {
+        let player = PlayerId::new_random();
+        let accounts = HashMap::from([(player, account("ada", Role::Player))]);
+        let (mut engine, broker) = test_engine("camp", accounts);
+        let mut connection = connect(&mut engine, &broker, player, "Ada");
+
+        engine.world.player_character(player).in_combat_until = 50;
+        run_command(&mut engine, player, "camp");
+        assert_eq!(
+            received(&mut engine, &mut connection),
+            "You can't make camp in the middle of a fight!"
+        );
+        assert_eq!(engine.world.player_characters[&player].camping_until, None);
+
+        engine.world.current_tick = 50;
+        run_command(&mut engine, player, "camp");
+        assert!(received(&mut engine, &mut connection).starts_with("You start making camp"));
+        assert!(engine.world.player_characters[&player]
+            .camping_until
+            .is_some());
+    }
+
+    #[test]
+    fn players_wake_where_they_left() {
+        let player = PlayerId::new_random();
+        let accounts = HashMap::from([(player, account("ada", Role::Player))]);
+        let (mut engine, broker) = test_engine("wake", accounts);
+        let place = Place::new("Quiet Glade".into(), "Moss and ferns".into());
+        let glade = place.location;
+        engine.world.places.insert(glade, place);
+        let character = engine.world.player_character(player);
+        character.name = "Ada".into();
+        character.location = glade;
+        character.camping_until = Some(3);
+
+        // As if the server restarted and loaded its save
+        let saved = serde_yaml::to_string(&engine.world).unwrap();
+        engine.world = serde_yaml::from_str(&saved).unwrap();
+        let mut connection = broker.setup_connection(player);
+        handle_connections(&mut engine);
+
+        let character = &engine.world.player_characters[&player];
+        assert_eq!(character.location, glade);
+        assert_eq!(character.camping_until, None);
+        let woke = received(&mut engine, &mut connection);
+        assert!(woke.starts_with("You wake in <title>Quiet Glade"));
+    }
+
+    #[test]
+    fn listed_admins_are_admins() {
+        let bob = account("Bob", Role::Player);
+        assert!(commands::listed_admin(&bob, &["bob".to_string()]));
+        assert!(!commands::listed_admin(&bob, &["ada".to_string()]));
+        assert!(!commands::listed_admin(&bob, &[]));
     }
 }
@@ -0,0 +1,187 @@
+//! The ways players reach the game. Each front end turns its protocol into
+//! lines from the player and messages to them, everything past that, logging
+//! in, picking a realm and talking to the engine, is shared. Adding a protocol
+//! means adding a front end, nothing here or in the login flow changes.
+
+use std::net::SocketAddr;
+
+use anyhow::Result;
+use futures::{future::BoxFuture, FutureExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::{
+    editor::{self, Edit, Editor},
+    markup,
+    state::PlayerId,
+    AccountStorage, AppErrors, ConnectionState, Realm, WELCOME,
+};
+
+/// One player's connection, however they reached us
+pub trait Transport: Send {
+    /// The next line the player sent, or None once they've gone. Dropping the
+    /// future before it's done mustn't lose anything the player sent.
+    fn next_line(&mut self) -> BoxFuture<'_, Option<String>>;
+
+    fn send(&mut self, msg: String) -> BoxFuture<'_, Result<()>>;
+
+    /// Asks the client not to show what's typed, while a password is entered
+    fn hide_input(&mut self, _hidden: bool) -> BoxFuture<'_, Result<()>> {
+        async { Ok(()) }.boxed()
+    }
+
+    /// Compresses what's sent from now on, if the client can take it
+    fn set_compression(&mut self, _on: bool) -> BoxFuture<'_, Result<()>> {
+        async { Ok(()) }.boxed()
+    }
+
+    /// How wide the client says its window is
+    fn width(&self) -> Option<u16> {
+        None
+    }
+}
+
+/// A protocol players connect over the network with
+pub trait FrontEnd: Send + Sync + 'static {
+    type Transport: Transport + 'static;
+
+    /// Sets up a player's connection on a freshly accepted stream
+    fn connect(&self, stream: TcpStream) -> BoxFuture<'static, Result<Self::Transport>>;
+}
+
+/// Accepts players on the address with the front end, letting them into the realms
+pub async fn listen<F: FrontEnd>(
+    front_end: F,
+    addr: String,
+    players: AccountStorage,
+    realms: Vec<Realm>,
+) -> Result<()> {
+    let addr: SocketAddr = addr
+        .parse()
+        .expect("Server address should be a valid <ip:port>");
+    let listener = TcpListener::bind(addr).await?;
+
+    loop {
+        while let Ok((stream, addr)) = listener.accept().await {
+            tracing::info!("Player connected from {addr}");
+
+            let players = players.clone();
+            let realms = realms.clone();
+            let connecting = front_end.connect(stream);
+
+            tokio::spawn(async move {
+                let mut connection_state = ConnectionState::Unauthorized;
+
+                let session = match connecting.await {
+                    Ok(transport) => {
+                        serve(transport, players.clone(), &realms, &mut connection_state).await
+                    }
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = session {
+                    if e.is::<AppErrors>() {
+                        if let Ok(AppErrors::PlayerDisconnected(id)) = e.downcast::<AppErrors>() {
+                            let pr = players.read().await;
+                            let n = pr.get(&id).map(|p| p.username.as_str()).unwrap_or_default();
+                            tracing::info!("Player disconnected: {n}");
+                        }
+                    } else {
+                        tracing::error!("Player connection error: {e}");
+                    }
+                }
+
+                if let Some((player_id, realm)) = connection_state.get_connection() {
+                    realms[realm].broker.end_connection(player_id);
+                }
+            });
+        }
+    }
+}
+
+/// Runs a player's session over the transport, from the welcome until they leave
+pub async fn serve(
+    mut transport: impl Transport,
+    player_registry: AccountStorage,
+    realms: &[Realm],
+    connection_state: &mut ConnectionState,
+) -> Result<()> {
+    transport.send(WELCOME.to_string()).await?;
+    // Open while the player's writing something long, see [`crate::editor`]
+    let mut editor: Option<Editor> = None;
+
+    loop {
+        if let ConnectionState::Authorized(_, _, ref mut handler) = connection_state {
+            // The engine asks for the editor alongside what it sends, but a line
+            // can beat that here and should still go to the editor
+            if let Some(document) = handler.take_editor() {
+                editor = Some(Editor::new(&document));
+            }
+            tokio::select! {
+                line = transport.next_line() => match line {
+                    Some(line) => match editor.as_mut().map(|e| e.handle(&line)) {
+                        None => handler.send(line)?,
+                        Some(Edit::Reply(reply)) => {
+                            if !reply.is_empty() {
+                                transport.send(reply).await?;
+                            }
+                        }
+                        Some(Edit::Save(document)) => {
+                            editor = None;
+                            handler.send(document)?;
+                        }
+                        Some(Edit::Abort) => {
+                            editor = None;
+                            handler.send(editor::ABORT.to_string())?;
+                        }
+                    },
+                    // The player hung up, stop now so the engine hears about it straight away
+                    None => break,
+                },
+                response = handler.recv() => {
+                    let player = handler.player();
+                    let response = render_for(&player_registry, player, &response?, transport.width()).await;
+                    transport.set_compression(wants_compression(&player_registry, player).await).await?;
+                    transport.send(response).await?;
+                }
+            }
+        } else {
+            let Some(line) = transport.next_line().await else {
+                break;
+            };
+            let was_hidden = connection_state.wants_password();
+            let reply = connection_state
+                .handle_login(line, &player_registry, realms)
+                .await?;
+
+            let hidden = connection_state.wants_password();
+            if hidden != was_hidden {
+                transport.hide_input(hidden).await?;
+            }
+            transport.send(reply).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Renders an engine message with or without colour, as the player prefers,
+/// wrapped to the width they picked or else the one their client reported
+async fn render_for(
+    player_registry: &AccountStorage,
+    player: PlayerId,
+    msg: &str,
+    client_width: Option<u16>,
+) -> String {
+    let accounts = player_registry.read().await;
+    let account = accounts.get(&player);
+    let color = account.is_none_or(|p| p.color);
+    let width = account
+        .and_then(|p| p.width)
+        .or(client_width.filter(|w| *w > 0))
+        .map_or(markup::DEFAULT_WIDTH, usize::from);
+    markup::wrap(&markup::render(msg, color), width)
+}
+
+async fn wants_compression(player_registry: &AccountStorage, player: PlayerId) -> bool {
+    let accounts = player_registry.read().await;
+    accounts.get(&player).is_none_or(|p| p.compress)
+}
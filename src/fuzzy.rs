@@ -0,0 +1,72 @@
+//! Fuzzy matching for names players mistype, so a miss can suggest what they
+//! probably meant. Commands, items, people and creatures all go through here
+//! so suggestions feel the same wherever they show up.
+
+/// One typo is forgiven for every this many characters typed, swapped letters count as one
+const CHARS_PER_TYPO: usize = 3;
+/// Never more typos than this, however long the name
+const MAX_TYPOS: usize = 3;
+
+/// How far the input is from a name, lower is closer. Case doesn't count, and
+/// each word of the name is tried too, so 'swrod' is close to 'rusty sword'.
+pub fn distance(input: &str, name: &str) -> usize {
+    let input = input.trim().to_lowercase();
+    let name = name.to_lowercase();
+    if !input.is_empty() && name.starts_with(&input) {
+        return 0;
+    }
+
+    std::iter::once(name.as_str())
+        .chain(name.split_whitespace())
+        .map(|n| strsim::osa_distance(&input, n))
+        .min()
+        .unwrap_or(usize::MAX)
+}
+
+/// The names, closest to the input first
+pub fn ranked<S: AsRef<str>>(input: &str, names: impl IntoIterator<Item = S>) -> Vec<S> {
+    let mut names: Vec<_> = names
+        .into_iter()
+        .map(|n| (distance(input, n.as_ref()), n))
+        .collect();
+    names.sort_by_key(|(d, _)| *d);
+    names.into_iter().map(|(_, n)| n).collect()
+}
+
+/// The closest name, if it's near enough that the player probably meant it
+pub fn closest<S: AsRef<str>>(input: &str, names: impl IntoIterator<Item = S>) -> Option<S> {
+    let allowed = (input.trim().chars().count() / CHARS_PER_TYPO).clamp(1, MAX_TYPOS);
+    ranked(input, names)
+        .into_iter()
+        .next()
+        .filter(|n| distance(input, n.as_ref()) <= allowed)
+}
+
+/// A suggestion to tack onto a 'not found' message, or nothing if no name is close
+pub fn did_you_mean<S: AsRef<str>>(input: &str, names: impl IntoIterator<Item = S>) -> String {
+    closest(input, names)
+        .map(|n| format!(", did you mean '{}'?", n.as_ref()))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn suggests_close_names() {
+        let names = ["rusty sword", "Honeycomb", "torch"];
+        assert_eq!(closest("swrod", names), Some("rusty sword"));
+        assert_eq!(closest("HONYCOMB", names), Some("Honeycomb"));
+        assert_eq!(closest("tor", names), Some("torch"));
+        assert_eq!(closest("lantern", names), None);
+        assert_eq!(closest("x", Vec::<String>::new()), None);
+
+        assert_eq!(ranked("torc", names)[0], "torch");
+        assert_eq!(
+            did_you_mean("swrod", names),
+            ", did you mean 'rusty sword'?"
+        );
+        assert_eq!(did_you_mean("lantern", names), "");
+    }
+}
@@ -0,0 +1,504 @@
+use std::{
+    fmt::Debug,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{anyhow, Context, Result};
+use futures::{future::BoxFuture, stream::BoxStream, FutureExt, StreamExt, TryFutureExt};
+use ollama_rs::{
+    generation::{
+        completion::request::GenerationRequest, options::GenerationOptions, parameters::FormatType,
+    },
+    Ollama,
+};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+
+use crate::config::AIBackendConfig;
+
+const OPENAI_BASE_URL: &str = "https://api.openai.com/v1";
+
+/// Which kind of API the AI backend speaks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BackendKind {
+    /// A local or remote Ollama server
+    #[default]
+    Ollama,
+    /// OpenAI or anything that copies its chat completions API,
+    /// such as vLLM, llama.cpp's server or OpenRouter
+    #[serde(alias = "openai-compatible")]
+    Openai,
+    /// Plays back responses recorded in the fixtures file, without any model
+    Replay,
+}
+
+/// Something that turns prompts into text. Everything above this, tone,
+/// seeding and the request budget, is handled by the `AIClient`.
+pub trait GenerationBackend: Debug + Send + Sync {
+    /// Completes a prompt, backends that support seeds should give the same
+    /// text for the same prompt and seed. If `json` is set the backend should
+    /// constrain its output to a JSON value where it's able to.
+    fn generate(
+        &self,
+        prompt: String,
+        seed: i32,
+        temperature: f32,
+        json: bool,
+    ) -> BoxFuture<'_, Result<String>>;
+
+    /// Completes a prompt a piece at a time as the model writes it. Backends
+    /// that can't stream give the whole reply as one piece.
+    fn generate_stream(
+        &self,
+        prompt: String,
+        seed: i32,
+        temperature: f32,
+    ) -> BoxStream<'_, Result<String>> {
+        self.generate(prompt, seed, temperature, false)
+            .into_stream()
+            .boxed()
+    }
+}
+
+/// Makes the backend described in the config. Offline servers only replay
+/// fixtures, otherwise a fixtures file records what the backend says.
+pub fn from_config(config: &AIBackendConfig, offline: bool) -> Result<Arc<dyn GenerationBackend>> {
+    let live: Arc<dyn GenerationBackend> = match config.kind {
+        _ if offline => {
+            return Ok(Arc::new(FixtureBackend::load(
+                config.fixtures.clone(),
+                None,
+            )?))
+        }
+        BackendKind::Ollama => Arc::new(OllamaBackend::new(config)?),
+        BackendKind::Openai => Arc::new(OpenAIBackend::new(config)),
+        BackendKind::Replay => Arc::new(FixtureBackend::load(config.fixtures.clone(), None)?),
+    };
+
+    Ok(match &config.fixtures {
+        Some(path) if config.kind != BackendKind::Replay => {
+            Arc::new(FixtureBackend::load(Some(path.clone()), Some(live))?)
+        }
+        _ => live,
+    })
+}
+
+/// A prompt and what the backend said to it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct Fixture {
+    prompt: String,
+    #[serde(default)]
+    json: bool,
+    response: String,
+}
+
+/// Replays responses from a YAML file, recording new ones from a live backend
+/// if it has one. Seeds and temperature are ignored so a prompt always gets
+/// the same reply, which lets generation run in tests and without a model.
+#[derive(Debug, Default)]
+pub struct FixtureBackend {
+    path: Option<PathBuf>,
+    fixtures: Mutex<Vec<Fixture>>,
+    recording: Option<Arc<dyn GenerationBackend>>,
+}
+
+impl FixtureBackend {
+    pub fn load(
+        path: Option<impl Into<PathBuf>>,
+        recording: Option<Arc<dyn GenerationBackend>>,
+    ) -> Result<Self> {
+        let path = path.map(Into::into);
+        let fixtures = match &path {
+            Some(path) if path.try_exists()? => {
+                serde_yaml::from_str(&std::fs::read_to_string(path)?)
+                    .with_context(|| format!("Could not read fixtures {}", path.display()))?
+            }
+            _ => Vec::new(),
+        };
+
+        Ok(Self {
+            path,
+            fixtures: Mutex::new(fixtures),
+            recording,
+        })
+    }
+
+    fn find(&self, prompt: &str, json: bool) -> Option<String> {
+        self.fixtures
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|f| f.prompt == prompt && f.json == json)
+            .map(|f| f.response.clone())
+    }
+
+    fn record(&self, fixture: Fixture) -> Result<()> {
+        let mut fixtures = self.fixtures.lock().unwrap();
+        fixtures.push(fixture);
+        if let Some(path) = &self.path {
+            std::fs::write(path, serde_yaml::to_string(&*fixtures)?)?;
+        }
+        Ok(())
+    }
+}
+
+impl GenerationBackend for FixtureBackend {
+    fn generate(
+        &self,
+        prompt: String,
+        seed: i32,
+        temperature: f32,
+        json: bool,
+    ) -> BoxFuture<'_, Result<String>> {
+        async move {
+            if let Some(response) = self.find(&prompt, json) {
+                return Ok(response);
+            }
+            let Some(live) = &self.recording else {
+                return Err(anyhow!("No recorded response for this prompt"));
+            };
+
+            let response = live
+                .generate(prompt.clone(), seed, temperature, json)
+                .await?;
+            self.record(Fixture {
+                prompt,
+                json,
+                response: response.clone(),
+            })?;
+            Ok(response)
+        }
+        .boxed()
+    }
+}
+
+#[derive(Debug)]
+pub struct OllamaBackend {
+    client: Ollama,
+    model: String,
+}
+
+impl OllamaBackend {
+    fn new(config: &AIBackendConfig) -> Result<Self> {
+        let client = if config.base_url.is_empty() {
+            Ollama::default()
+        } else {
+            let url = Url::parse(&config.base_url).context("Invalid Ollama base URL")?;
+            Ollama::new(
+                format!(
+                    "{}://{}",
+                    url.scheme(),
+                    url.host_str().context("Ollama base URL has no host")?
+                ),
+                url.port_or_known_default().unwrap_or(11434),
+            )
+        };
+
+        Ok(Self {
+            client,
+            model: config.model.clone(),
+        })
+    }
+}
+
+impl GenerationBackend for OllamaBackend {
+    fn generate(
+        &self,
+        prompt: String,
+        seed: i32,
+        temperature: f32,
+        json: bool,
+    ) -> BoxFuture<'_, Result<String>> {
+        async move {
+            let mut req = GenerationRequest::new(self.model.clone(), prompt).options(
+                GenerationOptions::default()
+                    .seed(seed)
+                    .temperature(temperature),
+            );
+            if json {
+                req = req.format(FormatType::Json);
+            }
+
+            Ok(self.client.generate(req).await?.response)
+        }
+        .boxed()
+    }
+
+    fn generate_stream(
+        &self,
+        prompt: String,
+        seed: i32,
+        temperature: f32,
+    ) -> BoxStream<'_, Result<String>> {
+        let req = GenerationRequest::new(self.model.clone(), prompt).options(
+            GenerationOptions::default()
+                .seed(seed)
+                .temperature(temperature),
+        );
+
+        self.client
+            .generate_stream(req)
+            .map_ok(|stream| {
+                stream.map(|chunk| Ok(chunk?.into_iter().map(|r| r.response).collect::<String>()))
+            })
+            .map_err(anyhow::Error::from)
+            .try_flatten_stream()
+            .boxed()
+    }
+}
+
+#[derive(Debug)]
+pub struct OpenAIBackend {
+    client: reqwest::Client,
+    base_url: String,
+    model: String,
+    api_key: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ChatRequest<'a> {
+    model: &'a str,
+    messages: [ChatMessage; 1],
+    temperature: f32,
+    seed: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<ResponseFormat>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
+}
+
+#[derive(Serialize)]
+struct ResponseFormat {
+    #[serde(rename = "type")]
+    kind: &'static str,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ChatMessage {
+    role: String,
+    content: String,
+}
+
+#[derive(Deserialize)]
+struct ChatResponse {
+    choices: Vec<ChatChoice>,
+}
+
+#[derive(Deserialize)]
+struct ChatChoice {
+    message: ChatMessage,
+}
+
+/// One event of a streamed chat completion
+#[derive(Deserialize)]
+struct ChatChunk {
+    choices: Vec<ChunkChoice>,
+}
+
+#[derive(Deserialize)]
+struct ChunkChoice {
+    #[serde(default)]
+    delta: ChatDelta,
+}
+
+#[derive(Default, Deserialize)]
+struct ChatDelta {
+    #[serde(default)]
+    content: Option<String>,
+}
+
+/// Takes the text out of each complete `data:` line of a streamed chat
+/// completion, leaving any partial line in the buffer for the next read
+fn take_chat_deltas(buffer: &mut Vec<u8>) -> Result<String> {
+    let mut text = String::new();
+    while let Some(end) = buffer.iter().position(|b| *b == b'\n') {
+        let line: Vec<_> = buffer.drain(..=end).collect();
+        let line = String::from_utf8_lossy(&line);
+        let Some(data) = line.trim().strip_prefix("data:") else {
+            continue;
+        };
+        let data = data.trim();
+        if data == "[DONE]" {
+            continue;
+        }
+
+        let chunk: ChatChunk = serde_json::from_str(data)?;
+        for choice in chunk.choices {
+            text.extend(choice.delta.content);
+        }
+    }
+    Ok(text)
+}
+
+impl OpenAIBackend {
+    fn new(config: &AIBackendConfig) -> Self {
+        let base_url = if config.base_url.is_empty() {
+            OPENAI_BASE_URL
+        } else {
+            &config.base_url
+        };
+
+        Self {
+            client: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            model: config.model.clone(),
+            api_key: config.api_key(),
+        }
+    }
+
+    fn chat_request(
+        &self,
+        prompt: String,
+        seed: i32,
+        temperature: f32,
+        json: bool,
+        stream: bool,
+    ) -> reqwest::RequestBuilder {
+        let body = ChatRequest {
+            model: &self.model,
+            messages: [ChatMessage {
+                role: "user".into(),
+                content: prompt,
+            }],
+            temperature,
+            seed,
+            response_format: json.then_some(ResponseFormat {
+                kind: "json_object",
+            }),
+            stream,
+        };
+
+        let req = self
+            .client
+            .post(format!("{}/chat/completions", self.base_url))
+            .json(&body);
+        match &self.api_key {
+            Some(key) => req.bearer_auth(key),
+            None => req,
+        }
+    }
+}
+
+impl GenerationBackend for OpenAIBackend {
+    fn generate(
+        &self,
+        prompt: String,
+        seed: i32,
+        temperature: f32,
+        json: bool,
+    ) -> BoxFuture<'_, Result<String>> {
+        async move {
+            let req = self.chat_request(prompt, seed, temperature, json, false);
+            let res: ChatResponse = req.send().await?.error_for_status()?.json().await?;
+            res.choices
+                .into_iter()
+                .next()
+                .map(|c| c.message.content)
+                .context("AI backend returned no choices")
+        }
+        .boxed()
+    }
+
+    fn generate_stream(
+        &self,
+        prompt: String,
+        seed: i32,
+        temperature: f32,
+    ) -> BoxStream<'_, Result<String>> {
+        let req = self.chat_request(prompt, seed, temperature, false, true);
+
+        async move {
+            let res = req.send().await?.error_for_status()?;
+            let mut buffer = Vec::new();
+            Ok(res.bytes_stream().map(move |bytes| {
+                buffer.extend_from_slice(&bytes?);
+                take_chat_deltas(&mut buffer)
+            }))
+        }
+        .try_flatten_stream()
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn backends_use_configured_urls() {
+        let mut config = AIBackendConfig {
+            base_url: "http://gpu-box:8000/".into(),
+            ..Default::default()
+        };
+        assert_eq!(
+            OllamaBackend::new(&config).unwrap().client.uri(),
+            "http://gpu-box:8000"
+        );
+        assert_eq!(OpenAIBackend::new(&config).base_url, "http://gpu-box:8000");
+
+        config.base_url = String::new();
+        assert_eq!(OpenAIBackend::new(&config).base_url, OPENAI_BASE_URL);
+        config.base_url = "not a url".into();
+        assert!(OllamaBackend::new(&config).is_err());
+
+        let kind: BackendKind = serde_yaml::from_str("openai-compatible").unwrap();
+        assert_eq!(kind, BackendKind::Openai);
+    }
+
+    #[test]
+    fn reads_streamed_chat() {
+        let mut buffer = b"data: {\"choices\":[{\"delta\":{\"role\":\"assistant\"}}]}\n\ndata: {\"choices\":[{\"delta\":{\"content\":\"Well \"}}]}\n\ndata: {\"choi".to_vec();
+        assert_eq!(take_chat_deltas(&mut buffer).unwrap(), "Well ");
+
+        buffer.extend_from_slice(b"ces\":[{\"delta\":{\"content\":\"met\"}}]}\n\ndata: [DONE]\n\n");
+        assert_eq!(take_chat_deltas(&mut buffer).unwrap(), "met");
+        assert!(buffer.is_empty());
+    }
+
+    #[derive(Debug)]
+    struct EchoBackend;
+
+    impl GenerationBackend for EchoBackend {
+        fn generate(
+            &self,
+            prompt: String,
+            _: i32,
+            _: f32,
+            _: bool,
+        ) -> BoxFuture<'_, Result<String>> {
+            async move { Ok(format!("You said {prompt}")) }.boxed()
+        }
+    }
+
+    #[tokio::test]
+    async fn records_then_replays() {
+        let path =
+            std::env::temp_dir().join(format!("somnuscape-fixtures-{}.yaml", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let recorder = FixtureBackend::load(Some(&path), Some(Arc::new(EchoBackend))).unwrap();
+        let said = recorder
+            .generate("hello".into(), 1, 0.5, false)
+            .await
+            .unwrap();
+        assert_eq!(said, "You said hello");
+
+        let replay = FixtureBackend::load(Some(&path), None).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            replay
+                .generate("hello".into(), 2, 0.9, false)
+                .await
+                .unwrap(),
+            said
+        );
+        assert!(replay.generate("hello".into(), 1, 0.5, true).await.is_err());
+        assert!(replay
+            .generate("goodbye".into(), 1, 0.5, false)
+            .await
+            .is_err());
+    }
+}
@@ -2,30 +2,185 @@ use anyhow::Result;
 use askama::Template;
 use serde::{Deserialize, Serialize};
 
-use crate::{generation, mud::character::Attributes};
+use crate::{
+    mud::{
+        character::{Attribute, Attributes, Character},
+        items::{ItemInstance, Quality},
+        world::Location,
+    },
+    AppErrors,
+};
 
-use super::AIClient;
+use super::{extract_md_kv_list, fallback, AIClient};
+
+/// Anything outside this range isn't a stat, it's the model rambling
+const ABSURD_ATTRIBUTE_RANGE: std::ops::RangeInclusive<i32> = 1..=100;
+const MIN_ATTRIBUTE: i32 = 3;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CreatureTemplate {
     pub name: String,
     pub attributes: Attributes,
     pub items: Vec<String>,
+    /// Any changes made to the generated stats to keep them in budget
+    #[serde(default)]
+    pub adjustments: Vec<String>,
+}
+
+/// How dangerous a creature is meant to be, which sets how many
+/// attribute points it gets to spend
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CreatureTier {
+    Minion,
+    Standard,
+    Elite,
+    Boss,
+}
+
+impl CreatureTier {
+    /// How dangerous a creature should be at a given depth into a dungeon,
+    /// the deepest room is where the boss lives
+    pub fn for_depth(depth: usize, max_depth: usize) -> Self {
+        match depth {
+            d if d == max_depth && d > 1 => CreatureTier::Boss,
+            d if d + 1 == max_depth && d > 1 => CreatureTier::Elite,
+            d if d > 2 => CreatureTier::Standard,
+            _ => CreatureTier::Minion,
+        }
+    }
+
+    fn quality(self) -> Quality {
+        match self {
+            CreatureTier::Minion => Quality::Crude,
+            CreatureTier::Standard => Quality::Common,
+            CreatureTier::Elite => Quality::Fine,
+            CreatureTier::Boss => Quality::Masterwork,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CreatureTier::Minion => "minion",
+            CreatureTier::Standard => "standard",
+            CreatureTier::Elite => "elite",
+            CreatureTier::Boss => "boss",
+        }
+    }
+
+    /// The highest any single attribute can be
+    pub fn attribute_cap(self) -> i32 {
+        match self {
+            CreatureTier::Minion => 14,
+            CreatureTier::Standard => 16,
+            CreatureTier::Elite => 18,
+            CreatureTier::Boss => 20,
+        }
+    }
+
+    /// The most all attributes can add up to
+    pub fn attribute_budget(self) -> i32 {
+        match self {
+            CreatureTier::Minion => 50,
+            CreatureTier::Standard => 60,
+            CreatureTier::Elite => 70,
+            CreatureTier::Boss => 85,
+        }
+    }
 }
 
-#[derive(Template, Default)]
+#[derive(Template)]
 #[template(path = "stat_creature.md")]
 struct StatCreatureTemplate<'a> {
     creature_name: &'a str,
+    tier: &'a str,
     attributes: &'a [&'a str],
 }
 
+#[derive(Template)]
+#[template(path = "creature_list.md")]
+struct CreatureListTemplate<'a> {
+    count: usize,
+    dungeon_name: &'a str,
+    dungeon_description: &'a str,
+}
+
+/// Comes up with creatures for a dungeon and stats one for each room that should
+/// have one, given the rooms' locations and how deep into the dungeon they are
+pub async fn populate(
+    client: &AIClient,
+    dungeon: &(String, String),
+    rooms: &[(Location, usize)],
+) -> Vec<(Location, Character)> {
+    let max_depth = rooms.iter().map(|(_, d)| *d).max().unwrap_or_default();
+    // The entrance is left empty so nobody is ambushed the moment they arrive
+    let occupied: Vec<_> = rooms.iter().filter(|(_, d)| *d > 1).collect();
+    if occupied.is_empty() {
+        return Vec::new();
+    }
+
+    let count = occupied.len().min(4);
+    let names = match client
+        .generate_with_tone(
+            CreatureListTemplate {
+                count,
+                dungeon_name: &dungeon.0,
+                dungeon_description: &dungeon.1,
+            }
+            .to_string(),
+        )
+        .await
+        .map(|res| extract_md_kv_list(&res))
+    {
+        Ok(names) if !names.is_empty() => names.into_iter().map(|(n, _)| n).collect(),
+        res => {
+            if let Err(e) = res {
+                tracing::warn!("Using fallback creatures for {}: {e}", dungeon.0);
+            }
+            fallback::creature_names(&dungeon.0, count)
+        }
+    };
+
+    let mut creatures = Vec::new();
+    for (i, (location, depth)) in occupied.into_iter().enumerate() {
+        let tier = CreatureTier::for_depth(*depth, max_depth);
+        let name = &names[i % names.len()];
+        let template = CreatureTemplate::stat_or_fallback(client, name, tier).await;
+        creatures.push((*location, template.spawn(tier)));
+    }
+
+    creatures
+}
+
 impl CreatureTemplate {
-    pub async fn stat_new(client: &AIClient, creature_name: &str) -> Result<Self> {
-        let res = client
-            .generate_simple(
+    /// Makes a fresh creature in the world from this template, its gear is
+    /// better made the more dangerous it is
+    pub fn spawn(&self, tier: CreatureTier) -> Character {
+        let mut character = Character {
+            name: self.name.clone(),
+            attributes: self.attributes.clone(),
+            ..Default::default()
+        };
+        character.health = character.max_health();
+        for item in &self.items {
+            character
+                .inventory
+                .add_instance(ItemInstance::new(item, tier.quality()));
+        }
+        character
+    }
+
+    pub async fn stat_new(
+        client: &AIClient,
+        creature_name: &str,
+        tier: CreatureTier,
+    ) -> Result<Self> {
+        let mut creature: Self = client
+            .stable()
+            .generate_structured(
                 StatCreatureTemplate {
                     creature_name,
+                    tier: tier.name(),
                     attributes: &[
                         "strength",
                         "toughness",
@@ -37,7 +192,74 @@ impl CreatureTemplate {
                 .to_string(),
             )
             .await?;
+        creature.normalize(tier)?;
+
+        Ok(creature)
+    }
+
+    /// Stats a creature with the AI backend, using a plain procedural creature
+    /// if that fails for any reason
+    pub async fn stat_or_fallback(
+        client: &AIClient,
+        creature_name: &str,
+        tier: CreatureTier,
+    ) -> Self {
+        match Self::stat_new(client, creature_name, tier).await {
+            Ok(creature) => creature,
+            Err(e) => {
+                tracing::warn!("Using fallback stats for {creature_name}: {e}");
+                fallback::creature(creature_name, tier)
+            }
+        }
+    }
+
+    /// Clamps the creature's attributes into the budget for its tier, recording
+    /// what was changed. Errors if the stats are too far gone to be salvaged.
+    pub fn normalize(&mut self, tier: CreatureTier) -> Result<(), AppErrors> {
+        if self
+            .attributes
+            .named()
+            .iter()
+            .any(|(_, a)| !ABSURD_ATTRIBUTE_RANGE.contains(&a.value()))
+        {
+            tracing::warn!(
+                "Rejecting absurd stats for {}: {:?}",
+                self.name,
+                self.attributes
+            );
+            return Err(AppErrors::AIStructureError);
+        }
+
+        let original = self.attributes.clone();
+        let cap = tier.attribute_cap();
+
+        for (_, attribute) in self.attributes.named_mut() {
+            *attribute = Attribute::new(attribute.value().clamp(MIN_ATTRIBUTE, cap));
+        }
+
+        // Shave points off the highest attribute until we're in budget, keeps the creature's shape
+        while self.attributes.total() > tier.attribute_budget() {
+            let mut named = self.attributes.named_mut();
+            let (_, highest) = named.iter_mut().max_by_key(|(_, a)| a.value()).unwrap();
+            **highest = Attribute::new(highest.value() - 1);
+        }
+
+        for ((name, before), (_, after)) in original.named().iter().zip(self.attributes.named()) {
+            if before != &after {
+                self.adjustments
+                    .push(format!("{name} {} -> {}", before.value(), after.value()));
+            }
+        }
+
+        if !self.adjustments.is_empty() {
+            tracing::info!(
+                "Normalized {} as a {} creature: {}",
+                self.name,
+                tier.name(),
+                self.adjustments.join(", ")
+            );
+        }
 
-        Ok(generation::extract_yaml(&res)?)
+        Ok(())
     }
 }
@@ -0,0 +1,77 @@
+//! Replies from the model saved to disk by what was asked, so asking the same
+//! thing again, like statting a creature that's been seen before, is answered
+//! straight away. The model gives the same reply to the same prompt and seed
+//! anyway, so the cache only ever saves the wait.
+
+use std::{
+    hash::{Hash, Hasher},
+    path::PathBuf,
+};
+
+use anyhow::Result;
+
+/// Where cached replies are kept, inside the state directory
+pub const CACHE_DIR: &str = "gen-cache";
+
+#[derive(Debug, Clone)]
+pub struct GenCache {
+    dir: PathBuf,
+    /// Which model and settings the replies came from, a different model won't
+    /// reuse another's replies
+    model: String,
+}
+
+impl GenCache {
+    pub fn new(dir: PathBuf, model: String) -> Self {
+        Self { dir, model }
+    }
+
+    fn path(&self, prompt: &str, seed: i32, json: bool) -> PathBuf {
+        let mut h = seahash::SeaHasher::new();
+        (&self.model, prompt, seed, json).hash(&mut h);
+        self.dir.join(format!("{:016x}.txt", h.finish()))
+    }
+
+    pub async fn get(&self, prompt: &str, seed: i32, json: bool) -> Option<String> {
+        tokio::fs::read_to_string(self.path(prompt, seed, json))
+            .await
+            .ok()
+    }
+
+    pub async fn put(&self, prompt: &str, seed: i32, json: bool, reply: &str) -> Result<()> {
+        let path = self.path(prompt, seed, json);
+        // Written aside then moved into place so a half written reply is never read
+        let partial = path.with_extension("partial");
+        tokio::fs::create_dir_all(&self.dir).await?;
+        tokio::fs::write(&partial, reply).await?;
+        tokio::fs::rename(partial, path).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn remembers_replies() {
+        let dir = std::env::temp_dir().join(format!("somnuscape-gen-cache-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let cache = GenCache::new(dir.clone(), "llama3".into());
+
+        assert_eq!(cache.get("Stat a minotaur", 7, true).await, None);
+        cache
+            .put("Stat a minotaur", 7, true, "{\"name\": \"minotaur\"}")
+            .await
+            .unwrap();
+        let cached = cache.get("Stat a minotaur", 7, true).await;
+        assert_eq!(cached.as_deref(), Some("{\"name\": \"minotaur\"}"));
+
+        assert_eq!(cache.get("Stat a minotaur", 8, true).await, None);
+        assert_eq!(cache.get("Stat a minotaur", 7, false).await, None);
+        let other_model = GenCache::new(dir.clone(), "mistral".into());
+        assert_eq!(other_model.get("Stat a minotaur", 7, true).await, None);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,216 @@
+//! An opt-in record of what's asked of the model and what it says back, for
+//! working out why generation's gone wrong. Player names are redacted before
+//! anything's written, and the file's rotated once it's big enough so only the
+//! latest few are kept. Unlike the gen cache this is only ever read by people,
+//! nothing's answered from it.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+
+/// Where captured generations are kept, inside the state directory
+pub const CAPTURE_DIR: &str = "gen-capture";
+/// What player names are replaced with
+pub const REDACTED: &str = "[player]";
+
+/// Names to keep out of what's captured, kept up to date by the engine
+pub type Redactions = Arc<RwLock<Vec<String>>>;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Generation {
+    /// When the reply came back, in milliseconds since the Unix epoch
+    pub at: u64,
+    pub seed: i32,
+    pub json: bool,
+    pub prompt: String,
+    /// What the model said, or why it didn't say anything
+    pub response: String,
+    pub failed: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct GenCapture {
+    dir: PathBuf,
+    /// How big the file gets before it's rotated
+    max_bytes: u64,
+    /// How many rotated files are kept
+    keep: usize,
+    redactions: Redactions,
+    /// Generations are written one at a time so lines never interleave
+    writing: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl GenCapture {
+    pub fn new(dir: PathBuf, max_bytes: u64, keep: usize, redactions: Redactions) -> Self {
+        Self {
+            dir,
+            max_bytes,
+            keep,
+            redactions,
+            writing: Default::default(),
+        }
+    }
+
+    /// Writes a prompt and the reply, or the error, with player names redacted
+    pub async fn record(
+        &self,
+        prompt: &str,
+        seed: i32,
+        json: bool,
+        reply: Result<&str, &anyhow::Error>,
+    ) -> Result<()> {
+        let response = match reply {
+            Ok(reply) => reply.to_string(),
+            Err(e) => format!("{e:#}"),
+        };
+        let generation = {
+            let names = self.redactions.read().unwrap_or_else(|e| e.into_inner());
+            Generation {
+                at: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis() as u64,
+                seed,
+                json,
+                prompt: redact(prompt, &names)?,
+                response: redact(&response, &names)?,
+                failed: reply.is_err(),
+            }
+        };
+        let mut line = serde_json::to_string(&generation)?;
+        line.push('\n');
+
+        let _writing = self.writing.lock().await;
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let path = rotated(&self.dir, 0);
+        let size = tokio::fs::metadata(&path).await.map_or(0, |m| m.len());
+        if size > 0 && size + line.len() as u64 > self.max_bytes {
+            self.rotate().await?;
+        }
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .await?;
+        file.write_all(line.as_bytes()).await?;
+        // Tokio hands writes to another thread, without waiting the next size check can miss this one
+        file.flush().await?;
+        Ok(())
+    }
+
+    /// Moves each file one older, dropping the oldest
+    async fn rotate(&self) -> Result<()> {
+        let oldest = rotated(&self.dir, self.keep);
+        if tokio::fs::try_exists(&oldest).await? {
+            tokio::fs::remove_file(oldest).await?;
+        }
+        for n in (0..self.keep).rev() {
+            let from = rotated(&self.dir, n);
+            if tokio::fs::try_exists(&from).await? {
+                tokio::fs::rename(from, rotated(&self.dir, n + 1)).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The latest generations captured in the directory, oldest first
+pub fn recent(dir: &Path, count: usize) -> Result<Vec<Generation>> {
+    let mut recent = Vec::new();
+    for n in 0.. {
+        let path = rotated(dir, n);
+        if recent.len() >= count || !path.try_exists()? {
+            break;
+        }
+        // A line still being written won't read, it's skipped
+        let text = std::fs::read_to_string(path)?;
+        recent.extend(
+            text.lines()
+                .rev()
+                .filter_map(|line| serde_json::from_str(line).ok())
+                .take(count - recent.len()),
+        );
+    }
+    recent.reverse();
+    Ok(recent)
+}
+
+/// The file `n` rotations old, 0 being the one written to
+fn rotated(dir: &Path, n: usize) -> PathBuf {
+    match n {
+        0 => dir.join("generations.jsonl"),
+        n => dir.join(format!("generations.{n}.jsonl")),
+    }
+}
+
+/// Replaces every name in the text, whole words only and ignoring case
+fn redact(text: &str, names: &[String]) -> Result<String> {
+    let mut names: Vec<_> = names
+        .iter()
+        .map(|n| n.trim())
+        .filter(|n| !n.is_empty())
+        .collect();
+    if names.is_empty() {
+        return Ok(text.to_string());
+    }
+    // Longer names first, so 'Ada Lovelace' goes whole rather than leaving 'Lovelace'
+    names.sort_by_key(|n| std::cmp::Reverse(n.len()));
+    let names: Vec<_> = names.into_iter().map(regex::escape).collect();
+    let re = Regex::new(&format!(r"(?i)\b(?:{})\b", names.join("|")))?;
+    Ok(re.replace_all(text, REDACTED).into_owned())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn redacts_whole_names() {
+        let names = vec!["ada".to_string(), "Ada Lovelace".to_string(), " ".into()];
+        assert_eq!(
+            redact("Ada Lovelace waves at ADA in Adamant Hall", &names).unwrap(),
+            "[player] waves at [player] in Adamant Hall"
+        );
+        assert_eq!(redact("No one here", &[]).unwrap(), "No one here");
+    }
+
+    #[tokio::test]
+    async fn rotates_and_reads_back() {
+        let dir = std::env::temp_dir().join(format!("somnuscape-capture-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let redactions = Redactions::default();
+        redactions.write().unwrap().push("bob".into());
+        let capture = GenCapture::new(dir.clone(), 300, 1, redactions);
+
+        for i in 0..6 {
+            let prompt = format!("Greet bob, number {i}");
+            capture
+                .record(&prompt, i, false, Ok("Hello"))
+                .await
+                .unwrap();
+        }
+        let failure = anyhow::anyhow!("Backend fell over");
+        capture
+            .record("Name a sword", 9, true, Err(&failure))
+            .await
+            .unwrap();
+        assert!(!rotated(&dir, 2).exists());
+
+        let latest = recent(&dir, 3).unwrap();
+        assert_eq!(latest.len(), 3);
+        assert_eq!(latest[0].prompt, "Greet [player], number 4");
+        assert_eq!(latest[2].response, "Backend fell over");
+        assert!(latest[2].failed && latest[2].json);
+        assert!(recent(&dir, 100).unwrap().len() < 7);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,84 @@
+use anyhow::Result;
+use askama::Template;
+
+use crate::mud::relationship::{Dialogue, Topic};
+
+use super::{fallback, AIClient};
+
+#[derive(Template)]
+#[template(path = "dialogue.md")]
+struct DialogueTemplate<'a> {
+    npc: &'a str,
+    place: &'a str,
+    standing: &'a str,
+    score: i32,
+    topic: Topic,
+    subject: Option<&'a str>,
+    witnessed: &'a [String],
+    heard: &'a [String],
+    description: &'a str,
+    said: Option<&'a str>,
+    recent: &'a [String],
+}
+
+async fn generate_line(
+    client: &AIClient,
+    dialogue: &Dialogue,
+    on_sentence: impl FnMut(String) + Send,
+) -> Result<String> {
+    let prompt = DialogueTemplate {
+        npc: &dialogue.npc,
+        place: &dialogue.place,
+        standing: dialogue.standing.name(),
+        score: dialogue.score,
+        topic: dialogue.topic,
+        subject: dialogue.subject.as_deref(),
+        witnessed: &dialogue.witnessed,
+        heard: &dialogue.heard,
+        description: &dialogue.description,
+        said: dialogue.said.as_deref(),
+        recent: &dialogue.recent,
+    }
+    .to_string();
+
+    let res = client.generate_streamed(prompt, on_sentence).await?;
+    Ok(unquoted(&res))
+}
+
+fn unquoted(line: &str) -> String {
+    line.trim().trim_matches('"').to_string()
+}
+
+/// What an NPC says to a player, coloured by how they feel about them. Each
+/// sentence is passed on as it's written, along with whether any were, since
+/// the fallback line isn't streamed.
+pub async fn speak(
+    client: &AIClient,
+    dialogue: &Dialogue,
+    mut on_sentence: impl FnMut(String) + Send,
+) -> (String, bool) {
+    let mut sent = Vec::new();
+    let res = generate_line(client, dialogue, |sentence| {
+        let sentence = unquoted(&sentence);
+        if !sentence.is_empty() {
+            sent.push(sentence.clone());
+            on_sentence(sentence);
+        }
+    })
+    .await;
+
+    match res {
+        Ok(line) if !line.is_empty() => (line, !sent.is_empty()),
+        // Whatever the player's already heard stands, there's no taking it back
+        Err(e) if !sent.is_empty() => {
+            tracing::warn!("Dialogue for {} was cut short: {e}", dialogue.npc);
+            (sent.join(" "), true)
+        }
+        res => {
+            if let Err(e) = res {
+                tracing::warn!("Using fallback dialogue for {}: {e}", dialogue.npc);
+            }
+            (fallback::dialogue_line(dialogue), false)
+        }
+    }
+}
@@ -0,0 +1,458 @@
+//! Purely procedural stand-ins for each kind of generated content. These are used
+//! when the AI backend is unavailable or over budget so the world keeps growing,
+//! just a little more blandly. Everything is seeded from its inputs so the same
+//! request always produces the same result.
+
+use std::hash::{Hash, Hasher};
+
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+use crate::mud::{
+    character::{Attribute, Attributes},
+    crafting::{Craft, Recipe},
+    haggle::Haggle,
+    items::Item,
+    lore::LoreKind,
+    relationship::{Dialogue, Standing, Topic},
+    storyline::{StoryStage, Storyline},
+    workshop::Blueprint,
+    world::Place,
+};
+
+use super::{
+    bestiary::{CreatureTemplate, CreatureTier},
+    place::PlaceType,
+};
+
+const ADJECTIVES: &[&str] = &[
+    "Old", "Grey", "Sunken", "Quiet", "Broken", "Mossy", "Hollow", "Red", "Lonely", "Misty",
+];
+
+const VILLAGE_NOUNS: &[&str] = &[
+    "Ford", "Crossing", "Mill", "Green", "Wick", "Haven", "Brook", "Stead",
+];
+const DUNGEON_NOUNS: &[&str] = &[
+    "Barrow",
+    "Crypt",
+    "Warren",
+    "Vault",
+    "Catacombs",
+    "Deeps",
+    "Pit",
+    "Halls",
+];
+
+const VILLAGE_ROOMS: &[&str] = &[
+    "Main Street",
+    "Market Square",
+    "Tavern",
+    "Smithy",
+    "Chapel",
+    "Well",
+    "Back Alley",
+    "General Store",
+];
+const DUNGEON_ROOMS: &[&str] = &[
+    "Entry Hall",
+    "Narrow Corridor",
+    "Guard Room",
+    "Store Room",
+    "Collapsed Passage",
+    "Shrine",
+    "Cells",
+    "Great Chamber",
+];
+
+const CREATURE_ADJECTIVES: &[&str] = &["Cave", "Grave", "Pale", "Gnawing", "Blind", "Rusted"];
+const CREATURE_NOUNS: &[&str] = &[
+    "Rat", "Spider", "Ghoul", "Bat", "Skeleton", "Crawler", "Knight",
+];
+
+const LORE_OPENINGS: &[&str] = &[
+    "Little is written about",
+    "Travellers tell conflicting tales of",
+    "Scholars have long argued over",
+    "Few have studied",
+];
+const LORE_CLOSINGS: &[&str] = &[
+    "What is known has mostly been learned the hard way.",
+    "Most accounts agree it is best approached with caution.",
+    "The rest is rumor and tavern talk.",
+];
+
+/// Noncommittal answers to whatever a player says to a shopkeeper
+const SMALL_TALK: &[&str] = &[
+    "Is that so? Well, you learn something new every day.",
+    "Hmm. Can't say I've ever thought about it.",
+    "You'd be surprised how often I hear that.",
+    "Ask me again once I've had my cider.",
+];
+
+const WEATHER_FRONTS: &[(&str, &str)] = &[
+    ("clear skies", "The sky is clear and the air still."),
+    ("a light drizzle", "A light drizzle beads on every surface."),
+    ("a grey overcast", "Low grey cloud presses down from above."),
+    (
+        "a cold wind",
+        "A cold wind tugs at cloaks and rattles shutters.",
+    ),
+    (
+        "a rolling fog",
+        "Fog rolls in, swallowing everything a few paces off.",
+    ),
+    (
+        "a heavy rain",
+        "Rain hammers down and runs in streams underfoot.",
+    ),
+];
+
+const ITEM_MATERIALS: &[&str] = &["Iron", "Bone", "Oak", "Copper", "Leather", "Stone"];
+const ITEM_KINDS: &[(&str, u32)] = &[
+    ("Dagger", 1),
+    ("Sword", 3),
+    ("Shield", 5),
+    ("Ring", 0),
+    ("Helm", 2),
+    ("Idol", 1),
+];
+
+fn seeded_rng(parts: &[&str]) -> StdRng {
+    let mut h = seahash::SeaHasher::new();
+    parts.hash(&mut h);
+    StdRng::seed_from_u64(h.finish())
+}
+
+fn nouns_for(place_type: &PlaceType) -> &'static [&'static str] {
+    if place_type.name == "village" {
+        VILLAGE_NOUNS
+    } else {
+        DUNGEON_NOUNS
+    }
+}
+
+fn rooms_for(place_type: &PlaceType) -> &'static [&'static str] {
+    if place_type.name == "village" {
+        VILLAGE_ROOMS
+    } else {
+        DUNGEON_ROOMS
+    }
+}
+
+/// A list of place names and descriptions, like `generate_place_list` would give
+pub fn place_list(place_type: &PlaceType, count: usize, seed: &str) -> Vec<(String, String)> {
+    let mut rng = seeded_rng(&[place_type.name, seed]);
+
+    (0..count)
+        .map(|_| {
+            let adjective = ADJECTIVES.choose(&mut rng).unwrap();
+            let noun = nouns_for(place_type).choose(&mut rng).unwrap();
+            (
+                format!("{adjective} {noun}"),
+                format!(
+                    "An unremarkable {}, {} by name and {} by nature.",
+                    place_type.name,
+                    adjective.to_lowercase(),
+                    adjective.to_lowercase()
+                ),
+            )
+        })
+        .collect()
+}
+
+/// A handful of rooms for a place, ready to be linked
+pub fn rooms(place_type: &PlaceType, place_name: &str) -> Vec<Place> {
+    let mut rng = seeded_rng(&[place_type.name, place_name]);
+    let count = rng.gen_range(3..=6);
+
+    let mut names = rooms_for(place_type).to_vec();
+    names.shuffle(&mut rng);
+
+    names
+        .into_iter()
+        .take(count)
+        .map(|n| {
+            Place::new(
+                n.to_string(),
+                format!(
+                    "You stand in the {} of {place_name}. There is little of note here.",
+                    n.to_lowercase()
+                ),
+            )
+        })
+        .collect()
+}
+
+/// An average creature for its tier, stats spread evenly within the budget
+pub fn creature(creature_name: &str, tier: CreatureTier) -> CreatureTemplate {
+    let mut rng = seeded_rng(&[creature_name, tier.name()]);
+    let mut attributes = Attributes::default();
+    let per_attribute = tier.attribute_budget() / attributes.named().len() as i32;
+
+    for (_, attribute) in attributes.named_mut() {
+        let value = per_attribute + rng.gen_range(-2..=0);
+        *attribute = Attribute::new(value.min(tier.attribute_cap()));
+    }
+
+    CreatureTemplate {
+        name: creature_name.to_string(),
+        attributes,
+        items: Vec::new(),
+        adjustments: Vec::new(),
+    }
+}
+
+/// A few creature names to fill a place with
+pub fn creature_names(place_name: &str, count: usize) -> Vec<String> {
+    let mut rng = seeded_rng(&[place_name, "creatures"]);
+
+    (0..count.max(1))
+        .map(|_| {
+            format!(
+                "{} {}",
+                CREATURE_ADJECTIVES.choose(&mut rng).unwrap(),
+                CREATURE_NOUNS.choose(&mut rng).unwrap()
+            )
+        })
+        .collect()
+}
+
+/// A short, vague encyclopedia entry
+pub fn lore(name: &str, kind: LoreKind) -> String {
+    let mut rng = seeded_rng(&[name, kind.name()]);
+    format!(
+        "{} the {} known as {name}. {}",
+        LORE_OPENINGS.choose(&mut rng).unwrap(),
+        kind.name(),
+        LORE_CLOSINGS.choose(&mut rng).unwrap()
+    )
+}
+
+/// A plain name and description for an invented dish or potion
+pub fn recipe_name(recipe: &Recipe) -> (String, String) {
+    let main = recipe
+        .ingredients
+        .first()
+        .map(String::as_str)
+        .unwrap_or("Mystery");
+    let (kind, verb) = match recipe.craft {
+        Craft::Cooking => ("Stew", "cooked"),
+        Craft::Alchemy => ("Tincture", "brewed"),
+    };
+
+    (
+        format!("{main} {kind}"),
+        format!(
+            "Something {verb} from {}. It {}.",
+            recipe.ingredients.join(", ").to_lowercase(),
+            recipe.effect()
+        ),
+    )
+}
+
+const HAGGLE_PLEASED: &[&str] = &[
+    "Well now, that's a fine piece. I suppose I can stretch a little.",
+    "You drive a hard bargain, but you've got a point.",
+    "Hm, I've heard of you. Alright, alright.",
+];
+const HAGGLE_UNIMPRESSED: &[&str] = &[
+    "I've seen a dozen like it this week.",
+    "That? I'll be doing you a favour taking it off your hands.",
+    "Nice story. Doesn't change what it's worth to me.",
+];
+
+/// A stock shopkeeper reply to a haggle, offering the middle of what they'll pay
+pub fn haggle_reply(haggle: &Haggle) -> (String, u32) {
+    let mut rng = seeded_rng(&[&haggle.item, &haggle.pitch]);
+    let replies = if haggle.persuaded {
+        HAGGLE_PLEASED
+    } else {
+        HAGGLE_UNIMPRESSED
+    };
+    (
+        replies.choose(&mut rng).unwrap().to_string(),
+        (haggle.min + haggle.max) / 2,
+    )
+}
+
+/// A stock line for an NPC, warmer the better they know the player
+pub fn dialogue_line(dialogue: &Dialogue) -> String {
+    match (dialogue.topic, dialogue.subject.as_deref()) {
+        (Topic::Greeting, _) if dialogue.said.is_some() => {
+            let said = dialogue.said.as_deref().unwrap_or_default();
+            SMALL_TALK
+                .choose(&mut seeded_rng(&[&dialogue.npc, said]))
+                .unwrap()
+                .to_string()
+        }
+        (Topic::Greeting | Topic::Story, _) => match dialogue.standing {
+            Standing::Hostile | Standing::Stranger => {
+                "Yes? Buying or selling, I haven't got all day.".to_string()
+            }
+            Standing::Acquaintance => "Oh, it's you again. Good to see a familiar face.".to_string(),
+            Standing::Friend | Standing::Confidant => {
+                "There you are! Come in, come in, sit yourself down.".to_string()
+            }
+        },
+        (Topic::Rumours, Some(place)) => match dialogue.heard.last() {
+            Some(heard) => format!("Word is that over at {place}, {heard}. Make of that what you will."),
+            None => format!(
+                "Folk say nobody's found the end of {place} yet. Strange lights, strange noises, you know how it is."
+            ),
+        },
+        (Topic::Rumours, None) => "Quiet lately. Too quiet, if you ask me.".to_string(),
+        (Topic::Errand, Some(item)) => format!(
+            "Could I ask a favour? I've been after a {item} for a while now. Bring me one and I'll make it worth your while."
+        ),
+        (Topic::Errand, None) => "You've done plenty for me already.".to_string(),
+    }
+}
+
+/// Stock storylines as a title, the chapters, where `{item}` is something the NPC
+/// asks the player to bring, and the keepsake they give at the end
+const STORYLINES: &[(&str, &[&str], &str, &str)] = &[
+    (
+        "The Letter Never Sent",
+        &[
+            "There was someone, once. We were going to leave this place together, before it all went wrong.",
+            "I wrote them a letter I never sent. Would you bring me a {item}? It was the last thing they gave me.",
+            "They went into the wilds and never came back. Some days I still watch the road.",
+            "I think it's time I let them go. Keep this, it was meant for them, but I'd rather it travelled.",
+        ],
+        "Unsent Locket",
+        "A small tarnished locket, holding a folded letter that was never read.",
+    ),
+    (
+        "My Father's Trade",
+        &[
+            "My father ran this place before me, and his mother before him. I never wanted it.",
+            "I wanted to be an adventurer, like you. Bring me a {item} and I'll show you I still remember a thing or two.",
+            "He found out I'd been sneaking off to the old ruins. We didn't speak for a year.",
+            "He left me this when he passed. I think he'd want someone who actually goes out there to have it.",
+        ],
+        "Heirloom Compass",
+        "A brass compass whose needle points to wherever its owner calls home.",
+    ),
+];
+
+/// A stock storyline for an NPC, with errands for items players can get hold of
+pub fn storyline(npc: &str, obtainable: &[String]) -> Storyline {
+    let mut rng = seeded_rng(&[npc]);
+    let (title, stages, reward, description) = STORYLINES.choose(&mut rng).unwrap();
+    let first_name = npc.split_whitespace().next().unwrap_or(npc);
+
+    Storyline {
+        npc: npc.to_string(),
+        title: title.to_string(),
+        stages: stages
+            .iter()
+            .map(|text| match obtainable.choose(&mut rng) {
+                Some(item) if text.contains("{item}") => StoryStage {
+                    text: text.replace("{item}", item),
+                    wants: Some(item.clone()),
+                },
+                _ => StoryStage {
+                    text: text.replace("a {item}", "something"),
+                    wants: None,
+                },
+            })
+            .collect(),
+        reward: Item {
+            name: format!("{first_name}'s {reward}"),
+            description: description.to_string(),
+            weight: 1,
+        },
+    }
+}
+
+/// Stock weather for a region, changing from one day to the next
+pub fn weather_front(region: &str, day: u64) -> (String, String) {
+    let mut rng = seeded_rng(&[region, &day.to_string()]);
+    let (name, description) = WEATHER_FRONTS.choose(&mut rng).unwrap();
+    (name.to_string(), description.to_string())
+}
+
+/// A few of the items on offer for a shop to sell, at twice what they're worth
+pub fn shop_stock(shop: &str, candidates: &[(String, u32)]) -> Vec<(String, u32)> {
+    let mut rng = seeded_rng(&[shop]);
+    let count = rng.gen_range(3..=5);
+    candidates
+        .choose_multiple(&mut rng, count)
+        .map(|(name, value)| (name.clone(), value * 2))
+        .collect()
+}
+
+/// A few of the products on offer, each made from one or two random materials.
+/// The engine settles how many of each it takes.
+pub fn blueprints(
+    workshop: &str,
+    products: &[(String, u32)],
+    materials: &[(String, u32)],
+) -> Vec<Blueprint> {
+    let mut rng = seeded_rng(&[workshop]);
+    let count = rng.gen_range(3..=5);
+    products
+        .choose_multiple(&mut rng, count)
+        .map(|(name, _)| {
+            let kinds = rng.gen_range(1..=2);
+            let needed = materials
+                .choose_multiple(&mut rng, kinds)
+                .map(|(material, _)| (material.clone(), rng.gen_range(1..=3)))
+                .collect();
+            Blueprint::new(name.clone(), needed)
+        })
+        .collect()
+}
+
+/// A plain piece of equipment or treasure themed on where it was found
+pub fn item(theme: &str) -> Item {
+    let mut rng = seeded_rng(&[theme]);
+    let material = ITEM_MATERIALS.choose(&mut rng).unwrap();
+    let (kind, weight) = ITEM_KINDS.choose(&mut rng).unwrap();
+
+    Item {
+        name: format!("{material} {kind}"),
+        description: format!(
+            "A simple {} {}.",
+            material.to_lowercase(),
+            kind.to_lowercase()
+        ),
+        weight: *weight,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::generation::{DUNGEON_PLACE_TYPE, VILLAGE_PLACE_TYPE};
+
+    use super::*;
+
+    #[test]
+    fn fallbacks_are_deterministic() {
+        assert_eq!(
+            place_list(&VILLAGE_PLACE_TYPE, 3, "seed"),
+            place_list(&VILLAGE_PLACE_TYPE, 3, "seed")
+        );
+        assert_eq!(place_list(&DUNGEON_PLACE_TYPE, 4, "seed").len(), 4);
+
+        let names = |rooms: Vec<Place>| rooms.into_iter().map(|r| r.name).collect::<Vec<_>>();
+        let crypt_rooms = names(rooms(&DUNGEON_PLACE_TYPE, "Old Crypt"));
+        assert!(crypt_rooms.len() >= 3);
+        assert_eq!(crypt_rooms, names(rooms(&DUNGEON_PLACE_TYPE, "Old Crypt")));
+
+        let mut rat = creature("rat", CreatureTier::Minion);
+        assert!(rat.attributes.total() <= CreatureTier::Minion.attribute_budget());
+        rat.normalize(CreatureTier::Minion).unwrap();
+        assert!(rat.adjustments.is_empty());
+
+        assert_eq!(item("Old Crypt"), item("Old Crypt"));
+        assert!(lore("Torch", LoreKind::Item).contains("Torch"));
+        assert_eq!(creature_names("Old Crypt", 3).len(), 3);
+
+        let obtainable = vec!["Honeycomb".to_string()];
+        let story = storyline("Marta of The Drowsy Goose", &obtainable);
+        assert!(story.validate(&obtainable).is_empty());
+        assert!(storyline("Marta of The Drowsy Goose", &[])
+            .validate(&[])
+            .is_empty());
+    }
+}
@@ -0,0 +1,59 @@
+use anyhow::Result;
+use askama::Template;
+use serde::Deserialize;
+
+use crate::mud::haggle::Haggle;
+
+use super::{extract_yaml, fallback, AIClient};
+
+#[derive(Template)]
+#[template(path = "haggle.md")]
+struct HaggleTemplate<'a> {
+    shopkeeper: &'a str,
+    item: &'a str,
+    pitch: &'a str,
+    value: u32,
+    standing: &'a str,
+    persuaded: bool,
+    min: u32,
+    max: u32,
+}
+
+#[derive(Deserialize)]
+struct HaggleReply {
+    reply: String,
+    offer: u32,
+}
+
+async fn generate_reply(client: &AIClient, haggle: &Haggle) -> Result<HaggleReply> {
+    let prompt = HaggleTemplate {
+        shopkeeper: &haggle.shopkeeper,
+        item: &haggle.item,
+        pitch: &haggle.pitch,
+        value: haggle.value,
+        standing: haggle.standing.name(),
+        persuaded: haggle.persuaded,
+        min: haggle.min,
+        max: haggle.max,
+    }
+    .to_string();
+
+    let res = client.generate_simple(prompt).await?;
+    extract_yaml(&res)
+}
+
+/// The shopkeeper's answer to a pitch and the price they name. The price
+/// isn't trusted, the engine settles it back within the haggle's limits.
+pub async fn negotiate(client: &AIClient, haggle: &Haggle) -> (String, u32) {
+    match generate_reply(client, haggle).await {
+        Ok(reply) if !reply.reply.trim().is_empty() => {
+            (reply.reply.trim().to_string(), reply.offer)
+        }
+        res => {
+            if let Err(e) = res {
+                tracing::warn!("Using fallback haggle reply for {}: {e}", haggle.item);
+            }
+            fallback::haggle_reply(haggle)
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use anyhow::Result;
+use askama::Template;
+
+use crate::mud::items::Item;
+
+use super::{extract_yaml, fallback, place::PlaceType, AIClient};
+
+/// Anything heavier is the model getting carried away
+const MAX_ITEM_WEIGHT: u32 = 10;
+
+#[derive(Template)]
+#[template(path = "item_list.md")]
+struct ItemListTemplate<'a> {
+    place_type: &'a str,
+    count: usize,
+}
+
+async fn generate_item_list(
+    client: &AIClient,
+    place_type: &PlaceType,
+    count: usize,
+) -> Result<Vec<Item>> {
+    let res = client
+        .generate_with_tone(
+            ItemListTemplate {
+                place_type: place_type.name(),
+                count,
+            }
+            .to_string(),
+        )
+        .await?;
+
+    let items: Vec<Item> = extract_yaml(&res)?;
+    Ok(items
+        .into_iter()
+        .filter(|i| !i.name.trim().is_empty())
+        .map(|mut i| {
+            i.name = i.name.trim().to_string();
+            i.weight = i.weight.min(MAX_ITEM_WEIGHT);
+            i
+        })
+        .take(count)
+        .collect())
+}
+
+/// Items themed on a kind of place, topped up with procedural ones
+/// if the AI backend can't come up with enough
+pub async fn generate_items(client: &AIClient, place_type: &PlaceType, count: usize) -> Vec<Item> {
+    tracing::info!("Generating {count} items for a {}", place_type.name());
+    let mut items = match generate_item_list(client, place_type, count).await {
+        Ok(items) => items,
+        Err(e) => {
+            tracing::warn!("Using fallback {} items: {e}", place_type.name());
+            Vec::new()
+        }
+    };
+
+    let seed = client.seed.to_string();
+    for i in items.len()..count {
+        items.push(fallback::item(&format!("{}-{seed}-{i}", place_type.name())));
+    }
+
+    items
+}
@@ -0,0 +1,32 @@
+use askama::Template;
+
+use crate::mud::lore::LoreKind;
+
+use super::{fallback, AIClient};
+
+#[derive(Template)]
+#[template(path = "lore_entry.md")]
+struct LoreEntryTemplate<'a> {
+    name: &'a str,
+    kind: &'a str,
+}
+
+/// Writes the flavor text for an encyclopedia entry, falling back
+/// to a plain procedural one if the AI backend can't help
+pub async fn write_entry(client: &AIClient, name: &str, kind: LoreKind) -> String {
+    let prompt = LoreEntryTemplate {
+        name,
+        kind: kind.name(),
+    }
+    .to_string();
+
+    match client.generate_with_tone(prompt).await {
+        Ok(res) if !res.trim().is_empty() => res.trim().to_string(),
+        res => {
+            if let Err(e) = res {
+                tracing::warn!("Using fallback lore for {name}: {e}");
+            }
+            fallback::lore(name, kind)
+        }
+    }
+}
@@ -1,19 +1,35 @@
+mod backend;
 mod bestiary;
+mod cache;
+mod capture;
+mod dialogue;
+mod fallback;
+mod haggle;
+mod items;
+mod lore;
 mod place;
+mod recipe;
+mod shop;
+mod storyline;
+mod stream;
+mod weather;
+mod workshop;
 
 use std::{
     collections::HashMap,
     hash::{Hash, Hasher},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
 };
 
-use anyhow::Result;
+use anyhow::{Context, Result};
+use backend::GenerationBackend;
+use cache::GenCache;
+use capture::{GenCapture, Redactions};
 use crossbeam::channel::{Receiver, Sender, TryRecvError};
 use futures::StreamExt;
-use ollama_rs::{
-    generation::{completion::request::GenerationRequest, options::GenerationOptions},
-    Ollama,
-};
-use place::PlaceType;
 use rand::{seq::IteratorRandom, SeedableRng};
 use regex::Regex;
 use serde::de::DeserializeOwned;
@@ -21,47 +37,189 @@ use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
 
 use crate::{
     config,
-    mud::world::{Location, Place},
+    markup::{from_markdown, strip_markdown},
+    mud::{
+        character::Character,
+        crafting::Recipe,
+        haggle::Haggle,
+        items::Item,
+        lore::LoreKind,
+        relationship::Dialogue,
+        storyline::Storyline,
+        weather::{WeatherFront, WorldTime},
+        workshop::Blueprint,
+        world::{Location, Place},
+    },
+    state::{self, PlayerId},
+    AppErrors,
 };
 
-pub use place::{DUNGEON_PLACE_TYPE, VILLAGE_PLACE_TYPE};
+pub use backend::BackendKind;
+pub use capture::{recent as recent_generations, CAPTURE_DIR};
+pub use place::{PlaceType, DUNGEON_PLACE_TYPE, VILLAGE_PLACE_TYPE};
+
+/// How many times an unreadable structured reply is asked for again
+const MAX_STRUCTURE_ATTEMPTS: usize = 3;
+
+/// Requests come with the channel the response should go back on,
+/// so several handles can share one generator
+type RoutedRequest = (GenerationReq, Sender<GenerationRes>);
 
 #[derive(Debug)]
 pub struct Generator {
-    request_queue: UnboundedReceiver<GenerationReq>,
-    response_queue: Sender<GenerationRes>,
+    request_queue: UnboundedReceiver<RoutedRequest>,
     client: AIClient,
 }
 
 #[derive(Debug)]
 pub struct GeneratorHandle {
-    request_queue: UnboundedSender<GenerationReq>,
+    request_queue: UnboundedSender<RoutedRequest>,
+    redactions: Redactions,
+    response_sender: Sender<GenerationRes>,
     response_queue: Receiver<GenerationRes>,
 }
 
 #[derive(Debug)]
 pub enum GenerationReq {
     Places(PlaceType, usize),
+    /// Flavor text for an encyclopedia entry
+    Lore(String, LoreKind),
+    /// A name and description for a newly invented dish or potion
+    NameRecipe(Recipe),
+    /// Creatures for a dungeon, given its name and description
+    /// and each room's location and depth
+    Creatures((String, String), Vec<(Location, usize)>),
+    /// Item definitions themed on a kind of place, to scatter through the region at a location
+    Items(Location, PlaceType, usize),
+    /// A shopkeeper's reply to a player haggling with them
+    Haggle(PlayerId, Haggle),
+    /// What a named NPC says to a player talking to them
+    Dialogue(PlayerId, Dialogue),
+    /// A personal storyline for a named NPC, given their name, where they
+    /// are and the items they could ask players for
+    Storyline(String, String, Vec<String>),
+    /// The next weather over a region, given its location, name and description,
+    /// the time and the weather it's replacing
+    Weather(Location, String, String, WorldTime, Option<String>),
+    /// Wares for a shop, given its location, shopkeeper, name and description
+    /// and the items it could sell with what each is worth
+    ShopStock(Location, String, String, String, Vec<(String, u32)>),
+    /// Blueprints for a workshop, given its location, name and description, the
+    /// equipment it could make and the materials it could use with what each is worth
+    Blueprints(
+        Location,
+        String,
+        String,
+        Vec<(String, u32)>,
+        Vec<(String, u32)>,
+    ),
 }
 
+// Responses are few and moved once, boxing places wouldn't buy anything
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug)]
 pub enum GenerationRes {
     Place(Place, HashMap<Location, Place>),
+    Lore(String, String),
+    Recipe(Recipe),
+    Creatures(Vec<(Location, Character)>),
+    /// Items for the region at a location
+    Items(Location, Vec<Item>),
+    /// The haggle, what the shopkeeper said and the price they named
+    Haggle(PlayerId, Haggle, String, u32),
+    /// The dialogue, the NPC's line and whether it was already streamed to the player
+    Dialogue(PlayerId, Dialogue, String, bool),
+    Storyline(Storyline),
+    Weather(Location, WeatherFront),
+    /// A shop's wares and the price asked for each
+    ShopStock(Location, Vec<(String, u32)>),
+    Blueprints(Location, Vec<Blueprint>),
+    /// Part of a reply for a player, sent on as it's written. The full
+    /// reply still arrives as its own response once it's finished.
+    Streamed(PlayerId, String),
+    /// A request that couldn't be met even with fallbacks, and why
+    Failed(GenerationReq, String),
+}
+
+impl GenerationRes {
+    /// Cleans up the markdown models tend to write. Descriptions and speech keep
+    /// their emphasis as style tags, names lose it entirely so they can still be typed.
+    fn tidied(self) -> Self {
+        fn tidy_place(place: &mut Place) {
+            place.name = strip_markdown(&place.name);
+            place.description = from_markdown(&place.description);
+        }
+        fn tidy_item(item: &mut Item) {
+            item.name = strip_markdown(&item.name);
+            item.description = from_markdown(&item.description);
+        }
+
+        match self {
+            GenerationRes::Place(mut place, mut places) => {
+                tidy_place(&mut place);
+                places.values_mut().for_each(tidy_place);
+                GenerationRes::Place(place, places)
+            }
+            GenerationRes::Lore(name, text) => GenerationRes::Lore(name, from_markdown(&text)),
+            GenerationRes::Recipe(mut recipe) => {
+                recipe.name = strip_markdown(&recipe.name);
+                recipe.description = from_markdown(&recipe.description);
+                GenerationRes::Recipe(recipe)
+            }
+            GenerationRes::Creatures(mut creatures) => {
+                for (_, creature) in &mut creatures {
+                    creature.name = strip_markdown(&creature.name);
+                }
+                GenerationRes::Creatures(creatures)
+            }
+            GenerationRes::Items(location, mut items) => {
+                items.iter_mut().for_each(tidy_item);
+                GenerationRes::Items(location, items)
+            }
+            GenerationRes::Haggle(player, haggle, reply, offer) => {
+                GenerationRes::Haggle(player, haggle, from_markdown(&reply), offer)
+            }
+            GenerationRes::Dialogue(player, dialogue, line, streamed) => {
+                GenerationRes::Dialogue(player, dialogue, from_markdown(&line), streamed)
+            }
+            GenerationRes::Streamed(player, text) => {
+                GenerationRes::Streamed(player, from_markdown(&text))
+            }
+            GenerationRes::Storyline(mut story) => {
+                story.title = strip_markdown(&story.title);
+                for stage in &mut story.stages {
+                    stage.text = from_markdown(&stage.text);
+                }
+                tidy_item(&mut story.reward);
+                GenerationRes::Storyline(story)
+            }
+            GenerationRes::Weather(location, mut front) => {
+                front.name = strip_markdown(&front.name);
+                front.description = from_markdown(&front.description);
+                GenerationRes::Weather(location, front)
+            }
+            stock @ GenerationRes::ShopStock(..) => stock,
+            blueprints @ GenerationRes::Blueprints(..) => blueprints,
+            failed @ GenerationRes::Failed(..) => failed,
+        }
+    }
 }
 
 impl Generator {
     pub fn new() -> (Self, GeneratorHandle) {
         let (req_s, req_r) = tokio::sync::mpsc::unbounded_channel();
         let (res_s, res_r) = crossbeam::channel::unbounded();
+        let redactions = Redactions::default();
 
         (
             Self {
                 request_queue: req_r,
-                response_queue: res_s,
-                client: AIClient::new_random(config::get().tone_words.clone()),
+                client: AIClient::new_random(config::get().tone_words.clone(), redactions.clone()),
             },
             GeneratorHandle {
                 request_queue: req_s,
+                redactions,
+                response_sender: res_s,
                 response_queue: res_r,
             },
         )
@@ -69,7 +227,7 @@ impl Generator {
 
     pub async fn run(mut self) {
         loop {
-            let req = self
+            let (req, response_queue) = self
                 .request_queue
                 .recv()
                 .await
@@ -77,34 +235,190 @@ impl Generator {
             match req {
                 GenerationReq::Places(place_type, count) => {
                     let client = self.client.clone();
-                    let response_queue = self.response_queue.clone();
 
                     tokio::spawn(async move {
                         place::generate_places(&client, &place_type, count)
                             .await
-                            .for_each_concurrent(3, |(place, places)| async {
+                            .for_each_concurrent(3, |res| async {
+                                let res = match res {
+                                    Ok((place, places)) => GenerationRes::Place(place, places),
+                                    Err(e) => GenerationRes::Failed(
+                                        GenerationReq::Places(place_type.clone(), 1),
+                                        format!("{e:#}"),
+                                    ),
+                                };
                                 response_queue
-                                    .send(GenerationRes::Place(place, places))
+                                    .send(res)
                                     .expect("Gen response channel shouldn't close");
                             })
                             .await;
                     })
                 }
+                GenerationReq::Lore(name, kind) => {
+                    let client = self.client.clone();
+
+                    tokio::spawn(async move {
+                        let text = lore::write_entry(&client, &name, kind).await;
+                        response_queue
+                            .send(GenerationRes::Lore(name, text))
+                            .expect("Gen response channel shouldn't close");
+                    })
+                }
+                GenerationReq::NameRecipe(recipe) => {
+                    let client = self.client.clone();
+
+                    tokio::spawn(async move {
+                        let recipe = recipe::name_recipe(&client, recipe).await;
+                        response_queue
+                            .send(GenerationRes::Recipe(recipe))
+                            .expect("Gen response channel shouldn't close");
+                    })
+                }
+                GenerationReq::Items(location, place_type, count) => {
+                    let client = self.client.clone();
+
+                    tokio::spawn(async move {
+                        let items = items::generate_items(&client, &place_type, count).await;
+                        response_queue
+                            .send(GenerationRes::Items(location, items))
+                            .expect("Gen response channel shouldn't close");
+                    })
+                }
+                GenerationReq::Haggle(player, haggle) => {
+                    let client = self.client.clone();
+
+                    tokio::spawn(async move {
+                        let (reply, offer) = haggle::negotiate(&client, &haggle).await;
+                        response_queue
+                            .send(GenerationRes::Haggle(player, haggle, reply, offer))
+                            .expect("Gen response channel shouldn't close");
+                    })
+                }
+                GenerationReq::Dialogue(player, dialogue) => {
+                    let client = self.client.clone();
+
+                    tokio::spawn(async move {
+                        let npc = dialogue.npc.clone();
+                        let mut first = true;
+                        let (line, streamed) = dialogue::speak(&client, &dialogue, |sentence| {
+                            let text = match std::mem::take(&mut first) {
+                                true => format!("{npc} says\n\"{sentence}\""),
+                                false => format!("\"{sentence}\""),
+                            };
+                            response_queue
+                                .send(GenerationRes::Streamed(player, text))
+                                .expect("Gen response channel shouldn't close");
+                        })
+                        .await;
+                        response_queue
+                            .send(GenerationRes::Dialogue(player, dialogue, line, streamed))
+                            .expect("Gen response channel shouldn't close");
+                    })
+                }
+                GenerationReq::Storyline(npc, place, obtainable) => {
+                    let client = self.client.clone();
+
+                    tokio::spawn(async move {
+                        let story =
+                            storyline::write_storyline(&client, &npc, &place, &obtainable).await;
+                        response_queue
+                            .send(GenerationRes::Storyline(story))
+                            .expect("Gen response channel shouldn't close");
+                    })
+                }
+                GenerationReq::Weather(location, region, description, time, last) => {
+                    let client = self.client.clone();
+
+                    tokio::spawn(async move {
+                        let front = weather::next_front(
+                            &client,
+                            &region,
+                            &description,
+                            time,
+                            last.as_deref(),
+                        )
+                        .await;
+                        response_queue
+                            .send(GenerationRes::Weather(location, front))
+                            .expect("Gen response channel shouldn't close");
+                    })
+                }
+                GenerationReq::ShopStock(location, shopkeeper, shop, description, candidates) => {
+                    let client = self.client.clone();
+
+                    tokio::spawn(async move {
+                        let wares = shop::stock_shop(
+                            &client,
+                            &shopkeeper,
+                            &shop,
+                            &description,
+                            &candidates,
+                        )
+                        .await;
+                        response_queue
+                            .send(GenerationRes::ShopStock(location, wares))
+                            .expect("Gen response channel shouldn't close");
+                    })
+                }
+                GenerationReq::Blueprints(location, name, description, products, materials) => {
+                    let client = self.client.clone();
+
+                    tokio::spawn(async move {
+                        let blueprints = workshop::draw_blueprints(
+                            &client,
+                            &name,
+                            &description,
+                            &products,
+                            &materials,
+                        )
+                        .await;
+                        response_queue
+                            .send(GenerationRes::Blueprints(location, blueprints))
+                            .expect("Gen response channel shouldn't close");
+                    })
+                }
+                GenerationReq::Creatures(dungeon, rooms) => {
+                    let client = self.client.clone();
+
+                    tokio::spawn(async move {
+                        let creatures = bestiary::populate(&client, &dungeon, &rooms).await;
+                        response_queue
+                            .send(GenerationRes::Creatures(creatures))
+                            .expect("Gen response channel shouldn't close");
+                    })
+                }
             };
         }
     }
 }
 
 impl GeneratorHandle {
+    /// Makes another handle to the same generator with its own response queue
+    pub fn new_handle(&self) -> Self {
+        let (res_s, res_r) = crossbeam::channel::unbounded();
+
+        Self {
+            request_queue: self.request_queue.clone(),
+            redactions: self.redactions.clone(),
+            response_sender: res_s,
+            response_queue: res_r,
+        }
+    }
+
+    /// Sets the player names kept out of captured generations
+    pub fn redact_names(&self, names: Vec<String>) {
+        *self.redactions.write().unwrap_or_else(|e| e.into_inner()) = names;
+    }
+
     pub fn request_generate(&mut self, req: GenerationReq) {
         self.request_queue
-            .send(req)
+            .send((req, self.response_sender.clone()))
             .expect("Gen handle request channel shouldn't close");
     }
 
     pub fn get_responses(&mut self) -> Option<GenerationRes> {
         match self.response_queue.try_recv() {
-            Ok(r) => Some(r),
+            Ok(r) => Some(r.tidied()),
             Err(TryRecvError::Empty) => None,
             Err(TryRecvError::Disconnected) => {
                 unreachable!("Gen handle response channel shouldn't close")
@@ -113,25 +427,52 @@ impl GeneratorHandle {
     }
 }
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Clone)]
 pub struct AIClient {
-    client: Ollama,
+    backend: Arc<dyn GenerationBackend>,
     seed: i32,
     tone: Vec<String>,
     /// We want to run deterministically for tests
     non_deterministic: bool,
+    /// Shared between clones so the request budget covers all generation
+    requests_made: Arc<AtomicU64>,
+    cache: Option<GenCache>,
+    capture: Option<GenCapture>,
 }
 
 impl AIClient {
-    pub fn new_random(tone_words: Vec<String>) -> Self {
+    pub fn new_random(tone_words: Vec<String>, redactions: Redactions) -> Self {
         AIClient {
-            client: Ollama::default(),
+            backend: configured_backend(),
             seed: rand::random(),
             tone: tone_words,
             non_deterministic: true,
+            requests_made: Default::default(),
+            cache: configured_cache(),
+            capture: configured_capture(redactions),
         }
     }
 
+    /// A copy that always answers the same prompt the same way, for things that
+    /// shouldn't change from one asking to the next, like a creature's stats
+    pub fn stable(&self) -> Self {
+        Self {
+            seed: 0,
+            non_deterministic: false,
+            ..self.clone()
+        }
+    }
+
+    /// Whether we should send requests to the AI backend at all, callers
+    /// should use the procedural fallbacks when this is false
+    pub fn available(&self) -> bool {
+        let config = config::get();
+        config.ai_generation
+            && !(config.offline && config.ai_backend.fixtures.is_none())
+            && (config.ai_request_budget == 0
+                || self.requests_made.load(Ordering::Relaxed) < config.ai_request_budget)
+    }
+
     pub async fn generate_with_tone(&self, mut prompt: String) -> Result<String> {
         let hash: i32 = self.make_gen_hash(&prompt);
 
@@ -151,26 +492,149 @@ impl AIClient {
 
         prompt.push_str(&tone);
 
-        self.generate(prompt, hash).await
+        self.generate(prompt, hash, false).await
     }
 
     pub async fn generate_simple(&self, prompt: String) -> Result<String> {
         let hash: i32 = self.make_gen_hash(&prompt);
-        self.generate(prompt, hash).await
+        self.generate(prompt, hash, false).await
     }
 
-    async fn generate(&self, prompt: String, hash: i32) -> Result<String> {
-        Ok(self
-            .client
-            .generate(
-                GenerationRequest::new("llama3:latest".to_string(), prompt).options(
-                    GenerationOptions::default()
-                        .seed(self.seed ^ hash)
-                        .temperature(config::get().model_temperature),
-                ),
-            )
-            .await?
-            .response)
+    /// Generates a value of the given type, constraining the backend to JSON where it's
+    /// able to. Replies that can't be read are retried with the problem pointed out,
+    /// anything that parses but doesn't make sense is left for the caller to check.
+    pub async fn generate_structured<T: DeserializeOwned + Send>(
+        &self,
+        prompt: String,
+    ) -> Result<T> {
+        let hash: i32 = self.make_gen_hash(&prompt);
+
+        let mut attempt_prompt = prompt.clone();
+        for attempt in 1..=MAX_STRUCTURE_ATTEMPTS {
+            let res = self
+                .generate(attempt_prompt, hash ^ attempt as i32, true)
+                .await?;
+            match serde_json::from_str(extract_json(&res)) {
+                Ok(value) => return Ok(value),
+                Err(e) => {
+                    tracing::warn!("Structured generation attempt {attempt} was unreadable: {e}");
+                    attempt_prompt = format!(
+                        "{prompt}\n\nYour last reply could not be read, make sure this one is valid JSON: {e}"
+                    );
+                }
+            }
+        }
+
+        Err(AppErrors::AIStructureError.into())
+    }
+
+    /// Generates plain text like `generate_simple`, handing each sentence to
+    /// `on_sentence` as the backend finishes it, and returns the whole reply.
+    /// If the backend gives up part way some sentences may already be handed over.
+    pub async fn generate_streamed(
+        &self,
+        prompt: String,
+        mut on_sentence: impl FnMut(String) + Send,
+    ) -> Result<String> {
+        let seed = self.seed ^ self.make_gen_hash(&prompt);
+        let cache = self.configured_cache();
+        if let Some(cache) = cache {
+            if let Some(reply) = cache.get(&prompt, seed, false).await {
+                let mut sentences = stream::Sentences::default();
+                sentences
+                    .push(&reply)
+                    .into_iter()
+                    .for_each(&mut on_sentence);
+                sentences.finish().into_iter().for_each(on_sentence);
+                return Ok(reply);
+            }
+        }
+
+        if !self.available() {
+            return Err(AppErrors::AIUnavailable.into());
+        }
+        self.requests_made.fetch_add(1, Ordering::Relaxed);
+
+        let mut pieces =
+            self.backend
+                .generate_stream(prompt.clone(), seed, config::get().model_temperature);
+        let mut sentences = stream::Sentences::default();
+        let mut reply = String::new();
+        let streamed: Result<()> = async {
+            while let Some(piece) = pieces.next().await {
+                let piece = piece?;
+                reply.push_str(&piece);
+                sentences
+                    .push(&piece)
+                    .into_iter()
+                    .for_each(&mut on_sentence);
+            }
+            Ok(())
+        }
+        .await;
+        self.capture(
+            &prompt,
+            seed,
+            false,
+            streamed.as_ref().map(|_| reply.as_str()),
+        )
+        .await;
+        streamed?;
+        sentences.finish().into_iter().for_each(on_sentence);
+
+        if let Some(cache) = cache {
+            if let Err(e) = cache.put(&prompt, seed, false, &reply).await {
+                tracing::warn!("Could not cache a generated reply: {e}");
+            }
+        }
+        Ok(reply)
+    }
+
+    /// Records what the backend said if generations are being captured
+    async fn capture(
+        &self,
+        prompt: &str,
+        seed: i32,
+        json: bool,
+        reply: Result<&str, &anyhow::Error>,
+    ) {
+        if let Some(capture) = &self.capture {
+            if let Err(e) = capture.record(prompt, seed, json, reply).await {
+                tracing::warn!("Could not capture a generation: {e}");
+            }
+        }
+    }
+
+    fn configured_cache(&self) -> Option<&GenCache> {
+        self.cache.as_ref().filter(|_| config::get().ai_generation)
+    }
+
+    async fn generate(&self, prompt: String, hash: i32, json: bool) -> Result<String> {
+        let seed = self.seed ^ hash;
+        let cache = self.configured_cache();
+        if let Some(cache) = cache {
+            if let Some(reply) = cache.get(&prompt, seed, json).await {
+                return Ok(reply);
+            }
+        }
+
+        if !self.available() {
+            return Err(AppErrors::AIUnavailable.into());
+        }
+        self.requests_made.fetch_add(1, Ordering::Relaxed);
+
+        let reply = self
+            .backend
+            .generate(prompt.clone(), seed, config::get().model_temperature, json)
+            .await;
+        self.capture(&prompt, seed, json, reply.as_deref()).await;
+        let reply = reply?;
+        if let Some(cache) = cache {
+            if let Err(e) = cache.put(&prompt, seed, json, &reply).await {
+                tracing::warn!("Could not cache a generated reply: {e}");
+            }
+        }
+        Ok(reply)
     }
 
     fn make_gen_hash(&self, prompt: &String) -> i32 {
@@ -184,6 +648,60 @@ impl AIClient {
     }
 }
 
+impl Default for AIClient {
+    fn default() -> Self {
+        Self {
+            backend: configured_backend(),
+            seed: 0,
+            tone: Vec::new(),
+            non_deterministic: false,
+            requests_made: Default::default(),
+            cache: configured_cache(),
+            capture: configured_capture(Redactions::default()),
+        }
+    }
+}
+
+/// Checks the configured AI backend can be set up, so a bad config is caught at startup
+pub fn check_backend(config: &config::SomnuscapeConfig) -> Result<()> {
+    backend::from_config(&config.ai_backend, config.offline)
+        .map(drop)
+        .context("Could not set up the AI backend")
+}
+
+fn configured_backend() -> Arc<dyn GenerationBackend> {
+    let config = config::get();
+    backend::from_config(&config.ai_backend, config.offline).unwrap_or_else(|e| {
+        // Caught by check_backend at startup, but generation falls back rather than panic
+        tracing::error!("Could not set up the AI backend: {e:#}");
+        Arc::new(backend::FixtureBackend::default())
+    })
+}
+
+fn configured_cache() -> Option<GenCache> {
+    let config = config::get();
+    let backend = &config.ai_backend;
+    let model = format!(
+        "{:?} {} {} {}",
+        backend.kind, backend.base_url, backend.model, config.model_temperature
+    );
+    config
+        .generation_cache
+        .then(|| GenCache::new(state::make_save_path(cache::CACHE_DIR), model))
+}
+
+fn configured_capture(redactions: Redactions) -> Option<GenCapture> {
+    let config = config::get();
+    config.generation_capture.then(|| {
+        GenCapture::new(
+            state::make_save_path(CAPTURE_DIR),
+            config.generation_capture_bytes,
+            config.generation_capture_files,
+            redactions,
+        )
+    })
+}
+
 fn extract_md_kv_list(res: &str) -> Vec<(String, String)> {
     let re = Regex::new(r"\d+\.\s*([\w\s]+):\s*(.*)").unwrap();
     let mut items = Vec::new();
@@ -198,6 +716,15 @@ fn extract_md_kv_list(res: &str) -> Vec<(String, String)> {
     items
 }
 
+/// The JSON object in a reply, models sometimes wrap it in a code block or
+/// add a sentence around it even when asked not to
+fn extract_json(res: &str) -> &str {
+    match (res.find('{'), res.rfind('}')) {
+        (Some(start), Some(end)) if start < end => &res[start..=end],
+        _ => res,
+    }
+}
+
 fn extract_yaml<T: DeserializeOwned + Send>(res: &str) -> Result<T> {
     let re = Regex::new(r"(?s)```(?i:yaml)?(.*?)```").unwrap();
 
@@ -213,40 +740,143 @@ fn extract_yaml<T: DeserializeOwned + Send>(res: &str) -> Result<T> {
 
 #[cfg(test)]
 mod test {
-    use bestiary::CreatureTemplate;
+    use bestiary::{CreatureTemplate, CreatureTier};
     use futures::StreamExt;
 
+    use crate::mud::character::Attribute;
+
     use super::*;
 
+    /// A client replaying the responses recorded in `fixtures/generation.yaml`,
+    /// caching to its own temp dir
+    fn fixture_client(name: &str) -> AIClient {
+        let fixtures = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/generation.yaml");
+        let dir = std::env::temp_dir().join(format!("somnuscape-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        AIClient {
+            backend: Arc::new(backend::FixtureBackend::load(Some(fixtures), None).unwrap()),
+            cache: Some(GenCache::new(dir, "fixtures".into())),
+            capture: None,
+            ..Default::default()
+        }
+    }
+
     #[tokio::test]
-    #[ignore = "ai"]
     async fn generate_sensible_creatures() {
-        let client = AIClient::default();
+        let client = fixture_client("creatures");
 
-        let minotaur = CreatureTemplate::stat_new(&client, "minotaur")
+        let minotaur = CreatureTemplate::stat_new(&client, "minotaur", CreatureTier::Elite)
             .await
             .unwrap();
         assert!(minotaur.attributes.strength > minotaur.attributes.intelligence);
         assert!(minotaur.attributes.toughness > minotaur.attributes.willpower);
 
-        let lich = CreatureTemplate::stat_new(&client, "lich").await.unwrap();
+        let lich = CreatureTemplate::stat_new(&client, "lich", CreatureTier::Boss)
+            .await
+            .unwrap();
         assert!(lich.attributes.intelligence > lich.attributes.strength);
         assert!(lich.attributes.willpower > lich.attributes.toughness);
     }
 
+    /// Replies with each canned response in turn
+    #[derive(Debug)]
+    struct CannedBackend(std::sync::Mutex<Vec<&'static str>>);
+
+    impl GenerationBackend for CannedBackend {
+        fn generate(
+            &self,
+            _prompt: String,
+            _seed: i32,
+            _temperature: f32,
+            json: bool,
+        ) -> futures::future::BoxFuture<'_, Result<String>> {
+            assert!(json);
+            let res = self.0.lock().unwrap().remove(0).to_string();
+            Box::pin(async move { Ok(res) })
+        }
+    }
+
+    #[tokio::test]
+    async fn structured_generation_retries() {
+        let client = AIClient {
+            backend: Arc::new(CannedBackend(std::sync::Mutex::new(vec![
+                "Here's your rat: {name: rat",
+                "Sure! ```json\n{\"name\": \"rat\", \"attributes\": {\"strength\": 4, \"toughness\": 5, \"agility\": 14, \"intelligence\": 3, \"willpower\": 6}, \"items\": []}\n```",
+            ]))),
+            cache: None,
+            ..Default::default()
+        };
+
+        let rat: CreatureTemplate = client
+            .generate_structured("A rat please".into())
+            .await
+            .unwrap();
+        assert_eq!(rat.name, "rat");
+        assert_eq!(rat.attributes.agility.value(), 14);
+        assert_eq!(client.requests_made.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn normalize_godly_rat() {
+        let mut rat: CreatureTemplate = extract_yaml(
+            "```yaml
+name: Godly rat
+attributes:
+  strength: 40
+  toughness: 30
+  agility: 18
+  intelligence: 2
+  willpower: 12
+items: []
+```",
+        )
+        .unwrap();
+
+        rat.normalize(CreatureTier::Minion).unwrap();
+        assert!(rat.attributes.total() <= CreatureTier::Minion.attribute_budget());
+        assert!(rat.attributes.strength.value() <= CreatureTier::Minion.attribute_cap());
+        assert!(rat.attributes.strength > rat.attributes.willpower);
+        assert_eq!(rat.attributes.intelligence.value(), 3);
+        assert!(!rat.adjustments.is_empty());
+
+        rat.attributes.strength = Attribute::new(9000);
+        assert!(rat.normalize(CreatureTier::Boss).is_err());
+    }
+
+    #[test]
+    fn boss_in_deepest_room() {
+        let tiers: Vec<_> = (1..=5).map(|d| CreatureTier::for_depth(d, 5)).collect();
+        assert_eq!(
+            tiers,
+            vec![
+                CreatureTier::Minion,
+                CreatureTier::Minion,
+                CreatureTier::Standard,
+                CreatureTier::Elite,
+                CreatureTier::Boss
+            ]
+        );
+
+        let rat = fallback::creature("rat", CreatureTier::Minion).spawn(CreatureTier::Minion);
+        assert_eq!(rat.name, "rat");
+        assert_eq!(rat.health, rat.max_health());
+    }
+
     #[tokio::test]
-    #[ignore = "ai"]
     async fn generate_places() {
-        let client = AIClient::default();
+        let client = fixture_client("places");
 
         let places: Vec<(Place, HashMap<Location, Place>)> =
             place::generate_places(&client, &DUNGEON_PLACE_TYPE, 3)
                 .await
+                .map(Result::unwrap)
                 .collect()
                 .await;
 
-        println!("{places:?}");
-
-        assert_eq!(places.len(), 3)
+        assert_eq!(places.len(), 3);
+        assert!(places
+            .iter()
+            .any(|(_, rooms)| rooms.values().any(|r| r.name == "Deep Chamber")));
+        assert_eq!(client.requests_made.load(Ordering::Relaxed), 7);
     }
 }
@@ -1,24 +1,75 @@
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use askama::Template;
 use futures::{stream, Stream, StreamExt};
+use rand::Rng;
 use serde::Deserialize;
 
 use crate::{
     filters,
-    generation::{extract_md_kv_list, extract_yaml},
-    mud::world::{Direction, Location, Place},
+    generation::extract_md_kv_list,
+    mud::world::{
+        Direction, Location, Place, CARTOGRAPHER_TAG, MARKET_TAG, SHOP_TAG, VENDOR_TAG,
+        WORKSHOP_TAG,
+    },
     AppErrors,
 };
 
-use super::AIClient;
+use super::{fallback, AIClient};
+
+/// How many times we'll try to generate a place before settling for a fallback
+const MAX_PLACE_ATTEMPTS: u32 = 3;
+/// How long to wait before retrying a place the first time, doubling after each failure
+const BASE_BACKOFF: Duration = Duration::from_millis(500);
+/// The longest we'll wait between attempts
+const MAX_BACKOFF: Duration = Duration::from_secs(8);
+/// Village rooms with any of these in their name become shops
+const SHOP_WORDS: &[&str] = &[
+    "shop",
+    "store",
+    "smith",
+    "forge",
+    "bakery",
+    "apothecary",
+    "emporium",
+    "merchant",
+    "trader",
+    "tailor",
+];
+/// The most shops one village gets
+const MAX_SHOPS: usize = 2;
+/// Village rooms with any of these in their name become its workshop
+const WORKSHOP_WORDS: &[&str] = &[
+    "workshop",
+    "smith",
+    "forge",
+    "foundry",
+    "tinker",
+    "carpenter",
+    "tanner",
+    "cooper",
+    "mason",
+];
+
+/// How long to wait after a place has failed this many times. Waits double up to
+/// the limit, and are jittered so places failing together don't retry together.
+fn backoff(failures: u32, rng: &mut impl Rng) -> Duration {
+    let delay = BASE_BACKOFF
+        .saturating_mul(1 << failures.saturating_sub(1).min(16))
+        .min(MAX_BACKOFF);
+    delay / 2 + delay.mul_f64(rng.gen_range(0.0..0.5))
+}
 
+/// Generates places, each either a finished place with its rooms or why it couldn't be made
 pub async fn generate_places<'a>(
     client: &'a AIClient,
     place_type: &'a PlaceType,
     max_count: usize,
-) -> impl Stream<Item = (Place, HashMap<Location, Place>)> + 'a {
+) -> impl Stream<Item = Result<(Place, HashMap<Location, Place>)>> + 'a {
     tracing::info!("Generating up to {} {}s", max_count, place_type.name);
 
     let idea_stream = stream::unfold(
@@ -31,13 +82,20 @@ pub async fn generate_places<'a>(
             let remaining_ungenerated = remaining_count.saturating_sub(place_ideas.len());
 
             while place_ideas.is_empty() && remaining_ungenerated > 0 {
-                let places = generate_place_list(client, &place_type.name, remaining_ungenerated)
-                    .await
-                    .unwrap_or_default()
-                    .into_iter()
-                    .take(remaining_ungenerated);
-
-                place_ideas.extend(places);
+                let places =
+                    match generate_place_list(client, place_type.name, remaining_ungenerated).await
+                    {
+                        Ok(places) if !places.is_empty() => places,
+                        res => {
+                            if let Err(e) = res {
+                                tracing::warn!("Using fallback {} list: {e}", place_type.name);
+                            }
+                            let seed = format!("{}-{remaining_count}", client.seed);
+                            fallback::place_list(place_type, remaining_ungenerated, &seed)
+                        }
+                    };
+
+                place_ideas.extend(places.into_iter().take(remaining_ungenerated));
             }
 
             place_ideas
@@ -48,13 +106,24 @@ pub async fn generate_places<'a>(
 
     let place_stream = idea_stream
         .map(move |place_idea| async move {
-            let mut place = generate_place(client, place_type, &place_idea).await;
-            while let Err(e) = place {
-                tracing::error!("Failed to generate place: {e}");
-                place = generate_place(client, place_type, &place_idea).await;
+            for attempt in 1..=MAX_PLACE_ATTEMPTS {
+                match generate_place(client, place_type, &place_idea).await {
+                    Ok(place) => return Ok(place),
+                    Err(e) => tracing::error!(
+                        "Failed to generate {} (attempt {attempt} of {MAX_PLACE_ATTEMPTS}): {e}",
+                        place_idea.0
+                    ),
+                }
+                if attempt < MAX_PLACE_ATTEMPTS {
+                    let wait = backoff(attempt, &mut rand::thread_rng());
+                    tokio::time::sleep(wait).await;
+                }
             }
 
-            place.unwrap()
+            tracing::warn!("Using fallback rooms for {}", place_idea.0);
+            let (entrance, rooms) = fallback_link(fallback::rooms(place_type, &place_idea.0));
+            connect_overworld(place_type, &place_idea, entrance, rooms)
+                .with_context(|| format!("Even the fallback rooms for {} failed", place_idea.0))
         })
         // Generate a few at once
         .buffered(3);
@@ -67,12 +136,49 @@ async fn generate_place(
     place_type: &PlaceType,
     place_idea: &(String, String),
 ) -> Result<(Place, HashMap<Location, Place>)> {
-    let rooms = generate_rooms(client, place_type, &place_idea).await?;
+    let rooms = match generate_rooms(client, place_type, place_idea).await {
+        Ok(rooms) if !rooms.is_empty() => rooms,
+        res => {
+            if let Err(e) = res {
+                tracing::warn!("Using fallback rooms for {}: {e}", place_idea.0);
+            }
+            fallback::rooms(place_type, &place_idea.0)
+        }
+    };
+    let (entrance, rooms) = link_rooms(client, place_type, &place_idea.0, rooms).await?;
+
+    connect_overworld(place_type, place_idea, entrance, rooms)
+}
+
+/// Makes the overworld entry for a place and links it down to the entrance
+fn connect_overworld(
+    place_type: &PlaceType,
+    place_idea: &(String, String),
+    entrance: Location,
+    mut rooms: HashMap<Location, Place>,
+) -> Result<(Place, HashMap<Location, Place>)> {
     let mut overworld_place = Place::new(
         format!("Overworld - {}", place_idea.0),
         place_idea.1.to_owned(),
     );
-    let (entrance, mut rooms) = link_rooms(client, place_type, &place_idea.0, rooms).await?;
+
+    // The place type doubles as the biome, deciding what can be gathered there
+    overworld_place.tags.insert(place_type.name.to_string());
+    for room in rooms.values_mut() {
+        room.tags.insert(place_type.name.to_string());
+    }
+
+    // Every village has somewhere near the way in to sell off loot, run a stall
+    // and trade maps
+    if *place_type == VILLAGE_PLACE_TYPE {
+        if let Some(room) = rooms.get_mut(&entrance) {
+            room.tags.insert(VENDOR_TAG.to_string());
+            room.tags.insert(MARKET_TAG.to_string());
+            room.tags.insert(CARTOGRAPHER_TAG.to_string());
+        }
+        mark_shops(entrance, &mut rooms);
+        mark_workshop(&mut rooms);
+    }
 
     overworld_place.add_connection(Direction::Down, entrance)?;
     rooms
@@ -83,6 +189,51 @@ async fn generate_place(
     Ok((overworld_place, rooms))
 }
 
+/// Turns the rooms that sound like shops into ones, or the entrance if none do
+fn mark_shops(entrance: Location, rooms: &mut HashMap<Location, Place>) {
+    let mut shops: Vec<_> = rooms
+        .values()
+        .filter(|r| {
+            let name = r.name.to_lowercase();
+            SHOP_WORDS.iter().any(|w| name.contains(w))
+        })
+        .collect();
+    shops.sort_by(|a, b| a.name.cmp(&b.name));
+    let mut shops: Vec<_> = shops
+        .into_iter()
+        .take(MAX_SHOPS)
+        .map(|r| r.location)
+        .collect();
+    if shops.is_empty() {
+        shops.push(entrance);
+    }
+
+    for location in shops {
+        if let Some(room) = rooms.get_mut(&location) {
+            room.tags.insert(VENDOR_TAG.to_string());
+            room.tags.insert(SHOP_TAG.to_string());
+        }
+    }
+}
+
+/// Turns the room that sounds most like a workshop into one, or a shop if none do
+fn mark_workshop(rooms: &mut HashMap<Location, Place>) {
+    let mut rooms_by_name: Vec<_> = rooms.values_mut().collect();
+    rooms_by_name.sort_by(|a, b| a.name.cmp(&b.name));
+    let sounds_like = |room: &Place| {
+        let name = room.name.to_lowercase();
+        WORKSHOP_WORDS.iter().any(|w| name.contains(w))
+    };
+
+    let idx = rooms_by_name
+        .iter()
+        .position(|r| sounds_like(r))
+        .or_else(|| rooms_by_name.iter().position(|r| r.tags.contains(SHOP_TAG)));
+    if let Some(idx) = idx {
+        rooms_by_name[idx].tags.insert(WORKSHOP_TAG.to_string());
+    }
+}
+
 #[derive(Template, Default)]
 #[template(path = "place_list.md")]
 struct CompletionTemplate<'a> {
@@ -121,10 +272,10 @@ async fn generate_rooms(
         &client
             .generate_with_tone(
                 GenerateRoomsTemplate {
-                    place_type: &place_type.name,
+                    place_type: place_type.name,
                     place_name: &place.0,
                     place_description: &place.1,
-                    room_type: &place_type.room_type,
+                    room_type: place_type.room_type,
                 }
                 .to_string(),
             )
@@ -137,12 +288,17 @@ async fn generate_rooms(
     Ok(rooms)
 }
 
+/// How many times we'll ask the model to fix its room links before
+/// falling back to linking them ourselves
+const MAX_LINK_ATTEMPTS: usize = 3;
+
 #[derive(Template)]
 #[template(path = "link_rooms.md")]
 struct LinkRoomsTemplate<'a> {
     place_type: &'a PlaceType,
     place_name: &'a str,
     rooms: &'a [Place],
+    errors: &'a [String],
 }
 
 #[derive(Debug, Deserialize)]
@@ -159,20 +315,122 @@ async fn link_rooms(
 ) -> Result<(Location, HashMap<Location, Place>)> {
     tracing::info!("Linking rooms for {place_name}");
 
-    let links: LinkRoomsOutput = extract_yaml(
-        &client
-            .generate_simple(
+    if rooms.is_empty() {
+        return Err(AppErrors::AIStructureError.into());
+    }
+
+    let mut errors = Vec::new();
+    for attempt in 1..=MAX_LINK_ATTEMPTS {
+        let res = client
+            .generate_structured::<LinkRoomsOutput>(
                 LinkRoomsTemplate {
                     place_type,
                     place_name,
                     rooms: &rooms,
+                    errors: &errors,
                 }
                 .to_string(),
             )
-            .await?,
-    )
-    .map_err(|_| AppErrors::AIStructureError)?;
+            .await;
+
+        let links = match res {
+            Ok(links) => links,
+            Err(e) => {
+                tracing::warn!("Link generation for {place_name} failed: {e}");
+                break;
+            }
+        };
+
+        errors = validate_links(&links, &rooms);
+        if errors.is_empty() {
+            return apply_links(&links, rooms);
+        }
+
+        tracing::warn!(
+            "Link attempt {attempt} for {place_name} was invalid: {}",
+            errors.join("; ")
+        );
+    }
+
+    tracing::warn!("Falling back to linear room links for {place_name}");
+    Ok(fallback_link(rooms))
+}
+
+/// Checks the model's links make a usable graph, returning a description
+/// of each problem so we can ask it to fix them
+fn validate_links(links: &LinkRoomsOutput, rooms: &[Place]) -> Vec<String> {
+    let mut errors = Vec::new();
+    let names: HashSet<_> = rooms.iter().map(|r| r.name.as_str()).collect();
+    let entrance = links.entrance.trim();
+
+    if !names.contains(entrance) {
+        errors.push(format!(
+            "The entrance '{entrance}' is not one of the listed rooms"
+        ));
+    }
 
+    let mut neighbours: HashMap<&str, HashSet<&str>> = HashMap::new();
+    for (node, connections) in &links.connections {
+        if !names.contains(node.as_str()) {
+            errors.push(format!("'{node}' is not one of the listed rooms"));
+            continue;
+        }
+
+        for con in connections {
+            if con == node {
+                errors.push(format!("'{node}' is connected to itself"));
+            } else if !names.contains(con.as_str()) {
+                errors.push(format!("'{node}' connects to '{con}' which is not listed"));
+            } else {
+                neighbours.entry(node).or_default().insert(con);
+                neighbours.entry(con).or_default().insert(node);
+            }
+        }
+    }
+
+    for (node, connected) in &neighbours {
+        // The entrance needs a free side to connect to the overworld
+        let limit = if *node == entrance {
+            Direction::values().len() - 1
+        } else {
+            Direction::values().len()
+        };
+
+        if connected.len() > limit {
+            errors.push(format!(
+                "'{node}' has {} connections but can have at most {limit}",
+                connected.len()
+            ));
+        }
+    }
+
+    let mut reached = HashSet::from([entrance]);
+    let mut frontier = vec![entrance];
+    while let Some(node) = frontier.pop() {
+        for next in neighbours.get(node).into_iter().flatten() {
+            if reached.insert(next) {
+                frontier.push(next);
+            }
+        }
+    }
+
+    for room in rooms {
+        if !reached.contains(room.name.as_str()) {
+            errors.push(format!(
+                "'{}' can't be reached from the entrance",
+                room.name
+            ));
+        }
+    }
+
+    errors
+}
+
+/// Connects rooms according to already validated links
+fn apply_links(
+    links: &LinkRoomsOutput,
+    rooms: Vec<Place>,
+) -> Result<(Location, HashMap<Location, Place>)> {
     let mut rooms: HashMap<_, _> = rooms.into_iter().map(|r| (r.location, r)).collect();
     let name_to_location: HashMap<_, _> = rooms
         .iter()
@@ -180,40 +438,60 @@ async fn link_rooms(
         .collect();
 
     for (node, connections) in &links.connections {
-        if let Some(a) = name_to_location.get(node) {
-            for con in connections {
-                if let Some(b) = name_to_location.get(con) {
-                    if !rooms[a].is_connected(*b) {
-                        let dir = rooms
-                            .get_mut(a)
-                            .unwrap()
-                            .add_connection(Direction::North, *b)
-                            .map_err(|_| AppErrors::AIStructureError)?;
-
-                        rooms
-                            .get_mut(b)
-                            .unwrap()
-                            .add_connection(dir.reverse(), *a)
-                            .map_err(|_| AppErrors::AIStructureError)?;
-                    }
-                }
+        let a = name_to_location[node];
+        for con in connections {
+            let b = name_to_location[con];
+            if !rooms[&a].is_connected(b) {
+                connect(&mut rooms, a, b)?;
             }
         }
     }
 
-    let entrance = links.entrance.trim().to_string();
-    let entrance_idx = *name_to_location
-        .get(&entrance)
-        .ok_or(AppErrors::AIStructureError)?;
+    let entrance = name_to_location[links.entrance.trim()];
 
-    Ok((entrance_idx, rooms))
+    Ok((entrance, rooms))
+}
+
+/// Chains the rooms together one after the other, the first room is the entrance.
+/// Bland, but always valid.
+fn fallback_link(rooms: Vec<Place>) -> (Location, HashMap<Location, Place>) {
+    let order: Vec<_> = rooms.iter().map(|r| r.location).collect();
+    let mut rooms: HashMap<_, _> = rooms.into_iter().map(|r| (r.location, r)).collect();
+
+    for pair in order.windows(2) {
+        connect(&mut rooms, pair[0], pair[1]).expect("A chain of rooms can always be connected");
+    }
+
+    (order[0], rooms)
+}
+
+fn connect(
+    rooms: &mut HashMap<Location, Place>,
+    a: Location,
+    b: Location,
+) -> Result<(), AppErrors> {
+    let dir = rooms
+        .get_mut(&a)
+        .unwrap()
+        .add_connection(Direction::North, b)?;
+    rooms
+        .get_mut(&b)
+        .unwrap()
+        .add_connection(dir.reverse(), a)?;
+    Ok(())
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct PlaceType {
-    name: &'static str,
-    room_type: &'static str,
-    room_types_pural: &'static str,
+    pub(super) name: &'static str,
+    pub(super) room_type: &'static str,
+    pub(super) room_types_pural: &'static str,
+}
+
+impl PlaceType {
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
 }
 
 pub const DUNGEON_PLACE_TYPE: PlaceType = PlaceType {
@@ -226,3 +504,61 @@ pub const VILLAGE_PLACE_TYPE: PlaceType = PlaceType {
     room_type: "building or street",
     room_types_pural: "buildings or streets",
 };
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rooms() -> Vec<Place> {
+        ["Gate", "Hall", "Crypt"]
+            .into_iter()
+            .map(|n| Place::new(n.to_string(), String::new()))
+            .collect()
+    }
+
+    #[test]
+    fn validates_links() {
+        let rooms = rooms();
+        let good = LinkRoomsOutput {
+            entrance: "Gate".into(),
+            connections: HashMap::from([
+                ("Gate".into(), vec!["Hall".into()]),
+                ("Hall".into(), vec!["Crypt".into()]),
+            ]),
+        };
+        assert!(validate_links(&good, &rooms).is_empty());
+
+        let bad = LinkRoomsOutput {
+            entrance: "Moat".into(),
+            connections: HashMap::from([("Hall".into(), vec!["Hall".into(), "Attic".into()])]),
+        };
+        let errors = validate_links(&bad, &rooms);
+        assert!(errors.iter().any(|e| e.contains("entrance")));
+        assert!(errors.iter().any(|e| e.contains("itself")));
+        assert!(errors.iter().any(|e| e.contains("Attic")));
+        assert!(errors.iter().any(|e| e.contains("reached")));
+
+        let (entrance, linked) = apply_links(&good, rooms).unwrap();
+        assert_eq!(linked[&entrance].name, "Gate");
+        assert_eq!(linked[&entrance].connections().len(), 1);
+    }
+
+    #[test]
+    fn backoff_doubles_with_jitter() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..20 {
+            let first = backoff(1, &mut rng);
+            assert!(first >= BASE_BACKOFF / 2 && first < BASE_BACKOFF);
+            let third = backoff(3, &mut rng);
+            assert!(third >= BASE_BACKOFF * 2 && third < BASE_BACKOFF * 4);
+            assert!(backoff(40, &mut rng) < MAX_BACKOFF);
+        }
+    }
+
+    #[test]
+    fn fallback_links_everything() {
+        let (entrance, linked) = fallback_link(rooms());
+        assert_eq!(linked[&entrance].name, "Gate");
+        assert!(linked.values().all(|r| !r.connections().is_empty()));
+    }
+}
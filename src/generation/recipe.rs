@@ -0,0 +1,54 @@
+use anyhow::Result;
+use askama::Template;
+use serde::Deserialize;
+
+use crate::mud::crafting::Recipe;
+
+use super::{extract_yaml, fallback, AIClient};
+
+#[derive(Template)]
+#[template(path = "name_recipe.md")]
+struct NameRecipeTemplate<'a> {
+    craft: &'a str,
+    product: &'a str,
+    ingredients: &'a [String],
+    effect: &'a str,
+}
+
+#[derive(Deserialize)]
+struct RecipeName {
+    name: String,
+    description: String,
+}
+
+async fn generate_name(client: &AIClient, recipe: &Recipe) -> Result<RecipeName> {
+    let prompt = NameRecipeTemplate {
+        craft: recipe.craft.name(),
+        product: recipe.craft.product(),
+        ingredients: &recipe.ingredients,
+        effect: &recipe.effect(),
+    }
+    .to_string();
+
+    let res = client.generate_with_tone(prompt).await?;
+    extract_yaml(&res)
+}
+
+/// Names a newly invented dish or potion, falling back to a
+/// plain procedural name if the AI backend can't help
+pub async fn name_recipe(client: &AIClient, mut recipe: Recipe) -> Recipe {
+    match generate_name(client, &recipe).await {
+        Ok(named) if !named.name.trim().is_empty() => {
+            recipe.name = named.name.trim().to_string();
+            recipe.description = named.description.trim().to_string();
+        }
+        res => {
+            if let Err(e) = res {
+                tracing::warn!("Using fallback name for {}: {e}", recipe.key());
+            }
+            (recipe.name, recipe.description) = fallback::recipe_name(&recipe);
+        }
+    }
+
+    recipe
+}
@@ -0,0 +1,84 @@
+use anyhow::Result;
+use askama::Template;
+use serde::Deserialize;
+
+use crate::mud::shop::MAX_WARES;
+
+use super::{extract_yaml, fallback, AIClient};
+
+#[derive(Template)]
+#[template(path = "shop_stock.md")]
+struct ShopStockTemplate<'a> {
+    shopkeeper: &'a str,
+    shop: &'a str,
+    description: &'a str,
+    candidates: &'a [(String, u32)],
+}
+
+#[derive(Deserialize)]
+struct StockOutput {
+    wares: Vec<WareOutput>,
+}
+
+#[derive(Deserialize)]
+struct WareOutput {
+    name: String,
+    price: u32,
+}
+
+async fn generate_stock(
+    client: &AIClient,
+    shopkeeper: &str,
+    shop: &str,
+    description: &str,
+    candidates: &[(String, u32)],
+) -> Result<StockOutput> {
+    let prompt = ShopStockTemplate {
+        shopkeeper,
+        shop,
+        description,
+        candidates,
+    }
+    .to_string();
+
+    let res = client.generate_with_tone(prompt).await?;
+    extract_yaml(&res)
+}
+
+/// The wares a shopkeeper picks from what's on offer and what they ask for each.
+/// Anything not on offer is dropped and the prices aren't trusted, the engine
+/// settles them against what each ware is worth.
+pub async fn stock_shop(
+    client: &AIClient,
+    shopkeeper: &str,
+    shop: &str,
+    description: &str,
+    candidates: &[(String, u32)],
+) -> Vec<(String, u32)> {
+    tracing::info!("Stocking {shop}");
+
+    let wares: Vec<_> =
+        match generate_stock(client, shopkeeper, shop, description, candidates).await {
+            Ok(output) => output
+                .wares
+                .into_iter()
+                .filter_map(|ware| {
+                    let (name, _) = candidates
+                        .iter()
+                        .find(|(name, _)| name.eq_ignore_ascii_case(ware.name.trim()))?;
+                    Some((name.clone(), ware.price))
+                })
+                .take(MAX_WARES)
+                .collect(),
+            Err(e) => {
+                tracing::warn!("Using fallback stock for {shop}: {e}");
+                Vec::new()
+            }
+        };
+
+    if wares.is_empty() {
+        fallback::shop_stock(shop, candidates)
+    } else {
+        wares
+    }
+}
@@ -0,0 +1,89 @@
+use askama::Template;
+use serde::Deserialize;
+
+use crate::mud::{
+    items::Item,
+    storyline::{StoryStage, Storyline},
+};
+
+use super::{extract_yaml, fallback, AIClient};
+
+const MAX_STORY_ATTEMPTS: usize = 3;
+/// Keepsakes are meant to be carried around
+const MAX_REWARD_WEIGHT: u32 = 5;
+
+#[derive(Template)]
+#[template(path = "storyline.md")]
+struct StorylineTemplate<'a> {
+    npc: &'a str,
+    place: &'a str,
+    obtainable: &'a [String],
+    errors: &'a [String],
+}
+
+#[derive(Deserialize)]
+struct StorylineOutput {
+    title: String,
+    stages: Vec<StoryStage>,
+    reward: Item,
+}
+
+/// A storyline for an NPC, retried with the problems pointed out if the model's
+/// one can't be played through. Falls back to a stock one after a few tries.
+pub async fn write_storyline(
+    client: &AIClient,
+    npc: &str,
+    place: &str,
+    obtainable: &[String],
+) -> Storyline {
+    tracing::info!("Writing a storyline for {npc}");
+
+    let mut errors = Vec::new();
+    for attempt in 1..=MAX_STORY_ATTEMPTS {
+        let res = client
+            .generate_with_tone(
+                StorylineTemplate {
+                    npc,
+                    place,
+                    obtainable,
+                    errors: &errors,
+                }
+                .to_string(),
+            )
+            .await;
+
+        let res = match res {
+            Ok(res) => res,
+            Err(e) => {
+                tracing::warn!("Storyline generation for {npc} failed: {e}");
+                break;
+            }
+        };
+
+        match extract_yaml::<StorylineOutput>(&res) {
+            Ok(output) => {
+                let mut reward = output.reward;
+                reward.weight = reward.weight.clamp(1, MAX_REWARD_WEIGHT);
+                let story = Storyline {
+                    npc: npc.to_string(),
+                    title: output.title.trim().to_string(),
+                    stages: output.stages,
+                    reward,
+                };
+
+                errors = story.validate(obtainable);
+                if errors.is_empty() {
+                    return story;
+                }
+            }
+            Err(e) => errors = vec![format!("The YAML could not be read: {e}")],
+        }
+
+        tracing::warn!(
+            "Storyline attempt {attempt} for {npc} was invalid: {}",
+            errors.join("; ")
+        );
+    }
+
+    fallback::storyline(npc, obtainable)
+}
@@ -0,0 +1,82 @@
+//! Splits streamed replies into sentences. Every message the engine sends is a
+//! line of its own, so players get each sentence as the model finishes it
+//! rather than a word per line.
+
+/// Collects streamed text, giving back each sentence once it's finished
+#[derive(Debug, Default)]
+pub struct Sentences {
+    pending: String,
+}
+
+impl Sentences {
+    /// Adds a piece of the reply, returning any sentences it finished
+    pub fn push(&mut self, piece: &str) -> Vec<String> {
+        self.pending.push_str(piece);
+
+        let mut done = Vec::new();
+        while let Some(end) = sentence_end(&self.pending) {
+            let sentence: String = self.pending.drain(..end).collect();
+            let sentence = sentence.trim();
+            if !sentence.is_empty() {
+                done.push(sentence.to_string());
+            }
+        }
+        done
+    }
+
+    /// Whatever's left once the reply is finished
+    pub fn finish(self) -> Option<String> {
+        let rest = self.pending.trim();
+        (!rest.is_empty()).then(|| rest.to_string())
+    }
+}
+
+/// Where the first finished sentence ends, taking any closing quotes with it.
+/// Punctuation only ends a sentence once whitespace follows, so a number like
+/// 3.5 or an ellipsis cut off mid-stream isn't split.
+fn sentence_end(text: &str) -> Option<usize> {
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if c == '\n' {
+            return Some(i + 1);
+        }
+        if !matches!(c, '.' | '!' | '?') {
+            continue;
+        }
+
+        let mut end = i + c.len_utf8();
+        while let Some(&(j, next)) = chars.peek() {
+            if !matches!(next, '.' | '!' | '?' | '"' | '\'' | ')' | '*' | '_') {
+                break;
+            }
+            end = j + next.len_utf8();
+            chars.next();
+        }
+        if chars.peek().is_some_and(|(_, next)| next.is_whitespace()) {
+            return Some(end);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn splits_finished_sentences() {
+        let mut sentences = Sentences::default();
+        assert!(sentences.push("Well met, trav").is_empty());
+        assert!(sentences.push("eller.").is_empty());
+        assert_eq!(
+            sentences.push(" That'll be 3.5 gold"),
+            ["Well met, traveller."]
+        );
+        assert_eq!(
+            sentences.push("! \"Really?\" she asks...\nNo"),
+            ["That'll be 3.5 gold!", "\"Really?\"", "she asks..."]
+        );
+        assert_eq!(sentences.finish().as_deref(), Some("No"));
+        assert_eq!(Sentences::default().finish(), None);
+    }
+}
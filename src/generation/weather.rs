@@ -0,0 +1,71 @@
+use anyhow::Result;
+use askama::Template;
+use serde::Deserialize;
+
+use crate::mud::weather::{WeatherFront, WorldTime};
+
+use super::{extract_yaml, fallback, AIClient};
+
+#[derive(Template)]
+#[template(path = "weather_front.md")]
+struct WeatherFrontTemplate<'a> {
+    region: &'a str,
+    description: &'a str,
+    phase: &'a str,
+    day: u64,
+    last: Option<&'a str>,
+}
+
+#[derive(Deserialize)]
+struct FrontOutput {
+    name: String,
+    description: String,
+}
+
+async fn generate_front(
+    client: &AIClient,
+    region: &str,
+    description: &str,
+    time: WorldTime,
+    last: Option<&str>,
+) -> Result<FrontOutput> {
+    let prompt = WeatherFrontTemplate {
+        region,
+        description,
+        phase: time.phase().name(),
+        day: time.day,
+        last,
+    }
+    .to_string();
+
+    let res = client.generate_with_tone(prompt).await?;
+    extract_yaml(&res)
+}
+
+/// The next weather over a region, given how it looks, the time and the
+/// weather it's replacing. It lasts until the engine says otherwise.
+pub async fn next_front(
+    client: &AIClient,
+    region: &str,
+    description: &str,
+    time: WorldTime,
+    last: Option<&str>,
+) -> WeatherFront {
+    let (name, description) = match generate_front(client, region, description, time, last).await {
+        Ok(front) if !front.name.trim().is_empty() && !front.description.trim().is_empty() => {
+            (front.name, front.description)
+        }
+        res => {
+            if let Err(e) = res {
+                tracing::warn!("Using fallback weather for {region}: {e}");
+            }
+            fallback::weather_front(region, time.day)
+        }
+    };
+
+    WeatherFront {
+        name: name.trim().to_string(),
+        description: description.trim().to_string(),
+        until_tick: 0,
+    }
+}
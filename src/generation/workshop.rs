@@ -0,0 +1,100 @@
+use anyhow::Result;
+use askama::Template;
+use serde::Deserialize;
+
+use crate::mud::workshop::{Blueprint, MAX_BLUEPRINTS};
+
+use super::{extract_yaml, fallback, AIClient};
+
+#[derive(Template)]
+#[template(path = "blueprints.md")]
+struct BlueprintsTemplate<'a> {
+    workshop: &'a str,
+    description: &'a str,
+    products: &'a [(String, u32)],
+    materials: &'a [(String, u32)],
+}
+
+#[derive(Deserialize)]
+struct BlueprintsOutput {
+    blueprints: Vec<BlueprintOutput>,
+}
+
+#[derive(Deserialize)]
+struct BlueprintOutput {
+    name: String,
+    materials: Vec<MaterialOutput>,
+}
+
+#[derive(Deserialize)]
+struct MaterialOutput {
+    name: String,
+    count: u32,
+}
+
+async fn generate_blueprints(
+    client: &AIClient,
+    workshop: &str,
+    description: &str,
+    products: &[(String, u32)],
+    materials: &[(String, u32)],
+) -> Result<BlueprintsOutput> {
+    let prompt = BlueprintsTemplate {
+        workshop,
+        description,
+        products,
+        materials,
+    }
+    .to_string();
+
+    let res = client.generate_with_tone(prompt).await?;
+    extract_yaml(&res)
+}
+
+/// Picks an item from the list by name, ignoring case, so names match the world's
+fn known(list: &[(String, u32)], name: &str) -> Option<String> {
+    list.iter()
+        .find(|(known, _)| known.eq_ignore_ascii_case(name.trim()))
+        .map(|(known, _)| known.clone())
+}
+
+/// Blueprints for a workshop, made from the products and materials on offer.
+/// Anything not on offer is dropped and the amounts aren't trusted, the engine
+/// settles them against what everything's worth.
+pub async fn draw_blueprints(
+    client: &AIClient,
+    workshop: &str,
+    description: &str,
+    products: &[(String, u32)],
+    materials: &[(String, u32)],
+) -> Vec<Blueprint> {
+    tracing::info!("Drawing up blueprints for {workshop}");
+
+    let blueprints: Vec<_> =
+        match generate_blueprints(client, workshop, description, products, materials).await {
+            Ok(output) => output
+                .blueprints
+                .into_iter()
+                .filter_map(|blueprint| {
+                    let name = known(products, &blueprint.name)?;
+                    let needed = blueprint
+                        .materials
+                        .into_iter()
+                        .filter_map(|m| Some((known(materials, &m.name)?, m.count)))
+                        .collect();
+                    Some(Blueprint::new(name, needed))
+                })
+                .take(MAX_BLUEPRINTS)
+                .collect(),
+            Err(e) => {
+                tracing::warn!("Using fallback blueprints for {workshop}: {e}");
+                Vec::new()
+            }
+        };
+
+    if blueprints.is_empty() {
+        fallback::blueprints(workshop, products, materials)
+    } else {
+        blueprints
+    }
+}
@@ -0,0 +1,209 @@
+//! Looking into and fixing saves offline, `somnuscape inspect` and `somnuscape
+//! edit`. Saves are read and written with the same models and storage the
+//! server uses, so edits should only be made while it's stopped or they'll be
+//! overwritten by its next save.
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::{
+    commands::GOLD,
+    config,
+    mud::{
+        ledger,
+        world::{Place, World},
+    },
+    state,
+};
+
+const INSPECT_USAGE: &str = "Usage: inspect <realm or save dir> [player <name> | place <name>]";
+const EDIT_USAGE: &str =
+    "Usage: edit <realm or save dir> remove-item <item> | repair | replace-place <place file>";
+/// The most common items listed in the statistics
+const TOP_ITEMS: usize = 15;
+
+/// Prints statistics for a saved world, or one player's character or place
+pub fn inspect(args: &[String]) -> Result<()> {
+    let [save, rest @ ..] = args else {
+        bail!(INSPECT_USAGE);
+    };
+    let mut world = load(save)?;
+
+    match rest {
+        [] => print!("{}", statistics(&mut world)),
+        [what, name @ ..] if what == "player" && !name.is_empty() => {
+            print!("{}", player(&world, &name.join(" "))?)
+        }
+        [what, name @ ..] if what == "place" && !name.is_empty() => {
+            let name = name.join(" ");
+            let places: Vec<_> = world
+                .places
+                .values()
+                .filter(|p| p.name.eq_ignore_ascii_case(&name))
+                .collect();
+            if places.is_empty() {
+                bail!("No place called {name}");
+            }
+            for place in places {
+                println!("---\n{}", serde_yaml::to_string(place)?);
+            }
+        }
+        _ => bail!(INSPECT_USAGE),
+    }
+
+    Ok(())
+}
+
+/// Changes a saved world, run while the server is stopped
+pub fn edit(args: &[String]) -> Result<()> {
+    let [save, rest @ ..] = args else {
+        bail!(EDIT_USAGE);
+    };
+    let mut world = load(save)?;
+
+    match rest {
+        [what, item @ ..] if what == "remove-item" && !item.is_empty() => {
+            let item = item.join(" ");
+            let removed = remove_item(&mut world, &item);
+            if removed == 0 {
+                bail!("Nobody has any {item}");
+            }
+            println!("Took {removed} {item} out of circulation");
+        }
+        [what] if what == "repair" => {
+            let fixes = world.repair();
+            if fixes.is_empty() {
+                println!("Nothing needed fixing");
+                return Ok(());
+            }
+            for fix in &fixes {
+                println!("Fixed: {fix}");
+            }
+        }
+        [what, file] if what == "replace-place" => {
+            let place: Place = serde_yaml::from_str(&std::fs::read_to_string(file)?)?;
+            let Some(old) = world.places.get_mut(&place.location) else {
+                bail!("There's no place at {} to replace", place.location);
+            };
+            println!("Replaced {}", old.name);
+            *old = place;
+
+            // The new record may point at places that don't exist
+            for fix in world.repair() {
+                println!("Fixed: {fix}");
+            }
+        }
+        _ => bail!(EDIT_USAGE),
+    }
+
+    world.save()
+}
+
+/// Loads a realm's world by name, or a world from a save directory
+fn load(save: &str) -> Result<World> {
+    let save_dir = config::get()
+        .realms()
+        .into_iter()
+        .find(|r| r.name.eq_ignore_ascii_case(save))
+        .map_or_else(|| save.to_string(), |r| r.save_dir);
+
+    World::load(&save_dir)?.ok_or_else(|| anyhow!("No world is saved in {save}"))
+}
+
+/// Takes every one of an item out of the world, returning how many there were.
+/// What's taken is written in the ledger so the next audit expects it gone.
+fn remove_item(world: &mut World, item: &str) -> u32 {
+    let before = world.holdings();
+    let mut removed: u32 = world
+        .inventories_mut()
+        .into_iter()
+        .map(|inventory| inventory.remove_all(item))
+        .sum();
+
+    for character in world.player_characters.values_mut() {
+        if character
+            .wielding
+            .take_if(|w| w.template.eq_ignore_ascii_case(item))
+            .is_some()
+        {
+            removed += 1;
+        }
+    }
+    for stall in world.places.values_mut().flat_map(|p| &mut p.stalls) {
+        stall
+            .prices
+            .retain(|name, _| !name.eq_ignore_ascii_case(item));
+    }
+
+    let after = world.holdings();
+    for (name, change) in ledger::changes(&before, &after) {
+        world.record_transaction("an admin", &name, change, "editing the save");
+    }
+    removed
+}
+
+fn statistics(world: &mut World) -> String {
+    let mut items = world.holdings();
+    let gold = items.remove(GOLD).unwrap_or_default();
+    let mut items: Vec<_> = items.into_iter().collect();
+    items.sort_by_key(|(name, count)| (std::cmp::Reverse(*count), name.clone()));
+
+    let time = world.time();
+    let mut res = format!(
+        "Cycle {}, day {} of the dream, tick {}\n\n",
+        world.cycle, time.day, world.current_tick
+    );
+    res.push_str(&format!(
+        "{} places, {} overworld areas\n{} characters, {} NPCs\n",
+        world.places.len(),
+        world.overworld_locales.len(),
+        world.player_characters.len(),
+        world.npcs.values().map(|n| n.len()).sum::<usize>()
+    ));
+    res.push_str(&format!(
+        "{} councils, {} parties, {} work orders, {} storylines\n",
+        world.factions.len(),
+        world.parties.len(),
+        world.work_orders.len(),
+        world.storylines.len()
+    ));
+    res.push_str(&format!(
+        "{} kinds of item known, {} in circulation, and {gold} {GOLD}s\n",
+        world.items.len(),
+        items.len()
+    ));
+    for (name, count) in items.iter().take(TOP_ITEMS) {
+        res.push_str(&format!("  {count} {name}\n"));
+    }
+    res
+}
+
+/// Finds a character by their player's username or the character's name
+fn player(world: &World, name: &str) -> Result<String> {
+    let accounts =
+        state::storage()?.load_accounts(&state::make_save_path("player-registry.yaml"))?;
+    let username = |id| {
+        accounts
+            .get(id)
+            .map_or("no account", |a| a.username.as_str())
+    };
+
+    let (id, character) = world
+        .player_characters
+        .iter()
+        .find(|(id, c)| {
+            username(*id).eq_ignore_ascii_case(name) || c.name.eq_ignore_ascii_case(name)
+        })
+        .ok_or_else(|| anyhow!("No character belongs to {name}"))?;
+
+    let place = world
+        .places
+        .get(&character.location)
+        .map_or("a place that doesn't exist", |p| &p.name);
+    Ok(format!(
+        "{} ({}) is level {} and in {place}\n---\n{}",
+        character.name,
+        username(id),
+        character.level(),
+        serde_yaml::to_string(character)?
+    ))
+}
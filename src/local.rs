@@ -0,0 +1,85 @@
+//! Playing in the terminal, `somnuscape play --local`. The engine runs in
+//! process with generation offline, so gameplay changes can be tried without
+//! a telnet client, a model or a network.
+
+use anyhow::Result;
+use futures::{future::BoxFuture, FutureExt};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines, Stdin, Stdout};
+
+use crate::{
+    engine::Engine, frontend, frontend::Transport, generation::Generator, AccountStorage,
+    ConnectionState, Realm,
+};
+
+/// The terminal the server was started from
+pub struct LocalTransport {
+    stdin: Lines<BufReader<Stdin>>,
+    stdout: Stdout,
+}
+
+impl LocalTransport {
+    pub fn new() -> Self {
+        Self {
+            stdin: BufReader::new(tokio::io::stdin()).lines(),
+            stdout: tokio::io::stdout(),
+        }
+    }
+}
+
+impl Transport for LocalTransport {
+    fn next_line(&mut self) -> BoxFuture<'_, Option<String>> {
+        // Lines::next_line can be dropped part way without losing input
+        async move { self.stdin.next_line().await.ok().flatten() }.boxed()
+    }
+
+    fn send(&mut self, msg: String) -> BoxFuture<'_, Result<()>> {
+        async move {
+            self.stdout.write_all(msg.as_bytes()).await?;
+            self.stdout.write_all(b"\n").await?;
+            self.stdout.flush().await?;
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+/// Runs the configured realms in process and plays them from the terminal
+/// until stdin closes. Usage: play --local
+pub async fn play(args: &[String]) -> Result<()> {
+    if !args.iter().any(|a| a == "--local") {
+        anyhow::bail!("Usage: play --local");
+    }
+
+    let players = AccountStorage::load_or_new("player-registry.yaml").await?;
+    let (gen, gen_handle) = Generator::new();
+    tokio::spawn(async move {
+        gen.run().await;
+    });
+
+    let realms: Vec<Realm> = crate::config::get()
+        .realms()
+        .iter()
+        .map(|realm_config| Realm {
+            name: realm_config.name.clone(),
+            broker: Engine::start_engine(
+                players.clone(),
+                gen_handle.new_handle(),
+                realm_config.clone(),
+            ),
+        })
+        .collect();
+
+    let mut connection_state = ConnectionState::Unauthorized;
+    let session = frontend::serve(
+        LocalTransport::new(),
+        players,
+        &realms,
+        &mut connection_state,
+    )
+    .await;
+
+    if let Some((player_id, realm)) = connection_state.get_connection() {
+        realms[realm].broker.end_connection(player_id);
+    }
+    session
+}
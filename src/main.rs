@@ -1,27 +1,55 @@
+mod alerts;
+mod args;
+mod bots;
 mod commands;
 mod connections;
+mod digest;
+mod editor;
 mod engine;
+mod frontend;
+mod fuzzy;
 mod generation;
+mod inspect;
+mod local;
+mod markup;
+mod mccp;
 mod mud;
+mod prompt;
+mod reports;
+mod rules;
+mod script_store;
+mod scripting;
 mod state;
+mod systems;
+mod telnet;
+mod websocket;
 
-use std::net::SocketAddr;
 use std::{error::Error, fmt::Display};
 
 use anyhow::Result;
 use connections::{EngineConnection, PlayerConnectionBroker};
 use engine::Engine;
-use futures::{SinkExt, StreamExt};
+use futures::FutureExt;
 use generation::Generator;
-use mud::world::Location;
-use nectar::{event::TelnetEvent, TelnetCodec};
+use mud::world::{Location, World};
 use serde::{Deserialize, Serialize};
-use state::{AccountStorage, PlayerId};
-use tokio::net::{TcpListener, TcpStream};
-use tokio_util::codec::Framed;
+use state::{AccountStorage, Password, PlayerId};
+use telnet::Telnet;
+use websocket::WebSocket;
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    config::get().validate()?;
+    match args.first().map(|a| a.as_str()) {
+        Some("clone-realm") => return clone_realm(&args[1..]),
+        Some("inspect") => return inspect::inspect(&args[1..]),
+        Some("edit") => return inspect::edit(&args[1..]),
+        Some("script-test") => return scripting::script_test(&args[1..]),
+        Some("play") => return local::play(&args[1..]).await,
+        _ => {}
+    }
+
     console_subscriber::init();
 
     let players = AccountStorage::load_or_new("player-registry.yaml").await?;
@@ -30,88 +58,125 @@ async fn main() -> Result<()> {
         gen.run().await;
     });
 
-    let engine_msg_handler = Engine::start_engine(players.clone(), gen_handle);
-
-    let addr: SocketAddr = config::get()
-        .server_address
-        .parse()
-        .expect("Server address should be a valid <ip:port>");
-    let listener = TcpListener::bind(addr).await?;
-
-    loop {
-        while let Ok((stream, addr)) = listener.accept().await {
-            tracing::info!("Player connected from {addr}");
-
-            let players = players.clone();
-            let player_conn_handler = engine_msg_handler.clone();
-
-            tokio::spawn(async move {
-                let mut connection_state = ConnectionState::Unauthorized;
-                let pch = player_conn_handler.clone();
-
-                if let Err(e) = handler(stream, players.clone(), pch, &mut connection_state).await {
-                    if e.is::<AppErrors>() {
-                        if let Ok(AppErrors::PlayerDisconnected(id)) = e.downcast::<AppErrors>() {
-                            let pr = players.read().await;
-                            let n = pr.get(&id).map(|p| p.username.as_str()).unwrap_or_default();
-                            tracing::info!("Player disconnected: {n}");
-                        }
-                    } else {
-                        tracing::error!("Player connection error: {e}");
-                    }
-                }
-
-                if let Some(player_id) = connection_state.get_player_id() {
-                    player_conn_handler.end_connection(player_id);
-                }
-            });
+    // Each realm gets its own engine and world, sharing the generator and accounts
+    let mut realms = Vec::new();
+    let mut listeners = Vec::new();
+    for realm_config in config::get().realms() {
+        let realm = Realm {
+            name: realm_config.name.clone(),
+            broker: Engine::start_engine(
+                players.clone(),
+                gen_handle.new_handle(),
+                realm_config.clone(),
+            ),
+        };
+
+        // Realms with their own address skip the realm selector
+        if let Some(addr) = &realm_config.server_address {
+            listeners.push(
+                frontend::listen(Telnet, addr.clone(), players.clone(), vec![realm.clone()])
+                    .boxed(),
+            );
         }
+        realms.push(realm);
+    }
+    if let Some(addr) = &config::get().websocket_address {
+        listeners.push(
+            frontend::listen(WebSocket, addr.clone(), players.clone(), realms.clone()).boxed(),
+        );
     }
+    listeners.push(
+        frontend::listen(
+            Telnet,
+            config::get().server_address.clone(),
+            players,
+            realms,
+        )
+        .boxed(),
+    );
+
+    futures::future::try_join_all(listeners).await?;
+
+    Ok(())
 }
 
-async fn handler(
-    stream: TcpStream,
-    player_registry: AccountStorage,
-    broker: PlayerConnectionBroker,
-    connection_state: &mut ConnectionState,
-) -> Result<()> {
-    let mut frame = Framed::new(stream, TelnetCodec::new(1024));
-
-    frame
-        .send(TelnetEvent::Message(
-            "<~~ Welcome adventurer! What is thy name? ~~>".to_string(),
-        ))
-        .await?;
-
-    loop {
-        if let ConnectionState::Authorized(_, ref mut handler) = connection_state {
-            tokio::select! {
-                Some(Ok(TelnetEvent::Message(player_msg))) = frame.next() => {
-                    handler.send(player_msg)?;
-                }
-                response = handler.recv() => {
-                    frame.send(TelnetEvent::Message(response?)).await?;
-                }
-            }
-        } else {
-            if let Some(Ok(TelnetEvent::Message(player_msg))) = frame.next().await {
-                let reply = connection_state
-                    .handle_login(player_msg, &player_registry, &broker)
-                    .await;
-                frame.send(TelnetEvent::Message(reply?)).await?;
-            } else {
-                break; // TODO: Better error handling?
-            }
-        }
+/// Copies one realm's saved world over another's, run while the server is stopped.
+/// Usage: clone-realm <from realm> <to realm> [--scrub-inventories]
+fn clone_realm(args: &[String]) -> Result<()> {
+    let [from, to, flags @ ..] = args else {
+        anyhow::bail!("Usage: clone-realm <from realm> <to realm> [--scrub-inventories]");
+    };
+    let scrub = flags.iter().any(|f| f == "--scrub-inventories");
+
+    let realms = config::get().realms();
+    let find = |name: &str| {
+        realms
+            .iter()
+            .find(|r| r.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| anyhow::anyhow!("No realm called {name}"))
+    };
+    let (from, to) = (find(from)?, find(to)?);
+    if from.save_dir == to.save_dir {
+        anyhow::bail!("{} and {} share a save directory", from.name, to.name);
     }
 
+    World::load(&from.save_dir)?
+        .ok_or_else(|| anyhow::anyhow!("{} has no saved world to copy", from.name))?
+        .clone_to_realm(&to.save_dir, scrub)?;
+    println!("Copied {} into {}", from.name, to.name);
+
     Ok(())
 }
 
+/// A world players can pick at login, and the broker to connect to its engine
+#[derive(Debug, Clone)]
+pub struct Realm {
+    pub name: String,
+    pub broker: PlayerConnectionBroker,
+}
+
+const WELCOME: &str = "<~~ Welcome adventurer! What is thy name? ~~>";
+/// Wrong passwords in a row before the player has to give their name again
+const MAX_LOGIN_ATTEMPTS: u32 = 3;
+/// How long a wrong password holds up the connection, more each time
+const LOGIN_FAIL_DELAY: std::time::Duration = std::time::Duration::from_secs(1);
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PlayerAccount {
     pub username: String,
-    pub password: u64,
+    pub password: Password,
+    /// Whether messages are sent with ANSI colours
+    #[serde(default = "color_default")]
+    pub color: bool,
+    /// How wide to wrap messages, overriding what the client reports
+    #[serde(default)]
+    pub width: Option<u16>,
+    /// Whether telnet output is compressed for clients that support it
+    #[serde(default = "compress_default")]
+    pub compress: bool,
+    #[serde(default)]
+    pub role: Role,
+    /// Banned players can't log in
+    #[serde(default)]
+    pub banned: bool,
+}
+
+/// What a player's allowed to do beyond playing
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Role {
+    #[default]
+    Player,
+    /// Can run admin commands, see [`commands::Command::admin`]
+    Admin,
+}
+
+fn color_default() -> bool {
+    true
+}
+
+fn compress_default() -> bool {
+    true
 }
 
 #[derive(Debug, Default)]
@@ -119,8 +184,10 @@ pub enum ConnectionState {
     #[default]
     Unauthorized,
     NewUser(String),
-    Login(PlayerId),
-    Authorized(PlayerId, EngineConnection),
+    /// Waiting on a player's password, with how many wrong ones they've given
+    Login(PlayerId, u32),
+    ChooseRealm(PlayerId),
+    Authorized(PlayerId, usize, EngineConnection),
 }
 
 impl ConnectionState {
@@ -128,7 +195,7 @@ impl ConnectionState {
         &mut self,
         msg: String,
         player_registry: &AccountStorage,
-        broker: &PlayerConnectionBroker,
+        realms: &[Realm],
     ) -> Result<String> {
         match self {
             ConnectionState::Unauthorized => {
@@ -139,11 +206,13 @@ impl ConnectionState {
                     .find(|(_, p)| p.username.to_lowercase() == username.to_lowercase());
 
                 if let Some((id, player)) = player {
-                    *self = ConnectionState::Login(*id);
+                    *self = ConnectionState::Login(*id, 0);
                     Ok(format!(
                         "Welcome back {}!\r\nPlease enter your password:",
                         player.username
                     ))
+                } else if let Err(e) = commands::check_name(username) {
+                    Ok(format!("{e}. What is thy name?"))
                 } else {
                     *self = ConnectionState::NewUser(username.to_string());
                     Ok(format!("Welcome {username}!\r\n\r\nWe haven't see you before, please choose a password!\r\n\
@@ -153,56 +222,148 @@ impl ConnectionState {
             }
             ConnectionState::NewUser(username) => {
                 let username = username.clone();
-                let password = seahash::hash(msg.as_bytes());
-                let player = PlayerAccount { username, password };
+                // Hashing's slow on purpose, so it's kept off the async workers
+                let password = tokio::task::spawn_blocking(move || Password::hash(&msg)).await??;
+                let player = PlayerAccount {
+                    username,
+                    password,
+                    color: true,
+                    width: None,
+                    compress: true,
+                    role: Role::Player,
+                    banned: false,
+                };
 
                 tracing::info!("Player {} registered an account", player.username);
                 let id = player_registry.register_user(player).await?;
 
-                *self = Self::Authorized(id, broker.setup_connection(id));
-
-                Ok("Password set.\r\nWelcome to Somnuscape!".to_owned())
+                Ok(format!(
+                    "Password set.\r\n{}",
+                    self.enter_realms(id, realms)
+                ))
             }
-            ConnectionState::Login(player_id) => {
-                let player_id = *player_id;
-                let password = seahash::hash(msg.as_bytes());
+            ConnectionState::Login(player_id, failures) => {
+                let (player_id, failures) = (*player_id, *failures + 1);
                 let read = player_registry.read().await;
                 let player = &read[&player_id];
+                let (username, banned) = (player.username.clone(), player.banned);
+                let stored = player.password.clone();
+                // The engine takes this lock each tick, so it's not held while hashing
+                drop(read);
+
+                if banned {
+                    tracing::info!("Banned player {username} tried to log in");
+                    *self = ConnectionState::Unauthorized;
+                    return Ok(format!("This account has been banned.\r\n{WELCOME}"));
+                }
 
-                if password == player.password {
-                    *self =
-                        ConnectionState::Authorized(player_id, broker.setup_connection(player_id));
-                    tracing::info!("Player {} logged in", player.username);
+                let legacy = stored.is_legacy();
+                let guess = msg.clone();
+                if tokio::task::spawn_blocking(move || stored.verify(&guess)).await? {
+                    tracing::info!("Player {username} logged in");
+
+                    if legacy {
+                        tracing::info!("Upgrading password hash for {username}");
+                        let password =
+                            tokio::task::spawn_blocking(move || Password::hash(&msg)).await??;
+                        player_registry
+                            .update(player_id, |p| p.password = password)
+                            .await?;
+                    }
 
-                    Ok("Login successful.\r\nWelcome back to Somnuscape!".to_owned())
-                } else {
                     Ok(format!(
-                        "Login failed, retry your password for {}:",
-                        player.username
+                        "Login successful.\r\n{}",
+                        self.enter_realms(player_id, realms)
                     ))
+                } else {
+                    tracing::info!("Failed login {failures} for {username}");
+                    tokio::time::sleep(LOGIN_FAIL_DELAY * failures).await;
+                    if failures >= MAX_LOGIN_ATTEMPTS {
+                        *self = ConnectionState::Unauthorized;
+                        return Ok(format!("Too many wrong passwords.\r\n{WELCOME}"));
+                    }
+                    *self = ConnectionState::Login(player_id, failures);
+                    Ok(format!("Login failed, retry your password for {username}:"))
                 }
             }
-            ConnectionState::Authorized(_, _) => {
+            ConnectionState::ChooseRealm(player_id) => {
+                let choice = msg.trim();
+                let realm = realms
+                    .iter()
+                    .enumerate()
+                    .find(|(i, r)| {
+                        r.name.eq_ignore_ascii_case(choice) || (i + 1).to_string() == choice
+                    })
+                    .map(|(i, _)| i);
+
+                match realm {
+                    Some(realm) => {
+                        let player_id = *player_id;
+                        *self = ConnectionState::Authorized(
+                            player_id,
+                            realm,
+                            realms[realm].broker.setup_connection(player_id),
+                        );
+                        Ok(format!("Welcome to {}!", realms[realm].name))
+                    }
+                    None => Ok(format!(
+                        "There's no realm called {choice}\r\n{}",
+                        realm_list(realms)
+                    )),
+                }
+            }
+            ConnectionState::Authorized(_, _, _) => {
                 unreachable!("Should not be handling login if already logged in");
             }
         }
     }
 
-    pub fn get_player_id(&self) -> Option<PlayerId> {
+    /// Whether the next thing the player types is a password
+    fn wants_password(&self) -> bool {
+        matches!(
+            self,
+            ConnectionState::NewUser(_) | ConnectionState::Login(..)
+        )
+    }
+
+    /// Connects straight to the only realm, or asks the player which one they want
+    fn enter_realms(&mut self, player_id: PlayerId, realms: &[Realm]) -> String {
+        if let [realm] = realms {
+            *self =
+                ConnectionState::Authorized(player_id, 0, realm.broker.setup_connection(player_id));
+            format!("Welcome to {}!", realm.name)
+        } else {
+            *self = ConnectionState::ChooseRealm(player_id);
+            realm_list(realms)
+        }
+    }
+
+    /// The player and index of the realm they're connected to, if they're in one
+    pub fn get_connection(&self) -> Option<(PlayerId, usize)> {
         match self {
             ConnectionState::Unauthorized => None,
             ConnectionState::NewUser(_) => None,
-            ConnectionState::Login(_) => None,
-            ConnectionState::Authorized(id, _) => Some(*id),
+            ConnectionState::Login(..) => None,
+            ConnectionState::ChooseRealm(_) => None,
+            ConnectionState::Authorized(id, realm, _) => Some((*id, *realm)),
         }
     }
 }
 
+fn realm_list(realms: &[Realm]) -> String {
+    let mut res = "Which realm would you like to enter?\r\n".to_string();
+    for (i, realm) in realms.iter().enumerate() {
+        res.push_str(&format!("{}. {}\r\n", i + 1, realm.name));
+    }
+    res
+}
+
 #[derive(Debug, PartialEq)]
 pub enum AppErrors {
     PlayerDisconnected(PlayerId),
     TooManyConnections(Location),
     AIStructureError,
+    AIUnavailable,
 }
 
 impl Error for AppErrors {}
@@ -215,6 +376,7 @@ impl Display for AppErrors {
                 f.write_fmt(format_args!("To many connections to place {l:?}"))
             }
             AppErrors::AIStructureError => f.write_str("AI Structure Error"),
+            AppErrors::AIUnavailable => f.write_str("AI Unavailable"),
         }
     }
 }
@@ -226,32 +388,346 @@ mod filters {
             s.push_str("es");
             Ok(s)
         } else {
-            s.push_str("s");
+            s.push('s');
             Ok(s)
         }
     }
 }
 
 mod config {
-    use std::{path::PathBuf, sync::OnceLock};
+    use std::{collections::HashMap, path::PathBuf, sync::OnceLock};
 
     use serde::Deserialize;
 
+    use crate::{
+        digest::WebhookKind, generation::BackendKind, rules::GameRules, scripting::Trust,
+        state::StorageKind,
+    };
+
     #[derive(Debug, Deserialize)]
     #[serde(default, rename_all = "kebab-case")]
     pub struct SomnuscapeConfig {
         pub server_address: String,
+        /// Where browsers can connect over WebSockets, if anywhere
+        pub websocket_address: Option<String>,
+        /// How many commands a player can send at once before being rate limited
+        pub command_burst: u32,
+        /// How many commands a second a player can keep sending
+        pub commands_per_second: f64,
+        /// The most commands run for one player each tick, the rest wait their turn
+        pub commands_per_tick: usize,
         pub save_every_x_ticks: u64,
         pub ticks_per_second: f64,
         pub model_temperature: f32,
         pub tone_words: Vec<String>,
         pub tone_words_per_generation: usize,
+        /// Usernames that can always run admin commands, whatever their account's
+        /// role, so there's someone to make the first admins
+        pub admins: Vec<String>,
+        /// How many times the average death rate something needs before it's flagged
+        pub balance_outlier_factor: f64,
+        /// Set to false to only use the procedural fallback generators
+        pub ai_generation: bool,
+        /// Never contact an AI model, only replaying recorded fixtures and otherwise
+        /// generating procedurally. Also set by starting the server with `--offline`,
+        /// and always on when playing with `play --local`.
+        pub offline: bool,
+        /// Max requests sent to the AI backend before falling back, 0 for no limit
+        pub ai_request_budget: u64,
+        /// Keep the AI backend's replies on disk so the same request is only made once.
+        /// Cached replies are still used offline or once the request budget runs out.
+        pub generation_cache: bool,
+        /// Write every prompt sent to the AI backend and its reply to rotating files in
+        /// the state directory, with player names redacted, to look into generation quality
+        pub generation_capture: bool,
+        /// How big a capture file gets before it's rotated
+        pub generation_capture_bytes: u64,
+        /// How many rotated capture files are kept
+        pub generation_capture_files: usize,
+        /// Where worlds and accounts are saved, yaml files or a sqlite database. Anything
+        /// already saved as yaml is moved into the database when sqlite is picked.
+        pub storage: StorageKind,
+        /// Which AI service generates content and how to reach it
+        pub ai_backend: AIBackendConfig,
+        /// Directory of handcrafted area files merged into the world at startup
+        pub areas_dir: String,
+        /// Whether to add the built in starting village to the world
+        pub starting_village: bool,
+        /// Names of places new characters can start in, one is picked at random
+        pub spawn_points: Vec<String>,
+        /// How long making camp takes before the player is logged out
+        pub camp_seconds: f64,
+        /// How long reading a recall scroll takes, moving or fighting breaks it
+        pub recall_seconds: f64,
+        /// What shrines and inns charge for a recall scroll
+        pub recall_scroll_cost: u32,
+        /// How long after a fight players are kept in the world and can't log out
+        pub combat_lockout_seconds: f64,
+        /// Which set of game rules to use, one of the rule profiles
+        pub rule_profile: String,
+        /// The rule profiles realms can pick from by name. Starts with casual, classic
+        /// and hardcore, setting it replaces those.
+        pub rule_profiles: HashMap<String, GameRules>,
+        /// Separate worlds hosted by this server, players pick one at login
+        pub realms: Vec<RealmConfig>,
+        /// How long each dream cycle lasts before the world resets, 0 to never reset
+        pub dream_cycle_hours: f64,
+        /// Fraction of each character's items kept through a world reset
+        pub reset_wealth_carryover: f64,
+        /// How long the marks left by a fight stay in a place's description
+        pub battle_scar_seconds: f64,
+        /// How long a corpse lasts before it crumbles and spills what it held
+        pub corpse_seconds: f64,
+        /// How long players have to wait between gathering ingredients
+        pub gather_seconds: f64,
+        /// How long cooking, brewing, crafting and coating a weapon take
+        pub craft_seconds: f64,
+        /// How long characters carrying more than they can manage have to catch
+        /// their breath between moves
+        pub burdened_move_seconds: f64,
+        /// How long enchanting and engraving take
+        pub enchant_seconds: f64,
+        /// How many hits a coat of poison lasts for
+        pub coating_charges: u32,
+        /// How long poison from a coated weapon lasts
+        pub poison_seconds: f64,
+        /// Gold taken from anyone caught carrying a poisoned weapon in a village
+        pub poison_fine: u32,
+        /// Words that can't appear in names players give to places
+        pub blocked_words: Vec<String>,
+        /// How often to check whether the world needs to grow
+        pub expansion_check_seconds: f64,
+        /// Unexplored overworld places to keep ahead of players, more are
+        /// generated once they've explored enough. Never fewer than one per player online.
+        pub min_unexplored_locales: usize,
+        /// The overworld stops growing once it has this many places
+        pub max_overworld_locales: usize,
+        /// How long vendors need before they'll haggle with the same player again
+        pub haggle_seconds: f64,
+        /// How long an NPC needs after a gift before another one wins them over any more
+        pub gift_seconds: f64,
+        /// How long a player waits between filing bug and typo reports
+        pub report_seconds: f64,
+        /// How often village shops stock back up on what's been bought
+        pub shop_restock_hours: f64,
+        /// Gold it costs to rent a market stall
+        pub stall_rent: u32,
+        /// How long one payment of rent keeps a stall open
+        pub stall_rent_hours: f64,
+        /// How often a passing trader stops by each open stall and maybe buys something
+        pub stall_trader_seconds: f64,
+        /// How often NPC artisans look over the work orders and maybe make something for one
+        pub artisan_seconds: f64,
+        /// The share of each market stall sale the village council takes
+        pub market_tax: f64,
+        /// How often village councils collect tribute, pay their guards and plan festivals
+        pub council_seconds: f64,
+        /// Gold each explored dungeon next to a village pays its council every meeting
+        pub dungeon_tribute: u32,
+        /// Gold each council pays its guards every meeting
+        pub guard_wages: u32,
+        /// How long festival decorations stay up
+        pub festival_seconds: f64,
+        /// How many events each place remembers, who came and went and what was said
+        pub place_history_length: usize,
+        /// How many parties of NPC adventurers roam each realm
+        pub adventurer_parties: usize,
+        /// How often each adventurer party does something, moving on, fighting or chatting
+        pub party_seconds: f64,
+        /// How many real minutes a day in the world lasts
+        pub day_minutes: f64,
+        /// Roughly how many real minutes a weather front hangs over a region
+        pub weather_minutes: f64,
+        /// Where to post a digest of each realm's day, if anywhere
+        pub digest: Option<DigestConfig>,
+        /// When admins are warned something's wrong, alerts are also posted to the digest webhook
+        pub alerts: AlertConfig,
+        /// How often the economy is audited for gold and items appearing or
+        /// vanishing, 0 to only audit when an admin asks
+        pub audit_seconds: f64,
+        /// How many times in a row a script can fail before it's rolled back to
+        /// its previous version
+        pub script_failures_before_rollback: u32,
+        /// How far scripts are trusted by what they're attached to, like
+        /// `commands/emote.lua: builder`. Scripts not listed are core.
+        pub script_trust: HashMap<String, Trust>,
+    }
+
+    #[derive(Debug, Clone, Deserialize)]
+    #[serde(default, rename_all = "kebab-case")]
+    pub struct AlertConfig {
+        /// How often the readings are checked against the limits
+        pub check_seconds: f64,
+        /// The longest ticks can take on average, in milliseconds
+        pub slow_tick_ms: f64,
+        /// The share of generation requests that can fail
+        pub generation_failure_rate: f64,
+        /// How many world saves can fail between checks
+        pub save_failures: u64,
+        /// How many players can connect between checks
+        pub connections: u32,
+        /// How often admins are reminded of alerts nobody's acknowledged
+        pub remind_minutes: f64,
+    }
+
+    impl Default for AlertConfig {
+        fn default() -> Self {
+            Self {
+                check_seconds: 60.0,
+                slow_tick_ms: 40.0,
+                generation_failure_rate: 0.5,
+                save_failures: 0,
+                connections: 30,
+                remind_minutes: 15.0,
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Deserialize)]
+    #[serde(default, rename_all = "kebab-case")]
+    pub struct DigestConfig {
+        /// The webhook to post to
+        pub url: String,
+        /// Discord, Slack or plain JSON for anything else
+        pub kind: WebhookKind,
+        /// How many real hours each digest covers
+        pub hours: f64,
+    }
+
+    impl Default for DigestConfig {
+        fn default() -> Self {
+            Self {
+                url: String::new(),
+                kind: WebhookKind::default(),
+                hours: 24.0,
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    pub struct RealmConfig {
+        pub name: String,
+        /// Where the realm's world is saved, inside the state directory. Defaults to
+        /// the realm's name so realms don't save over each other.
+        #[serde(default)]
+        pub save_dir: String,
+        /// An extra address that connects straight to this realm
+        #[serde(default)]
+        pub server_address: Option<String>,
+        /// Overrides the server wide rule profile for this realm
+        #[serde(default)]
+        pub rule_profile: Option<String>,
+    }
+
+    #[derive(Debug, Clone, Deserialize)]
+    #[serde(default, rename_all = "kebab-case")]
+    pub struct AIBackendConfig {
+        pub kind: BackendKind,
+        pub model: String,
+        /// Where the API is served, leave empty for the backend's usual address
+        pub base_url: String,
+        /// Sent with each request if set, the SOMNUSCAPE_API_KEY environment variable is used otherwise
+        pub api_key: Option<String>,
+        /// A YAML file of recorded responses. The replay backend and offline servers play
+        /// them back, any other backend records what it says into the file as it goes.
+        pub fixtures: Option<String>,
+    }
+
+    impl AIBackendConfig {
+        pub fn api_key(&self) -> Option<String> {
+            self.api_key
+                .clone()
+                .or_else(|| std::env::var("SOMNUSCAPE_API_KEY").ok())
+        }
+    }
+
+    impl Default for AIBackendConfig {
+        fn default() -> Self {
+            Self {
+                kind: BackendKind::Ollama,
+                model: "llama3:latest".into(),
+                base_url: String::new(),
+                api_key: None,
+                fixtures: None,
+            }
+        }
+    }
+
+    impl RealmConfig {
+        pub fn rule_profile(&self) -> &str {
+            self.rule_profile.as_deref().unwrap_or(&get().rule_profile)
+        }
+    }
+
+    impl SomnuscapeConfig {
+        pub fn seconds_to_ticks(&self, seconds: f64) -> u64 {
+            (seconds * self.ticks_per_second).ceil() as u64
+        }
+
+        /// The configured realms, or a single realm saved in the state directory if there are none
+        pub fn realms(&self) -> Vec<RealmConfig> {
+            if self.realms.is_empty() {
+                vec![RealmConfig {
+                    name: "Somnuscape".into(),
+                    save_dir: String::new(),
+                    server_address: None,
+                    rule_profile: None,
+                }]
+            } else {
+                self.realms
+                    .iter()
+                    .cloned()
+                    .map(|mut realm| {
+                        if realm.save_dir.trim().is_empty() {
+                            realm.save_dir = realm.name.clone();
+                        }
+                        realm
+                    })
+                    .collect()
+            }
+        }
+
+        /// Checks for settings that can't work, so the server refuses to start
+        /// rather than going wrong once it's running
+        pub fn validate(&self) -> anyhow::Result<()> {
+            let realms = self.realms();
+            for (i, realm) in realms.iter().enumerate() {
+                for other in &realms[..i] {
+                    if realm.name.eq_ignore_ascii_case(&other.name) {
+                        anyhow::bail!("There's more than one realm called {}", realm.name);
+                    }
+                    if realm.save_dir == other.save_dir {
+                        anyhow::bail!(
+                            "Realms {} and {} both save to '{}'",
+                            other.name,
+                            realm.name,
+                            realm.save_dir
+                        );
+                    }
+                }
+            }
+            for realm in &realms {
+                let profile = realm.rule_profile.as_deref().unwrap_or(&self.rule_profile);
+                if !self.rule_profiles.contains_key(profile) {
+                    anyhow::bail!(
+                        "Realm {} uses the rule profile '{profile}', which isn't one of the rule profiles",
+                        realm.name
+                    );
+                }
+            }
+            crate::generation::check_backend(self)
+        }
     }
 
     impl Default for SomnuscapeConfig {
         fn default() -> Self {
             Self {
                 server_address: "0.0.0.0:5000".into(),
+                websocket_address: None,
+                command_burst: 10,
+                commands_per_second: 4.0,
+                commands_per_tick: 2,
                 model_temperature: 0.9,
                 tone_words: vec![
                     "mystical".into(),
@@ -264,6 +740,65 @@ mod config {
                 tone_words_per_generation: 2,
                 save_every_x_ticks: 200,
                 ticks_per_second: 20.0,
+                admins: Vec::new(),
+                balance_outlier_factor: 2.0,
+                ai_generation: true,
+                offline: false,
+                ai_request_budget: 0,
+                generation_cache: true,
+                generation_capture: false,
+                generation_capture_bytes: 1_000_000,
+                generation_capture_files: 4,
+                storage: StorageKind::Yaml,
+                ai_backend: AIBackendConfig::default(),
+                areas_dir: "areas".into(),
+                starting_village: true,
+                spawn_points: vec!["Village Square".into()],
+                camp_seconds: 30.0,
+                recall_seconds: 10.0,
+                recall_scroll_cost: 10,
+                combat_lockout_seconds: 10.0,
+                rule_profile: "classic".into(),
+                rule_profiles: GameRules::built_in_profiles(),
+                realms: Vec::new(),
+                dream_cycle_hours: 0.0,
+                reset_wealth_carryover: 0.1,
+                battle_scar_seconds: 1800.0,
+                corpse_seconds: 3600.0,
+                gather_seconds: 20.0,
+                craft_seconds: 4.0,
+                burdened_move_seconds: 3.0,
+                enchant_seconds: 8.0,
+                coating_charges: 3,
+                poison_seconds: 60.0,
+                poison_fine: 10,
+                blocked_words: Vec::new(),
+                expansion_check_seconds: 30.0,
+                min_unexplored_locales: 2,
+                max_overworld_locales: 40,
+                haggle_seconds: 30.0,
+                gift_seconds: 300.0,
+                report_seconds: 60.0,
+                shop_restock_hours: 6.0,
+                stall_rent: 20,
+                stall_rent_hours: 24.0,
+                stall_trader_seconds: 300.0,
+                artisan_seconds: 600.0,
+                market_tax: 0.1,
+                council_seconds: 600.0,
+                dungeon_tribute: 5,
+                guard_wages: 4,
+                festival_seconds: 3600.0,
+                place_history_length: 50,
+                adventurer_parties: 2,
+                party_seconds: 8.0,
+                day_minutes: 60.0,
+                weather_minutes: 20.0,
+                digest: None,
+                alerts: AlertConfig::default(),
+                audit_seconds: 600.0,
+                script_failures_before_rollback: 5,
+                script_trust: HashMap::new(),
             }
         }
     }
@@ -273,14 +808,55 @@ mod config {
 
         CONFIG.get_or_init(|| {
             let p: PathBuf = "config.yaml".into();
-            if p.try_exists().unwrap_or_default() {
+            let mut config: SomnuscapeConfig = if p.try_exists().unwrap_or_default() {
                 std::fs::read_to_string(p)
-                    .and_then(|y| Ok(serde_yaml::from_str(&y)))
+                    .map(|y| serde_yaml::from_str(&y))
                     .expect("Could not read config")
                     .expect("Could not deserialize config")
             } else {
                 SomnuscapeConfig::default()
-            }
+            };
+            config.offline |= std::env::args().any(|a| a == "--offline" || a == "--local");
+            config
         })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn banned_players_cant_log_in() {
+        let player = PlayerId::new_random();
+        let account = PlayerAccount {
+            username: "bob".into(),
+            password: Password::hash("hunter2").unwrap(),
+            color: true,
+            width: None,
+            compress: true,
+            role: Role::Player,
+            banned: true,
+        };
+        let path = std::env::temp_dir().join(format!(
+            "somnuscape-login-{}/accounts.yaml",
+            std::process::id()
+        ));
+        let accounts = AccountStorage::at(path, HashMap::from([(player, account)]));
+
+        let mut state = ConnectionState::Unauthorized;
+        state
+            .handle_login("Bob".into(), &accounts, &[])
+            .await
+            .unwrap();
+        assert!(state.wants_password());
+        let reply = state
+            .handle_login("hunter2".into(), &accounts, &[])
+            .await
+            .unwrap();
+        assert!(reply.starts_with("This account has been banned"));
+        assert!(matches!(state, ConnectionState::Unauthorized));
+    }
+}
@@ -0,0 +1,366 @@
+//! Inline style tags for messages sent to players. The engine wraps text in tags
+//! like `<title>Village Square</>` and the connection handler turns them into
+//! ANSI colours, or strips them for players who've turned colour off. Rendered
+//! messages are then wrapped to fit the player's screen. Generated text comes
+//! in as markdown, so that's turned into tags first.
+
+use std::fmt::Display;
+
+const RESET: &str = "\x1b[0m";
+/// The width messages wrap to when the client doesn't say and the player hasn't picked one
+pub const DEFAULT_WIDTH: usize = 80;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    /// Place names at the top of a description
+    Title,
+    /// Directions and where they lead
+    Exit,
+    /// Other players and creatures
+    Name,
+    /// Hits, misses and knockouts
+    Combat,
+    /// Healing, discoveries and other good news
+    Good,
+    /// Warnings and refusals
+    Warn,
+    /// Bold text in generated writing
+    Strong,
+    /// Emphasised text in generated writing, underlined since few clients do italics
+    Emphasis,
+    /// What ghosts see, dimmed
+    Faint,
+}
+
+impl Style {
+    const ALL: [Style; 9] = [
+        Style::Title,
+        Style::Exit,
+        Style::Name,
+        Style::Combat,
+        Style::Good,
+        Style::Warn,
+        Style::Strong,
+        Style::Emphasis,
+        Style::Faint,
+    ];
+
+    fn tag(self) -> &'static str {
+        match self {
+            Style::Title => "<title>",
+            Style::Exit => "<exit>",
+            Style::Name => "<name>",
+            Style::Combat => "<combat>",
+            Style::Good => "<good>",
+            Style::Warn => "<warn>",
+            Style::Strong => "<strong>",
+            Style::Emphasis => "<em>",
+            Style::Faint => "<faint>",
+        }
+    }
+
+    fn ansi(self) -> &'static str {
+        match self {
+            Style::Title => "\x1b[1;36m",
+            Style::Exit => "\x1b[32m",
+            Style::Name => "\x1b[1;33m",
+            Style::Combat => "\x1b[31m",
+            Style::Good => "\x1b[1;32m",
+            Style::Warn => "\x1b[35m",
+            Style::Strong => "\x1b[1m",
+            Style::Emphasis => "\x1b[4m",
+            Style::Faint => "\x1b[2m",
+        }
+    }
+}
+
+/// Wraps text in a style's tags
+pub fn styled(style: Style, text: impl Display) -> String {
+    format!("{}{text}</>", style.tag())
+}
+
+/// Doubles every `<` in text players wrote, so what they say shows as typed
+/// and can't pick up styles or pass itself off as the game
+pub fn escape(text: &str) -> String {
+    text.replace('<', "<<")
+}
+
+/// Turns style tags into ANSI escapes, or removes them if colour is off.
+/// Anything that looks like a tag but isn't one is left alone, and a doubled
+/// `<` from [`escape`] shows as one.
+pub fn render(msg: &str, color: bool) -> String {
+    let mut res = String::with_capacity(msg.len());
+    let mut rest = msg;
+
+    while let Some(start) = rest.find('<') {
+        res.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        if rest.starts_with("<<") {
+            res.push('<');
+            rest = &rest[2..];
+            continue;
+        }
+        let replacement = if rest.starts_with("</>") {
+            Some(("</>".len(), RESET))
+        } else {
+            Style::ALL
+                .iter()
+                .find(|s| rest.starts_with(s.tag()))
+                .map(|s| (s.tag().len(), s.ansi()))
+        };
+
+        match replacement {
+            Some((len, ansi)) => {
+                if color {
+                    res.push_str(ansi);
+                }
+                rest = &rest[len..];
+            }
+            None => {
+                res.push('<');
+                rest = &rest[1..];
+            }
+        }
+    }
+    res.push_str(rest);
+
+    res
+}
+
+/// Turns the markdown models like to write into style tags. Bold and emphasis
+/// keep their styling, list items get bullets, and headers, quotes, code,
+/// links and rules are reduced to their text.
+pub fn from_markdown(text: &str) -> String {
+    let mut lines = Vec::new();
+    for line in text.trim().lines() {
+        let body = line.trim_start();
+        let indent = &line[..line.len() - body.len()];
+        if body.starts_with("```") || is_rule(body) {
+            continue;
+        }
+
+        let body = match body.trim_start_matches('#') {
+            heading if heading.len() < body.len() && heading.starts_with(' ') => heading.trim(),
+            _ => body,
+        };
+        let body = body.strip_prefix('>').map_or(body, str::trim_start);
+        let (bullet, body) = match ["- ", "* ", "+ "].iter().find_map(|b| body.strip_prefix(b)) {
+            Some(item) => ("• ", item),
+            None => ("", body),
+        };
+        lines.push(format!("{indent}{bullet}{}", inline_markdown(body)));
+    }
+
+    lines.join("\n")
+}
+
+/// Generated text with any markdown taken out, for names and the like
+pub fn strip_markdown(text: &str) -> String {
+    render(&from_markdown(text), false)
+}
+
+/// Whether the line is a horizontal rule, like '---' or '* * *'
+fn is_rule(line: &str) -> bool {
+    let marks: Vec<_> = line.chars().filter(|c| !c.is_whitespace()).collect();
+    marks.len() >= 3 && ['-', '*', '_'].iter().any(|m| marks.iter().all(|c| c == m))
+}
+
+fn inline_markdown(text: &str) -> String {
+    let mut res = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find(['*', '_', '`', '[']) {
+        res.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        match markdown_span(rest, res.chars().last()) {
+            Some((span, len)) => {
+                res.push_str(&span);
+                rest = &rest[len..];
+            }
+            None => {
+                let c = rest.chars().next().unwrap();
+                res.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    res.push_str(rest);
+
+    res
+}
+
+/// The span of markdown the text starts with, if it's closed, and how long it was
+fn markdown_span(text: &str, before: Option<char>) -> Option<(String, usize)> {
+    if let Some(rest) = text.strip_prefix('[') {
+        let close = rest.find("](")?;
+        let end = close + rest[close..].find(')')?;
+        return Some((inline_markdown(&rest[..close]), end + 2));
+    }
+    if let Some(rest) = text.strip_prefix('`') {
+        let end = rest.find('`')?;
+        return Some((rest[..end].to_string(), end + 2));
+    }
+    // Underscores inside words, like in snake_case, aren't emphasis
+    if text.starts_with('_') && before.is_some_and(char::is_alphanumeric) {
+        return None;
+    }
+
+    for (marker, style) in [
+        ("**", Style::Strong),
+        ("__", Style::Strong),
+        ("*", Style::Emphasis),
+        ("_", Style::Emphasis),
+    ] {
+        let Some(rest) = text.strip_prefix(marker) else {
+            continue;
+        };
+        let inner = &rest[..rest.find(marker)?];
+        if inner.is_empty() || inner.starts_with(' ') || inner.ends_with(' ') {
+            return None;
+        }
+        return Some((
+            styled(style, inline_markdown(inner)),
+            inner.len() + marker.len() * 2,
+        ));
+    }
+
+    None
+}
+
+/// Wraps each line of a rendered message to fit the width, counting only the
+/// characters that show. Blank lines between paragraphs are kept, and list
+/// items and indented lines keep their indent as they wrap.
+pub fn wrap(msg: &str, width: usize) -> String {
+    msg.split('\n')
+        .map(|line| wrap_line(line, width))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn wrap_line(line: &str, width: usize) -> String {
+    if visible_len(line) <= width {
+        return line.to_string();
+    }
+
+    let body = line.trim_start();
+    let lead = &line[..line.len() - body.len()];
+    let indent = " ".repeat(lead.chars().count() + bullet_len(body));
+
+    let mut res = lead.to_string();
+    let mut len = visible_len(lead);
+    let mut at_start = true;
+    for (gap, word) in words(body) {
+        let word_len = visible_len(word);
+        let gap_len = visible_len(gap);
+        if !at_start && len + gap_len + word_len > width {
+            res.push('\n');
+            res.push_str(&indent);
+            len = indent.len();
+        } else {
+            res.push_str(gap);
+            len += gap_len;
+        }
+        res.push_str(word);
+        len += word_len;
+        at_start = false;
+    }
+
+    res
+}
+
+/// Splits text into words, each with the spaces that came before it so
+/// wrapped lines keep their spacing
+fn words(text: &str) -> impl Iterator<Item = (&str, &str)> {
+    let mut rest = text;
+    std::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+        let start = rest.find(|c| c != ' ').unwrap_or(rest.len());
+        let end = rest[start..].find(' ').map_or(rest.len(), |e| start + e);
+        let (gap, word) = (&rest[..start], &rest[start..end]);
+        rest = &rest[end..];
+        Some((gap, word))
+    })
+}
+
+/// How wide a list marker at the start of a line is, like '- ' or '12. '
+fn bullet_len(line: &str) -> usize {
+    if ["- ", "* ", "• "].iter().any(|b| line.starts_with(b)) {
+        return 2;
+    }
+
+    let digits = line.chars().take_while(char::is_ascii_digit).count();
+    if digits > 0 && line[digits..].starts_with(". ") {
+        digits + 2
+    } else {
+        0
+    }
+}
+
+/// How many characters of the text show, leaving out ANSI escapes
+fn visible_len(text: &str) -> usize {
+    let mut len = 0;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            chars.by_ref().find(|c| c.is_ascii_alphabetic());
+        } else if c != '\r' {
+            len += 1;
+        }
+    }
+    len
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn renders_and_strips_tags() {
+        let msg = format!("You move to {} <3", styled(Style::Title, "the Inn"));
+
+        assert_eq!(render(&msg, false), "You move to the Inn <3");
+        assert_eq!(
+            render(&msg, true),
+            "You move to \x1b[1;36mthe Inn\x1b[0m <3"
+        );
+
+        let chat = format!("Ada says, \"{}\"", escape("<title>hi</> <<3"));
+        assert_eq!(render(&chat, true), "Ada says, \"<title>hi</> <<3\"");
+    }
+
+    #[test]
+    fn renders_markdown() {
+        let md = "## The Old Mill\n\nA **creaking** wheel, see [the map](http://x) *if* you dare.\n\n* flour_sacks\n- `rope`\n---\n> 2 * 3 is six";
+        assert_eq!(
+            from_markdown(md),
+            "The Old Mill\n\nA <strong>creaking</> wheel, see the map <em>if</> you dare.\n\n• flour_sacks\n• rope\n2 * 3 is six"
+        );
+        assert_eq!(strip_markdown("**Rusty _Sword_**"), "Rusty Sword");
+        assert_eq!(render(&from_markdown("__Run__"), true), "\x1b[1mRun\x1b[0m");
+    }
+
+    #[test]
+    fn wraps_to_width() {
+        let msg =
+            "A long line of words that goes on\n\n- an item that wraps over\n  indented text here";
+        assert_eq!(
+            wrap(msg, 16),
+            "A long line of\nwords that goes\non\n\n- an item that\n  wraps over\n  indented text\n  here"
+        );
+
+        let colored = render(&styled(Style::Title, "Village Square"), true);
+        assert_eq!(wrap(&colored, 14), colored);
+        assert_eq!(
+            wrap("12. numbered list item", 13),
+            "12. numbered\n    list item"
+        );
+        assert_eq!(
+            wrap("Sword     10 gold\nA shield  20 gold each", 17),
+            "Sword     10 gold\nA shield  20 gold\neach"
+        );
+    }
+}
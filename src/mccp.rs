@@ -0,0 +1,191 @@
+//! MCCP2, compressing what's sent to telnet clients that support it. Generated
+//! descriptions are long and compress well, so this saves a lot of bandwidth.
+//! Once the client agrees, everything after the start marker is one zlib
+//! stream, synced after each event so the client can show it straight away.
+
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+use bytes::BytesMut;
+use flate2::{Compress, Compression, FlushCompress, Status};
+use nectar::{event::TelnetEvent, TelnetCodec};
+use tokio_util::codec::{Decoder, Encoder};
+
+/// Bytes of telnet output before and after compression, across every connection
+#[derive(Debug, Default)]
+pub struct CompressionStats {
+    raw: AtomicU64,
+    compressed: AtomicU64,
+    connections: AtomicUsize,
+}
+
+impl CompressionStats {
+    /// A short summary for admins
+    pub fn report(&self) -> String {
+        let raw = self.raw.load(Ordering::Relaxed);
+        let compressed = self.compressed.load(Ordering::Relaxed);
+        let connections = self.connections.load(Ordering::Relaxed);
+        let saved = match raw {
+            0 => 0.0,
+            _ => 100.0 * (1.0 - compressed as f64 / raw as f64),
+        };
+
+        format!(
+            "Compression\n\n{connections} connections compressing\n{raw} bytes sent as {compressed}, {saved:.1}% saved"
+        )
+    }
+}
+
+static STATS: CompressionStats = CompressionStats {
+    raw: AtomicU64::new(0),
+    compressed: AtomicU64::new(0),
+    connections: AtomicUsize::new(0),
+};
+
+pub fn stats() -> &'static CompressionStats {
+    &STATS
+}
+
+/// The telnet codec with output optionally compressed
+#[derive(Debug)]
+pub struct MccpCodec {
+    telnet: TelnetCodec,
+    compressor: Option<Compress>,
+    /// Set when compression's been turned off, the stream's ended before the next event
+    finishing: bool,
+}
+
+impl MccpCodec {
+    pub fn new(telnet: TelnetCodec) -> Self {
+        Self {
+            telnet,
+            compressor: None,
+            finishing: false,
+        }
+    }
+
+    pub fn is_compressing(&self) -> bool {
+        self.compressor.is_some() && !self.finishing
+    }
+
+    /// Compresses everything encoded from now on, call once the start marker's been sent
+    pub fn start(&mut self) {
+        if self.compressor.is_none() {
+            STATS.connections.fetch_add(1, Ordering::Relaxed);
+        }
+        self.compressor = Some(Compress::new(Compression::default(), true));
+        self.finishing = false;
+    }
+
+    /// Ends the compressed stream before the next event, which is sent as is
+    pub fn stop(&mut self) {
+        self.finishing = self.compressor.is_some();
+    }
+
+    fn compress(&mut self, input: &[u8], flush: FlushCompress, dst: &mut BytesMut) {
+        let compressor = self.compressor.as_mut().unwrap();
+        let mut out = Vec::with_capacity(input.len() / 2 + 64);
+        let mut consumed = 0;
+        loop {
+            let before = compressor.total_in();
+            let status = compressor.compress_vec(&input[consumed..], &mut out, flush);
+            consumed += (compressor.total_in() - before) as usize;
+
+            let full = out.len() == out.capacity();
+            match status {
+                Ok(Status::StreamEnd) => break,
+                Ok(_) if consumed == input.len() && !full => break,
+                Ok(_) => out.reserve(out.capacity().max(64)),
+                Err(e) => {
+                    tracing::error!("Compression failed: {e}");
+                    break;
+                }
+            }
+        }
+
+        STATS.raw.fetch_add(input.len() as u64, Ordering::Relaxed);
+        STATS
+            .compressed
+            .fetch_add(out.len() as u64, Ordering::Relaxed);
+        dst.extend_from_slice(&out);
+    }
+}
+
+impl Drop for MccpCodec {
+    fn drop(&mut self) {
+        if self.compressor.is_some() {
+            STATS.connections.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Decoder for MccpCodec {
+    type Item = TelnetEvent;
+    type Error = <TelnetCodec as Decoder>::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.telnet.decode(src)
+    }
+}
+
+impl Encoder<TelnetEvent> for MccpCodec {
+    type Error = <TelnetCodec as Encoder<TelnetEvent>>::Error;
+
+    fn encode(&mut self, event: TelnetEvent, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        if self.finishing {
+            self.compress(&[], FlushCompress::Finish, dst);
+            self.compressor = None;
+            self.finishing = false;
+            STATS.connections.fetch_sub(1, Ordering::Relaxed);
+        }
+        if self.compressor.is_none() {
+            return self.telnet.encode(event, dst);
+        }
+
+        let mut raw = BytesMut::new();
+        self.telnet.encode(event, &mut raw)?;
+        self.compress(&raw, FlushCompress::Sync, dst);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Read;
+
+    use flate2::read::ZlibDecoder;
+
+    use super::*;
+
+    #[test]
+    fn compresses_between_start_and_stop() {
+        let mut codec = MccpCodec::new(TelnetCodec::new(1024));
+        let mut out = BytesMut::new();
+        codec
+            .encode(TelnetEvent::Message("plain".into()), &mut out)
+            .unwrap();
+        assert_eq!(&out[..], b"plain\r\n");
+
+        codec.start();
+        let mut compressed = BytesMut::new();
+        let description = "The mist rolls over the moor. ".repeat(20);
+        codec
+            .encode(TelnetEvent::Message(description.clone()), &mut compressed)
+            .unwrap();
+        assert!(codec.is_compressing());
+        assert!(compressed.len() < description.len() / 4);
+
+        codec.stop();
+        codec
+            .encode(TelnetEvent::Message("plain again".into()), &mut compressed)
+            .unwrap();
+        assert!(!codec.is_compressing());
+        assert!(compressed.ends_with(b"plain again\r\n"));
+
+        let stream = &compressed[..compressed.len() - b"plain again\r\n".len()];
+        let mut decoded = String::new();
+        ZlibDecoder::new(stream)
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, format!("{description}\r\n"));
+    }
+}
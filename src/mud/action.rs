@@ -0,0 +1,76 @@
+//! Commands that take a while, like crafting or enchanting. The command runs
+//! once the action's done, and getting hurt knocks the character out of it.
+
+/// How many progress updates the player gets over an action
+const PROGRESS_STEPS: u64 = 4;
+
+/// Something a character is in the middle of doing
+#[derive(Debug, Clone, PartialEq)]
+pub struct Action {
+    /// The command that runs once the action's done
+    pub line: String,
+    /// What the character is doing, like "cooking"
+    pub verb: String,
+    pub started: u64,
+    pub until: u64,
+}
+
+impl Action {
+    pub fn new(line: String, verb: &str, current_tick: u64, ticks: u64) -> Self {
+        Self {
+            line,
+            verb: verb.to_string(),
+            started: current_tick,
+            until: current_tick + ticks.max(1),
+        }
+    }
+
+    pub fn is_done(&self, current_tick: u64) -> bool {
+        current_tick >= self.until
+    }
+
+    /// A note on how it's going, when the action passes one of its progress marks
+    pub fn progress(&self, current_tick: u64) -> Option<String> {
+        let length = self.until - self.started;
+        let step = |tick: u64| (tick.saturating_sub(self.started) * PROGRESS_STEPS) / length;
+        let now = step(current_tick);
+        if now == 0 || now >= PROGRESS_STEPS || now == step(current_tick.saturating_sub(1)) {
+            return None;
+        }
+
+        Some(format!(
+            "You keep {}... {}%",
+            self.verb,
+            now * 100 / PROGRESS_STEPS
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reports_progress() {
+        let action = Action::new("cook barley".into(), "cooking", 100, 8);
+        let notes: Vec<_> = (101..=108).filter_map(|t| action.progress(t)).collect();
+        assert_eq!(
+            notes,
+            [
+                "You keep cooking... 25%",
+                "You keep cooking... 50%",
+                "You keep cooking... 75%"
+            ]
+        );
+        assert!(!action.is_done(107));
+        assert!(action.is_done(108));
+
+        let quick = Action::new("cook barley".into(), "cooking", 100, 0);
+        assert!(quick.is_done(101));
+        assert_eq!(quick.progress(101), None);
+        assert_eq!(
+            Action::new("cook barley".into(), "cooking", 0, 8).progress(0),
+            None
+        );
+    }
+}
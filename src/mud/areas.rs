@@ -0,0 +1,402 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+
+use anyhow::{anyhow, bail, Result};
+use serde::Deserialize;
+
+use super::world::{Direction, Location, Place, World};
+
+/// A hand authored area, loaded from YAML and merged into the generated world.
+/// Exits are two way, declaring the return exit is optional but it must match.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct AreaFile {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// The name of an existing place to attach to, without one the area
+    /// becomes a new overworld locale
+    #[serde(default)]
+    pub anchor: Option<String>,
+    /// Which way from the anchor leads into the area
+    #[serde(default = "default_anchor_direction")]
+    pub anchor_direction: Direction,
+    pub entrance: String,
+    pub rooms: Vec<AreaRoom>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct AreaRoom {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub exits: HashMap<Direction, String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+fn default_anchor_direction() -> Direction {
+    Direction::Down
+}
+
+impl AreaFile {
+    /// The tag put on every place made from this area
+    pub fn tag(&self) -> String {
+        format!("area:{}", self.name)
+    }
+
+    /// Checks the area's rooms and exits agree with each other,
+    /// returning every conflict found
+    pub fn validate(&self) -> Vec<String> {
+        let mut conflicts = Vec::new();
+        let mut names = HashSet::new();
+
+        for room in &self.rooms {
+            if !names.insert(room.name.as_str()) {
+                conflicts.push(format!("Room '{}' is defined more than once", room.name));
+            }
+        }
+
+        if !names.contains(self.entrance.as_str()) {
+            conflicts.push(format!("Entrance '{}' is not a room", self.entrance));
+        }
+
+        let exits: HashMap<_, _> = self
+            .rooms
+            .iter()
+            .map(|r| (r.name.as_str(), &r.exits))
+            .collect();
+
+        for room in &self.rooms {
+            for (dir, target) in &room.exits {
+                if target == &room.name {
+                    conflicts.push(format!("'{}' has an exit to itself", room.name));
+                    continue;
+                }
+
+                let Some(target_exits) = exits.get(target.as_str()) else {
+                    conflicts.push(format!(
+                        "'{}' exits {} to unknown room '{target}'",
+                        room.name,
+                        dir.name()
+                    ));
+                    continue;
+                };
+
+                match target_exits.get(&dir.reverse()) {
+                    Some(back) if back != &room.name => conflicts.push(format!(
+                        "'{}' exits {} to '{target}' but '{target}' exits {} to '{back}'",
+                        room.name,
+                        dir.name(),
+                        dir.reverse().name()
+                    )),
+                    _ => {}
+                }
+            }
+        }
+
+        // Every exit makes one back, so two rooms can't both leave the same way into a third
+        // and one room can't lead to another two ways
+        let mut sides = HashMap::new();
+        let mut links = HashSet::new();
+        for room in &self.rooms {
+            for (dir, target) in &room.exits {
+                if target == &room.name || !exits.contains_key(target.as_str()) {
+                    continue;
+                }
+                for (from, way, to) in [
+                    (room.name.as_str(), *dir, target.as_str()),
+                    (target.as_str(), dir.reverse(), room.name.as_str()),
+                ] {
+                    match sides.insert((from, way), to) {
+                        Some(other) if other != to => conflicts.push(format!(
+                            "'{from}' would exit {} to both '{other}' and '{to}'",
+                            way.name()
+                        )),
+                        _ => {}
+                    }
+                }
+                let pair = if room.name.as_str() < target.as_str() {
+                    (room.name.as_str(), target.as_str(), *dir)
+                } else {
+                    (target.as_str(), room.name.as_str(), dir.reverse())
+                };
+                links.insert(pair);
+            }
+        }
+        let mut linked = HashSet::new();
+        for (a, b, _) in &links {
+            if !linked.insert((a, b)) {
+                conflicts.push(format!("'{a}' and '{b}' are joined by more than one exit"));
+            }
+        }
+
+        conflicts.sort();
+        conflicts.dedup();
+        conflicts
+    }
+
+    /// Makes the area's places, returning the entrance location
+    fn build(&self) -> Result<(Location, HashMap<Location, Place>)> {
+        let tag = self.tag();
+        let mut name_to_location = HashMap::new();
+        let mut places = HashMap::new();
+
+        for room in &self.rooms {
+            let mut place = Place::new(room.name.clone(), room.description.clone());
+            place.tags.insert(tag.clone());
+            place.tags.extend(room.tags.iter().cloned());
+            name_to_location.insert(room.name.as_str(), place.location);
+            places.insert(place.location, place);
+        }
+
+        let location_of = |name: &str| {
+            name_to_location
+                .get(name)
+                .copied()
+                .ok_or_else(|| anyhow!("'{name}' is not a room"))
+        };
+        for room in &self.rooms {
+            let a = location_of(&room.name)?;
+            for (dir, target) in &room.exits {
+                let b = location_of(target)?;
+                if places[&a].is_connected(b) {
+                    continue;
+                }
+                for (from, way, to) in [(a, *dir, b), (b, dir.reverse(), a)] {
+                    let place = places.get_mut(&from).unwrap();
+                    if place.add_connection(way, to)? != way {
+                        bail!("'{}' already has an exit {}", place.name, way.name());
+                    }
+                }
+            }
+        }
+
+        Ok((location_of(&self.entrance)?, places))
+    }
+
+    /// Adds the area to the world, either at its anchor or as a new overworld locale.
+    /// Nothing's added if it conflicts with itself or the world, the error lists why.
+    pub fn merge_into(&self, world: &mut World) -> Result<()> {
+        let mut conflicts = self.validate();
+        let tag = self.tag();
+
+        if world.places.values().any(|p| p.tags.contains(&tag)) {
+            conflicts.push(format!("Area '{}' is already in the world", self.name));
+        }
+
+        let anchor = match &self.anchor {
+            Some(anchor_name) => {
+                let matches: Vec<_> = world
+                    .places
+                    .values()
+                    .filter(|p| &p.name == anchor_name)
+                    .collect();
+
+                match matches.as_slice() {
+                    [] => conflicts.push(format!("Anchor '{anchor_name}' does not exist")),
+                    [anchor] if anchor.connections().contains_key(&self.anchor_direction) => {
+                        conflicts.push(format!(
+                            "Anchor '{anchor_name}' already has an exit {}",
+                            self.anchor_direction.name()
+                        ))
+                    }
+                    [_] => {}
+                    _ => conflicts.push(format!("Anchor '{anchor_name}' is ambiguous")),
+                }
+
+                matches.first().map(|p| p.location)
+            }
+            None => None,
+        };
+
+        let entrance_exits = self
+            .rooms
+            .iter()
+            .find(|r| r.name == self.entrance)
+            .map(|r| &r.exits);
+        let back = self.anchor_direction.reverse();
+        if entrance_exits.is_some_and(|e| e.contains_key(&back)) {
+            conflicts.push(format!(
+                "Entrance '{}' needs its {} exit free to connect to the anchor",
+                self.entrance,
+                back.name()
+            ));
+        }
+
+        if !conflicts.is_empty() {
+            bail!(conflicts.join("; "));
+        }
+
+        let (entrance, mut rooms) = self.build()?;
+        let mut locale = None;
+        let (anchor_location, parent) = match anchor {
+            Some(anchor) => (anchor, world.places[&anchor].parent.or(Some(anchor))),
+            None => {
+                let mut place = Place::new(self.name.clone(), self.description.clone());
+                place.tags.insert(tag);
+                let location = place.location;
+                locale = Some(place);
+                (location, Some(location))
+            }
+        };
+        for room in rooms.values_mut() {
+            room.parent = parent;
+        }
+        rooms
+            .get_mut(&entrance)
+            .unwrap()
+            .add_connection(back, anchor_location)?;
+
+        // Only the world's touched once nothing else can go wrong
+        match locale {
+            Some(mut locale) => {
+                locale.add_connection(self.anchor_direction, entrance)?;
+                world.add_overworld_locale(locale);
+            }
+            None => {
+                world
+                    .places
+                    .get_mut(&anchor_location)
+                    .unwrap()
+                    .add_connection(self.anchor_direction, entrance)?;
+            }
+        }
+
+        world.place_changed(anchor_location);
+        for location in rooms.keys() {
+            world.place_changed(*location);
+        }
+        world.places.extend(rooms);
+
+        Ok(())
+    }
+}
+
+/// Loads every area file in a directory into the world, skipping
+/// any that are already present or conflict with what's there
+pub fn load_areas(world: &mut World, dir: &Path) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+
+    let mut paths: Vec<_> = entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|e| e == "yaml" || e == "yml"))
+        .collect();
+    paths.sort();
+
+    for path in paths {
+        let area: AreaFile = match std::fs::read_to_string(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|y| Ok(serde_yaml::from_str(&y)?))
+        {
+            Ok(area) => area,
+            Err(e) => {
+                tracing::error!("Could not read area file {}: {e}", path.display());
+                continue;
+            }
+        };
+
+        if world.places.values().any(|p| p.tags.contains(&area.tag())) {
+            continue;
+        }
+
+        match area.merge_into(world) {
+            Ok(()) => tracing::info!("Loaded handcrafted area {}", area.name),
+            Err(e) => tracing::error!(
+                "Could not load area {} from {}: {e:#}",
+                area.name,
+                path.display()
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const INN: &str = "
+name: Whispering Inn
+description: A crooked inn at the crossroads
+entrance: Common Room
+rooms:
+  - name: Common Room
+    description: Warm and loud
+    exits:
+      north: Kitchen
+      east: Guest Rooms
+  - name: Kitchen
+    exits:
+      south: Common Room
+  - name: Guest Rooms
+";
+
+    #[test]
+    fn merges_area() {
+        let area: AreaFile = serde_yaml::from_str(INN).unwrap();
+        assert!(area.validate().is_empty());
+
+        let mut world = World::default();
+        area.merge_into(&mut world).unwrap();
+
+        assert_eq!(world.overworld_locales.len(), 1);
+        assert_eq!(world.places.len(), 4);
+
+        let guest_rooms = world
+            .places
+            .values()
+            .find(|p| p.name == "Guest Rooms")
+            .unwrap();
+        let (dir, _) = guest_rooms.connections().iter().next().unwrap();
+        assert_eq!(*dir, Direction::West);
+
+        assert!(area.merge_into(&mut world).is_err());
+    }
+
+    #[test]
+    fn detects_conflicts() {
+        let mut area: AreaFile = serde_yaml::from_str(INN).unwrap();
+        area.rooms[1]
+            .exits
+            .insert(Direction::South, "Guest Rooms".into());
+        area.rooms[2].exits.insert(Direction::West, "Cellar".into());
+        area.anchor = Some("Nowhere".into());
+
+        let mut world = World::default();
+        let conflicts = area.merge_into(&mut world).unwrap_err().to_string();
+
+        assert!(conflicts.contains("Cellar"));
+        assert!(conflicts.contains("exits south"));
+        assert!(conflicts.contains("Nowhere"));
+        assert!(world.places.is_empty());
+    }
+
+    #[test]
+    fn detects_one_way_conflicts() {
+        let mut area: AreaFile = serde_yaml::from_str(INN).unwrap();
+        // Both lead north into the kitchen, which can only lead south back to one of them
+        area.rooms[2]
+            .exits
+            .insert(Direction::North, "Kitchen".into());
+        assert!(area
+            .validate()
+            .iter()
+            .any(|c| c.contains("'Kitchen' would exit south to both")));
+
+        let mut area: AreaFile = serde_yaml::from_str(INN).unwrap();
+        area.rooms[0]
+            .exits
+            .insert(Direction::West, "Guest Rooms".into());
+        assert!(area
+            .validate()
+            .iter()
+            .any(|c| c.contains("joined by more than one exit")));
+        assert!(area.merge_into(&mut World::default()).is_err());
+    }
+}
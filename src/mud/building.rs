@@ -0,0 +1,152 @@
+//! Hand-editing the world from inside it. Admins dig new places off the one
+//! they're standing in and link or unlink ways between places to extend or
+//! correct what was generated. Every edit marks the places it touched as
+//! changed, so they're saved with the rest of the world.
+
+use super::world::{Direction, Location, Place, World, VOID_TAG};
+
+/// Parses a direction by name, ignoring case
+pub fn direction_named(name: &str) -> Result<Direction, String> {
+    Direction::values()
+        .into_iter()
+        .find(|d| d.name().eq_ignore_ascii_case(name))
+        .ok_or_else(|| {
+            format!("{name} isn't a direction, try north, east, south, west, up or down")
+        })
+}
+
+/// Makes a new, empty place the given way from `from` and joins them both
+/// ways. The new place belongs to the same area as the one it was dug from.
+pub fn dig(
+    world: &mut World,
+    from: Location,
+    direction: Direction,
+    name: String,
+) -> Result<Location, String> {
+    let parent = editable(world, from)?.parent;
+    check_free(world, from, direction)?;
+
+    let mut place = Place::new(name, String::new());
+    place.parent = parent;
+    let location = place.location;
+    world.places.insert(location, place);
+    link(world, from, direction, location)?;
+    Ok(location)
+}
+
+/// Joins two places, `direction` leading from `from` to `to` and the reverse
+/// leading back
+pub fn link(
+    world: &mut World,
+    from: Location,
+    direction: Direction,
+    to: Location,
+) -> Result<(), String> {
+    if from == to {
+        return Err("A place can't lead to itself".to_string());
+    }
+    editable(world, from)?;
+    editable(world, to)?;
+    check_free(world, from, direction)?;
+    check_free(world, to, direction.reverse())?;
+
+    // Both sides are free, so neither picks a different direction
+    for (location, direction, other) in [(from, direction, to), (to, direction.reverse(), from)] {
+        let place = world.places.get_mut(&location).unwrap();
+        place
+            .add_connection(direction, other)
+            .map_err(|e| e.to_string())?;
+        world.place_changed(location);
+    }
+    Ok(())
+}
+
+/// Removes the way out of `from` in a direction and the way back, returning
+/// where it led
+pub fn unlink(world: &mut World, from: Location, direction: Direction) -> Result<Location, String> {
+    let to = world
+        .places
+        .get_mut(&from)
+        .ok_or("That place doesn't exist any more")?
+        .remove_connection(direction)
+        .ok_or_else(|| format!("There's no way {} from here", direction.name()))?;
+    world.place_changed(from);
+
+    if let Some(other) = world.places.get_mut(&to) {
+        if other.connections().get(&direction.reverse()) == Some(&from) {
+            other.remove_connection(direction.reverse());
+        }
+        world.place_changed(to);
+    }
+    Ok(to)
+}
+
+/// The place, if it can be built onto. The void is left alone so players
+/// waiting in it can't wander off into the unfinished world.
+fn editable(world: &World, location: Location) -> Result<&Place, String> {
+    let place = world
+        .places
+        .get(&location)
+        .ok_or("That place doesn't exist any more")?;
+    if place.tags.contains(VOID_TAG) {
+        return Err("The void can't be built onto".to_string());
+    }
+    Ok(place)
+}
+
+fn check_free(world: &World, location: Location, direction: Direction) -> Result<(), String> {
+    let place = &world.places[&location];
+    match place.connections().get(&direction) {
+        Some(to) => Err(format!(
+            "{} already leads {} to {}",
+            place.name,
+            direction.name(),
+            world
+                .places
+                .get(to)
+                .map_or("somewhere", |p| p.name.as_str())
+        )),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn digs_links_and_unlinks_both_ways() {
+        let mut world = World::default();
+        world.ensure_starting_village();
+        let start = world.spawn_location().unwrap();
+        world.take_changes();
+
+        let cellar = dig(&mut world, start, Direction::Down, "Cellar".into()).unwrap();
+        assert_eq!(world.places[&start].connections()[&Direction::Down], cellar);
+        assert_eq!(world.places[&cellar].connections()[&Direction::Up], start);
+        assert_eq!(world.places[&cellar].parent, world.places[&start].parent);
+        assert!(dig(&mut world, start, Direction::Down, "Pit".into()).is_err());
+
+        let vault = dig(&mut world, cellar, Direction::North, "Vault".into()).unwrap();
+        let well = dig(&mut world, cellar, Direction::East, "Well".into()).unwrap();
+        // The well's way west already leads back to the cellar
+        assert!(link(&mut world, vault, Direction::East, well).is_err());
+        assert!(link(&mut world, vault, Direction::Up, well).is_ok());
+        assert_eq!(world.places[&well].connections()[&Direction::Down], vault);
+        assert!(link(&mut world, vault, Direction::West, vault).is_err());
+
+        assert_eq!(unlink(&mut world, cellar, Direction::North), Ok(vault));
+        assert!(!world.places[&vault]
+            .connections()
+            .contains_key(&Direction::South));
+        assert!(unlink(&mut world, cellar, Direction::North).is_err());
+
+        let changes = world.take_changes();
+        for location in [start, cellar, vault, well] {
+            assert!(changes.world.places.contains_key(&location));
+        }
+
+        let void = world.void_location();
+        assert!(link(&mut world, start, Direction::West, void).is_err());
+    }
+}
@@ -0,0 +1,52 @@
+//! Village maps, pieced together from what explorers tell the cartographers.
+//! Players are paid for each place they add to a village's map, and anyone can
+//! buy a copy to learn of places they've never been.
+
+use std::collections::HashSet;
+
+use super::world::Location;
+
+/// Names for cartographers, picked by the place they work in
+pub const CARTOGRAPHER_NAMES: &[&str] = &["Maren", "Quill", "Isolde", "Anselm", "Tibalt", "Rhosyn"];
+/// Gold a cartographer pays for each place that isn't on their map yet
+pub const PAY_PER_PLACE: u32 = 2;
+/// What a copy of the map costs for each place on it the buyer doesn't know of
+pub const PRICE_PER_PLACE: u32 = 1;
+
+/// Adds the places a player has been to a village's map, returning how many
+/// the map didn't have before
+pub fn contribute(map: &mut HashSet<Location>, visited: &HashSet<Location>) -> usize {
+    let before = map.len();
+    map.extend(visited);
+    map.len() - before
+}
+
+/// The places on a map the player doesn't know of yet
+pub fn unknown<'a>(
+    map: &'a HashSet<Location>,
+    known: impl Fn(&Location) -> bool + 'a,
+) -> impl Iterator<Item = Location> + 'a {
+    map.iter().filter(move |l| !known(l)).copied()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::mud::world::Place;
+
+    use super::*;
+
+    #[test]
+    fn pays_for_new_places() {
+        let [a, b, c] = ["A", "B", "C"].map(|n| Place::new(n.into(), String::new()).location);
+        let mut map = HashSet::from([a]);
+
+        assert_eq!(contribute(&mut map, &HashSet::from([a, b])), 1);
+        assert_eq!(contribute(&mut map, &HashSet::from([a, b])), 0);
+
+        let known = HashSet::from([a, c]);
+        assert_eq!(
+            unknown(&map, |l| known.contains(l)).collect::<Vec<_>>(),
+            [b]
+        );
+    }
+}
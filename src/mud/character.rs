@@ -1,6 +1,42 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
 use serde::{Deserialize, Serialize};
 
-use super::{items::Inventory, world::Location};
+use super::{
+    action::Action,
+    companion::Companion,
+    creation::CreationStep,
+    entity::EntityId,
+    experience::{self, LevelUp},
+    haggle::Offer,
+    input::InputQueue,
+    items::{self, Inventory, ItemInstance},
+    lore::LoreKind,
+    relationship::{Conversation, Standing},
+    world::Location,
+};
+
+/// What anyone can carry before strength is counted
+const BASE_CAPACITY: u32 = 30;
+/// How much more a character can carry for each point of strength
+const CAPACITY_PER_STRENGTH: u32 = 3;
+/// Past this many times their capacity a character can't move at all
+const OVERLOADED: f64 = 1.5;
+/// The most aliases a character can set up
+pub const MAX_ALIASES: usize = 50;
+/// The longest an alias's name or what it stands for can be
+pub const MAX_ALIAS_NAME: usize = 20;
+pub const MAX_ALIAS_LENGTH: usize = 200;
+
+/// How much what a character's carrying slows them down
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encumbrance {
+    Unburdened,
+    /// Over what they can carry, they have to catch their breath between moves
+    Burdened,
+    /// Too much to move at all
+    Overloaded,
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 #[serde(transparent)]
@@ -36,28 +72,387 @@ pub struct Attributes {
     pub willpower: Attribute,
 }
 
-#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+impl Attributes {
+    pub fn named(&self) -> [(&'static str, Attribute); 5] {
+        [
+            ("strength", self.strength),
+            ("toughness", self.toughness),
+            ("agility", self.agility),
+            ("intelligence", self.intelligence),
+            ("willpower", self.willpower),
+        ]
+    }
+
+    pub fn named_mut(&mut self) -> [(&'static str, &mut Attribute); 5] {
+        [
+            ("strength", &mut self.strength),
+            ("toughness", &mut self.toughness),
+            ("agility", &mut self.agility),
+            ("intelligence", &mut self.intelligence),
+            ("willpower", &mut self.willpower),
+        ]
+    }
+
+    /// The sum of all attribute scores
+    pub fn total(&self) -> i32 {
+        self.named().iter().map(|(_, a)| a.value()).sum()
+    }
+}
+
+/// A temporary change to one of a character's attributes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct StatusEffect {
+    pub name: String,
+    pub attribute: String,
+    /// The change to the attribute for each stack
+    pub amount: i32,
+    pub until_tick: u64,
+    #[serde(default = "one")]
+    pub stacks: u32,
+}
+
+fn one() -> u32 {
+    1
+}
+
+/// A poison applied to the character's weapon, used up as they land hits
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Coating {
+    pub poison: String,
+    pub charges: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case", default)]
 pub struct Character {
+    /// Stays the same whatever the character's called, see [`EntityId`]
+    pub id: EntityId,
     pub name: String,
     pub location: Location,
     pub health: u32,
     pub attributes: Attributes,
+    /// Experience earned so far, which sets the character's level
+    pub experience: u32,
     pub inventory: Inventory,
+    /// The tick until which the character counts as fighting and can't log out
+    pub in_combat_until: u64,
+    /// Whether the player has agreed to fight other players
+    pub pvp_consent: bool,
+    /// Every place the character has set foot in this cycle
+    pub visited: HashSet<Location>,
+    /// Places the character knows of from maps without having been there
+    pub charted: HashSet<Location>,
+    /// Temporary changes to the character's attributes, from food, potions and the like
+    pub effects: Vec<StatusEffect>,
+    /// Recipes the character has made and can make again, by name
+    pub known_recipes: BTreeSet<String>,
+    /// The piece of equipment in hand, its enchantments count towards the character's attributes
+    #[serde(deserialize_with = "items::deserialize_wielding")]
+    pub wielding: Option<ItemInstance>,
+    /// Poison on the character's weapon, if any
+    pub coating: Option<Coating>,
+    /// The tick from which the character can gather ingredients again
+    pub next_gather_tick: u64,
+    /// Items and creatures the character has come across, unlocking their lore
+    pub encountered: BTreeMap<String, LoreKind>,
+    /// A befriended creature that follows the character around
+    pub companion: Option<Box<Companion>>,
+    /// Names of items the player has flagged as junk, to sell off and leave lying around
+    pub junk: BTreeSet<String>,
+    /// How each named NPC feels about the character, see [`Standing`]
+    pub relationships: BTreeMap<String, i32>,
+    /// Items NPCs have asked the character to bring them, by NPC
+    pub errands: BTreeMap<String, String>,
+    /// The tick from which a gift raises each NPC's opinion again, by NPC
+    pub next_gift_ticks: BTreeMap<String, u64>,
+    /// How many chapters of each NPC's storyline the character has heard
+    pub stories: BTreeMap<String, usize>,
+    /// Goods sent to the character, waiting to be collected in a village
+    pub mailbox: Inventory,
+    /// Notes that came with the mail
+    pub letters: Vec<String>,
+    /// Shorthands the player's set up for commands, see [`Character::expand_alias`]
+    pub aliases: BTreeMap<String, String>,
+    /// Set while the player is still creating the character, who stays in the void until done
+    pub creating: Option<CreationStep>,
+    /// The tick from which the character can haggle with vendors again
+    pub next_haggle_tick: u64,
+    /// The tick from which the character can file another bug or typo report
+    #[serde(skip)]
+    pub next_report_tick: u64,
+    /// The tick from which the character can move again after moving while burdened
+    #[serde(skip)]
+    pub next_move_tick: u64,
+    /// Set while the character is making camp to log out
+    #[serde(skip)]
+    pub camping_until: Option<u64>,
+    /// Where the character's body lies while they wander as a ghost
+    pub ghost: Option<Location>,
+    /// The shrine or inn the character comes to after being knocked out and recalls to
+    pub bound_to: Option<Location>,
+    /// Set while the character is reading a recall scroll
+    #[serde(skip)]
+    pub recalling_until: Option<u64>,
+    /// A price a vendor has agreed to pay, until the character accepts it or moves on
+    #[serde(skip)]
+    pub offer: Option<Offer>,
+    /// Who the character's talking to and what's been said, until they move on
+    #[serde(skip)]
+    pub conversation: Option<Conversation>,
+    /// Something the character is busy doing, like crafting
+    #[serde(skip)]
+    pub action: Option<Action>,
+    /// Commands typed while the character was busy, waiting to run
+    #[serde(skip)]
+    pub input: InputQueue,
+}
+
+impl Default for Character {
+    fn default() -> Self {
+        let mut character = Self {
+            id: EntityId::new_random(),
+            name: Default::default(),
+            location: Default::default(),
+            health: 0,
+            attributes: Default::default(),
+            experience: 0,
+            inventory: Default::default(),
+            in_combat_until: 0,
+            pvp_consent: false,
+            visited: Default::default(),
+            charted: Default::default(),
+            encountered: Default::default(),
+            junk: Default::default(),
+            companion: None,
+            effects: Vec::new(),
+            known_recipes: Default::default(),
+            wielding: None,
+            coating: None,
+            next_gather_tick: 0,
+            relationships: Default::default(),
+            errands: Default::default(),
+            next_gift_ticks: Default::default(),
+            stories: Default::default(),
+            mailbox: Default::default(),
+            letters: Vec::new(),
+            aliases: Default::default(),
+            creating: None,
+            next_haggle_tick: 0,
+            next_report_tick: 0,
+            next_move_tick: 0,
+            camping_until: None,
+            ghost: None,
+            bound_to: None,
+            recalling_until: None,
+            offer: None,
+            conversation: None,
+            action: None,
+            input: Default::default(),
+        };
+        character.health = character.max_health();
+        character
+    }
 }
 
 impl Character {
+    /// How a named NPC feels about the character
+    pub fn standing(&self, npc: &str) -> Standing {
+        Standing::from_score(self.relationships.get(npc).copied().unwrap_or_default())
+    }
+
+    pub fn change_relationship(&mut self, npc: &str, amount: i32) {
+        *self.relationships.entry(npc.to_string()).or_default() += amount;
+    }
+
+    /// Knocks the character out of whatever they were busy doing, returning a note saying so
+    pub fn interrupt(&mut self) -> Option<String> {
+        let action = self.action.take()?;
+        Some(format!(
+            "\nThe blow breaks your focus, you stop {}",
+            action.verb
+        ))
+    }
+
+    /// The line with its first word swapped for what it stands for, if it's one
+    /// of the character's aliases. Anything after is kept on the end. Aliases
+    /// only expand once, so they can't loop.
+    pub fn expand_alias(&self, line: &str) -> Option<String> {
+        let line = line.trim();
+        let (name, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let expansion = self.aliases.get(name)?;
+        Some(match rest.trim() {
+            "" => expansion.clone(),
+            rest => format!("{expansion} {rest}"),
+        })
+    }
+
+    /// Sends the character a letter and maybe some goods to collect from a village
+    pub fn send_mail(&mut self, letter: String, parcel: Inventory) {
+        self.letters.push(letter);
+        self.mailbox.append(parcel);
+    }
+
+    pub fn in_combat(&self, current_tick: u64) -> bool {
+        self.in_combat_until > current_tick
+    }
+
+    /// Finds something the character knows enough about to look up, ignoring case.
+    /// Anything they're carrying counts as encountered.
+    pub fn known_lore(&self, name: &str) -> Option<(String, LoreKind)> {
+        self.encountered
+            .iter()
+            .map(|(n, k)| (n.as_str(), *k))
+            .chain(self.inventory.names().map(|n| (n, LoreKind::Item)))
+            .chain(
+                self.wielding
+                    .iter()
+                    .map(|i| (i.template.as_str(), LoreKind::Item)),
+            )
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(n, k)| (n.to_string(), k))
+    }
+
+    /// The character's attributes with all their status effects and enchantments applied
+    pub fn effective_attributes(&self) -> Attributes {
+        let enchantments = self.wielding.iter().flat_map(|i| i.active_enchantments());
+        let changes = self
+            .effects
+            .iter()
+            .map(|e| (e.attribute.as_str(), e.amount * e.stacks as i32))
+            .chain(enchantments.map(|e| (e.attribute.as_str(), e.amount)));
+
+        let mut attributes = self.attributes.clone();
+        for (changed, amount) in changes {
+            for (name, attribute) in attributes.named_mut() {
+                if name == changed {
+                    *attribute = Attribute::new((attribute.value() + amount).max(1));
+                }
+            }
+        }
+        attributes
+    }
+
+    /// Adds an effect, replacing any older effect with the same name
+    pub fn add_effect(&mut self, effect: StatusEffect) {
+        self.effects.retain(|e| e.name != effect.name);
+        self.effects.push(effect);
+    }
+
+    /// Adds an effect or, if the character already has it, adds a stack
+    /// up to the maximum and refreshes how long it lasts
+    pub fn stack_effect(&mut self, effect: StatusEffect, max_stacks: u32) -> u32 {
+        match self.effects.iter_mut().find(|e| e.name == effect.name) {
+            Some(existing) => {
+                existing.stacks = (existing.stacks + 1).min(max_stacks);
+                existing.until_tick = effect.until_tick;
+                existing.stacks
+            }
+            None => {
+                self.effects.push(effect);
+                1
+            }
+        }
+    }
+
+    /// Removes effects that have run out, returning their names
+    pub fn expire_effects(&mut self, current_tick: u64) -> Vec<String> {
+        let (expired, active) = std::mem::take(&mut self.effects)
+            .into_iter()
+            .partition(|e| e.until_tick <= current_tick);
+        self.effects = active;
+        expired.into_iter().map(|e: StatusEffect| e.name).collect()
+    }
+
+    /// Heals the character up to their max health, returning how much they healed
+    pub fn heal(&mut self, amount: u32) -> u32 {
+        let before = self.health;
+        self.health = (self.health + amount).min(self.max_health());
+        self.health.saturating_sub(before)
+    }
+
+    pub fn level(&self) -> u32 {
+        experience::level_for(self.experience)
+    }
+
+    /// What the character has on them, see [`Inventory::counts`]
+    pub fn holdings(&self) -> BTreeMap<String, i64> {
+        let mut res = self.inventory.counts();
+        if let Some(wielding) = &self.wielding {
+            *res.entry(wielding.template.clone()).or_default() += 1;
+        }
+        res
+    }
+
+    /// Adds experience, raising an attribute and the character's health for each level reached
+    pub fn gain_experience(&mut self, amount: u32) -> Vec<LevelUp> {
+        let (before, max_health) = (self.level(), self.max_health());
+        self.experience = self.experience.saturating_add(amount);
+
+        let mut reached = Vec::new();
+        for level in before + 1..=self.level() {
+            let attribute = experience::attribute_for(level, &self.attributes);
+            for (name, value) in self.attributes.named_mut() {
+                if name == attribute {
+                    *value = Attribute::new(value.value() + 1);
+                }
+            }
+            reached.push(LevelUp { level, attribute });
+        }
+        // The extra health comes fresh, however hurt the character is
+        self.health += self.max_health().saturating_sub(max_health);
+        reached
+    }
+
+    /// How much the character can carry before they're burdened
+    pub fn carry_capacity(&self) -> u32 {
+        let strength = self.effective_attributes().strength.value().max(1) as u32;
+        BASE_CAPACITY + strength * CAPACITY_PER_STRENGTH
+    }
+
+    /// What everything the character's carrying and wielding weighs, given
+    /// what one of each item weighs
+    pub fn carried_weight(&self, weight_of: impl Fn(&str) -> u32) -> u32 {
+        let wielded = self.wielding.as_ref().map_or(0, |i| weight_of(&i.template));
+        self.inventory.total_weight(&weight_of) + wielded
+    }
+
+    pub fn encumbrance(&self, weight_of: impl Fn(&str) -> u32) -> Encumbrance {
+        let (weight, capacity) = (self.carried_weight(weight_of), self.carry_capacity());
+        if weight as f64 > capacity as f64 * OVERLOADED {
+            Encumbrance::Overloaded
+        } else if weight > capacity {
+            Encumbrance::Burdened
+        } else {
+            Encumbrance::Unburdened
+        }
+    }
+
     pub fn max_health(&self) -> u32 {
-        ((self.attributes.toughness.modifier() * 2) + 8)
+        ((self.attributes.toughness.modifier() * 2)
+            + 8
+            + (self.level() as i32 - 1) * experience::HEALTH_PER_LEVEL)
             .max(1)
             .try_into()
             .unwrap()
     }
+
+    /// How hurt the character looks to someone else
+    pub fn condition(&self) -> &'static str {
+        match self.health * 4 / self.max_health() {
+            4.. => "unhurt",
+            3 => "lightly wounded",
+            2 => "wounded",
+            1 => "badly wounded",
+            0 => "barely standing",
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::mud::items::ItemStack;
+    use crate::mud::items::{ItemStack, Quality};
 
     use super::*;
 
@@ -85,7 +480,7 @@ mod test {
 
         let mut inv = Inventory::default();
 
-        inv.add(&torch, 2);
+        inv.add(torch, 2);
 
         assert_eq!(
             inv.get("Torch"),
@@ -98,12 +493,13 @@ mod test {
 
         assert_eq!(inv.get("Sword"), None);
 
-        inv.add(&gold, 3);
-        inv.add(&sword, 1);
+        inv.add(gold, 3);
+        inv.add(sword, 1);
 
-        assert_eq!(inv.total_weight(), 6);
+        let weight_of = |name: &str| if name == gold { 0 } else { 2 };
+        assert_eq!(inv.total_weight(weight_of), 6);
 
-        assert!(inv.remove(&torch, 1));
+        assert!(inv.remove(torch, 1));
         assert_eq!(
             inv.get("Torch"),
             Some(ItemStack {
@@ -112,9 +508,55 @@ mod test {
             })
             .as_ref()
         );
-        assert!(inv.remove(&torch, 1));
+        assert!(inv.remove(torch, 1));
         assert_eq!(inv.get("Torch"), None);
-        assert_eq!(inv.total_weight(), 4);
+        assert_eq!(inv.total_weight(weight_of), 2);
+
+        let table = inv.describe(weight_of);
+        assert!(table.contains("Gold Coin"));
+        assert!(table.ends_with("Total weight: 2"));
+        assert_eq!(
+            Inventory::default().describe(weight_of),
+            "You aren't carrying anything"
+        );
+    }
+
+    #[test]
+    fn expands_aliases_once() {
+        let mut ada = Character::default();
+        ada.aliases.insert("k".into(), "attack".into());
+        ada.aliases.insert("attack".into(), "k".into());
+        ada.aliases.insert("gb".into(), "give bob".into());
+
+        assert_eq!(
+            ada.expand_alias("k cave rat"),
+            Some("attack cave rat".into())
+        );
+        assert_eq!(ada.expand_alias("attack"), Some("k".into()));
+        assert_eq!(
+            ada.expand_alias("  gb  2 bread "),
+            Some("give bob 2 bread".into())
+        );
+        assert_eq!(ada.expand_alias("kick bob"), None);
+        assert_eq!(ada.expand_alias("K"), None);
+    }
+
+    #[test]
+    fn heavy_loads_encumber() {
+        let mut ada = Character::default();
+        let weight_of = |name: &str| if name == "Anvil" { 20 } else { 1 };
+        assert_eq!(ada.carry_capacity(), 60);
+
+        ada.inventory.add("Anvil", 3);
+        assert_eq!(ada.encumbrance(weight_of), Encumbrance::Unburdened);
+        ada.wielding = Some(ItemInstance::new("Anvil", Quality::Common));
+        assert_eq!(ada.carried_weight(weight_of), 80);
+        assert_eq!(ada.encumbrance(weight_of), Encumbrance::Burdened);
+        ada.inventory.add("Anvil", 1);
+        assert_eq!(ada.encumbrance(weight_of), Encumbrance::Overloaded);
+
+        ada.attributes.strength = Attribute::new(24);
+        assert_eq!(ada.encumbrance(weight_of), Encumbrance::Unburdened);
     }
 
     #[test]
@@ -0,0 +1,236 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::state::PlayerId;
+
+use super::{character::Character, crafting::Recipe};
+
+/// Running totals for one participant over the course of a fight
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct FightStats {
+    pub damage_dealt: u32,
+    pub damage_taken: u32,
+    pub healing: u32,
+    pub killing_blows: u32,
+}
+
+/// Everything that happened in a single fight. Kept around after the fight
+/// ends so the players involved can `recap` it.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct FightLog {
+    pub rounds: u32,
+    pub participants: BTreeMap<String, FightStats>,
+    pub players: HashSet<PlayerId>,
+    /// Creature templates that took part, used for balance telemetry
+    pub creatures: BTreeSet<String>,
+    pub downed_players: u32,
+    pub loot_value: u32,
+}
+
+impl FightLog {
+    pub fn record_damage(&mut self, attacker: &str, defender: &str, amount: u32) {
+        self.rounds += 1;
+        self.participants
+            .entry(attacker.to_string())
+            .or_default()
+            .damage_dealt += amount;
+        self.participants
+            .entry(defender.to_string())
+            .or_default()
+            .damage_taken += amount;
+    }
+
+    pub fn record_miss(&mut self, attacker: &str, defender: &str) {
+        self.record_damage(attacker, defender, 0);
+    }
+
+    pub fn record_healing(&mut self, name: &str, amount: u32) {
+        self.participants
+            .entry(name.to_string())
+            .or_default()
+            .healing += amount;
+    }
+
+    pub fn record_kill(&mut self, killer: &str) {
+        self.participants
+            .entry(killer.to_string())
+            .or_default()
+            .killing_blows += 1;
+    }
+
+    /// A per participant breakdown of the fight, formatted as a table
+    pub fn recap(&self) -> String {
+        let mut res = format!("Fight recap, {} rounds\n\n", self.rounds);
+        res.push_str(&format!(
+            "{:20}{:>8}{:>8}{:>8}{:>8}\n",
+            "Name", "Dealt", "Taken", "Healed", "Kills"
+        ));
+
+        for (name, stats) in &self.participants {
+            res.push_str(&format!(
+                "{:20}{:>8}{:>8}{:>8}{:>8}\n",
+                name, stats.damage_dealt, stats.damage_taken, stats.healing, stats.killing_blows
+            ));
+        }
+
+        res
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttackOutcome {
+    Miss,
+    Hit(u32),
+    Downed(u32),
+}
+
+/// Resolves a single attack, rolling to hit against the defender's agility
+/// and applying strength based damage to their health.
+pub fn attack(attacker: &Character, defender: &mut Character, rng: &mut impl Rng) -> AttackOutcome {
+    let strength = attacker.effective_attributes().strength.modifier();
+    let to_hit = rng.gen_range(1..=20) + strength;
+    if to_hit < 10 + defender.effective_attributes().agility.modifier() {
+        return AttackOutcome::Miss;
+    }
+
+    let damage = (rng.gen_range(1..=4) + strength).max(1) as u32;
+    let damage = damage.min(defender.health);
+    defender.health -= damage;
+
+    if defender.health == 0 {
+        AttackOutcome::Downed(damage)
+    } else {
+        AttackOutcome::Hit(damage)
+    }
+}
+
+/// How many times a poison can stack on one character
+pub const MAX_POISON_STACKS: u32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PoisonOutcome {
+    Poisoned(u32),
+    Resisted,
+}
+
+/// Uses a charge of the attacker's weapon coating on the defender after a hit.
+/// The defender gets a toughness save against the poison's strength.
+pub fn apply_coating(
+    attacker: &mut Character,
+    defender: &mut Character,
+    poison: &Recipe,
+    until_tick: u64,
+    rng: &mut impl Rng,
+) -> Option<PoisonOutcome> {
+    let coating = attacker.coating.as_mut()?;
+    coating.charges = coating.charges.saturating_sub(1);
+    if coating.charges == 0 {
+        attacker.coating = None;
+    }
+
+    let save = rng.gen_range(1..=20) + defender.effective_attributes().toughness.modifier();
+    if save >= 10 + poison.amount {
+        return Some(PoisonOutcome::Resisted);
+    }
+
+    let effect = poison.status_effect(until_tick)?;
+    Some(PoisonOutcome::Poisoned(
+        defender.stack_effect(effect, MAX_POISON_STACKS),
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn fight_log_totals() {
+        let mut log = FightLog::default();
+        log.record_damage("Ada", "Steve", 4);
+        log.record_miss("Steve", "Ada");
+        log.record_damage("Ada", "Steve", 3);
+        log.record_kill("Ada");
+
+        assert_eq!(log.rounds, 3);
+        assert_eq!(
+            log.participants["Ada"],
+            FightStats {
+                damage_dealt: 7,
+                damage_taken: 0,
+                healing: 0,
+                killing_blows: 1,
+            }
+        );
+        assert_eq!(log.participants["Steve"].damage_taken, 7);
+        assert!(log.recap().contains("Steve"));
+    }
+
+    #[test]
+    fn attacks_until_downed() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+        let ada = Character::default();
+        let mut steve = Character::default();
+        let starting_health = steve.health;
+
+        let mut dealt = 0;
+        loop {
+            match attack(&ada, &mut steve, &mut rng) {
+                AttackOutcome::Miss => {}
+                AttackOutcome::Hit(d) => dealt += d,
+                AttackOutcome::Downed(d) => {
+                    dealt += d;
+                    break;
+                }
+            }
+        }
+
+        assert_eq!(steve.health, 0);
+        assert_eq!(dealt, starting_health);
+    }
+
+    #[test]
+    fn poison_stacks_and_runs_out() {
+        use crate::mud::{
+            character::Coating,
+            crafting::{Craft, Recipe},
+        };
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(3);
+        let poison = Recipe::combine(
+            Craft::Alchemy,
+            &["Spider Venom", "Spider Venom", "Nightshade"],
+        )
+        .unwrap();
+        let mut ada = Character {
+            coating: Some(Coating {
+                poison: "Widow's Kiss".into(),
+                charges: 5,
+            }),
+            ..Default::default()
+        };
+        let mut steve = Character::default();
+
+        let mut poisoned = 0;
+        for _ in 0..5 {
+            match apply_coating(&mut ada, &mut steve, &poison, 100, &mut rng) {
+                Some(PoisonOutcome::Poisoned(stacks)) => poisoned = stacks,
+                Some(PoisonOutcome::Resisted) => {}
+                None => panic!("Coating should have charges left"),
+            }
+        }
+
+        assert!(ada.coating.is_none());
+        assert!(apply_coating(&mut ada, &mut steve, &poison, 100, &mut rng).is_none());
+        assert!(poisoned > 0 && poisoned <= MAX_POISON_STACKS);
+        assert_eq!(
+            steve.effective_attributes().strength.value(),
+            10 - 3 * poisoned as i32
+        );
+    }
+}
@@ -0,0 +1,135 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use super::character::Character;
+
+/// How loyal a newly befriended companion is
+const STARTING_LOYALTY: u32 = 5;
+const MAX_LOYALTY: u32 = 10;
+
+/// When a companion joins in on a fight
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Stance {
+    /// Joins any fight its owner is in
+    Aggressive,
+    /// Only strikes back at whoever attacks its owner
+    #[default]
+    Defensive,
+    /// Stays out of fights entirely
+    Passive,
+}
+
+impl Stance {
+    pub fn values() -> [Stance; 3] {
+        [Stance::Aggressive, Stance::Defensive, Stance::Passive]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Stance::Aggressive => "aggressive",
+            Stance::Defensive => "defensive",
+            Stance::Passive => "passive",
+        }
+    }
+
+    /// Whether a companion in this stance fights, given if its owner started the fight
+    pub fn fights(self, owner_attacking: bool) -> bool {
+        match self {
+            Stance::Aggressive => true,
+            Stance::Defensive => !owner_attacking,
+            Stance::Passive => false,
+        }
+    }
+}
+
+/// A creature that's been befriended and follows a player around, saved with its owner
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Companion {
+    /// Its stats and whatever it's carrying, its name is the one its owner gave it
+    pub creature: Character,
+    /// What kind of creature it was before it was befriended
+    pub kind: String,
+    pub loyalty: u32,
+    #[serde(default)]
+    pub stance: Stance,
+}
+
+impl Companion {
+    pub fn new(creature: Character) -> Self {
+        Self {
+            kind: creature.name.clone(),
+            creature,
+            loyalty: STARTING_LOYALTY,
+            stance: Stance::default(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.creature.name
+    }
+
+    /// Fighting at its owner's side brings a companion closer to them
+    pub fn won_fight(&mut self) {
+        self.loyalty = (self.loyalty + 1).min(MAX_LOYALTY);
+    }
+
+    /// Seeing its owner beaten shakes a companion's faith,
+    /// returns true if it's lost all loyalty and wants to leave
+    pub fn lost_fight(&mut self) -> bool {
+        self.loyalty = self.loyalty.saturating_sub(2);
+        self.loyalty == 0
+    }
+
+    /// A short line about the companion for its owner
+    pub fn describe(&self) -> String {
+        format!(
+            "{}, a {} ({}/{} health)\nLoyalty: {}/{MAX_LOYALTY}\nStance: {}",
+            self.name(),
+            self.kind,
+            self.creature.health,
+            self.creature.max_health(),
+            self.loyalty,
+            self.stance.name()
+        )
+    }
+}
+
+/// Tries to win a creature over, a willpower contest between the two of them
+pub fn befriend(player: &Character, creature: &Character, rng: &mut impl Rng) -> bool {
+    let persuasion = rng.gen_range(1..=20) + player.effective_attributes().willpower.modifier();
+    persuasion >= 10 + creature.effective_attributes().willpower.modifier()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn loyalty_rises_and_falls() {
+        let rat = Character {
+            name: "Cave Rat".into(),
+            ..Default::default()
+        };
+        let mut companion = Companion::new(rat);
+        companion.creature.name = "Whiskers".into();
+        assert_eq!(companion.kind, "Cave Rat");
+
+        for _ in 0..10 {
+            companion.won_fight();
+        }
+        assert_eq!(companion.loyalty, MAX_LOYALTY);
+
+        let losses = (0..10).take_while(|_| !companion.lost_fight()).count();
+        assert_eq!(losses, 4);
+
+        assert!(Stance::Aggressive.fights(true));
+        assert!(Stance::Defensive.fights(false));
+        assert!(!Stance::Defensive.fights(true));
+        assert!(!Stance::Passive.fights(false));
+
+        let yaml = serde_yaml::to_string(&companion).unwrap();
+        assert_eq!(serde_yaml::from_str::<Companion>(&yaml).unwrap(), companion);
+    }
+}
@@ -0,0 +1,33 @@
+use serde::{Deserialize, Serialize};
+
+use crate::state::PlayerId;
+
+use super::items::Inventory;
+
+/// What's left where a character fell when the rules don't have ghosts. It
+/// holds whatever the death penalty took from them and only they can loot it,
+/// until it crumbles and spills everything onto the ground for anyone to take.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Corpse {
+    pub owner: PlayerId,
+    pub owner_name: String,
+    pub contents: Inventory,
+    /// The tick it crumbles away on
+    pub crumbles_at: u64,
+}
+
+impl Corpse {
+    pub fn new(owner: PlayerId, owner_name: String, contents: Inventory, crumbles_at: u64) -> Self {
+        Self {
+            owner,
+            owner_name,
+            contents,
+            crumbles_at,
+        }
+    }
+
+    pub fn crumbled(&self, current_tick: u64) -> bool {
+        self.crumbles_at <= current_tick
+    }
+}
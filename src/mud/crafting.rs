@@ -0,0 +1,350 @@
+use serde::{Deserialize, Serialize};
+
+use super::character::StatusEffect;
+
+/// The most ingredients that can go into one dish or potion
+pub const MAX_INGREDIENTS: usize = 3;
+/// The affinity of poisonous ingredients, potions made mostly from them are poisons
+pub const VENOM: &str = "venom";
+/// The name of the effect poisons give, and the attribute it saps
+const POISONED: &str = "Poisoned";
+const POISONED_ATTRIBUTE: &str = "strength";
+
+/// Which kind of consumable is being made
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Craft {
+    /// Food, gentle and long lasting
+    Cooking,
+    /// Potions, strong but brief
+    Alchemy,
+}
+
+impl Craft {
+    pub fn name(self) -> &'static str {
+        match self {
+            Craft::Cooking => "cooking",
+            Craft::Alchemy => "alchemy",
+        }
+    }
+
+    /// What the craft makes, for prompts and messages
+    pub fn product(self) -> &'static str {
+        match self {
+            Craft::Cooking => "dish",
+            Craft::Alchemy => "potion",
+        }
+    }
+
+    fn duration_seconds(self) -> f64 {
+        match self {
+            Craft::Cooking => 600.0,
+            Craft::Alchemy => 120.0,
+        }
+    }
+}
+
+/// Something that can be gathered and combined, its affinity is the
+/// attribute it strengthens or "health" if it heals
+pub struct Ingredient {
+    pub name: &'static str,
+    /// Tags of the places it can be gathered in
+    pub biomes: &'static [&'static str],
+    pub affinity: &'static str,
+}
+
+pub const INGREDIENTS: &[Ingredient] = &[
+    Ingredient {
+        name: "Honeycomb",
+        biomes: &["village"],
+        affinity: "health",
+    },
+    Ingredient {
+        name: "Wild Garlic",
+        biomes: &["village"],
+        affinity: "toughness",
+    },
+    Ingredient {
+        name: "Barley",
+        biomes: &["village"],
+        affinity: "strength",
+    },
+    Ingredient {
+        name: "Mint Leaf",
+        biomes: &["village"],
+        affinity: "intelligence",
+    },
+    Ingredient {
+        name: "Cave Mushroom",
+        biomes: &["dungeon"],
+        affinity: "toughness",
+    },
+    Ingredient {
+        name: "Glowmoss",
+        biomes: &["dungeon"],
+        affinity: "intelligence",
+    },
+    Ingredient {
+        name: "Bat Wing",
+        biomes: &["dungeon"],
+        affinity: "agility",
+    },
+    Ingredient {
+        name: "Bone Dust",
+        biomes: &["dungeon"],
+        affinity: "willpower",
+    },
+    Ingredient {
+        name: "Bloodroot",
+        biomes: &["dungeon"],
+        affinity: "health",
+    },
+    Ingredient {
+        name: "Nightshade",
+        biomes: &["village"],
+        affinity: VENOM,
+    },
+    Ingredient {
+        name: "Spider Venom",
+        biomes: &["dungeon"],
+        affinity: VENOM,
+    },
+];
+
+/// Recipes with fixed names, everything else is named the first time someone makes it
+const KNOWN_RECIPES: &[(Craft, &[&str], &str, &str)] = &[
+    (
+        Craft::Cooking,
+        &["Barley", "Honeycomb"],
+        "Honey Cake",
+        "A dense, sticky cake that keeps a traveller going.",
+    ),
+    (
+        Craft::Cooking,
+        &["Cave Mushroom", "Wild Garlic"],
+        "Mushroom Stew",
+        "A thick, earthy stew that warms you to your bones.",
+    ),
+    (
+        Craft::Alchemy,
+        &["Bloodroot", "Honeycomb"],
+        "Healing Draught",
+        "A sweet red potion that knits wounds closed.",
+    ),
+    (
+        Craft::Alchemy,
+        &["Bat Wing", "Bat Wing", "Glowmoss"],
+        "Quicksilver Tonic",
+        "A faintly glowing potion that makes the world seem to slow down.",
+    ),
+    (
+        Craft::Alchemy,
+        &["Nightshade", "Spider Venom"],
+        "Widow's Kiss",
+        "A thick black poison, best kept well away from your own lips.",
+    ),
+];
+
+/// A dish or potion and what it does when consumed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Recipe {
+    pub name: String,
+    pub description: String,
+    pub craft: Craft,
+    pub ingredients: Vec<String>,
+    /// The attribute boosted, or "health" if it only heals
+    pub affinity: String,
+    pub amount: i32,
+    pub heal: u32,
+    pub duration_seconds: f64,
+}
+
+impl Recipe {
+    /// Works out what combining the ingredients does, the most common affinity wins
+    /// and more of it makes for a stronger effect. Names are left for the caller.
+    pub fn combine(craft: Craft, ingredients: &[&str]) -> Result<Self, String> {
+        if !(2..=MAX_INGREDIENTS).contains(&ingredients.len()) {
+            return Err(format!(
+                "You need between 2 and {MAX_INGREDIENTS} ingredients"
+            ));
+        }
+
+        let mut affinities = Vec::new();
+        let mut names = Vec::new();
+        for name in ingredients {
+            let Some(ingredient) = INGREDIENTS
+                .iter()
+                .find(|i| i.name.eq_ignore_ascii_case(name))
+            else {
+                return Err(format!(
+                    "{name} isn't something you can use in {}",
+                    craft.name()
+                ));
+            };
+            affinities.push(ingredient.affinity);
+            names.push(ingredient.name.to_string());
+        }
+        names.sort();
+
+        let (affinity, potency) = ATTRIBUTE_ORDER
+            .iter()
+            .map(|a| (*a, affinities.iter().filter(|b| *b == a).count() as i32))
+            .fold(
+                ("health", 0),
+                |best, next| if next.1 > best.1 { next } else { best },
+            );
+
+        let (amount, heal) = match (craft, affinity) {
+            (Craft::Cooking, VENOM) => {
+                return Err("That would poison whoever ate it, try brewing it instead".to_string())
+            }
+            (Craft::Alchemy, VENOM) => (potency, 0),
+            (Craft::Cooking, "health") => (0, 3 * potency as u32),
+            (Craft::Alchemy, "health") => (0, 5 * potency as u32),
+            (Craft::Cooking, _) => (potency, 2),
+            (Craft::Alchemy, _) => (potency + 1, 0),
+        };
+
+        Ok(Self {
+            name: String::new(),
+            description: String::new(),
+            craft,
+            ingredients: names,
+            affinity: affinity.to_string(),
+            amount,
+            heal,
+            duration_seconds: craft.duration_seconds(),
+        })
+    }
+
+    /// Identifies the combination regardless of the order ingredients were given in
+    pub fn key(&self) -> String {
+        format!("{}:{}", self.craft.name(), self.ingredients.join("+"))
+    }
+
+    /// Fills in the name and description if this is one of the fixed recipes
+    pub fn with_known_name(mut self) -> Option<Self> {
+        let (_, _, name, description) =
+            KNOWN_RECIPES.iter().find(|(craft, ingredients, _, _)| {
+                *craft == self.craft && ingredients.iter().eq(self.ingredients.iter())
+            })?;
+        self.name = name.to_string();
+        self.description = description.to_string();
+        Some(self)
+    }
+
+    pub fn is_poison(&self) -> bool {
+        self.affinity == VENOM
+    }
+
+    /// The status effect consuming it gives, or that it gives
+    /// whoever it cuts if it's a poison coating a weapon
+    pub fn status_effect(&self, until_tick: u64) -> Option<StatusEffect> {
+        if self.is_poison() {
+            Some(StatusEffect {
+                name: POISONED.to_string(),
+                attribute: POISONED_ATTRIBUTE.to_string(),
+                amount: -self.amount,
+                until_tick,
+                stacks: 1,
+            })
+        } else if self.amount > 0 {
+            Some(StatusEffect {
+                name: self.name.clone(),
+                attribute: self.affinity.clone(),
+                amount: self.amount,
+                until_tick,
+                stacks: 1,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// A short description of what consuming it does
+    pub fn effect(&self) -> String {
+        if self.is_poison() {
+            return format!(
+                "saps {} {POISONED_ATTRIBUTE} from whoever it cuts, stacking with each wound",
+                self.amount
+            );
+        }
+
+        let mut effects = Vec::new();
+        if self.heal > 0 {
+            effects.push(format!("heals {}", self.heal));
+        }
+        if self.amount > 0 {
+            effects.push(format!(
+                "+{} {} for {:.0} seconds",
+                self.amount, self.affinity, self.duration_seconds
+            ));
+        }
+        effects.join(", ")
+    }
+}
+
+/// Every fixed recipe, for players to discover
+pub fn known_recipes() -> impl Iterator<Item = Recipe> {
+    KNOWN_RECIPES.iter().map(|(craft, ingredients, _, _)| {
+        Recipe::combine(*craft, ingredients)
+            .ok()
+            .and_then(Recipe::with_known_name)
+            .expect("Fixed recipes should be valid")
+    })
+}
+
+/// Order used to settle ties between affinities
+const ATTRIBUTE_ORDER: &[&str] = &[
+    "health",
+    "strength",
+    "toughness",
+    "agility",
+    "intelligence",
+    "willpower",
+    VENOM,
+];
+
+/// Ingredients that can be gathered somewhere with the given tags
+pub fn gatherable<'a>(tags: impl Iterator<Item = &'a str>) -> Vec<&'static Ingredient> {
+    let tags: Vec<_> = tags.collect();
+    INGREDIENTS
+        .iter()
+        .filter(|i| i.biomes.iter().any(|b| tags.contains(b)))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn combines_ingredients() {
+        let stew = Recipe::combine(Craft::Cooking, &["wild garlic", "Cave Mushroom"]).unwrap();
+        assert_eq!(stew.ingredients, vec!["Cave Mushroom", "Wild Garlic"]);
+        assert_eq!(stew.affinity, "toughness");
+        assert_eq!(stew.amount, 2);
+        assert_eq!(stew.with_known_name().unwrap().name, "Mushroom Stew");
+
+        let novel = Recipe::combine(Craft::Alchemy, &["Bone Dust", "Mint Leaf"]).unwrap();
+        assert_eq!(novel.affinity, "intelligence");
+        assert_eq!(novel.amount, 2);
+        assert!(novel.clone().with_known_name().is_none());
+        assert_eq!(
+            novel.key(),
+            Recipe::combine(Craft::Alchemy, &["Mint Leaf", "Bone Dust"])
+                .unwrap()
+                .key()
+        );
+
+        let poison = Recipe::combine(Craft::Alchemy, &["Spider Venom", "Nightshade"]).unwrap();
+        assert!(poison.is_poison());
+        assert_eq!(poison.status_effect(10).unwrap().amount, -2);
+        assert!(Recipe::combine(Craft::Cooking, &["Nightshade", "Spider Venom"]).is_err());
+
+        assert!(Recipe::combine(Craft::Cooking, &["Barley"]).is_err());
+        assert!(Recipe::combine(Craft::Cooking, &["Barley", "Torch"]).is_err());
+        assert_eq!(known_recipes().count(), KNOWN_RECIPES.len());
+    }
+}
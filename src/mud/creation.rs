@@ -0,0 +1,108 @@
+//! Character creation for a player's first time in a world. They pick a name and
+//! spread a pool of points over their attributes before waking up.
+
+use serde::{Deserialize, Serialize};
+
+use super::character::{Attribute, Attributes};
+
+/// What every attribute starts at before points are spent
+const STARTING_SCORE: i32 = 8;
+/// Points to spread across attributes, a little more than it takes to reach 10 everywhere
+pub const STARTING_POINTS: i32 = 12;
+/// No attribute can start higher than this
+const MAX_STARTING_SCORE: i32 = 15;
+
+/// How far through creating their character a player is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CreationStep {
+    Name,
+    /// Spending attribute points, with this many left
+    Attributes(i32),
+}
+
+impl CreationStep {
+    pub fn prompt(self, username: &str, attributes: &Attributes) -> String {
+        match self {
+            CreationStep::Name => format!(
+                "Before you wake, who are you? Enter your character's name, or nothing to go by {username}"
+            ),
+            CreationStep::Attributes(points) => format!(
+                "{}\nSpend your points like 'strength 3' or take them back like 'strength -1'. Enter 'done' when you're happy",
+                describe(attributes, points)
+            ),
+        }
+    }
+}
+
+pub fn starting_attributes() -> Attributes {
+    let mut attributes = Attributes::default();
+    for (_, attribute) in attributes.named_mut() {
+        *attribute = Attribute::new(STARTING_SCORE);
+    }
+    attributes
+}
+
+/// Moves points into or out of an attribute, returning how many points are left
+pub fn spend(
+    attributes: &mut Attributes,
+    points: i32,
+    name: &str,
+    amount: i32,
+) -> Result<i32, String> {
+    let Some((name, attribute)) = attributes
+        .named_mut()
+        .into_iter()
+        .find(|(n, _)| n.starts_with(&name.to_lowercase()) && !name.is_empty())
+    else {
+        return Err(format!("There's no attribute called {name}"));
+    };
+
+    let score = attribute.value().saturating_add(amount);
+    if amount > points {
+        Err(format!("You only have {points} points left"))
+    } else if score > MAX_STARTING_SCORE {
+        Err(format!(
+            "{name} can't start higher than {MAX_STARTING_SCORE}"
+        ))
+    } else if score < STARTING_SCORE {
+        Err(format!("{name} can't start lower than {STARTING_SCORE}"))
+    } else {
+        *attribute = Attribute::new(score);
+        Ok(points - amount)
+    }
+}
+
+pub fn describe(attributes: &Attributes, points: i32) -> String {
+    let mut res = String::new();
+    for (name, attribute) in attributes.named() {
+        res.push_str(&format!("{name:<15}{:>3}\n", attribute.value()));
+    }
+    res.push_str(&format!("\n{points} points left to spend"));
+    res
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn spends_points() {
+        let mut attributes = starting_attributes();
+        let mut points = STARTING_POINTS;
+
+        points = spend(&mut attributes, points, "str", 7).unwrap();
+        assert_eq!(attributes.strength.value(), 15);
+        assert!(spend(&mut attributes, points, "strength", 1).is_err());
+        assert!(spend(&mut attributes, points, "agility", -1).is_err());
+        assert!(spend(&mut attributes, points, "agility", 6).is_err());
+        assert!(spend(&mut attributes, points, "luck", 1).is_err());
+        assert!(spend(&mut attributes, i32::MAX, "agility", i32::MAX).is_err());
+        assert!(spend(&mut attributes, points, "agility", i32::MIN).is_err());
+
+        points = spend(&mut attributes, points, "Strength", -2).unwrap();
+        points = spend(&mut attributes, points, "willpower", 7).unwrap();
+        assert_eq!(points, 0);
+        assert_eq!(attributes.total(), 5 * STARTING_SCORE + STARTING_POINTS);
+    }
+}
@@ -0,0 +1,119 @@
+//! Stable IDs for the things in the world, creatures and pieces of equipment,
+//! so they can be told apart and referred to even when they share a name or
+//! get renamed. Players still type names, which are resolved to IDs here.
+
+use std::fmt::{self, Display};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    args::Selector,
+    state::{deserialize_u128_hex, serialize_u128_hex},
+};
+
+/// Identifies one particular creature or piece of equipment
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(transparent)]
+pub struct EntityId(
+    #[serde(
+        serialize_with = "serialize_u128_hex",
+        deserialize_with = "deserialize_u128_hex"
+    )]
+    u128,
+);
+
+impl EntityId {
+    pub fn new_random() -> Self {
+        Self(rand::random())
+    }
+
+    /// Reads an ID back from how it's displayed, for scripts handing them back
+    pub fn parse(s: &str) -> Option<Self> {
+        u128::from_str_radix(s, 16).ok().map(Self)
+    }
+}
+
+impl Default for EntityId {
+    fn default() -> Self {
+        Self::new_random()
+    }
+}
+
+impl Display for EntityId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:x}", self.0)
+    }
+}
+
+/// Picks out one of several named things from what a player typed. Names match
+/// ignoring case, whole names before ones that only start with the input, and
+/// '2.rat' picks the second match so things sharing a name can be told apart.
+/// Targets that pick several things, like 'all.rat', pick nothing here.
+pub fn resolve<'a>(
+    input: &str,
+    candidates: impl IntoIterator<Item = (EntityId, &'a str)>,
+) -> Option<EntityId> {
+    resolve_matching(input, candidates, true)
+}
+
+/// Like [`resolve`] but only whole names match, for checking several kinds of
+/// thing for a whole match before settling for a partial one
+pub fn resolve_exact<'a>(
+    input: &str,
+    candidates: impl IntoIterator<Item = (EntityId, &'a str)>,
+) -> Option<EntityId> {
+    resolve_matching(input, candidates, false)
+}
+
+fn resolve_matching<'a>(
+    input: &str,
+    candidates: impl IntoIterator<Item = (EntityId, &'a str)>,
+    prefixes: bool,
+) -> Option<EntityId> {
+    let Some(Selector::Nth(nth, name)) = Selector::parse(input) else {
+        return None;
+    };
+
+    let name = name.to_lowercase();
+    let candidates: Vec<_> = candidates.into_iter().collect();
+    let exact = candidates
+        .iter()
+        .filter(|(_, n)| n.to_lowercase() == name)
+        .map(|(id, _)| *id);
+    let prefix = candidates
+        .iter()
+        .filter(|(_, n)| {
+            let n = n.to_lowercase();
+            prefixes && n != name && n.starts_with(&name)
+        })
+        .map(|(id, _)| *id);
+
+    exact.chain(prefix).nth(nth - 1)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn resolves_names_to_ids() {
+        let (a, b, c) = (
+            EntityId::new_random(),
+            EntityId::new_random(),
+            EntityId::new_random(),
+        );
+        let things = [(a, "Cave Rat"), (b, "Cave Rat King"), (c, "cave rat")];
+
+        assert_eq!(resolve("cave rat", things), Some(a));
+        assert_eq!(resolve("2.cave rat", things), Some(c));
+        assert_eq!(resolve("3.cave rat", things), Some(b));
+        assert_eq!(resolve("cave", things), Some(a));
+        assert_eq!(resolve("4.cave rat", things), None);
+        assert_eq!(resolve("bat", things), None);
+        assert_eq!(resolve("0.cave rat", things), None);
+        assert_eq!(resolve_exact("cave", things), None);
+        assert_eq!(resolve_exact("2.cave rat", things), Some(c));
+
+        assert_eq!(EntityId::parse(&a.to_string()), Some(a));
+    }
+}
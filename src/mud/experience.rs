@@ -0,0 +1,82 @@
+//! Experience and levels. Characters earn experience knocking out opponents and
+//! exploring places they've never been, and every level brings a little more
+//! health and a point on one of their attributes, strongest first so each
+//! character grows into what they're already good at.
+
+use super::character::Attributes;
+
+pub const MAX_LEVEL: u32 = 20;
+/// Experience for setting foot somewhere for the first time
+pub const EXPLORE_XP: u32 = 10;
+/// Experience for being the first to explore every corner of an area
+pub const DISCOVERY_XP: u32 = 100;
+/// Experience for knocking someone out, for each of their levels
+pub const KNOCKOUT_XP_PER_LEVEL: u32 = 25;
+/// Extra health for each level past the first
+pub const HEALTH_PER_LEVEL: i32 = 2;
+
+/// The total experience needed to reach a level, the first needs none
+pub fn threshold(level: u32) -> u32 {
+    let level = level.clamp(1, MAX_LEVEL);
+    100 * (level - 1) * level / 2
+}
+
+/// The level a total amount of experience reaches
+pub fn level_for(experience: u32) -> u32 {
+    (2..=MAX_LEVEL)
+        .take_while(|l| threshold(*l) <= experience)
+        .last()
+        .unwrap_or(1)
+}
+
+/// The attribute reaching a level raises. Levels go round the attributes from
+/// the character's strongest to their weakest.
+pub fn attribute_for(level: u32, attributes: &Attributes) -> &'static str {
+    let mut named = attributes.named();
+    named.sort_by_key(|(_, a)| std::cmp::Reverse(*a));
+    named[(level.saturating_sub(2) as usize) % named.len()].0
+}
+
+/// A level a character reached and the attribute it raised
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LevelUp {
+    pub level: u32,
+    pub attribute: &'static str,
+}
+
+#[cfg(test)]
+mod test {
+    use crate::mud::character::{Attribute, Character};
+
+    use super::*;
+
+    #[test]
+    fn levels_up() {
+        assert_eq!([1, 2, 3, 4].map(threshold), [0, 100, 300, 600]);
+        assert_eq!([0, 99, 100, 650].map(level_for), [1, 1, 2, 4]);
+        assert_eq!(level_for(u32::MAX), MAX_LEVEL);
+
+        let mut ada = Character::default();
+        ada.attributes.agility = Attribute::new(14);
+        let health = ada.max_health();
+
+        assert!(ada.gain_experience(50).is_empty());
+        assert_eq!(
+            ada.gain_experience(260),
+            [
+                LevelUp {
+                    level: 2,
+                    attribute: "agility"
+                },
+                LevelUp {
+                    level: 3,
+                    attribute: "strength"
+                }
+            ]
+        );
+        assert_eq!(ada.level(), 3);
+        assert_eq!(ada.attributes.agility.value(), 15);
+        assert_eq!(ada.max_health(), health + 4);
+        assert_eq!(ada.health, ada.max_health());
+    }
+}
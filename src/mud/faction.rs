@@ -0,0 +1,142 @@
+//! Village councils, which tax the market, fine lawbreakers and take tribute from
+//! the explored dungeons around them, then spend it on guards and festivals. How
+//! full the treasury is shows in how the village looks and how well it's guarded.
+
+use serde::{Deserialize, Serialize};
+
+/// What a council has to spend when it's first founded
+const STARTING_TREASURY: u32 = 100;
+/// What a festival costs the council
+pub const FESTIVAL_COST: u32 = 150;
+
+/// The council that runs a village, keyed in the world by the village's overworld place
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct Faction {
+    /// Gold the council has to spend
+    pub treasury: u32,
+    /// Everything paid in since the council was founded
+    pub collected: u32,
+}
+
+impl Default for Faction {
+    fn default() -> Self {
+        Self {
+            treasury: STARTING_TREASURY,
+            collected: 0,
+        }
+    }
+}
+
+impl Faction {
+    pub fn prosperity(&self) -> Prosperity {
+        Prosperity::from_treasury(self.treasury)
+    }
+
+    pub fn deposit(&mut self, gold: u32) {
+        self.treasury += gold;
+        self.collected += gold;
+    }
+
+    /// Spends gold if the treasury has it, returning whether it did
+    pub fn spend(&mut self, gold: u32) -> bool {
+        if self.treasury < gold {
+            return false;
+        }
+        self.treasury -= gold;
+        true
+    }
+}
+
+/// How well off a village is, going by its council's treasury
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Prosperity {
+    Struggling,
+    Modest,
+    Thriving,
+    Wealthy,
+}
+
+impl Prosperity {
+    pub fn from_treasury(treasury: u32) -> Self {
+        match treasury {
+            0..=49 => Prosperity::Struggling,
+            50..=249 => Prosperity::Modest,
+            250..=599 => Prosperity::Thriving,
+            _ => Prosperity::Wealthy,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Prosperity::Struggling => "struggling",
+            Prosperity::Modest => "getting by",
+            Prosperity::Thriving => "thriving",
+            Prosperity::Wealthy => "wealthy",
+        }
+    }
+
+    /// How much better than usual the guards are at catching lawbreakers
+    pub fn guard_bonus(self) -> i32 {
+        match self {
+            Prosperity::Struggling => -3,
+            Prosperity::Modest => 0,
+            Prosperity::Thriving => 2,
+            Prosperity::Wealthy => 4,
+        }
+    }
+
+    /// The chance the council throws a festival each time it meets, if it can afford one
+    pub fn festival_chance(self) -> f64 {
+        match self {
+            Prosperity::Struggling => 0.0,
+            Prosperity::Modest => 0.05,
+            Prosperity::Thriving => 0.15,
+            Prosperity::Wealthy => 0.3,
+        }
+    }
+
+    /// A line added to the description of every place in the village
+    pub fn description(self) -> &'static str {
+        match self {
+            Prosperity::Struggling => {
+                "Paint peels from the shutters and the few guards about look underfed."
+            }
+            Prosperity::Modest => "",
+            Prosperity::Thriving => {
+                "Fresh paint brightens the doors and well kept guards walk their rounds."
+            }
+            Prosperity::Wealthy => {
+                "Banners hang from polished posts and guards in bright livery watch every corner."
+            }
+        }
+    }
+}
+
+/// The council's cut of a sale
+pub fn tax(price: u32, rate: f64) -> u32 {
+    (price as f64 * rate).round() as u32
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn treasury_sets_prosperity() {
+        let mut faction = Faction::default();
+        assert_eq!(faction.prosperity(), Prosperity::Modest);
+
+        faction.deposit(500);
+        assert_eq!(faction.prosperity(), Prosperity::Wealthy);
+        assert_eq!(faction.collected, 500);
+
+        assert!(faction.spend(580));
+        assert!(!faction.spend(21));
+        assert_eq!(faction.prosperity(), Prosperity::Struggling);
+        assert!(Prosperity::Struggling.guard_bonus() < Prosperity::Wealthy.guard_bonus());
+
+        assert_eq!(tax(15, 0.1), 2);
+        assert_eq!(tax(4, 0.1), 0);
+    }
+}
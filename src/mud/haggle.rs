@@ -0,0 +1,141 @@
+use rand::Rng;
+
+use super::{character::Attribute, entity::EntityId, relationship::Standing};
+
+/// What a pitch has to beat to win a vendor over
+const PERSUASION_DC: i32 = 12;
+/// The most a vendor will ever pay over an item's value, as a fraction of it
+const MAX_MARKUP: f64 = 0.5;
+/// What a vendor knocks off an item's value when the pitch falls flat
+const FAILED_DISCOUNT: f64 = 0.25;
+/// Discoveries past this don't make a seller any more famous
+const MAX_REPUTATION: usize = 5;
+
+/// A player trying to talk a vendor up. The pitch and reply are free text but the
+/// price always ends up between the limits worked out here.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Haggle {
+    pub shopkeeper: String,
+    pub item: String,
+    /// The particular piece of equipment on offer, in case others share its name
+    pub item_id: EntityId,
+    pub pitch: String,
+    /// What the vendor would pay without any haggling
+    pub value: u32,
+    /// How the shopkeeper feels about the seller
+    pub standing: Standing,
+    pub persuaded: bool,
+    pub min: u32,
+    pub max: u32,
+}
+
+impl Haggle {
+    /// Rolls the seller's intelligence and reputation against the vendor to set how far the
+    /// price can move, a convincing seller can raise it while a poor pitch lowers it.
+    /// Shopkeepers who like the seller will go further than they would for anyone else.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        shopkeeper: String,
+        item: String,
+        item_id: EntityId,
+        pitch: String,
+        value: u32,
+        standing: Standing,
+        intelligence: Attribute,
+        reputation: usize,
+        rng: &mut impl Rng,
+    ) -> Self {
+        let reputation = reputation.min(MAX_REPUTATION);
+        let roll = rng.gen_range(1..=20) + intelligence.modifier() + reputation as i32;
+        let persuaded = roll >= PERSUASION_DC;
+
+        let (min, max) = if persuaded {
+            let markup = (0.1 * intelligence.modifier().max(0) as f64 + 0.05 * reputation as f64)
+                .clamp(0.1, MAX_MARKUP)
+                + standing.markup();
+            (value, (value as f64 * (1.0 + markup)).ceil() as u32)
+        } else {
+            (
+                (value as f64 * (1.0 - FAILED_DISCOUNT)).floor() as u32,
+                value,
+            )
+        };
+
+        Self {
+            shopkeeper,
+            item,
+            item_id,
+            pitch,
+            value,
+            standing,
+            persuaded,
+            min,
+            max,
+        }
+    }
+
+    /// Brings whatever price the vendor named back within the limits
+    pub fn settle(&self, offer: u32) -> u32 {
+        offer.clamp(self.min, self.max)
+    }
+}
+
+/// A price a vendor has agreed to pay for a piece of equipment
+#[derive(Debug, Clone, PartialEq)]
+pub struct Offer {
+    pub item: EntityId,
+    /// What the item was called when the offer was made
+    pub name: String,
+    pub price: u32,
+}
+
+#[cfg(test)]
+mod test {
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn prices_stay_in_bounds() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+        for seed_round in 0..50 {
+            let haggle = Haggle::new(
+                "the innkeeper".into(),
+                "Fine Iron Sword".into(),
+                EntityId::new_random(),
+                format!("Pitch {seed_round}"),
+                20,
+                Standing::Stranger,
+                Attribute::new(18),
+                3,
+                &mut rng,
+            );
+
+            if haggle.persuaded {
+                assert_eq!((haggle.min, haggle.max), (20, 30));
+            } else {
+                assert_eq!((haggle.min, haggle.max), (15, 20));
+            }
+            assert_eq!(haggle.settle(1000), haggle.max);
+            assert_eq!(haggle.settle(0), haggle.min);
+        }
+
+        let friendly = (0..50)
+            .map(|_| {
+                Haggle::new(
+                    "the innkeeper".into(),
+                    "Fine Iron Sword".into(),
+                    EntityId::new_random(),
+                    String::new(),
+                    20,
+                    Standing::Confidant,
+                    Attribute::new(18),
+                    3,
+                    &mut rng,
+                )
+            })
+            .find(|h| h.persuaded)
+            .unwrap();
+        assert_eq!(friendly.max, 34);
+    }
+}
@@ -0,0 +1,94 @@
+//! A short memory of what's happened in each place, who came and went, who
+//! was knocked out and what was said. The oldest events are forgotten as new
+//! ones come in. Shopkeepers bring up what they've seen when they talk, and
+//! admins can read it back to find out what went on somewhere.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::{self, Display},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::world::{Direction, Location};
+
+/// How many of the latest events in their place an NPC brings up when talking
+pub const RECALLED_EVENTS: usize = 5;
+
+/// Something that happened in a place, and when
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct PlaceEvent {
+    pub tick: u64,
+    pub kind: EventKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EventKind {
+    Arrived(String),
+    Left(String, Direction),
+    /// Who spoke and what they said
+    Said(String, String),
+    /// Who was knocked out and who by
+    KnockedOut(String, String),
+}
+
+impl Display for EventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EventKind::Arrived(name) => write!(f, "{name} arrived"),
+            EventKind::Left(name, direction) => {
+                write!(f, "{name} left heading {}", direction.name())
+            }
+            EventKind::Said(name, text) => write!(f, "{name} said \"{text}\""),
+            EventKind::KnockedOut(name, by) => write!(f, "{by} knocked out {name}"),
+        }
+    }
+}
+
+/// Adds an event to a place's history, forgetting the oldest ones past the limit
+pub fn record(history: &mut VecDeque<PlaceEvent>, event: PlaceEvent, limit: usize) {
+    history.push_back(event);
+    while history.len() > limit {
+        history.pop_front();
+    }
+}
+
+/// The place where the most people were knocked out and how many, if anyone was
+pub fn bloodiest(history: &HashMap<Location, VecDeque<PlaceEvent>>) -> Option<(Location, usize)> {
+    history
+        .iter()
+        .map(|(location, events)| {
+            let knockouts = events
+                .iter()
+                .filter(|e| matches!(e.kind, EventKind::KnockedOut(..)))
+                .count();
+            (*location, knockouts)
+        })
+        .filter(|(_, knockouts)| *knockouts > 0)
+        .max_by_key(|(_, knockouts)| *knockouts)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn forgets_old_events() {
+        let mut history = VecDeque::new();
+        for tick in 0..5 {
+            let event = PlaceEvent {
+                tick,
+                kind: EventKind::Arrived(format!("Ada {tick}")),
+            };
+            record(&mut history, event, 3);
+        }
+        assert_eq!(history.len(), 3);
+        assert_eq!(history[0].tick, 2);
+        assert_eq!(history[2].kind.to_string(), "Ada 4 arrived");
+
+        let knockout = EventKind::KnockedOut("Ada".into(), "Bryn".into());
+        assert_eq!(knockout.to_string(), "Bryn knocked out Ada");
+    }
+}
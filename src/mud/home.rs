@@ -0,0 +1,73 @@
+//! Places admins have deeded to players as their homes. A homeowner can
+//! decorate theirs with some words added to how it looks and a cosmetic script
+//! for greetings and ambient messages. Decorations wait for an admin to look
+//! them over before anyone else sees them.
+
+use serde::{Deserialize, Serialize};
+
+use crate::state::PlayerId;
+
+/// The longest look text a homeowner can add
+pub const MAX_LOOK_LENGTH: usize = 500;
+/// The most lines a decoration script can have
+pub const MAX_SCRIPT_LINES: usize = 100;
+
+/// What a homeowner's added to their home
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Decoration {
+    /// Added to the home's description when someone looks around
+    pub look: Option<String>,
+    /// Lua run for things happening in the home, with only the functions a
+    /// player submitted script is trusted with
+    pub script: Option<String>,
+}
+
+impl Decoration {
+    pub fn is_empty(&self) -> bool {
+        self.look.is_none() && self.script.is_none()
+    }
+
+    pub fn describe(&self) -> String {
+        if self.is_empty() {
+            return "Nothing\n".to_string();
+        }
+        let mut res = String::new();
+        if let Some(look) = &self.look {
+            res.push_str(&format!("Look: {look}\n"));
+        }
+        if let Some(script) = &self.script {
+            res.push_str(&format!("Script:\n{script}\n"));
+        }
+        res
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Home {
+    pub owner: PlayerId,
+    pub owner_name: String,
+    /// What everyone sees, approved by an admin
+    #[serde(default)]
+    pub decoration: Decoration,
+    /// Changes waiting for an admin to approve
+    #[serde(default)]
+    pub pending: Option<Decoration>,
+}
+
+impl Home {
+    pub fn new(owner: PlayerId, owner_name: String) -> Self {
+        Self {
+            owner,
+            owner_name,
+            decoration: Decoration::default(),
+            pending: None,
+        }
+    }
+
+    /// The decoration the owner's working on, starting from what's approved
+    pub fn draft(&mut self) -> &mut Decoration {
+        self.pending.get_or_insert_with(|| self.decoration.clone())
+    }
+}
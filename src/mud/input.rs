@@ -0,0 +1,79 @@
+//! What happens to the lines a player types. Most run straight away, but
+//! while the player's busy or waiting on someone they line up and run in
+//! order once the player's free, and menus take the next line as an answer.
+
+use std::collections::VecDeque;
+
+/// How many commands a player can have lined up at once
+pub const MAX_QUEUED: usize = 10;
+
+/// Whether the player's next line can run now
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InputState {
+    Ready,
+    /// In the middle of an action, like crafting
+    Busy(String),
+    /// Waiting for an NPC to answer
+    Prompting(String),
+    /// Answering a menu, which takes the next line whatever it is
+    Menu,
+}
+
+impl InputState {
+    /// Tells the player when a line they typed will run
+    pub fn holding_note(&self) -> String {
+        match self {
+            InputState::Ready | InputState::Menu => "You'll do that next".to_string(),
+            InputState::Busy(verb) => format!("You'll do that once you're done {verb}"),
+            InputState::Prompting(npc) => format!("You'll do that once {npc} has finished"),
+        }
+    }
+}
+
+/// Lines waiting for the player to be ready, oldest first
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct InputQueue(VecDeque<String>);
+
+impl InputQueue {
+    /// Lines up a command, unless there's already too much lined up
+    pub fn push(&mut self, line: String) -> bool {
+        if self.0.len() >= MAX_QUEUED {
+            return false;
+        }
+        self.0.push_back(line);
+        true
+    }
+
+    pub fn pop(&mut self) -> Option<String> {
+        self.0.pop_front()
+    }
+
+    /// Forgets everything lined up, returning how much there was
+    pub fn clear(&mut self) -> usize {
+        let count = self.0.len();
+        self.0.clear();
+        count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn queues_in_order() {
+        let mut queue = InputQueue::default();
+        assert!(queue.push("look".into()));
+        assert!(queue.push("north".into()));
+        assert_eq!(queue.pop().as_deref(), Some("look"));
+
+        while queue.push("wait".into()) {}
+        assert_eq!(queue.clear(), MAX_QUEUED);
+        assert!(queue.is_empty());
+        assert_eq!(queue.pop(), None);
+    }
+}
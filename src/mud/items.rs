@@ -1,5 +1,27 @@
-use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
+use serde::{Deserialize, Deserializer, Serialize};
+
+use super::entity::{self, EntityId};
+
+/// What enchanting uses up and disenchanting gives back
+pub const ESSENCE: &str = "Arcane Essence";
+/// Essence needed for the first enchantment, each one after costs double the last
+const BASE_ENCHANT_COST: u32 = 2;
+/// The most an item can be enchanted
+pub const MAX_ENCHANTMENTS: usize = 5;
+/// The chance loot that's fine or better is found already enchanted. Breaking
+/// it down is where everyone's first essence comes from.
+pub const MAGIC_LOOT_CHANCE: f64 = 0.4;
+/// How many uses common quality equipment lasts
+const BASE_DURABILITY: u32 = 40;
+/// What vendors pay for each commodity in a stack
+pub const COMMODITY_VALUE: u32 = 1;
+/// How much each commodity in a stack weighs
+pub const COMMODITY_WEIGHT: u32 = 1;
+
+/// What an item is, shared by every copy of it. Equipment is carried as
+/// [`ItemInstance`]s pointing back at one of these by name.
 #[derive(Debug, Hash, PartialEq, Eq, Default, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case", default)]
 pub struct Item {
@@ -8,6 +30,292 @@ pub struct Item {
     pub weight: u32,
 }
 
+/// How well a piece of equipment was made, better made things last longer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Quality {
+    Crude,
+    Common,
+    Fine,
+    Masterwork,
+}
+
+impl Quality {
+    pub fn name(self) -> &'static str {
+        match self {
+            Quality::Crude => "Crude",
+            Quality::Common => "Common",
+            Quality::Fine => "Fine",
+            Quality::Masterwork => "Masterwork",
+        }
+    }
+
+    /// What vendors pay for a piece of equipment this well made
+    fn value(self) -> u32 {
+        match self {
+            Quality::Crude => 1,
+            Quality::Common => 2,
+            Quality::Fine => 4,
+            Quality::Masterwork => 8,
+        }
+    }
+
+    fn max_durability(self) -> u32 {
+        match self {
+            Quality::Crude => BASE_DURABILITY / 2,
+            Quality::Common => BASE_DURABILITY,
+            Quality::Fine => BASE_DURABILITY * 3 / 2,
+            Quality::Masterwork => BASE_DURABILITY * 2,
+        }
+    }
+}
+
+/// One particular piece of equipment, so two swords made from the same
+/// template can wear down, be enchanted and be named differently
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ItemInstance {
+    pub id: EntityId,
+    /// The name of the [`Item`] this is a copy of
+    pub template: String,
+    pub quality: Quality,
+    pub durability: u32,
+    pub max_durability: u32,
+    /// Layers of enchantment, oldest first
+    #[serde(default)]
+    pub enchantments: Vec<Enchantment>,
+    /// A name the owner has given it, used instead of the template's
+    #[serde(default)]
+    pub engraved: Option<String>,
+}
+
+/// Equipment in hand as it was saved before each piece was its own instance,
+/// a whole item named for how enchanted it was
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct LegacyWielded {
+    name: String,
+    #[serde(default)]
+    base_name: String,
+    #[serde(default)]
+    enchantments: Vec<Enchantment>,
+}
+
+/// Reads what a character's wielding, turning equipment saved the old way
+/// into a common quality instance of what it was made from
+pub fn deserialize_wielding<'de, D>(deserializer: D) -> Result<Option<ItemInstance>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Stored {
+        Instance(ItemInstance),
+        Legacy(LegacyWielded),
+    }
+
+    Ok(match Option::<Stored>::deserialize(deserializer)? {
+        Some(Stored::Instance(item)) => Some(item),
+        Some(Stored::Legacy(legacy)) => {
+            let template = match legacy.base_name.is_empty() {
+                true => legacy.name,
+                false => legacy.base_name,
+            };
+            let mut item = ItemInstance::new(&template, Quality::Common);
+            item.enchantments = legacy.enchantments;
+            Some(item)
+        }
+        None => None,
+    })
+}
+
+/// One layer of enchantment, raising an attribute while the item is wielded
+#[derive(Debug, Hash, PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Enchantment {
+    pub attribute: String,
+    pub amount: i32,
+}
+
+impl ItemInstance {
+    pub fn new(template: &str, quality: Quality) -> Self {
+        Self {
+            id: EntityId::new_random(),
+            template: template.to_string(),
+            quality,
+            durability: quality.max_durability(),
+            max_durability: quality.max_durability(),
+            enchantments: Vec::new(),
+            engraved: None,
+        }
+    }
+
+    /// What players see it called, its engraved name or its quality,
+    /// template and how enchanted it is
+    pub fn name(&self) -> String {
+        if let Some(engraved) = &self.engraved {
+            return engraved.clone();
+        }
+
+        let mut name = match self.quality {
+            Quality::Common => self.template.clone(),
+            quality => format!("{} {}", quality.name(), self.template),
+        };
+        if !self.enchantments.is_empty() {
+            name.push_str(&format!(" +{}", self.enchantments.len()));
+        }
+        name
+    }
+
+    /// Whether players could mean this item by the name, either what it's
+    /// called or the template it was made from
+    pub fn matches(&self, name: &str) -> bool {
+        self.name().eq_ignore_ascii_case(name) || self.template.eq_ignore_ascii_case(name)
+    }
+
+    /// Neither enchanted nor engraved, one of many like it
+    pub fn is_plain(&self) -> bool {
+        self.enchantments.is_empty() && self.engraved.is_none()
+    }
+
+    /// Broken equipment does nothing for its wielder until it's mended
+    pub fn is_broken(&self) -> bool {
+        self.durability == 0
+    }
+
+    /// Wears the item down, returning true if that's what broke it
+    pub fn wear(&mut self, amount: u32) -> bool {
+        let was_broken = self.is_broken();
+        self.durability = self.durability.saturating_sub(amount);
+        !was_broken && self.is_broken()
+    }
+
+    /// Enchantments that count while it's wielded, none if it's broken
+    pub fn active_enchantments(&self) -> impl Iterator<Item = &Enchantment> {
+        self.enchantments.iter().filter(|_| !self.is_broken())
+    }
+
+    /// What vendors pay for it, nothing once it's broken
+    pub fn value(&self) -> u32 {
+        if self.is_broken() {
+            return 0;
+        }
+
+        let magic = if self.enchantments.is_empty() {
+            0
+        } else {
+            self.essence_value()
+        };
+        self.quality.value() + magic
+    }
+
+    /// How this item stacks up against another, usually whatever's being wielded
+    pub fn compare(&self, other: Option<&ItemInstance>) -> String {
+        let Some(other) = other else {
+            return format!(
+                "You aren't holding anything, the {} would be an improvement\n\n{}",
+                self.name(),
+                self.describe(None)
+            );
+        };
+
+        let mut res = format!("{} compared to your {}\n", self.name(), other.name());
+        res.push_str(&format!(
+            "\n{:14}{:>12}{:>12}",
+            "Quality",
+            self.quality.name(),
+            other.quality.name()
+        ));
+        res.push_str(&format!(
+            "\n{:14}{:>12}{:>12}",
+            "Durability",
+            format!("{}/{}", self.durability, self.max_durability),
+            format!("{}/{}", other.durability, other.max_durability)
+        ));
+
+        let mut attributes: Vec<_> = self
+            .enchantments
+            .iter()
+            .chain(&other.enchantments)
+            .map(|e| e.attribute.as_str())
+            .collect();
+        attributes.sort_unstable();
+        attributes.dedup();
+        for attribute in attributes {
+            let bonus = |item: &ItemInstance| -> i32 {
+                item.active_enchantments()
+                    .filter(|e| e.attribute == attribute)
+                    .map(|e| e.amount)
+                    .sum()
+            };
+            let (mine, theirs) = (bonus(self), bonus(other));
+            res.push_str(&format!(
+                "\n{:14}{:>12}{:>12}  {:+}",
+                attribute,
+                format!("{mine:+}"),
+                format!("{theirs:+}"),
+                mine - theirs
+            ));
+        }
+
+        res
+    }
+
+    /// Essence needed to add the next layer of enchantment
+    pub fn enchant_cost(&self) -> u32 {
+        BASE_ENCHANT_COST << self.enchantments.len()
+    }
+
+    /// Chance the next enchantment fails, higher the more layers there already are
+    pub fn enchant_failure_chance(&self) -> f64 {
+        (self.enchantments.len() as f64 * 0.15).min(0.75)
+    }
+
+    /// Essence recovered by breaking the item down, half of what went into it
+    pub fn essence_value(&self) -> u32 {
+        let spent: u32 = (0..self.enchantments.len())
+            .map(|i| BASE_ENCHANT_COST << i)
+            .sum();
+        (spent / 2).max(1)
+    }
+
+    /// Adds another layer of enchantment
+    pub fn enchant(&mut self, attribute: &str) {
+        self.enchantments.push(Enchantment {
+            attribute: attribute.to_string(),
+            amount: 1,
+        });
+    }
+
+    /// The item's name, condition and enchantments, with the template's
+    /// description if it's a known item
+    pub fn describe(&self, template: Option<&Item>) -> String {
+        let mut res = self.name();
+        if self.engraved.is_some() {
+            res.push_str(&format!(" ({})", self.template));
+        }
+        if let Some(template) = template {
+            res.push_str(&format!("\n\n{}", template.description));
+        }
+        res.push_str(&format!(
+            "\n\n{} quality, durability {}/{}",
+            self.quality.name(),
+            self.durability,
+            self.max_durability
+        ));
+        if self.is_broken() {
+            res.push_str(", broken");
+        }
+        for enchantment in &self.enchantments {
+            res.push_str(&format!(
+                "\n  +{} {}",
+                enchantment.amount, enchantment.attribute
+            ));
+        }
+        res
+    }
+}
+
 #[derive(Debug, Hash, PartialEq, Eq, Default, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case", default)]
 pub struct ItemStack {
@@ -15,6 +323,7 @@ pub struct ItemStack {
     pub count: u32,
 }
 
+#[allow(dead_code)]
 impl ItemStack {
     pub fn new(name: String, count: u32) -> Self {
         Self { name, count }
@@ -24,9 +333,13 @@ impl ItemStack {
 #[derive(Debug, Default, PartialEq, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case", default)]
 pub struct Inventory {
+    /// Commodities, which stack because every one is the same
     items: Vec<ItemStack>,
+    /// Equipment, kept apart because each piece can differ
+    equipment: Vec<ItemInstance>,
 }
 
+#[allow(dead_code)]
 impl Inventory {
     pub fn get(&self, name: &str) -> Option<&ItemStack> {
         self.items.iter().find(|i| i.name == name)
@@ -71,7 +384,472 @@ impl Inventory {
         }
     }
 
-    pub fn total_weight(&mut self) -> u32 {
-        self.items.iter().map(|i| i.count).sum()
+    /// A copy of the inventory with each stack cut down to a fraction of its size,
+    /// keeping the same fraction of equipment starting with the best made
+    pub fn scaled(&self, fraction: f64) -> Inventory {
+        let items = self
+            .items
+            .iter()
+            .map(|i| ItemStack::new(i.name.clone(), (i.count as f64 * fraction).floor() as u32))
+            .filter(|i| i.count > 0)
+            .collect();
+
+        let mut equipment = self.equipment.clone();
+        equipment.sort_by_key(|i| std::cmp::Reverse(i.quality));
+        equipment.truncate((self.equipment.len() as f64 * fraction).floor() as usize);
+
+        Inventory { items, equipment }
+    }
+
+    /// Takes a fraction of the inventory out, picked the same way as [`Inventory::scaled`]
+    pub fn take_share(&mut self, fraction: f64) -> Inventory {
+        let share = self.scaled(fraction);
+        for stack in &share.items {
+            self.remove(&stack.name, stack.count);
+        }
+        self.equipment
+            .retain(|e| !share.equipment.iter().any(|s| s.id == e.id));
+        share
+    }
+
+    pub fn stacks(&self) -> impl Iterator<Item = &ItemStack> {
+        self.items.iter()
+    }
+
+    /// Finds a stack ignoring case, whole names before ones that only start with the input
+    pub fn find_stack(&self, name: &str) -> Option<&ItemStack> {
+        let name = name.trim().to_lowercase();
+        if name.is_empty() {
+            return None;
+        }
+        self.items
+            .iter()
+            .find(|i| i.name.to_lowercase() == name)
+            .or_else(|| {
+                self.items
+                    .iter()
+                    .find(|i| i.name.to_lowercase().starts_with(&name))
+            })
+    }
+
+    /// The names of every stack and the templates of every piece of equipment
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.items
+            .iter()
+            .map(|i| i.name.as_str())
+            .chain(self.equipment.iter().map(|i| i.template.as_str()))
+    }
+
+    /// How much everything weighs, given what one of each item weighs
+    pub fn total_weight(&self, weight_of: impl Fn(&str) -> u32) -> u32 {
+        let stacks: u32 = self
+            .items
+            .iter()
+            .map(|i| weight_of(&i.name) * i.count)
+            .sum();
+        let equipment: u32 = self.equipment.iter().map(|i| weight_of(&i.template)).sum();
+        stacks + equipment
+    }
+
+    pub fn equipment(&self) -> &[ItemInstance] {
+        &self.equipment
+    }
+
+    pub fn add_instance(&mut self, item: ItemInstance) {
+        self.equipment.push(item);
+    }
+
+    /// Turns stacks of equipment, from saves made before each piece was its own
+    /// instance, into common quality instances. Returns how many were turned.
+    pub fn unstack_equipment(&mut self, is_equipment: impl Fn(&str) -> bool) -> u32 {
+        let (equipment, commodities) = std::mem::take(&mut self.items)
+            .into_iter()
+            .partition::<Vec<_>, _>(|s| is_equipment(&s.name));
+        self.items = commodities;
+
+        let mut turned = 0;
+        for stack in equipment {
+            for _ in 0..stack.count {
+                self.add_instance(ItemInstance::new(&stack.name, Quality::Common));
+            }
+            turned += stack.count;
+        }
+        turned
+    }
+
+    /// Finds a piece of equipment by name, preferring an exact match
+    /// over one that was only made from a template with that name
+    pub fn find_instance(&self, name: &str) -> Option<&ItemInstance> {
+        self.instance_idx(name).map(|idx| &self.equipment[idx])
+    }
+
+    pub fn find_instance_mut(&mut self, name: &str) -> Option<&mut ItemInstance> {
+        self.instance_idx(name).map(|idx| &mut self.equipment[idx])
     }
-}
\ No newline at end of file
+
+    /// Removes every stack and piece of equipment the filter picks by name or template,
+    /// returning what they were worth. Enchanted and engraved pieces are kept, they're
+    /// only sold when they're asked for by name, see [`Inventory::sell_named`].
+    pub fn sell(&mut self, mut filter: impl FnMut(&str) -> bool) -> (Vec<String>, u32) {
+        let mut sold = Vec::new();
+        let mut value = 0;
+
+        self.items.retain(|stack| {
+            let selling = filter(&stack.name);
+            if selling {
+                sold.push(format!("{} x{}", stack.name, stack.count));
+                value += stack.count * COMMODITY_VALUE;
+            }
+            !selling
+        });
+        self.equipment.retain(|item| {
+            let selling = item.is_plain() && filter(&item.template);
+            if selling {
+                sold.push(item.name());
+                value += item.value();
+            }
+            !selling
+        });
+
+        (sold, value)
+    }
+
+    /// Removes up to `count` of one thing, the whole stack or a single piece of equipment
+    /// if there's no count, returning what was sold and what it was worth. Equipment is
+    /// picked by what it's called, or by its template if it's neither enchanted nor engraved.
+    pub fn sell_named(&mut self, name: &str, count: Option<u32>) -> Option<(Vec<String>, u32)> {
+        let name = name.trim();
+        let named: Vec<_> = self
+            .equipment
+            .iter()
+            .filter(|i| i.name().eq_ignore_ascii_case(name))
+            .map(|i| i.id)
+            .collect();
+        let stack = self
+            .items
+            .iter()
+            .find(|i| i.name.eq_ignore_ascii_case(name))
+            .cloned();
+
+        let pieces = match (named.is_empty(), stack) {
+            (true, Some(stack)) => {
+                let count = count.unwrap_or(stack.count).min(stack.count);
+                if count == 0 || !self.remove(&stack.name, count) {
+                    return None;
+                }
+                let sold = format!("{} x{count}", stack.name);
+                return Some((vec![sold], count * COMMODITY_VALUE));
+            }
+            (true, None) => self
+                .equipment
+                .iter()
+                .filter(|i| i.is_plain() && i.template.eq_ignore_ascii_case(name))
+                .map(|i| i.id)
+                .collect(),
+            (false, _) => named,
+        };
+
+        let (sold, value) = pieces
+            .into_iter()
+            .take(count.unwrap_or(1) as usize)
+            .filter_map(|id| self.take_by_id(id))
+            .fold((Vec::new(), 0), |(mut sold, value), item| {
+                sold.push(item.name());
+                (sold, value + item.value())
+            });
+        (!sold.is_empty()).then_some((sold, value))
+    }
+
+    /// Moves a piece of equipment or some of a stack into another inventory, the whole
+    /// stack if no count is given. Returns a description of what was moved.
+    pub fn give(&mut self, to: &mut Inventory, name: &str, count: Option<u32>) -> Option<String> {
+        if let Some(item) = self.take_instance(name) {
+            let moved = item.name();
+            to.add_instance(item);
+            return Some(moved);
+        }
+
+        let stack = self
+            .items
+            .iter()
+            .find(|i| i.name.eq_ignore_ascii_case(name))?
+            .clone();
+        let count = count.unwrap_or(stack.count);
+        if !self.remove(&stack.name, count) {
+            return None;
+        }
+        to.add(&stack.name, count);
+
+        Some(match count {
+            1 => stack.name,
+            count => format!("{} x{count}", stack.name),
+        })
+    }
+
+    /// Moves everything in another inventory into this one
+    pub fn append(&mut self, other: Inventory) {
+        for stack in other.items {
+            self.add(&stack.name, stack.count);
+        }
+        self.equipment.extend(other.equipment);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty() && self.equipment.is_empty()
+    }
+
+    /// How many of each item there are, counting equipment by what it was made from
+    pub fn counts(&self) -> BTreeMap<String, i64> {
+        let mut res = BTreeMap::<String, i64>::new();
+        for stack in &self.items {
+            *res.entry(stack.name.clone()).or_default() += stack.count as i64;
+        }
+        for piece in &self.equipment {
+            *res.entry(piece.template.clone()).or_default() += 1;
+        }
+        res
+    }
+
+    /// Takes every stack and piece of equipment of an item out, returning how many went
+    pub fn remove_all(&mut self, name: &str) -> u32 {
+        let mut removed = 0;
+        self.items.retain(|stack| {
+            let keep = !stack.name.eq_ignore_ascii_case(name);
+            if !keep {
+                removed += stack.count;
+            }
+            keep
+        });
+
+        let pieces = self.equipment.len();
+        self.equipment
+            .retain(|e| !e.template.eq_ignore_ascii_case(name));
+        removed + (pieces - self.equipment.len()) as u32
+    }
+
+    /// Removes a piece of equipment by name, see [`Inventory::find_instance`]
+    pub fn take_instance(&mut self, name: &str) -> Option<ItemInstance> {
+        self.instance_idx(name)
+            .map(|idx| self.equipment.remove(idx))
+    }
+
+    /// Takes a particular piece of equipment out of the inventory
+    pub fn take_by_id(&mut self, id: EntityId) -> Option<ItemInstance> {
+        let idx = self.equipment.iter().position(|i| i.id == id)?;
+        Some(self.equipment.remove(idx))
+    }
+
+    /// Finds equipment by what players see it called, then by its template,
+    /// see [`entity::resolve`] for how names match. Whole names are tried first,
+    /// and a stack with the whole name means there's no equipment by that name,
+    /// so 'torch' is the stack of Torches and not the Torch of Embers.
+    fn instance_idx(&self, name: &str) -> Option<usize> {
+        let names: Vec<_> = self.equipment.iter().map(|i| (i.id, i.name())).collect();
+        let names = || names.iter().map(|(id, n)| (*id, n.as_str()));
+        let templates = || self.equipment.iter().map(|i| (i.id, i.template.as_str()));
+
+        let id = match entity::resolve_exact(name, names())
+            .or_else(|| entity::resolve_exact(name, templates()))
+        {
+            Some(id) => id,
+            None if self.items.iter().any(|s| s.name.eq_ignore_ascii_case(name)) => return None,
+            None => {
+                entity::resolve(name, names()).or_else(|| entity::resolve(name, templates()))?
+            }
+        };
+        self.equipment.iter().position(|i| i.id == id)
+    }
+
+    /// A readable table of the item stacks and equipment and how much they weigh
+    /// altogether, see [`Inventory::total_weight`]
+    pub fn describe(&self, weight_of: impl Fn(&str) -> u32) -> String {
+        if self.items.is_empty() && self.equipment.is_empty() {
+            return "You aren't carrying anything".to_string();
+        }
+
+        let mut res = format!("You are carrying\n\n{:30}{:>8}\n", "Item", "Count");
+        for stack in &self.items {
+            res.push_str(&format!("{:30}{:>8}\n", stack.name, stack.count));
+        }
+        if !self.equipment.is_empty() {
+            res.push_str(&format!("\n{:30}{:>8}\n", "Equipment", "Wear"));
+            for item in &self.equipment {
+                res.push_str(&format!(
+                    "{:30}{:>8}\n",
+                    item.name(),
+                    format!("{}/{}", item.durability, item.max_durability)
+                ));
+            }
+        }
+        res.push_str(&format!("\nTotal weight: {}", self.total_weight(weight_of)));
+
+        res
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn instances_differ() {
+        let mut inventory = Inventory::default();
+        inventory.add("Gold Coin", 5);
+        inventory.add_instance(ItemInstance::new("Iron Sword", Quality::Common));
+        inventory.add_instance(ItemInstance::new("Iron Sword", Quality::Fine));
+
+        let sword = inventory.find_instance_mut("fine iron sword").unwrap();
+        assert_eq!(sword.max_durability, 60);
+        assert_eq!(sword.enchant_cost(), 2);
+        assert_eq!(sword.enchant_failure_chance(), 0.0);
+        sword.enchant("strength");
+        sword.enchant("agility");
+        assert_eq!(sword.name(), "Fine Iron Sword +2");
+        assert_eq!(sword.enchant_cost(), 8);
+        assert!(sword.enchant_failure_chance() > 0.0);
+        assert_eq!(sword.essence_value(), 3);
+        assert!(sword.describe(None).contains("+1 agility"));
+
+        sword.engraved = Some("Nightfall".into());
+        assert!(!sword.wear(59));
+        assert!(sword.wear(5));
+        assert!(sword.is_broken());
+        assert_eq!(sword.active_enchantments().count(), 0);
+
+        assert_eq!(
+            inventory.find_instance("iron sword").unwrap().name(),
+            "Iron Sword"
+        );
+        assert!(inventory.take_instance("nightfall").is_some());
+        let weight_of = |name: &str| if name == "Iron Sword" { 3 } else { 1 };
+        assert_eq!(inventory.total_weight(weight_of), 8);
+
+        let yaml = serde_yaml::to_string(&inventory).unwrap();
+        assert_eq!(serde_yaml::from_str::<Inventory>(&yaml).unwrap(), inventory);
+        assert_eq!(inventory.scaled(0.5).equipment().len(), 0);
+
+        // A stack with the whole name wins over equipment that only starts with it
+        inventory.add("Torch", 3);
+        inventory.add_instance(ItemInstance::new("Torch of Embers", Quality::Common));
+        assert!(inventory.find_instance("torch").is_none());
+        let mut dropped = Inventory::default();
+        assert!(inventory.give(&mut dropped, "torch", None).is_some());
+        assert_eq!(dropped.get("Torch").unwrap().count, 3);
+        assert!(dropped.equipment().is_empty());
+        assert!(inventory.find_instance("torch of").is_some());
+    }
+
+    #[test]
+    fn loads_stacked_equipment() {
+        let old = "items:\n- name: Iron Sword\n  count: 2\n- name: Gold Coin\n  count: 5\n";
+        let mut inventory: Inventory = serde_yaml::from_str(old).unwrap();
+        assert_eq!(inventory.unstack_equipment(|n| n == "Iron Sword"), 2);
+        assert_eq!(inventory.get("Gold Coin").map(|s| s.count), Some(5));
+        assert_eq!(inventory.get("Iron Sword"), None);
+
+        let sword = inventory.find_instance_mut("iron sword").unwrap();
+        assert_eq!((sword.durability, sword.max_durability), (40, 40));
+        assert!(!sword.wear(39));
+        assert!(sword.value() > 0);
+        assert!(sword.wear(1));
+        assert!(sword.is_broken());
+        assert_eq!(sword.value(), 0);
+        assert!(!sword.wear(1));
+        assert_eq!(
+            inventory
+                .equipment()
+                .iter()
+                .filter(|i| i.is_broken())
+                .count(),
+            1
+        );
+
+        let yaml = serde_yaml::to_string(&inventory).unwrap();
+        let mut loaded: Inventory = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(loaded, inventory);
+        assert_eq!(loaded.unstack_equipment(|n| n == "Iron Sword"), 0);
+
+        let old = "name: Ada\nwielding:\n  name: Iron Sword +1\n  base-name: Iron Sword\n  \
+            enchantments:\n  - attribute: strength\n    amount: 1\n";
+        let character: crate::mud::character::Character = serde_yaml::from_str(old).unwrap();
+        let mut wielding = character.wielding.unwrap();
+        assert_eq!(wielding.name(), "Iron Sword +1");
+        assert_eq!(wielding.active_enchantments().count(), 1);
+        assert!(wielding.wear(40));
+        assert_eq!(wielding.active_enchantments().count(), 0);
+    }
+
+    #[test]
+    fn takes_a_share() {
+        let mut inventory = Inventory::default();
+        inventory.add("Gold Coin", 10);
+        inventory.add("Torch", 1);
+        inventory.add_instance(ItemInstance::new("Iron Sword", Quality::Common));
+        inventory.add_instance(ItemInstance::new("Iron Sword", Quality::Fine));
+
+        let share = inventory.take_share(0.5);
+        assert_eq!(share.get("Gold Coin").map(|s| s.count), Some(5));
+        assert_eq!(share.get("Torch"), None);
+        assert_eq!(share.equipment()[0].name(), "Fine Iron Sword");
+        assert_eq!(inventory.get("Gold Coin").map(|s| s.count), Some(5));
+        assert_eq!(inventory.equipment()[0].name(), "Iron Sword");
+
+        let mut everything = inventory.take_share(1.0);
+        assert!(inventory.is_empty());
+        assert_eq!(everything.remove_all("iron sword"), 1);
+    }
+
+    #[test]
+    fn sells_and_compares() {
+        let mut inventory = Inventory::default();
+        inventory.add("Gold Coin", 5);
+        inventory.add("Bone Dust", 3);
+        inventory.add_instance(ItemInstance::new("Rusty Dagger", Quality::Crude));
+
+        let mut held = ItemInstance::new("Iron Sword", Quality::Fine);
+        held.enchant("strength");
+        let comparison = inventory.equipment()[0].compare(Some(&held));
+        assert!(comparison.contains("Crude"));
+        assert!(comparison.contains("strength"));
+        assert!(comparison.contains("-1"));
+        assert_eq!(held.value(), 5);
+
+        let mut ground = Inventory::default();
+        assert_eq!(
+            inventory.give(&mut ground, "gold coin", Some(2)),
+            Some("Gold Coin x2".into())
+        );
+        assert_eq!(inventory.give(&mut ground, "gold coin", Some(9)), None);
+        assert_eq!(
+            ground.give(&mut inventory, "Gold Coin", None),
+            Some("Gold Coin x2".into())
+        );
+        assert_eq!(inventory.get("Gold Coin").map(|s| s.count), Some(5));
+        assert_eq!(inventory.find_stack("bone").map(|s| s.count), Some(3));
+        assert!(inventory.find_stack("coin").is_none());
+
+        let (sold, value) = inventory.sell(|n| n != "Gold Coin");
+        assert_eq!(sold, vec!["Bone Dust x3", "Crude Rusty Dagger"]);
+        assert_eq!(value, 4);
+        assert_eq!(inventory.names().collect::<Vec<_>>(), vec!["Gold Coin"]);
+
+        inventory.add("Bone Dust", 5);
+        inventory.add_instance(ItemInstance::new("Torch", Quality::Common));
+        inventory.add_instance(ItemInstance::new("Torch", Quality::Common));
+        let mut magic = ItemInstance::new("Torch", Quality::Common);
+        magic.enchant("wits");
+        inventory.add_instance(magic);
+        assert_eq!(
+            inventory.sell_named("bone dust", Some(2)),
+            Some((vec!["Bone Dust x2".into()], 2))
+        );
+        assert_eq!(
+            inventory.sell_named("torch", Some(5)),
+            Some((vec!["Torch".into(), "Torch".into()], 4))
+        );
+        assert_eq!(inventory.sell_named("torch", None), None);
+        assert_eq!(inventory.sell(|n| n == "Torch"), (Vec::new(), 0));
+        assert!(inventory.sell_named("torch +1", None).is_some());
+        assert_eq!(inventory.find_stack("bone dust").map(|s| s.count), Some(3));
+    }
+}
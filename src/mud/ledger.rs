@@ -0,0 +1,202 @@
+//! Checks the economy adds up. Everything that makes or destroys gold and items,
+//! loot, vendors, crafting and the like, is written in the ledger as it happens.
+//! Every so often the engine counts what players actually hold, on them, in the
+//! mail, in stalls, on corpses and the ground, and compares. Anything that
+//! doesn't match is likely a duplication bug, reported with the transactions
+//! since the last audit that touched it.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    fmt::{self, Display},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Transactions kept for reporting, older ones are dropped once this many are waiting
+const MAX_RECENT: usize = 2000;
+/// The most transactions listed for each discrepancy
+const MAX_SUSPECTS: usize = 10;
+
+/// Gold or items made or destroyed, positive when made
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Transaction {
+    pub tick: u64,
+    pub who: String,
+    pub item: String,
+    pub change: i64,
+    pub why: String,
+}
+
+impl Display for Transaction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "tick {}, {} {:+} {} for {}",
+            self.tick, self.who, self.change, self.item, self.why
+        )
+    }
+}
+
+/// An item there's more or less of than the ledger says there should be
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Discrepancy {
+    pub item: String,
+    pub expected: i64,
+    pub counted: i64,
+    /// The latest transactions involving the item since the last audit
+    pub transactions: Vec<Transaction>,
+}
+
+impl Display for Discrepancy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} where the ledger expected {}",
+            self.counted, self.item, self.expected
+        )?;
+        if self.transactions.is_empty() {
+            return write!(f, ", nothing recorded touched it since the last audit");
+        }
+        for transaction in &self.transactions {
+            write!(f, "\n  {transaction}")?;
+        }
+        Ok(())
+    }
+}
+
+/// What changed between two counts, see [`crate::mud::items::Inventory::counts`]
+pub fn changes(
+    before: &BTreeMap<String, i64>,
+    after: &BTreeMap<String, i64>,
+) -> Vec<(String, i64)> {
+    let items: BTreeSet<_> = before.keys().chain(after.keys()).collect();
+    items
+        .into_iter()
+        .map(|item| {
+            let change = after.get(item).copied().unwrap_or_default()
+                - before.get(item).copied().unwrap_or_default();
+            (item.clone(), change)
+        })
+        .filter(|(_, change)| *change != 0)
+        .collect()
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct Ledger {
+    /// How much of each item should be held, none until the first count is taken
+    expected: Option<BTreeMap<String, i64>>,
+    /// Transactions since the last audit
+    recent: VecDeque<Transaction>,
+    /// When the last audit was taken
+    pub audited_tick: u64,
+    /// What the last audit found
+    pub discrepancies: usize,
+}
+
+impl Ledger {
+    pub fn record(&mut self, transaction: Transaction) {
+        if transaction.change == 0 {
+            return;
+        }
+        if let Some(expected) = &mut self.expected {
+            *expected.entry(transaction.item.clone()).or_default() += transaction.change;
+        }
+        self.recent.push_back(transaction);
+        if self.recent.len() > MAX_RECENT {
+            self.recent.pop_front();
+        }
+    }
+
+    /// Whether the first count's been taken to audit against
+    pub fn started(&self) -> bool {
+        self.expected.is_some()
+    }
+
+    /// How many transactions were recorded since the last audit
+    pub fn pending(&self) -> usize {
+        self.recent.len()
+    }
+
+    /// Compares what's held against what the ledger expects, then starts over from
+    /// the count so each discrepancy is only reported once. The first audit only
+    /// takes the count.
+    pub fn audit(&mut self, counted: BTreeMap<String, i64>, tick: u64) -> Vec<Discrepancy> {
+        let recent = std::mem::take(&mut self.recent);
+        self.audited_tick = tick;
+        let Some(expected) = self.expected.replace(counted.clone()) else {
+            return Vec::new();
+        };
+
+        let items: BTreeSet<_> = expected.keys().chain(counted.keys()).collect();
+        let discrepancies: Vec<_> = items
+            .into_iter()
+            .map(|item| {
+                let expected = expected.get(item).copied().unwrap_or_default();
+                let counted = counted.get(item).copied().unwrap_or_default();
+                (item, expected, counted)
+            })
+            .filter(|(_, expected, counted)| expected != counted)
+            .map(|(item, expected, counted)| Discrepancy {
+                item: item.clone(),
+                expected,
+                counted,
+                transactions: recent
+                    .iter()
+                    .rev()
+                    .filter(|t| &t.item == item)
+                    .take(MAX_SUSPECTS)
+                    .cloned()
+                    .collect(),
+            })
+            .collect();
+        self.discrepancies = discrepancies.len();
+        discrepancies
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn transaction(item: &str, change: i64, why: &str) -> Transaction {
+        Transaction {
+            tick: 5,
+            who: "Ada".into(),
+            item: item.into(),
+            change,
+            why: why.into(),
+        }
+    }
+
+    #[test]
+    fn catches_what_doesnt_add_up() {
+        let mut ledger = Ledger::default();
+        let count = |pairs: &[(&str, i64)]| {
+            pairs
+                .iter()
+                .map(|(i, c)| (i.to_string(), *c))
+                .collect::<BTreeMap<_, _>>()
+        };
+
+        ledger.record(transaction("Gold Coin", 10, "before counting"));
+        assert!(ledger.audit(count(&[("Gold Coin", 100)]), 1).is_empty());
+
+        ledger.record(transaction("Gold Coin", 20, "selling Honeycomb"));
+        ledger.record(transaction("Honeycomb", -2, "selling Honeycomb"));
+        ledger.record(transaction("Torch", 1, "buying a torch"));
+        let found = ledger.audit(count(&[("Gold Coin", 140), ("Honeycomb", 0)]), 10);
+
+        assert_eq!(found.len(), 3);
+        assert_eq!(found[0].item, "Gold Coin");
+        assert_eq!((found[0].expected, found[0].counted), (120, 140));
+        assert_eq!(found[0].transactions[0].why, "selling Honeycomb");
+        assert_eq!((found[1].expected, found[1].counted), (-2, 0));
+        assert_eq!((found[2].expected, found[2].counted), (1, 0));
+        assert_eq!(ledger.discrepancies, 3);
+
+        assert!(ledger.audit(count(&[("Gold Coin", 140)]), 20).is_empty());
+        assert_eq!(ledger.pending(), 0);
+    }
+}
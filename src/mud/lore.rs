@@ -0,0 +1,23 @@
+use serde::{Deserialize, Serialize};
+
+/// The kinds of things that get an entry in the encyclopedia
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LoreKind {
+    Item,
+    Creature,
+}
+
+impl LoreKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            LoreKind::Item => "item",
+            LoreKind::Creature => "creature",
+        }
+    }
+}
+
+/// Formats an encyclopedia entry for a player to read
+pub fn entry(name: &str, kind: LoreKind, text: &str) -> String {
+    format!("{name} ({})\n\n{text}", kind.name())
+}
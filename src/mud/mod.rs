@@ -1,3 +1,33 @@
+pub mod action;
+pub mod areas;
+pub mod building;
+pub mod cartography;
 pub mod character;
-pub mod world;
+pub mod combat;
+pub mod companion;
+pub mod corpse;
+pub mod crafting;
+pub mod creation;
+pub mod entity;
+pub mod experience;
+pub mod faction;
+pub mod haggle;
+pub mod history;
+pub mod home;
+pub mod input;
 pub mod items;
+pub mod ledger;
+pub mod lore;
+pub mod overlay;
+pub mod party;
+pub mod relationship;
+pub mod shop;
+pub mod social;
+pub mod stall;
+pub mod storyline;
+pub mod telemetry;
+pub mod transaction;
+pub mod weather;
+pub mod work_order;
+pub mod workshop;
+pub mod world;
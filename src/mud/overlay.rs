@@ -0,0 +1,125 @@
+use serde::{Deserialize, Serialize};
+
+/// What left its mark on a place, each place holds at most one overlay of each kind
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OverlayKind {
+    BattleScars,
+    /// Put up for a village festival
+    Decorations,
+}
+
+/// A fragment of description layered over a place's generated one by something
+/// that happened there. It reads differently once it's faded and is gone after
+/// it's lasted its full time.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Overlay {
+    pub kind: OverlayKind,
+    pub fresh: String,
+    /// Shown for the second half of the overlay's life, if not set the fresh text stays
+    #[serde(default)]
+    pub faded: Option<String>,
+    pub added_tick: u64,
+    pub lasts_ticks: u64,
+}
+
+impl Overlay {
+    pub fn new(kind: OverlayKind, fresh: &str, faded: Option<&str>, tick: u64, lasts: u64) -> Self {
+        Self {
+            kind,
+            fresh: fresh.to_string(),
+            faded: faded.map(str::to_string),
+            added_tick: tick,
+            lasts_ticks: lasts,
+        }
+    }
+
+    pub fn expired(&self, current_tick: u64) -> bool {
+        current_tick.saturating_sub(self.added_tick) >= self.lasts_ticks
+    }
+
+    /// The text to show at the given tick, or nothing once the overlay has expired
+    pub fn text(&self, current_tick: u64) -> Option<&str> {
+        let age = current_tick.saturating_sub(self.added_tick);
+        if self.expired(current_tick) {
+            None
+        } else if age * 2 >= self.lasts_ticks {
+            Some(self.faded.as_deref().unwrap_or(&self.fresh))
+        } else {
+            Some(&self.fresh)
+        }
+    }
+}
+
+/// Adds an overlay, replacing any of the same kind
+pub fn add(overlays: &mut Vec<Overlay>, overlay: Overlay) {
+    overlays.retain(|o| o.kind != overlay.kind);
+    overlays.push(overlay);
+}
+
+/// The place's description with every live overlay appended
+pub fn compose(description: &str, overlays: &[Overlay], current_tick: u64) -> String {
+    let mut res = description.to_string();
+    for text in overlays.iter().filter_map(|o| o.text(current_tick)) {
+        res.push(' ');
+        res.push_str(text);
+    }
+    res
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn overlays_fade_and_expire() {
+        let mut overlays = Vec::new();
+        add(
+            &mut overlays,
+            Overlay::new(
+                OverlayKind::BattleScars,
+                "Blood is spattered across the floor.",
+                Some("Old stains mark the floor."),
+                100,
+                50,
+            ),
+        );
+        add(
+            &mut overlays,
+            Overlay::new(
+                OverlayKind::Decorations,
+                "Red garlands hang here.",
+                None,
+                100,
+                500,
+            ),
+        );
+
+        assert_eq!(
+            compose("A cave.", &overlays, 110),
+            "A cave. Blood is spattered across the floor. Red garlands hang here."
+        );
+        assert_eq!(
+            compose("A cave.", &overlays, 130),
+            "A cave. Old stains mark the floor. Red garlands hang here."
+        );
+        assert_eq!(
+            compose("A cave.", &overlays, 150),
+            "A cave. Red garlands hang here."
+        );
+
+        add(
+            &mut overlays,
+            Overlay::new(
+                OverlayKind::Decorations,
+                "Blue garlands hang here.",
+                None,
+                200,
+                500,
+            ),
+        );
+        assert_eq!(overlays.len(), 2);
+        assert!(overlays[0].expired(150));
+    }
+}
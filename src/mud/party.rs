@@ -0,0 +1,129 @@
+//! Parties of NPC adventurers roaming the world, so it doesn't feel empty when
+//! few dreamers are online. Their members are characters like any player's and
+//! get about by running the same commands players type. Players can hire a
+//! party to follow them, and parties knocked out in a fight wait for rescue.
+
+use rand::{seq::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
+
+use super::world::Direction;
+use crate::state::PlayerId;
+
+pub const PARTY_NAMES: &[&str] = &[
+    "The Lantern Company",
+    "The Grey Wanderers",
+    "The Moth Guard",
+    "The Hollow Oaks",
+    "The Last Candle",
+    "The Brass Owls",
+];
+const MEMBER_NAMES: &[&str] = &[
+    "Ysolde", "Garrick", "Fen", "Odalys", "Rook", "Tamsin", "Hadrian", "Lark", "Morwen", "Cade",
+    "Sabine", "Thorne",
+];
+/// Things members say to each other on the road
+pub const CHATTER: &[&str] = &[
+    "keep your torch up, it's darker than it looks",
+    "I'm sure the way out was back there",
+    "whatever lives here, let's not wake it",
+    "we should have stayed at the Drowsy Goose",
+    "does anyone else hear that?",
+];
+
+pub const PARTY_SIZE: usize = 3;
+/// What it costs to hire a party
+pub const HIRE_COST: u32 = 20;
+/// How long a hired party follows whoever paid them
+pub const HIRE_SECONDS: f64 = 600.0;
+/// What a rescued party gives whoever saved them
+pub const RESCUE_REWARD: u32 = 15;
+/// How long a knocked out party waits for help before limping home
+pub const STRANDED_SECONDS: f64 = 900.0;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Party {
+    pub name: String,
+    /// The characters in the party, the leader first
+    pub members: Vec<PlayerId>,
+    /// Who's paid the party to follow them, and the tick they're paid until
+    #[serde(default)]
+    pub hired_by: Option<(PlayerId, u64)>,
+    /// The tick the party was knocked out, they can't do anything until rescued
+    #[serde(default)]
+    pub stranded_since: Option<u64>,
+}
+
+impl Party {
+    /// A party with fresh members, named for how many parties there are already
+    pub fn new(index: usize, rng: &mut impl Rng) -> (Self, Vec<String>) {
+        let name = match PARTY_NAMES.get(index) {
+            Some(name) => name.to_string(),
+            None => format!("{} {}", PARTY_NAMES[index % PARTY_NAMES.len()], index + 1),
+        };
+        let names = MEMBER_NAMES
+            .choose_multiple(rng, PARTY_SIZE)
+            .map(|n| n.to_string())
+            .collect();
+        let party = Self {
+            name,
+            members: (0..PARTY_SIZE).map(|_| PlayerId::new_random()).collect(),
+            hired_by: None,
+            stranded_since: None,
+        };
+        (party, names)
+    }
+
+    pub fn leader(&self) -> PlayerId {
+        self.members[0]
+    }
+
+    /// Who the party's following, if anyone's paid them and it hasn't run out
+    pub fn employer(&self, current_tick: u64) -> Option<PlayerId> {
+        self.hired_by
+            .filter(|(_, until)| *until > current_tick)
+            .map(|(p, _)| p)
+    }
+}
+
+/// Which way a party heads next, somewhere the leader hasn't been if they can.
+/// Each exit comes with whether the leader's already visited where it leads.
+pub fn pick_exit(exits: &[(Direction, bool)], rng: &mut impl Rng) -> Option<Direction> {
+    let unvisited: Vec<_> = exits.iter().filter(|(_, v)| !v).collect();
+    match unvisited.choose(rng) {
+        Some((direction, _)) => Some(*direction),
+        None => exits.choose(rng).map(|(d, _)| *d),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn explores_somewhere_new() {
+        let rng = &mut rand::thread_rng();
+        let exits = [
+            (Direction::North, true),
+            (Direction::East, false),
+            (Direction::Up, true),
+        ];
+        assert!((0..20).all(|_| pick_exit(&exits, rng) == Some(Direction::East)));
+
+        let seen = [(Direction::North, true)];
+        assert_eq!(pick_exit(&seen, rng), Some(Direction::North));
+        assert_eq!(pick_exit(&[], rng), None);
+    }
+
+    #[test]
+    fn hires_run_out() {
+        let (mut party, names) = Party::new(7, &mut rand::thread_rng());
+        assert_eq!(party.name, "The Grey Wanderers 8");
+        assert_eq!(names.len(), PARTY_SIZE);
+
+        let player = PlayerId::new_random();
+        party.hired_by = Some((player, 100));
+        assert_eq!(party.employer(99), Some(player));
+        assert_eq!(party.employer(100), None);
+    }
+}
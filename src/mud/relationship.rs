@@ -0,0 +1,186 @@
+//! How named NPCs feel about each player. Scores rise with gifts and errands and
+//! fall with crimes, and each standing opens up more to talk about.
+
+use std::collections::VecDeque;
+
+/// How much a gift raises a relationship for each gold coin it's worth
+const GIFT_SCORE_PER_GOLD: i32 = 1;
+/// The most a single gift can raise a score, so nobody buys a friendship outright
+const MAX_GIFT_SCORE: i32 = 5;
+/// Lost with every NPC the moment a player is caught breaking the law
+pub const CRIME_PENALTY: i32 = 5;
+/// Gained for bringing an NPC what they asked for
+pub const ERRAND_SCORE: i32 = 10;
+/// Gold given for finishing an errand, on top of the relationship
+pub const ERRAND_REWARD: u32 = 15;
+/// How many lines of a conversation an NPC keeps in mind when replying
+const CONVERSATION_LINES: usize = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Standing {
+    Hostile,
+    Stranger,
+    Acquaintance,
+    Friend,
+    Confidant,
+}
+
+impl Standing {
+    pub fn from_score(score: i32) -> Self {
+        match score {
+            s if s < 0 => Standing::Hostile,
+            0..=4 => Standing::Stranger,
+            5..=14 => Standing::Acquaintance,
+            15..=29 => Standing::Friend,
+            _ => Standing::Confidant,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Standing::Hostile => "hostile",
+            Standing::Stranger => "stranger",
+            Standing::Acquaintance => "acquaintance",
+            Standing::Friend => "friend",
+            Standing::Confidant => "confidant",
+        }
+    }
+
+    /// How much more a vendor will pay when haggling, as a fraction of the price
+    pub fn markup(self) -> f64 {
+        match self {
+            Standing::Hostile | Standing::Stranger => 0.0,
+            Standing::Acquaintance => 0.05,
+            Standing::Friend => 0.1,
+            Standing::Confidant => 0.2,
+        }
+    }
+
+    /// What a player can bring up with an NPC at this standing
+    pub fn topics(self) -> &'static [Topic] {
+        match self {
+            Standing::Hostile => &[],
+            Standing::Stranger => &[Topic::Greeting],
+            Standing::Acquaintance => &[Topic::Greeting, Topic::Rumours],
+            Standing::Friend | Standing::Confidant => {
+                &[Topic::Greeting, Topic::Rumours, Topic::Errand, Topic::Story]
+            }
+        }
+    }
+}
+
+/// Things to talk to an NPC about
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Topic {
+    Greeting,
+    /// Word of places nobody has explored yet
+    Rumours,
+    /// A personal favour, fetching them something
+    Errand,
+    /// Their own story, see [`Storyline`](super::storyline::Storyline)
+    Story,
+}
+
+impl Topic {
+    pub fn name(self) -> &'static str {
+        match self {
+            Topic::Greeting => "greeting",
+            Topic::Rumours => "rumours",
+            Topic::Errand => "errand",
+            Topic::Story => "story",
+        }
+    }
+
+    pub fn find(name: &str) -> Option<Self> {
+        [Topic::Greeting, Topic::Rumours, Topic::Errand, Topic::Story]
+            .into_iter()
+            .find(|t| t.name().eq_ignore_ascii_case(name))
+    }
+}
+
+/// A player talking to a named NPC, the line itself is generated
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dialogue {
+    pub npc: String,
+    pub place: String,
+    pub standing: Standing,
+    pub score: i32,
+    pub topic: Topic,
+    /// What the topic is about, the place a rumour is of or the item an errand is for
+    pub subject: Option<String>,
+    /// The latest goings on where the NPC is, oldest first
+    pub witnessed: Vec<String>,
+    /// The latest goings on in the place a rumour is of
+    pub heard: Vec<String>,
+    /// How the place the NPC's in looks
+    pub description: String,
+    /// What the player said to them, if they said anything in particular
+    pub said: Option<String>,
+    /// What's been said between them lately, oldest first
+    pub recent: Vec<String>,
+}
+
+/// What's been said lately between a player and the NPC they're talking to
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Conversation {
+    pub npc: String,
+    lines: VecDeque<String>,
+}
+
+impl Conversation {
+    pub fn with(npc: &str) -> Self {
+        Self {
+            npc: npc.to_string(),
+            lines: VecDeque::new(),
+        }
+    }
+
+    /// Adds a line spoken by someone, forgetting the oldest once there are too many
+    pub fn add(&mut self, speaker: &str, line: &str) {
+        if self.lines.len() == CONVERSATION_LINES {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(format!("{speaker}: {line}"));
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        self.lines.iter().cloned().collect()
+    }
+}
+
+/// How much a gift worth this much gold raises a relationship
+pub fn gift_score(value: u32) -> i32 {
+    (value as i32 * GIFT_SCORE_PER_GOLD).clamp(1, MAX_GIFT_SCORE)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn standing_unlocks_topics() {
+        assert_eq!(Standing::from_score(-1), Standing::Hostile);
+        assert!(Standing::from_score(-CRIME_PENALTY).topics().is_empty());
+        assert_eq!(Standing::from_score(0), Standing::Stranger);
+        assert_eq!(gift_score(100), MAX_GIFT_SCORE);
+        assert!(!Standing::from_score(gift_score(1))
+            .topics()
+            .contains(&Topic::Rumours));
+        assert_eq!(Standing::from_score(ERRAND_SCORE), Standing::Acquaintance);
+        assert!(Standing::from_score(15).topics().contains(&Topic::Errand));
+        assert!(Standing::Confidant.markup() > Standing::Friend.markup());
+        assert_eq!(gift_score(0), 1);
+        assert_eq!(Topic::find("Rumours"), Some(Topic::Rumours));
+    }
+
+    #[test]
+    fn conversations_forget() {
+        let mut conversation = Conversation::with("Hilde");
+        for n in 0..=CONVERSATION_LINES {
+            conversation.add("Ada", &n.to_string());
+        }
+        let lines = conversation.lines();
+        assert_eq!(lines.len(), CONVERSATION_LINES);
+        assert_eq!(lines[0], "Ada: 1");
+    }
+}
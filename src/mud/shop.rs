@@ -0,0 +1,127 @@
+//! Village shops. Each shopkeeper picks a few wares to sell at their own prices
+//! the first time someone asks what they have, and tops their stock back up
+//! every so often as players buy it out.
+
+use serde::{Deserialize, Serialize};
+
+/// The most wares one shop sells
+pub const MAX_WARES: usize = 6;
+/// How many of each ware a shop keeps in stock
+pub const WARE_STOCK: u32 = 5;
+/// Shops charge at least this many times what something's worth, so nothing can
+/// be bought to sell straight back to a vendor at a profit
+const MIN_MARKUP: f64 = 1.5;
+/// And at most this many times, whatever the shopkeeper asks
+const MAX_MARKUP: f64 = 3.0;
+
+/// What a shop charges for something worth `value` when the shopkeeper asks `asked`
+pub fn fair_price(value: u32, asked: u32) -> u32 {
+    let min = (value as f64 * MIN_MARKUP).ceil().max(value as f64 + 1.0) as u32;
+    let max = (value as f64 * MAX_MARKUP).ceil() as u32;
+    asked.clamp(min, max.max(min))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Ware {
+    pub name: String,
+    pub price: u32,
+    /// How many are left until the shop restocks
+    pub count: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Shop {
+    pub wares: Vec<Ware>,
+    /// The tick every ware is stocked back up on
+    pub restocks_at: u64,
+}
+
+impl Shop {
+    /// A fully stocked shop selling wares at the given prices
+    pub fn new(wares: Vec<(String, u32)>, restocks_at: u64) -> Self {
+        Self {
+            wares: wares
+                .into_iter()
+                .take(MAX_WARES)
+                .map(|(name, price)| Ware {
+                    name,
+                    price,
+                    count: WARE_STOCK,
+                })
+                .collect(),
+            restocks_at,
+        }
+    }
+
+    /// Stocks every ware back up if it's time, `every` ticks apart
+    pub fn restock(&mut self, current_tick: u64, every: u64) {
+        if self.restocks_at > current_tick {
+            return;
+        }
+        for ware in &mut self.wares {
+            ware.count = WARE_STOCK;
+        }
+        self.restocks_at = current_tick + every;
+    }
+
+    /// A ware by name, ignoring case
+    pub fn ware(&self, name: &str) -> Option<&Ware> {
+        self.wares
+            .iter()
+            .find(|w| w.name.eq_ignore_ascii_case(name.trim()))
+    }
+
+    /// Takes one of a ware out of stock, returning its name and price
+    pub fn sell_one(&mut self, name: &str) -> Option<(String, u32)> {
+        let ware = self
+            .wares
+            .iter_mut()
+            .find(|w| w.name.eq_ignore_ascii_case(name.trim()) && w.count > 0)?;
+        ware.count -= 1;
+        Some((ware.name.clone(), ware.price))
+    }
+
+    pub fn describe(&self, keeper: &str) -> String {
+        let mut res = format!(
+            "{keeper} has for sale\n\n{:30}{:>8}{:>8}\n",
+            "Ware", "Price", "Stock"
+        );
+        for ware in &self.wares {
+            let count = match ware.count {
+                0 => "sold out".to_string(),
+                count => count.to_string(),
+            };
+            res.push_str(&format!("{:30}{:>8}{count:>8}\n", ware.name, ware.price));
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sells_and_restocks() {
+        assert_eq!(fair_price(10, 1), 15);
+        assert_eq!(fair_price(10, 22), 22);
+        assert_eq!(fair_price(10, 500), 30);
+        assert_eq!(fair_price(1, 1), 2);
+
+        let mut shop = Shop::new(vec![("Torch".into(), 3), ("Iron Sword".into(), 90)], 100);
+        for _ in 0..WARE_STOCK {
+            assert_eq!(shop.sell_one("torch"), Some(("Torch".into(), 3)));
+        }
+        assert_eq!(shop.sell_one("torch"), None);
+        assert_eq!(shop.sell_one("Barley"), None);
+        assert!(shop.describe("Hilde").contains("sold out"));
+
+        shop.restock(99, 50);
+        assert_eq!(shop.ware("Torch").unwrap().count, 0);
+        shop.restock(100, 50);
+        assert_eq!(shop.ware("Torch").unwrap().count, WARE_STOCK);
+        assert_eq!(shop.restocks_at, 150);
+    }
+}
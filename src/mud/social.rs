@@ -0,0 +1,134 @@
+//! Socials, short canned actions like waving or bowing. Each is worded for
+//! whoever does it, whoever it's aimed at and everyone else watching.
+
+/// A social's wording, `{actor}` and `{target}` are filled in with names
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Social {
+    pub name: &'static str,
+    /// What the actor sees and what everyone else sees, done to nobody in particular
+    pub alone: [&'static str; 2],
+    /// What the actor, the target and everyone else see
+    pub targeted: [&'static str; 3],
+}
+
+pub const SOCIALS: &[Social] = &[
+    Social {
+        name: "wave",
+        alone: ["You wave", "{actor} waves"],
+        targeted: [
+            "You wave at {target}",
+            "{actor} waves at you",
+            "{actor} waves at {target}",
+        ],
+    },
+    Social {
+        name: "bow",
+        alone: ["You bow deeply", "{actor} bows deeply"],
+        targeted: [
+            "You bow before {target}",
+            "{actor} bows before you",
+            "{actor} bows before {target}",
+        ],
+    },
+    Social {
+        name: "laugh",
+        alone: ["You laugh", "{actor} laughs"],
+        targeted: [
+            "You laugh at {target}",
+            "{actor} laughs at you",
+            "{actor} laughs at {target}",
+        ],
+    },
+    Social {
+        name: "nod",
+        alone: ["You nod", "{actor} nods"],
+        targeted: [
+            "You nod to {target}",
+            "{actor} nods to you",
+            "{actor} nods to {target}",
+        ],
+    },
+    Social {
+        name: "shrug",
+        alone: ["You shrug", "{actor} shrugs"],
+        targeted: [
+            "You shrug at {target}",
+            "{actor} shrugs at you",
+            "{actor} shrugs at {target}",
+        ],
+    },
+];
+
+/// What a social looks like to each side
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rendered {
+    pub actor: String,
+    /// What the target sees, if there is one
+    pub target: Option<String>,
+    pub room: String,
+}
+
+impl Social {
+    pub fn render(&self, actor: &str, target: Option<&str>) -> Rendered {
+        let fill = |t: &str| {
+            t.replace("{actor}", actor)
+                .replace("{target}", target.unwrap_or_default())
+        };
+        match target {
+            Some(_) => Rendered {
+                actor: fill(self.targeted[0]),
+                target: Some(fill(self.targeted[1])),
+                room: fill(self.targeted[2]),
+            },
+            None => Rendered {
+                actor: fill(self.alone[0]),
+                target: None,
+                room: fill(self.alone[1]),
+            },
+        }
+    }
+}
+
+/// Turns free text into an emote, starting with the actor's name and ending
+/// in punctuation, so 'emote grins' reads "Ada grins."
+pub fn emote(actor: &str, text: &str) -> String {
+    let text = text.trim();
+    let end = if text.ends_with(['.', '!', '?']) {
+        ""
+    } else {
+        "."
+    };
+    // Possessives and the like join straight onto the name
+    let gap = if text.starts_with(['\'', ',']) {
+        ""
+    } else {
+        " "
+    };
+    format!("{actor}{gap}{text}{end}")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn renders_each_side() {
+        let wave = SOCIALS.iter().find(|s| s.name == "wave").unwrap();
+        assert_eq!(
+            wave.render("Ada", Some("Bryn")),
+            Rendered {
+                actor: "You wave at Bryn".into(),
+                target: Some("Ada waves at you".into()),
+                room: "Ada waves at Bryn".into(),
+            }
+        );
+        let alone = wave.render("Ada", None);
+        assert_eq!(
+            (alone.actor.as_str(), alone.room.as_str()),
+            ("You wave", "Ada waves")
+        );
+
+        assert_eq!(emote("Ada", "grins"), "Ada grins.");
+        assert_eq!(emote("Ada", "'s eyes narrow!"), "Ada's eyes narrow!");
+    }
+}
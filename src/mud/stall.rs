@@ -0,0 +1,193 @@
+use std::collections::BTreeMap;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::state::PlayerId;
+
+use super::{
+    faction,
+    items::{Inventory, COMMODITY_VALUE},
+};
+
+/// How many stalls fit in one market
+pub const MAX_STALLS: usize = 4;
+/// Passing traders only buy things priced at most this many times what they're worth
+const NPC_MAX_MARKUP: f64 = 1.5;
+/// The chance a passing trader buys something from a stall each time one comes by
+const NPC_BUY_CHANCE: f64 = 0.3;
+/// How many past sales a stall remembers to tell its owner about
+const MAX_LEDGER: usize = 20;
+
+/// A market stall a player rents to sell things from while they're away. Goods
+/// sit in the stall's own stock until someone buys them and the gold waits
+/// there until the owner comes back for it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Stall {
+    pub owner: PlayerId,
+    pub owner_name: String,
+    /// The stall stops selling once its rent runs out, but keeps its stock
+    pub rented_until: u64,
+    pub stock: Inventory,
+    /// The asking price of each item, by name
+    pub prices: BTreeMap<String, u32>,
+    /// Gold from sales, waiting to be collected
+    pub proceeds: u32,
+    /// What sold since the owner last collected
+    #[serde(default)]
+    pub ledger: Vec<String>,
+}
+
+impl Stall {
+    pub fn new(owner: PlayerId, owner_name: String, rented_until: u64) -> Self {
+        Self {
+            owner,
+            owner_name,
+            rented_until,
+            stock: Default::default(),
+            prices: Default::default(),
+            proceeds: 0,
+            ledger: Vec::new(),
+        }
+    }
+
+    pub fn open(&self, current_tick: u64) -> bool {
+        self.rented_until > current_tick
+    }
+
+    /// The full name and price of something on sale, matched loosely like inventory names
+    pub fn listing(&self, name: &str) -> Option<(String, u32)> {
+        let name = match self.stock.find_instance(name) {
+            Some(instance) => instance.name(),
+            None => self
+                .stock
+                .stacks()
+                .find(|s| s.name.eq_ignore_ascii_case(name))?
+                .name
+                .clone(),
+        };
+        let price = *self.prices.get(&name)?;
+        Some((name, price))
+    }
+
+    /// Moves a sale into a buyer's inventory, returning what was bought and what it cost
+    pub fn sell_to(&mut self, buyer: &mut Inventory, name: &str) -> Option<(String, u32)> {
+        let (name, price) = self.listing(name)?;
+        self.stock.give(buyer, &name, Some(1))?;
+        self.record_sale(&name, price);
+        Some((name, price))
+    }
+
+    /// Maybe sells something to a passing trader, if anything's priced fairly enough
+    pub fn npc_sale(&mut self, rng: &mut impl Rng) -> Option<(String, u32)> {
+        if !rng.gen_bool(NPC_BUY_CHANCE) {
+            return None;
+        }
+
+        let fair: Vec<_> = self
+            .stock
+            .equipment()
+            .iter()
+            .map(|i| (i.name(), i.value()))
+            .chain(
+                self.stock
+                    .stacks()
+                    .map(|s| (s.name.clone(), COMMODITY_VALUE)),
+            )
+            .filter_map(|(name, value)| {
+                let price = *self.prices.get(&name)?;
+                (price as f64 <= value.max(1) as f64 * NPC_MAX_MARKUP).then_some((name, price))
+            })
+            .collect();
+        let (name, price) = fair.get(rng.gen_range(0..fair.len().max(1)))?.clone();
+
+        self.stock.give(&mut Inventory::default(), &name, Some(1))?;
+        self.record_sale(&name, price);
+        Some((name, price))
+    }
+
+    /// Takes the council's cut of a sale out of the proceeds, returning what it came to
+    pub fn pay_tax(&mut self, price: u32, rate: f64) -> u32 {
+        let tax = faction::tax(price, rate).min(self.proceeds);
+        self.proceeds -= tax;
+        tax
+    }
+
+    fn record_sale(&mut self, name: &str, price: u32) {
+        self.proceeds += price;
+        if !self.stock.names().any(|n| n == name) {
+            self.prices.remove(name);
+        }
+        self.ledger.push(format!("{name} for {price}"));
+        if self.ledger.len() > MAX_LEDGER {
+            self.ledger.remove(0);
+        }
+    }
+
+    /// A listing of what's on sale and for how much
+    pub fn describe(&self, current_tick: u64) -> String {
+        let mut res = format!("{}'s stall", self.owner_name);
+        if !self.open(current_tick) {
+            res.push_str(" (closed)");
+        }
+
+        let goods: Vec<_> = self
+            .stock
+            .equipment()
+            .iter()
+            .map(|i| (i.name(), 1))
+            .chain(self.stock.stacks().map(|s| (s.name.clone(), s.count)))
+            .collect();
+        if goods.is_empty() {
+            res.push_str("\n  Nothing for sale");
+        }
+        for (name, count) in goods {
+            let price = self.prices.get(&name).copied().unwrap_or_default();
+            res.push_str(&format!("\n  {name:<30} x{count:<4} {price:>5} gold"));
+        }
+
+        res
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rand::SeedableRng;
+
+    use crate::mud::items::{ItemInstance, Quality};
+
+    use super::*;
+
+    #[test]
+    fn sells_stock() {
+        let mut stall = Stall::new(PlayerId::new_random(), "ada".into(), 100);
+        stall.stock.add("Honeycomb", 2);
+        stall.prices.insert("Honeycomb".into(), 1);
+        stall
+            .stock
+            .add_instance(ItemInstance::new("Iron Sword", Quality::Fine));
+        stall.prices.insert("Fine Iron Sword".into(), 500);
+
+        let mut buyer = Inventory::default();
+        assert_eq!(
+            stall.sell_to(&mut buyer, "honeycomb"),
+            Some(("Honeycomb".into(), 1))
+        );
+        assert_eq!(buyer.get("Honeycomb").unwrap().count, 1);
+        assert_eq!(
+            stall.listing("iron sword"),
+            Some(("Fine Iron Sword".into(), 500))
+        );
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(3);
+        let npc_sales: Vec<_> = (0..50).filter_map(|_| stall.npc_sale(&mut rng)).collect();
+        assert_eq!(npc_sales, vec![("Honeycomb".into(), 1)]);
+        assert_eq!(stall.proceeds, 2);
+        assert_eq!(stall.ledger.len(), 2);
+        assert!(!stall.prices.contains_key("Honeycomb"));
+        assert_eq!(stall.pay_tax(15, 0.1), 2);
+        assert_eq!(stall.proceeds, 0);
+        assert!(stall.open(99) && !stall.open(100));
+    }
+}
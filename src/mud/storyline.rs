@@ -0,0 +1,122 @@
+use serde::{Deserialize, Serialize};
+
+use super::{items::Item, relationship::Standing};
+
+/// How many chapters a storyline can have
+pub const STAGE_RANGE: std::ops::RangeInclusive<usize> = 3..=5;
+
+/// A personal story a named NPC shares with players who've won them over, one
+/// chapter at a time. Later chapters only open up as the relationship deepens
+/// and some need the player to bring something before the story can go on.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Storyline {
+    pub npc: String,
+    pub title: String,
+    pub stages: Vec<StoryStage>,
+    /// A one of a kind keepsake given at the end
+    pub reward: Item,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct StoryStage {
+    /// What the NPC tells the player in this chapter
+    pub text: String,
+    /// An item the player has to bring them before the story moves on
+    #[serde(default)]
+    pub wants: Option<String>,
+}
+
+impl Storyline {
+    /// Checks a generated storyline can actually be played through, returning a
+    /// description of each problem. Anything wanted has to be one of the obtainable items.
+    pub fn validate(&self, obtainable: &[String]) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        if self.title.trim().is_empty() {
+            errors.push("The storyline has no title".to_string());
+        }
+        if !STAGE_RANGE.contains(&self.stages.len()) {
+            errors.push(format!(
+                "There are {} stages but there should be between {} and {}",
+                self.stages.len(),
+                STAGE_RANGE.start(),
+                STAGE_RANGE.end()
+            ));
+        }
+        for (i, stage) in self.stages.iter().enumerate() {
+            if stage.text.trim().is_empty() {
+                errors.push(format!("Stage {} has no text", i + 1));
+            }
+            if let Some(wants) = &stage.wants {
+                if !obtainable.iter().any(|o| o.eq_ignore_ascii_case(wants)) {
+                    errors.push(format!(
+                        "Stage {} wants '{wants}' which isn't one of the listed items",
+                        i + 1
+                    ));
+                }
+            }
+        }
+        if self.stages.last().is_some_and(|s| s.wants.is_some()) {
+            errors.push("The last stage shouldn't want an item".to_string());
+        }
+        if self.reward.name.trim().is_empty() {
+            errors.push("The reward has no name".to_string());
+        }
+
+        errors
+    }
+
+    /// How well the player has to know the NPC to hear a chapter, the last
+    /// is only for confidants
+    pub fn required_standing(&self, stage: usize) -> Standing {
+        if stage + 1 >= self.stages.len() {
+            Standing::Confidant
+        } else {
+            Standing::Friend
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn validates_storylines() {
+        let stage = |text: &str, wants: Option<&str>| StoryStage {
+            text: text.into(),
+            wants: wants.map(str::to_string),
+        };
+        let mut story = Storyline {
+            npc: "Marta of The Drowsy Goose".into(),
+            title: "The Lost Ring".into(),
+            stages: vec![
+                stage("I had a ring once.", None),
+                stage(
+                    "Bring me some honeycomb, it reminds me of him.",
+                    Some("honeycomb"),
+                ),
+                stage("He gave it to me by the river.", None),
+            ],
+            reward: Item {
+                name: "Marta's Ring".into(),
+                description: "A thin silver band.".into(),
+                weight: 1,
+            },
+        };
+        let obtainable = vec!["Honeycomb".to_string()];
+
+        assert!(story.validate(&obtainable).is_empty());
+        assert_eq!(story.required_standing(1), Standing::Friend);
+        assert_eq!(story.required_standing(2), Standing::Confidant);
+
+        story.stages[1].wants = Some("Dragon Egg".into());
+        story.stages.push(stage("", Some("Honeycomb")));
+        assert_eq!(story.validate(&obtainable).len(), 3);
+
+        story.stages.truncate(1);
+        assert_eq!(story.validate(&obtainable).len(), 1);
+    }
+}
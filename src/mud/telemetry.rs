@@ -0,0 +1,153 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::combat::FightLog;
+
+/// Fights needed before we'll consider something an outlier,
+/// a couple of unlucky fights shouldn't flag a creature
+const MIN_FIGHTS_FOR_OUTLIER: u32 = 5;
+
+/// Aggregated fight outcomes for one piece of generated content.
+/// Deliberately holds no player names or IDs.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct OutcomeStats {
+    pub fights: u32,
+    pub player_deaths: u32,
+    pub total_rounds: u32,
+    pub loot_value: u32,
+}
+
+impl OutcomeStats {
+    pub fn death_rate(&self) -> f64 {
+        if self.fights == 0 {
+            return 0.0;
+        }
+        self.player_deaths as f64 / self.fights as f64
+    }
+
+    pub fn average_fight_length(&self) -> f64 {
+        if self.fights == 0 {
+            return 0.0;
+        }
+        self.total_rounds as f64 / self.fights as f64
+    }
+
+    fn record(&mut self, fight: &FightLog) {
+        self.fights += 1;
+        self.player_deaths += fight.downed_players;
+        self.total_rounds += fight.rounds;
+        self.loot_value += fight.loot_value;
+    }
+}
+
+/// Server wide balance numbers for generated creatures and places
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct BalanceTelemetry {
+    pub creatures: BTreeMap<String, OutcomeStats>,
+    pub places: BTreeMap<String, OutcomeStats>,
+}
+
+impl BalanceTelemetry {
+    pub fn record_fight(&mut self, place_name: &str, fight: &FightLog) {
+        self.places
+            .entry(place_name.to_string())
+            .or_default()
+            .record(fight);
+
+        for creature in &fight.creatures {
+            self.creatures
+                .entry(creature.to_string())
+                .or_default()
+                .record(fight);
+        }
+    }
+
+    /// Entries whose death rate is well above the average of their group
+    pub fn outliers(stats: &BTreeMap<String, OutcomeStats>, factor: f64) -> Vec<&str> {
+        let sampled: Vec<_> = stats
+            .iter()
+            .filter(|(_, s)| s.fights >= MIN_FIGHTS_FOR_OUTLIER)
+            .collect();
+        if sampled.is_empty() {
+            return Vec::new();
+        }
+
+        let mean = sampled.iter().map(|(_, s)| s.death_rate()).sum::<f64>() / sampled.len() as f64;
+
+        sampled
+            .into_iter()
+            .filter(|(_, s)| s.death_rate() > 0.0 && s.death_rate() > mean * factor)
+            .map(|(n, _)| n.as_str())
+            .collect()
+    }
+
+    /// A readable breakdown for admins, with outliers marked
+    pub fn report(&self, factor: f64) -> String {
+        let mut res = String::from("Balance report\n");
+
+        for (title, stats) in [("Creatures", &self.creatures), ("Places", &self.places)] {
+            let outliers = Self::outliers(stats, factor);
+
+            res.push_str(&format!(
+                "\n{title}\n{:30}{:>8}{:>8}{:>10}{:>8}\n",
+                "Name", "Fights", "Deaths", "Avg rnds", "Loot"
+            ));
+            for (name, s) in stats {
+                let flag = if outliers.contains(&name.as_str()) {
+                    " <- outlier"
+                } else {
+                    ""
+                };
+                res.push_str(&format!(
+                    "{:30}{:>8}{:>8.2}{:>10.1}{:>8}{flag}\n",
+                    name,
+                    s.fights,
+                    s.death_rate(),
+                    s.average_fight_length(),
+                    s.loot_value
+                ));
+            }
+        }
+
+        res
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn flags_deadly_places() {
+        let mut telemetry = BalanceTelemetry::default();
+        let calm = FightLog {
+            rounds: 4,
+            ..Default::default()
+        };
+        let deadly = FightLog {
+            rounds: 2,
+            downed_players: 1,
+            creatures: ["Cave Rat".to_string()].into(),
+            loot_value: 3,
+            ..Default::default()
+        };
+
+        for _ in 0..10 {
+            telemetry.record_fight("Meadow", &calm);
+            telemetry.record_fight("Pit", &deadly);
+        }
+        telemetry.record_fight("Cellar", &calm);
+
+        assert_eq!(telemetry.places["Pit"].death_rate(), 1.0);
+        assert_eq!(telemetry.places["Meadow"].average_fight_length(), 4.0);
+        assert_eq!(telemetry.creatures["Cave Rat"].fights, 10);
+        assert_eq!(telemetry.creatures["Cave Rat"].loot_value, 30);
+        assert_eq!(
+            BalanceTelemetry::outliers(&telemetry.places, 1.5),
+            vec!["Pit"]
+        );
+    }
+}
@@ -0,0 +1,168 @@
+//! Changes that touch several characters and places at once, like a sale at a
+//! market stall or a parcel sent off by mail. Everything's staged on copies and
+//! only written back to the world when the transaction is committed, so a step
+//! that fails part way can just return its error and the world is left as it
+//! was, never with the gold paid and nothing delivered.
+
+use std::collections::HashMap;
+
+use crate::state::PlayerId;
+
+use super::{
+    character::Character,
+    ledger::Transaction as LedgerEntry,
+    stall::Stall,
+    work_order::WorkOrder,
+    world::{Location, Place, World},
+};
+
+pub struct Transaction<'w> {
+    world: &'w mut World,
+    characters: HashMap<PlayerId, Character>,
+    places: HashMap<Location, Place>,
+    work_orders: Option<Vec<WorkOrder>>,
+    /// Ledger entries, only written if it all goes through
+    entries: Vec<LedgerEntry>,
+}
+
+impl<'w> Transaction<'w> {
+    pub fn new(world: &'w mut World) -> Self {
+        Self {
+            world,
+            characters: HashMap::new(),
+            places: HashMap::new(),
+            work_orders: None,
+            entries: Vec::new(),
+        }
+    }
+
+    /// The staged copy of a player's character. Unlike
+    /// [`World::player_character`] this won't make one for a player who has none.
+    pub fn character(&mut self, player: PlayerId) -> Result<&mut Character, String> {
+        if !self.characters.contains_key(&player) {
+            let character = self
+                .world
+                .player_characters
+                .get(&player)
+                .ok_or("They aren't in this world any more")?;
+            self.characters.insert(player, character.clone());
+        }
+        Ok(self.characters.get_mut(&player).unwrap())
+    }
+
+    /// The staged copy of a place
+    pub fn place(&mut self, location: Location) -> Result<&mut Place, String> {
+        if !self.places.contains_key(&location) {
+            let place = self
+                .world
+                .places
+                .get(&location)
+                .ok_or("That place doesn't exist any more")?;
+            self.places.insert(location, place.clone());
+        }
+        Ok(self.places.get_mut(&location).unwrap())
+    }
+
+    /// The staged copy of a player's stall in a place
+    pub fn stall(&mut self, location: Location, owner: PlayerId) -> Result<&mut Stall, String> {
+        self.place(location)?
+            .stalls
+            .iter_mut()
+            .find(|s| s.owner == owner)
+            .ok_or_else(|| "That stall has been packed up".to_string())
+    }
+
+    /// The staged copy of the work order board
+    pub fn work_orders(&mut self) -> &mut Vec<WorkOrder> {
+        self.work_orders
+            .get_or_insert_with(|| self.world.work_orders.clone())
+    }
+
+    /// Writes gold or items made or destroyed in the ledger once committed,
+    /// see [`World::record_transaction`]
+    pub fn record(&mut self, who: &str, item: &str, change: i64, why: &str) {
+        self.entries.push(LedgerEntry {
+            tick: self.world.current_tick,
+            who: who.to_string(),
+            item: item.to_string(),
+            change,
+            why: why.to_string(),
+        });
+    }
+
+    /// Writes everything staged back to the world
+    pub fn commit(self) {
+        let world = self.world;
+        for (player, character) in self.characters {
+            world.player_characters.insert(player, character);
+            world.player_changed(player);
+        }
+        for (location, place) in self.places {
+            world.places.insert(location, place);
+            world.place_changed(location);
+        }
+        if let Some(work_orders) = self.work_orders {
+            world.work_orders = work_orders;
+        }
+        for entry in self.entries {
+            world.ledger.record(entry);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{commands::GOLD, mud::items::Inventory};
+
+    use super::*;
+
+    #[test]
+    fn commits_all_or_nothing() {
+        let mut world = World::default();
+        world.ensure_starting_village();
+        let (buyer, seller) = (PlayerId::new_random(), PlayerId::new_random());
+        world.player_character(buyer).inventory.add(GOLD, 5);
+        let location = world.player_character(seller).location;
+        let mut stall = Stall::new(seller, "Ada".into(), 100);
+        stall.stock.add("Honeycomb", 1);
+        stall.prices.insert("Honeycomb".into(), 10);
+        world.places.get_mut(&location).unwrap().stalls.push(stall);
+
+        let buy = |world: &mut World| -> Result<(), String> {
+            let mut tx = world.transaction();
+            let mut bought = Inventory::default();
+            let stall = tx.stall(location, seller)?;
+            let (_, price) = stall.sell_to(&mut bought, "Honeycomb").ok_or("Sold out")?;
+            let character = tx.character(buyer)?;
+            if !character.inventory.remove(GOLD, price) {
+                return Err("Too poor".to_string());
+            }
+            character.inventory.append(bought);
+            tx.record("Ada", GOLD, 1, "testing");
+            tx.commit();
+            Ok(())
+        };
+
+        // The stall's sold it before finding out the buyer can't pay
+        assert_eq!(buy(&mut world), Err("Too poor".to_string()));
+        let stall = &world.places[&location].stalls[0];
+        assert_eq!(
+            (stall.proceeds, stall.stock.get("Honeycomb").unwrap().count),
+            (0, 1)
+        );
+        assert_eq!(world.ledger.pending(), 0);
+
+        world.player_character(buyer).inventory.add(GOLD, 5);
+        assert_eq!(buy(&mut world), Ok(()));
+        let character = &world.player_characters[&buyer];
+        assert_eq!(character.inventory.get("Honeycomb").unwrap().count, 1);
+        assert!(character.inventory.get(GOLD).is_none());
+        assert_eq!(world.places[&location].stalls[0].proceeds, 10);
+        assert_eq!(world.ledger.pending(), 1);
+
+        assert!(world
+            .transaction()
+            .character(PlayerId::new_random())
+            .is_err());
+    }
+}
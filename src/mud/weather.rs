@@ -0,0 +1,142 @@
+//! The time of day and the weather. The clock runs off the world's ticks, a
+//! whole day passing every `day-minutes` of real time, and each region has
+//! weather fronts written by the generator that blow in and move on again.
+
+use serde::{Deserialize, Serialize};
+
+const MINUTES_PER_DAY: u64 = 24 * 60;
+
+/// Broad parts of the day, they change how places look outdoors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DayPhase {
+    Dawn,
+    Day,
+    Dusk,
+    Night,
+}
+
+impl DayPhase {
+    pub fn at_hour(hour: u64) -> Self {
+        match hour {
+            5..=6 => DayPhase::Dawn,
+            7..=17 => DayPhase::Day,
+            18..=19 => DayPhase::Dusk,
+            _ => DayPhase::Night,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DayPhase::Dawn => "dawn",
+            DayPhase::Day => "day",
+            DayPhase::Dusk => "dusk",
+            DayPhase::Night => "night",
+        }
+    }
+
+    /// How the light falls outdoors, broad daylight isn't worth mentioning
+    pub fn light(self) -> Option<&'static str> {
+        match self {
+            DayPhase::Dawn => Some("Pale dawn light creeps across everything."),
+            DayPhase::Day => None,
+            DayPhase::Dusk => Some("The light is going and long shadows stretch out."),
+            DayPhase::Night => Some("Moonlight filters through the dark."),
+        }
+    }
+
+    /// What players outdoors are told when the phase begins
+    pub fn arrival(self) -> &'static str {
+        match self {
+            DayPhase::Dawn => "Dawn breaks, the sky paling in the east",
+            DayPhase::Day => "The sun climbs clear of the horizon",
+            DayPhase::Dusk => "The sun sinks low and the shadows lengthen",
+            DayPhase::Night => "Night falls",
+        }
+    }
+}
+
+/// The time in the world, worked out from the tick. Worlds begin at dawn of the first day.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorldTime {
+    pub day: u64,
+    pub hour: u64,
+    pub minute: u64,
+}
+
+impl WorldTime {
+    pub fn at_tick(tick: u64, ticks_per_day: u64) -> Self {
+        let ticks_per_day = ticks_per_day.max(1);
+        let tick = tick + ticks_per_day * 5 / 24;
+        let minutes =
+            (tick % ticks_per_day) as u128 * MINUTES_PER_DAY as u128 / ticks_per_day as u128;
+        let minutes = minutes as u64;
+        Self {
+            day: tick / ticks_per_day + 1,
+            hour: minutes / 60,
+            minute: minutes % 60,
+        }
+    }
+
+    pub fn phase(&self) -> DayPhase {
+        DayPhase::at_hour(self.hour)
+    }
+
+    pub fn describe(&self) -> String {
+        let part = match self.hour {
+            0..=4 => "the dead of night",
+            5..=6 => "dawn",
+            7..=11 => "morning",
+            12..=13 => "midday",
+            14..=17 => "afternoon",
+            18..=19 => "dusk",
+            _ => "night",
+        };
+        format!(
+            "It's {:02}:{:02} on day {} of the dream, {part}",
+            self.hour, self.minute, self.day
+        )
+    }
+}
+
+/// A spell of weather over a region
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct WeatherFront {
+    /// A few words for it, like "a cold drizzle"
+    pub name: String,
+    /// A sentence woven into how places under it look
+    pub description: String,
+    /// The tick it blows over
+    #[serde(default)]
+    pub until_tick: u64,
+}
+
+impl WeatherFront {
+    pub fn passed(&self, current_tick: u64) -> bool {
+        self.until_tick <= current_tick
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn days_turn() {
+        let start = WorldTime::at_tick(0, 2400);
+        assert_eq!((start.day, start.hour, start.minute), (1, 5, 0));
+        assert_eq!(start.phase(), DayPhase::Dawn);
+
+        let night = WorldTime::at_tick(1700, 2400);
+        assert_eq!((night.day, night.hour), (1, 22));
+        assert_eq!(night.phase(), DayPhase::Night);
+        assert!(night.phase().light().unwrap().contains("Moonlight"));
+
+        let next = WorldTime::at_tick(2000, 2400);
+        assert_eq!((next.day, next.hour), (2, 1));
+        assert_eq!(
+            WorldTime::at_tick(0, 2400).describe(),
+            "It's 05:00 on day 1 of the dream, dawn"
+        );
+    }
+}
@@ -0,0 +1,94 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::state::PlayerId;
+
+/// How many orders one player can have on the board at once
+pub const MAX_ORDERS: usize = 5;
+/// Artisans only take on orders paying at least this many times what each item's worth
+const ARTISAN_MIN_MARKUP: f64 = 1.5;
+/// The chance an artisan takes up a well paid order each time they look over the board
+const ARTISAN_CHANCE: f64 = 0.25;
+/// The most an artisan makes for one order in one go
+const ARTISAN_BATCH: u32 = 3;
+
+/// A standing request on the market board for some number of an item at a
+/// set price each. The gold for everything still wanted is held in escrow from
+/// the moment it's posted, so whoever fills it is always paid and the goods
+/// are mailed to the poster.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct WorkOrder {
+    pub id: u32,
+    pub poster: PlayerId,
+    pub poster_name: String,
+    pub item: String,
+    /// How many are still wanted
+    pub wanted: u32,
+    /// What's paid for each one
+    pub price: u32,
+}
+
+impl WorkOrder {
+    /// The gold held for what's still wanted
+    pub fn escrow(&self) -> u32 {
+        self.wanted.saturating_mul(self.price)
+    }
+
+    /// Fills up to `count` of the order, returning how many were taken and what they earned
+    pub fn fill(&mut self, count: u32) -> (u32, u32) {
+        let filled = count.min(self.wanted);
+        self.wanted -= filled;
+        (filled, filled.saturating_mul(self.price))
+    }
+
+    /// How many an NPC artisan makes for the order this time, if it pays well
+    /// enough for something worth `value` and they feel like it
+    pub fn artisan_work(&self, value: u32, rng: &mut impl Rng) -> u32 {
+        if (self.price as f64) < value.max(1) as f64 * ARTISAN_MIN_MARKUP
+            || !rng.gen_bool(ARTISAN_CHANCE)
+        {
+            return 0;
+        }
+        rng.gen_range(1..=ARTISAN_BATCH).min(self.wanted)
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "#{:<4} {:<30} x{:<4} {:>5} gold each, for {}",
+            self.id, self.item, self.wanted, self.price, self.poster_name
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn fills_orders() {
+        let mut order = WorkOrder {
+            id: 1,
+            poster: PlayerId::new_random(),
+            poster_name: "ada".into(),
+            item: "Honeycomb".into(),
+            wanted: 5,
+            price: 2,
+        };
+        assert_eq!(order.escrow(), 10);
+        assert_eq!(order.fill(3), (3, 6));
+        assert_eq!(order.fill(3), (2, 4));
+        assert_eq!(order.escrow(), 0);
+
+        order.wanted = 10;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+        assert!((0..50).all(|_| order.artisan_work(2, &mut rng) == 0));
+        let made: u32 = (0..50).map(|_| order.artisan_work(1, &mut rng)).sum();
+        assert!(made > 0);
+
+        order.price = u32::MAX;
+        assert_eq!(order.escrow(), u32::MAX);
+    }
+}
@@ -0,0 +1,167 @@
+//! Village workshops, where players make equipment out of materials. Whoever
+//! works there draws up a few blueprints the first time someone asks what they
+//! can make, each turning a handful of gathered or looted things into a piece of
+//! equipment.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+/// The most blueprints one workshop draws up
+pub const MAX_BLUEPRINTS: usize = 5;
+/// The most kinds of material one blueprint calls for
+pub const MAX_MATERIALS: usize = 3;
+/// The most of any one material a blueprint calls for
+pub const MAX_MATERIAL_COUNT: u32 = 5;
+
+/// How to make a piece of equipment
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Blueprint {
+    /// What it makes
+    pub name: String,
+    /// What it uses up and how many of each
+    pub materials: Vec<(String, u32)>,
+}
+
+impl Blueprint {
+    pub fn new(name: String, materials: Vec<(String, u32)>) -> Self {
+        Self { name, materials }
+    }
+
+    /// The blueprint cut down to materials that can be had, made no cheaper than
+    /// what it makes so crafting can't turn a profit at a vendor. `value_of` is
+    /// what a common one of something is worth, if it can be had at all.
+    pub fn settled(mut self, value_of: impl Fn(&str) -> Option<u32>) -> Option<Self> {
+        let product_value = value_of(&self.name)?;
+
+        let mut materials: BTreeMap<String, u32> = BTreeMap::new();
+        for (material, count) in self.materials {
+            if material != self.name && value_of(&material).is_some() {
+                *materials.entry(material).or_default() += count.max(1);
+            }
+        }
+        self.materials = materials
+            .into_iter()
+            .take(MAX_MATERIALS)
+            .map(|(material, count)| (material, count.min(MAX_MATERIAL_COUNT)))
+            .collect();
+        if self.materials.is_empty() {
+            return None;
+        }
+
+        let worth = |materials: &[(String, u32)]| -> u32 {
+            materials
+                .iter()
+                .map(|(m, count)| value_of(m).unwrap_or_default() * count)
+                .sum()
+        };
+        // Ask for more of the cheapest materials until it costs enough
+        while worth(&self.materials) < product_value {
+            let cheapest = self
+                .materials
+                .iter_mut()
+                .filter(|(_, count)| *count < MAX_MATERIAL_COUNT)
+                .min_by_key(|(m, _)| value_of(m).unwrap_or_default())?;
+            cheapest.1 += 1;
+        }
+        Some(self)
+    }
+
+    /// The materials as a list, like "Bone Dust x2, Rusty Dagger"
+    pub fn materials_list(&self) -> String {
+        self.materials
+            .iter()
+            .map(|(material, count)| match count {
+                1 => material.clone(),
+                count => format!("{material} x{count}"),
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Workshop {
+    pub blueprints: Vec<Blueprint>,
+}
+
+impl Workshop {
+    pub fn new(blueprints: Vec<Blueprint>) -> Self {
+        Self {
+            blueprints: blueprints.into_iter().take(MAX_BLUEPRINTS).collect(),
+        }
+    }
+
+    /// A blueprint by what it makes, ignoring case
+    pub fn blueprint(&self, name: &str) -> Option<&Blueprint> {
+        self.blueprints
+            .iter()
+            .find(|b| b.name.eq_ignore_ascii_case(name.trim()))
+    }
+
+    pub fn describe(&self, workshop: &str) -> String {
+        if self.blueprints.is_empty() {
+            return format!("Nobody at {workshop} knows how to make anything yet");
+        }
+        let mut res = format!("The blueprints pinned up in {workshop}\n\n");
+        for blueprint in &self.blueprints {
+            res.push_str(&format!(
+                "{}: {}\n",
+                blueprint.name,
+                blueprint.materials_list()
+            ));
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn settles_blueprints() {
+        let value_of = |name: &str| match name {
+            "Iron Sword" => Some(8),
+            "Rusty Dagger" => Some(2),
+            "Bone Dust" => Some(1),
+            _ => None,
+        };
+        let materials = |pairs: &[(&str, u32)]| {
+            pairs
+                .iter()
+                .map(|(m, c)| (m.to_string(), *c))
+                .collect::<Vec<_>>()
+        };
+
+        let sword = Blueprint::new(
+            "Iron Sword".into(),
+            materials(&[("Rusty Dagger", 1), ("Moonbeam", 3), ("Bone Dust", 1)]),
+        )
+        .settled(value_of)
+        .unwrap();
+        assert_eq!(
+            sword.materials,
+            materials(&[("Bone Dust", 5), ("Rusty Dagger", 2)])
+        );
+        assert_eq!(sword.materials_list(), "Bone Dust x5, Rusty Dagger x2");
+
+        let dagger = Blueprint::new("Rusty Dagger".into(), materials(&[("Bone Dust", 9)]));
+        assert_eq!(
+            dagger.settled(value_of).unwrap().materials,
+            materials(&[("Bone Dust", MAX_MATERIAL_COUNT)])
+        );
+
+        // Nothing it asks for can be had, or it makes something unknown
+        let moonbeam = Blueprint::new("Iron Sword".into(), materials(&[("Moonbeam", 1)]));
+        assert_eq!(moonbeam.settled(value_of), None);
+        let unknown = Blueprint::new("Sunbeam".into(), materials(&[("Bone Dust", 1)]));
+        assert_eq!(unknown.settled(value_of), None);
+
+        // Too valuable to make from so few materials
+        let crown = Blueprint::new("Iron Sword".into(), materials(&[("Bone Dust", 1)]));
+        assert_eq!(crown.settled(|n| value_of(n).map(|v| v * 10)), None);
+    }
+}
@@ -1,14 +1,80 @@
 use core::fmt;
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
     fmt::{Debug, Display},
+    path::PathBuf,
 };
 
 use serde::{Deserialize, Serialize};
 
-use crate::{state::{self, PlayerId}, AppErrors};
+use rand::{seq::SliceRandom, Rng};
 
-use super::character::Character;
+use crate::{
+    commands::GOLD,
+    config,
+    markup::{self, styled, Style},
+    state::{self, PlayerId, WorldChanges},
+    AppErrors,
+};
+
+use super::{
+    areas::AreaFile,
+    cartography,
+    character::{Attributes, Character},
+    combat::FightLog,
+    corpse::Corpse,
+    crafting::{self, Recipe},
+    entity::{self, EntityId},
+    faction::{self, Faction},
+    history::{self, EventKind, PlaceEvent},
+    home::Home,
+    items::{
+        Inventory, Item, ItemInstance, Quality, COMMODITY_VALUE, COMMODITY_WEIGHT,
+        MAGIC_LOOT_CHANCE,
+    },
+    ledger::{Ledger, Transaction},
+    lore::LoreKind,
+    overlay::{self, Overlay, OverlayKind},
+    party::Party,
+    shop::Shop,
+    stall::Stall,
+    storyline::Storyline,
+    telemetry::BalanceTelemetry,
+    transaction,
+    weather::{WeatherFront, WorldTime},
+    work_order::WorkOrder,
+    workshop::Workshop,
+};
+
+/// The built in village new players start in, so there's always somewhere
+/// to stand even before the generator has made anything
+const STARTING_VILLAGE: &str = include_str!("starting_village.yaml");
+
+/// Tag for the holding room players wait in until there's somewhere to spawn
+pub const VOID_TAG: &str = "void";
+/// Tag for places players can always log out from
+pub const SAFE_TAG: &str = "safe";
+/// Tag for places with someone who'll buy what players don't want
+pub const VENDOR_TAG: &str = "vendor";
+/// Tag for places players can rent market stalls in
+pub const MARKET_TAG: &str = "market";
+/// Tag for villages and the places in them, each village has a council
+pub const VILLAGE_TAG: &str = "village";
+/// Tag for places ghosts can revive at
+pub const SHRINE_TAG: &str = "shrine";
+/// Tag for inns, players can bind themselves to these and shrines
+pub const INN_TAG: &str = "inn";
+/// Tag for places with a cartographer, who buys and sells the village's map
+pub const CARTOGRAPHER_TAG: &str = "cartographer";
+/// Tag for vendor places that also sell their own wares, see [`Shop`]
+pub const SHOP_TAG: &str = "shop";
+/// Tag for places players can make equipment in, see [`Workshop`]
+pub const WORKSHOP_TAG: &str = "workshop";
+
+/// First names for the people keeping vendor places, picked by the place's name
+const KEEPER_NAMES: &[&str] = &[
+    "Marta", "Oswin", "Bryn", "Hilde", "Tobias", "Wenna", "Aldric", "Perrin", "Edda", "Corwin",
+];
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
@@ -17,40 +83,1064 @@ pub struct World {
     pub overworld_locales: Vec<Location>,
     pub player_characters: HashMap<PlayerId, Character>,
     pub current_tick: u64,
+    /// Fights currently underway, one per place
+    #[serde(default)]
+    pub fights: HashMap<Location, FightLog>,
+    /// The most recent finished fight each player took part in
+    #[serde(default)]
+    pub last_fights: HashMap<PlayerId, FightLog>,
+    #[serde(default)]
+    pub telemetry: BalanceTelemetry,
+    /// Which dream cycle this is, counting resets of the world
+    #[serde(default)]
+    pub cycle: u32,
+    #[serde(default)]
+    pub cycle_started_tick: u64,
+    /// Notable events in the world's history, kept across dream cycles
+    #[serde(default)]
+    pub chronicle: Vec<ChronicleEntry>,
+    /// Encyclopedia entries written so far, by the name of what they describe
+    #[serde(default)]
+    pub lore: BTreeMap<String, String>,
+    /// Dishes and potions players have invented, by recipe key
+    #[serde(default)]
+    pub recipes: BTreeMap<String, Recipe>,
+    /// What each kind of item is, by name. Equipment instances refer back to these.
+    #[serde(default)]
+    pub items: BTreeMap<String, Item>,
+    /// Each named NPC's personal storyline, written the first time someone asks
+    #[serde(default)]
+    pub storylines: BTreeMap<String, Storyline>,
+    /// The council of each village, by the village's overworld place
+    #[serde(default)]
+    pub factions: HashMap<Location, Faction>,
+    /// Work orders on the market boards, shared by every market
+    #[serde(default)]
+    pub work_orders: Vec<WorkOrder>,
+    /// Creatures living in each place
+    #[serde(default)]
+    pub npcs: HashMap<Location, Vec<Character>>,
+    /// The latest events in each place, oldest first
+    #[serde(default)]
+    pub history: HashMap<Location, VecDeque<PlaceEvent>>,
+    /// NPC adventurers roaming the world
+    #[serde(default)]
+    pub parties: Vec<Party>,
+    /// The weather over each region, by its overworld place
+    #[serde(default)]
+    pub weather: HashMap<Location, WeatherFront>,
+    /// Each village's map, every place explorers have told its cartographers of
+    #[serde(default)]
+    pub maps: HashMap<Location, HashSet<Location>>,
+    /// Gold and items made and destroyed since the last economy audit
+    #[serde(default)]
+    pub ledger: Ledger,
+    /// Where this world is saved, set when it's loaded
+    #[serde(skip)]
+    pub save_path: PathBuf,
+    /// What needs saving next time
+    #[serde(skip)]
+    pub changes: Changes,
+}
+
+/// The places and characters changed since the world was last saved. Players can
+/// only change the place they're in and the characters there, so marking those
+/// whenever a player acts catches most changes; anything done to the world from
+/// elsewhere marks what it touches. Places and characters are only ever removed
+/// when a new cycle starts, and new worlds save everything.
+#[derive(Debug, Clone)]
+pub struct Changes {
+    /// Set when the whole world needs saving
+    pub all: bool,
+    pub places: HashSet<Location>,
+    pub characters: HashSet<PlayerId>,
+}
+
+impl Default for Changes {
+    fn default() -> Self {
+        Self {
+            all: true,
+            places: HashSet::new(),
+            characters: HashSet::new(),
+        }
+    }
 }
 
 impl World {
-    pub fn load_or_default() -> Self {
-        let p = state::make_save_path("world.yaml");
-        if p.try_exists().unwrap_or_default() {
-            std::fs::read_to_string(p)
-                .and_then(|y| Ok(serde_yaml::from_str(&y)))
-                .expect("Could not read save file")
-                .expect("Could not deserialize")
+    /// Loads the world saved in the given directory inside the state directory
+    pub fn load_or_default(save_dir: &str) -> Self {
+        let mut world = Self::load(save_dir)
+            .expect("Could not load the world")
+            .unwrap_or_default();
+
+        world.save_path = state::make_save_path(save_dir).join("world.yaml");
+        world
+    }
+
+    /// Loads the world saved in the given directory, if there is one
+    pub fn load(save_dir: &str) -> anyhow::Result<Option<Self>> {
+        let p = state::make_save_path(save_dir).join("world.yaml");
+        let world = state::storage()?.load_world(&p)?;
+
+        Ok(world.map(|mut world| {
+            world.save_path = p;
+            world.unstack_equipment();
+            world
+        }))
+    }
+
+    /// Turns any equipment saved in stacks, from before each piece was its own
+    /// instance, into instances
+    pub fn unstack_equipment(&mut self) {
+        let registered: HashSet<String> = self.items.keys().cloned().collect();
+        let turned: u32 = self
+            .inventories_mut()
+            .into_iter()
+            .map(|i| i.unstack_equipment(|name| registered.contains(name)))
+            .sum();
+        if turned > 0 {
+            tracing::info!("Turned {turned} stacked pieces of equipment into instances");
+            self.changes.all = true;
+        }
+    }
+
+    /// Adds the built in starting village if it isn't in the world yet
+    pub fn ensure_starting_village(&mut self) {
+        let village: AreaFile =
+            serde_yaml::from_str(STARTING_VILLAGE).expect("Starting village should be valid");
+
+        let tag = village.tag();
+        if !self.places.values().any(|p| p.tags.contains(&tag)) {
+            village
+                .merge_into(self)
+                .expect("Starting village should merge into the world");
+            return;
+        }
+
+        // Villages saved before a room was given a tag, like the inn, pick it up
+        for room in &village.rooms {
+            for place in self
+                .places
+                .values_mut()
+                .filter(|p| p.name == room.name && p.tags.contains(&tag))
+            {
+                place.tags.extend(room.tags.iter().cloned());
+            }
+        }
+    }
+
+    /// Picks one of the configured spawn points that exists in the world,
+    /// or any overworld locale if none of them do
+    pub fn spawn_location(&self) -> Option<Location> {
+        let spawns: Vec<_> = config::get()
+            .spawn_points
+            .iter()
+            .filter_map(|name| self.places.values().find(|p| &p.name == name))
+            .map(|p| p.location)
+            .collect();
+
+        spawns
+            .choose(&mut rand::thread_rng())
+            .or(self.overworld_locales.first())
+            .copied()
+    }
+
+    /// The time of day, see [`WorldTime`]
+    pub fn time(&self) -> WorldTime {
+        let config = config::get();
+        let ticks_per_day = config.seconds_to_ticks(config.day_minutes * 60.0);
+        WorldTime::at_tick(self.current_tick, ticks_per_day)
+    }
+
+    /// The overworld place a location is part of
+    pub fn region_of(&self, location: Location) -> Location {
+        self.places
+            .get(&location)
+            .and_then(|p| p.parent)
+            .unwrap_or(location)
+    }
+
+    /// The weather over a location's region, if there's any that hasn't passed
+    pub fn weather_at(&self, location: Location) -> Option<&WeatherFront> {
+        self.weather
+            .get(&self.region_of(location))
+            .filter(|w| !w.passed(self.current_tick))
+    }
+
+    /// How the sky looks from a place, the light and the weather, or nothing
+    /// for places that can't see it
+    pub fn sky(&self, location: Location) -> Option<String> {
+        if !self.places.get(&location)?.outdoors() {
+            return None;
+        }
+        let sky: Vec<_> = self
+            .time()
+            .phase()
+            .light()
+            .into_iter()
+            .chain(self.weather_at(location).map(|w| w.description.as_str()))
+            .collect();
+        (!sky.is_empty()).then(|| sky.join(" "))
+    }
+
+    /// Whether ghosts can revive here, at a shrine or any spawn point
+    pub fn is_shrine(&self, location: Location) -> bool {
+        self.places.get(&location).is_some_and(|p| {
+            p.tags.contains(SHRINE_TAG) || config::get().spawn_points.contains(&p.name)
+        })
+    }
+
+    /// Whether players can bind themselves here, at a shrine or an inn
+    pub fn is_bind_point(&self, location: Location) -> bool {
+        self.is_shrine(location)
+            || self
+                .places
+                .get(&location)
+                .is_some_and(|p| p.tags.contains(INN_TAG))
+    }
+
+    /// Where the player comes to, the place they're bound to if it's still
+    /// around or one of the spawn points
+    pub fn respawn_location(&self, player: PlayerId) -> Option<Location> {
+        self.player_characters
+            .get(&player)
+            .and_then(|c| c.bound_to)
+            .filter(|l| self.places.contains_key(l))
+            .or_else(|| self.spawn_location())
+    }
+
+    /// The holding room for players when there's nowhere to spawn yet
+    pub fn void_location(&mut self) -> Location {
+        if let Some(void) = self.places.values().find(|p| p.tags.contains(VOID_TAG)) {
+            return void.location;
+        }
+
+        let mut void = Place::new(
+            "The Void".into(),
+            "Formless grey mist stretches in every direction. The world is still being dreamt, \
+            it will take shape around you soon."
+                .into(),
+        );
+        void.tags.insert(VOID_TAG.into());
+        void.tags.insert(SAFE_TAG.into());
+        let location = void.location;
+        self.places.insert(location, void);
+        self.place_changed(location);
+
+        location
+    }
+
+    /// Gets a player's character, making a new one at a spawn point if they don't have one yet
+    pub fn player_character(&mut self, player: PlayerId) -> &mut Character {
+        if !self.player_characters.contains_key(&player) {
+            let location = self
+                .spawn_location()
+                .unwrap_or_else(|| self.void_location());
+            let character = Character {
+                location,
+                ..Default::default()
+            };
+            self.player_characters.insert(player, character);
+        }
+
+        self.changes.characters.insert(player);
+        self.player_characters.get_mut(&player).unwrap()
+    }
+
+    /// Which creature in a place a player means, see [`entity::resolve`]
+    pub fn creature_named(&self, location: Location, name: &str) -> Option<EntityId> {
+        let creatures = self.npcs.get(&location)?;
+        entity::resolve(name, creatures.iter().map(|c| (c.id, c.name.as_str())))
+    }
+
+    /// Finds a creature anywhere in the world, and where it is
+    pub fn find_creature(&self, id: EntityId) -> Option<(Location, &Character)> {
+        self.npcs.iter().find_map(|(location, creatures)| {
+            creatures
+                .iter()
+                .find(|c| c.id == id)
+                .map(|c| (*location, c))
+        })
+    }
+
+    /// Takes a creature out of the world, wherever it is
+    pub fn remove_creature(&mut self, id: EntityId) -> Option<Character> {
+        let (location, _) = self.find_creature(id)?;
+        let creatures = self.npcs.get_mut(&location)?;
+        let idx = creatures.iter().position(|c| c.id == id)?;
+        self.changes.places.insert(location);
+        Some(creatures.remove(idx))
+    }
+
+    /// Whether a character's one of an adventurer party's rather than a player's
+    pub fn in_party(&self, player: PlayerId) -> bool {
+        self.parties.iter().any(|p| p.members.contains(&player))
+    }
+
+    /// The adventurer parties whose leader is in a place
+    pub fn parties_at(&self, location: Location) -> impl Iterator<Item = &Party> {
+        self.parties.iter().filter(move |p| {
+            self.player_characters
+                .get(&p.leader())
+                .is_some_and(|c| c.location == location)
+        })
+    }
+
+    /// Which party in a place a player means, by name with or without the "The"
+    pub fn party_named(&self, location: Location, name: &str) -> Option<usize> {
+        let name = name.trim();
+        let bare = |n: &str| {
+            let n = n.to_lowercase();
+            n.strip_prefix("the ").map(str::to_string).unwrap_or(n)
+        };
+        let here: Vec<_> = self.parties_at(location).map(|p| p.name.clone()).collect();
+        let wanted = here.iter().find(|n| bare(n) == bare(name))?;
+        self.parties.iter().position(|p| &p.name == wanted)
+    }
+
+    /// Notes that a place needs saving
+    pub fn place_changed(&mut self, location: Location) {
+        self.changes.places.insert(location);
+    }
+
+    /// Notes that a player's character and everything where they are needs saving
+    pub fn player_changed(&mut self, player: PlayerId) {
+        if let Some(character) = self.player_characters.get(&player) {
+            self.changes.places.insert(character.location);
+            self.changes.characters.insert(player);
+        }
+    }
+
+    /// Moves the player to a spawn point if their place no longer exists, or if they're
+    /// waiting in the void and the world has caught up. Returns true if they were moved.
+    pub fn check_player_location(&mut self, player: PlayerId) -> bool {
+        let location = self.player_character(player).location;
+        let in_void = self
+            .places
+            .get(&location)
+            .is_some_and(|p| p.tags.contains(VOID_TAG));
+
+        if self.places.contains_key(&location) && !in_void {
+            return false;
+        }
+
+        let new_location = match self.respawn_location(player) {
+            Some(l) => l,
+            None if in_void => return false,
+            None => self.void_location(),
+        };
+        self.player_character(player).location = new_location;
+
+        true
+    }
+
+    /// Add a new overworld map entry to the world and connect it to existing entries,
+    /// preferring the exploration frontier so the world grows where players are
+    pub fn add_overworld_locale(&mut self, mut place: Place) {
+        let frontier = self.frontier();
+        for ow_location in frontier.iter().chain(&self.overworld_locales) {
+            self.changes.places.insert(*ow_location);
+            let ow_place = self.places.get_mut(ow_location).unwrap();
+            // Limit to 5 connections to avoid adding things in the up direction
+            if ow_place.connections().len() < 5 {
+                let dir = ow_place
+                    .add_connection(Direction::North, place.location)
+                    .expect("Should be able to add overworld connection");
+                place
+                    .add_connection(dir.reverse(), *ow_location)
+                    .expect("Should be able to add overworld connection");
+                break;
+            }
+        }
+
+        self.overworld_locales.push(place.location);
+        self.place_changed(place.location);
+        self.places.insert(place.location, place);
+    }
+
+    fn explored(&self, location: &Location) -> bool {
+        self.player_characters
+            .values()
+            .any(|c| c.visited.contains(location))
+    }
+
+    /// How many overworld places no player has set foot in yet
+    pub fn unexplored_locales(&self) -> usize {
+        self.unexplored().count()
+    }
+
+    /// The name of the overworld place a place is in, for saying roughly where someone is
+    pub fn locale_name(&self, location: Location) -> Option<&str> {
+        let place = self.places.get(&location)?;
+        let region = place
+            .parent
+            .and_then(|p| self.places.get(&p))
+            .unwrap_or(place);
+        // Generated overworld places are named for telling them apart from what's inside
+        Some(region.name.trim_start_matches("Overworld - "))
+    }
+
+    /// Where the player's market stall is, if they've rented one
+    pub fn stall_location(&self, player: PlayerId) -> Option<Location> {
+        self.places
+            .values()
+            .find(|p| p.stalls.iter().any(|s| s.owner == player))
+            .map(|p| p.location)
+    }
+
+    /// Overworld places nobody has explored every corner of yet
+    pub fn unexplored(&self) -> impl Iterator<Item = &Place> {
+        self.overworld_locales
+            .iter()
+            .filter(|l| !self.explored(l))
+            .map(|l| &self.places[l])
+    }
+
+    /// The newest explored overworld place with room for another connection
+    fn frontier(&self) -> Option<Location> {
+        self.overworld_locales
+            .iter()
+            .rev()
+            .find(|l| self.explored(l) && self.places[l].connections().len() < 5)
+            .copied()
+    }
+
+    /// Heals a player, counting it in the fight they're in if there is one
+    pub fn heal_player(&mut self, player: PlayerId, amount: u32) {
+        let character = self.player_character(player);
+        let (name, location) = (character.name.clone(), character.location);
+        let healed = character.heal(amount);
+        if let Some(fight) = self.fights.get_mut(&location) {
+            if fight.players.contains(&player) && healed > 0 {
+                fight.record_healing(&name, healed);
+            }
+        }
+    }
+
+    /// Ends fights nobody's still in, once every player in them has left, been
+    /// knocked out or gone long enough without a blow
+    pub fn end_abandoned_fights(&mut self) {
+        let abandoned: Vec<_> = self
+            .fights
+            .iter()
+            .filter(|(location, fight)| {
+                !fight.players.iter().any(|p| {
+                    self.player_characters.get(p).is_some_and(|c| {
+                        c.location == **location
+                            && c.ghost.is_none()
+                            && c.in_combat(self.current_tick)
+                    })
+                })
+            })
+            .map(|(location, _)| *location)
+            .collect();
+        for location in abandoned {
+            self.end_fight(location);
+        }
+    }
+
+    /// Finish the fight happening at a location, keeping a copy of
+    /// the log for each player involved so they can recap it
+    pub fn end_fight(&mut self, location: Location) {
+        if let Some(fight) = self.fights.remove(&location) {
+            let region = self
+                .places
+                .get(&location)
+                .map(|p| p.parent.unwrap_or(location));
+            let region_name = region
+                .and_then(|r| self.places.get(&r))
+                .map(|p| p.name.as_str())
+                .unwrap_or("Unknown");
+            self.telemetry.record_fight(region_name, &fight);
+
+            self.changes.places.insert(location);
+            if let Some(place) = self.places.get_mut(&location) {
+                let (fresh, faded) = if fight.downed_players > 0 {
+                    (
+                        "Blood is spattered across the ground where someone fell in a recent fight.",
+                        "Faded bloodstains mark where someone fell in a fight.",
+                    )
+                } else {
+                    (
+                        "Scuffed footprints and a few fresh scratches show a fight happened here.",
+                        "Old scuffs show a fight happened here a while ago.",
+                    )
+                };
+                let lasts = config::get().seconds_to_ticks(config::get().battle_scar_seconds);
+                overlay::add(
+                    &mut place.overlays,
+                    Overlay::new(
+                        OverlayKind::BattleScars,
+                        fresh,
+                        Some(faded),
+                        self.current_tick,
+                        lasts,
+                    ),
+                );
+            }
+
+            for player in &fight.players {
+                self.last_fights.insert(*player, fight.clone());
+            }
+        }
+    }
+
+    /// How many steps each of an area's rooms is from the area's overworld place
+    pub fn area_depths(&self, region: Location) -> Vec<(Location, usize)> {
+        let mut depths = vec![(region, 0)];
+        let mut seen = HashSet::from([region]);
+        let mut i = 0;
+
+        while let Some((location, depth)) = depths.get(i).copied() {
+            for next in self.places[&location].connections().values() {
+                let in_area = self
+                    .places
+                    .get(next)
+                    .is_some_and(|p| p.parent == Some(region));
+                if in_area && seen.insert(*next) {
+                    depths.push((*next, depth + 1));
+                }
+            }
+            i += 1;
+        }
+
+        depths.remove(0);
+        depths
+    }
+
+    /// How many areas the named character is credited with discovering
+    pub fn discoveries_by(&self, name: &str) -> usize {
+        self.places
+            .values()
+            .filter_map(|p| p.discovery.as_ref())
+            .filter(|d| d.discoverer == name)
+            .count()
+    }
+
+    /// Adds an item's definition, unless there's already one by that name
+    pub fn register_item(&mut self, item: Item) {
+        self.items.entry(item.name.clone()).or_insert(item);
+    }
+
+    /// Registers the items and leaves one of each lying somewhere in the area,
+    /// the deeper into it they're left the better made and likelier to be magical they are
+    pub fn scatter_loot(&mut self, region: Location, items: Vec<Item>, rng: &mut impl Rng) {
+        let rooms = self.area_depths(region);
+        let max_depth = rooms.iter().map(|(_, d)| *d).max().unwrap_or_default();
+
+        for item in items {
+            if let Some((location, depth)) = rooms.choose(rng) {
+                let quality = match depth * 4 / (max_depth + 1) {
+                    0 => Quality::Crude,
+                    1 => Quality::Common,
+                    2 => Quality::Fine,
+                    _ => Quality::Masterwork,
+                };
+                let mut loot = ItemInstance::new(&item.name, quality);
+                if quality >= Quality::Fine && rng.gen_bool(MAGIC_LOOT_CHANCE) {
+                    let layers = if quality == Quality::Masterwork { 2 } else { 1 };
+                    for _ in 0..layers {
+                        let (attribute, _) = *Attributes::default().named().choose(rng).unwrap();
+                        loot.enchant(attribute);
+                    }
+                }
+                self.changes.places.insert(*location);
+                let place = self.places.get_mut(location).unwrap();
+                place.ground.add_instance(loot);
+                self.record_transaction("the world", &item.name, 1, "loot");
+            }
+            self.register_item(item);
+        }
+    }
+
+    /// What one of an item weighs. Coins weigh nothing, so saving up never
+    /// slows anyone down.
+    pub fn weight_of(&self, name: &str) -> u32 {
+        if name == GOLD {
+            return 0;
+        }
+        self.items.get(name).map_or(COMMODITY_WEIGHT, |i| i.weight)
+    }
+
+    /// What a common one of an item is worth, if it's equipment or an ingredient
+    pub fn base_value(&self, name: &str) -> Option<u32> {
+        if self.items.contains_key(name) {
+            Some(ItemInstance::new(name, Quality::Common).value())
+        } else if crafting::INGREDIENTS.iter().any(|i| i.name == name) {
+            Some(COMMODITY_VALUE)
         } else {
-            Self::default()
+            None
         }
     }
 
+    /// What vendors would pay for everything in an inventory, coins at face value
+    pub fn worth(&self, inventory: &Inventory) -> u32 {
+        let stacks = inventory.stacks().map(|s| match s.name.as_str() {
+            GOLD => s.count,
+            _ => s.count.saturating_mul(COMMODITY_VALUE),
+        });
+        let equipment = inventory.equipment().iter().map(|e| e.value());
+        stacks.chain(equipment).fold(0, u32::saturating_add)
+    }
+
+    /// Finds a fixed or invented recipe by the name of what it makes, ignoring case
+    pub fn find_recipe(&self, name: &str) -> Option<Recipe> {
+        crafting::known_recipes()
+            .chain(self.recipes.values().cloned())
+            .find(|r| r.name.eq_ignore_ascii_case(name))
+    }
+
+    /// The overworld place of the village a place is in, if it's in one
+    pub fn village_of(&self, location: Location) -> Option<Location> {
+        let place = self.places.get(&location)?;
+        let region = place.parent.unwrap_or(location);
+        let in_village = place.tags.contains(VILLAGE_TAG)
+            || self
+                .places
+                .get(&region)
+                .is_some_and(|r| r.tags.contains(VILLAGE_TAG));
+        in_village.then_some(region)
+    }
+
+    /// The council of the village a place is in, founding it if it's new
+    pub fn faction_mut(&mut self, location: Location) -> Option<&mut Faction> {
+        let region = self.village_of(location)?;
+        Some(self.factions.entry(region).or_default())
+    }
+
+    /// How well off the village a place is in is, if it's in one
+    pub fn prosperity(&self, location: Location) -> Option<faction::Prosperity> {
+        let region = self.village_of(location)?;
+        Some(
+            self.factions
+                .get(&region)
+                .map(Faction::prosperity)
+                .unwrap_or_else(|| Faction::default().prosperity()),
+        )
+    }
+
+    /// The overworld places of every village in the world
+    pub fn villages(&self) -> HashSet<Location> {
+        self.places
+            .keys()
+            .filter_map(|l| self.village_of(*l))
+            .collect()
+    }
+
+    /// Explored dungeons next to a village on the overworld, which pay it tribute
+    pub fn tributaries(&self, village: Location) -> usize {
+        self.places.get(&village).map_or(0, |v| {
+            v.connections()
+                .values()
+                .filter_map(|l| self.places.get(l))
+                .filter(|p| p.tags.contains("dungeon") && p.discovery.is_some())
+                .count()
+        })
+    }
+
+    /// Has a village's council pay for a festival, decorating every place in the village
+    pub fn hold_festival(&mut self, village: Location) -> bool {
+        let current_tick = self.current_tick;
+        let underway = self.places.get(&village).is_some_and(|p| {
+            p.overlays
+                .iter()
+                .any(|o| o.kind == OverlayKind::Decorations && !o.expired(current_tick))
+        });
+        if underway {
+            return false;
+        }
+
+        let paid = self
+            .factions
+            .entry(village)
+            .or_default()
+            .spend(faction::FESTIVAL_COST);
+        if !paid {
+            return false;
+        }
+
+        let lasts = config::get().seconds_to_ticks(config::get().festival_seconds);
+        let places: Vec<_> = self
+            .places
+            .values()
+            .filter(|p| p.location == village || p.parent == Some(village))
+            .map(|p| p.location)
+            .collect();
+        for location in places {
+            self.place_changed(location);
+            overlay::add(
+                &mut self.places.get_mut(&location).unwrap().overlays,
+                Overlay::new(
+                    OverlayKind::Decorations,
+                    "Garlands and paper lanterns are strung overhead for the festival.",
+                    Some("A few garlands left over from the festival still hang about."),
+                    current_tick,
+                    lasts,
+                ),
+            );
+        }
+        let name = self.places[&village].name.clone();
+        self.record_chronicle(format!("The council of {name} held a festival"));
+        true
+    }
+
+    /// Adds an entry to the chronicle, stamped with the current cycle and tick
+    pub fn record_chronicle(&mut self, text: String) {
+        self.chronicle.push(ChronicleEntry {
+            cycle: self.cycle,
+            tick: self.current_tick,
+            text,
+        });
+    }
+
+    /// Adds an event to a place's history, stamped with the current tick
+    pub fn record_event(&mut self, location: Location, kind: EventKind) {
+        let event = PlaceEvent {
+            tick: self.current_tick,
+            kind,
+        };
+        let history = self.history.entry(location).or_default();
+        history::record(history, event, config::get().place_history_length);
+    }
+
+    /// The latest events in a place as they'd be told, oldest first
+    pub fn recent_events(&self, location: Location, count: usize) -> Vec<String> {
+        let events = self.history.get(&location).into_iter().flatten();
+        let skip = events.clone().count().saturating_sub(count);
+        events.skip(skip).map(|e| e.kind.to_string()).collect()
+    }
+
+    /// Marks the player's current place as visited. If that finishes exploring an
+    /// unclaimed generated area, the player is credited as its discoverer and the
+    /// area's location is returned.
+    pub fn visit(&mut self, player: PlayerId) -> Option<Location> {
+        let location = self.player_character(player).location;
+        let creatures: Vec<_> = self
+            .npcs
+            .get(&location)
+            .into_iter()
+            .flatten()
+            .map(|c| c.name.clone())
+            .collect();
+
+        let character = self.player_character(player);
+        for creature in creatures {
+            character.encountered.insert(creature, LoreKind::Creature);
+        }
+        if !character.visited.insert(location) {
+            return None;
+        }
+
+        let region = self.places.get(&location)?.parent?;
+        let region_place = &self.places[&region];
+        if region_place.discovery.is_some() || region_place.is_handcrafted() {
+            return None;
+        }
+
+        let visited = &self.player_characters[&player].visited;
+        let explored = visited.contains(&region)
+            && self
+                .places
+                .values()
+                .filter(|p| p.parent == Some(region))
+                .all(|p| visited.contains(&p.location));
+        if !explored {
+            return None;
+        }
+
+        let discoverer = self.player_characters[&player].name.clone();
+        self.place_changed(region);
+        let region_place = self.places.get_mut(&region).unwrap();
+        region_place.discovery = Some(Discovery {
+            discoverer: discoverer.clone(),
+            renamed: false,
+        });
+        let text = format!(
+            "{discoverer} was the first to explore {}",
+            region_place.name
+        );
+        self.record_chronicle(text);
+
+        Some(region)
+    }
+
     /// Increment the current tick count and then check and save if needed
     pub fn tick_and_check_save(&mut self, interval: u64) {
         self.current_tick += 1;
 
-        if self.current_tick % interval == 0 {
-            let world_copy = self.clone();
-            // We don't have an async context to use for IO here so save on a seperate thread
-            std::thread::spawn(move || {
-                let yaml = serde_yaml::to_string(&world_copy);
-                let save = match yaml {
-                    Ok(y) => std::fs::write(state::make_save_path("world.yaml"), y),
-                    Err(e) => Ok(tracing::error!("Failed serializing world: {e}")),
+        if self.current_tick.is_multiple_of(interval) {
+            let current_tick = self.current_tick;
+            for place in self.places.values_mut() {
+                let overlays = place.overlays.len();
+                place.overlays.retain(|o| !o.expired(current_tick));
+                if place.overlays.len() != overlays {
+                    self.changes.places.insert(place.location);
+                }
+            }
+
+            state::persist(self.take_changes());
+        }
+    }
+
+    /// Copies out everything that's changed since the last save, the whole world if
+    /// it's never been saved, leaving the world marked as saved
+    pub fn take_changes(&mut self) -> WorldChanges {
+        let changes = std::mem::replace(
+            &mut self.changes,
+            Changes {
+                all: false,
+                ..Default::default()
+            },
+        );
+        let places = std::mem::take(&mut self.places);
+        let player_characters = std::mem::take(&mut self.player_characters);
+        let mut copy = self.clone();
+        self.places = places;
+        self.player_characters = player_characters;
+
+        if changes.all {
+            copy.places = self.places.clone();
+            copy.player_characters = self.player_characters.clone();
+            return WorldChanges {
+                world: copy,
+                full: true,
+            };
+        }
+
+        copy.places = changes
+            .places
+            .iter()
+            .filter_map(|l| Some((*l, self.places.get(l)?.clone())))
+            .collect();
+        copy.player_characters = self
+            .player_characters
+            .iter()
+            .filter(|(p, c)| changes.characters.contains(p) || changes.places.contains(&c.location))
+            .map(|(p, c)| (*p, c.clone()))
+            .collect();
+        WorldChanges {
+            world: copy,
+            full: false,
+        }
+    }
+
+    /// Writes the world to the configured storage under its save path, blocking until done
+    pub fn save(&self) -> anyhow::Result<()> {
+        state::storage()?.save_world(self)
+    }
+
+    /// Every inventory players' things can be in, carried, mailed, dropped, left on
+    /// a corpse or up for sale
+    pub fn inventories_mut(&mut self) -> Vec<&mut Inventory> {
+        let mut res = Vec::new();
+        for character in self.player_characters.values_mut() {
+            res.push(&mut character.inventory);
+            res.push(&mut character.mailbox);
+            if let Some(companion) = &mut character.companion {
+                res.push(&mut companion.creature.inventory);
+            }
+        }
+        for place in self.places.values_mut() {
+            res.push(&mut place.ground);
+            for stall in &mut place.stalls {
+                res.push(&mut stall.stock);
+            }
+            for corpse in &mut place.corpses {
+                res.push(&mut corpse.contents);
+            }
+        }
+        res
+    }
+
+    /// How much of each item players hold, counting equipment by what it was made
+    /// from and gold waiting in stalls and work order escrow
+    pub fn holdings(&mut self) -> BTreeMap<String, i64> {
+        let mut res = BTreeMap::<String, i64>::new();
+        for inventory in self.inventories_mut() {
+            for (item, count) in inventory.counts() {
+                *res.entry(item).or_default() += count;
+            }
+        }
+        for piece in self.player_characters.values().flat_map(|c| &c.wielding) {
+            *res.entry(piece.template.clone()).or_default() += 1;
+        }
+
+        let gold = self
+            .places
+            .values()
+            .flat_map(|p| &p.stalls)
+            .map(|s| i64::from(s.proceeds))
+            .chain(self.work_orders.iter().map(|o| i64::from(o.escrow())))
+            .sum::<i64>();
+        if gold > 0 {
+            *res.entry(GOLD.to_string()).or_default() += gold;
+        }
+        res.retain(|_, count| *count != 0);
+        res
+    }
+
+    /// Writes gold or items made or destroyed in the ledger, a negative change
+    /// for destroyed
+    pub fn record_transaction(&mut self, who: &str, item: &str, change: i64, why: &str) {
+        self.ledger.record(Transaction {
+            tick: self.current_tick,
+            who: who.to_string(),
+            item: item.to_string(),
+            change,
+            why: why.to_string(),
+        });
+    }
+
+    /// Starts staging changes to several characters and places at once, see
+    /// [`transaction::Transaction`]
+    pub fn transaction(&mut self) -> transaction::Transaction<'_> {
+        transaction::Transaction::new(self)
+    }
+
+    /// Fixes records that point at places that no longer exist or disagree with
+    /// each other, returning what was changed. Meant for saves edited by hand or
+    /// left broken by a crash, a healthy world needs nothing done.
+    pub fn repair(&mut self) -> Vec<String> {
+        let mut fixes = Vec::new();
+        let locations: HashSet<Location> = self.places.keys().copied().collect();
+
+        for (location, place) in &mut self.places {
+            if place.location != *location {
+                fixes.push(format!("{} was filed under the wrong location", place.name));
+                place.location = *location;
+            }
+            if place.parent.is_some_and(|p| !locations.contains(&p)) {
+                fixes.push(format!("{}'s parent area is gone", place.name));
+                place.parent = None;
+            }
+
+            let dangling: Vec<_> = place
+                .connections
+                .iter()
+                .filter(|(_, l)| !locations.contains(l))
+                .map(|(d, _)| *d)
+                .collect();
+            for direction in dangling {
+                fixes.push(format!(
+                    "{}'s exit {} led nowhere",
+                    place.name,
+                    direction.name()
+                ));
+                place.remove_connection(direction);
+            }
+        }
+
+        let mut seen = HashSet::new();
+        let locales = self.overworld_locales.len();
+        self.overworld_locales
+            .retain(|l| locations.contains(l) && seen.insert(*l));
+        if self.overworld_locales.len() < locales {
+            fixes.push(format!(
+                "{} overworld locales were missing or listed twice",
+                locales - self.overworld_locales.len()
+            ));
+        }
+
+        let orphans = self.npcs.len() + self.factions.len() + self.weather.len() + self.maps.len();
+        self.npcs.retain(|l, _| locations.contains(l));
+        self.factions.retain(|l, _| locations.contains(l));
+        self.weather.retain(|l, _| locations.contains(l));
+        self.maps.retain(|l, _| locations.contains(l));
+        let kept = self.npcs.len() + self.factions.len() + self.weather.len() + self.maps.len();
+        if kept < orphans {
+            fixes.push(format!(
+                "{} NPC, council, weather or map records were for places that are gone",
+                orphans - kept
+            ));
+        }
+
+        if !fixes.is_empty() {
+            self.changes.all = true;
+        }
+        fixes
+    }
+
+    /// Ends the dream cycle, swapping in an empty world that keeps the characters but
+    /// only a slice of their items. Returns the old world so it can be archived.
+    pub fn next_cycle(&mut self, wealth_carryover: f64) -> World {
+        if let Some((location, knockouts)) = history::bloodiest(&self.history) {
+            let name = self
+                .places
+                .get(&location)
+                .map_or("somewhere forgotten", |p| &p.name);
+            let text = format!(
+                "More blood was spilled at {name} than anywhere else, {knockouts} were knocked out there"
+            );
+            self.record_chronicle(text);
+        }
+
+        // Parties are formed afresh each cycle, so their members aren't kept
+        let player_characters = self
+            .player_characters
+            .iter()
+            .filter(|(player, _)| !self.in_party(**player))
+            .map(|(player, character)| {
+                let carried = Character {
+                    id: character.id,
+                    name: character.name.clone(),
+                    // Levels are kept, they're what the raised attributes were earned with
+                    attributes: character.attributes.clone(),
+                    experience: character.experience,
+                    inventory: character.inventory.scaled(wealth_carryover),
+                    pvp_consent: character.pvp_consent,
+                    companion: character.companion.clone(),
+                    relationships: character.relationships.clone(),
+                    stories: character.stories.clone(),
+                    mailbox: character.mailbox.clone(),
+                    letters: character.letters.clone(),
+                    aliases: character.aliases.clone(),
+                    ..Default::default()
                 };
+                (*player, carried)
+            })
+            .collect();
 
-                if let Err(e) = save {
-                    tracing::error!("Failed saving world: {e}");
-                }
-            });
+        let next = World {
+            player_characters,
+            current_tick: self.current_tick,
+            telemetry: self.telemetry.clone(),
+            chronicle: self.chronicle.clone(),
+            lore: self.lore.clone(),
+            recipes: self.recipes.clone(),
+            items: self.items.clone(),
+            storylines: self.storylines.clone(),
+            work_orders: self.work_orders.clone(),
+            cycle: self.cycle + 1,
+            cycle_started_tick: self.current_tick,
+            save_path: self.save_path.clone(),
+            ..Default::default()
+        };
+
+        std::mem::replace(self, next)
+    }
+
+    /// Saves the world next to its usual save in the archive, for the chronicle
+    pub fn archive(mut self) -> anyhow::Result<()> {
+        let dir = self
+            .save_path
+            .parent()
+            .map(|p| p.join("archive"))
+            .unwrap_or_else(|| "archive".into());
+        self.save_path = dir.join(format!("world-cycle-{}.yaml", self.cycle));
+        self.save()
+    }
+
+    /// Copies this world's save into another realm's save directory so changes can be
+    /// tried against a realistic world. Optionally empties every character's inventory.
+    pub fn clone_to_realm(mut self, save_dir: &str, scrub_inventories: bool) -> anyhow::Result<()> {
+        if scrub_inventories {
+            for character in self.player_characters.values_mut() {
+                character.inventory = Default::default();
+            }
         }
+
+        self.save_path = state::make_save_path(save_dir).join("world.yaml");
+        self.save()
     }
 }
 
@@ -65,6 +1155,33 @@ pub struct Place {
     pub location: Location,
     pub description: String,
     pub tags: HashSet<String>,
+    /// The overworld place this one belongs to, if it's part of a larger area
+    #[serde(default)]
+    pub parent: Option<Location>,
+    /// Who first explored every corner of this place, only set on overworld places
+    #[serde(default)]
+    pub discovery: Option<Discovery>,
+    /// Marks left by things that happened here, layered over the description
+    #[serde(default)]
+    pub overlays: Vec<Overlay>,
+    /// Whatever's lying around to be picked up
+    #[serde(default)]
+    pub ground: Inventory,
+    /// Market stalls players have rented here
+    #[serde(default)]
+    pub stalls: Vec<Stall>,
+    /// Bodies left by characters who fell here, holding what they lost
+    #[serde(default)]
+    pub corpses: Vec<Corpse>,
+    /// What's sold here, none until someone first asks
+    #[serde(default)]
+    pub shop: Option<Shop>,
+    /// The blueprints that can be made here, none until someone first asks
+    #[serde(default)]
+    pub workshop: Option<Workshop>,
+    /// Who lives here and how they've decorated, if it's been deeded to a player
+    #[serde(default)]
+    pub home: Option<Home>,
     connections: HashMap<Direction, Location>,
 }
 
@@ -75,6 +1192,15 @@ impl Place {
             description,
             location: Location::new_location(),
             tags: Default::default(),
+            parent: None,
+            discovery: None,
+            overlays: Vec::new(),
+            ground: Default::default(),
+            stalls: Vec::new(),
+            corpses: Vec::new(),
+            shop: None,
+            workshop: None,
+            home: None,
             connections: Default::default(),
         }
     }
@@ -95,8 +1221,7 @@ impl Place {
         if self.connections.contains_key(&direction) {
             let next_dir = directions
                 .iter()
-                .filter(|d| !self.connections.contains_key(&d))
-                .next()
+                .find(|d| !self.connections.contains_key(d))
                 .unwrap();
 
             self.connections.insert(*next_dir, location);
@@ -107,9 +1232,14 @@ impl Place {
         }
     }
 
+    /// Removes the way out in a direction, returning where it led
+    pub fn remove_connection(&mut self, direction: Direction) -> Option<Location> {
+        self.connections.remove(&direction)
+    }
+
     /// Checks if a given location is directly adjacent to this one
     pub fn is_connected(&self, location: Location) -> bool {
-        for (_, l) in &self.connections {
+        for l in self.connections.values() {
             if *l == location {
                 return true;
             }
@@ -121,13 +1251,193 @@ impl Place {
         &self.connections
     }
 
+    /// The named person keeping a vendor place, always the same for the same place name
+    pub fn keeper(&self) -> Option<String> {
+        if !self.tags.contains(VENDOR_TAG) {
+            return None;
+        }
+        let idx = seahash::hash(self.name.as_bytes()) as usize % KEEPER_NAMES.len();
+        Some(format!("{} of {}", KEEPER_NAMES[idx], self.name))
+    }
+
+    /// Whether the sky can be seen from here, dungeons, shops, inns and the void are shut off from it
+    pub fn outdoors(&self) -> bool {
+        !["dungeon", VENDOR_TAG, INN_TAG, VOID_TAG]
+            .iter()
+            .any(|t| self.tags.contains(*t))
+    }
+
+    /// The cartographer working here, if there is one
+    pub fn cartographer(&self) -> Option<String> {
+        if !self.tags.contains(CARTOGRAPHER_TAG) {
+            return None;
+        }
+        let names = cartography::CARTOGRAPHER_NAMES;
+        let idx = seahash::hash(self.name.as_bytes()) as usize % names.len();
+        Some(format!("{} the cartographer", names[idx]))
+    }
+
+    /// Whether this place came from a hand authored area rather than the generator
+    pub fn is_handcrafted(&self) -> bool {
+        self.tags.iter().any(|t| t.starts_with("area:"))
+    }
+
     /// Generates the "look" text for the given place, describing what your character can see
     pub fn look(&self, world: &World, start: &str) -> String {
-        let mut look_msg = format!("{start} {}\n\n{}\n\n", self.name, self.description);
+        let mut description =
+            overlay::compose(&self.description, &self.overlays, world.current_tick);
+        if let Some(look) = self.home.as_ref().and_then(|h| h.decoration.look.as_ref()) {
+            description.push(' ');
+            description.push_str(&markup::escape(look));
+        }
+        let prosperity = world
+            .prosperity(self.location)
+            .map(|p| p.description())
+            .unwrap_or_default();
+        if !prosperity.is_empty() {
+            description.push(' ');
+            description.push_str(prosperity);
+        }
+        if let Some(sky) = world.sky(self.location) {
+            description.push(' ');
+            description.push_str(&sky);
+        }
+        let mut look_msg = format!(
+            "{start} {}\n\n{description}\n\n",
+            styled(Style::Title, &self.name)
+        );
+        let discovery = world
+            .places
+            .get(&self.parent.unwrap_or(self.location))
+            .and_then(|p| p.discovery.as_ref());
+        if let Some(discovery) = discovery {
+            look_msg.push_str(&styled(
+                Style::Good,
+                format!("First explored by {}\n\n", discovery.discoverer),
+            ));
+        }
+        let creatures: Vec<_> = world
+            .npcs
+            .get(&self.location)
+            .into_iter()
+            .flatten()
+            .map(|c| styled(Style::Name, &c.name))
+            .collect();
+        if !creatures.is_empty() {
+            look_msg.push_str(&format!("Creatures here: {}\n\n", creatures.join(", ")));
+        }
+        for party in world.parties_at(self.location) {
+            let members: Vec<_> = party
+                .members
+                .iter()
+                .filter_map(|m| world.player_characters.get(m))
+                .map(|c| c.name.as_str())
+                .collect();
+            let status = match party.stranded_since {
+                Some(_) => format!(
+                    " lie knocked out here, 'rescue {}' to help them",
+                    party.name
+                ),
+                None => " are here".to_string(),
+            };
+            look_msg.push_str(&format!(
+                "{} ({}){status}\n\n",
+                styled(Style::Name, &party.name),
+                members.join(", ")
+            ));
+        }
+        for body in world
+            .player_characters
+            .values()
+            .filter(|c| c.ghost == Some(self.location))
+        {
+            look_msg.push_str(&format!(
+                "The body of {} lies here\n\n",
+                styled(Style::Name, &body.name)
+            ));
+        }
+        for corpse in &self.corpses {
+            look_msg.push_str(&format!(
+                "The corpse of {} lies here\n\n",
+                styled(Style::Name, &corpse.owner_name)
+            ));
+        }
+        if let Some(keeper) = self.keeper() {
+            look_msg.push_str(&format!(
+                "{} keeps this place\n\n",
+                styled(Style::Name, keeper)
+            ));
+        }
+        if let Some(cartographer) = self.cartographer() {
+            look_msg.push_str(&format!(
+                "{} pores over a half finished map, 'chart' to trade what you've seen\n\n",
+                styled(Style::Name, cartographer)
+            ));
+        }
+        if self.tags.contains(WORKSHOP_TAG) {
+            look_msg.push_str(
+                "Tools hang ready over a workbench, 'blueprints' to see what can be made\n\n",
+            );
+        }
+        if !self.stalls.is_empty() {
+            let stalls: Vec<_> = self
+                .stalls
+                .iter()
+                .map(|s| format!("{}'s", styled(Style::Name, &s.owner_name)))
+                .collect();
+            look_msg.push_str(&format!("Market stalls: {}\n\n", stalls.join(", ")));
+        }
+        let ground: Vec<_> = self
+            .ground
+            .equipment()
+            .iter()
+            .map(|i| i.name())
+            .chain(self.ground.stacks().map(|s| match s.count {
+                1 => s.name.clone(),
+                count => format!("{} x{count}", s.name),
+            }))
+            .collect();
+        if !ground.is_empty() {
+            look_msg.push_str(&format!("On the ground: {}\n\n", ground.join(", ")));
+        }
+
+        for (dir, loc) in self.connections() {
+            look_msg.push_str(&format!(
+                "Looking {} you see {}\n",
+                styled(Style::Exit, dir.name()),
+                world.places[loc].name
+            ));
+        }
+        look_msg
+    }
+
+    /// The opening sentence of the description, enough to tell what the place is
+    pub fn first_sentence(&self) -> &str {
+        self.description
+            .split_inclusive(['.', '!', '?'])
+            .next()
+            .unwrap_or_default()
+            .trim()
+    }
+
+    /// What a ghost sees, the shape of the place and its exits through a grey
+    /// haze, but nobody and nothing in it
+    pub fn ghost_look(&self, world: &World, start: &str) -> String {
+        let mut look_msg = format!(
+            "{start} {}\n\n{}\n\n",
+            styled(Style::Title, &self.name),
+            styled(
+                Style::Faint,
+                format!(
+                    "{} Everything else is lost in a grey haze.",
+                    self.first_sentence()
+                )
+            )
+        );
         for (dir, loc) in self.connections() {
             look_msg.push_str(&format!(
                 "Looking {} you see {}\n",
-                dir.name(),
+                styled(Style::Exit, dir.name()),
                 world.places[loc].name
             ));
         }
@@ -135,6 +1445,25 @@ impl Place {
     }
 }
 
+/// Credit for the first player to fully explore a generated area
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Discovery {
+    pub discoverer: String,
+    /// Discoverers get to name the place once
+    #[serde(default)]
+    pub renamed: bool,
+}
+
+/// Something worth remembering that happened in the world
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ChronicleEntry {
+    pub cycle: u32,
+    pub tick: u64,
+    pub text: String,
+}
+
 /// A unique key for each Place. It's default state is invalid,
 /// to get a valid new Location call `new_location`.
 #[derive(Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq, Hash)]
@@ -154,6 +1483,26 @@ impl Location {
         let v: u128 = rand::random();
         Self(v.saturating_add(1))
     }
+
+    /// Reads a location back from its hex id, as displayed like
+    /// `Location (1f3a)` or just the hex
+    pub fn parse(s: &str) -> Option<Self> {
+        let hex = s.trim();
+        let hex = hex
+            .strip_prefix("Location (")
+            .and_then(|h| h.strip_suffix(')'))
+            .unwrap_or(hex);
+        let hex = hex.strip_prefix("0x").unwrap_or(hex);
+        u128::from_str_radix(hex, 16)
+            .ok()
+            .filter(|v| *v != 0)
+            .map(Self)
+    }
+
+    /// Just the hex of the id, as scripts are handed it
+    pub fn hex(&self) -> String {
+        format!("{:x}", self.0)
+    }
 }
 
 impl Display for Location {
@@ -193,6 +1542,15 @@ impl Direction {
         ]
     }
 
+    /// Describes arriving from this direction, like "from the north" or "from below"
+    pub fn arrival(self) -> String {
+        match self {
+            Direction::Up => "from above".to_string(),
+            Direction::Down => "from below".to_string(),
+            d => format!("from the {}", d.name()),
+        }
+    }
+
     pub fn reverse(self) -> Self {
         match self {
             Direction::North => Direction::South,
@@ -215,3 +1573,358 @@ impl Direction {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn parses_locations_as_displayed() {
+        let location = Location::new_location();
+        assert_eq!(Location::parse(&location.to_string()), Some(location));
+        assert_eq!(Location::parse("1f3a"), Some(Location(0x1f3a)));
+        assert_eq!(Location::parse(" 0x1F3A "), Some(Location(0x1f3a)));
+        assert_eq!(Location::parse("0"), None);
+        assert_eq!(Location::parse("Old Shrine"), None);
+    }
+
+    #[test]
+    fn next_cycle_keeps_characters() {
+        let mut world = World::default();
+        world.ensure_starting_village();
+        world.current_tick = 500;
+
+        let player = PlayerId::new_random();
+        world
+            .player_character(player)
+            .inventory
+            .add("Gold Coin", 25);
+        world.player_character(player).inventory.add("Torch", 1);
+        world
+            .player_character(player)
+            .aliases
+            .insert("gg".into(), "get gold".into());
+        let location = world.player_character(player).location;
+        let (party, _) = Party::new(0, &mut rand::thread_rng());
+        for member in &party.members {
+            world.player_character(*member);
+        }
+        world.parties.push(party);
+        world.record_event(location, EventKind::Arrived("Ada".into()));
+        world.record_event(location, EventKind::KnockedOut("Ada".into(), "Bryn".into()));
+        assert_eq!(
+            world.recent_events(location, 1),
+            ["Bryn knocked out Ada".to_string()]
+        );
+
+        let old = world.next_cycle(0.2);
+
+        assert_eq!(old.cycle, 0);
+        assert_eq!(world.cycle, 1);
+        assert_eq!(world.cycle_started_tick, 500);
+        assert!(world.places.is_empty());
+        assert!(!old.places.is_empty());
+        assert!(world.history.is_empty());
+        assert!(world.parties.is_empty());
+        assert_eq!(world.player_characters.len(), 1);
+        let summary = &world.chronicle.last().unwrap().text;
+        assert!(summary.contains(&old.places[&location].name));
+
+        let inventory = &world.player_characters[&player].inventory;
+        assert_eq!(inventory.get("Gold Coin").map(|i| i.count), Some(5));
+        assert_eq!(inventory.get("Torch"), None);
+        assert_eq!(world.player_characters[&player].aliases.len(), 1);
+
+        assert!(world.check_player_location(player));
+    }
+
+    #[test]
+    fn levels_carry_over_cycles() {
+        let mut world = World::default();
+        let player = PlayerId::new_random();
+        world.player_character(player).gain_experience(1000);
+        let levelled = world.player_characters[&player].clone();
+        assert!(levelled.level() > 1);
+
+        world.next_cycle(0.2);
+        world.next_cycle(0.2);
+        let character = &world.player_characters[&player];
+        assert_eq!(character.level(), levelled.level());
+        assert_eq!(character.attributes, levelled.attributes);
+    }
+
+    #[test]
+    fn abandoned_fights_end() {
+        let mut world = World::default();
+        world.ensure_starting_village();
+        world.current_tick = 10;
+        let player = PlayerId::new_random();
+        let character = world.player_character(player);
+        character.name = "Ada".into();
+        character.in_combat_until = 20;
+        character.health = 1;
+        let location = character.location;
+        world
+            .fights
+            .entry(location)
+            .or_default()
+            .players
+            .insert(player);
+
+        world.heal_player(player, 3);
+        assert_eq!(world.fights[&location].participants["Ada"].healing, 3);
+        world.end_abandoned_fights();
+        assert!(world.fights.contains_key(&location));
+
+        world.player_character(player).location = Location::new_location();
+        world.end_abandoned_fights();
+        assert!(world.fights.is_empty());
+        assert_eq!(world.last_fights[&player].participants["Ada"].healing, 3);
+    }
+
+    #[test]
+    fn repairs_broken_records() {
+        let mut world = World::default();
+        world.ensure_starting_village();
+        assert!(world.repair().is_empty());
+
+        let gone = Location::new_location();
+        let location = world.overworld_locales[0];
+        let place = world.places.get_mut(&location).unwrap();
+        let direction = Direction::values()
+            .into_iter()
+            .find(|d| !place.connections().contains_key(d))
+            .unwrap();
+        place.add_connection(direction, gone).unwrap();
+        place.parent = Some(gone);
+        world.overworld_locales.push(location);
+        world.npcs.insert(gone, Vec::new());
+
+        assert_eq!(world.repair().len(), 4);
+        let place = &world.places[&location];
+        assert!(!place.is_connected(gone));
+        assert_eq!(place.parent, None);
+        assert_eq!(world.overworld_locales, [location]);
+        assert!(world.npcs.is_empty());
+        assert!(world.repair().is_empty());
+    }
+
+    #[test]
+    fn takes_only_changes() {
+        let mut world = World::default();
+        world.ensure_starting_village();
+        let player = PlayerId::new_random();
+        let location = world.player_character(player).location;
+        let bystander = PlayerId::new_random();
+        world.player_character(bystander).location = location;
+
+        let first = world.take_changes();
+        assert!(first.full);
+        assert_eq!(first.world.places.len(), world.places.len());
+
+        let nothing = world.take_changes();
+        assert!(!nothing.full);
+        assert!(nothing.world.places.is_empty() && nothing.world.player_characters.is_empty());
+
+        world.current_tick = 7;
+        world.player_changed(player);
+        let changes = world.take_changes();
+        assert_eq!(changes.world.current_tick, 7);
+        assert_eq!(changes.world.places.keys().collect::<Vec<_>>(), [&location]);
+        assert_eq!(changes.world.player_characters.len(), 2);
+        assert!(!world.places.is_empty());
+
+        world.next_cycle(0.2);
+        assert!(world.take_changes().full);
+    }
+
+    #[test]
+    fn spawns_in_void_until_village() {
+        let mut world = World::default();
+        let player = PlayerId::new_random();
+
+        let void = world.player_character(player).location;
+        assert!(world.places[&void].tags.contains(VOID_TAG));
+        assert!(!world.check_player_location(player));
+
+        world.ensure_starting_village();
+        world.ensure_starting_village();
+        assert!(world.check_player_location(player));
+
+        let location = world.player_character(player).location;
+        assert_eq!(world.places[&location].name, "Village Square");
+        assert_eq!(world.overworld_locales.len(), 1);
+    }
+
+    #[test]
+    fn ghosts_revive_at_shrines() {
+        let mut world = World::default();
+        world.ensure_starting_village();
+        let named = |name: &str| {
+            world
+                .places
+                .values()
+                .find(|p| p.name == name)
+                .unwrap()
+                .location
+        };
+
+        assert!(world.is_shrine(named("Old Shrine")));
+        assert!(world.is_shrine(named("Village Square")));
+        assert!(!world.is_shrine(named("Lantern Street")));
+
+        let shrine = &world.places[&named("Old Shrine")];
+        assert_eq!(
+            shrine.first_sentence(),
+            "You kneel in a quiet shrine to a forgotten sleeping god."
+        );
+        assert!(!shrine
+            .ghost_look(&world, "You drift through")
+            .contains("Candle"));
+    }
+
+    #[test]
+    fn sky_shows_outdoors() {
+        let mut world = World::default();
+        world.ensure_starting_village();
+        let player = PlayerId::new_random();
+        let square = world.player_character(player).location;
+        let config = config::get();
+        // Late evening on the first day
+        world.current_tick = config.seconds_to_ticks(config.day_minutes * 60.0) * 17 / 24;
+        assert!(world.sky(square).unwrap().contains("Moonlight"));
+
+        let region = world.region_of(square);
+        world.weather.insert(
+            region,
+            WeatherFront {
+                name: "a light drizzle".into(),
+                description: "A light drizzle beads on every surface.".into(),
+                until_tick: world.current_tick + 10,
+            },
+        );
+        assert!(world.places[&square]
+            .look(&world, "You're standing in")
+            .contains("Moonlight filters through the dark. A light drizzle"));
+
+        world.current_tick += 10;
+        assert!(world.weather_at(square).is_none());
+        let mut crypt = Place::new("Old Crypt".into(), String::new());
+        crypt.tags.insert("dungeon".into());
+        assert!(!crypt.outdoors());
+    }
+
+    #[test]
+    fn respawns_where_bound() {
+        let mut world = World::default();
+        world.ensure_starting_village();
+        let player = PlayerId::new_random();
+        let square = world.player_character(player).location;
+        let inn = world
+            .places
+            .values()
+            .find(|p| p.name == "The Drowsy Goose")
+            .unwrap()
+            .location;
+        assert!(world.is_bind_point(inn));
+        assert_eq!(world.respawn_location(player), Some(square));
+
+        world.player_character(player).bound_to = Some(inn);
+        assert_eq!(world.respawn_location(player), Some(inn));
+
+        world.places.remove(&inn);
+        assert_eq!(world.respawn_location(player), Some(square));
+    }
+
+    #[test]
+    fn credits_first_explorer() {
+        let mut world = World::default();
+        world.ensure_starting_village();
+
+        let mut crypt = Place::new("Old Crypt".into(), String::new());
+        let mut rooms: Vec<_> = ["Entry Hall", "Cells"]
+            .into_iter()
+            .map(|n| Place::new(n.into(), String::new()))
+            .collect();
+        for room in &mut rooms {
+            room.parent = Some(crypt.location);
+        }
+        crypt
+            .add_connection(Direction::Down, rooms[0].location)
+            .unwrap();
+        let crypt_location = crypt.location;
+        let room_locations: Vec<_> = rooms.iter().map(|r| r.location).collect();
+        world
+            .places
+            .extend(rooms.into_iter().map(|r| (r.location, r)));
+        world.add_overworld_locale(crypt);
+
+        assert_eq!(
+            world.area_depths(crypt_location),
+            vec![(room_locations[0], 1)]
+        );
+
+        let idol = Item {
+            name: "Bone Idol".into(),
+            description: "A small carved idol.".into(),
+            weight: 1,
+        };
+        let idol_copy = idol.clone();
+        world.scatter_loot(crypt_location, vec![idol], &mut rand::thread_rng());
+        assert!(world.items.contains_key("Bone Idol"));
+        assert_eq!(world.places[&room_locations[0]].ground.equipment().len(), 1);
+        assert!(world.places[&room_locations[0]]
+            .look(&world, "You're in")
+            .contains("On the ground: Fine Bone Idol"));
+        let mut rng = rand::rngs::StdRng::seed_from_u64(5);
+        world.scatter_loot(crypt_location, vec![idol_copy; 20], &mut rng);
+        assert!(world.places[&room_locations[0]]
+            .ground
+            .equipment()
+            .iter()
+            .any(|e| !e.enchantments.is_empty()));
+
+        let player = PlayerId::new_random();
+        world.player_character(player).name = "Ada".into();
+
+        let visit = |world: &mut World, location| {
+            world.player_character(player).location = location;
+            world.visit(player)
+        };
+        assert_eq!(visit(&mut world, crypt_location), None);
+        assert_eq!(visit(&mut world, room_locations[0]), None);
+        assert_eq!(visit(&mut world, room_locations[1]), Some(crypt_location));
+        assert_eq!(visit(&mut world, room_locations[1]), None);
+
+        let discovery = world.places[&crypt_location].discovery.as_ref().unwrap();
+        assert_eq!(discovery.discoverer, "Ada");
+        assert_eq!(world.chronicle.len(), 1);
+        assert!(world.places[&room_locations[0]]
+            .look(&world, "You're in")
+            .contains("First explored by Ada"));
+
+        let village: Vec<_> = world
+            .places
+            .values()
+            .filter(|p| p.is_handcrafted())
+            .map(|p| p.location)
+            .collect();
+        assert_eq!(world.unexplored_locales(), 1);
+        for location in village {
+            assert_eq!(visit(&mut world, location), None);
+        }
+        assert_eq!(world.unexplored_locales(), 0);
+
+        // New places grow out from the most recently explored one
+        let barrow = Place::new("Barrow".into(), String::new());
+        let barrow_location = barrow.location;
+        world.add_overworld_locale(barrow);
+        assert!(world.places[&crypt_location]
+            .connections()
+            .values()
+            .any(|l| *l == barrow_location));
+        assert_eq!(world.unexplored_locales(), 1);
+    }
+}
@@ -0,0 +1,153 @@
+//! Questions the engine asks players. While a player has a prompt open, the
+//! next line they type answers it instead of running as a command. Prompts
+//! stack, so answering one can ask a follow up, and a menu is just a prompt
+//! that maps numbered or named choices to what each one does.
+
+use crate::{editor, engine::Engine, state::PlayerId};
+
+/// What a prompt made of the player's answer
+pub enum Answer {
+    /// The prompt is finished with
+    Done,
+    /// The answer didn't work, tell the player why and ask again
+    Retry(String),
+}
+
+pub type AnswerFn = Box<dyn FnMut(&mut Engine, PlayerId, &str) -> Answer + Send>;
+pub type ChoiceFn = Box<dyn Fn(&mut Engine, PlayerId) + Send>;
+
+pub struct Prompt {
+    pub question: String,
+    /// Whether 'cancel' closes the prompt without answering it
+    pub cancellable: bool,
+    pub answer: AnswerFn,
+}
+
+impl Prompt {
+    pub fn new(question: String, answer: AnswerFn) -> Self {
+        Self {
+            question,
+            cancellable: false,
+            answer,
+        }
+    }
+
+    /// A list of choices picked by number or name, which can always be cancelled
+    pub fn menu(title: &str, choices: Vec<(String, ChoiceFn)>) -> Self {
+        let mut question = title.to_string();
+        for (i, (name, _)) in choices.iter().enumerate() {
+            question.push_str(&format!("\n  {}. {name}", i + 1));
+        }
+        question.push_str("\nPick one, or 'cancel'");
+
+        let names: Vec<_> = choices.iter().map(|(n, _)| n.clone()).collect();
+        let mut prompt = Self::new(
+            question,
+            Box::new(move |engine, player, line| match choose(&names, line) {
+                Some(i) => {
+                    (choices[i].1)(engine, player);
+                    Answer::Done
+                }
+                None => Answer::Retry(format!("'{line}' isn't one of the choices")),
+            }),
+        );
+        prompt.cancellable = true;
+        prompt
+    }
+}
+
+/// Which choice a line picks, by its number or the start of its name
+pub fn choose(names: &[String], line: &str) -> Option<usize> {
+    let line = line.trim();
+    if line.is_empty() {
+        return None;
+    }
+    if let Ok(n) = line.parse::<usize>() {
+        return (1..=names.len()).contains(&n).then(|| n - 1);
+    }
+    names
+        .iter()
+        .position(|n| n.to_lowercase().starts_with(&line.to_lowercase()))
+}
+
+/// Asks the player something, their next line goes to this prompt
+pub fn ask(engine: &mut Engine, player: PlayerId, prompt: Prompt) {
+    engine
+        .connection_broker
+        .send_player_message(player, prompt.question.clone());
+    engine.prompts.entry(player).or_default().push(prompt);
+}
+
+/// Opens the editor on the player's connection to write something long,
+/// starting from `document`. What they save answers `save`, and if that asks
+/// again the editor reopens on what they wrote so they can fix it.
+pub fn edit(
+    engine: &mut Engine,
+    player: PlayerId,
+    title: &str,
+    document: String,
+    mut save: AnswerFn,
+) {
+    let mut prompt = Prompt::new(
+        format!("{title}\n{}", editor::HELP),
+        Box::new(move |engine, player, document| {
+            let answer = save(engine, player, document);
+            if let Answer::Retry(_) = answer {
+                engine
+                    .connection_broker
+                    .open_editor(player, document.to_string());
+            }
+            answer
+        }),
+    );
+    prompt.cancellable = true;
+    engine.connection_broker.open_editor(player, document);
+    ask(engine, player, prompt);
+}
+
+pub fn is_prompting(engine: &Engine, player: PlayerId) -> bool {
+    engine.prompts.get(&player).is_some_and(|p| !p.is_empty())
+}
+
+/// Hands a line to the player's latest prompt
+pub fn answer(engine: &mut Engine, player: PlayerId, line: &str) {
+    let Some(mut prompt) = engine.prompts.get_mut(&player).and_then(Vec::pop) else {
+        return;
+    };
+    let depth = engine.prompts[&player].len();
+
+    if prompt.cancellable && line.trim().eq_ignore_ascii_case("cancel") {
+        engine
+            .connection_broker
+            .send_player_message(player, "Never mind then".to_string());
+        return;
+    }
+
+    match (prompt.answer)(engine, player, line.trim()) {
+        Answer::Done => return,
+        Answer::Retry(why) => {
+            let msg = format!("{why}\n{}", prompt.question);
+            engine.connection_broker.send_player_message(player, msg);
+        }
+    }
+    // Follow ups the prompt asked stay on top of it
+    let stack = engine.prompts.entry(player).or_default();
+    stack.insert(depth.min(stack.len()), prompt);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn chooses_by_number_or_name() {
+        let names = vec!["Buy".to_string(), "Sell".to_string(), "Leave".to_string()];
+        assert_eq!(choose(&names, "2"), Some(1));
+        assert_eq!(choose(&names, "le"), Some(2));
+        assert_eq!(choose(&names, " BUY "), Some(0));
+        assert_eq!(choose(&names, "4"), None);
+        assert_eq!(choose(&names, "0"), None);
+        assert_eq!(choose(&names, ""), None);
+        assert_eq!(choose(&names, "steal"), None);
+    }
+}
@@ -0,0 +1,107 @@
+//! Bugs and typos players report from inside the game. Each report's written
+//! to its own YAML file under `somnustate/reports/`, named for when it was
+//! filed, with where the player was and how the place read at the time so
+//! worldbuilders can find and fix generated text that's gone wrong.
+
+use std::{
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{mud::world::Location, state::PlayerId};
+
+pub const REPORT_DIR: &str = "reports";
+/// The longest message a report can have
+pub const MAX_REPORT_LENGTH: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReportKind {
+    Bug,
+    Typo,
+}
+
+impl ReportKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            ReportKind::Bug => "bug",
+            ReportKind::Typo => "typo",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Report {
+    pub kind: ReportKind,
+    /// When it was filed, in milliseconds since the Unix epoch
+    pub filed_at: u128,
+    pub realm: String,
+    pub tick: u64,
+    pub player: PlayerId,
+    pub character: String,
+    pub location: Location,
+    pub place: String,
+    /// The place's own description, without anything layered over it
+    pub description: String,
+    pub message: String,
+}
+
+/// Milliseconds since the Unix epoch, for when a report's filed
+pub fn now() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+}
+
+impl Report {
+    /// Writes the report to its own file in the directory, returning its path
+    pub fn file(&self, dir: &Path) -> anyhow::Result<PathBuf> {
+        std::fs::create_dir_all(dir)?;
+        let stem = format!("{}-{}", self.filed_at, self.kind.name());
+        let mut path = dir.join(format!("{stem}.yaml"));
+        // Two reports in the same millisecond get numbered
+        let mut n = 1;
+        while path.exists() {
+            n += 1;
+            path = dir.join(format!("{stem}-{n}.yaml"));
+        }
+        std::fs::write(&path, serde_yaml::to_string(self)?)?;
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn files_each_report_separately() {
+        let dir = std::env::temp_dir().join(format!("somnuscape-reports-{}", std::process::id()));
+        let report = Report {
+            kind: ReportKind::Typo,
+            filed_at: now(),
+            realm: "Dreamland".into(),
+            tick: 12,
+            player: PlayerId::new_random(),
+            character: "Ada".into(),
+            location: Location::default(),
+            place: "Village Square".into(),
+            description: "You stand in teh square".into(),
+            message: "'teh' square".into(),
+        };
+
+        let first = report.file(&dir).unwrap();
+        let second = report.file(&dir).unwrap();
+        assert_ne!(first, second);
+        assert!(first.to_string_lossy().ends_with("-typo.yaml"));
+
+        let read: Report =
+            serde_yaml::from_str(&std::fs::read_to_string(&second).unwrap()).unwrap();
+        assert_eq!(read, report);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+
+/// Who players are allowed to attack
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PvpMode {
+    Off,
+    /// Both players need to have opted in with the `pvp` command
+    Consent,
+    Open,
+}
+
+/// The rule toggles that give a server its personality. Grouped into named
+/// profiles so operators can pick one and only override what they need.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct GameRules {
+    pub pvp: PvpMode,
+    /// Multiplier applied to all experience gained
+    pub xp_rate: f64,
+    /// Fraction of carried wealth lost on death, left behind with the body
+    pub death_penalty: f64,
+    /// Players can only quit in safe rooms and must `camp` elsewhere
+    pub safe_logout_only: bool,
+    /// Knocked out players wander as ghosts until they get back to their body
+    /// or a shrine, rather than leaving a corpse and coming to somewhere safe
+    pub ghosts: bool,
+}
+
+impl Default for GameRules {
+    fn default() -> Self {
+        Self::classic()
+    }
+}
+
+impl GameRules {
+    pub fn casual() -> Self {
+        Self {
+            pvp: PvpMode::Off,
+            xp_rate: 1.5,
+            death_penalty: 0.0,
+            safe_logout_only: false,
+            ghosts: true,
+        }
+    }
+
+    pub fn classic() -> Self {
+        Self {
+            pvp: PvpMode::Consent,
+            xp_rate: 1.0,
+            death_penalty: 0.1,
+            safe_logout_only: false,
+            ghosts: true,
+        }
+    }
+
+    pub fn hardcore() -> Self {
+        Self {
+            pvp: PvpMode::Open,
+            xp_rate: 0.75,
+            death_penalty: 1.0,
+            safe_logout_only: true,
+            ghosts: false,
+        }
+    }
+
+    /// The profiles a config starts with, before an operator changes them
+    pub fn built_in_profiles() -> HashMap<String, Self> {
+        HashMap::from([
+            ("casual".to_string(), Self::casual()),
+            ("classic".to_string(), Self::classic()),
+            ("hardcore".to_string(), Self::hardcore()),
+        ])
+    }
+
+    /// Finds a profile in the config by name
+    pub fn profile(name: &str) -> Option<Self> {
+        config::get().rule_profiles.get(name).cloned()
+    }
+
+    /// A readable summary of the rules for players
+    pub fn describe(&self, profile_name: &str) -> String {
+        let yes_no = |b: bool| if b { "on" } else { "off" };
+        let pvp = match self.pvp {
+            PvpMode::Off => "off",
+            PvpMode::Consent => "by consent",
+            PvpMode::Open => "open",
+        };
+
+        format!(
+            "Rules: {}\n\nPvP: {pvp}\nExperience rate: {}x\nDeath penalty: {:.0}% of wealth\nSafe room logout only: {}\nGhosts: {}",
+            profile_name,
+            self.xp_rate,
+            self.death_penalty * 100.0,
+            yes_no(self.safe_logout_only),
+            yes_no(self.ghosts),
+        )
+    }
+}
@@ -0,0 +1,316 @@
+//! Every version of the Lua scripts the server's run, kept under
+//! `somnustate/scripts/` so a script that starts misbehaving can be rolled back
+//! without anyone logging in to the machine. Builders still edit the files in
+//! `scripts/`, each time the server loads one that's changed it's stored as a
+//! new version and goes live.
+//!
+//! Scripts are stored by their attachment, what they're attached to and their
+//! file name like `commands/emote.lua`, with each version in its own file next
+//! to a record of which version is live. Rolling back only changes which version
+//! is live, so nothing's lost and a rollback can itself be undone.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock},
+};
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+
+use crate::{config, state};
+
+const STORE_DIR: &str = "scripts";
+const LIVE_FILE: &str = "live.yaml";
+/// The longest script the diff compares line by line, past it versions are only
+/// said to differ
+const MAX_DIFF_LINES: usize = 2000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct Versions {
+    /// The version scripts run
+    live: u32,
+    /// The newest version stored, versions count up from 1
+    latest: u32,
+}
+
+pub struct ScriptStore {
+    dir: PathBuf,
+    attachments: BTreeMap<String, Versions>,
+    /// How many times in a row each attachment's live version has failed
+    failures: HashMap<String, u32>,
+}
+
+/// The store every realm shares, opened the first time it's needed
+pub fn store() -> &'static Mutex<ScriptStore> {
+    static STORE: OnceLock<Mutex<ScriptStore>> = OnceLock::new();
+
+    STORE.get_or_init(|| Mutex::new(ScriptStore::open(&state::make_save_path(STORE_DIR))))
+}
+
+/// What a script file's attached to and its name, like `commands/emote.lua`
+pub fn attachment(path: &Path) -> String {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    match path.parent().and_then(|p| p.file_name()) {
+        Some(dir) => format!("{}/{name}", dir.to_string_lossy()),
+        None => name.to_string(),
+    }
+}
+
+impl ScriptStore {
+    /// Opens the store in the directory, starting an empty one if the record of
+    /// what's live can't be read
+    pub fn open(dir: &Path) -> Self {
+        let attachments = std::fs::read_to_string(dir.join(LIVE_FILE))
+            .ok()
+            .and_then(|s| match serde_yaml::from_str(&s) {
+                Ok(attachments) => Some(attachments),
+                Err(e) => {
+                    tracing::error!("Couldn't read which script versions are live: {e}");
+                    None
+                }
+            })
+            .unwrap_or_default();
+
+        Self {
+            dir: dir.to_path_buf(),
+            attachments,
+            failures: HashMap::new(),
+        }
+    }
+
+    /// Stores the script file as a new version if it's changed since the newest
+    /// one, making it live. Returns the live version and its source.
+    pub fn sync(&mut self, path: &Path) -> anyhow::Result<(u32, String)> {
+        let source = std::fs::read_to_string(path)?;
+        let attachment = attachment(path);
+        let latest = self.attachments.get(&attachment).map(|v| v.latest);
+        let changed = match latest {
+            Some(latest) => self.source(&attachment, latest).ok().as_ref() != Some(&source),
+            None => true,
+        };
+
+        if changed {
+            let version = latest.unwrap_or_default() + 1;
+            let file = self.version_path(&attachment, version);
+            std::fs::create_dir_all(file.parent().unwrap())?;
+            std::fs::write(&file, &source)?;
+            self.attachments.insert(
+                attachment.clone(),
+                Versions {
+                    live: version,
+                    latest: version,
+                },
+            );
+            self.failures.remove(&attachment);
+            self.save()?;
+            tracing::info!("Stored {attachment} version {version}");
+        }
+
+        let live = self.attachments[&attachment].live;
+        Ok((live, self.source(&attachment, live)?))
+    }
+
+    /// The version of the script scripts run
+    pub fn live(&self, attachment: &str) -> Option<u32> {
+        self.attachments.get(attachment).map(|v| v.live)
+    }
+
+    pub fn source(&self, attachment: &str, version: u32) -> anyhow::Result<String> {
+        std::fs::read_to_string(self.version_path(attachment, version))
+            .with_context(|| format!("there's no version {version} of {attachment}"))
+    }
+
+    /// Makes an earlier or later version live, the one before the live one if
+    /// none's given, returning the version that's now live
+    pub fn rollback(&mut self, attachment: &str, to: Option<u32>) -> anyhow::Result<u32> {
+        let Some(versions) = self.attachments.get_mut(attachment) else {
+            bail!("there's no script stored for {attachment}");
+        };
+        let to = match to {
+            Some(to) => to,
+            None if versions.live > 1 => versions.live - 1,
+            None => bail!("{attachment} has no earlier version to roll back to"),
+        };
+        if !(1..=versions.latest).contains(&to) {
+            bail!("{attachment} only has versions 1 to {}", versions.latest);
+        }
+
+        versions.live = to;
+        self.failures.remove(attachment);
+        self.save()?;
+        Ok(to)
+    }
+
+    /// Counts whether a run of the live version worked. Once it's failed enough
+    /// times in a row it's rolled back to the version before, returning which
+    /// version's live now.
+    pub fn record(&mut self, attachment: &str, ok: bool) -> Option<u32> {
+        if ok {
+            self.failures.remove(attachment);
+            return None;
+        }
+        let failures = self.failures.entry(attachment.to_string()).or_default();
+        *failures += 1;
+        let failures = *failures;
+        if failures < config::get().script_failures_before_rollback {
+            return None;
+        }
+
+        match self.rollback(attachment, None) {
+            Ok(version) => {
+                tracing::warn!(
+                    "{attachment} failed {failures} times in a row, rolled back to version {version}"
+                );
+                Some(version)
+            }
+            // Nothing earlier to fall back on, keep counting
+            Err(_) => None,
+        }
+    }
+
+    /// Every stored attachment with its live and newest versions
+    pub fn describe(&self) -> String {
+        if self.attachments.is_empty() {
+            return "No scripts have been stored yet".to_string();
+        }
+        let mut res = format!("{:30}{:>8}{:>8}\n", "Script", "Live", "Latest");
+        for (attachment, versions) in &self.attachments {
+            res.push_str(&format!(
+                "{attachment:30}{:>8}{:>8}\n",
+                versions.live, versions.latest
+            ));
+        }
+        res
+    }
+
+    /// The lines that changed between two versions of a script
+    pub fn diff(&self, attachment: &str, from: u32, to: u32) -> anyhow::Result<String> {
+        let (old, new) = (self.source(attachment, from)?, self.source(attachment, to)?);
+        Ok(format!(
+            "{attachment} version {from} to {to}\n\n{}",
+            diff_lines(&old, &new)
+        ))
+    }
+
+    /// Looks an attachment up by its file name alone if that's all that's given
+    pub fn find(&self, name: &str) -> Option<String> {
+        let name = if name.ends_with(".lua") {
+            name.to_string()
+        } else {
+            format!("{name}.lua")
+        };
+        self.attachments
+            .keys()
+            .find(|a| a.eq_ignore_ascii_case(&name) || a.rsplit('/').next() == Some(&name))
+            .cloned()
+    }
+
+    fn version_path(&self, attachment: &str, version: u32) -> PathBuf {
+        let stem = attachment.trim_end_matches(".lua");
+        self.dir.join(stem).join(format!("{version}.lua"))
+    }
+
+    fn save(&self) -> anyhow::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        std::fs::write(
+            self.dir.join(LIVE_FILE),
+            serde_yaml::to_string(&self.attachments)?,
+        )?;
+        Ok(())
+    }
+}
+
+/// A line by line diff, removed lines marked with `-` and added ones with `+`
+fn diff_lines(old: &str, new: &str) -> String {
+    let (old, new): (Vec<_>, Vec<_>) = (old.lines().collect(), new.lines().collect());
+    if old == new {
+        return "They're the same\n".to_string();
+    }
+    if old.len() > MAX_DIFF_LINES || new.len() > MAX_DIFF_LINES {
+        return "They differ, but they're too long to compare line by line\n".to_string();
+    }
+
+    // The longest run of lines in common from each pair of positions onwards
+    let mut common = vec![vec![0u32; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut res = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            res.push_str(&format!("- {}\n", old[i]));
+            i += 1;
+        } else {
+            res.push_str(&format!("+ {}\n", new[j]));
+            j += 1;
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn stores_versions_and_rolls_back() {
+        let dir = std::env::temp_dir().join(format!("somnuscape-store-{}", std::process::id()));
+        let scripts = dir.join("events");
+        std::fs::create_dir_all(&scripts).unwrap();
+        let path = scripts.join("bell.lua");
+        let mut store = ScriptStore::open(&dir.join("store"));
+
+        std::fs::write(&path, "print('bong')\n").unwrap();
+        assert_eq!(store.sync(&path).unwrap().0, 1);
+        assert_eq!(store.sync(&path).unwrap().0, 1);
+        std::fs::write(&path, "print('bong')\nerror('cracked')\n").unwrap();
+        assert_eq!(store.sync(&path).unwrap().0, 2);
+
+        let attachment = store.find("bell").unwrap();
+        assert_eq!(attachment, "events/bell.lua");
+        assert_eq!(
+            store.diff(&attachment, 1, 2).unwrap(),
+            "events/bell.lua version 1 to 2\n\n+ error('cracked')\n"
+        );
+
+        let limit = config::get().script_failures_before_rollback;
+        for _ in 1..limit {
+            assert_eq!(store.record(&attachment, false), None);
+        }
+        assert_eq!(store.record(&attachment, false), Some(1));
+        // Nothing before the first version to fall back on
+        for _ in 0..limit {
+            assert_eq!(store.record(&attachment, false), None);
+        }
+
+        // What's live is remembered, and the file hasn't changed since
+        let mut store = ScriptStore::open(&dir.join("store"));
+        assert_eq!(
+            store.sync(&path).unwrap(),
+            (1, "print('bong')\n".to_string())
+        );
+        assert!(store.rollback(&attachment, Some(3)).is_err());
+        assert_eq!(store.rollback(&attachment, Some(2)).unwrap(), 2);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn diffs_lines() {
+        assert_eq!(diff_lines("a\nb\nc\n", "a\nx\nc\nd\n"), "- b\n+ x\n+ d\n");
+        assert_eq!(diff_lines("a\n", "a\n"), "They're the same\n");
+    }
+}
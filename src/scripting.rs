@@ -0,0 +1,1584 @@
+//! Lua scripts loaded at startup, so server operators can add gameplay without
+//! recompiling. Scripts in `scripts/commands/` add commands by calling
+//! `command { name = ..., aliases = { ... }, help = ..., run = function(ctx) ... end }`,
+//! and scripts in `scripts/events/` react to things happening in the world by
+//! calling `on("player_entered_room", function(event, world) ... end)`.
+//! Event handlers can call `wait(seconds)` to pause and pick up where they left
+//! off once the time's passed, for cutscenes and other timed sequences. The
+//! engine carries on meanwhile, and a paused handler sees the world as it is
+//! when it wakes.
+//!
+//! Scripts get their own Lua state with only the table, string, maths, utf8 and
+//! coroutine libraries, so no files or OS access. They see a snapshot of the world and
+//! can only change it through the functions they're handed. Creatures and
+//! equipment are handed to scripts by their [`EntityId`], as a hex string.
+//!
+//! Places are looked up by their `id` or by name, though names needn't be
+//! unique, and read as a table with their `id`, `name`, `description`, `tags`,
+//! `exits` by direction, the `players` and `creatures` in them and what's on
+//! the `ground`, with the `equipment` there listed one piece at a time. Scripts can send messages, open and
+//! close ways between places and spawn known items on the ground, but never
+//! gold or anything the world hasn't heard of.
+//!
+//! How much of that a script's handed depends on its [`Trust`]. Core scripts
+//! get everything, builders' scripts can look at the world but not change it,
+//! and scripts players submit can only send messages. A function a script
+//! isn't trusted with is never bound, so it's simply missing.
+//!
+//! Scripts are loaded through the [`script_store`], which keeps every version
+//! and can roll one back, and a script that keeps failing is rolled back on its
+//! own. [`dry_run`] runs a script against the world without applying what it
+//! does, so builders can try one out on a live server with `script dryrun` or
+//! offline with `somnuscape script-test`.
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Context;
+use mlua::{
+    Debug, Function, HookTriggers, IntoLuaMulti, Lua, LuaOptions, RegistryKey, StdLib, Table,
+    Thread, ThreadStatus, Value,
+};
+use serde::Deserialize;
+
+use crate::{
+    commands::Command,
+    config,
+    engine::{self, Engine},
+    markup::{styled, Style},
+    mud::{
+        crafting,
+        entity::EntityId,
+        items::{ItemInstance, Quality},
+        world::{Direction, Location, World},
+    },
+    script_store,
+    state::PlayerId,
+};
+
+pub const SCRIPT_DIR: &str = "scripts/commands";
+pub const HOOK_DIR: &str = "scripts/events";
+/// How many Lua instructions a script gets each time it runs before it's stopped
+const INSTRUCTION_LIMIT: u32 = 1_000_000;
+/// How much memory one script's Lua state can use
+const MEMORY_LIMIT: usize = 8 * 1024 * 1024;
+/// How many events can wait for scripts before new ones are dropped
+const MAX_QUEUED_EVENTS: usize = 1000;
+/// The most of an item a script can spawn at once
+const MAX_SPAWN: u32 = 20;
+/// How many event handlers can be waiting at once, the newest are stopped past it
+const MAX_WAITING: usize = 100;
+/// How many times a dry run wakes a waiting handler before giving up on it
+const MAX_DRY_WAKES: usize = 20;
+const SCRIPT_TEST_USAGE: &str = "Usage: script-test <script file> [command arguments]";
+/// Lets event handlers pause, see [`Waiting`]
+const WAIT: &str = "function wait(seconds) coroutine.yield(seconds or 0) end";
+/// Swaps `load` for one that only takes source text, never precompiled chunks
+const TEXT_ONLY_LOAD: &str =
+    "local text = load; function load(chunk, name, _, env) return text(chunk, name, 't', env) end";
+
+/// How far a script's trusted, which decides the functions it's handed. Scripts
+/// in `scripts/` are core unless the config says otherwise.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Trust {
+    /// Written by the server's operators, can change the world
+    #[default]
+    Core,
+    /// Can read the world and send messages, but not change anything
+    Builder,
+    /// Written by players, like decorations for their homes, can only send
+    /// messages
+    PlayerSubmitted,
+}
+
+impl Trust {
+    fn allows(self, power: Power) -> bool {
+        match self {
+            Trust::Core => true,
+            Trust::Builder => power != Power::Mutate,
+            Trust::PlayerSubmitted => power == Power::Cosmetic,
+        }
+    }
+}
+
+/// What a function handed to scripts can do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Power {
+    /// Only sends messages
+    Cosmetic,
+    /// Reads the world
+    Read,
+    /// Changes the world
+    Mutate,
+}
+
+/// Keeps a script to the one place it's in, so it can only send messages there
+/// and to the players in it. Kept in the script's Lua state.
+#[derive(Debug, Clone, Copy)]
+struct Confined(Location);
+
+/// How far the script attached as `attachment` is trusted
+pub fn trust_of(attachment: &str) -> Trust {
+    config::get()
+        .script_trust
+        .get(attachment)
+        .copied()
+        .unwrap_or_default()
+}
+
+/// Something a script asked to happen, applied once it's finished
+#[derive(Debug, Clone, PartialEq)]
+enum Effect {
+    /// Tells a player something
+    Send(PlayerId, String),
+    /// Tells everyone in a place something, except perhaps the player it's about
+    Say(Location, String, Option<PlayerId>),
+    Heal(PlayerId, u32),
+    /// Hurts a player, though never enough to knock them out
+    Hurt(PlayerId, u32),
+    /// Opens a way between two places, and back again
+    Open(Location, Direction, Location),
+    /// Closes the way out of a place in a direction, and the way back
+    Close(Location, Direction),
+    RemoveCreature(EntityId),
+    /// Puts some of an item on the ground
+    Spawn(Location, String, u32),
+}
+
+/// Something that happened in the world that event scripts can react to
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// Sent every tick the world moves
+    Tick,
+    /// A player moved into a place
+    PlayerEnteredRoom(PlayerId, Location),
+    /// A player picked something up, like "Torch x2"
+    ItemPickedUp(PlayerId, Location, String),
+    /// A player was knocked out in a fight
+    PlayerDowned(PlayerId, Location),
+}
+
+impl Event {
+    fn name(&self) -> &'static str {
+        match self {
+            Event::Tick => "tick",
+            Event::PlayerEnteredRoom(..) => "player_entered_room",
+            Event::ItemPickedUp(..) => "item_picked_up",
+            Event::PlayerDowned(..) => "player_downed",
+        }
+    }
+
+    fn player(&self) -> Option<(PlayerId, Location)> {
+        match self {
+            Event::Tick => None,
+            Event::PlayerEnteredRoom(player, location)
+            | Event::ItemPickedUp(player, location, _)
+            | Event::PlayerDowned(player, location) => Some((*player, *location)),
+        }
+    }
+}
+
+/// An event handler that called `wait`, kept as a paused coroutine in its
+/// script's registry until it's woken
+struct Waiting {
+    /// Which of the scripts it's from
+    script: usize,
+    thread: RegistryKey,
+    wakes_at: u64,
+}
+
+/// Handlers that have just waited, and how many seconds each is waiting for
+type Waits = Vec<(RegistryKey, f64)>;
+
+/// A script loaded from the store, see [`script_store`]
+struct Live {
+    attachment: String,
+    version: u32,
+    trust: Trust,
+    lua: Lua,
+}
+
+impl Live {
+    /// Loads the live version if it's changed since this one was loaded,
+    /// returning whether it had to
+    fn refresh(&mut self) -> anyhow::Result<bool> {
+        let store = script_store::store().lock().unwrap();
+        let Some(live) = store.live(&self.attachment).filter(|v| *v != self.version) else {
+            return Ok(false);
+        };
+        let source = store.source(&self.attachment, live)?;
+        drop(store);
+
+        self.lua = load_source(&self.attachment, &source, self.trust)?;
+        self.version = live;
+        tracing::info!("Loaded {} version {live}", self.attachment);
+        Ok(true)
+    }
+
+    /// Counts whether a run worked, returning the version it's been rolled back
+    /// to if it keeps failing
+    fn record(&self, ok: bool) -> Option<u32> {
+        script_store::store()
+            .lock()
+            .unwrap()
+            .record(&self.attachment, ok)
+    }
+}
+
+/// An approved decoration script for a home, see [`crate::mud::home`]
+struct Decorated {
+    source: String,
+    /// None once it's failed, until a changed script is approved
+    lua: Option<Lua>,
+}
+
+/// Event scripts and the events waiting for them
+#[derive(Default)]
+pub struct Hooks {
+    scripts: Vec<Live>,
+    /// Decoration scripts by the home they're in
+    homes: HashMap<Location, Decorated>,
+    queue: Vec<Event>,
+    waiting: Vec<Waiting>,
+}
+
+impl Hooks {
+    /// Loads every event script in the directory, logging and skipping any that fail
+    pub fn load(dir: &Path) -> Self {
+        let scripts = script_paths(dir)
+            .into_iter()
+            .filter_map(|path| match load_live(&path) {
+                Ok(live) => {
+                    tracing::info!("Loaded event script {}", live.attachment);
+                    Some(live)
+                }
+                Err(e) => {
+                    tracing::error!("Couldn't load script {}: {e:#}", path.display());
+                    None
+                }
+            })
+            .collect();
+
+        Self {
+            scripts,
+            homes: HashMap::new(),
+            queue: Vec::new(),
+            waiting: Vec::new(),
+        }
+    }
+
+    /// Queues an event for scripts to handle at the end of the tick
+    pub fn emit(&mut self, event: Event) {
+        let listening = !self.scripts.is_empty() || !self.homes.is_empty();
+        if listening && self.queue.len() < MAX_QUEUED_EVENTS {
+            self.queue.push(event);
+        }
+    }
+}
+
+/// Wakes any handlers that have waited long enough, then hands the tick's
+/// events to every script listening for them
+pub fn run_hooks(engine: &mut Engine) {
+    sync_homes(engine);
+    if engine.hooks.scripts.is_empty() && engine.hooks.homes.is_empty() {
+        return;
+    }
+    let config = config::get();
+    let current_tick = engine.world.current_tick;
+    let wakes_at = |seconds: f64| current_tick + config.seconds_to_ticks(seconds).max(1);
+    let mut events = std::mem::take(&mut engine.hooks.queue);
+    events.push(Event::Tick);
+
+    // Scripts rolled back or forward since last tick are reloaded, and whatever
+    // their old version had waiting is forgotten
+    for (script, live) in engine.hooks.scripts.iter_mut().enumerate() {
+        match live.refresh() {
+            Ok(true) => engine.hooks.waiting.retain(|w| w.script != script),
+            Ok(false) => {}
+            Err(e) => tracing::error!("Couldn't reload {}: {e:#}", live.attachment),
+        }
+    }
+    let (due, mut waiting): (Vec<_>, Vec<_>) = std::mem::take(&mut engine.hooks.waiting)
+        .into_iter()
+        .partition(|w| w.wakes_at <= current_tick);
+
+    let mut effects = Vec::new();
+    let mut rolled_back = Vec::new();
+    for woken in due {
+        let live = &engine.hooks.scripts[woken.script];
+        let woke = wake(&live.lua, &engine.world, &woken.thread);
+        rolled_back.extend(
+            live.record(woke.is_ok())
+                .map(|v| (live.attachment.clone(), v)),
+        );
+        match woke {
+            Ok((mut more, wait)) => {
+                effects.append(&mut more);
+                if let Some(seconds) = wait {
+                    waiting.push(Waiting {
+                        wakes_at: wakes_at(seconds),
+                        ..woken
+                    });
+                }
+            }
+            Err(e) => tracing::error!("Event script {} failed: {e}", live.attachment),
+        }
+    }
+    for (script, live) in engine.hooks.scripts.iter().enumerate() {
+        for event in events.iter().filter(|e| handles(&live.lua, e)) {
+            let handled = handle_event(&live.lua, &engine.world, event);
+            rolled_back.extend(
+                live.record(handled.is_ok())
+                    .map(|v| (live.attachment.clone(), v)),
+            );
+            match handled {
+                Ok((mut more, waits)) => {
+                    effects.append(&mut more);
+                    waiting.extend(waits.into_iter().map(|(thread, seconds)| Waiting {
+                        script,
+                        thread,
+                        wakes_at: wakes_at(seconds),
+                    }));
+                }
+                Err(e) => tracing::error!("Event script {} failed: {e}", live.attachment),
+            }
+        }
+    }
+    for (attachment, version) in rolled_back {
+        warn_rolled_back(engine, &attachment, version);
+    }
+    for (location, home) in &mut engine.hooks.homes {
+        let Some(lua) = &home.lua else {
+            continue;
+        };
+        let here = |e: &&Event| e.player().is_none_or(|(_, l)| l == *location);
+        for event in events.iter().filter(here).filter(|e| handles(lua, e)) {
+            match handle_event(lua, &engine.world, event) {
+                // Decorations can't wait, handlers that try are just dropped
+                Ok((mut more, _)) => effects.append(&mut more),
+                Err(e) => {
+                    tracing::warn!(
+                        "A decoration script at {location} failed, it's off until it changes: {e}"
+                    );
+                    home.lua = None;
+                    break;
+                }
+            }
+        }
+    }
+
+    if waiting.len() > MAX_WAITING {
+        tracing::warn!(
+            "Stopping {} waiting event handlers, scripts can only have {MAX_WAITING} waiting",
+            waiting.len() - MAX_WAITING
+        );
+        waiting.truncate(MAX_WAITING);
+    }
+    engine.hooks.waiting = waiting;
+    apply(engine, effects);
+}
+
+/// Loads approved decoration scripts that are new or have changed since last
+/// tick, and forgets any that have been taken down
+fn sync_homes(engine: &mut Engine) {
+    let scripts: HashMap<Location, &String> = engine
+        .world
+        .places
+        .values()
+        .filter_map(|p| Some((p.location, p.home.as_ref()?.decoration.script.as_ref()?)))
+        .collect();
+    let homes = &mut engine.hooks.homes;
+    homes.retain(|location, _| scripts.contains_key(location));
+
+    for (location, source) in scripts {
+        if homes.get(&location).is_some_and(|d| &d.source == source) {
+            continue;
+        }
+        let lua = match load_decoration(location, source) {
+            Ok(lua) => Some(lua),
+            Err(e) => {
+                tracing::warn!("Couldn't load the decoration script at {location}: {e:#}");
+                None
+            }
+        };
+        let source = source.clone();
+        homes.insert(location, Decorated { source, lua });
+    }
+}
+
+/// Loads a home's decoration script, trusted only to send messages in the home
+fn load_decoration(home: Location, source: &str) -> anyhow::Result<Lua> {
+    let lua = load_source("decoration", source, Trust::PlayerSubmitted)?;
+    lua.set_app_data(Confined(home));
+    Ok(lua)
+}
+
+/// Checks a decoration script compiles, so a homeowner finds out about mistakes
+/// before an admin has to. It's never run until it's approved.
+pub fn check_decoration(source: &str) -> anyhow::Result<()> {
+    sandbox()?
+        .load(source)
+        .set_name("decoration")
+        .into_function()?;
+    Ok(())
+}
+
+/// Loads every script in the directory, logging and skipping any that fail
+pub fn load_commands(dir: &Path) -> Vec<Command> {
+    script_paths(dir)
+        .into_iter()
+        .filter_map(|path| match load_live(&path).and_then(command) {
+            Ok(command) => {
+                tracing::info!("Loaded script command {}", command.name);
+                Some(command)
+            }
+            Err(e) => {
+                tracing::error!("Couldn't load script {}: {e:#}", path.display());
+                None
+            }
+        })
+        .collect()
+}
+
+fn script_paths(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<_> = entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|e| e == "lua"))
+        .collect();
+    paths.sort();
+    paths
+}
+
+/// A Lua state without access to anything outside it
+fn sandbox() -> mlua::Result<Lua> {
+    let lua = Lua::new_with(
+        StdLib::TABLE | StdLib::STRING | StdLib::MATH | StdLib::UTF8 | StdLib::COROUTINE,
+        LuaOptions::default(),
+    )?;
+    // The base library can still read files, and load precompiled chunks that
+    // can break out of the sandbox
+    let globals = lua.globals();
+    globals.set("dofile", Value::Nil)?;
+    globals.set("loadfile", Value::Nil)?;
+    lua.load(TEXT_ONLY_LOAD).exec()?;
+    drop(globals);
+    lua.set_memory_limit(MEMORY_LIMIT)?;
+    limit_instructions(&lua);
+    Ok(lua)
+}
+
+/// Stops the script once it's run too long, starting the count again
+fn limit_instructions(lua: &Lua) {
+    lua.set_hook(
+        HookTriggers::new().every_nth_instruction(INSTRUCTION_LIMIT),
+        ran_too_long,
+    );
+}
+
+fn ran_too_long(_: &Lua, _: Debug) -> mlua::Result<()> {
+    Err(mlua::Error::runtime("script ran for too long"))
+}
+
+/// Makes a loaded command script into a command. It picks up whichever version
+/// is live each time it's run, though a new name or aliases need a restart.
+fn command(live: Live) -> anyhow::Result<Command> {
+    let (name, aliases, help) = {
+        let def: Table = live
+            .lua
+            .named_registry_value("command")
+            .context("the script never calls command { ... }")?;
+        let _: Function = def.get("run")?;
+        let aliases: Vec<String> = def.get::<_, Option<_>>("aliases")?.unwrap_or_default();
+        (
+            def.get::<_, String>("name")?,
+            aliases,
+            def.get::<_, String>("help")?,
+        )
+    };
+
+    let aliases: Vec<&str> = aliases.iter().map(String::as_str).collect();
+    let live = Arc::new(Mutex::new(live));
+    Ok(Command::new(
+        &name,
+        &aliases,
+        &help,
+        Box::new(move |engine, player, args| {
+            let args = args.rest();
+            let mut live = live.lock().unwrap();
+            let effects = live.refresh().and_then(|_| {
+                run_command(&live.lua, &engine.world, player, args).map_err(Into::into)
+            });
+            let rolled_back = live.record(effects.is_ok());
+            match effects {
+                Ok(effects) => apply(engine, effects),
+                Err(e) => {
+                    tracing::error!("Script command {} failed: {e:#}", live.attachment);
+                    engine.connection_broker.send_player_message(
+                        player,
+                        "Something went wrong, that didn't work".to_string(),
+                    );
+                }
+            }
+            if let Some(version) = rolled_back {
+                warn_rolled_back(engine, &live.attachment, version);
+            }
+        }),
+    ))
+}
+
+fn run_command(
+    lua: &Lua,
+    world: &World,
+    player: PlayerId,
+    args: &str,
+) -> mlua::Result<Vec<Effect>> {
+    let character = &world.player_characters[&player];
+    let location = character.location;
+    let place = &world.places[&location];
+    let effects = RefCell::new(Vec::new());
+    limit_instructions(lua);
+
+    lua.scope(|scope| {
+        let ctx = lua.create_table()?;
+        ctx.set("args", args)?;
+        ctx.set("name", character.name.as_str())?;
+        ctx.set("health", character.health)?;
+        ctx.set("max_health", character.max_health())?;
+        ctx.set("place", place.name.as_str())?;
+        ctx.set("place_id", location.hex())?;
+        ctx.set("tick", world.current_tick)?;
+
+        bind(
+            lua,
+            &ctx,
+            "count",
+            Power::Read,
+            scope.create_function(|_, item: String| {
+                Ok(character.inventory.get(&item).map_or(0, |s| s.count))
+            })?,
+        )?;
+        bind(
+            lua,
+            &ctx,
+            "send",
+            Power::Cosmetic,
+            scope.create_function(|_, msg: String| {
+                effects.borrow_mut().push(Effect::Send(player, msg));
+                Ok(())
+            })?,
+        )?;
+        bind(
+            lua,
+            &ctx,
+            "say",
+            Power::Cosmetic,
+            scope.create_function(|_, msg: String| {
+                effects
+                    .borrow_mut()
+                    .push(Effect::Say(location, msg, Some(player)));
+                Ok(())
+            })?,
+        )?;
+        bind(
+            lua,
+            &ctx,
+            "heal",
+            Power::Mutate,
+            scope.create_function(|_, amount: u32| {
+                effects.borrow_mut().push(Effect::Heal(player, amount));
+                Ok(())
+            })?,
+        )?;
+        bind(
+            lua,
+            &ctx,
+            "here",
+            Power::Read,
+            scope.create_function(|lua, ()| place_table(lua, world, location))?,
+        )?;
+        bind(
+            lua,
+            &ctx,
+            "spawn",
+            Power::Mutate,
+            scope.create_function(|_, (item, count): (String, Option<u32>)| {
+                let effect = spawn(world, location, &item, count)?;
+                effects.borrow_mut().push(effect);
+                Ok(())
+            })?,
+        )?;
+        bind(
+            lua,
+            &ctx,
+            "open",
+            Power::Mutate,
+            scope.create_function(|_, (direction, to): (String, String)| {
+                let direction = find_direction(&direction)?;
+                let to = find_place(world, &to)?;
+                effects
+                    .borrow_mut()
+                    .push(Effect::Open(location, direction, to));
+                Ok(())
+            })?,
+        )?;
+
+        let def: Table = lua.named_registry_value("command")?;
+        def.get::<_, Function>("run")?.call::<_, ()>(ctx)
+    })?;
+
+    Ok(effects.into_inner())
+}
+
+/// Runs a script in a fresh sandbox with both `command` and `on` to call, so
+/// either kind of script can be loaded before knowing which it is
+fn load_script(path: &Path) -> anyhow::Result<Lua> {
+    let source = std::fs::read_to_string(path)?;
+    let trust = trust_of(&script_store::attachment(path));
+    load_source(&path.display().to_string(), &source, trust)
+}
+
+/// Loads the live version of a script file from the store, storing the file
+/// first if it's changed. A version that won't even load is rolled back
+/// straight away, as it'd only fail every time it ran.
+fn load_live(path: &Path) -> anyhow::Result<Live> {
+    let attachment = script_store::attachment(path);
+    let trust = trust_of(&attachment);
+    let mut store = script_store::store().lock().unwrap();
+    let (mut version, mut source) = store.sync(path)?;
+    loop {
+        match load_source(&attachment, &source, trust) {
+            Ok(lua) => {
+                return Ok(Live {
+                    attachment,
+                    version,
+                    trust,
+                    lua,
+                })
+            }
+            Err(e) => {
+                let Ok(earlier) = store.rollback(&attachment, None) else {
+                    return Err(e);
+                };
+                tracing::warn!(
+                    "{attachment} version {version} doesn't load, rolled back to version {earlier}: {e:#}"
+                );
+                version = earlier;
+                source = store.source(&attachment, version)?;
+            }
+        }
+    }
+}
+
+fn load_source(name: &str, source: &str, trust: Trust) -> anyhow::Result<Lua> {
+    let lua = sandbox()?;
+    lua.set_app_data(trust);
+    lua.set_named_registry_value("hooks", lua.create_table()?)?;
+    lua.set_named_registry_value("api", lua.create_table()?)?;
+    lua.load(WAIT).exec()?;
+
+    let on = lua.create_function(|lua, (event, handler): (String, Function)| {
+        let hooks: Table = lua.named_registry_value("hooks")?;
+        let handlers = match hooks.get::<_, Option<Table>>(event.as_str())? {
+            Some(handlers) => handlers,
+            None => {
+                let handlers = lua.create_table()?;
+                hooks.set(event, handlers.clone())?;
+                handlers
+            }
+        };
+        handlers.push(handler)
+    })?;
+    lua.globals().set("on", on)?;
+    let register =
+        lua.create_function(|lua, def: Table| lua.set_named_registry_value("command", def))?;
+    lua.globals().set("command", register)?;
+    lua.load(source).set_name(name).exec()?;
+
+    Ok(lua)
+}
+
+/// Whether the script has any handlers for the event
+fn handles(lua: &Lua, event: &Event) -> bool {
+    lua.named_registry_value::<Table>("hooks")
+        .and_then(|hooks| hooks.contains_key(event.name()))
+        .unwrap_or_default()
+}
+
+/// Tells admins a script kept failing and was rolled back
+fn warn_rolled_back(engine: &mut Engine, attachment: &str, version: u32) {
+    let text = format!("{attachment} kept failing, so it was rolled back to version {version}");
+    engine.digest.error(text.clone());
+    let admins = engine::admins_online(engine);
+    engine.connection_broker.broadcast_to(
+        admins,
+        styled(Style::Warn, format!("[{}] {text}", engine.realm.name)),
+    );
+}
+
+/// Runs every handler for the event, returning what they did and the ones
+/// that are waiting with how many seconds they're waiting for
+fn handle_event(lua: &Lua, world: &World, event: &Event) -> mlua::Result<(Vec<Effect>, Waits)> {
+    let handlers: Option<Table> = lua
+        .named_registry_value::<Table>("hooks")?
+        .get(event.name())?;
+    let Some(handlers) = handlers else {
+        return Ok((Vec::new(), Vec::new()));
+    };
+    // The player may have logged out since
+    if let Some((player, _)) = event.player() {
+        if !world.player_characters.contains_key(&player) {
+            return Ok((Vec::new(), Vec::new()));
+        }
+    }
+
+    let ev = lua.create_table()?;
+    ev.set("name", event.name())?;
+    ev.set("tick", world.current_tick)?;
+    if let Some((player, location)) = event.player() {
+        ev.set("player", world.player_characters[&player].name.as_str())?;
+        if let Some(place) = world.places.get(&location) {
+            ev.set("place", place.name.as_str())?;
+            ev.set("place_id", location.hex())?;
+        }
+    }
+    if let Event::ItemPickedUp(_, _, item) = event {
+        ev.set("item", item.as_str())?;
+    }
+
+    let mut waits = Vec::new();
+    let effects = with_api(lua, world, |api| {
+        for handler in handlers.sequence_values::<Function>() {
+            let thread = lua.create_thread(handler?)?;
+            if let Some(seconds) = resume(&thread, (ev.clone(), api.clone()))? {
+                waits.push((lua.create_registry_value(thread)?, seconds));
+            }
+        }
+        Ok(())
+    })?;
+    Ok((effects, waits))
+}
+
+/// Picks a waiting handler back up, returning what it did and how many more
+/// seconds it's waiting for if it waits again
+fn wake(
+    lua: &Lua,
+    world: &World,
+    thread: &RegistryKey,
+) -> mlua::Result<(Vec<Effect>, Option<f64>)> {
+    let thread: Thread = lua.registry_value(thread)?;
+    let mut wait = None;
+    let effects = with_api(lua, world, |_| {
+        wait = resume(&thread, ())?;
+        Ok(())
+    })?;
+    Ok((effects, wait))
+}
+
+/// Runs a handler's coroutine until it finishes or waits, returning how many
+/// seconds it's waiting for
+fn resume<'lua>(thread: &Thread<'lua>, args: impl IntoLuaMulti<'lua>) -> mlua::Result<Option<f64>> {
+    thread.set_hook(
+        HookTriggers::new().every_nth_instruction(INSTRUCTION_LIMIT),
+        ran_too_long,
+    );
+    let seconds: Option<f64> = thread.resume(args)?;
+    Ok((thread.status() == ThreadStatus::Resumable).then(|| seconds.unwrap_or_default().max(0.0)))
+}
+
+/// Fills in the functions event scripts are handed for the world, then runs
+/// `f` with them. The table's kept in the registry so handlers that wait still
+/// hold it when they wake, and get the functions for the world as it is then.
+fn with_api(
+    lua: &Lua,
+    world: &World,
+    f: impl FnOnce(Table) -> mlua::Result<()>,
+) -> mlua::Result<Vec<Effect>> {
+    let effects = RefCell::new(Vec::new());
+    limit_instructions(lua);
+    let confined = lua.app_data_ref::<Confined>().map(|c| c.0);
+
+    let find_player = |name: &str| {
+        world
+            .player_characters
+            .iter()
+            .filter(|(_, c)| confined.is_none_or(|l| c.location == l))
+            .find(|(_, c)| c.name.eq_ignore_ascii_case(name))
+            .map(|(p, _)| *p)
+            .ok_or_else(|| mlua::Error::runtime(format!("There's no player called {name}")))
+    };
+    let find_place = |name: &str| match confined {
+        Some(home) => world
+            .places
+            .get(&home)
+            .filter(|p| p.name.eq_ignore_ascii_case(name) || Location::parse(name) == Some(home))
+            .map(|p| p.location)
+            .ok_or_else(|| mlua::Error::runtime("This script can only reach its own place")),
+        None => find_place(world, name),
+    };
+
+    lua.scope(|scope| {
+        let api: Table = lua.named_registry_value("api")?;
+        bind(
+            lua,
+            &api,
+            "send",
+            Power::Cosmetic,
+            scope.create_function(|_, (player, msg): (String, String)| {
+                let player = find_player(&player)?;
+                effects.borrow_mut().push(Effect::Send(player, msg));
+                Ok(())
+            })?,
+        )?;
+        bind(
+            lua,
+            &api,
+            "say",
+            Power::Cosmetic,
+            scope.create_function(|_, (place, msg): (String, String)| {
+                let place = find_place(&place)?;
+                effects.borrow_mut().push(Effect::Say(place, msg, None));
+                Ok(())
+            })?,
+        )?;
+        bind(
+            lua,
+            &api,
+            "heal",
+            Power::Mutate,
+            scope.create_function(|_, (player, amount): (String, u32)| {
+                let player = find_player(&player)?;
+                effects.borrow_mut().push(Effect::Heal(player, amount));
+                Ok(())
+            })?,
+        )?;
+        bind(
+            lua,
+            &api,
+            "hurt",
+            Power::Mutate,
+            scope.create_function(|_, (player, amount): (String, u32)| {
+                let player = find_player(&player)?;
+                effects.borrow_mut().push(Effect::Hurt(player, amount));
+                Ok(())
+            })?,
+        )?;
+        bind(
+            lua,
+            &api,
+            "open",
+            Power::Mutate,
+            scope.create_function(|_, (from, direction, to): (String, String, String)| {
+                let (from, to) = (find_place(&from)?, find_place(&to)?);
+                let direction = find_direction(&direction)?;
+                effects.borrow_mut().push(Effect::Open(from, direction, to));
+                Ok(())
+            })?,
+        )?;
+        bind(
+            lua,
+            &api,
+            "close",
+            Power::Mutate,
+            scope.create_function(|_, (place, direction): (String, String)| {
+                let place = find_place(&place)?;
+                let direction = find_direction(&direction)?;
+                effects.borrow_mut().push(Effect::Close(place, direction));
+                Ok(())
+            })?,
+        )?;
+        bind(
+            lua,
+            &api,
+            "place",
+            Power::Read,
+            scope.create_function(|lua, place: String| {
+                place_table(lua, world, find_place(&place)?)
+            })?,
+        )?;
+        bind(
+            lua,
+            &api,
+            "spawn",
+            Power::Mutate,
+            scope.create_function(|_, (place, item, count): (String, String, Option<u32>)| {
+                let effect = spawn(world, find_place(&place)?, &item, count)?;
+                effects.borrow_mut().push(effect);
+                Ok(())
+            })?,
+        )?;
+        bind(
+            lua,
+            &api,
+            "creatures",
+            Power::Read,
+            scope.create_function(|lua, place: String| {
+                let place = find_place(&place)?;
+                let list = lua.create_table()?;
+                for creature in world.npcs.get(&place).into_iter().flatten() {
+                    let entry = lua.create_table()?;
+                    entry.set("id", creature.id.to_string())?;
+                    entry.set("name", creature.name.as_str())?;
+                    entry.set("health", creature.health)?;
+                    list.push(entry)?;
+                }
+                Ok(list)
+            })?,
+        )?;
+        bind(
+            lua,
+            &api,
+            "remove_creature",
+            Power::Mutate,
+            scope.create_function(|_, id: String| {
+                let creature = EntityId::parse(&id)
+                    .filter(|id| world.find_creature(*id).is_some())
+                    .ok_or_else(|| mlua::Error::runtime(format!("There's no creature {id}")))?;
+                effects.borrow_mut().push(Effect::RemoveCreature(creature));
+                Ok(())
+            })?,
+        )?;
+        bind(
+            lua,
+            &api,
+            "players",
+            Power::Read,
+            scope.create_function(|_, place: String| {
+                let place = find_place(&place)?;
+                Ok(world
+                    .player_characters
+                    .values()
+                    .filter(|c| c.location == place)
+                    .map(|c| c.name.clone())
+                    .collect::<Vec<_>>())
+            })?,
+        )?;
+
+        f(api)
+    })?;
+
+    Ok(effects.into_inner())
+}
+
+/// Hands a script a function, if it's trusted with what the function can do.
+/// Scripts that aren't just find it missing.
+fn bind<'lua>(
+    lua: &Lua,
+    table: &Table<'lua>,
+    name: &str,
+    power: Power,
+    function: Function<'lua>,
+) -> mlua::Result<()> {
+    let trust = lua
+        .app_data_ref::<Trust>()
+        .map_or(Trust::PlayerSubmitted, |t| *t);
+    if trust.allows(power) {
+        table.set(name, function)?;
+    }
+    Ok(())
+}
+
+/// Finds a place by its id, or the first one by that name
+fn find_place(world: &World, name: &str) -> mlua::Result<Location> {
+    Location::parse(name)
+        .filter(|l| world.places.contains_key(l))
+        .or_else(|| {
+            world
+                .places
+                .values()
+                .find(|p| p.name.eq_ignore_ascii_case(name))
+                .map(|p| p.location)
+        })
+        .ok_or_else(|| mlua::Error::runtime(format!("There's no place called {name}")))
+}
+
+fn find_direction(name: &str) -> mlua::Result<Direction> {
+    Direction::values()
+        .into_iter()
+        .find(|d| d.name().eq_ignore_ascii_case(name))
+        .ok_or_else(|| mlua::Error::runtime(format!("{name} isn't a direction")))
+}
+
+/// Checks a script can spawn an item, which has to be equipment or an
+/// ingredient the world knows about
+fn spawn(
+    world: &World,
+    location: Location,
+    item: &str,
+    count: Option<u32>,
+) -> mlua::Result<Effect> {
+    let count = count.unwrap_or(1);
+    if count == 0 || count > MAX_SPAWN {
+        return Err(mlua::Error::runtime(format!(
+            "Scripts can spawn between 1 and {MAX_SPAWN} of something at once"
+        )));
+    }
+    let name = world
+        .items
+        .keys()
+        .map(String::as_str)
+        .chain(crafting::INGREDIENTS.iter().map(|i| i.name))
+        .find(|name| name.eq_ignore_ascii_case(item.trim()))
+        .ok_or_else(|| mlua::Error::runtime(format!("There's no item called {item}")))?;
+    Ok(Effect::Spawn(location, name.to_string(), count))
+}
+
+/// What scripts see of a place
+fn place_table<'lua>(
+    lua: &'lua Lua,
+    world: &World,
+    location: Location,
+) -> mlua::Result<Table<'lua>> {
+    let place = &world.places[&location];
+    let table = lua.create_table()?;
+    table.set("id", location.hex())?;
+    table.set("name", place.name.as_str())?;
+    table.set("description", place.description.as_str())?;
+
+    let mut tags: Vec<_> = place.tags.iter().map(String::as_str).collect();
+    tags.sort();
+    table.set("tags", tags)?;
+
+    let exits = lua.create_table()?;
+    for (direction, to) in place.connections() {
+        if let Some(to) = world.places.get(to) {
+            exits.set(direction.name(), to.name.as_str())?;
+        }
+    }
+    table.set("exits", exits)?;
+
+    let mut players: Vec<_> = world
+        .player_characters
+        .values()
+        .filter(|c| c.location == location)
+        .map(|c| c.name.as_str())
+        .collect();
+    players.sort();
+    table.set("players", players)?;
+
+    let creatures = lua.create_table()?;
+    for creature in world.npcs.get(&location).into_iter().flatten() {
+        let entry = lua.create_table()?;
+        entry.set("id", creature.id.to_string())?;
+        entry.set("name", creature.name.as_str())?;
+        entry.set("health", creature.health)?;
+        creatures.push(entry)?;
+    }
+    table.set("creatures", creatures)?;
+
+    let ground = lua.create_table()?;
+    for (item, count) in place.ground.counts() {
+        ground.set(item, count)?;
+    }
+    table.set("ground", ground)?;
+
+    let equipment = lua.create_table()?;
+    for piece in place.ground.equipment() {
+        let entry = lua.create_table()?;
+        entry.set("id", piece.id.to_string())?;
+        entry.set("name", piece.name())?;
+        equipment.push(entry)?;
+    }
+    table.set("equipment", equipment)?;
+    Ok(table)
+}
+
+/// Runs a script file against a copy of the starting village as a new
+/// character, `somnuscape script-test <file> [args...]`
+pub fn script_test(args: &[String]) -> anyhow::Result<()> {
+    let [file, rest @ ..] = args else {
+        anyhow::bail!(SCRIPT_TEST_USAGE);
+    };
+    let mut world = World::default();
+    world.ensure_starting_village();
+    let player = PlayerId::new_random();
+    world.player_character(player).name = "Tester".to_string();
+
+    print!(
+        "{}",
+        dry_run(Path::new(file), &world, player, &rest.join(" "))?
+    );
+    Ok(())
+}
+
+/// Runs a script as a player without changing the world, describing what it
+/// would have done. A command script is run with the arguments. An event script
+/// is handed each kind of event as if the player caused it where they are, and
+/// handlers that wait are woken straight away rather than after their seconds.
+pub fn dry_run(path: &Path, world: &World, player: PlayerId, args: &str) -> anyhow::Result<String> {
+    let lua = load_script(path)?;
+    let location = world
+        .player_characters
+        .get(&player)
+        .context("the player has no character")?
+        .location;
+    let mut res = format!("Dry run of {}, nothing was changed\n", path.display());
+    let report = |res: &mut String, effects: mlua::Result<Vec<Effect>>| match effects {
+        Ok(effects) if effects.is_empty() => res.push_str("  Nothing happens\n"),
+        Ok(effects) => {
+            for effect in effects {
+                res.push_str(&format!("  {}\n", describe_effect(world, &effect)));
+            }
+        }
+        Err(e) => res.push_str(&format!("  Failed: {e}\n")),
+    };
+
+    if lua
+        .named_registry_value::<Option<Table>>("command")?
+        .is_some()
+    {
+        res.push_str(&format!("Running the command with '{args}'\n"));
+        report(&mut res, run_command(&lua, world, player, args));
+        return Ok(res);
+    }
+
+    let events = [
+        Event::Tick,
+        Event::PlayerEnteredRoom(player, location),
+        Event::ItemPickedUp(player, location, "Torch".to_string()),
+        Event::PlayerDowned(player, location),
+    ];
+    for event in &events {
+        res.push_str(&format!("On {}\n", event.name()));
+        let waits = match handle_event(&lua, world, event) {
+            Ok((effects, waits)) => {
+                report(&mut res, Ok(effects));
+                waits
+            }
+            Err(e) => {
+                report(&mut res, Err(e));
+                continue;
+            }
+        };
+
+        for (thread, seconds) in waits {
+            let (mut waited, mut seconds) = (0.0, Some(seconds));
+            for _ in 0..MAX_DRY_WAKES {
+                let Some(s) = seconds else {
+                    break;
+                };
+                waited += s;
+                res.push_str(&format!("After waiting {waited} seconds\n"));
+                let woken = wake(&lua, world, &thread);
+                seconds = woken.as_ref().ok().and_then(|(_, wait)| *wait);
+                report(&mut res, woken.map(|(effects, _)| effects));
+            }
+            if seconds.is_some() {
+                res.push_str("  Still waiting, stopped it there\n");
+            }
+        }
+    }
+    Ok(res)
+}
+
+fn describe_effect(world: &World, effect: &Effect) -> String {
+    let player = |p: &PlayerId| {
+        world
+            .player_characters
+            .get(p)
+            .map_or("someone who's left", |c| c.name.as_str())
+    };
+    let place = |l: &Location| {
+        world
+            .places
+            .get(l)
+            .map_or("a place that doesn't exist", |p| p.name.as_str())
+    };
+    match effect {
+        Effect::Send(p, msg) => format!("Tells {}: {msg}", player(p)),
+        Effect::Say(l, msg, _) => format!("Says in {}: {msg}", place(l)),
+        Effect::Heal(p, amount) => format!("Heals {} by {amount}", player(p)),
+        Effect::Hurt(p, amount) => format!("Hurts {} by {amount}", player(p)),
+        Effect::Open(from, direction, to) => format!(
+            "Opens a way {} from {} to {}",
+            direction.name(),
+            place(from),
+            place(to)
+        ),
+        Effect::Close(l, direction) => {
+            format!("Closes the way {} out of {}", direction.name(), place(l))
+        }
+        Effect::RemoveCreature(id) => match world.find_creature(*id) {
+            Some((_, creature)) => format!("Removes the {} ({id})", creature.name),
+            None => format!("Removes creature {id}, which is already gone"),
+        },
+        Effect::Spawn(l, item, count) => format!("Spawns {item} x{count} in {}", place(l)),
+    }
+}
+
+fn apply(engine: &mut Engine, effects: Vec<Effect>) {
+    for effect in effects {
+        match effect {
+            Effect::Send(player, msg) => engine.connection_broker.send_player_message(player, msg),
+            Effect::Say(location, msg, except) => {
+                engine.connection_broker.broadcast_to_location(
+                    &engine.world,
+                    location,
+                    msg,
+                    except,
+                );
+            }
+            Effect::Heal(player, amount) => engine.world.heal_player(player, amount),
+            Effect::Hurt(player, amount) => {
+                let character = engine.world.player_character(player);
+                character.health = character.health.saturating_sub(amount).max(1);
+                if let Some(interrupted) = character.interrupt() {
+                    engine
+                        .connection_broker
+                        .send_player_message(player, interrupted.trim().to_string());
+                }
+            }
+            Effect::Open(from, direction, to) => {
+                let places = &mut engine.world.places;
+                if !places.contains_key(&to) {
+                    tracing::warn!("An event script tried to open a way to a place that's gone");
+                    continue;
+                }
+                let Some(start) = places.get_mut(&from) else {
+                    tracing::warn!("An event script tried to open a way from a place that's gone");
+                    continue;
+                };
+                if from == to || start.is_connected(to) {
+                    continue;
+                }
+                let opened = start.add_connection(direction, to).and_then(|d| {
+                    let back = engine
+                        .world
+                        .places
+                        .get_mut(&to)
+                        .unwrap()
+                        .add_connection(d.reverse(), from);
+                    if back.is_err() {
+                        // Without the way back it'd be a one way door
+                        engine
+                            .world
+                            .places
+                            .get_mut(&from)
+                            .unwrap()
+                            .remove_connection(d);
+                    }
+                    back
+                });
+                if let Err(e) = opened {
+                    tracing::warn!("An event script couldn't open a way: {e}");
+                    continue;
+                }
+                engine.world.place_changed(from);
+                engine.world.place_changed(to);
+            }
+            Effect::RemoveCreature(id) => {
+                engine.world.remove_creature(id);
+            }
+            Effect::Spawn(location, item, count) => {
+                let Some(place) = engine.world.places.get_mut(&location) else {
+                    continue;
+                };
+                if engine.world.items.contains_key(&item) {
+                    for _ in 0..count {
+                        place
+                            .ground
+                            .add_instance(ItemInstance::new(&item, Quality::Common));
+                    }
+                } else {
+                    place.ground.add(&item, count);
+                }
+                engine.world.place_changed(location);
+                engine
+                    .world
+                    .record_transaction("a script", &item, count as i64, "spawning it");
+            }
+            Effect::Close(location, direction) => {
+                let Some(place) = engine.world.places.get_mut(&location) else {
+                    continue;
+                };
+                if let Some(to) = place.remove_connection(direction) {
+                    if let Some(other) = engine.world.places.get_mut(&to) {
+                        other.remove_connection(direction.reverse());
+                    }
+                    engine.world.place_changed(location);
+                    engine.world.place_changed(to);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn write(name: &str, source: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(name);
+        std::fs::write(&path, source).unwrap();
+        path
+    }
+
+    fn load(name: &str, source: &str) -> anyhow::Result<Command> {
+        let path = write(name, source);
+        let res = load_script(&path).and_then(|lua| {
+            command(Live {
+                attachment: name.to_string(),
+                version: 1,
+                trust: Trust::Core,
+                lua,
+            })
+        });
+        std::fs::remove_file(path).unwrap();
+        res
+    }
+
+    #[test]
+    fn loads_sandboxed_scripts() {
+        let command = load(
+            "somnuscape-wave.lua",
+            r#"command { name = "wave", aliases = { "hi" }, help = "Waves", run = function(ctx) end }"#,
+        )
+        .unwrap();
+        assert_eq!(command.name, "wave");
+        assert!(command.match_name("hi"));
+
+        assert!(load("somnuscape-os.lua", "os.exit(1)").is_err());
+        assert!(load("somnuscape-io.lua", "io.open('config.yaml')").is_err());
+        assert!(load("somnuscape-dofile.lua", "dofile('config.yaml')").is_err());
+        assert!(load("somnuscape-loadfile.lua", "loadfile('config.yaml')").is_err());
+        let binary = r#"assert(load(string.dump(function() end))) command { name = "b", help = "", run = function(ctx) end }"#;
+        assert!(load("somnuscape-binary.lua", binary).is_err());
+        let text = r#"assert(load("return 1")) command { name = "t", help = "", run = function(ctx) end }"#;
+        assert!(load("somnuscape-text.lua", text).is_ok());
+        assert!(load("somnuscape-loop.lua", "while true do end").is_err());
+        assert!(load("somnuscape-none.lua", "local x = 1").is_err());
+    }
+
+    #[test]
+    fn hooks_only_see_their_events() {
+        let path = write(
+            "somnuscape-bell.lua",
+            r#"on("tick", function(event, world) world.say("Bell Tower", "Bong " .. event.tick) end)"#,
+        );
+        let lua = load_script(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        let mut world = World {
+            current_tick: 12,
+            ..Default::default()
+        };
+        let place = crate::mud::world::Place::new("Bell Tower".into(), String::new());
+        let location = place.location;
+        world.places.insert(location, place);
+
+        let (effects, _) = handle_event(&lua, &world, &Event::Tick).unwrap();
+        assert_eq!(effects, [Effect::Say(location, "Bong 12".into(), None)]);
+
+        let moved = Event::PlayerEnteredRoom(PlayerId::new_random(), location);
+        assert_eq!(handle_event(&lua, &world, &moved).unwrap().0, []);
+    }
+
+    #[test]
+    fn places_are_found_by_id() {
+        let path = write(
+            "somnuscape-echo.lua",
+            r#"on("player_entered_room", function(event, world)
+                world.say(event.place_id, world.place(event.place_id).id)
+              end)"#,
+        );
+        let lua = load_script(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        // Two places sharing a name can still be told apart
+        let mut world = World::default();
+        let mut tower = None;
+        for _ in 0..2 {
+            let place = crate::mud::world::Place::new("Bell Tower".into(), String::new());
+            tower = Some(place.location);
+            world.places.insert(place.location, place);
+        }
+        let tower = tower.unwrap();
+        let player = PlayerId::new_random();
+        world.player_character(player).location = tower;
+
+        let moved = Event::PlayerEnteredRoom(player, tower);
+        assert_eq!(
+            handle_event(&lua, &world, &moved).unwrap().0,
+            [Effect::Say(tower, tower.hex(), None)]
+        );
+    }
+
+    #[test]
+    fn handlers_wait_across_ticks() {
+        let path = write(
+            "somnuscape-ghost.lua",
+            r#"on("tick", function(event, world)
+                world.say("Bell Tower", "A ghost appears")
+                wait(2)
+                world.say("Bell Tower", "It speaks on tick " .. event.tick)
+                wait()
+                world.say("Bell Tower", "It fades")
+              end)"#,
+        );
+        let lua = load_script(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        let mut world = World {
+            current_tick: 12,
+            ..Default::default()
+        };
+        let place = crate::mud::world::Place::new("Bell Tower".into(), String::new());
+        let location = place.location;
+        world.places.insert(location, place);
+        let said = |msg: &str| vec![Effect::Say(location, msg.into(), None)];
+
+        let (effects, mut waits) = handle_event(&lua, &world, &Event::Tick).unwrap();
+        assert_eq!(effects, said("A ghost appears"));
+        let (thread, seconds) = waits.pop().unwrap();
+        assert_eq!(seconds, 2.0);
+
+        world.current_tick = 14;
+        let (effects, wait) = wake(&lua, &world, &thread).unwrap();
+        assert_eq!(effects, said("It speaks on tick 12"));
+        assert_eq!(wait, Some(0.0));
+        let (effects, wait) = wake(&lua, &world, &thread).unwrap();
+        assert_eq!(effects, said("It fades"));
+        assert_eq!(wait, None);
+    }
+
+    #[test]
+    fn reads_places_and_spawns_known_items() {
+        let path = write(
+            "somnuscape-cellar.lua",
+            r#"on("tick", function(event, world)
+                local here = world.place("village square")
+                if here.exits.east == "The Drowsy Goose" and here.tags[1] then
+                  world.spawn(here.name, "honeycomb", 3)
+                end
+              end)"#,
+        );
+        let lua = load_script(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        let mut world = World::default();
+        world.ensure_starting_village();
+        let square = find_place(&world, "Village Square").unwrap();
+
+        let (effects, _) = handle_event(&lua, &world, &Event::Tick).unwrap();
+        assert_eq!(effects, [Effect::Spawn(square, "Honeycomb".into(), 3)]);
+        assert!(spawn(&world, square, "Gold Coin", None).is_err());
+        assert!(spawn(&world, square, "Honeycomb", Some(MAX_SPAWN + 1)).is_err());
+
+        let lua = sandbox().unwrap();
+        let table = place_table(&lua, &world, square).unwrap();
+        assert_eq!(table.get::<_, String>("name").unwrap(), "Village Square");
+        let exits: Table = table.get("exits").unwrap();
+        assert_eq!(exits.get::<_, String>("west").unwrap(), "Old Shrine");
+    }
+
+    #[test]
+    fn binds_only_what_scripts_are_trusted_with() {
+        let source = r#"on("tick", function(event, world)
+            world.say("Bell Tower", tostring(world.place ~= nil) .. " " .. tostring(world.spawn ~= nil))
+          end)"#;
+        let mut world = World::default();
+        let place = crate::mud::world::Place::new("Bell Tower".into(), String::new());
+        let location = place.location;
+        world.places.insert(location, place);
+
+        for (trust, said) in [
+            (Trust::Core, "true true"),
+            (Trust::Builder, "true false"),
+            (Trust::PlayerSubmitted, "false false"),
+        ] {
+            let lua = load_source("bell", source, trust).unwrap();
+            let (effects, _) = handle_event(&lua, &world, &Event::Tick).unwrap();
+            assert_eq!(effects, [Effect::Say(location, said.into(), None)]);
+        }
+
+        let lua = load_source(
+            "bell",
+            r#"on("tick", function(event, world) world.heal("Ada", 5) end)"#,
+            Trust::Builder,
+        )
+        .unwrap();
+        assert!(handle_event(&lua, &world, &Event::Tick).is_err());
+    }
+
+    #[test]
+    fn decorations_only_reach_their_home() {
+        let mut world = World::default();
+        world.ensure_starting_village();
+        let home = find_place(&world, "Village Square").unwrap();
+        let player = PlayerId::new_random();
+        world.player_character(player).name = "Ada".to_string();
+        world.player_character(player).location = home;
+
+        let lua = load_decoration(
+            home,
+            r#"on("player_entered_room", function(event, world)
+                world.send(event.player, "Welcome home")
+                world.say(event.place, "The chimes ring")
+              end)"#,
+        )
+        .unwrap();
+        let entered = Event::PlayerEnteredRoom(player, home);
+        let (effects, _) = handle_event(&lua, &world, &entered).unwrap();
+        assert_eq!(
+            effects,
+            [
+                Effect::Send(player, "Welcome home".into()),
+                Effect::Say(home, "The chimes ring".into(), None)
+            ]
+        );
+
+        let lua = load_decoration(
+            home,
+            r#"on("tick", function(event, world) world.say("Old Shrine", "Boo") end)"#,
+        )
+        .unwrap();
+        assert!(handle_event(&lua, &world, &Event::Tick).is_err());
+        assert!(check_decoration("world.spawn(").is_err());
+        // Checking only compiles, so nothing in the script runs before approval
+        assert!(check_decoration(r#"error("ran")"#).is_ok());
+    }
+
+    #[test]
+    fn dry_runs_describe_without_changing_anything() {
+        let mut world = World::default();
+        world.ensure_starting_village();
+        let player = PlayerId::new_random();
+        world.player_character(player).name = "Tester".to_string();
+
+        let path = write(
+            "somnuscape-dry-wave.lua",
+            r#"command { name = "wave", help = "Waves", run = function(ctx)
+                ctx.say(ctx.name .. " " .. ctx.args)
+                ctx.spawn("honeycomb")
+              end }"#,
+        );
+        let res = dry_run(&path, &world, player, "hello").unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert!(res.contains("Says in Village Square: Tester hello"));
+        assert!(res.contains("Spawns Honeycomb x1 in Village Square"));
+        assert!(world
+            .places
+            .values()
+            .all(|p| p.ground.get("Honeycomb").is_none()));
+
+        let path = write(
+            "somnuscape-dry-ghost.lua",
+            r#"on("player_entered_room", function(event, world)
+                wait(3)
+                world.send(event.player, "Boo")
+                while true do wait(1) end
+              end)"#,
+        );
+        let res = dry_run(&path, &world, player, "").unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert!(res.contains("After waiting 3 seconds\n  Tells Tester: Boo"));
+        assert!(res.contains("Still waiting"));
+    }
+}
@@ -1,15 +1,445 @@
 use core::fmt;
-use std::{collections::HashMap, path::PathBuf, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+};
 
+use anyhow::Context;
+use argon2::{
+    password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use crossbeam::channel::{Receiver, Sender};
+use rusqlite::{params, Connection, OptionalExtension};
 use serde::{
     de::{self, Visitor},
     Deserialize, Deserializer, Serialize, Serializer,
 };
+use serde_json::{Map, Value};
 use tokio::sync::{RwLock, RwLockReadGuard};
 
-use crate::PlayerAccount;
+use crate::{config, mud::world::World, PlayerAccount};
 
 pub const STATE_DIR: &str = "somnustate/";
+/// The database inside the state directory used by the SQLite storage
+const SQLITE_FILE: &str = "somnuscape.db";
+/// Parts of a world saved as their own rows so they're only written when they change
+const WORLD_TABLES: &[(&str, &str)] = &[("places", "places"), ("player-characters", "characters")];
+
+/// How worlds and accounts are saved
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum StorageKind {
+    /// One YAML file per world and one for the accounts, easy to read and edit by hand
+    #[default]
+    Yaml,
+    /// A SQLite database, only rewriting the places and characters that changed
+    Sqlite,
+}
+
+/// Somewhere worlds and accounts are kept between runs. Worlds are identified by
+/// their save path and accounts by the path of the registry file, so the SQLite
+/// storage can find and migrate anything saved as YAML before it was switched on.
+pub trait Storage: Send + Sync {
+    fn load_accounts(&self, path: &Path) -> anyhow::Result<HashMap<PlayerId, PlayerAccount>>;
+
+    /// Saves the accounts after `changed` was added or updated
+    fn save_account(
+        &self,
+        path: &Path,
+        accounts: &HashMap<PlayerId, PlayerAccount>,
+        changed: PlayerId,
+    ) -> anyhow::Result<()>;
+
+    fn load_world(&self, path: &Path) -> anyhow::Result<Option<World>>;
+
+    fn save_world(&self, world: &World) -> anyhow::Result<()>;
+
+    /// Saves a world given just the parts of it that changed since the last save,
+    /// `changed` only has the places and characters that changed
+    fn save_world_changes(&self, world: &World, _changed: &World) -> anyhow::Result<()> {
+        self.save_world(world)
+    }
+}
+
+/// A copy of a world to save, either all of it or just what's changed
+pub struct WorldChanges {
+    pub world: World,
+    /// Whether every place and character is there, not just the changed ones
+    pub full: bool,
+}
+
+/// World saves that have failed since the server started, across every realm
+static SAVE_FAILURES: AtomicU64 = AtomicU64::new(0);
+
+pub fn save_failures() -> u64 {
+    SAVE_FAILURES.load(Ordering::Relaxed)
+}
+
+/// Changes handed to the persistence thread that it hasn't saved yet
+static UNSAVED: AtomicUsize = AtomicUsize::new(0);
+
+/// Blocks until the persistence thread has saved everything it's been handed
+pub fn wait_for_persistence() {
+    while UNSAVED.load(Ordering::SeqCst) > 0 {
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+}
+
+/// Hands a world's changes to the persistence thread, which saves them in the background
+pub fn persist(changes: WorldChanges) {
+    static SENDER: OnceLock<Sender<WorldChanges>> = OnceLock::new();
+
+    UNSAVED.fetch_add(1, Ordering::SeqCst);
+    SENDER
+        .get_or_init(|| {
+            let (sender, receiver) = crossbeam::channel::unbounded();
+            std::thread::Builder::new()
+                .name("persistence".into())
+                .spawn(move || persist_worlds(receiver))
+                .expect("Could not start the persistence thread");
+            sender
+        })
+        .send(changes)
+        .expect("Persistence thread should not stop");
+}
+
+/// Keeps a copy of every world up to date with the changes sent to it, so
+/// storage that can only write whole worlds still can
+fn persist_worlds(receiver: Receiver<WorldChanges>) {
+    let mut worlds: HashMap<PathBuf, World> = HashMap::new();
+    // Worlds whose last save failed, the changes it had are only in the copy so
+    // the next save writes the whole world
+    let mut failed: HashSet<PathBuf> = HashSet::new();
+
+    for WorldChanges {
+        world: mut changed,
+        full,
+    } in receiver
+    {
+        let path = changed.save_path.clone();
+        if full {
+            match storage().and_then(|s| s.save_world(&changed)) {
+                Ok(()) => failed.remove(&path),
+                Err(e) => {
+                    tracing::error!("Failed saving world: {e}");
+                    SAVE_FAILURES.fetch_add(1, Ordering::Relaxed);
+                    failed.insert(path.clone())
+                }
+            };
+            worlds.insert(path, changed);
+            UNSAVED.fetch_sub(1, Ordering::SeqCst);
+            continue;
+        }
+
+        let mut copy = worlds.remove(&path).unwrap_or_else(|| {
+            tracing::warn!("Saving changes to a world that was never fully saved");
+            storage()
+                .and_then(|s| s.load_world(&path))
+                .ok()
+                .flatten()
+                .unwrap_or_default()
+        });
+        copy.places
+            .extend(changed.places.iter().map(|(l, p)| (*l, p.clone())));
+        copy.player_characters.extend(
+            changed
+                .player_characters
+                .iter()
+                .map(|(p, c)| (*p, c.clone())),
+        );
+
+        // Everything but the places and characters comes from the latest changes
+        let places = std::mem::take(&mut copy.places);
+        let player_characters = std::mem::take(&mut copy.player_characters);
+        let changed_places = std::mem::take(&mut changed.places);
+        let changed_characters = std::mem::take(&mut changed.player_characters);
+        copy = World {
+            places,
+            player_characters,
+            ..changed.clone()
+        };
+        changed.places = changed_places;
+        changed.player_characters = changed_characters;
+
+        let saved = storage().and_then(|s| match failed.contains(&path) {
+            true => s.save_world(&copy),
+            false => s.save_world_changes(&copy, &changed),
+        });
+        match saved {
+            Ok(()) => failed.remove(&path),
+            Err(e) => {
+                tracing::error!("Failed saving world: {e}");
+                SAVE_FAILURES.fetch_add(1, Ordering::Relaxed);
+                failed.insert(path.clone())
+            }
+        };
+        worlds.insert(path, copy);
+        UNSAVED.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// The storage picked in the config, opened the first time it's needed
+pub fn storage() -> anyhow::Result<&'static dyn Storage> {
+    static STORAGE: OnceLock<Box<dyn Storage>> = OnceLock::new();
+
+    if let Some(storage) = STORAGE.get() {
+        return Ok(storage.as_ref());
+    }
+    let opened: Box<dyn Storage> = match config::get().storage {
+        StorageKind::Yaml => Box::new(YamlStorage),
+        StorageKind::Sqlite => Box::new(
+            SqliteStorage::open(&make_save_path(SQLITE_FILE))
+                .context("Could not open the SQLite database")?,
+        ),
+    };
+    Ok(STORAGE.get_or_init(|| opened).as_ref())
+}
+
+pub struct YamlStorage;
+
+impl Storage for YamlStorage {
+    fn load_accounts(&self, path: &Path) -> anyhow::Result<HashMap<PlayerId, PlayerAccount>> {
+        if !path.try_exists()? {
+            return Ok(HashMap::new());
+        }
+        Ok(serde_yaml::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    fn save_account(
+        &self,
+        path: &Path,
+        accounts: &HashMap<PlayerId, PlayerAccount>,
+        _changed: PlayerId,
+    ) -> anyhow::Result<()> {
+        write_creating_dir(path, serde_yaml::to_string(accounts)?)
+    }
+
+    fn load_world(&self, path: &Path) -> anyhow::Result<Option<World>> {
+        if !path.try_exists()? {
+            return Ok(None);
+        }
+        Ok(Some(serde_yaml::from_str(&std::fs::read_to_string(path)?)?))
+    }
+
+    fn save_world(&self, world: &World) -> anyhow::Result<()> {
+        write_creating_dir(&world.save_path, serde_yaml::to_string(world)?)
+    }
+}
+
+fn write_creating_dir(path: &Path, contents: String) -> anyhow::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, contents)?;
+    Ok(())
+}
+
+/// Hashes of the rows written for a world, by table and key
+type RowHashes = HashMap<(&'static str, String), u64>;
+
+/// Keeps everything in one SQLite database. Each place and character is its own
+/// row and a hash of what was last written is kept so saves only touch rows that
+/// changed. Anything not found in the database is looked for in the YAML files
+/// and imported the next time it's saved.
+pub struct SqliteStorage {
+    conn: Mutex<Connection>,
+    /// What was last written for each world
+    written: Mutex<HashMap<String, RowHashes>>,
+}
+
+impl SqliteStorage {
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        Self::new(Connection::open(path)?)
+    }
+
+    fn new(conn: Connection) -> anyhow::Result<Self> {
+        conn.execute_batch(
+            "PRAGMA journal_mode = WAL;
+            CREATE TABLE IF NOT EXISTS accounts (registry TEXT, id TEXT, data TEXT, PRIMARY KEY (registry, id));
+            CREATE TABLE IF NOT EXISTS worlds (world TEXT PRIMARY KEY, data TEXT);
+            CREATE TABLE IF NOT EXISTS places (world TEXT, key TEXT, data TEXT, PRIMARY KEY (world, key));
+            CREATE TABLE IF NOT EXISTS characters (world TEXT, key TEXT, data TEXT, PRIMARY KEY (world, key));",
+        )?;
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+            written: Default::default(),
+        })
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn load_accounts(&self, path: &Path) -> anyhow::Result<HashMap<PlayerId, PlayerAccount>> {
+        let registry = path.to_string_lossy();
+        let rows: Vec<(String, String)> = {
+            let conn = self.conn.lock().unwrap();
+            let mut stmt = conn.prepare("SELECT id, data FROM accounts WHERE registry = ?1")?;
+            let rows = stmt.query_map([&registry], |r| Ok((r.get(0)?, r.get(1)?)))?;
+            rows.collect::<Result<_, _>>()?
+        };
+
+        if rows.is_empty() {
+            let accounts = YamlStorage.load_accounts(path)?;
+            if !accounts.is_empty() {
+                tracing::info!("Migrating {} accounts from {registry}", accounts.len());
+                for player in accounts.keys() {
+                    self.save_account(path, &accounts, *player)?;
+                }
+            }
+            return Ok(accounts);
+        }
+
+        rows.into_iter()
+            .map(|(id, data)| {
+                let id = serde_json::from_value(Value::String(id))?;
+                Ok((id, serde_json::from_str(&data)?))
+            })
+            .collect()
+    }
+
+    fn save_account(
+        &self,
+        path: &Path,
+        accounts: &HashMap<PlayerId, PlayerAccount>,
+        changed: PlayerId,
+    ) -> anyhow::Result<()> {
+        let Some(account) = accounts.get(&changed) else {
+            return Ok(());
+        };
+        let id = serde_json::to_value(changed)?;
+
+        self.conn.lock().unwrap().execute(
+            "INSERT OR REPLACE INTO accounts (registry, id, data) VALUES (?1, ?2, ?3)",
+            params![
+                path.to_string_lossy(),
+                id.as_str(),
+                serde_json::to_string(account)?
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn load_world(&self, path: &Path) -> anyhow::Result<Option<World>> {
+        let key = path.to_string_lossy().to_string();
+        let conn = self.conn.lock().unwrap();
+        let data: Option<String> = conn
+            .query_row("SELECT data FROM worlds WHERE world = ?1", [&key], |r| {
+                r.get(0)
+            })
+            .optional()?;
+        let Some(data) = data else {
+            drop(conn);
+            let world = YamlStorage.load_world(path)?;
+            if world.is_some() {
+                tracing::info!("Migrating world from {key}, it'll be imported on the next save");
+            }
+            return Ok(world);
+        };
+
+        let mut world: Map<String, Value> = serde_json::from_str(&data)?;
+        let mut written = HashMap::new();
+        for (field, table) in WORLD_TABLES {
+            let mut stmt =
+                conn.prepare(&format!("SELECT key, data FROM {table} WHERE world = ?1"))?;
+            let mut rows = Map::new();
+            for row in stmt.query_map([&key], |r| {
+                Ok((r.get::<_, String>(0)?, r.get::<_, String>(1)?))
+            })? {
+                let (row_key, data) = row?;
+                written.insert((*table, row_key.clone()), seahash::hash(data.as_bytes()));
+                rows.insert(row_key, serde_json::from_str(&data)?);
+            }
+            world.insert(field.to_string(), Value::Object(rows));
+        }
+        self.written.lock().unwrap().insert(key.clone(), written);
+
+        let world = serde_json::from_value(Value::Object(world))
+            .with_context(|| format!("Could not deserialize world {key}"))?;
+        Ok(Some(world))
+    }
+
+    fn save_world(&self, world: &World) -> anyhow::Result<()> {
+        self.write_world(world, true)
+    }
+
+    fn save_world_changes(&self, _world: &World, changed: &World) -> anyhow::Result<()> {
+        self.write_world(changed, false)
+    }
+}
+
+impl SqliteStorage {
+    /// Writes the rows of a world that differ from what's stored. If `complete` is set
+    /// the world has every place and character, so rows it doesn't have are removed.
+    fn write_world(&self, world: &World, complete: bool) -> anyhow::Result<()> {
+        let key = world.save_path.to_string_lossy().to_string();
+        let Value::Object(mut data) = serde_json::to_value(world)? else {
+            anyhow::bail!("World didn't serialize to an object");
+        };
+
+        let mut written = self.written.lock().unwrap();
+        // Worlds that weren't loaded from here might have stale rows left over, so start fresh
+        let fresh = complete && !written.contains_key(&key);
+        // Only remember what was written once it's committed
+        let mut last = written.get(&key).cloned().unwrap_or_default();
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        if fresh {
+            for (_, table) in WORLD_TABLES {
+                tx.execute(&format!("DELETE FROM {table} WHERE world = ?1"), [&key])?;
+            }
+        }
+
+        for (field, table) in WORLD_TABLES {
+            let Some(Value::Object(rows)) = data.remove(*field) else {
+                continue;
+            };
+
+            let stale: Vec<_> = last
+                .keys()
+                .filter(|(t, k)| complete && t == table && !rows.contains_key(k))
+                .cloned()
+                .collect();
+            for entry in stale {
+                tx.execute(
+                    &format!("DELETE FROM {table} WHERE world = ?1 AND key = ?2"),
+                    [&key, &entry.1],
+                )?;
+                last.remove(&entry);
+            }
+
+            for (row_key, row) in rows {
+                let row = serde_json::to_string(&row)?;
+                let hash = seahash::hash(row.as_bytes());
+                if last.get(&(*table, row_key.clone())) == Some(&hash) {
+                    continue;
+                }
+                tx.execute(
+                    &format!(
+                        "INSERT OR REPLACE INTO {table} (world, key, data) VALUES (?1, ?2, ?3)"
+                    ),
+                    [&key, &row_key, &row],
+                )?;
+                last.insert((*table, row_key), hash);
+            }
+        }
+
+        tx.execute(
+            "INSERT OR REPLACE INTO worlds (world, data) VALUES (?1, ?2)",
+            [&key, &serde_json::to_string(&data)?],
+        )?;
+        tx.commit()?;
+        written.insert(key, last);
+        Ok(())
+    }
+}
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
 #[serde(transparent)]
@@ -21,6 +451,55 @@ pub struct PlayerId(
     u128,
 );
 
+/// The top bits of every simulated player's id, so they never clash with a real one
+const BOT_ID_PREFIX: u128 = 0x50b0_7000 << 96;
+
+impl PlayerId {
+    pub fn new_random() -> Self {
+        Self(rand::random())
+    }
+
+    /// The nth simulated player, the same each time so bots reuse their characters
+    pub fn bot(n: usize) -> Self {
+        Self(BOT_ID_PREFIX | n as u128)
+    }
+}
+
+/// A stored password, new accounts get an argon2 hash with its own salt while
+/// accounts made before that keep their seahash until they next log in.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Password {
+    Argon2(String),
+    Legacy(u64),
+}
+
+impl Password {
+    pub fn hash(password: &str) -> anyhow::Result<Self> {
+        let salt = SaltString::generate(&mut rand::rngs::OsRng);
+        let hash = Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map_err(|e| anyhow::anyhow!("could not hash password: {e}"))?;
+
+        Ok(Password::Argon2(hash.to_string()))
+    }
+
+    pub fn verify(&self, password: &str) -> bool {
+        match self {
+            Password::Argon2(hash) => PasswordHash::new(hash).is_ok_and(|hash| {
+                Argon2::default()
+                    .verify_password(password.as_bytes(), &hash)
+                    .is_ok()
+            }),
+            Password::Legacy(hash) => seahash::hash(password.as_bytes()) == *hash,
+        }
+    }
+
+    pub fn is_legacy(&self) -> bool {
+        matches!(self, Password::Legacy(_))
+    }
+}
+
 /// Keeps track of player accounts, linking a username and password
 /// to an easily copied ID.
 #[derive(Debug, Clone)]
@@ -29,39 +508,75 @@ pub struct AccountStorage(Arc<RwLock<HashMap<PlayerId, PlayerAccount>>>, PathBuf
 impl AccountStorage {
     pub async fn load_or_new(filename: &str) -> anyhow::Result<Self> {
         let path = make_save_path(filename);
-
-        let values = if tokio::fs::try_exists(&path).await.is_ok_and(|r| r) {
-            let yaml = tokio::fs::read_to_string(&path).await?;
-            serde_yaml::from_str(&yaml)?
-        } else {
-            if let Some(dir) = path.parent() {
-                tokio::fs::create_dir_all(dir).await?;
-            }
-
-            HashMap::new()
-        };
+        let load = path.clone();
+        let values = tokio::task::spawn_blocking(move || storage()?.load_accounts(&load)).await??;
 
         Ok(AccountStorage(RwLock::new(values).into(), path))
     }
 
     pub async fn register_user(&self, player: PlayerAccount) -> anyhow::Result<PlayerId> {
         let mut write = self.0.write().await;
-        let id = PlayerId(rand::random());
+        let id = PlayerId::new_random();
         write.insert(id, player);
-
-        let yaml = serde_yaml::to_string::<HashMap<PlayerId, PlayerAccount>>(&write)?;
-        tokio::fs::write(&self.1, yaml).await?;
+        self.save(write.clone(), id).await?;
 
         Ok(id)
     }
 
-    pub async fn read(&self) -> RwLockReadGuard<HashMap<PlayerId, PlayerAccount>> {
+    /// Changes a player's account, saving the registry right away
+    pub async fn update(
+        &self,
+        player: PlayerId,
+        update: impl FnOnce(&mut PlayerAccount),
+    ) -> anyhow::Result<()> {
+        let mut write = self.0.write().await;
+        if let Some(account) = write.get_mut(&player) {
+            update(account);
+        }
+        self.save(write.clone(), player).await?;
+
+        Ok(())
+    }
+
+    /// Saves the accounts off the async runtime, storage blocks on files and the database
+    async fn save(
+        &self,
+        accounts: HashMap<PlayerId, PlayerAccount>,
+        changed: PlayerId,
+    ) -> anyhow::Result<()> {
+        let path = self.1.clone();
+        tokio::task::spawn_blocking(move || storage()?.save_account(&path, &accounts, changed))
+            .await?
+    }
+
+    pub async fn read(&self) -> RwLockReadGuard<'_, HashMap<PlayerId, PlayerAccount>> {
         self.0.read().await
     }
 
-    pub fn blocking_read(&self) -> RwLockReadGuard<HashMap<PlayerId, PlayerAccount>> {
+    /// Changes a player's account from the engine thread, saving the registry right away
+    pub fn blocking_update(
+        &self,
+        player: PlayerId,
+        update: impl FnOnce(&mut PlayerAccount),
+    ) -> anyhow::Result<()> {
+        let mut write = self.0.blocking_write();
+        if let Some(account) = write.get_mut(&player) {
+            update(account);
+        }
+        storage()?.save_account(&self.1, &write, player)?;
+
+        Ok(())
+    }
+
+    pub fn blocking_read(&self) -> RwLockReadGuard<'_, HashMap<PlayerId, PlayerAccount>> {
         self.0.blocking_read()
     }
+
+    /// Accounts saved somewhere other than the state directory, for tests
+    #[cfg(test)]
+    pub fn at(path: PathBuf, accounts: HashMap<PlayerId, PlayerAccount>) -> Self {
+        AccountStorage(RwLock::new(accounts).into(), path)
+    }
 }
 
 /// We use u128 for some IDs, we save them as a hex string which
@@ -79,8 +594,8 @@ impl<'de> Visitor<'de> for HexIdVisitor {
     where
         E: de::Error,
     {
-        Ok(u128::from_str_radix(v, 16)
-            .map_err(|e| E::custom(format!("could not parse hex string: {e}")))?)
+        u128::from_str_radix(v, 16)
+            .map_err(|e| E::custom(format!("could not parse hex string: {e}")))
     }
 }
 
@@ -103,3 +618,95 @@ pub fn make_save_path(filename: &str) -> PathBuf {
     path.push(filename);
     path
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sqlite_saves_only_changes() {
+        let storage = SqliteStorage::new(Connection::open_in_memory().unwrap()).unwrap();
+        let mut world = World::default();
+        world.ensure_starting_village();
+        world.save_path = "realm/world.yaml".into();
+        let player = PlayerId::new_random();
+        world.player_character(player).name = "ada".into();
+        storage.save_world(&world).unwrap();
+
+        let count = |table: &str| -> usize {
+            let conn = storage.conn.lock().unwrap();
+            conn.query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |r| r.get(0))
+                .unwrap()
+        };
+        assert_eq!(count("places"), world.places.len());
+        assert_eq!(count("characters"), 1);
+
+        // Only rows that changed are rewritten, so a row edited behind its back stays put
+        let village = *world.places.keys().next().unwrap();
+        let village_key = serde_json::to_value(village).unwrap();
+        storage
+            .conn
+            .lock()
+            .unwrap()
+            .execute(
+                "UPDATE places SET data = 'tampered' WHERE key = ?1",
+                [village_key.as_str()],
+            )
+            .unwrap();
+        world.current_tick = 42;
+        world.player_characters.clear();
+        storage.save_world(&world).unwrap();
+        assert_eq!(count("characters"), 0);
+        let untouched = storage
+            .conn
+            .lock()
+            .unwrap()
+            .execute("DELETE FROM places WHERE data = 'tampered'", [])
+            .unwrap();
+        assert_eq!(untouched, 1);
+        world.places.remove(&village);
+
+        let loaded = storage.load_world(&world.save_path).unwrap().unwrap();
+        assert_eq!(loaded.current_tick, 42);
+        assert_eq!(loaded.places.len(), world.places.len());
+        assert!(storage
+            .load_world(Path::new("elsewhere.yaml"))
+            .unwrap()
+            .is_none());
+
+        let path = Path::new("registry.yaml");
+        let accounts = HashMap::from([(
+            player,
+            PlayerAccount {
+                username: "ada".into(),
+                password: Password::Legacy(1),
+                color: false,
+                width: None,
+                compress: false,
+                role: crate::Role::Player,
+                banned: false,
+            },
+        )]);
+        storage.save_account(path, &accounts, player).unwrap();
+        let loaded = storage.load_accounts(path).unwrap();
+        assert_eq!(loaded[&player].username, "ada");
+    }
+
+    #[test]
+    fn verifies_new_and_legacy_passwords() {
+        let password = Password::hash("hunter2").unwrap();
+        assert!(password.verify("hunter2"));
+        assert!(!password.verify("hunter3"));
+        assert!(!password.is_legacy());
+        assert_ne!(
+            serde_yaml::to_string(&password).unwrap(),
+            serde_yaml::to_string(&Password::hash("hunter2").unwrap()).unwrap()
+        );
+
+        let legacy: Password =
+            serde_yaml::from_str(&seahash::hash(b"hunter2").to_string()).unwrap();
+        assert!(legacy.is_legacy());
+        assert!(legacy.verify("hunter2"));
+        assert!(!legacy.verify("hunter3"));
+    }
+}
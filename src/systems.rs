@@ -0,0 +1,167 @@
+//! The registry of systems the engine runs each tick. Systems declare the phase
+//! they belong to and which other systems must run before them, and the
+//! registry works out a fixed order once at startup, rejecting anything that
+//! can't be satisfied rather than quietly running it in the wrong place.
+
+use std::collections::HashSet;
+
+/// The broad stages of a tick, run in this order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Phase {
+    /// Connections and player commands, these still run while the world is paused
+    Input,
+    /// Everything that moves the world along on its own
+    Simulation,
+    /// Reacting to and sending out what happened
+    Output,
+    /// Advancing the clock and saving
+    Persistence,
+}
+
+pub type SystemFn<T> = fn(&mut T);
+
+/// One step of the tick
+pub struct System<T> {
+    pub name: &'static str,
+    pub phase: Phase,
+    /// Systems that must run before this one, in this phase or an earlier one
+    pub after: &'static [&'static str],
+    pub run: SystemFn<T>,
+}
+
+impl<T> System<T> {
+    pub fn new(name: &'static str, phase: Phase, run: SystemFn<T>) -> Self {
+        Self {
+            name,
+            phase,
+            after: &[],
+            run,
+        }
+    }
+
+    pub fn after(mut self, after: &'static [&'static str]) -> Self {
+        self.after = after;
+        self
+    }
+}
+
+/// Systems in the order they run
+pub struct Schedule<T> {
+    systems: Vec<System<T>>,
+}
+
+impl<T> Schedule<T> {
+    /// Orders the systems by phase, then so each runs after its dependencies,
+    /// otherwise keeping the order they were registered in
+    pub fn build(mut registered: Vec<System<T>>) -> Result<Self, String> {
+        let mut names = HashSet::new();
+        for system in &registered {
+            if !names.insert(system.name) {
+                return Err(format!("System '{}' is registered twice", system.name));
+            }
+        }
+
+        for system in &registered {
+            for dep in system.after {
+                let Some(dep) = registered.iter().find(|s| s.name == *dep) else {
+                    return Err(format!(
+                        "System '{}' runs after '{dep}', which isn't registered",
+                        system.name
+                    ));
+                };
+                if dep.phase > system.phase {
+                    return Err(format!(
+                        "System '{}' runs after '{}', which is in a later phase",
+                        system.name, dep.name
+                    ));
+                }
+            }
+        }
+
+        registered.sort_by_key(|s| s.phase);
+
+        let mut systems = Vec::with_capacity(registered.len());
+        let mut done = HashSet::new();
+        while !registered.is_empty() {
+            let ready = registered
+                .iter()
+                .position(|s| s.after.iter().all(|d| done.contains(d)))
+                .ok_or_else(|| {
+                    let stuck: Vec<_> = registered.iter().map(|s| s.name).collect();
+                    format!("Systems {} depend on each other", stuck.join(", "))
+                })?;
+            let system = registered.remove(ready);
+            done.insert(system.name);
+            systems.push(system);
+        }
+
+        Ok(Self { systems })
+    }
+
+    /// Runs every system in order, skipping phases the filter turns down
+    pub fn run(&self, state: &mut T, phases: impl Fn(Phase) -> bool) {
+        for system in self.systems.iter().filter(|s| phases(s.phase)) {
+            (system.run)(state);
+        }
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.systems.iter().map(|s| s.name)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    type Log = Vec<&'static str>;
+
+    fn system(name: &'static str, phase: Phase) -> System<Log> {
+        System::new(name, phase, |_| {})
+    }
+
+    #[test]
+    fn orders_by_phase_and_dependency() {
+        let schedule = Schedule::build(vec![
+            system("save", Phase::Persistence),
+            system("weather", Phase::Simulation).after(&["npcs"]),
+            system("npcs", Phase::Simulation).after(&["commands"]),
+            system("combat", Phase::Simulation),
+            system("commands", Phase::Input),
+        ])
+        .unwrap();
+
+        let order: Vec<_> = schedule.names().collect();
+        assert_eq!(order, ["commands", "npcs", "weather", "combat", "save"]);
+
+        let mut log = Log::new();
+        let schedule = Schedule::build(vec![
+            System::new("input", Phase::Input, |l: &mut Log| l.push("input")),
+            System::new("sim", Phase::Simulation, |l: &mut Log| l.push("sim")),
+        ])
+        .unwrap();
+        schedule.run(&mut log, |p| p == Phase::Input);
+        assert_eq!(log, ["input"]);
+    }
+
+    #[test]
+    fn rejects_bad_dependencies() {
+        let cycle = Schedule::build(vec![
+            system("a", Phase::Simulation).after(&["b"]),
+            system("b", Phase::Simulation).after(&["a"]),
+        ]);
+        assert!(cycle.is_err());
+
+        let later = Schedule::build(vec![
+            system("commands", Phase::Input).after(&["save"]),
+            system("save", Phase::Persistence),
+        ]);
+        assert!(later.is_err());
+
+        let missing = Schedule::build(vec![system("a", Phase::Input).after(&["b"])]);
+        assert!(missing.is_err());
+
+        let twice = Schedule::build(vec![system("a", Phase::Input), system("a", Phase::Output)]);
+        assert!(twice.is_err());
+    }
+}
@@ -0,0 +1,131 @@
+//! Plain telnet, the way players have always reached the game. Clients can
+//! tell us how wide their window is, and ones that support MCCP2 have what
+//! we send compressed.
+
+use anyhow::Result;
+use bytes::Bytes;
+use futures::{future::BoxFuture, FutureExt, SinkExt, StreamExt};
+use nectar::{
+    event::TelnetEvent, option::TelnetOption, subnegotiation::SubnegotiationType, TelnetCodec,
+};
+use tokio::net::TcpStream;
+use tokio_util::codec::Framed;
+
+use crate::{
+    frontend::{FrontEnd, Transport},
+    mccp::MccpCodec,
+};
+
+pub struct Telnet;
+
+impl FrontEnd for Telnet {
+    type Transport = TelnetTransport;
+
+    fn connect(&self, stream: TcpStream) -> BoxFuture<'static, Result<TelnetTransport>> {
+        async move {
+            let mut frame = Framed::new(stream, MccpCodec::new(TelnetCodec::new(1024)));
+            frame.send(TelnetEvent::Do(TelnetOption::NAWS)).await?;
+            frame.send(TelnetEvent::Will(TelnetOption::MCCP2)).await?;
+
+            Ok(TelnetTransport {
+                frame,
+                client_width: None,
+                client_compresses: false,
+                hidden: false,
+                unechoed: false,
+            })
+        }
+        .boxed()
+    }
+}
+
+pub struct TelnetTransport {
+    frame: Framed<TcpStream, MccpCodec>,
+    /// How wide the player's window is, if their client tells us
+    client_width: Option<u16>,
+    /// Whether the client has agreed to compressed output
+    client_compresses: bool,
+    /// Whether we've asked the client to stop echoing
+    hidden: bool,
+    /// Set when the last line was typed unechoed, the enter key wasn't echoed either
+    unechoed: bool,
+}
+
+impl Transport for TelnetTransport {
+    fn next_line(&mut self) -> BoxFuture<'_, Option<String>> {
+        async move {
+            loop {
+                match self.frame.next().await {
+                    Some(Ok(TelnetEvent::Message(line))) => {
+                        self.unechoed = self.hidden;
+                        return Some(line);
+                    }
+                    Some(Ok(TelnetEvent::Subnegotiate(SubnegotiationType::WindowSize(
+                        width,
+                        _,
+                    )))) => self.client_width = Some(width),
+                    // Compression waits until we know whether the player wants it
+                    Some(Ok(TelnetEvent::Do(TelnetOption::MCCP2))) => self.client_compresses = true,
+                    Some(Ok(TelnetEvent::Dont(TelnetOption::MCCP2))) => {
+                        self.client_compresses = false;
+                        self.frame.codec_mut().stop();
+                    }
+                    // Replies to our negotiation, nothing to do
+                    Some(Ok(_)) => {}
+                    Some(Err(_)) | None => return None,
+                }
+            }
+        }
+        .boxed()
+    }
+
+    fn send(&mut self, mut msg: String) -> BoxFuture<'_, Result<()>> {
+        async move {
+            // Start a fresh line since the player's enter key wasn't echoed
+            if std::mem::take(&mut self.unechoed) {
+                msg.insert_str(0, "\r\n");
+            }
+            self.frame.send(TelnetEvent::Message(msg)).await?;
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn hide_input(&mut self, hidden: bool) -> BoxFuture<'_, Result<()>> {
+        async move {
+            self.hidden = hidden;
+            let option = TelnetOption::Echo;
+            let negotiation = if hidden {
+                TelnetEvent::Will(option)
+            } else {
+                TelnetEvent::Wont(option)
+            };
+            self.frame.send(negotiation).await?;
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn set_compression(&mut self, on: bool) -> BoxFuture<'_, Result<()>> {
+        async move {
+            let on = on && self.client_compresses;
+            if self.frame.codec().is_compressing() == on {
+                return Ok(());
+            }
+
+            if on {
+                let start = SubnegotiationType::Unknown(TelnetOption::MCCP2, Bytes::new());
+                self.frame.send(TelnetEvent::Subnegotiate(start)).await?;
+                self.frame.codec_mut().start();
+            } else {
+                self.frame.codec_mut().stop();
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn width(&self) -> Option<u16> {
+        self.client_width
+    }
+}
@@ -0,0 +1,65 @@
+//! Lets browsers play over WebSockets. Each text frame is a line from the
+//! player or a message from the engine, otherwise it's the same login and
+//! session as telnet.
+
+use std::collections::VecDeque;
+
+use anyhow::Result;
+use futures::{future::BoxFuture, FutureExt, SinkExt, StreamExt};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{tungstenite::Message, WebSocketStream};
+
+use crate::frontend::{FrontEnd, Transport};
+
+pub struct WebSocket;
+
+impl FrontEnd for WebSocket {
+    type Transport = WebSocketTransport;
+
+    fn connect(&self, stream: TcpStream) -> BoxFuture<'static, Result<WebSocketTransport>> {
+        async move {
+            Ok(WebSocketTransport {
+                socket: tokio_tungstenite::accept_async(stream).await?,
+                lines: VecDeque::new(),
+            })
+        }
+        .boxed()
+    }
+}
+
+pub struct WebSocketTransport {
+    socket: WebSocketStream<TcpStream>,
+    /// Lines from a frame that held several, waiting their turn
+    lines: VecDeque<String>,
+}
+
+impl Transport for WebSocketTransport {
+    fn next_line(&mut self) -> BoxFuture<'_, Option<String>> {
+        async move {
+            loop {
+                if let Some(line) = self.lines.pop_front() {
+                    return Some(line);
+                }
+
+                match self.socket.next().await {
+                    Some(Ok(Message::Text(text))) => {
+                        self.lines
+                            .extend(text.lines().map(|l| l.trim().to_string()));
+                    }
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return None,
+                    // Pings are answered for us
+                    Some(Ok(_)) => {}
+                }
+            }
+        }
+        .boxed()
+    }
+
+    fn send(&mut self, msg: String) -> BoxFuture<'_, Result<()>> {
+        async move {
+            self.socket.send(Message::Text(msg)).await?;
+            Ok(())
+        }
+        .boxed()
+    }
+}